@@ -17,19 +17,21 @@ use iron_shapes::CoordinateType;
 
 use crate::index::*;
 use crate::prelude::{
-    HierarchyBase, HierarchyEdit, L2NBase, L2NEdit, LayoutBase, LayoutEdit, MapPointwise,
-    NetlistBase, NetlistEdit,
+    BusBase, BusEdit, HierarchyBase, HierarchyEdit, L2NBase, L2NEdit, LayoutBase, LayoutEdit,
+    MapPointwise, NetlistBase, NetlistEdit, PowerBase, PowerEdit,
 };
 use itertools::Itertools;
 use std::borrow::{Borrow, BorrowMut};
 use std::collections::HashMap;
 use std::hash::Hash;
 
+use crate::netlist::bus::BitOrder;
 use crate::netlist::direction::Direction;
 // use crate::rc_string::RcString;
 use std::fmt::Debug;
 
-use crate::layout::types::LayerInfo;
+use crate::layout::array::InstanceArray;
+use crate::layout::types::{LayerInfo, PlacementStatus};
 use crate::property_storage::{PropertyStore, PropertyValue};
 
 // Use an alternative hasher that has better performance for integer keys.
@@ -100,6 +102,21 @@ impl From<PinInstId> for TerminalId {
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct NetId(usize);
 
+/// Pin bus identifier.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PinBusId(u32);
+
+/// Net bus identifier.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct NetBusId(u32);
+
+/// Power domain identifier.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PowerDomainId(u32);
+
 /// Unique (across layout) identifier of a shape.
 pub type ShapeId = Index<Shape<Coord>, u32>;
 
@@ -122,6 +139,9 @@ impl_from_for_id!(CellInstId, usize);
 impl_from_for_id!(PinId, u32);
 impl_from_for_id!(PinInstId, usize);
 impl_from_for_id!(NetId, usize);
+impl_from_for_id!(PinBusId, u32);
+impl_from_for_id!(NetBusId, u32);
+impl_from_for_id!(PowerDomainId, u32);
 
 /// A circuit is defined by an interface (pins) and
 /// a content which consists of interconnected circuit instances.
@@ -167,12 +187,18 @@ where
     user_data: U,
 
     // == Netlist == //
+    /// Power domain of the content of this circuit.
+    power_domain: Option<PowerDomainId>,
     /// Pin definitions, the actual pin structs are in the top level `Chip` struct.
     pins: Vec<PinId>,
     /// All nets in this circuit.
     nets: IntHashSet<NetId>,
     /// Nets IDs stored by name.
     nets_by_name: HashMap<NameT, NetId>,
+    /// Pin buses of this circuit stored by name.
+    pin_buses_by_name: HashMap<NameT, PinBusId>,
+    /// Net buses of this circuit stored by name.
+    net_buses_by_name: HashMap<NameT, NetBusId>,
     /// Logic constant LOW net.
     net_low: NetId,
     /// Logic constant HIGH net.
@@ -214,11 +240,6 @@ impl Circuit {
     }
 }
 
-// pub enum PlacementStatus {
-//     Unplaced,
-//     Fixed
-// }
-
 /// Instance of a circuit.
 ///
 /// Template parameters:
@@ -246,13 +267,16 @@ where
     // == Netlist == //
     /// List of pins of this instance.
     pins: Vec<PinInstId>,
+    /// Power domain of this instance, overrides the power domain of the parent circuit.
+    power_domain: Option<PowerDomainId>,
 
     // == Layout == //
     /// Transformation to put the cell to the right place an into the right scale/rotation.
     transform: SimpleTransform<C>,
-    // TODO: Repetition
-    // /// Current status of the cell placement.
-    // placement_status: PlacementStatus
+    /// Array parameters if this instance is a regular array of placements.
+    array: Option<InstanceArray<C>>,
+    /// Current status of the cell placement.
+    placement_status: PlacementStatus,
 }
 
 impl CircuitInst {
@@ -269,7 +293,7 @@ impl CircuitInst {
     }
 }
 
-/// Single bit wire pin.
+/// Single bit wire pin. Multi-bit ports are represented by a [`Bus`] of pins.
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Pin {
@@ -281,6 +305,8 @@ pub struct Pin {
     circuit: CellId,
     /// Net that is connected to this pin.
     net: Option<NetId>,
+    /// Bus that contains this pin together with the bit index of the pin.
+    bus: Option<(PinBusId, usize)>,
 
     // == Layout == //
     /// List of shapes in the layout that represent the physical pin.
@@ -311,6 +337,8 @@ pub struct Net {
     pub pins: IntHashSet<PinId>,
     /// Pin instances connected to this net.
     pub pin_instances: IntHashSet<PinInstId>,
+    /// Bus that contains this net together with the bit index of the net.
+    pub bus: Option<(NetBusId, usize)>,
 
     // == Layout == //
     /// List of shapes in the layout that represent the physical net.
@@ -319,6 +347,42 @@ pub struct Net {
 
 impl Net {}
 
+/// A power domain is supplied by a pair of supply and ground nets.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PowerDomain {
+    /// Name of the power domain.
+    pub name: NameT,
+    /// Circuit which contains the supply nets.
+    pub parent_id: CellId,
+    /// Supply net (VDD).
+    pub supply_net: NetId,
+    /// Ground net (VSS).
+    pub ground_net: NetId,
+    /// Nominal supply voltage in volts.
+    pub voltage: f64,
+    /// Tells if the domain can never be switched off.
+    pub always_on: bool,
+    /// Circuits which are assigned to this domain.
+    pub circuits: IntHashSet<CellId>,
+    /// Circuit instances which are assigned to this domain.
+    pub circuit_instances: IntHashSet<CellInstId>,
+}
+
+/// Group of single-bit pins or nets which form a multi-bit bus.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Bus<T> {
+    /// Name of the bus.
+    pub name: NameT,
+    /// Parent circuit of the bus.
+    pub parent_id: CellId,
+    /// Order in which the bits have been declared.
+    pub bit_order: BitOrder,
+    /// Pins or nets of the bus. The position in this list is the bit index.
+    pub bits: Vec<T>,
+}
+
 /// A netlist is the container of circuits.
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -329,6 +393,10 @@ pub struct Chip<C: CoordinateType = Coord> {
     nets: IntHashMap<NetId, Net>,
     pins: IntHashMap<PinId, Pin>,
     pin_instances: IntHashMap<PinInstId, PinInst>,
+    pin_buses: IntHashMap<PinBusId, Bus<PinId>>,
+    net_buses: IntHashMap<NetBusId, Bus<NetId>>,
+    power_domains: IntHashMap<PowerDomainId, PowerDomain>,
+    power_domains_by_name: HashMap<NameT, PowerDomainId>,
 
     /// Top-level properties.
     properties: PropertyStore<NameT>,
@@ -338,6 +406,9 @@ pub struct Chip<C: CoordinateType = Coord> {
     id_counter_pin: u32,
     id_counter_pin_inst: usize,
     id_counter_net: usize,
+    id_counter_pin_bus: u32,
+    id_counter_net_bus: u32,
+    id_counter_power_domain: u32,
 
     // == Layout == //
     dbu: C,
@@ -369,12 +440,19 @@ impl<C: CoordinateType + One> Default for Chip<C> {
             nets: Default::default(),
             pins: Default::default(),
             pin_instances: Default::default(),
+            pin_buses: Default::default(),
+            net_buses: Default::default(),
+            power_domains: Default::default(),
+            power_domains_by_name: Default::default(),
             properties: Default::default(),
             id_counter_circuit: 0,
             id_counter_circuit_inst: 0,
             id_counter_pin: 0,
             id_counter_pin_inst: 0,
             id_counter_net: 0,
+            id_counter_pin_bus: 0,
+            id_counter_net_bus: 0,
+            id_counter_power_domain: 0,
             dbu: C::one(),
             layer_index_generator: Default::default(),
             layers_by_name: Default::default(),
@@ -465,6 +543,9 @@ impl Chip<Coord> {
             references: Default::default(),
             nets: Default::default(),
             nets_by_name: Default::default(),
+            pin_buses_by_name: Default::default(),
+            net_buses_by_name: Default::default(),
+            power_domain: None,
             // Create LOW and HIGH nets.
             net_low: NetId(0),
             net_high: NetId(0),
@@ -528,11 +609,37 @@ impl Chip<Coord> {
         for inst in references {
             self.remove_circuit_instance(&inst);
         }
+        // Clean up power domains.
+        self.set_power_domain_of_cell(circuit_id, None);
+        let power_domains = self
+            .power_domains
+            .iter()
+            .filter(|(_, d)| &d.parent_id == circuit_id)
+            .map(|(id, _)| *id)
+            .collect_vec();
+        for domain in power_domains {
+            self.remove_power_domain(&domain);
+        }
+        // Clean up buses.
+        let circuit = self.circuit(circuit_id);
+        let pin_buses = circuit.pin_buses_by_name.values().copied().collect_vec();
+        let net_buses = circuit.net_buses_by_name.values().copied().collect_vec();
+        for bus in pin_buses {
+            self.pin_buses.remove(&bus);
+        }
+        for bus in net_buses {
+            self.net_buses.remove(&bus);
+        }
         // Clean up pin definitions.
         let pins = self.circuit(circuit_id).pins.clone();
         for pin in pins {
             self.pins.remove(&pin).unwrap();
         }
+        // Clean up nets.
+        let nets = self.circuit(circuit_id).nets.iter().copied().collect_vec();
+        for net in nets {
+            self.nets.remove(&net);
+        }
         // Remove the circuit.
         let name = self.circuit(circuit_id).name.clone();
         self.circuits_by_name.remove(&name).unwrap();
@@ -582,7 +689,10 @@ impl Chip<Coord> {
             properties: Default::default(),
             user_data: (),
             pins: pins,
+            power_domain: None,
             transform: Default::default(),
+            array: None,
+            placement_status: Default::default(),
         };
 
         self.circuit_instances.insert(id, inst);
@@ -622,10 +732,14 @@ impl Chip<Coord> {
     fn remove_circuit_instance(&mut self, circuit_inst_id: &CellInstId) {
         // Remove the instance name.
         self.rename_cell_instance(circuit_inst_id, None);
+        // Remove the power domain assignment.
+        self.set_power_domain_of_cell_instance(circuit_inst_id, None);
 
         // Disconnect all pins first.
         for pin in self.circuit_inst(circuit_inst_id).pins.clone() {
             self.disconnect_pin_instance(&pin);
+            // Delete the pin instance struct.
+            self.pin_instances.remove(&pin);
         }
         // Remove the instance and all references.
         let parent = self.circuit_inst(&circuit_inst_id).parent_circuit_id;
@@ -682,6 +796,7 @@ impl Chip<Coord> {
             parent_id: *parent,
             pins: Default::default(),
             pin_instances: Default::default(),
+            bus: None,
             net_shapes: Default::default(),
         };
         self.nets.insert(id, net);
@@ -752,13 +867,18 @@ impl Chip<Coord> {
             "Cannot remove constant HIGH net."
         );
 
+        // A bus cannot have holes, so dissolve the bus of this net.
+        if let Some((bus, _)) = self.net(net).bus {
+            self.remove_net_bus(&bus);
+        }
+
+        // Power domains cannot exist without their supply nets.
+        for domain in self.each_power_domain_of_supply_net_vec(net) {
+            self.remove_power_domain(&domain);
+        }
+
         // Remove all links from shapes to this net.
-        let net_shapes = self
-            .net_shapes
-            .get(net)
-            .iter()
-            .flat_map(|shape_ids| shape_ids.iter().cloned())
-            .collect_vec();
+        let net_shapes = self.net(net).net_shapes.iter().cloned().collect_vec();
         for net_shape in &net_shapes {
             self.set_net_of_shape(net_shape, None);
         }
@@ -893,6 +1013,20 @@ impl Chip<Coord> {
         self.nets.get_mut(id).unwrap()
     }
 
+    /// Get a reference to a power domain by its ID.
+    fn power_domain(&self, id: &PowerDomainId) -> &PowerDomain {
+        self.power_domains
+            .get(id)
+            .expect("Power domain ID does not exist in this netlist.")
+    }
+
+    /// Get a mutable reference to a power domain by its ID.
+    fn power_domain_mut(&mut self, id: &PowerDomainId) -> &mut PowerDomain {
+        self.power_domains
+            .get_mut(id)
+            .expect("Power domain ID does not exist in this netlist.")
+    }
+
     /// Get a reference to a pin by its ID.
     fn pin(&self, id: &PinId) -> &Pin {
         &self.pins[id]
@@ -936,6 +1070,7 @@ impl Chip<Coord> {
             direction,
             circuit: parent,
             net: Default::default(),
+            bus: None,
             pin_shapes: Default::default(),
         };
         self.pins.insert(pin_id, pin);
@@ -1016,6 +1151,13 @@ impl Chip<Coord> {
             .get(shape_id)
             .expect("Shape not found.")
     }
+
+    /// Get a reference to a shape struct by its ID.
+    /// Returns `None` if the shape does not exist.
+    fn try_shape(&self, shape_id: &ShapeId) -> Option<&Shape<Coord>> {
+        let (cell, layer) = self.shape_parents.get(shape_id)?;
+        self.circuits.get(cell)?.shapes(layer)?.shapes.get(shape_id)
+    }
 }
 
 impl NetlistBase for Chip {
@@ -1080,6 +1222,56 @@ impl NetlistBase for Chip {
         self.net(net).name.clone()
     }
 
+    fn pin_exists(&self, pin: &Self::PinId) -> bool {
+        self.pins.contains_key(pin)
+    }
+
+    fn pin_instance_exists(&self, pin_instance: &Self::PinInstId) -> bool {
+        self.pin_instances.contains_key(pin_instance)
+    }
+
+    fn net_exists(&self, net: &Self::NetId) -> bool {
+        self.nets.contains_key(net)
+    }
+
+    fn try_template_pin(&self, pin_instance: &Self::PinInstId) -> Option<Self::PinId> {
+        self.pin_instances
+            .get(pin_instance)
+            .map(|p| p.template_pin_id)
+    }
+
+    fn try_pin_direction(&self, pin: &Self::PinId) -> Option<Direction> {
+        self.pins.get(pin).map(|p| p.direction)
+    }
+
+    fn try_pin_name(&self, pin: &Self::PinId) -> Option<Self::NameType> {
+        self.pins.get(pin).map(|p| p.name.clone())
+    }
+
+    fn try_parent_cell_of_pin(&self, pin: &Self::PinId) -> Option<Self::CellId> {
+        self.pins.get(pin).map(|p| p.circuit)
+    }
+
+    fn try_parent_of_pin_instance(&self, pin_inst: &Self::PinInstId) -> Option<Self::CellInstId> {
+        self.pin_instances.get(pin_inst).map(|p| p.circuit_inst)
+    }
+
+    fn try_parent_cell_of_net(&self, net: &Self::NetId) -> Option<Self::CellId> {
+        self.nets.get(net).map(|n| n.parent_id)
+    }
+
+    fn try_net_of_pin(&self, pin: &Self::PinId) -> Option<Option<Self::NetId>> {
+        self.pins.get(pin).map(|p| p.net)
+    }
+
+    fn try_net_of_pin_instance(&self, pin_inst: &Self::PinInstId) -> Option<Option<Self::NetId>> {
+        self.pin_instances.get(pin_inst).map(|p| p.net)
+    }
+
+    fn try_net_name(&self, net: &Self::NetId) -> Option<Option<Self::NameType>> {
+        self.nets.get(net).map(|n| n.name.clone())
+    }
+
     fn for_each_pin<F>(&self, circuit: &Self::CellId, f: F)
     where
         F: FnMut(Self::PinId) -> (),
@@ -1171,6 +1363,11 @@ impl NetlistEdit for Chip {
     }
 
     fn remove_pin(&mut self, id: &Self::PinId) {
+        // A bus cannot have holes, so dissolve the bus of this pin.
+        if let Some((bus, _)) = self.pin(id).bus {
+            self.remove_pin_bus(&bus);
+        }
+
         // Remove all links from shapes to this pin.
         let pin_shapes = self.pin(id).pin_shapes.iter().cloned().collect_vec();
         for pin_shape in &pin_shapes {
@@ -1179,7 +1376,7 @@ impl NetlistEdit for Chip {
 
         // Disconnect the pin for all instances.
         let cell = self.parent_cell_of_pin(id);
-        for inst in self.each_cell_instance_vec(&cell) {
+        for inst in self.each_cell_reference_vec(&cell) {
             let pin_inst = self.pin_instance(&inst, id);
             self.disconnect_pin_instance(&pin_inst);
 
@@ -1237,6 +1434,372 @@ impl NetlistEdit for Chip {
     }
 }
 
+impl BusBase for Chip {
+    type PinBusId = PinBusId;
+    type NetBusId = NetBusId;
+
+    fn pin_bus_by_name(&self, parent_circuit: &Self::CellId, name: &str) -> Option<Self::PinBusId> {
+        self.circuit(parent_circuit)
+            .pin_buses_by_name
+            .get(name)
+            .copied()
+    }
+
+    fn pin_bus_name(&self, bus: &Self::PinBusId) -> Self::NameType {
+        self.pin_buses[bus].name.clone()
+    }
+
+    fn parent_cell_of_pin_bus(&self, bus: &Self::PinBusId) -> Self::CellId {
+        self.pin_buses[bus].parent_id
+    }
+
+    fn pin_bus_bit_order(&self, bus: &Self::PinBusId) -> BitOrder {
+        self.pin_buses[bus].bit_order
+    }
+
+    fn bus_of_pin(&self, pin: &Self::PinId) -> Option<Self::PinBusId> {
+        self.pin(pin).bus.map(|(bus, _)| bus)
+    }
+
+    fn pin_bit_index(&self, pin: &Self::PinId) -> Option<usize> {
+        self.pin(pin).bus.map(|(_, index)| index)
+    }
+
+    fn net_bus_by_name(&self, parent_circuit: &Self::CellId, name: &str) -> Option<Self::NetBusId> {
+        self.circuit(parent_circuit)
+            .net_buses_by_name
+            .get(name)
+            .copied()
+    }
+
+    fn net_bus_name(&self, bus: &Self::NetBusId) -> Self::NameType {
+        self.net_buses[bus].name.clone()
+    }
+
+    fn parent_cell_of_net_bus(&self, bus: &Self::NetBusId) -> Self::CellId {
+        self.net_buses[bus].parent_id
+    }
+
+    fn net_bus_bit_order(&self, bus: &Self::NetBusId) -> BitOrder {
+        self.net_buses[bus].bit_order
+    }
+
+    fn bus_of_net(&self, net: &Self::NetId) -> Option<Self::NetBusId> {
+        self.net(net).bus.map(|(bus, _)| bus)
+    }
+
+    fn net_bit_index(&self, net: &Self::NetId) -> Option<usize> {
+        self.net(net).bus.map(|(_, index)| index)
+    }
+
+    fn for_each_pin_bus<F>(&self, circuit: &Self::CellId, f: F)
+    where
+        F: FnMut(Self::PinBusId) -> (),
+    {
+        self.circuit(circuit)
+            .pin_buses_by_name
+            .values()
+            .copied()
+            .for_each(f)
+    }
+
+    fn for_each_pin_of_bus<F>(&self, bus: &Self::PinBusId, f: F)
+    where
+        F: FnMut(Self::PinId) -> (),
+    {
+        self.pin_buses[bus].bits.iter().copied().for_each(f)
+    }
+
+    fn pin_bus_bit(&self, bus: &Self::PinBusId, bit_index: usize) -> Option<Self::PinId> {
+        self.pin_buses[bus].bits.get(bit_index).copied()
+    }
+
+    fn pin_bus_width(&self, bus: &Self::PinBusId) -> usize {
+        self.pin_buses[bus].bits.len()
+    }
+
+    fn for_each_net_bus<F>(&self, circuit: &Self::CellId, f: F)
+    where
+        F: FnMut(Self::NetBusId) -> (),
+    {
+        self.circuit(circuit)
+            .net_buses_by_name
+            .values()
+            .copied()
+            .for_each(f)
+    }
+
+    fn for_each_net_of_bus<F>(&self, bus: &Self::NetBusId, f: F)
+    where
+        F: FnMut(Self::NetId) -> (),
+    {
+        self.net_buses[bus].bits.iter().copied().for_each(f)
+    }
+
+    fn net_bus_bit(&self, bus: &Self::NetBusId, bit_index: usize) -> Option<Self::NetId> {
+        self.net_buses[bus].bits.get(bit_index).copied()
+    }
+
+    fn net_bus_width(&self, bus: &Self::NetBusId) -> usize {
+        self.net_buses[bus].bits.len()
+    }
+}
+
+impl BusEdit for Chip {
+    fn create_pin_bus_from_pins(
+        &mut self,
+        name: Self::NameType,
+        pins: Vec<Self::PinId>,
+        bit_order: BitOrder,
+    ) -> Self::PinBusId {
+        assert!(!pins.is_empty(), "A bus must have at least one bit.");
+        let cell = self.pin(&pins[0]).circuit;
+        assert!(
+            !self.circuit(&cell).pin_buses_by_name.contains_key(&name),
+            "Pin bus name already exists in cell '{}': '{}'",
+            self.cell_name(&cell),
+            name
+        );
+        for pin in &pins {
+            let p = self.pin(pin);
+            assert_eq!(p.circuit, cell, "Pins of a bus must live in the same cell.");
+            assert!(
+                p.bus.is_none(),
+                "Pin is already part of a bus: '{}'",
+                p.name
+            );
+        }
+
+        let id = PinBusId(Self::next_id_counter_u32(&mut self.id_counter_pin_bus));
+        for (index, pin) in pins.iter().enumerate() {
+            self.pin_mut(pin).bus = Some((id, index));
+        }
+        self.circuit_mut(&cell)
+            .pin_buses_by_name
+            .insert(name.clone(), id);
+        let bus = Bus {
+            name,
+            parent_id: cell,
+            bit_order,
+            bits: pins,
+        };
+        self.pin_buses.insert(id, bus);
+        id
+    }
+
+    fn remove_pin_bus(&mut self, bus: &Self::PinBusId) {
+        let bus = self.pin_buses.remove(bus).expect("Pin bus not found.");
+        for pin in &bus.bits {
+            self.pin_mut(pin).bus = None;
+        }
+        self.circuit_mut(&bus.parent_id)
+            .pin_buses_by_name
+            .remove(&bus.name);
+    }
+
+    fn create_net_bus_from_nets(
+        &mut self,
+        name: Self::NameType,
+        nets: Vec<Self::NetId>,
+        bit_order: BitOrder,
+    ) -> Self::NetBusId {
+        assert!(!nets.is_empty(), "A bus must have at least one bit.");
+        let cell = self.net(&nets[0]).parent_id;
+        assert!(
+            !self.circuit(&cell).net_buses_by_name.contains_key(&name),
+            "Net bus name already exists in cell '{}': '{}'",
+            self.cell_name(&cell),
+            name
+        );
+        for net in &nets {
+            let n = self.net(net);
+            assert_eq!(
+                n.parent_id, cell,
+                "Nets of a bus must live in the same cell."
+            );
+            assert!(n.bus.is_none(), "Net is already part of a bus.");
+        }
+
+        let id = NetBusId(Self::next_id_counter_u32(&mut self.id_counter_net_bus));
+        for (index, net) in nets.iter().enumerate() {
+            self.net_mut(net).bus = Some((id, index));
+        }
+        self.circuit_mut(&cell)
+            .net_buses_by_name
+            .insert(name.clone(), id);
+        let bus = Bus {
+            name,
+            parent_id: cell,
+            bit_order,
+            bits: nets,
+        };
+        self.net_buses.insert(id, bus);
+        id
+    }
+
+    fn remove_net_bus(&mut self, bus: &Self::NetBusId) {
+        let bus = self.net_buses.remove(bus).expect("Net bus not found.");
+        for net in &bus.bits {
+            self.net_mut(net).bus = None;
+        }
+        self.circuit_mut(&bus.parent_id)
+            .net_buses_by_name
+            .remove(&bus.name);
+    }
+}
+
+impl PowerBase for Chip {
+    type PowerDomainId = PowerDomainId;
+
+    fn power_domain_by_name(&self, name: &str) -> Option<Self::PowerDomainId> {
+        self.power_domains_by_name.get(name).copied()
+    }
+
+    fn power_domain_name(&self, domain: &Self::PowerDomainId) -> Self::NameType {
+        self.power_domain(domain).name.clone()
+    }
+
+    fn parent_cell_of_power_domain(&self, domain: &Self::PowerDomainId) -> Self::CellId {
+        self.power_domain(domain).parent_id
+    }
+
+    fn power_domain_supply_net(&self, domain: &Self::PowerDomainId) -> Self::NetId {
+        self.power_domain(domain).supply_net
+    }
+
+    fn power_domain_ground_net(&self, domain: &Self::PowerDomainId) -> Self::NetId {
+        self.power_domain(domain).ground_net
+    }
+
+    fn power_domain_voltage(&self, domain: &Self::PowerDomainId) -> f64 {
+        self.power_domain(domain).voltage
+    }
+
+    fn power_domain_is_always_on(&self, domain: &Self::PowerDomainId) -> bool {
+        self.power_domain(domain).always_on
+    }
+
+    fn for_each_power_domain<F>(&self, f: F)
+    where
+        F: FnMut(Self::PowerDomainId) -> (),
+    {
+        self.power_domains.keys().copied().for_each(f)
+    }
+
+    fn power_domain_of_cell(&self, cell: &Self::CellId) -> Option<Self::PowerDomainId> {
+        self.circuit(cell).power_domain
+    }
+
+    fn assigned_power_domain_of_cell_instance(
+        &self,
+        inst: &Self::CellInstId,
+    ) -> Option<Self::PowerDomainId> {
+        self.circuit_inst(inst).power_domain
+    }
+}
+
+impl PowerEdit for Chip {
+    fn create_power_domain(
+        &mut self,
+        name: Self::NameType,
+        supply_net: Self::NetId,
+        ground_net: Self::NetId,
+        voltage: f64,
+        always_on: bool,
+    ) -> Self::PowerDomainId {
+        assert!(
+            !self.power_domains_by_name.contains_key(&name),
+            "Power domain with this name already exists: {}",
+            &name
+        );
+        let parent_id = self.parent_cell_of_net(&supply_net);
+        assert_eq!(
+            parent_id,
+            self.parent_cell_of_net(&ground_net),
+            "Supply and ground net do not live in the same circuit."
+        );
+
+        let id = PowerDomainId(Self::next_id_counter_u32(&mut self.id_counter_power_domain));
+        let domain = PowerDomain {
+            name: name.clone(),
+            parent_id,
+            supply_net,
+            ground_net,
+            voltage,
+            always_on,
+            circuits: Default::default(),
+            circuit_instances: Default::default(),
+        };
+        self.power_domains.insert(id, domain);
+        self.power_domains_by_name.insert(name, id);
+        id
+    }
+
+    fn remove_power_domain(&mut self, domain: &Self::PowerDomainId) {
+        let domain = self
+            .power_domains
+            .remove(domain)
+            .expect("Power domain ID does not exist in this netlist.");
+        self.power_domains_by_name.remove(&domain.name);
+        for cell in &domain.circuits {
+            self.circuit_mut(cell).power_domain = None;
+        }
+        for inst in &domain.circuit_instances {
+            self.circuit_inst_mut(inst).power_domain = None;
+        }
+    }
+
+    fn set_power_domain_voltage(&mut self, domain: &Self::PowerDomainId, voltage: f64) -> f64 {
+        std::mem::replace(&mut self.power_domain_mut(domain).voltage, voltage)
+    }
+
+    fn set_power_domain_always_on(
+        &mut self,
+        domain: &Self::PowerDomainId,
+        always_on: bool,
+    ) -> bool {
+        std::mem::replace(&mut self.power_domain_mut(domain).always_on, always_on)
+    }
+
+    fn set_power_domain_of_cell(
+        &mut self,
+        cell: &Self::CellId,
+        domain: Option<Self::PowerDomainId>,
+    ) -> Option<Self::PowerDomainId> {
+        if let Some(domain) = &domain {
+            self.power_domain_mut(domain).circuits.insert(*cell);
+        }
+        let previous = std::mem::replace(&mut self.circuit_mut(cell).power_domain, domain);
+        if let Some(previous) = &previous {
+            if Some(*previous) != domain {
+                self.power_domain_mut(previous).circuits.remove(cell);
+            }
+        }
+        previous
+    }
+
+    fn set_power_domain_of_cell_instance(
+        &mut self,
+        inst: &Self::CellInstId,
+        domain: Option<Self::PowerDomainId>,
+    ) -> Option<Self::PowerDomainId> {
+        if let Some(domain) = &domain {
+            self.power_domain_mut(domain)
+                .circuit_instances
+                .insert(*inst);
+        }
+        let previous = std::mem::replace(&mut self.circuit_inst_mut(inst).power_domain, domain);
+        if let Some(previous) = &previous {
+            if Some(*previous) != domain {
+                self.power_domain_mut(previous)
+                    .circuit_instances
+                    .remove(inst);
+            }
+        }
+        previous
+    }
+}
+
 #[test]
 fn test_create_populated_netlist() {
     let mut netlist = Chip::default();
@@ -1389,6 +1952,39 @@ impl HierarchyBase for Chip<Coord> {
         self.circuit_inst(circuit_instance).template_circuit_id
     }
 
+    fn cell_exists(&self, cell: &Self::CellId) -> bool {
+        self.circuits.contains_key(cell)
+    }
+
+    fn cell_instance_exists(&self, cell_inst: &Self::CellInstId) -> bool {
+        self.circuit_instances.contains_key(cell_inst)
+    }
+
+    fn try_cell_name(&self, cell: &Self::CellId) -> Option<Self::NameType> {
+        self.circuits.get(cell).map(|c| c.name.clone())
+    }
+
+    fn try_cell_instance_name(
+        &self,
+        cell_inst: &Self::CellInstId,
+    ) -> Option<Option<Self::NameType>> {
+        self.circuit_instances
+            .get(cell_inst)
+            .map(|i| i.name.clone())
+    }
+
+    fn try_parent_cell(&self, cell_instance: &Self::CellInstId) -> Option<Self::CellId> {
+        self.circuit_instances
+            .get(cell_instance)
+            .map(|i| i.parent_circuit_id)
+    }
+
+    fn try_template_cell(&self, cell_instance: &Self::CellInstId) -> Option<Self::CellId> {
+        self.circuit_instances
+            .get(cell_instance)
+            .map(|i| i.template_circuit_id)
+    }
+
     fn for_each_cell<F>(&self, f: F)
     where
         F: FnMut(Self::CellId) -> (),
@@ -1476,6 +2072,13 @@ impl HierarchyBase for Chip<Coord> {
         self.circuit(cell).references.len()
     }
 
+    fn cell_instance_array_size(&self, cell_inst: &Self::CellInstId) -> (u32, u32) {
+        self.circuit_inst(cell_inst)
+            .array
+            .map(|a| (a.columns, a.rows))
+            .unwrap_or((1, 1))
+    }
+
     fn num_child_instances(&self, cell: &Self::CellId) -> usize {
         self.circuit(cell).instances.len()
     }
@@ -1503,6 +2106,33 @@ impl HierarchyBase for Chip<Coord> {
     ) -> Option<PropertyValue> {
         self.circuit_inst(inst).properties.get(key).cloned()
     }
+
+    fn for_each_chip_property<F>(&self, mut f: F)
+    where
+        F: FnMut(&Self::NameType, &PropertyValue) -> (),
+    {
+        self.properties.iter().for_each(|(k, v)| f(k, v))
+    }
+
+    fn for_each_cell_property<F>(&self, cell: &Self::CellId, mut f: F)
+    where
+        F: FnMut(&Self::NameType, &PropertyValue) -> (),
+    {
+        self.circuit(cell)
+            .properties
+            .iter()
+            .for_each(|(k, v)| f(k, v))
+    }
+
+    fn for_each_cell_instance_property<F>(&self, inst: &Self::CellInstId, mut f: F)
+    where
+        F: FnMut(&Self::NameType, &PropertyValue) -> (),
+    {
+        self.circuit_inst(inst)
+            .properties
+            .iter()
+            .for_each(|(k, v)| f(k, v))
+    }
 }
 
 impl LayoutBase for Chip<Coord> {
@@ -1547,10 +2177,16 @@ impl LayoutBase for Chip<Coord> {
         self.for_each_cell_instance(cell, |i| {
             let template = self.template_cell(&i);
             let tf = self.get_transform(&i);
+            let array = self.circuit_inst(&i).array;
             let child_bbox = self
                 .bounding_box_per_layer(&template, layer)
                 // Transform the child bounding box to ther correct position.
-                .map(|b| b.transform(|p| tf.transform_point(p)));
+                .map(|b| b.transform(|p| tf.transform_point(p)))
+                // Extend it over all elements of an array.
+                .map(|b| match &array {
+                    Some(array) => array.bounding_box(&b),
+                    None => b,
+                });
 
             bbox = match (bbox, child_bbox) {
                 (None, None) => None,
@@ -1607,10 +2243,57 @@ impl LayoutBase for Chip<Coord> {
             .clone()
     }
 
+    fn layer_exists(&self, layer: &Self::LayerId) -> bool {
+        self.layer_info.contains_key(layer)
+    }
+
+    fn shape_exists(&self, shape_id: &Self::ShapeId) -> bool {
+        self.shape_parents.contains_key(shape_id)
+    }
+
+    fn try_layer_info(&self, layer: &Self::LayerId) -> Option<LayerInfo<Self::NameType>> {
+        self.layer_info.get(layer).cloned()
+    }
+
+    fn try_shape_geometry(&self, shape_id: &Self::ShapeId) -> Option<Geometry<Self::Coord>> {
+        self.try_shape(shape_id).map(|s| s.geometry.clone())
+    }
+
+    fn try_shape_layer(&self, shape_id: &Self::ShapeId) -> Option<Self::LayerId> {
+        self.shape_parents.get(shape_id).map(|(_, layer)| *layer)
+    }
+
+    fn try_parent_of_shape(
+        &self,
+        shape_id: &Self::ShapeId,
+    ) -> Option<(Self::CellId, Self::LayerId)> {
+        self.shape_parents.get(shape_id).cloned()
+    }
+
     fn get_transform(&self, cell_inst: &Self::CellInstId) -> SimpleTransform<Self::Coord> {
         self.circuit_inst(cell_inst).get_transform().clone()
     }
 
+    fn try_get_transform(
+        &self,
+        cell_inst: &Self::CellInstId,
+    ) -> Option<SimpleTransform<Self::Coord>> {
+        self.circuit_instances
+            .get(cell_inst)
+            .map(|inst| inst.get_transform().clone())
+    }
+
+    fn cell_instance_array(
+        &self,
+        cell_inst: &Self::CellInstId,
+    ) -> Option<InstanceArray<Self::Coord>> {
+        self.circuit_inst(cell_inst).array
+    }
+
+    fn placement_status(&self, cell_inst: &Self::CellInstId) -> PlacementStatus {
+        self.circuit_inst(cell_inst).placement_status
+    }
+
     fn get_shape_property(
         &self,
         shape: &Self::ShapeId,
@@ -1623,6 +2306,19 @@ impl LayoutBase for Chip<Coord> {
             .and_then(|props| props.get(key))
             .cloned()
     }
+
+    fn for_each_shape_property<F>(&self, shape: &Self::ShapeId, mut f: F)
+    where
+        F: FnMut(&Self::NameType, &PropertyValue) -> (),
+    {
+        let (cell, layer) = self.shape_parents[shape].clone();
+        if let Some(props) = self.circuit(&cell).shapes_map[&layer]
+            .shape_properties
+            .get(shape)
+        {
+            props.iter().for_each(|(k, v)| f(k, v))
+        }
+    }
 }
 
 impl HierarchyEdit for Chip<Coord> {
@@ -1683,6 +2379,18 @@ impl HierarchyEdit for Chip<Coord> {
     ) {
         self.circuit_inst_mut(inst).properties.insert(key, value);
     }
+
+    fn remove_chip_property(&mut self, key: &Self::NameType) {
+        self.properties.remove(key);
+    }
+
+    fn remove_cell_property(&mut self, cell: &Self::CellId, key: &Self::NameType) {
+        self.circuit_mut(cell).properties.remove(key);
+    }
+
+    fn remove_cell_instance_property(&mut self, inst: &Self::CellInstId, key: &Self::NameType) {
+        self.circuit_inst_mut(inst).properties.remove(key);
+    }
 }
 
 impl LayoutEdit for Chip<Coord> {
@@ -1811,12 +2519,12 @@ impl LayoutEdit for Chip<Coord> {
         let (parent_cell, layer) = self.shape_parents[shape_id].clone();
         self.shape_parents.remove(shape_id);
 
-        self.circuit_mut(&parent_cell)
+        let shapes = self
+            .circuit_mut(&parent_cell)
             .shapes_mut(&layer)
-            .expect("Layer not found.")
-            .shapes
-            .remove(shape_id)
-            .map(|s| s.geometry)
+            .expect("Layer not found.");
+        shapes.shape_properties.remove(shape_id);
+        shapes.shapes.remove(shape_id).map(|s| s.geometry)
     }
 
     fn replace_shape(
@@ -1844,6 +2552,25 @@ impl LayoutEdit for Chip<Coord> {
         self.circuit_inst_mut(cell_inst).set_transform(tf)
     }
 
+    fn set_cell_instance_array(
+        &mut self,
+        cell_inst: &Self::CellInstId,
+        array: Option<InstanceArray<Self::Coord>>,
+    ) -> Option<InstanceArray<Self::Coord>> {
+        std::mem::replace(&mut self.circuit_inst_mut(cell_inst).array, array)
+    }
+
+    fn set_placement_status(
+        &mut self,
+        cell_inst: &Self::CellInstId,
+        status: PlacementStatus,
+    ) -> PlacementStatus {
+        std::mem::replace(
+            &mut self.circuit_inst_mut(cell_inst).placement_status,
+            status,
+        )
+    }
+
     fn set_shape_property(
         &mut self,
         shape: &Self::ShapeId,
@@ -1860,6 +2587,20 @@ impl LayoutEdit for Chip<Coord> {
             .or_insert(Default::default())
             .insert(key, value);
     }
+
+    fn remove_shape_property(&mut self, shape: &Self::ShapeId, key: &Self::NameType) {
+        let (cell, layer) = self.shape_parents[shape].clone();
+        if let Some(props) = self
+            .circuit_mut(&cell)
+            .shapes_map
+            .get_mut(&layer)
+            .expect("Layer not found.")
+            .shape_properties
+            .get_mut(shape)
+        {
+            props.remove(key);
+        }
+    }
 }
 
 impl L2NBase for Chip<Coord> {
@@ -1878,6 +2619,14 @@ impl L2NBase for Chip<Coord> {
     fn get_pin_of_shape(&self, shape_id: &Self::ShapeId) -> Option<Self::PinId> {
         self.shape(shape_id).pin.clone()
     }
+
+    fn try_get_net_of_shape(&self, shape_id: &Self::ShapeId) -> Option<Option<Self::NetId>> {
+        self.try_shape(shape_id).map(|s| s.net)
+    }
+
+    fn try_get_pin_of_shape(&self, shape_id: &Self::ShapeId) -> Option<Option<Self::PinId>> {
+        self.try_shape(shape_id).map(|s| s.pin)
+    }
 }
 
 impl L2NEdit for Chip<Coord> {
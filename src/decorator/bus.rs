@@ -0,0 +1,330 @@
+// SPDX-FileCopyrightText: 2022 Thomas Kramer
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use crate::decorator::{Decorator, MutDecorator};
+use crate::prelude::BitOrder;
+use crate::traits::{BusBase, BusEdit, HierarchyBase, NetlistBase, NetlistEdit};
+
+/// Define the same functions as [`BusBase`] but just prepend a `d_` to
+/// avoid naming conflicts.
+/// The default implementation just forwards the call to the `base()`.
+/// This allows to selectively re-implement some functions or fully delegate
+/// the trait to an attribute of a struct.
+pub trait BusBaseDecorator: Decorator
+where
+    Self::D: BusBase,
+{
+    fn d_pin_bus_by_name(
+        &self,
+        parent_circuit: &<Self::D as HierarchyBase>::CellId,
+        name: &str,
+    ) -> Option<<Self::D as BusBase>::PinBusId> {
+        self.base().pin_bus_by_name(parent_circuit, name)
+    }
+
+    fn d_pin_bus_name(
+        &self,
+        bus: &<Self::D as BusBase>::PinBusId,
+    ) -> <Self::D as HierarchyBase>::NameType {
+        self.base().pin_bus_name(bus)
+    }
+
+    fn d_parent_cell_of_pin_bus(
+        &self,
+        bus: &<Self::D as BusBase>::PinBusId,
+    ) -> <Self::D as HierarchyBase>::CellId {
+        self.base().parent_cell_of_pin_bus(bus)
+    }
+
+    fn d_pin_bus_bit_order(&self, bus: &<Self::D as BusBase>::PinBusId) -> BitOrder {
+        self.base().pin_bus_bit_order(bus)
+    }
+
+    fn d_bus_of_pin(
+        &self,
+        pin: &<Self::D as NetlistBase>::PinId,
+    ) -> Option<<Self::D as BusBase>::PinBusId> {
+        self.base().bus_of_pin(pin)
+    }
+
+    fn d_pin_bit_index(&self, pin: &<Self::D as NetlistBase>::PinId) -> Option<usize> {
+        self.base().pin_bit_index(pin)
+    }
+
+    fn d_for_each_pin_bus<F>(&self, circuit: &<Self::D as HierarchyBase>::CellId, f: F)
+    where
+        F: FnMut(<Self::D as BusBase>::PinBusId) -> (),
+    {
+        self.base().for_each_pin_bus(circuit, f)
+    }
+
+    fn d_for_each_pin_of_bus<F>(&self, bus: &<Self::D as BusBase>::PinBusId, f: F)
+    where
+        F: FnMut(<Self::D as NetlistBase>::PinId) -> (),
+    {
+        self.base().for_each_pin_of_bus(bus, f)
+    }
+
+    fn d_pin_bus_bit(
+        &self,
+        bus: &<Self::D as BusBase>::PinBusId,
+        bit_index: usize,
+    ) -> Option<<Self::D as NetlistBase>::PinId> {
+        self.base().pin_bus_bit(bus, bit_index)
+    }
+
+    fn d_pin_bus_width(&self, bus: &<Self::D as BusBase>::PinBusId) -> usize {
+        self.base().pin_bus_width(bus)
+    }
+
+    fn d_net_bus_by_name(
+        &self,
+        parent_circuit: &<Self::D as HierarchyBase>::CellId,
+        name: &str,
+    ) -> Option<<Self::D as BusBase>::NetBusId> {
+        self.base().net_bus_by_name(parent_circuit, name)
+    }
+
+    fn d_net_bus_name(
+        &self,
+        bus: &<Self::D as BusBase>::NetBusId,
+    ) -> <Self::D as HierarchyBase>::NameType {
+        self.base().net_bus_name(bus)
+    }
+
+    fn d_parent_cell_of_net_bus(
+        &self,
+        bus: &<Self::D as BusBase>::NetBusId,
+    ) -> <Self::D as HierarchyBase>::CellId {
+        self.base().parent_cell_of_net_bus(bus)
+    }
+
+    fn d_net_bus_bit_order(&self, bus: &<Self::D as BusBase>::NetBusId) -> BitOrder {
+        self.base().net_bus_bit_order(bus)
+    }
+
+    fn d_bus_of_net(
+        &self,
+        net: &<Self::D as NetlistBase>::NetId,
+    ) -> Option<<Self::D as BusBase>::NetBusId> {
+        self.base().bus_of_net(net)
+    }
+
+    fn d_net_bit_index(&self, net: &<Self::D as NetlistBase>::NetId) -> Option<usize> {
+        self.base().net_bit_index(net)
+    }
+
+    fn d_for_each_net_bus<F>(&self, circuit: &<Self::D as HierarchyBase>::CellId, f: F)
+    where
+        F: FnMut(<Self::D as BusBase>::NetBusId) -> (),
+    {
+        self.base().for_each_net_bus(circuit, f)
+    }
+
+    fn d_for_each_net_of_bus<F>(&self, bus: &<Self::D as BusBase>::NetBusId, f: F)
+    where
+        F: FnMut(<Self::D as NetlistBase>::NetId) -> (),
+    {
+        self.base().for_each_net_of_bus(bus, f)
+    }
+
+    fn d_net_bus_bit(
+        &self,
+        bus: &<Self::D as BusBase>::NetBusId,
+        bit_index: usize,
+    ) -> Option<<Self::D as NetlistBase>::NetId> {
+        self.base().net_bus_bit(bus, bit_index)
+    }
+
+    fn d_net_bus_width(&self, bus: &<Self::D as BusBase>::NetBusId) -> usize {
+        self.base().net_bus_width(bus)
+    }
+}
+
+impl<T, N> BusBase for T
+where
+    T: NetlistBase<
+            NameType = N::NameType,
+            CellId = N::CellId,
+            CellInstId = N::CellInstId,
+            PinId = N::PinId,
+            PinInstId = N::PinInstId,
+            NetId = N::NetId,
+        > + BusBaseDecorator<D = N>,
+    N: BusBase + 'static,
+{
+    type PinBusId = N::PinBusId;
+    type NetBusId = N::NetBusId;
+
+    fn pin_bus_by_name(&self, parent_circuit: &Self::CellId, name: &str) -> Option<Self::PinBusId> {
+        self.d_pin_bus_by_name(parent_circuit, name)
+    }
+
+    fn pin_bus_name(&self, bus: &Self::PinBusId) -> Self::NameType {
+        self.d_pin_bus_name(bus)
+    }
+
+    fn parent_cell_of_pin_bus(&self, bus: &Self::PinBusId) -> Self::CellId {
+        self.d_parent_cell_of_pin_bus(bus)
+    }
+
+    fn pin_bus_bit_order(&self, bus: &Self::PinBusId) -> BitOrder {
+        self.d_pin_bus_bit_order(bus)
+    }
+
+    fn bus_of_pin(&self, pin: &Self::PinId) -> Option<Self::PinBusId> {
+        self.d_bus_of_pin(pin)
+    }
+
+    fn pin_bit_index(&self, pin: &Self::PinId) -> Option<usize> {
+        self.d_pin_bit_index(pin)
+    }
+
+    fn for_each_pin_bus<F>(&self, circuit: &Self::CellId, f: F)
+    where
+        F: FnMut(Self::PinBusId) -> (),
+    {
+        self.d_for_each_pin_bus(circuit, f)
+    }
+
+    fn for_each_pin_of_bus<F>(&self, bus: &Self::PinBusId, f: F)
+    where
+        F: FnMut(Self::PinId) -> (),
+    {
+        self.d_for_each_pin_of_bus(bus, f)
+    }
+
+    fn pin_bus_bit(&self, bus: &Self::PinBusId, bit_index: usize) -> Option<Self::PinId> {
+        self.d_pin_bus_bit(bus, bit_index)
+    }
+
+    fn pin_bus_width(&self, bus: &Self::PinBusId) -> usize {
+        self.d_pin_bus_width(bus)
+    }
+
+    fn net_bus_by_name(&self, parent_circuit: &Self::CellId, name: &str) -> Option<Self::NetBusId> {
+        self.d_net_bus_by_name(parent_circuit, name)
+    }
+
+    fn net_bus_name(&self, bus: &Self::NetBusId) -> Self::NameType {
+        self.d_net_bus_name(bus)
+    }
+
+    fn parent_cell_of_net_bus(&self, bus: &Self::NetBusId) -> Self::CellId {
+        self.d_parent_cell_of_net_bus(bus)
+    }
+
+    fn net_bus_bit_order(&self, bus: &Self::NetBusId) -> BitOrder {
+        self.d_net_bus_bit_order(bus)
+    }
+
+    fn bus_of_net(&self, net: &Self::NetId) -> Option<Self::NetBusId> {
+        self.d_bus_of_net(net)
+    }
+
+    fn net_bit_index(&self, net: &Self::NetId) -> Option<usize> {
+        self.d_net_bit_index(net)
+    }
+
+    fn for_each_net_bus<F>(&self, circuit: &Self::CellId, f: F)
+    where
+        F: FnMut(Self::NetBusId) -> (),
+    {
+        self.d_for_each_net_bus(circuit, f)
+    }
+
+    fn for_each_net_of_bus<F>(&self, bus: &Self::NetBusId, f: F)
+    where
+        F: FnMut(Self::NetId) -> (),
+    {
+        self.d_for_each_net_of_bus(bus, f)
+    }
+
+    fn net_bus_bit(&self, bus: &Self::NetBusId, bit_index: usize) -> Option<Self::NetId> {
+        self.d_net_bus_bit(bus, bit_index)
+    }
+
+    fn net_bus_width(&self, bus: &Self::NetBusId) -> usize {
+        self.d_net_bus_width(bus)
+    }
+}
+
+/// Define the same functions as [`BusEdit`] but just prepend a `d_` to
+/// avoid naming conflicts.
+pub trait BusEditDecorator: MutDecorator
+where
+    Self::D: BusEdit,
+{
+    /// Group existing pins into a bus.
+    fn d_create_pin_bus_from_pins(
+        &mut self,
+        name: <Self::D as HierarchyBase>::NameType,
+        pins: Vec<<Self::D as NetlistBase>::PinId>,
+        bit_order: BitOrder,
+    ) -> <Self::D as BusBase>::PinBusId {
+        self.mut_base()
+            .create_pin_bus_from_pins(name, pins, bit_order)
+    }
+
+    /// Remove the bus but keep its pins.
+    fn d_remove_pin_bus(&mut self, bus: &<Self::D as BusBase>::PinBusId) {
+        self.mut_base().remove_pin_bus(bus)
+    }
+
+    /// Group existing nets into a bus.
+    fn d_create_net_bus_from_nets(
+        &mut self,
+        name: <Self::D as HierarchyBase>::NameType,
+        nets: Vec<<Self::D as NetlistBase>::NetId>,
+        bit_order: BitOrder,
+    ) -> <Self::D as BusBase>::NetBusId {
+        self.mut_base()
+            .create_net_bus_from_nets(name, nets, bit_order)
+    }
+
+    /// Remove the bus but keep its nets.
+    fn d_remove_net_bus(&mut self, bus: &<Self::D as BusBase>::NetBusId) {
+        self.mut_base().remove_net_bus(bus)
+    }
+}
+
+impl<T, N> BusEdit for T
+where
+    T: NetlistEdit<
+            NameType = N::NameType,
+            CellId = N::CellId,
+            CellInstId = N::CellInstId,
+            PinId = N::PinId,
+            PinInstId = N::PinInstId,
+            NetId = N::NetId,
+        > + BusBase<PinBusId = N::PinBusId, NetBusId = N::NetBusId>
+        + BusEditDecorator<D = N>,
+    N: BusEdit + 'static,
+{
+    fn create_pin_bus_from_pins(
+        &mut self,
+        name: Self::NameType,
+        pins: Vec<Self::PinId>,
+        bit_order: BitOrder,
+    ) -> Self::PinBusId {
+        self.d_create_pin_bus_from_pins(name, pins, bit_order)
+    }
+
+    fn remove_pin_bus(&mut self, bus: &Self::PinBusId) {
+        self.d_remove_pin_bus(bus)
+    }
+
+    fn create_net_bus_from_nets(
+        &mut self,
+        name: Self::NameType,
+        nets: Vec<Self::NetId>,
+        bit_order: BitOrder,
+    ) -> Self::NetBusId {
+        self.d_create_net_bus_from_nets(name, nets, bit_order)
+    }
+
+    fn remove_net_bus(&mut self, bus: &Self::NetBusId) {
+        self.d_remove_net_bus(bus)
+    }
+}
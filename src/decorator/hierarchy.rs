@@ -477,6 +477,33 @@ where
         self.base().template_cell(cell_instance)
     }
 
+    fn d_cell_exists(&self, cell: &Self::CellId) -> bool {
+        self.base().cell_exists(cell)
+    }
+
+    fn d_cell_instance_exists(&self, cell_inst: &Self::CellInstId) -> bool {
+        self.base().cell_instance_exists(cell_inst)
+    }
+
+    fn d_try_cell_name(&self, cell: &Self::CellId) -> Option<Self::NameType> {
+        self.base().try_cell_name(cell)
+    }
+
+    fn d_try_cell_instance_name(
+        &self,
+        cell_inst: &Self::CellInstId,
+    ) -> Option<Option<Self::NameType>> {
+        self.base().try_cell_instance_name(cell_inst)
+    }
+
+    fn d_try_parent_cell(&self, cell_instance: &Self::CellInstId) -> Option<Self::CellId> {
+        self.base().try_parent_cell(cell_instance)
+    }
+
+    fn d_try_template_cell(&self, cell_instance: &Self::CellInstId) -> Option<Self::CellId> {
+        self.base().try_template_cell(cell_instance)
+    }
+
     fn d_for_each_cell<F>(&self, f: F)
     where
         F: FnMut(Self::CellId) -> (),
@@ -576,6 +603,10 @@ where
         self.base().num_cell_references(cell)
     }
 
+    fn d_cell_instance_array_size(&self, cell_inst: &Self::CellInstId) -> (u32, u32) {
+        self.base().cell_instance_array_size(cell_inst)
+    }
+
     fn d_num_child_instances(&self, cell: &Self::CellId) -> usize {
         self.base().num_child_instances(cell)
     }
@@ -603,6 +634,27 @@ where
     ) -> Option<PropertyValue> {
         self.base().get_cell_instance_property(inst, key)
     }
+
+    fn d_for_each_chip_property<F>(&self, f: F)
+    where
+        F: FnMut(&Self::NameType, &PropertyValue) -> (),
+    {
+        self.base().for_each_chip_property(f)
+    }
+
+    fn d_for_each_cell_property<F>(&self, cell: &Self::CellId, f: F)
+    where
+        F: FnMut(&Self::NameType, &PropertyValue) -> (),
+    {
+        self.base().for_each_cell_property(cell, f)
+    }
+
+    fn d_for_each_cell_instance_property<F>(&self, inst: &Self::CellInstId, f: F)
+    where
+        F: FnMut(&Self::NameType, &PropertyValue) -> (),
+    {
+        self.base().for_each_cell_instance_property(inst, f)
+    }
 }
 
 impl<T, H> HierarchyBase for T
@@ -647,6 +699,33 @@ where
         self.d_template_cell(cell_instance)
     }
 
+    fn cell_exists(&self, cell: &Self::CellId) -> bool {
+        self.d_cell_exists(cell)
+    }
+
+    fn cell_instance_exists(&self, cell_inst: &Self::CellInstId) -> bool {
+        self.d_cell_instance_exists(cell_inst)
+    }
+
+    fn try_cell_name(&self, cell: &Self::CellId) -> Option<Self::NameType> {
+        self.d_try_cell_name(cell)
+    }
+
+    fn try_cell_instance_name(
+        &self,
+        cell_inst: &Self::CellInstId,
+    ) -> Option<Option<Self::NameType>> {
+        self.d_try_cell_instance_name(cell_inst)
+    }
+
+    fn try_parent_cell(&self, cell_instance: &Self::CellInstId) -> Option<Self::CellId> {
+        self.d_try_parent_cell(cell_instance)
+    }
+
+    fn try_template_cell(&self, cell_instance: &Self::CellInstId) -> Option<Self::CellId> {
+        self.d_try_template_cell(cell_instance)
+    }
+
     fn for_each_cell<F>(&self, f: F)
     where
         F: FnMut(Self::CellId) -> (),
@@ -746,6 +825,10 @@ where
         self.d_num_cell_references(cell)
     }
 
+    fn cell_instance_array_size(&self, cell_inst: &Self::CellInstId) -> (u32, u32) {
+        self.d_cell_instance_array_size(cell_inst)
+    }
+
     fn num_child_instances(&self, cell: &Self::CellId) -> usize {
         self.d_num_child_instances(cell)
     }
@@ -773,6 +856,27 @@ where
     ) -> Option<PropertyValue> {
         self.d_get_cell_instance_property(inst, key)
     }
+
+    fn for_each_chip_property<F>(&self, f: F)
+    where
+        F: FnMut(&Self::NameType, &PropertyValue) -> (),
+    {
+        self.d_for_each_chip_property(f)
+    }
+
+    fn for_each_cell_property<F>(&self, cell: &Self::CellId, f: F)
+    where
+        F: FnMut(&Self::NameType, &PropertyValue) -> (),
+    {
+        self.d_for_each_cell_property(cell, f)
+    }
+
+    fn for_each_cell_instance_property<F>(&self, inst: &Self::CellInstId, f: F)
+    where
+        F: FnMut(&Self::NameType, &PropertyValue) -> (),
+    {
+        self.d_for_each_cell_instance_property(inst, f)
+    }
 }
 
 pub trait HierarchyEditDecorator: MutDecorator
@@ -847,6 +951,26 @@ where
     ) {
         self.mut_base().set_cell_instance_property(inst, key, value)
     }
+
+    fn d_remove_chip_property(&mut self, key: &<Self::D as HierarchyBase>::NameType) {
+        self.mut_base().remove_chip_property(key)
+    }
+
+    fn d_remove_cell_property(
+        &mut self,
+        cell: &<Self::D as HierarchyBase>::CellId,
+        key: &<Self::D as HierarchyBase>::NameType,
+    ) {
+        self.mut_base().remove_cell_property(cell, key)
+    }
+
+    fn d_remove_cell_instance_property(
+        &mut self,
+        inst: &<Self::D as HierarchyBase>::CellInstId,
+        key: &<Self::D as HierarchyBase>::NameType,
+    ) {
+        self.mut_base().remove_cell_instance_property(inst, key)
+    }
 }
 
 impl<T, H> HierarchyEdit for T
@@ -909,6 +1033,18 @@ where
     ) {
         self.d_set_cell_instance_property(inst, key, value)
     }
+
+    fn remove_chip_property(&mut self, key: &Self::NameType) {
+        self.d_remove_chip_property(key)
+    }
+
+    fn remove_cell_property(&mut self, cell: &Self::CellId, key: &Self::NameType) {
+        self.d_remove_cell_property(cell, key)
+    }
+
+    fn remove_cell_instance_property(&mut self, inst: &Self::CellInstId, key: &Self::NameType) {
+        self.d_remove_cell_instance_property(inst, key)
+    }
 }
 
 #[test]
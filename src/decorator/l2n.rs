@@ -42,6 +42,20 @@ where
     ) -> Option<<Self::D as NetlistBase>::PinId> {
         self.base().get_pin_of_shape(shape_id)
     }
+
+    fn d_try_get_net_of_shape(
+        &self,
+        shape_id: &<Self::D as LayoutBase>::ShapeId,
+    ) -> Option<Option<<Self::D as NetlistBase>::NetId>> {
+        self.base().try_get_net_of_shape(shape_id)
+    }
+
+    fn d_try_get_pin_of_shape(
+        &self,
+        shape_id: &<Self::D as LayoutBase>::ShapeId,
+    ) -> Option<Option<<Self::D as NetlistBase>::PinId>> {
+        self.base().try_get_pin_of_shape(shape_id)
+    }
 }
 
 impl<T, N> L2NBase for T
@@ -67,6 +81,14 @@ where
     fn get_pin_of_shape(&self, shape_id: &Self::ShapeId) -> Option<Self::PinId> {
         self.d_get_pin_of_shape(shape_id)
     }
+
+    fn try_get_net_of_shape(&self, shape_id: &Self::ShapeId) -> Option<Option<Self::NetId>> {
+        self.d_try_get_net_of_shape(shape_id)
+    }
+
+    fn try_get_pin_of_shape(&self, shape_id: &Self::ShapeId) -> Option<Option<Self::PinId>> {
+        self.d_try_get_pin_of_shape(shape_id)
+    }
 }
 
 pub trait L2NEditDecorator: MutDecorator
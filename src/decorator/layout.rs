@@ -4,7 +4,10 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
 use crate::decorator::{Decorator, MutDecorator};
-use crate::prelude::{Geometry, LayerInfo, PropertyValue, Rect, SimpleTransform, UInt};
+use crate::layout::array::InstanceArray;
+use crate::prelude::{
+    Geometry, LayerInfo, PlacementStatus, PropertyValue, Rect, SimpleTransform, UInt,
+};
 use crate::traits::{HierarchyBase, HierarchyEdit, LayoutBase, LayoutEdit};
 
 /// Define the same functions as [`LayoutBase`] but just prepend a `d_` to
@@ -93,6 +96,45 @@ where
         self.base().parent_of_shape(shape_id)
     }
 
+    fn d_layer_exists(&self, layer: &<Self::D as LayoutBase>::LayerId) -> bool {
+        self.base().layer_exists(layer)
+    }
+
+    fn d_shape_exists(&self, shape_id: &<Self::D as LayoutBase>::ShapeId) -> bool {
+        self.base().shape_exists(shape_id)
+    }
+
+    fn d_try_layer_info(
+        &self,
+        layer: &<Self::D as LayoutBase>::LayerId,
+    ) -> Option<LayerInfo<<Self::D as HierarchyBase>::NameType>> {
+        self.base().try_layer_info(layer)
+    }
+
+    fn d_try_shape_geometry(
+        &self,
+        shape_id: &<Self::D as LayoutBase>::ShapeId,
+    ) -> Option<Geometry<<Self::D as LayoutBase>::Coord>> {
+        self.base().try_shape_geometry(shape_id)
+    }
+
+    fn d_try_shape_layer(
+        &self,
+        shape_id: &<Self::D as LayoutBase>::ShapeId,
+    ) -> Option<<Self::D as LayoutBase>::LayerId> {
+        self.base().try_shape_layer(shape_id)
+    }
+
+    fn d_try_parent_of_shape(
+        &self,
+        shape_id: &<Self::D as LayoutBase>::ShapeId,
+    ) -> Option<(
+        <Self::D as HierarchyBase>::CellId,
+        <Self::D as LayoutBase>::LayerId,
+    )> {
+        self.base().try_parent_of_shape(shape_id)
+    }
+
     fn d_for_each_shape_recursive<F>(
         &self,
         cell: &<Self::D as HierarchyBase>::CellId,
@@ -115,6 +157,27 @@ where
         self.base().get_transform(cell_inst)
     }
 
+    fn d_try_get_transform(
+        &self,
+        cell_inst: &<Self::D as HierarchyBase>::CellInstId,
+    ) -> Option<SimpleTransform<<Self::D as LayoutBase>::Coord>> {
+        self.base().try_get_transform(cell_inst)
+    }
+
+    fn d_cell_instance_array(
+        &self,
+        cell_inst: &<Self::D as HierarchyBase>::CellInstId,
+    ) -> Option<InstanceArray<<Self::D as LayoutBase>::Coord>> {
+        self.base().cell_instance_array(cell_inst)
+    }
+
+    fn d_placement_status(
+        &self,
+        cell_inst: &<Self::D as HierarchyBase>::CellInstId,
+    ) -> PlacementStatus {
+        self.base().placement_status(cell_inst)
+    }
+
     fn d_get_shape_property(
         &self,
         shape: &<Self::D as LayoutBase>::ShapeId,
@@ -122,6 +185,13 @@ where
     ) -> Option<PropertyValue> {
         self.base().get_shape_property(shape, key)
     }
+
+    fn d_for_each_shape_property<F>(&self, shape: &<Self::D as LayoutBase>::ShapeId, f: F)
+    where
+        F: FnMut(&<Self::D as HierarchyBase>::NameType, &PropertyValue) -> (),
+    {
+        self.base().for_each_shape_property(shape, f)
+    }
 }
 
 impl<T, L> LayoutBase for T
@@ -193,6 +263,36 @@ where
         self.base().parent_of_shape(shape_id)
     }
 
+    fn layer_exists(&self, layer: &Self::LayerId) -> bool {
+        self.base().layer_exists(layer)
+    }
+
+    fn shape_exists(&self, shape_id: &Self::ShapeId) -> bool {
+        self.base().shape_exists(shape_id)
+    }
+
+    fn try_layer_info(&self, layer: &Self::LayerId) -> Option<LayerInfo<Self::NameType>> {
+        self.base().try_layer_info(layer)
+    }
+
+    fn try_shape_geometry(
+        &self,
+        shape_id: &Self::ShapeId,
+    ) -> Option<Geometry<<Self as LayoutBase>::Coord>> {
+        self.base().try_shape_geometry(shape_id)
+    }
+
+    fn try_shape_layer(&self, shape_id: &Self::ShapeId) -> Option<Self::LayerId> {
+        self.base().try_shape_layer(shape_id)
+    }
+
+    fn try_parent_of_shape(
+        &self,
+        shape_id: &Self::ShapeId,
+    ) -> Option<(Self::CellId, Self::LayerId)> {
+        self.base().try_parent_of_shape(shape_id)
+    }
+
     fn for_each_shape_recursive<F>(&self, cell: &Self::CellId, layer: &Self::LayerId, f: F)
     where
         F: FnMut(
@@ -211,6 +311,24 @@ where
         self.base().get_transform(cell_inst)
     }
 
+    fn try_get_transform(
+        &self,
+        cell_inst: &Self::CellInstId,
+    ) -> Option<SimpleTransform<<Self as LayoutBase>::Coord>> {
+        self.base().try_get_transform(cell_inst)
+    }
+
+    fn cell_instance_array(
+        &self,
+        cell_inst: &Self::CellInstId,
+    ) -> Option<InstanceArray<<Self as LayoutBase>::Coord>> {
+        self.base().cell_instance_array(cell_inst)
+    }
+
+    fn placement_status(&self, cell_inst: &Self::CellInstId) -> PlacementStatus {
+        self.base().placement_status(cell_inst)
+    }
+
     fn get_shape_property(
         &self,
         shape: &Self::ShapeId,
@@ -218,6 +336,13 @@ where
     ) -> Option<PropertyValue> {
         self.base().get_shape_property(shape, key)
     }
+
+    fn for_each_shape_property<F>(&self, shape: &Self::ShapeId, f: F)
+    where
+        F: FnMut(&Self::NameType, &PropertyValue) -> (),
+    {
+        self.base().for_each_shape_property(shape, f)
+    }
 }
 
 #[test]
@@ -320,6 +445,22 @@ where
         self.mut_base().set_transform(cell_inst, tf)
     }
 
+    fn d_set_cell_instance_array(
+        &mut self,
+        cell_inst: &<Self::D as HierarchyBase>::CellInstId,
+        array: Option<InstanceArray<<Self::D as LayoutBase>::Coord>>,
+    ) -> Option<InstanceArray<<Self::D as LayoutBase>::Coord>> {
+        self.mut_base().set_cell_instance_array(cell_inst, array)
+    }
+
+    fn d_set_placement_status(
+        &mut self,
+        cell_inst: &<Self::D as HierarchyBase>::CellInstId,
+        status: PlacementStatus,
+    ) -> PlacementStatus {
+        self.mut_base().set_placement_status(cell_inst, status)
+    }
+
     fn d_set_shape_property(
         &mut self,
         shape: &<Self::D as LayoutBase>::ShapeId,
@@ -328,6 +469,14 @@ where
     ) {
         self.mut_base().set_shape_property(shape, key, value)
     }
+
+    fn d_remove_shape_property(
+        &mut self,
+        shape: &<Self::D as LayoutBase>::ShapeId,
+        key: &<Self::D as HierarchyBase>::NameType,
+    ) {
+        self.mut_base().remove_shape_property(shape, key)
+    }
 }
 
 impl<T, L> LayoutEdit for T
@@ -387,6 +536,22 @@ where
         self.d_set_transform(cell_inst, tf)
     }
 
+    fn set_cell_instance_array(
+        &mut self,
+        cell_inst: &Self::CellInstId,
+        array: Option<InstanceArray<Self::Coord>>,
+    ) -> Option<InstanceArray<Self::Coord>> {
+        self.d_set_cell_instance_array(cell_inst, array)
+    }
+
+    fn set_placement_status(
+        &mut self,
+        cell_inst: &Self::CellInstId,
+        status: PlacementStatus,
+    ) -> PlacementStatus {
+        self.d_set_placement_status(cell_inst, status)
+    }
+
     fn set_shape_property(
         &mut self,
         shape: &Self::ShapeId,
@@ -395,6 +560,10 @@ where
     ) {
         self.d_set_shape_property(shape, key, value)
     }
+
+    fn remove_shape_property(&mut self, shape: &Self::ShapeId, key: &Self::NameType) {
+        self.d_remove_shape_property(shape, key)
+    }
 }
 
 #[test]
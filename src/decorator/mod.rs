@@ -9,10 +9,12 @@
 //! This module contains helper traits which make such extension easier.
 //!
 
+pub mod bus;
 pub mod hierarchy;
 pub mod l2n;
 pub mod layout;
 pub mod netlist;
+pub mod power;
 
 pub trait Decorator {
     /// The decorated type.
@@ -126,6 +126,78 @@ where
         self.base().net_name(net)
     }
 
+    fn d_pin_exists(&self, pin: &<Self::D as NetlistBase>::PinId) -> bool {
+        self.base().pin_exists(pin)
+    }
+
+    fn d_pin_instance_exists(&self, pin_instance: &<Self::D as NetlistBase>::PinInstId) -> bool {
+        self.base().pin_instance_exists(pin_instance)
+    }
+
+    fn d_net_exists(&self, net: &<Self::D as NetlistBase>::NetId) -> bool {
+        self.base().net_exists(net)
+    }
+
+    fn d_try_template_pin(
+        &self,
+        pin_instance: &<Self::D as NetlistBase>::PinInstId,
+    ) -> Option<<Self::D as NetlistBase>::PinId> {
+        self.base().try_template_pin(pin_instance)
+    }
+
+    fn d_try_pin_direction(&self, pin: &<Self::D as NetlistBase>::PinId) -> Option<Direction> {
+        self.base().try_pin_direction(pin)
+    }
+
+    fn d_try_pin_name(
+        &self,
+        pin: &<Self::D as NetlistBase>::PinId,
+    ) -> Option<<Self::D as HierarchyBase>::NameType> {
+        self.base().try_pin_name(pin)
+    }
+
+    fn d_try_parent_cell_of_pin(
+        &self,
+        pin: &<Self::D as NetlistBase>::PinId,
+    ) -> Option<<Self::D as HierarchyBase>::CellId> {
+        self.base().try_parent_cell_of_pin(pin)
+    }
+
+    fn d_try_parent_of_pin_instance(
+        &self,
+        pin_inst: &<Self::D as NetlistBase>::PinInstId,
+    ) -> Option<<Self::D as HierarchyBase>::CellInstId> {
+        self.base().try_parent_of_pin_instance(pin_inst)
+    }
+
+    fn d_try_parent_cell_of_net(
+        &self,
+        net: &<Self::D as NetlistBase>::NetId,
+    ) -> Option<<Self::D as HierarchyBase>::CellId> {
+        self.base().try_parent_cell_of_net(net)
+    }
+
+    fn d_try_net_of_pin(
+        &self,
+        pin: &<Self::D as NetlistBase>::PinId,
+    ) -> Option<Option<<Self::D as NetlistBase>::NetId>> {
+        self.base().try_net_of_pin(pin)
+    }
+
+    fn d_try_net_of_pin_instance(
+        &self,
+        pin_instance: &<Self::D as NetlistBase>::PinInstId,
+    ) -> Option<Option<<Self::D as NetlistBase>::NetId>> {
+        self.base().try_net_of_pin_instance(pin_instance)
+    }
+
+    fn d_try_net_name(
+        &self,
+        net: &<Self::D as NetlistBase>::NetId,
+    ) -> Option<Option<<Self::D as HierarchyBase>::NameType>> {
+        self.base().try_net_name(net)
+    }
+
     fn d_for_each_pin<F>(&self, circuit: &<Self::D as HierarchyBase>::CellId, f: F)
     where
         F: FnMut(<Self::D as NetlistBase>::PinId) -> (),
@@ -362,6 +434,57 @@ where
         self.d_net_name(net)
     }
 
+    fn pin_exists(&self, pin: &Self::PinId) -> bool {
+        self.d_pin_exists(pin)
+    }
+
+    fn pin_instance_exists(&self, pin_instance: &Self::PinInstId) -> bool {
+        self.d_pin_instance_exists(pin_instance)
+    }
+
+    fn net_exists(&self, net: &Self::NetId) -> bool {
+        self.d_net_exists(net)
+    }
+
+    fn try_template_pin(&self, pin_instance: &Self::PinInstId) -> Option<Self::PinId> {
+        self.d_try_template_pin(pin_instance)
+    }
+
+    fn try_pin_direction(&self, pin: &Self::PinId) -> Option<Direction> {
+        self.d_try_pin_direction(pin)
+    }
+
+    fn try_pin_name(&self, pin: &Self::PinId) -> Option<Self::NameType> {
+        self.d_try_pin_name(pin)
+    }
+
+    fn try_parent_cell_of_pin(&self, pin: &Self::PinId) -> Option<Self::CellId> {
+        self.d_try_parent_cell_of_pin(pin)
+    }
+
+    fn try_parent_of_pin_instance(&self, pin_inst: &Self::PinInstId) -> Option<Self::CellInstId> {
+        self.d_try_parent_of_pin_instance(pin_inst)
+    }
+
+    fn try_parent_cell_of_net(&self, net: &Self::NetId) -> Option<Self::CellId> {
+        self.d_try_parent_cell_of_net(net)
+    }
+
+    fn try_net_of_pin(&self, pin: &Self::PinId) -> Option<Option<Self::NetId>> {
+        self.d_try_net_of_pin(pin)
+    }
+
+    fn try_net_of_pin_instance(
+        &self,
+        pin_instance: &Self::PinInstId,
+    ) -> Option<Option<Self::NetId>> {
+        self.d_try_net_of_pin_instance(pin_instance)
+    }
+
+    fn try_net_name(&self, net: &Self::NetId) -> Option<Option<Self::NameType>> {
+        self.d_try_net_name(net)
+    }
+
     fn for_each_pin<F>(&self, circuit: &Self::CellId, f: F)
     where
         F: FnMut(Self::PinId) -> (),
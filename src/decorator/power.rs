@@ -0,0 +1,269 @@
+// SPDX-FileCopyrightText: 2022 Thomas Kramer
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use crate::decorator::{Decorator, MutDecorator};
+use crate::traits::{HierarchyBase, NetlistBase, NetlistEdit, PowerBase, PowerEdit};
+
+/// Define the same functions as [`PowerBase`] but just prepend a `d_` to
+/// avoid naming conflicts.
+/// The default implementation just forwards the call to the `base()`.
+/// This allows to selectively re-implement some functions or fully delegate
+/// the trait to an attribute of a struct.
+pub trait PowerBaseDecorator: Decorator
+where
+    Self::D: PowerBase,
+{
+    fn d_power_domain_by_name(&self, name: &str) -> Option<<Self::D as PowerBase>::PowerDomainId> {
+        self.base().power_domain_by_name(name)
+    }
+
+    fn d_power_domain_name(
+        &self,
+        domain: &<Self::D as PowerBase>::PowerDomainId,
+    ) -> <Self::D as HierarchyBase>::NameType {
+        self.base().power_domain_name(domain)
+    }
+
+    fn d_parent_cell_of_power_domain(
+        &self,
+        domain: &<Self::D as PowerBase>::PowerDomainId,
+    ) -> <Self::D as HierarchyBase>::CellId {
+        self.base().parent_cell_of_power_domain(domain)
+    }
+
+    fn d_power_domain_supply_net(
+        &self,
+        domain: &<Self::D as PowerBase>::PowerDomainId,
+    ) -> <Self::D as NetlistBase>::NetId {
+        self.base().power_domain_supply_net(domain)
+    }
+
+    fn d_power_domain_ground_net(
+        &self,
+        domain: &<Self::D as PowerBase>::PowerDomainId,
+    ) -> <Self::D as NetlistBase>::NetId {
+        self.base().power_domain_ground_net(domain)
+    }
+
+    fn d_power_domain_voltage(&self, domain: &<Self::D as PowerBase>::PowerDomainId) -> f64 {
+        self.base().power_domain_voltage(domain)
+    }
+
+    fn d_power_domain_is_always_on(&self, domain: &<Self::D as PowerBase>::PowerDomainId) -> bool {
+        self.base().power_domain_is_always_on(domain)
+    }
+
+    fn d_for_each_power_domain<F>(&self, f: F)
+    where
+        F: FnMut(<Self::D as PowerBase>::PowerDomainId) -> (),
+    {
+        self.base().for_each_power_domain(f)
+    }
+
+    fn d_power_domain_of_cell(
+        &self,
+        cell: &<Self::D as HierarchyBase>::CellId,
+    ) -> Option<<Self::D as PowerBase>::PowerDomainId> {
+        self.base().power_domain_of_cell(cell)
+    }
+
+    fn d_assigned_power_domain_of_cell_instance(
+        &self,
+        inst: &<Self::D as HierarchyBase>::CellInstId,
+    ) -> Option<<Self::D as PowerBase>::PowerDomainId> {
+        self.base().assigned_power_domain_of_cell_instance(inst)
+    }
+
+    fn d_each_power_domain_of_supply_net_vec(
+        &self,
+        net: &<Self::D as NetlistBase>::NetId,
+    ) -> Vec<<Self::D as PowerBase>::PowerDomainId> {
+        self.base().each_power_domain_of_supply_net_vec(net)
+    }
+}
+
+impl<T, N> PowerBase for T
+where
+    T: NetlistBase<
+            NameType = N::NameType,
+            CellId = N::CellId,
+            CellInstId = N::CellInstId,
+            PinId = N::PinId,
+            PinInstId = N::PinInstId,
+            NetId = N::NetId,
+        > + PowerBaseDecorator<D = N>,
+    N: PowerBase + 'static,
+{
+    type PowerDomainId = N::PowerDomainId;
+
+    fn power_domain_by_name(&self, name: &str) -> Option<Self::PowerDomainId> {
+        self.d_power_domain_by_name(name)
+    }
+
+    fn power_domain_name(&self, domain: &Self::PowerDomainId) -> Self::NameType {
+        self.d_power_domain_name(domain)
+    }
+
+    fn parent_cell_of_power_domain(&self, domain: &Self::PowerDomainId) -> Self::CellId {
+        self.d_parent_cell_of_power_domain(domain)
+    }
+
+    fn power_domain_supply_net(&self, domain: &Self::PowerDomainId) -> Self::NetId {
+        self.d_power_domain_supply_net(domain)
+    }
+
+    fn power_domain_ground_net(&self, domain: &Self::PowerDomainId) -> Self::NetId {
+        self.d_power_domain_ground_net(domain)
+    }
+
+    fn power_domain_voltage(&self, domain: &Self::PowerDomainId) -> f64 {
+        self.d_power_domain_voltage(domain)
+    }
+
+    fn power_domain_is_always_on(&self, domain: &Self::PowerDomainId) -> bool {
+        self.d_power_domain_is_always_on(domain)
+    }
+
+    fn for_each_power_domain<F>(&self, f: F)
+    where
+        F: FnMut(Self::PowerDomainId) -> (),
+    {
+        self.d_for_each_power_domain(f)
+    }
+
+    fn power_domain_of_cell(&self, cell: &Self::CellId) -> Option<Self::PowerDomainId> {
+        self.d_power_domain_of_cell(cell)
+    }
+
+    fn assigned_power_domain_of_cell_instance(
+        &self,
+        inst: &Self::CellInstId,
+    ) -> Option<Self::PowerDomainId> {
+        self.d_assigned_power_domain_of_cell_instance(inst)
+    }
+
+    fn each_power_domain_of_supply_net_vec(&self, net: &Self::NetId) -> Vec<Self::PowerDomainId> {
+        self.d_each_power_domain_of_supply_net_vec(net)
+    }
+}
+
+/// Define the same functions as [`PowerEdit`] but just prepend a `d_` to
+/// avoid naming conflicts.
+pub trait PowerEditDecorator: MutDecorator
+where
+    Self::D: PowerEdit,
+{
+    /// Create a power domain which is supplied by `supply_net` and `ground_net`.
+    fn d_create_power_domain(
+        &mut self,
+        name: <Self::D as HierarchyBase>::NameType,
+        supply_net: <Self::D as NetlistBase>::NetId,
+        ground_net: <Self::D as NetlistBase>::NetId,
+        voltage: f64,
+        always_on: bool,
+    ) -> <Self::D as PowerBase>::PowerDomainId {
+        self.mut_base()
+            .create_power_domain(name, supply_net, ground_net, voltage, always_on)
+    }
+
+    /// Remove the power domain.
+    fn d_remove_power_domain(&mut self, domain: &<Self::D as PowerBase>::PowerDomainId) {
+        self.mut_base().remove_power_domain(domain)
+    }
+
+    /// Set the nominal supply voltage of the domain. Returns the old voltage.
+    fn d_set_power_domain_voltage(
+        &mut self,
+        domain: &<Self::D as PowerBase>::PowerDomainId,
+        voltage: f64,
+    ) -> f64 {
+        self.mut_base().set_power_domain_voltage(domain, voltage)
+    }
+
+    /// Mark the domain as always-on or switchable. Returns the old value.
+    fn d_set_power_domain_always_on(
+        &mut self,
+        domain: &<Self::D as PowerBase>::PowerDomainId,
+        always_on: bool,
+    ) -> bool {
+        self.mut_base()
+            .set_power_domain_always_on(domain, always_on)
+    }
+
+    /// Assign the cell to a power domain. Returns the previously assigned domain.
+    fn d_set_power_domain_of_cell(
+        &mut self,
+        cell: &<Self::D as HierarchyBase>::CellId,
+        domain: Option<<Self::D as PowerBase>::PowerDomainId>,
+    ) -> Option<<Self::D as PowerBase>::PowerDomainId> {
+        self.mut_base().set_power_domain_of_cell(cell, domain)
+    }
+
+    /// Assign the cell instance to a power domain. Returns the previously assigned domain.
+    fn d_set_power_domain_of_cell_instance(
+        &mut self,
+        inst: &<Self::D as HierarchyBase>::CellInstId,
+        domain: Option<<Self::D as PowerBase>::PowerDomainId>,
+    ) -> Option<<Self::D as PowerBase>::PowerDomainId> {
+        self.mut_base()
+            .set_power_domain_of_cell_instance(inst, domain)
+    }
+}
+
+impl<T, N> PowerEdit for T
+where
+    T: NetlistEdit<
+            NameType = N::NameType,
+            CellId = N::CellId,
+            CellInstId = N::CellInstId,
+            PinId = N::PinId,
+            PinInstId = N::PinInstId,
+            NetId = N::NetId,
+        > + PowerBase<PowerDomainId = N::PowerDomainId>
+        + PowerEditDecorator<D = N>,
+    N: PowerEdit + 'static,
+{
+    fn create_power_domain(
+        &mut self,
+        name: Self::NameType,
+        supply_net: Self::NetId,
+        ground_net: Self::NetId,
+        voltage: f64,
+        always_on: bool,
+    ) -> Self::PowerDomainId {
+        self.d_create_power_domain(name, supply_net, ground_net, voltage, always_on)
+    }
+
+    fn remove_power_domain(&mut self, domain: &Self::PowerDomainId) {
+        self.d_remove_power_domain(domain)
+    }
+
+    fn set_power_domain_voltage(&mut self, domain: &Self::PowerDomainId, voltage: f64) -> f64 {
+        self.d_set_power_domain_voltage(domain, voltage)
+    }
+
+    fn set_power_domain_always_on(
+        &mut self,
+        domain: &Self::PowerDomainId,
+        always_on: bool,
+    ) -> bool {
+        self.d_set_power_domain_always_on(domain, always_on)
+    }
+
+    fn set_power_domain_of_cell(
+        &mut self,
+        cell: &Self::CellId,
+        domain: Option<Self::PowerDomainId>,
+    ) -> Option<Self::PowerDomainId> {
+        self.d_set_power_domain_of_cell(cell, domain)
+    }
+
+    fn set_power_domain_of_cell_instance(
+        &mut self,
+        inst: &Self::CellInstId,
+        domain: Option<Self::PowerDomainId>,
+    ) -> Option<Self::PowerDomainId> {
+        self.d_set_power_domain_of_cell_instance(inst, domain)
+    }
+}
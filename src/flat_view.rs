@@ -6,8 +6,23 @@
 //! Wrapper around a netlist which provides an on-the-fly flat view of a certain cell.
 //! The presented view is flattened until leaf cells.
 //! Internally this works by using component IDs that are actually paths through the hierarchy.
+//!
+//! If the underlying structure is a netlist, the flat view also presents flattened nets.
+//! Net segments in different levels of the hierarchy which are connected through pins
+//! of flattened cells form one flat net.
+//!
+//! Arrays of cell instances are expanded. Each element of a path is a tuple
+//! `(cell instance, column, row)` which selects one element of an array. Cell instances which are
+//! not arrays only have the element in column `0` and row `0`. Array elements are named
+//! `name[column,row]`.
 
-use crate::traits::HierarchyBase;
+use crate::layout::types::{LayerInfo, UInt};
+use crate::netlist::bus::BitOrder;
+use crate::netlist::direction::Direction;
+use crate::prelude::{Geometry, MapPointwise, Point, PropertyValue, Rect};
+use crate::traits::{BusBase, HierarchyBase, LayoutBase, NetlistBase, RegionSearch};
+use iron_shapes::transform::SimpleTransform;
+use iron_shapes::CoordinateType;
 use std::collections::{HashMap, HashSet};
 
 /// Wrapper around ID types.
@@ -87,6 +102,68 @@ impl<'a, N: HierarchyBase> FlatView<'a, N> {
         }
     }
 
+    /// Get all elements of a cell instance as `(cell instance, column, row)` tuples.
+    /// A cell instance which is not an array has the single element in column `0` and row `0`.
+    fn array_elements(&self, inst: N::CellInstId) -> Vec<(N::CellInstId, u32, u32)> {
+        let (columns, rows) = self.base.cell_instance_array_size(&inst);
+        (0..rows)
+            .flat_map(|row| (0..columns).map(move |column| (column, row)))
+            .map(|(column, row)| (inst.clone(), column, row))
+            .collect()
+    }
+
+    /// Check if the cell instance exists and has an element in the given column and row.
+    fn array_element_exists(&self, (inst, column, row): &(N::CellInstId, u32, u32)) -> bool {
+        if !self.base.cell_instance_exists(inst) {
+            return false;
+        }
+        let (columns, rows) = self.base.cell_instance_array_size(inst);
+        *column < columns && *row < rows
+    }
+
+    /// Get the name of an array element. Elements of arrays are named `name[column,row]`.
+    fn array_element_name(
+        &self,
+        (inst, column, row): &(N::CellInstId, u32, u32),
+    ) -> Option<N::NameType> {
+        let name = self.base.cell_instance_name(inst)?;
+        if self.base.cell_instance_array_size(inst) == (1, 1) {
+            Some(name)
+        } else {
+            Some(format!("{}[{},{}]", name, column, row).into())
+        }
+    }
+
+    /// Find an array element by its name. This is the inverse of `array_element_name()`.
+    fn array_element_by_name(
+        &self,
+        parent_cell: &N::CellId,
+        name: &str,
+    ) -> Option<(N::CellInstId, u32, u32)> {
+        if let Some(inst) = self.base.cell_instance_by_name(parent_cell, name) {
+            if self.base.cell_instance_array_size(&inst) == (1, 1) {
+                return Some((inst, 0, 0));
+            }
+        }
+        // Split off the array index.
+        let (inst_name, index) = name.strip_suffix(']')?.rsplit_once('[')?;
+        let (column, row) = index.split_once(',')?;
+        let inst = self.base.cell_instance_by_name(parent_cell, inst_name)?;
+        let element = (inst, column.parse().ok()?, row.parse().ok()?);
+        let is_array = self.base.cell_instance_array_size(&element.0) != (1, 1);
+        if is_array && self.array_element_exists(&element) {
+            Some(element)
+        } else {
+            None
+        }
+    }
+
+    /// Get the names of the path elements.
+    /// Returns `None` if an element has no name.
+    fn path_names(&self, path: &[(N::CellInstId, u32, u32)]) -> Option<Vec<N::NameType>> {
+        path.iter().map(|e| self.array_element_name(e)).collect()
+    }
+
     fn cell_is_leaf(&self, cell: &N::CellId) -> bool {
         self.base.num_child_instances(&cell) == 0
     }
@@ -102,12 +179,78 @@ impl<'a, N: HierarchyBase> FlatView<'a, N> {
         let is_leaf = self.cell_is_leaf(cell);
         !is_top && !is_leaf
     }
+
+    /// Check if `path` leads from a cell of the flat view through flattened cells
+    /// down to `cell`. An empty path is valid if `cell` exists in the flat view.
+    /// This is used to validate IDs of objects which live inside flattened cells.
+    fn is_flattened_path(&self, path: &[(N::CellInstId, u32, u32)], cell: &N::CellId) -> bool {
+        if !path.iter().all(|e| self.array_element_exists(e)) {
+            return false;
+        }
+        let is_connected_path = path
+            .windows(2)
+            .all(|w| self.base.template_cell(&w[0].0) == self.base.parent_cell(&w[1].0));
+        let is_valid_path = match (path.first(), path.last()) {
+            (Some((first, _, _)), Some((last, _, _))) => {
+                self.cell_exists_in_flat_view(&self.base.parent_cell(first))
+                    && &self.base.template_cell(last) == cell
+                    && !self.cell_is_leaf(cell)
+            }
+            _ => self.cell_exists_in_flat_view(cell),
+        };
+        is_connected_path && is_valid_path
+    }
+
+    /// Call a function for the cell and for each flattened cell below it.
+    /// The function is called with the path to the cell and the cell ID.
+    /// Cells inside arrays are visited once for each array element.
+    fn for_each_flattened_cell(
+        &self,
+        cell: &N::CellId,
+        path: &mut Vec<(N::CellInstId, u32, u32)>,
+        f: &mut dyn FnMut(&[(N::CellInstId, u32, u32)], &N::CellId),
+    ) {
+        f(path, cell);
+        self.base.for_each_cell_instance(cell, |inst| {
+            let template = self.base.template_cell(&inst);
+            if !self.cell_is_leaf(&template) {
+                for element in self.array_elements(inst) {
+                    path.push(element);
+                    self.for_each_flattened_cell(&template, path, f);
+                    path.pop();
+                }
+            }
+        });
+    }
+
+    /// Call a function for each path which leads from a cell of the flat view down to `cell`.
+    /// `path_rev` holds the already known path elements below `cell` in reverse order.
+    fn for_each_reference_path(
+        &self,
+        cell: &N::CellId,
+        path_rev: &mut Vec<(N::CellInstId, u32, u32)>,
+        f: &mut dyn FnMut(Vec<(N::CellInstId, u32, u32)>),
+    ) {
+        self.base.for_each_cell_reference(cell, |r| {
+            let parent = self.base.parent_cell(&r);
+            for element in self.array_elements(r) {
+                path_rev.push(element);
+                if self.cell_exists_in_flat_view(&parent) {
+                    // Reached the top.
+                    f(path_rev.iter().rev().cloned().collect());
+                } else {
+                    self.for_each_reference_path(&parent, path_rev, f);
+                }
+                path_rev.pop();
+            }
+        });
+    }
 }
 
 impl<'a, N: HierarchyBase> HierarchyBase for FlatView<'a, N> {
     type NameType = N::NameType;
     type CellId = N::CellId;
-    type CellInstId = Vec<N::CellInstId>;
+    type CellInstId = Vec<(N::CellInstId, u32, u32)>;
 
     fn cell_by_name(&self, name: &str) -> Option<Self::CellId> {
         let cell = self.base.cell_by_name(name);
@@ -134,11 +277,11 @@ impl<'a, N: HierarchyBase> HierarchyBase for FlatView<'a, N> {
         // For each path element...
         for name in path {
             // Find the child in the current parent.
-            let inst = self.base.cell_instance_by_name(&parent_cell, name);
-            if let Some(inst) = inst {
+            let element = self.array_element_by_name(&parent_cell, name);
+            if let Some(element) = element {
                 // Descend into the child.
-                parent_cell = self.base.template_cell(&inst);
-                current_inst.push(inst);
+                parent_cell = self.base.template_cell(&element.0);
+                current_inst.push(element);
             } else {
                 // No child could be found.
                 current_inst.clear();
@@ -163,22 +306,45 @@ impl<'a, N: HierarchyBase> HierarchyBase for FlatView<'a, N> {
 
     fn cell_instance_name(&self, cell_inst: &Self::CellInstId) -> Option<Self::NameType> {
         // Try to find the name of each path element.
-        let path_names: Option<Vec<_>> = cell_inst
-            .iter()
-            .map(|inst| self.base.cell_instance_name(inst))
-            .collect();
         // If a name could be found for each element
         // join them with the path separator.
-        path_names.map(|names| names.join(&self.path_separator).into())
+        self.path_names(cell_inst)
+            .map(|names| names.join(&self.path_separator).into())
     }
 
     fn parent_cell(&self, cell_instance: &Self::CellInstId) -> Self::CellId {
-        self.base.parent_cell(&cell_instance[0])
+        self.base.parent_cell(&cell_instance[0].0)
     }
 
     fn template_cell(&self, cell_instance: &Self::CellInstId) -> Self::CellId {
         self.base
-            .template_cell(&cell_instance[cell_instance.len() - 1])
+            .template_cell(&cell_instance[cell_instance.len() - 1].0)
+    }
+
+    fn cell_exists(&self, cell: &Self::CellId) -> bool {
+        self.base.cell_exists(cell) && self.cell_exists_in_flat_view(cell)
+    }
+
+    fn cell_instance_exists(&self, cell_inst: &Self::CellInstId) -> bool {
+        // The path must start in a cell of the flat view, end in a leaf cell
+        // and each path element must be a child of the previous one.
+        let (first, last) = match (cell_inst.first(), cell_inst.last()) {
+            (Some((first, _, _)), Some((last, _, _))) => (first, last),
+            _ => return false,
+        };
+
+        let all_exist = cell_inst.iter().all(|e| self.array_element_exists(e));
+        if !all_exist {
+            return false;
+        }
+
+        let is_connected_path = cell_inst
+            .windows(2)
+            .all(|w| self.base.template_cell(&w[0].0) == self.base.parent_cell(&w[1].0));
+
+        is_connected_path
+            && self.cell_exists_in_flat_view(&self.base.parent_cell(first))
+            && self.cell_is_leaf(&self.base.template_cell(last))
     }
 
     fn for_each_cell<F>(&self, mut f: F)
@@ -199,7 +365,12 @@ impl<'a, N: HierarchyBase> HierarchyBase for FlatView<'a, N> {
     {
         // Depth-first traversal of the dependency graph.
         // Start with the top-level instances.
-        let mut stack = vec![self.base.each_cell_instance(cell)];
+        let each_element = |cell: &N::CellId| {
+            self.base
+                .each_cell_instance(cell)
+                .flat_map(move |inst| self.array_elements(inst))
+        };
+        let mut stack = vec![each_element(cell)];
 
         // Path through the hierarchy to the current cell.
         let mut path = vec![];
@@ -207,19 +378,19 @@ impl<'a, N: HierarchyBase> HierarchyBase for FlatView<'a, N> {
         // Work through all the levels until none is left.
         while let Some(mut insts) = stack.pop() {
             // Take the next instance from the current level...
-            if let Some(inst) = insts.next() {
+            if let Some(element) = insts.next() {
                 // ... and directly push the current level again on the stack.
                 stack.push(insts);
-                let template = self.base.template_cell(&inst);
-                path.push(inst);
+                let template = self.base.template_cell(&element.0);
+                path.push(element);
 
                 if self.base.num_child_instances(&template) == 0 {
                     // Leaf cell.
-                    f(path.clone())
+                    f(path.clone());
+                    path.pop();
                 } else {
                     // Push new level.
-                    let sub_insts = self.base.each_cell_instance(&template);
-                    stack.push(sub_insts);
+                    stack.push(each_element(&template));
                 }
             } else {
                 // insts is empty. We go a level up.
@@ -278,342 +449,1017 @@ impl<'a, N: HierarchyBase> HierarchyBase for FlatView<'a, N> {
             self.base.cell_name(cell)
         );
 
-        let mut references = vec![self.base.each_cell_reference(&cell)];
-        let mut path_rev = vec![];
+        self.for_each_reference_path(cell, &mut vec![], &mut f);
+    }
+
+    fn num_child_instances(&self, cell: &Self::CellId) -> usize {
+        // Count how many times each cell is instantiated.
+        // Each element of an array counts as an instance.
+        let mut counted_cells: HashMap<N::CellId, usize> = Default::default();
+        self.base.for_each_cell_instance(cell, |inst| {
+            let template = self.base.template_cell(&inst);
+            let (columns, rows) = self.base.cell_instance_array_size(&inst);
+            *counted_cells.entry(template).or_insert(0) += columns as usize * rows as usize;
+        });
 
-        while let Some(mut refs) = references.pop() {
-            if let Some(r) = refs.next() {
-                references.push(refs);
-                let parent = self.base.parent_cell(&r);
-                path_rev.push(r.clone());
-                if self.cell_exists_in_flat_view(&parent) {
-                    // Reached the top.
-                    let mut path = path_rev.clone();
-                    path.reverse();
-                    f(path);
+        // Compute recursively the number of children.
+        counted_cells
+            .into_iter()
+            .map(|(cell, num)| {
+                if self.cell_is_leaf(&cell) {
+                    num
                 } else {
-                    // Get parent references.
-                    references.push(self.base.each_cell_reference(&parent));
+                    num * self.num_child_instances(&cell)
                 }
-            } else {
-                // Worked through all references on this level.
-                path_rev.pop();
+            })
+            .sum()
+    }
+
+    fn num_cells(&self) -> usize {
+        let mut count = 0;
+        self.for_each_cell(|_| count += 1);
+        count
+    }
+}
+
+impl<'a, N: NetlistBase> FlatView<'a, N> {
+    /// Find the net on the next higher level of the hierarchy which is connected to `net`.
+    /// `path` is the path to the cell which contains `net`.
+    /// Returns `None` if `net` is not connected to a net outside of the cell.
+    ///
+    /// If the net is connected to the upper level through more than one pin, the first pin
+    /// (in the order of the cell pins) decides.
+    fn upper_net(&self, path: &[(N::CellInstId, u32, u32)], net: &N::NetId) -> Option<N::NetId> {
+        let (inst, _, _) = path.last()?;
+        let template = self.base.template_cell(inst);
+        self.base
+            .each_pin(&template)
+            .filter(|pin| self.base.net_of_pin(pin).as_ref() == Some(net))
+            .find_map(|pin| {
+                self.base
+                    .net_of_pin_instance(&self.base.pin_instance(inst, &pin))
+            })
+    }
+
+    /// Follow the net segment up through the hierarchy until the highest-level segment is found.
+    /// The highest-level segment is used to identify the flat net.
+    fn canonical_net(
+        &self,
+        mut path: Vec<(N::CellInstId, u32, u32)>,
+        mut net: N::NetId,
+    ) -> (Vec<(N::CellInstId, u32, u32)>, N::NetId) {
+        while let Some(upper) = self.upper_net(&path, &net) {
+            path.pop();
+            net = upper;
+        }
+        (path, net)
+    }
+}
+
+/// Nets of the flat view are formed by all the hierarchical net segments which are connected through pins
+/// of flattened cells. A flat net is identified by its highest-level segment together with the path
+/// to the cell which contains this segment. Names of the flat nets are constructed from
+/// the path and the name of the highest-level segment.
+///
+/// Pins are only visible on top-level cells and leaf cells. Pin instances are identified
+/// by the flat cell instance together with the pin instance of the last path element.
+///
+/// All elements of an array are connected to the same nets of the parent cell. Nets inside
+/// a flattened array exist once for each element.
+///
+/// # Caveat
+/// Two nets which are shorted by a cell that connects one net to more than one pin
+/// are presented as separate flat nets.
+impl<'a, N: NetlistBase> NetlistBase for FlatView<'a, N> {
+    type PinId = N::PinId;
+    type PinInstId = (Vec<(N::CellInstId, u32, u32)>, N::PinInstId);
+    type NetId = (Vec<(N::CellInstId, u32, u32)>, N::NetId);
+
+    fn template_pin(&self, (_, pin_instance): &Self::PinInstId) -> Self::PinId {
+        self.base.template_pin(pin_instance)
+    }
+
+    fn pin_direction(&self, pin: &Self::PinId) -> Direction {
+        self.base.pin_direction(pin)
+    }
+
+    fn pin_name(&self, pin: &Self::PinId) -> Self::NameType {
+        self.base.pin_name(pin)
+    }
+
+    fn pin_by_name(&self, parent_circuit: &Self::CellId, name: &str) -> Option<Self::PinId> {
+        self.base.pin_by_name(parent_circuit, name)
+    }
+
+    fn parent_cell_of_pin(&self, pin: &Self::PinId) -> Self::CellId {
+        self.base.parent_cell_of_pin(pin)
+    }
+
+    fn parent_of_pin_instance(&self, (cell_inst, _): &Self::PinInstId) -> Self::CellInstId {
+        cell_inst.clone()
+    }
+
+    fn pin_instance(&self, cell_inst: &Self::CellInstId, pin: &Self::PinId) -> Self::PinInstId {
+        let (leaf_inst, _, _) = &cell_inst[cell_inst.len() - 1];
+        (cell_inst.clone(), self.base.pin_instance(leaf_inst, pin))
+    }
+
+    fn parent_cell_of_net(&self, (path, net): &Self::NetId) -> Self::CellId {
+        if let Some((instance, _, _)) = path.first() {
+            // The parent of the flattened net is equal to the parent of the first
+            // cell instance in the path.
+            self.base.parent_cell(instance)
+        } else {
+            // The net lives in the top-cell.
+            self.base.parent_cell_of_net(net)
+        }
+    }
+
+    fn net_of_pin(&self, pin: &Self::PinId) -> Option<Self::NetId> {
+        // Pins exist only on top-level and leaf cells, hence the net cannot go further up.
+        self.base.net_of_pin(pin).map(|n| (vec![], n))
+    }
+
+    fn net_of_pin_instance(&self, (path, pin_instance): &Self::PinInstId) -> Option<Self::NetId> {
+        // The net of the pin instance lives in the parent of the leaf instance.
+        let parent_path = path[..path.len() - 1].to_vec();
+        self.base
+            .net_of_pin_instance(pin_instance)
+            .map(|n| self.canonical_net(parent_path, n))
+    }
+
+    fn net_zero(&self, parent_circuit: &Self::CellId) -> Self::NetId {
+        (vec![], self.base.net_zero(parent_circuit))
+    }
+
+    fn net_one(&self, parent_circuit: &Self::CellId) -> Self::NetId {
+        (vec![], self.base.net_one(parent_circuit))
+    }
+
+    fn net_by_name(&self, parent_circuit: &Self::CellId, name: &str) -> Option<Self::NetId> {
+        // Find last path separator after which comes the net name.
+        if let Some(last_separator_pos) = name.rfind(self.path_separator.as_str()) {
+            let path_string = &name[0..last_separator_pos];
+            let net_name = &name[last_separator_pos + self.path_separator.len()..];
+
+            // Resolve cell instance.
+            let path = self.cell_instance_by_name(parent_circuit, path_string)?;
+            let template = self.base.template_cell(&path[path.len() - 1].0);
+            if self.cell_is_leaf(&template) {
+                // Nets inside leaf cells are not part of the flat netlist of the parent.
+                return None;
             }
+            self.base
+                .net_by_name(&template, net_name)
+                .map(|n| self.canonical_net(path, n))
+        } else {
+            // No separator in net name. Look directly in the top cell.
+            self.base
+                .net_by_name(parent_circuit, name)
+                .map(|n| (vec![], n))
         }
     }
 
-    fn num_child_instances(&self, cell: &Self::CellId) -> usize {
-        let num_non_flat_children = self.base.num_child_instances(cell);
-        if num_non_flat_children == 0 {
-            0
+    fn net_name(&self, (path, net): &Self::NetId) -> Option<Self::NameType> {
+        let net_name = self.base.net_name(net)?;
+        // Try to find the name of each path element.
+        // If a name could be found for each element
+        // join them with the path separator.
+        self.path_names(path).map(|mut names| {
+            names.push(net_name);
+            names.join(&self.path_separator).into()
+        })
+    }
+
+    fn pin_exists(&self, pin: &Self::PinId) -> bool {
+        self.base.pin_exists(pin)
+            && self.cell_exists_in_flat_view(&self.base.parent_cell_of_pin(pin))
+    }
+
+    fn pin_instance_exists(&self, (cell_inst, pin_instance): &Self::PinInstId) -> bool {
+        self.cell_instance_exists(cell_inst)
+            && self.base.pin_instance_exists(pin_instance)
+            && Some(&self.base.parent_of_pin_instance(pin_instance))
+                == cell_inst.last().map(|(inst, _, _)| inst)
+    }
+
+    fn net_exists(&self, (path, net): &Self::NetId) -> bool {
+        self.base.net_exists(net)
+            && self.is_flattened_path(path, &self.base.parent_cell_of_net(net))
+            // Only the highest-level segment is a valid ID.
+            && self.upper_net(path, net).is_none()
+    }
+
+    fn for_each_pin<F>(&self, circuit: &Self::CellId, f: F)
+    where
+        F: FnMut(Self::PinId) -> (),
+    {
+        self.base.for_each_pin(circuit, f)
+    }
+
+    fn for_each_pin_instance<F>(&self, circuit_inst: &Self::CellInstId, mut f: F)
+    where
+        F: FnMut(Self::PinInstId) -> (),
+    {
+        let (leaf_inst, _, _) = &circuit_inst[circuit_inst.len() - 1];
+        self.base
+            .for_each_pin_instance(leaf_inst, |p| f((circuit_inst.clone(), p)))
+    }
+
+    fn for_each_internal_net<F>(&self, circuit: &Self::CellId, mut f: F)
+    where
+        F: FnMut(Self::NetId) -> (),
+    {
+        self.for_each_flattened_cell(circuit, &mut vec![], &mut |path, cell| {
+            self.base.for_each_internal_net(cell, |net| {
+                // Skip segments which are part of a net on a higher level.
+                if self.upper_net(path, &net).is_none() {
+                    f((path.to_vec(), net))
+                }
+            })
+        })
+    }
+
+    fn num_pins(&self, circuit: &Self::CellId) -> usize {
+        self.base.num_pins(circuit)
+    }
+
+    fn for_each_pin_of_net<F>(&self, (path, net): &Self::NetId, f: F)
+    where
+        F: FnMut(Self::PinId) -> (),
+    {
+        // Only nets of the top-level cell can be connected to pins.
+        if path.is_empty() {
+            self.base.for_each_pin_of_net(net, f)
+        }
+    }
+
+    fn for_each_pin_instance_of_net<F>(&self, net: &Self::NetId, mut f: F)
+    where
+        F: FnMut(Self::PinInstId) -> (),
+    {
+        // Depth-first traversal of the net segments.
+        let mut visited = HashSet::new();
+        let mut stack = vec![net.clone()];
+        while let Some((path, net)) = stack.pop() {
+            if !visited.insert((path.clone(), net.clone())) {
+                continue;
+            }
+            self.base.for_each_pin_instance_of_net(&net, |pin_inst| {
+                let inst = self.base.parent_of_pin_instance(&pin_inst);
+                let template = self.base.template_cell(&inst);
+                // All elements of an array are connected to the net.
+                for element in self.array_elements(inst) {
+                    let mut sub_path = path.clone();
+                    sub_path.push(element);
+                    if self.cell_is_leaf(&template) {
+                        f((sub_path, pin_inst.clone()))
+                    } else {
+                        // Descend into the flattened cell.
+                        let pin = self.base.template_pin(&pin_inst);
+                        if let Some(sub_net) = self.base.net_of_pin(&pin) {
+                            // Follow only segments which belong to this flat net.
+                            if self.upper_net(&sub_path, &sub_net).as_ref() == Some(&net) {
+                                stack.push((sub_path, sub_net));
+                            }
+                        }
+                    }
+                }
+            })
+        }
+    }
+}
+
+/// Only buses of the top-level cell are visible. Nets of buses in flattened cells
+/// can be merged with nets of the parent, hence these buses are dissolved.
+impl<'a, N: BusBase> BusBase for FlatView<'a, N> {
+    type PinBusId = N::PinBusId;
+    type NetBusId = N::NetBusId;
+
+    fn pin_bus_by_name(&self, parent_circuit: &Self::CellId, name: &str) -> Option<Self::PinBusId> {
+        self.base.pin_bus_by_name(parent_circuit, name)
+    }
+
+    fn pin_bus_name(&self, bus: &Self::PinBusId) -> Self::NameType {
+        self.base.pin_bus_name(bus)
+    }
+
+    fn parent_cell_of_pin_bus(&self, bus: &Self::PinBusId) -> Self::CellId {
+        self.base.parent_cell_of_pin_bus(bus)
+    }
+
+    fn pin_bus_bit_order(&self, bus: &Self::PinBusId) -> BitOrder {
+        self.base.pin_bus_bit_order(bus)
+    }
+
+    fn bus_of_pin(&self, pin: &Self::PinId) -> Option<Self::PinBusId> {
+        self.base.bus_of_pin(pin)
+    }
+
+    fn pin_bit_index(&self, pin: &Self::PinId) -> Option<usize> {
+        self.base.pin_bit_index(pin)
+    }
+
+    fn net_bus_by_name(&self, parent_circuit: &Self::CellId, name: &str) -> Option<Self::NetBusId> {
+        self.base.net_bus_by_name(parent_circuit, name)
+    }
+
+    fn net_bus_name(&self, bus: &Self::NetBusId) -> Self::NameType {
+        self.base.net_bus_name(bus)
+    }
+
+    fn parent_cell_of_net_bus(&self, bus: &Self::NetBusId) -> Self::CellId {
+        self.base.parent_cell_of_net_bus(bus)
+    }
+
+    fn net_bus_bit_order(&self, bus: &Self::NetBusId) -> BitOrder {
+        self.base.net_bus_bit_order(bus)
+    }
+
+    fn bus_of_net(&self, (path, net): &Self::NetId) -> Option<Self::NetBusId> {
+        // Only buses of the top-level cell are visible.
+        if path.is_empty() {
+            self.base.bus_of_net(net)
+        } else {
+            None
+        }
+    }
+
+    fn net_bit_index(&self, (path, net): &Self::NetId) -> Option<usize> {
+        if path.is_empty() {
+            self.base.net_bit_index(net)
+        } else {
+            None
+        }
+    }
+
+    fn for_each_pin_bus<F>(&self, circuit: &Self::CellId, f: F)
+    where
+        F: FnMut(Self::PinBusId) -> (),
+    {
+        self.base.for_each_pin_bus(circuit, f)
+    }
+
+    fn for_each_pin_of_bus<F>(&self, bus: &Self::PinBusId, f: F)
+    where
+        F: FnMut(Self::PinId) -> (),
+    {
+        self.base.for_each_pin_of_bus(bus, f)
+    }
+
+    fn for_each_net_bus<F>(&self, circuit: &Self::CellId, f: F)
+    where
+        F: FnMut(Self::NetBusId) -> (),
+    {
+        self.base.for_each_net_bus(circuit, f)
+    }
+
+    fn for_each_net_of_bus<F>(&self, bus: &Self::NetBusId, mut f: F)
+    where
+        F: FnMut(Self::NetId) -> (),
+    {
+        // Nets of a top-level bus are never merged into a higher-level net.
+        self.base.for_each_net_of_bus(bus, |net| f((vec![], net)))
+    }
+}
+
+impl<'a, N: LayoutBase> FlatView<'a, N> {
+    /// Compute the transform of the last cell instance in the path relative to the first parent cell.
+    /// Each path element contributes the transform of its array element.
+    fn path_transform(&self, path: &[(N::CellInstId, u32, u32)]) -> SimpleTransform<N::Coord> {
+        path.iter()
+            .rev()
+            .fold(SimpleTransform::identity(), |acc, (inst, column, row)| {
+                acc.then(&self.base.array_element_transform(inst, *column, *row))
+            })
+    }
+}
+
+/// Shapes of flattened cells are presented as shapes of the top-level cell.
+/// A flat shape is identified by the path to the cell which contains the shape and
+/// the shape ID in this cell. All geometries are given in the coordinates of the
+/// top-level cell.
+impl<'a, N: LayoutBase> LayoutBase for FlatView<'a, N> {
+    type Coord = N::Coord;
+    type Area = N::Area;
+    type LayerId = N::LayerId;
+    type ShapeId = (Vec<(N::CellInstId, u32, u32)>, N::ShapeId);
+
+    fn dbu(&self) -> Self::Coord {
+        self.base.dbu()
+    }
+
+    fn each_layer(&self) -> Box<dyn Iterator<Item = Self::LayerId> + '_> {
+        self.base.each_layer()
+    }
+
+    fn layer_info(&self, layer: &Self::LayerId) -> LayerInfo<Self::NameType> {
+        self.base.layer_info(layer)
+    }
+
+    fn find_layer(&self, index: UInt, datatype: UInt) -> Option<Self::LayerId> {
+        self.base.find_layer(index, datatype)
+    }
+
+    fn layer_by_name(&self, name: &str) -> Option<Self::LayerId> {
+        self.base.layer_by_name(name)
+    }
+
+    fn bounding_box_per_layer(
+        &self,
+        cell: &Self::CellId,
+        layer: &Self::LayerId,
+    ) -> Option<Rect<Self::Coord>> {
+        // Flattening does not change the geometry.
+        self.base.bounding_box_per_layer(cell, layer)
+    }
+
+    fn each_shape_id(
+        &self,
+        cell: &Self::CellId,
+        layer: &Self::LayerId,
+    ) -> Box<dyn Iterator<Item = Self::ShapeId> + '_> {
+        let mut shapes = vec![];
+        self.for_each_flattened_cell(cell, &mut vec![], &mut |path, cell| {
+            shapes.extend(
+                self.base
+                    .each_shape_id(cell, layer)
+                    .map(|id| (path.to_vec(), id)),
+            )
+        });
+        Box::new(shapes.into_iter())
+    }
+
+    fn for_each_shape<F>(&self, cell: &Self::CellId, layer: &Self::LayerId, mut f: F)
+    where
+        F: FnMut(&Self::ShapeId, &Geometry<Self::Coord>) -> (),
+    {
+        self.for_each_flattened_cell(cell, &mut vec![], &mut |path, cell| {
+            let tf = self.path_transform(path);
+            self.base.for_each_shape(cell, layer, |id, geometry| {
+                let flat_id = (path.to_vec(), id.clone());
+                if path.is_empty() {
+                    f(&flat_id, geometry)
+                } else {
+                    f(&flat_id, &geometry.transform(|p| tf.transform_point(p)))
+                }
+            })
+        })
+    }
+
+    fn with_shape<F, R>(&self, (path, shape_id): &Self::ShapeId, mut f: F) -> R
+    where
+        F: FnMut(&Self::LayerId, &Geometry<Self::Coord>) -> R,
+    {
+        let tf = self.path_transform(path);
+        self.base.with_shape(shape_id, |layer, geometry| {
+            f(layer, &geometry.transform(|p| tf.transform_point(p)))
+        })
+    }
+
+    fn parent_of_shape(&self, (path, shape_id): &Self::ShapeId) -> (Self::CellId, Self::LayerId) {
+        let (cell, layer) = self.base.parent_of_shape(shape_id);
+        if let Some((first, _, _)) = path.first() {
+            (self.base.parent_cell(first), layer)
         } else {
-            // Count how many times each cell is instantiated.
-            let mut counted_cells: HashMap<N::CellId, usize> = Default::default();
-            self.base.for_each_cell_instance(cell, |inst| {
+            (cell, layer)
+        }
+    }
+
+    fn layer_exists(&self, layer: &Self::LayerId) -> bool {
+        self.base.layer_exists(layer)
+    }
+
+    fn shape_exists(&self, (path, shape_id): &Self::ShapeId) -> bool {
+        self.base.shape_exists(shape_id)
+            && self.is_flattened_path(path, &self.base.parent_of_shape(shape_id).0)
+    }
+
+    fn get_transform(&self, cell_inst: &Self::CellInstId) -> SimpleTransform<Self::Coord> {
+        self.path_transform(cell_inst)
+    }
+
+    fn get_shape_property(
+        &self,
+        (_, shape_id): &Self::ShapeId,
+        key: &Self::NameType,
+    ) -> Option<PropertyValue> {
+        self.base.get_shape_property(shape_id, key)
+    }
+
+    fn for_each_shape_property<F>(&self, (_, shape_id): &Self::ShapeId, f: F)
+    where
+        F: FnMut(&Self::NameType, &PropertyValue) -> (),
+    {
+        self.base.for_each_shape_property(shape_id, f)
+    }
+}
+
+/// Region queries are forwarded to the underlying layout. The search region is
+/// transformed into the coordinates of each flattened cell.
+impl<'a, N: RegionSearch> RegionSearch for FlatView<'a, N> {
+    fn each_shape_in_region_per_layer(
+        &self,
+        cell: &Self::CellId,
+        layer_id: &Self::LayerId,
+        search_region: &Rect<Self::Coord>,
+    ) -> Box<dyn Iterator<Item = Self::ShapeId> + '_> {
+        let mut shapes = vec![];
+        // Stack of (path, cell, search region in coordinates of the cell).
+        let mut stack = vec![(vec![], cell.clone(), *search_region)];
+        while let Some((path, cell, region)) = stack.pop() {
+            shapes.extend(
+                self.base
+                    .each_shape_in_region_per_layer(&cell, layer_id, &region)
+                    .map(|id| (path.clone(), id)),
+            );
+            for inst in self.base.each_cell_instance_in_region(&cell, &region) {
                 let template = self.base.template_cell(&inst);
-                *counted_cells.entry(template).or_insert(0) += 1;
-            });
-
-            // Compute recursively the number of children.
-            counted_cells
-                .into_iter()
-                .map(|(cell, num)| num * self.base.num_child_instances(&cell))
-                .sum()
+                if !self.cell_is_leaf(&template) {
+                    for (column, row) in self.base.each_array_element_in_region(&inst, &region) {
+                        let tf = self.base.array_element_transform(&inst, column, row);
+                        let sub_region = inverse_transform_rect(&tf, &region);
+                        let mut sub_path = path.clone();
+                        sub_path.push((inst.clone(), column, row));
+                        stack.push((sub_path, template.clone(), sub_region));
+                    }
+                }
+            }
         }
+        Box::new(shapes.into_iter())
     }
 
-    fn num_cells(&self) -> usize {
-        let mut count = 0;
-        self.for_each_cell(|_| count += 1);
-        count
+    fn each_cell_instance_in_region(
+        &self,
+        cell: &Self::CellId,
+        search_region: &Rect<Self::Coord>,
+    ) -> Box<dyn Iterator<Item = Self::CellInstId> + '_> {
+        let mut instances = vec![];
+        // Stack of (path, cell, search region in coordinates of the cell).
+        let mut stack = vec![(vec![], cell.clone(), *search_region)];
+        while let Some((path, cell, region)) = stack.pop() {
+            for inst in self.base.each_cell_instance_in_region(&cell, &region) {
+                let template = self.base.template_cell(&inst);
+                let is_leaf = self.cell_is_leaf(&template);
+                // Visit only the array elements which interact with the search region.
+                for (column, row) in self.base.each_array_element_in_region(&inst, &region) {
+                    let mut sub_path = path.clone();
+                    sub_path.push((inst.clone(), column, row));
+                    if is_leaf {
+                        instances.push(sub_path);
+                    } else {
+                        let tf = self.base.array_element_transform(&inst, column, row);
+                        let sub_region = inverse_transform_rect(&tf, &region);
+                        stack.push((sub_path, template.clone(), sub_region));
+                    }
+                }
+            }
+        }
+        Box::new(instances.into_iter())
     }
 }
 
-// On-the-fly flattening of nets is not solved yet.
-// The main difficulty is: Many nets might now be fused together into one net. How can this be uniquely and efficiently represented?
-// impl<'a, N: NetlistBase> NetlistBase for FlatView<'a, N> {
-//     type PinId = N::PinId;
-//     type PinInstId = (Self::CellInstId, N::PinInstId);
-//     // Pin instances need to be extended with the path through the hierarhcy.
-//     type NetId = (Self::CellInstId, N::NetId);
-//
-//     fn template_pin(&self, (_, pin_instance): &Self::PinInstId) -> Self::PinId {
-//         self.base.template_pin(pin_instance)
-//     }
-//
-//     fn pin_direction(&self, pin: &Self::PinId) -> Direction {
-//         self.base.pin_direction(pin)
-//     }
-//
-//     fn pin_name(&self, pin: &Self::PinId) -> Self::NameType {
-//         self.base.pin_name(pin)
-//     }
-//
-//     fn pin_by_name(&self, parent_circuit: &Self::CellId, name: &str) -> Option<Self::PinId> {
-//         self.base.pin_by_name(parent_circuit, name)
-//     }
-//
-//     fn parent_cell_of_pin(&self, pin: &Self::PinId) -> Self::CellId {
-//         self.base.parent_cell_of_pin(pin)
-//     }
-//
-//     fn parent_of_pin_instance(&self, (cell_inst, _pin_inst): &Self::PinInstId) -> Self::CellInstId {
-//         cell_inst.clone()
-//     }
-//
-//     fn parent_cell_of_net(&self, (path, net): &Self::NetId) -> Self::CellId {
-//         if let Some(instance) = path.iter().nth(0) {
-//             // The parent of the flattened net is equal to the parent of the first
-//             // cell instance in the path.
-//             self.base.parent_cell(instance)
-//         } else {
-//             // The net lives in the top-cell.
-//             self.base.parent_cell_of_net(net)
-//         }
-//     }
-//
-//     fn net_of_pin(&self, pin: &Self::PinId) -> Option<Self::NetId> {
-//         let net = self.base.net_of_pin(pin);
-//         net.map(
-//             |n| (vec![], n)
-//         )
-//     }
-//
-//     fn net_of_pin_instance(&self, (path, pin_instance): &Self::PinInstId) -> Option<Self::NetId> {
-//         let non_flattened_net = self.base.net_of_pin_instance(pin_instance);
-//         non_flattened_net.map(
-//             |n| (path.clone(), n)
-//         )
-//     }
-//
-//     fn net_zero(&self, parent_circuit: &Self::CellId) -> Self::NetId {
-//         (vec![], self.base.net_zero(parent_circuit))
-//     }
-//
-//     fn net_one(&self, parent_circuit: &Self::CellId) -> Self::NetId {
-//         (vec![], self.base.net_one(parent_circuit))
-//     }
-//
-//     fn net_by_name(&self, parent_circuit: &Self::CellId, name: &str) -> Option<Self::NetId> {
-//         // Find last path separator after which comes the net name.
-//
-//         if let Some(last_separator_pos) = name.rfind(self.path_separator.as_str()) {
-//             let path_string = &name[0..last_separator_pos];
-//             let net_name = &name[last_separator_pos + 1..name.len()];
-//
-//             // Resolve cell instance.
-//             if let Some(cell_inst) = self.cell_instance_by_name(parent_circuit, path_string) {
-//                 let template = self.base.template_cell(&cell_inst[cell_inst.len() - 1]);
-//                 let net = self.base.net_by_name(&template, net_name);
-//                 net.map(
-//                     |n| (cell_inst, n)
-//                 )
-//             } else {
-//                 // Cell instance not found.
-//                 None
-//             }
-//         } else {
-//             // No separator in net name. Look directly in the top cell.
-//             let net = self.base.net_by_name(parent_circuit, name);
-//             net.map(
-//                 |n| (vec![], n)
-//             )
-//         }
-//     }
-//
-//     fn net_name(&self, (path, net): &Self::NetId) -> Option<Self::NameType> {
-//         if let Some(net_name) = self.base.net_name(net) {
-//             // Try to find the name of each path element.
-//             let path_names: Option<Vec<_>> = path.iter()
-//                 .map(|inst| self.base.cell_instance_name(inst))
-//                 .collect();
-//             // If a name could be found for each element
-//             // join them with the path separator.
-//             path_names.map(|mut names| {
-//                 names.push(net_name);
-//                 names.join(&self.path_separator).into()
-//             })
-//         } else {
-//             None
-//         }
-//     }
-//
-//     fn for_each_pin<F>(&self, circuit: &Self::CellId, f: F) where F: FnMut(Self::PinId) -> () {
-//         self.base.for_each_pin(circuit, f)
-//     }
-//
-//     fn for_each_pin_instance<F>(&self, circuit_inst: &Self::CellInstId, mut f: F) where F: FnMut(Self::PinInstId) -> () {
-//         let hierarchical_inst = &circuit_inst[circuit_inst.len() - 1];
-//         self.base.for_each_pin_instance(hierarchical_inst, |p| {
-//             f((circuit_inst.clone(), p))
-//         })
-//     }
-//
-//     fn for_each_internal_net<F>(&self, circuit: &Self::CellId, f: F) where F: FnMut(Self::NetId) -> () {
-//         unimplemented!()
-//     }
-//
-//     fn num_pins(&self, cell: &Self::CellId) -> usize {
-//         self.base.num_pins(cell)
-//     }
-//
-//     fn for_each_pin_of_net<F>(&self, net: &Self::NetId, f: F) where F: FnMut(Self::PinId) -> () {
-//         unimplemented!()
-//     }
-//
-//     fn for_each_pin_instance_of_net<F>(&self, net: &Self::NetId, f: F) where F: FnMut(Self::PinInstId) -> () {
-//         unimplemented!()
-//     }
-//
-//     fn num_internal_nets(&self, parent: &Self::CellId) -> usize {
-//         unimplemented!()
-//     }
-// }
-//
-// #[cfg(test)]
-// mod tests_with_hierarchy {
-//     use crate::prelude::Chip;
-//     use crate::prelude::*;
-//     use crate::flat_view::FlatView;
-//
-//     fn create_test_chip() -> Chip {
-//         let mut chip = Chip::new();
-//         let top1 = chip.create_cell("TOP1".into());
-//         let top2 = chip.create_cell("TOP2".into());
-//         let intermediate = chip.create_cell("INTERMEDIATE".into());
-//         let leaf1 = chip.create_cell("LEAF1".into());
-//         let leaf2 = chip.create_cell("LEAF2".into());
-//
-//         chip.create_cell_instance(&intermediate, &leaf1, Some("leaf1_inst1".into()));
-//         chip.create_cell_instance(&intermediate, &leaf1, Some("leaf1_inst2".into()));
-//         chip.create_cell_instance(&intermediate, &leaf2, Some("leaf2_inst1".into()));
-//         chip.create_cell_instance(&intermediate, &leaf2, Some("leaf2_inst2".into()));
-//
-//         chip.create_cell_instance(&top1, &intermediate, Some("intermediate_inst1".into()));
-//         chip.create_cell_instance(&top1, &intermediate, Some("intermediate_inst2".into()));
-//
-//         // Create instances inanother cell with same names as in TOP1.
-//         chip.create_cell_instance(&top2, &leaf1, Some("leaf1_inst1".into()));
-//         chip.create_cell_instance(&top2, &leaf2, Some("leaf2_inst1".into()));
-//         chip.create_cell_instance(&top2, &leaf2, Some("leaf2_inst2".into()));
-//         chip
-//     }
-//
-//     #[test]
-//     fn test_num_cells() {
-//         let chip = create_test_chip();
-//         let flatview = FlatView::new(&chip);
-//         assert_eq!(flatview.num_cells(), 4); // Two top cells, two leaf cells.
-//     }
-//
-//     #[test]
-//     fn test_access_top_cell() {
-//         let chip = create_test_chip();
-//
-//         let flatview = FlatView::new(&chip);
-//         let top1 = flatview.cell_by_name("TOP1").expect("Cell not found.");
-//         assert_eq!(flatview.num_child_instances(&top1), 2 * 4);
-//         assert_eq!(flatview.num_dependent_cells(&top1), 0);
-//         assert_eq!(flatview.num_cell_dependencies(&top1), 2);
-//         assert_eq!(flatview.each_cell_instance(&top1).count(), 8);
-//     }
-//
-//     #[test]
-//     fn test_find_template_cell() {
-//         let chip = create_test_chip();
-//         let flatview = FlatView::new(&chip);
-//         let top1 = flatview.cell_by_name("TOP1").expect("Cell not found.");
-//         let leaf1 = flatview.cell_by_name("LEAF1").expect("Cell not found.");
-//
-//         // Template
-//         assert_eq!(
-//             &flatview.template_cell(
-//                 &flatview.cell_instance_by_name(&top1, "intermediate_inst1/leaf1_inst1",
-//                 ).unwrap()),
-//             &leaf1);
-//     }
-//
-//     #[test]
-//     fn test_find_instance_by_name() {
-//         let chip = create_test_chip();
-//         let flatview = FlatView::new(&chip);
-//         let top1 = flatview.cell_by_name("TOP1").expect("Cell not found.");
-//
-//         // Find by name.
-//         {
-//             let names = vec![
-//                 "intermediate_inst1/leaf1_inst1",
-//                 "intermediate_inst2/leaf1_inst1",
-//                 "intermediate_inst2/leaf2_inst1",
-//                 "intermediate_inst2/leaf2_inst2",
-//             ];
-//             for name in names {
-//                 let inst = flatview.cell_instance_by_name(&top1, name)
-//                     .expect("instance not found");
-//                 assert_eq!(flatview.cell_instance_name(&inst), Some(name.into()));
-//
-//                 // Parent
-//                 assert_eq!(&flatview.parent_cell(&inst), &top1);
-//             }
-//         }
-//     }
-//
-//     #[test]
-//     fn test_count_references() {
-//         let chip = create_test_chip();
-//         let flatview = FlatView::new(&chip);
-//         let top1 = flatview.cell_by_name("TOP1").expect("Cell not found.");
-//         let leaf1 = flatview.cell_by_name("LEAF1").expect("Cell not found.");
-//         let leaf2 = flatview.cell_by_name("LEAF2").expect("Cell not found.");
-//
-//         // References.
-//         assert_eq!(flatview.num_cell_references(&leaf1), 2 * 2 + 1);
-//         assert_eq!(flatview.num_cell_references(&leaf2), 2 * 2 + 2);
-//         assert_eq!(flatview.num_cell_references(&top1), 0);
-//     }
-//
-//
-//     #[test]
-//     fn test_another_top_cell() {
-//         // TOP2 contains instances with same name as in TOP1.
-//         let chip = create_test_chip();
-//         let flatview = FlatView::new(&chip);
-//         let top2 = flatview.cell_by_name("TOP2").expect("Cell not found.");
-//
-//         assert_eq!(flatview.num_dependent_cells(&top2), 0);
-//         assert_eq!(flatview.num_cell_dependencies(&top2), 2);
-//         assert_eq!(flatview.each_cell_instance(&top2).count(), 3);
-//     }
-//
-// }
-//
-//
-// #[cfg(test)]
-// mod tests_with_netlist {
-//     use crate::prelude::Chip;
-//     use crate::prelude::*;
-//     use crate::flat_view::FlatView;
-//
-//     fn create_test_netlist() -> Chip {
-//         let mut chip = Chip::new();
-//         let top = chip.create_cell("TOP".into());
-//         let sub = chip.create_cell("SUB".into());
-//         chip.create_net(&sub, Some("A".into()));
-//
-//         let inst_sub1 = chip.create_cell_instance(&top, &sub, Some("sub1".into()));
-//         let inst_sub2 = chip.create_cell_instance(&top, &sub, Some("sub2".into()));
-//
-//         chip
-//     }
-//
-//     #[test]
-//     fn test_net_by_name() {
-//         let chip = create_test_netlist();
-//         let flatview = FlatView::new(&chip);
-//         let top = flatview.cell_by_name("TOP").expect("Cell not found.");
-//         assert!(flatview.net_by_name(&top, "sub1/A").is_some());
-//         assert!(flatview.net_by_name(&top, "sub2/A").is_some());
-//     }
-//
-//
-// }
+/// Find the region which is mapped onto `region` by the transformation `tf`.
+///
+/// `SimpleTransform`s consist of rotations by multiples of 90 degrees, mirroring,
+/// magnification and translation. Hence the inverse of the linear part is its transpose
+/// divided by the squared magnification. If the magnification is not `1` the result is
+/// enlarged by one unit to compensate rounding.
+pub(crate) fn inverse_transform_rect<C: CoordinateType>(
+    tf: &SimpleTransform<C>,
+    region: &Rect<C>,
+) -> Rect<C> {
+    let (zero, one) = (C::zero(), C::one());
+    let d = tf.transform_point(Point::zero());
+    let ex = tf.transform_point(Point::new(one, zero)) - d;
+    let ey = tf.transform_point(Point::new(zero, one)) - d;
+    // Squared magnification.
+    let m2 = ex.x * ex.x + ex.y * ex.y;
+    let inverse = |p: Point<C>| {
+        let v = p - d;
+        Point::new(
+            (ex.x * v.x + ex.y * v.y) / m2,
+            (ey.x * v.x + ey.y * v.y) / m2,
+        )
+    };
+    let r = Rect::new(inverse(region.lower_left()), inverse(region.upper_right()));
+    if m2 == one {
+        r
+    } else {
+        let (ll, ur) = (r.lower_left(), r.upper_right());
+        Rect::new(
+            Point::new(ll.x - one, ll.y - one),
+            Point::new(ur.x + one, ur.y + one),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests_with_hierarchy {
+    use crate::flat_view::FlatView;
+    use crate::prelude::Chip;
+    use crate::prelude::*;
+
+    fn create_test_chip() -> Chip {
+        let mut chip = Chip::new();
+        let top1 = chip.create_cell("TOP1".into());
+        let top2 = chip.create_cell("TOP2".into());
+        let intermediate = chip.create_cell("INTERMEDIATE".into());
+        let leaf1 = chip.create_cell("LEAF1".into());
+        let leaf2 = chip.create_cell("LEAF2".into());
+
+        chip.create_cell_instance(&intermediate, &leaf1, Some("leaf1_inst1".into()));
+        chip.create_cell_instance(&intermediate, &leaf1, Some("leaf1_inst2".into()));
+        chip.create_cell_instance(&intermediate, &leaf2, Some("leaf2_inst1".into()));
+        chip.create_cell_instance(&intermediate, &leaf2, Some("leaf2_inst2".into()));
+
+        chip.create_cell_instance(&top1, &intermediate, Some("intermediate_inst1".into()));
+        chip.create_cell_instance(&top1, &intermediate, Some("intermediate_inst2".into()));
+
+        // Create instances in another cell with same names as in TOP1.
+        chip.create_cell_instance(&top2, &leaf1, Some("leaf1_inst1".into()));
+        chip.create_cell_instance(&top2, &leaf2, Some("leaf2_inst1".into()));
+        chip.create_cell_instance(&top2, &leaf2, Some("leaf2_inst2".into()));
+        chip
+    }
+
+    #[test]
+    fn test_num_cells() {
+        let chip = create_test_chip();
+        let flatview = FlatView::new(&chip);
+        assert_eq!(flatview.num_cells(), 4); // Two top cells, two leaf cells.
+    }
+
+    #[test]
+    fn test_access_top_cell() {
+        let chip = create_test_chip();
+
+        let flatview = FlatView::new(&chip);
+        let top1 = flatview.cell_by_name("TOP1").expect("Cell not found.");
+        assert_eq!(flatview.num_child_instances(&top1), 2 * 4);
+        assert_eq!(flatview.num_dependent_cells(&top1), 0);
+        assert_eq!(flatview.num_cell_dependencies(&top1), 2);
+        assert_eq!(flatview.each_cell_instance(&top1).count(), 8);
+    }
+
+    #[test]
+    fn test_find_template_cell() {
+        let chip = create_test_chip();
+        let flatview = FlatView::new(&chip);
+        let top1 = flatview.cell_by_name("TOP1").expect("Cell not found.");
+        let leaf1 = flatview.cell_by_name("LEAF1").expect("Cell not found.");
+
+        // Template
+        assert_eq!(
+            &flatview.template_cell(
+                &flatview
+                    .cell_instance_by_name(&top1, "intermediate_inst1/leaf1_inst1",)
+                    .unwrap()
+            ),
+            &leaf1
+        );
+    }
+
+    #[test]
+    fn test_find_instance_by_name() {
+        let chip = create_test_chip();
+        let flatview = FlatView::new(&chip);
+        let top1 = flatview.cell_by_name("TOP1").expect("Cell not found.");
+
+        // Find by name.
+        {
+            let names = vec![
+                "intermediate_inst1/leaf1_inst1",
+                "intermediate_inst2/leaf1_inst1",
+                "intermediate_inst2/leaf2_inst1",
+                "intermediate_inst2/leaf2_inst2",
+            ];
+            for name in names {
+                let inst = flatview
+                    .cell_instance_by_name(&top1, name)
+                    .expect("instance not found");
+                assert_eq!(flatview.cell_instance_name(&inst), Some(name.into()));
+
+                // Parent
+                assert_eq!(&flatview.parent_cell(&inst), &top1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_count_references() {
+        let chip = create_test_chip();
+        let flatview = FlatView::new(&chip);
+        let top1 = flatview.cell_by_name("TOP1").expect("Cell not found.");
+        let leaf1 = flatview.cell_by_name("LEAF1").expect("Cell not found.");
+        let leaf2 = flatview.cell_by_name("LEAF2").expect("Cell not found.");
+
+        // References.
+        assert_eq!(flatview.num_cell_references(&leaf1), 2 * 2 + 1);
+        assert_eq!(flatview.num_cell_references(&leaf2), 2 * 2 + 2);
+        assert_eq!(flatview.num_cell_references(&top1), 0);
+    }
+
+    #[test]
+    fn test_another_top_cell() {
+        // TOP2 contains instances with same name as in TOP1.
+        let chip = create_test_chip();
+        let flatview = FlatView::new(&chip);
+        let top2 = flatview.cell_by_name("TOP2").expect("Cell not found.");
+
+        assert_eq!(flatview.num_dependent_cells(&top2), 0);
+        assert_eq!(flatview.num_cell_dependencies(&top2), 2);
+        assert_eq!(flatview.each_cell_instance(&top2).count(), 3);
+    }
+}
+
+#[cfg(test)]
+mod tests_with_netlist {
+    use crate::flat_view::FlatView;
+    use crate::prelude::Chip;
+    use crate::prelude::*;
+
+    /// Create a netlist `TOP -> SUB -> LEAF`.
+    /// The input `A` of `TOP` is connected through `SUB` to the inputs of two `LEAF` instances.
+    fn create_test_netlist() -> Chip {
+        let mut chip = Chip::new();
+        let top = chip.create_cell("TOP".into());
+        let sub = chip.create_cell("SUB".into());
+        let leaf = chip.create_cell("LEAF".into());
+
+        let leaf_a = chip.create_pin(&leaf, "A".into(), Direction::Input);
+
+        let sub_a = chip.create_pin(&sub, "A".into(), Direction::Input);
+        let sub_net_a = chip.create_net(&sub, Some("A".into()));
+        chip.connect_pin(&sub_a, Some(sub_net_a));
+        // Internal net which is not connected to the outside.
+        let sub_net_b = chip.create_net(&sub, Some("B".into()));
+        for name in &["leaf1", "leaf2"] {
+            let inst = chip.create_cell_instance(&sub, &leaf, Some((*name).into()));
+            let pin_inst = chip.pin_instance(&inst, &leaf_a);
+            chip.connect_pin_instance(&pin_inst, Some(sub_net_a));
+        }
+        let leaf3 = chip.create_cell_instance(&sub, &leaf, Some("leaf3".into()));
+        let leaf3_a = chip.pin_instance(&leaf3, &leaf_a);
+        chip.connect_pin_instance(&leaf3_a, Some(sub_net_b));
+
+        let top_a = chip.create_pin(&top, "A".into(), Direction::Input);
+        let top_net_a = chip.create_net(&top, Some("netA".into()));
+        chip.connect_pin(&top_a, Some(top_net_a));
+        for name in &["sub1", "sub2"] {
+            let inst = chip.create_cell_instance(&top, &sub, Some((*name).into()));
+            let pin_inst = chip.pin_instance(&inst, &sub_a);
+            chip.connect_pin_instance(&pin_inst, Some(top_net_a));
+        }
+
+        chip
+    }
+
+    #[test]
+    fn test_net_by_name() {
+        let chip = create_test_netlist();
+        let flatview = FlatView::new(&chip);
+        let top = flatview.cell_by_name("TOP").expect("Cell not found.");
+        let net_a = flatview.net_by_name(&top, "netA").unwrap();
+
+        // Segments of the same net resolve to the highest-level segment.
+        assert_eq!(flatview.net_by_name(&top, "sub1/A"), Some(net_a.clone()));
+        assert_eq!(flatview.net_by_name(&top, "sub2/A"), Some(net_a.clone()));
+        assert_eq!(flatview.net_name(&net_a), Some("netA".into()));
+
+        let net_b = flatview.net_by_name(&top, "sub1/B").unwrap();
+        assert_ne!(net_b, net_a);
+        assert_eq!(flatview.net_name(&net_b), Some("sub1/B".into()));
+        assert_eq!(flatview.parent_cell_of_net(&net_b), top);
+        assert!(flatview.net_exists(&net_b));
+    }
+
+    #[test]
+    fn test_flat_net_connectivity() {
+        let chip = create_test_netlist();
+        let flatview = FlatView::new(&chip);
+        let top = flatview.cell_by_name("TOP").expect("Cell not found.");
+        let net_a = flatview.net_by_name(&top, "netA").unwrap();
+
+        // One top-level net, two instances of the unconnected net `B` and
+        // the constant nets of TOP and both instances of SUB.
+        assert_eq!(flatview.num_internal_nets(&top), 1 + 2 + 3 * 2);
+
+        assert_eq!(flatview.num_net_pins(&net_a), 1);
+        assert_eq!(flatview.num_net_pin_instances(&net_a), 2 * 2);
+        for pin_inst in flatview.each_pin_instance_of_net(&net_a) {
+            assert_eq!(flatview.net_of_pin_instance(&pin_inst), Some(net_a.clone()));
+            assert_eq!(
+                flatview.parent_cell(&flatview.parent_of_pin_instance(&pin_inst)),
+                top
+            );
+        }
+
+        let leaf3 = flatview.cell_instance_by_name(&top, "sub2/leaf3").unwrap();
+        let leaf_a = flatview.each_pin_instance(&leaf3).next().unwrap();
+        let net_b = flatview.net_of_pin_instance(&leaf_a).unwrap();
+        assert_eq!(flatview.net_name(&net_b), Some("sub2/B".into()));
+        assert_eq!(flatview.each_pin_instance_of_net_vec(&net_b), vec![leaf_a]);
+    }
+
+    #[test]
+    fn test_flat_array_nets() {
+        let mut chip = create_test_netlist();
+        let top = chip.cell_by_name("TOP").unwrap();
+        let sub1 = chip.cell_instance_by_name(&top, "sub1").unwrap();
+        let array = InstanceArray::new(2, 1, Vector::new(100, 0), Vector::new(0, 0));
+        chip.set_cell_instance_array(&sub1, Some(array));
+
+        let flatview = FlatView::new(&chip);
+        let net_a = flatview.net_by_name(&top, "netA").unwrap();
+        // All array elements are connected to the same net of the parent.
+        assert_eq!(flatview.num_net_pin_instances(&net_a), 3 * 2);
+        // Each array element has its own internal nets.
+        assert_eq!(flatview.num_internal_nets(&top), 1 + 3 + 4 * 2);
+        let net_b0 = flatview.net_by_name(&top, "sub1[0,0]/B").unwrap();
+        let net_b1 = flatview.net_by_name(&top, "sub1[1,0]/B").unwrap();
+        assert_ne!(net_b0, net_b1);
+        assert_eq!(flatview.net_name(&net_b1), Some("sub1[1,0]/B".into()));
+        assert_eq!(flatview.net_by_name(&top, "sub1/B"), None);
+    }
+}
+
+#[cfg(test)]
+mod tests_with_layout {
+    use crate::flat_view::FlatView;
+    use crate::prelude::*;
+    use crate::region_search::RegionSearchAdapter;
+
+    /// Create a layout `TOP -> SUB -> LEAF` where each cell contains one shape.
+    fn create_test_layout() -> Chip {
+        let mut chip = Chip::new();
+        let layer = chip.create_layer(1, 0);
+        let top = chip.create_cell("TOP".into());
+        let sub = chip.create_cell("SUB".into());
+        let leaf = chip.create_cell("LEAF".into());
+
+        chip.insert_shape(&top, &layer, Rect::new((0, 0), (1, 1)).into());
+        chip.insert_shape(&sub, &layer, Rect::new((0, 0), (10, 10)).into());
+        chip.insert_shape(&leaf, &layer, Rect::new((0, 0), (5, 5)).into());
+
+        let leaf_inst = chip.create_cell_instance(&sub, &leaf, Some("leaf".into()));
+        chip.set_transform(&leaf_inst, SimpleTransform::translate((20, 0)));
+        let sub_inst = chip.create_cell_instance(&top, &sub, Some("sub".into()));
+        chip.set_transform(&sub_inst, SimpleTransform::translate((100, 0)));
+
+        chip
+    }
+
+    #[test]
+    fn test_flat_shapes() {
+        let chip = create_test_layout();
+        let flatview = FlatView::new(&chip);
+        let top = flatview.cell_by_name("TOP").unwrap();
+        let layer = flatview.find_layer(1, 0).unwrap();
+
+        // Shapes of the leaf cell are not part of the flattened cell.
+        let shapes = flatview.each_shape_id(&top, &layer).collect::<Vec<_>>();
+        assert_eq!(shapes.len(), 2);
+
+        let sub_shape = shapes.iter().find(|(path, _)| !path.is_empty()).unwrap();
+        assert!(flatview.shape_exists(sub_shape));
+        assert_eq!(flatview.parent_of_shape(sub_shape), (top, layer));
+        assert_eq!(
+            flatview.shape_geometry(sub_shape),
+            Rect::new((100, 0), (110, 10)).into()
+        );
+
+        let leaf_inst = flatview.cell_instance_by_name(&top, "sub/leaf").unwrap();
+        assert_eq!(
+            flatview
+                .get_transform(&leaf_inst)
+                .transform_point(Point::zero()),
+            Point::new(120, 0)
+        );
+    }
+
+    #[test]
+    fn test_flat_leaf_array() {
+        let mut chip = create_test_layout();
+        let sub = chip.cell_by_name("SUB").unwrap();
+        let sub_inst = chip.each_cell_instance_vec(&chip.cell_by_name("TOP").unwrap())[0];
+        let leaf_inst = chip.each_cell_instance_vec(&sub)[0];
+        chip.set_transform(
+            &sub_inst,
+            SimpleTransform::new(false, Angle::R90, 1, Vector::new(100, 0)),
+        );
+        let array = InstanceArray::new(2, 1, Vector::new(10, 0), Vector::new(0, 0));
+        chip.set_cell_instance_array(&leaf_inst, Some(array));
+
+        let flatview = FlatView::new(&chip);
+        let top = flatview.cell_by_name("TOP").unwrap();
+        assert_eq!(flatview.num_child_instances(&top), 2);
+        // The array itself cannot be addressed, only its elements.
+        assert_eq!(flatview.cell_instance_by_name(&top, "sub/leaf"), None);
+        assert_eq!(flatview.cell_instance_by_name(&top, "sub/leaf[2,0]"), None);
+
+        // The array pitch is rotated together with the flattened parent.
+        for (name, location) in [("sub/leaf[0,0]", (100, 20)), ("sub/leaf[1,0]", (100, 30))] {
+            let inst = flatview.cell_instance_by_name(&top, name).unwrap();
+            assert!(flatview.cell_instance_exists(&inst));
+            assert_eq!(flatview.cell_instance_name(&inst), Some(name.into()));
+            assert_eq!(flatview.cell_instance_array(&inst), None);
+            assert_eq!(
+                flatview.get_transform(&inst).transform_point(Point::zero()),
+                Point::from(location)
+            );
+        }
+    }
+
+    #[test]
+    fn test_flat_array_of_flattened_cell() {
+        let mut chip = create_test_layout();
+        let top = chip.cell_by_name("TOP").unwrap();
+        let sub_inst = chip.each_cell_instance_vec(&top)[0];
+        let array = InstanceArray::new(2, 1, Vector::new(50, 0), Vector::new(0, 0));
+        chip.set_cell_instance_array(&sub_inst, Some(array));
+
+        {
+            let flatview = FlatView::new(&chip);
+            let layer = flatview.find_layer(1, 0).unwrap();
+
+            // Each array element is flattened with its own shapes.
+            let mut geometries: Vec<_> = flatview
+                .each_shape_id(&top, &layer)
+                .filter(|(path, _)| !path.is_empty())
+                .map(|shape| {
+                    assert!(flatview.shape_exists(&shape));
+                    flatview.shape_geometry(&shape).try_bounding_box().unwrap()
+                })
+                .collect();
+            geometries.sort_by_key(|r| r.lower_left().x);
+            assert_eq!(
+                geometries,
+                vec![
+                    Rect::new((100, 0), (110, 10)),
+                    Rect::new((150, 0), (160, 10))
+                ]
+            );
+
+            // ... and its own leaf instances.
+            assert_eq!(flatview.num_child_instances(&top), 2);
+            assert_eq!(flatview.each_cell_instance(&top).count(), 2);
+            let leaf = flatview.cell_by_name("LEAF").unwrap();
+            assert_eq!(flatview.num_cell_references(&leaf), 2);
+            let inst = flatview
+                .cell_instance_by_name(&top, "sub[1,0]/leaf")
+                .unwrap();
+            assert_eq!(
+                flatview.get_transform(&inst).transform_point(Point::zero()),
+                Point::new(170, 0)
+            );
+        }
+
+        let region_search = RegionSearchAdapter::new(&mut chip);
+        let flatview = FlatView::new(&region_search);
+        let layer = flatview.find_layer(1, 0).unwrap();
+        let shapes: Vec<_> = flatview
+            .each_shape_in_region_per_layer(&top, &layer, &Rect::new((155, 5), (156, 6)))
+            .collect();
+        assert_eq!(shapes.len(), 1);
+        assert_eq!(
+            flatview.shape_geometry(&shapes[0]),
+            Rect::new((150, 0), (160, 10)).into()
+        );
+        let instances: Vec<_> = flatview
+            .each_cell_instance_in_region(&top, &Rect::new((171, 1), (172, 2)))
+            .collect();
+        assert_eq!(instances.len(), 1);
+        assert_eq!(
+            flatview.cell_instance_name(&instances[0]),
+            Some("sub[1,0]/leaf".into())
+        );
+    }
+
+    #[test]
+    fn test_flat_region_search() {
+        let mut chip = create_test_layout();
+        let region_search = RegionSearchAdapter::new(&mut chip);
+        let flatview = FlatView::new(&region_search);
+        let top = flatview.cell_by_name("TOP").unwrap();
+        let layer = flatview.find_layer(1, 0).unwrap();
+
+        let num_shapes = |region: Rect<_>| {
+            flatview
+                .each_shape_in_region_per_layer(&top, &layer, &region)
+                .count()
+        };
+        assert_eq!(num_shapes(Rect::new((105, 5), (106, 6))), 1);
+        assert_eq!(num_shapes(Rect::new((0, 0), (200, 10))), 2);
+        assert_eq!(num_shapes(Rect::new((200, 200), (300, 300))), 0);
+
+        let instances: Vec<_> = flatview
+            .each_cell_instance_in_region(&top, &Rect::new((121, 1), (122, 2)))
+            .collect();
+        assert_eq!(instances.len(), 1);
+        assert_eq!(
+            flatview.cell_instance_name(&instances[0]),
+            Some("sub/leaf".into())
+        );
+    }
+}
@@ -121,6 +121,63 @@ pub trait HierarchyBase {
     /// Get the ID of the template cell of this instance.
     fn template_cell(&self, cell_instance: &Self::CellInstId) -> Self::CellId;
 
+    /// Check if the cell ID is valid, i.e. the cell exists.
+    fn cell_exists(&self, cell: &Self::CellId) -> bool {
+        // Inefficient default implementation.
+        self.each_cell().any(|c| &c == cell)
+    }
+
+    /// Check if the cell instance ID is valid, i.e. the cell instance exists.
+    fn cell_instance_exists(&self, cell_inst: &Self::CellInstId) -> bool {
+        // Inefficient default implementation.
+        self.each_cell()
+            .any(|c| self.each_cell_instance(&c).any(|i| &i == cell_inst))
+    }
+
+    /// Get the name of the cell.
+    /// Returns `None` if the cell does not exist.
+    fn try_cell_name(&self, cell: &Self::CellId) -> Option<Self::NameType> {
+        if self.cell_exists(cell) {
+            Some(self.cell_name(cell))
+        } else {
+            None
+        }
+    }
+
+    /// Get the name of the cell instance.
+    /// Returns `None` if the cell instance does not exist and `Some(None)` if the
+    /// cell instance exists but has no name.
+    fn try_cell_instance_name(
+        &self,
+        cell_inst: &Self::CellInstId,
+    ) -> Option<Option<Self::NameType>> {
+        if self.cell_instance_exists(cell_inst) {
+            Some(self.cell_instance_name(cell_inst))
+        } else {
+            None
+        }
+    }
+
+    /// Get the ID of the parent cell of this instance.
+    /// Returns `None` if the cell instance does not exist.
+    fn try_parent_cell(&self, cell_instance: &Self::CellInstId) -> Option<Self::CellId> {
+        if self.cell_instance_exists(cell_instance) {
+            Some(self.parent_cell(cell_instance))
+        } else {
+            None
+        }
+    }
+
+    /// Get the ID of the template cell of this instance.
+    /// Returns `None` if the cell instance does not exist.
+    fn try_template_cell(&self, cell_instance: &Self::CellInstId) -> Option<Self::CellId> {
+        if self.cell_instance_exists(cell_instance) {
+            Some(self.template_cell(cell_instance))
+        } else {
+            None
+        }
+    }
+
     /// Call a function on each cell of the netlist.
     fn for_each_cell<F>(&self, f: F)
     where
@@ -245,6 +302,15 @@ pub trait HierarchyBase {
         counter
     }
 
+    /// Get the number of columns and rows of a cell instance.
+    /// A cell instance which is not an array has one column and one row.
+    ///
+    /// Layouts which support arrays of cell instances must override this consistently
+    /// with [`LayoutBase::cell_instance_array`](crate::traits::LayoutBase::cell_instance_array).
+    fn cell_instance_array_size(&self, cell_inst: &Self::CellInstId) -> (u32, u32) {
+        (1, 1)
+    }
+
     /// Get the number of cell instances inside the `cell`.
     fn num_child_instances(&self, cell: &Self::CellId) -> usize;
 
@@ -273,6 +339,27 @@ pub trait HierarchyBase {
     ) -> Option<PropertyValue> {
         None
     }
+
+    /// Call a function for each property of the top-level chip data structure.
+    fn for_each_chip_property<F>(&self, f: F)
+    where
+        F: FnMut(&Self::NameType, &PropertyValue) -> (),
+    {
+    }
+
+    /// Call a function for each property of a cell.
+    fn for_each_cell_property<F>(&self, cell: &Self::CellId, f: F)
+    where
+        F: FnMut(&Self::NameType, &PropertyValue) -> (),
+    {
+    }
+
+    /// Call a function for each property of a cell instance.
+    fn for_each_cell_instance_property<F>(&self, inst: &Self::CellInstId, f: F)
+    where
+        F: FnMut(&Self::NameType, &PropertyValue) -> (),
+    {
+    }
 }
 
 /// Additional requirement that all ID types are `Send + Sync` as needed for multithreading
@@ -398,4 +485,13 @@ pub trait HierarchyEdit: HierarchyBase {
         value: PropertyValue,
     ) {
     }
+
+    /// Remove a property of the top-level chip data structure.
+    fn remove_chip_property(&mut self, key: &Self::NameType) {}
+
+    /// Remove a property of a cell.
+    fn remove_cell_property(&mut self, cell: &Self::CellId, key: &Self::NameType) {}
+
+    /// Remove a property of a cell instance.
+    fn remove_cell_instance_property(&mut self, inst: &Self::CellInstId, key: &Self::NameType) {}
 }
@@ -33,6 +33,25 @@ pub trait HierarchyUtil: HierarchyBase {
         Box::new(self.each_cell().filter(move |c| self.is_leaf_cell(c)))
     }
 
+    /// Check if the `cell` is the same as `other` or contains instances of `other`, directly or
+    /// through other cells.
+    ///
+    /// An instance of `cell` must not be created inside `other` if this is true, because this
+    /// would make the hierarchy recursive.
+    fn cell_depends_on(&self, cell: &Self::CellId, other: &Self::CellId) -> bool {
+        let mut visited: FnvHashSet<_> = Default::default();
+        let mut stack = vec![cell.clone()];
+        while let Some(c) = stack.pop() {
+            if &c == other {
+                return true;
+            }
+            if visited.insert(c.clone()) {
+                stack.extend(self.each_cell_dependency(&c));
+            }
+        }
+        false
+    }
+
     /// Iterate over topologically sorted cells (from leaf-cells to top-cells).
     fn each_cell_bottom_to_top(&self) -> Box<dyn Iterator<Item = Self::CellId> + '_> {
         let mut unsorted_cells: Vec<_> = self.each_cell_vec();
@@ -70,6 +89,9 @@ impl<N: HierarchyBase> HierarchyUtil for N {}
 
 /// Modifying utility functions for the cell hierarchy..
 /// Import the this trait to use the utility functions all types that implement the `HierarchyEdit` trait.
+///
+/// Functions which need to copy the content of cells, such as the uniquification of cell instances,
+/// are found in [`L2NEditUtil`](crate::l2n::util::L2NEditUtil).
 pub trait HierarchyEditUtil: HierarchyEdit {
     /// Remove all child instances inside the `cell`.
     fn clear_cell_instances(&mut self, cell: &Self::CellId) {
@@ -0,0 +1,2182 @@
+// SPDX-FileCopyrightText: 2022 Thomas Kramer
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Human readable JSON format for exchanging designs with scripts and other tools.
+//!
+//! The JSON format contains the same information as the [`snapshot`](super::snapshot) format:
+//! the cell hierarchy, the netlist including buses and power domains, the layout, the links between layout shapes
+//! and nets/pins and all user defined properties. It is much larger and slower than a snapshot but
+//! can be produced and consumed by any language which has a JSON library.
+//!
+//! # Schema
+//!
+//! A document is a single JSON object:
+//!
+//! ```json
+//! {
+//!   "format": "libreda-db",
+//!   "version": 1,
+//!   "properties": {"design": {"string": "top"}},
+//!   "dbu": 1000,
+//!   "layers": [{"index": 1, "datatype": 0, "name": "metal1"}],
+//!   "power_domains": [
+//!     {"name": "CORE", "cell": "TOP", "supply_net": 1, "ground_net": 2, "voltage": 0.9, "always_on": false}
+//!   ],
+//!   "cells": [
+//!     {
+//!       "name": "INV",
+//!       "properties": {},
+//!       "pins": [{"name": "A", "direction": "input", "net": 0}],
+//!       "nets": [{"name": "a"}, {"name": null, "constant": 0}],
+//!       "instances": [],
+//!       "shapes": [
+//!         {
+//!           "layer": 0,
+//!           "geometry": {"type": "rect", "lower_left": [0, 0], "upper_right": [10, 10]},
+//!           "properties": {},
+//!           "net": 0,
+//!           "pin": "A"
+//!         }
+//!       ]
+//!     },
+//!     {
+//!       "name": "TOP",
+//!       "properties": {},
+//!       "power_domain": "CORE",
+//!       "pins": [],
+//!       "nets": [{"name": "in"}, {"name": "VDD"}, {"name": "VSS"}],
+//!       "instances": [
+//!         {
+//!           "name": "u1",
+//!           "template": "INV",
+//!           "properties": {},
+//!           "connections": {"A": 0},
+//!           "transform": {"mirror": false, "rotation": 90, "magnification": 1, "displacement": [100, 0]}
+//!         }
+//!       ]
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! Only `version` and the `name` of cells are required. Missing lists and objects are treated as empty.
+//! Cells may be listed in any order. The `template` of an instance may also name a cell which
+//! already exists in the data base the document is read into.
+//!
+//! * Nets are referenced by their index in the `nets` list of the cell. Nets without name have `"name": null`.
+//!   The constant nets of a cell are marked with `"constant": 0` and `"constant": 1` respectively.
+//! * Pins are referenced by their name. The `direction` is one of `none`, `input`, `output`,
+//!   `inout`, `clock`, `supply` and `ground`.
+//! * Cells with buses have a `buses` entry with the lists `pins` and `nets`. A bus is written as
+//!   `{"name": "data", "bit_order": "descending", "bits": [...]}` where `bit_order` is `descending`
+//!   or `ascending` and `bits` are the pin names or net indices ordered by bit index.
+//! * `power_domains` lists all power domains. The supply and ground nets of a domain are
+//!   referenced by their index in the `nets` list of the `cell` of the domain. Cells and instances
+//!   which are assigned to a domain have a `power_domain` entry with the name of the domain.
+//! * `connections` maps the names of the pins of the template cell to nets of the parent cell.
+//! * The `transform` of an instance is `rotation` (0, 90, 180 or 270 degrees counter-clockwise) and
+//!   `magnification` applied after an optional `mirror` at the x-axis followed by the `displacement`.
+//!   Missing entries default to the identity transform.
+//! * Arrayed instances have an additional `array` entry:
+//!   `{"columns": 4, "rows": 2, "column_pitch": [10, 0], "row_pitch": [0, 20]}`.
+//!   The element in column `c` and row `r` is displaced by `c * column_pitch + r * row_pitch`.
+//! * Instances which are not `placed` have a `placement_status` entry which is one of `unplaced`,
+//!   `placed`, `firm`, `fixed` and `cover`.
+//! * The `layer` of a shape is the index in the top-level `layers` list.
+//! * Geometries are distinguished by their `type`:
+//!   * `{"type": "point", "location": [x, y]}`
+//!   * `{"type": "edge", "start": [x, y], "end": [x, y]}`
+//!   * `{"type": "rect", "lower_left": [x, y], "upper_right": [x, y]}`
+//!   * `{"type": "polygon", "points": [[x, y], ...]}` with optional `"holes": [[[x, y], ...], ...]`
+//!   * `{"type": "path", "points": [[x, y], ...], "width": w, "end_type": "flat"}` where `end_type`
+//!     is `flat`, `round` or `extended`. Extended paths also have `"extension": [begin, end]`.
+//!   * `{"type": "text", "text": "...", "location": [x, y]}`
+//! * Property values are objects with a single entry which defines the type:
+//!   `{"string": "..."}`, `{"bytes": [0, 255]}`, `{"sint": -1}`, `{"uint": 1}` or `{"float": 1.5}`.
+//!   Non-finite floats are written as the strings `"NaN"`, `"inf"` and `"-inf"`.
+//!
+//! The netlist writer omits the layout entries (`dbu`, `layers`, `transform`, `array`,
+//! `placement_status`, `shapes`) and the
+//! layout writer omits the netlist entries (`pins`, `nets`, `buses`, `power_domains`, `power_domain`,
+//! `connections`). Readers ignore the entries they do not need, hence a complete document can be read
+//! as a netlist or as a layout.
+//! Buses and power domains are only written and read by [`JsonWriter::write_json()`] and
+//! [`JsonReader::read_json()`] because the netlist reader and writer work with any netlist, also
+//! without support for buses or power domains.
+//!
+//! # Example
+//!
+//! ```
+//! use libreda_db::prelude::*;
+//! use libreda_db::l2n::io::json::{JsonReader, JsonWriter};
+//!
+//! let mut chip = Chip::new();
+//! let top = chip.create_cell("TOP".into());
+//! let layer = chip.create_layer(1, 0);
+//! chip.insert_shape(&top, &layer, Rect::new((0, 0), (10, 10)).into());
+//!
+//! let mut buffer = Vec::new();
+//! JsonWriter::new().write_json(&mut buffer, &chip).unwrap();
+//!
+//! let restored: Chip = JsonReader::new().read_json(&mut buffer.as_slice()).unwrap();
+//! let top = restored.cell_by_name("TOP").unwrap();
+//! assert_eq!(restored.bounding_box(&top), Some(Rect::new((0, 0), (10, 10))));
+//! ```
+
+use crate::layout::io::{LayoutStreamReader, LayoutStreamWriter};
+use crate::netlist::io::{NetlistReader, NetlistWriter};
+use crate::prelude::*;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::hash::Hash;
+use std::io::{Read, Write};
+
+/// Value of the `format` entry of every document.
+const FORMAT_NAME: &str = "libreda-db";
+/// Version of the schema. Changes when the schema becomes incompatible.
+pub const FORMAT_VERSION: i64 = 1;
+
+/// Error type used for reading and writing JSON documents.
+#[derive(Debug)]
+pub enum JsonError {
+    /// Error of the underlying byte stream.
+    Io(std::io::Error),
+    /// The data is not valid JSON.
+    Syntax {
+        /// Line number where the error was detected.
+        line: usize,
+        /// Description of the error.
+        message: String,
+    },
+    /// The document is valid JSON but does not follow the schema.
+    Malformed(String),
+    /// The document was written with an incompatible version of the schema.
+    UnsupportedVersion(i64),
+    /// A cell with this name already exists in the data base.
+    CellNameCollision(String),
+    /// A power domain with this name already exists in the data base.
+    PowerDomainNameCollision(String),
+    /// The geometry type cannot be represented in the JSON format.
+    UnsupportedGeometry,
+}
+
+impl std::fmt::Display for JsonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JsonError::Io(err) => write!(f, "IO error: {}", err),
+            JsonError::Syntax { line, message } => {
+                write!(f, "JSON syntax error on line {}: {}", line, message)
+            }
+            JsonError::Malformed(msg) => write!(f, "Malformed document: {}", msg),
+            JsonError::UnsupportedVersion(version) => write!(
+                f,
+                "Unsupported document version {} (supported: {}).",
+                version, FORMAT_VERSION
+            ),
+            JsonError::CellNameCollision(name) => write!(f, "Cell '{}' already exists.", name),
+            JsonError::PowerDomainNameCollision(name) => {
+                write!(f, "Power domain '{}' already exists.", name)
+            }
+            JsonError::UnsupportedGeometry => {
+                write!(f, "Geometry type is not supported by the JSON format.")
+            }
+        }
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+impl From<std::io::Error> for JsonError {
+    fn from(err: std::io::Error) -> Self {
+        JsonError::Io(err)
+    }
+}
+
+fn malformed(message: impl Into<String>) -> JsonError {
+    JsonError::Malformed(message.into())
+}
+
+/// Write data bases as JSON documents.
+#[derive(Debug, Clone)]
+pub struct JsonWriter {
+    pretty: bool,
+}
+
+impl Default for JsonWriter {
+    fn default() -> Self {
+        Self { pretty: true }
+    }
+}
+
+impl JsonWriter {
+    /// Create a default JSON writer which indents the output.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enable or disable indentation. Without indentation the whole document is written on a single line.
+    pub fn pretty(mut self, pretty: bool) -> Self {
+        self.pretty = pretty;
+        self
+    }
+
+    /// Write the cell hierarchy, the netlist including buses and power domains, the layout,
+    /// the links between layout and netlist and all properties of `chip`. IDs are not stored.
+    pub fn write_json<W: Write, LN: L2NBase<Coord = i32> + BusBase + PowerBase>(
+        &self,
+        writer: &mut W,
+        chip: &LN,
+    ) -> Result<(), JsonError> {
+        let table = CellTable::new(chip);
+        let mut doc = encode_hierarchy(chip, &table);
+        encode_netlist(chip, &table, &mut doc);
+        encode_buses(chip, &table, &mut doc);
+        encode_layout(chip, &table, &mut doc)?;
+        encode_power(chip, &table, &mut doc);
+        encode_l2n(chip, &table, &mut doc);
+        self.write_document(writer, doc)
+    }
+
+    fn write_document<W: Write>(&self, writer: &mut W, doc: Document) -> Result<(), JsonError> {
+        let mut out = String::new();
+        doc.into_json().write(&mut out, self.pretty.then_some(0));
+        out.push('\n');
+        writer.write_all(out.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// Write only the hierarchy and the netlist.
+impl NetlistWriter for JsonWriter {
+    type Error = JsonError;
+
+    fn write_netlist<W: Write, N: NetlistBase>(
+        &self,
+        writer: &mut W,
+        netlist: &N,
+    ) -> Result<(), Self::Error> {
+        let table = CellTable::new(netlist);
+        let mut doc = encode_hierarchy(netlist, &table);
+        encode_netlist(netlist, &table, &mut doc);
+        self.write_document(writer, doc)
+    }
+}
+
+/// Write only the hierarchy and the layout.
+impl LayoutStreamWriter for JsonWriter {
+    type Error = JsonError;
+
+    fn write_layout<W: Write, L: LayoutBase<Coord = i32>>(
+        &self,
+        writer: &mut W,
+        layout: &L,
+    ) -> Result<(), Self::Error> {
+        let table = CellTable::new(layout);
+        let mut doc = encode_hierarchy(layout, &table);
+        encode_layout(layout, &table, &mut doc)?;
+        self.write_document(writer, doc)
+    }
+}
+
+/// Read data bases from JSON documents.
+#[derive(Debug, Clone, Default)]
+pub struct JsonReader {}
+
+impl JsonReader {
+    /// Create a default JSON reader.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read a document into a new data base.
+    pub fn read_json<R: Read, LN: L2NEdit<Coord = i32> + BusEdit + PowerEdit>(
+        &self,
+        reader: &mut R,
+    ) -> Result<LN, JsonError> {
+        let mut chip = LN::new();
+        self.read_json_into(reader, &mut chip)?;
+        Ok(chip)
+    }
+
+    /// Read a document and add its content to `chip`.
+    /// Cell names of the document must not exist yet in `chip`.
+    pub fn read_json_into<R: Read, LN: L2NEdit<Coord = i32> + BusEdit + PowerEdit>(
+        &self,
+        reader: &mut R,
+        chip: &mut LN,
+    ) -> Result<(), JsonError> {
+        let doc = read_document(reader)?;
+        let table = decode_hierarchy(&doc, chip)?;
+        let nets = decode_netlist(&doc, chip, &table)?;
+        decode_buses(&doc, chip, &table, &nets)?;
+        decode_power(&doc, chip, &table, &nets)?;
+        let shapes = decode_layout(&doc, chip, &table)?;
+        decode_l2n(&doc, chip, &table, &nets, &shapes)
+    }
+}
+
+/// Read the hierarchy and the netlist. Layout entries are ignored.
+impl NetlistReader for JsonReader {
+    type Error = JsonError;
+
+    fn read_into_netlist<R: Read, N: NetlistEdit>(
+        &self,
+        reader: &mut R,
+        netlist: &mut N,
+    ) -> Result<(), Self::Error> {
+        let doc = read_document(reader)?;
+        let table = decode_hierarchy(&doc, netlist)?;
+        decode_netlist(&doc, netlist, &table)?;
+        Ok(())
+    }
+}
+
+/// Read the hierarchy and the layout. Netlist entries are ignored.
+impl LayoutStreamReader for JsonReader {
+    type Error = JsonError;
+
+    fn read_layout<R: Read, L: LayoutEdit<Coord = i32>>(
+        &self,
+        reader: &mut R,
+        layout: &mut L,
+    ) -> Result<(), Self::Error> {
+        let doc = read_document(reader)?;
+        let table = decode_hierarchy(&doc, layout)?;
+        decode_layout(&doc, layout, &table)?;
+        Ok(())
+    }
+}
+
+/// Cells and their instances in the order in which they appear in the document.
+struct CellTable<H: HierarchyBase> {
+    cells: Vec<H::CellId>,
+    /// Instances grouped by parent cell.
+    instances: Vec<Vec<H::CellInstId>>,
+}
+
+impl<H: HierarchyBase> CellTable<H> {
+    /// Define the order of cells and instances for writing.
+    fn new(chip: &H) -> Self {
+        let cells = chip.each_cell_vec();
+        let instances = cells
+            .iter()
+            .map(|cell| chip.each_cell_instance_vec(cell))
+            .collect();
+        Self { cells, instances }
+    }
+}
+
+// JSON values.
+
+/// Parsed JSON value. Objects keep the order of their entries.
+#[derive(Debug, Clone, PartialEq)]
+enum Json {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+fn entry(key: impl Into<String>, value: Json) -> (String, Json) {
+    (key.into(), value)
+}
+
+impl Json {
+    fn opt_string<S: Into<String>>(s: Option<S>) -> Json {
+        s.map(|s| Json::String(s.into())).unwrap_or(Json::Null)
+    }
+
+    /// Check if the value can be written on a single line without hurting readability.
+    /// This is the case if it does not contain nested objects.
+    fn is_flat(&self) -> bool {
+        let is_flat_child = |v: &Json| !matches!(v, Json::Object(_)) && v.is_flat();
+        match self {
+            Json::Array(items) => items.iter().all(is_flat_child),
+            Json::Object(entries) => entries.iter().all(|(_, v)| is_flat_child(v)),
+            _ => true,
+        }
+    }
+
+    /// Serialize the value. With an indentation level nested values are written on separate lines.
+    fn write(&self, out: &mut String, indent: Option<usize>) {
+        let pretty = indent.is_some();
+        let broken = indent.filter(|_| !self.is_flat());
+        let inner = indent.map(|i| i + 1);
+        let separator = |out: &mut String, i: usize| {
+            if i > 0 {
+                out.push(',');
+                if pretty && broken.is_none() {
+                    out.push(' ');
+                }
+            }
+            newline(out, broken.map(|i| i + 1));
+        };
+        match self {
+            Json::Null => out.push_str("null"),
+            Json::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+            Json::Int(v) => out.push_str(&v.to_string()),
+            Json::Float(v) => out.push_str(&format!("{:?}", v)),
+            Json::String(s) => write_string(out, s),
+            Json::Array(items) => {
+                out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    separator(out, i);
+                    item.write(out, inner);
+                }
+                if !items.is_empty() {
+                    newline(out, broken);
+                }
+                out.push(']');
+            }
+            Json::Object(entries) => {
+                out.push('{');
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    separator(out, i);
+                    write_string(out, key);
+                    out.push_str(if pretty { ": " } else { ":" });
+                    value.write(out, inner);
+                }
+                if !entries.is_empty() {
+                    newline(out, broken);
+                }
+                out.push('}');
+            }
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn field(&self, key: &str) -> Result<&Json, JsonError> {
+        self.get(key)
+            .ok_or_else(|| malformed(format!("missing entry '{}'", key)))
+    }
+
+    /// Get a list which may be omitted when it is empty.
+    fn items(&self, key: &str) -> Result<&[Json], JsonError> {
+        self.get(key).map(Json::as_array).unwrap_or(Ok(&[]))
+    }
+
+    /// Get a string which may be omitted or `null`.
+    fn opt_str(&self, key: &str) -> Result<Option<&str>, JsonError> {
+        match self.get(key) {
+            None | Some(Json::Null) => Ok(None),
+            Some(v) => v.as_str().map(Some),
+        }
+    }
+
+    fn as_str(&self) -> Result<&str, JsonError> {
+        match self {
+            Json::String(s) => Ok(s),
+            _ => Err(malformed("expected a string")),
+        }
+    }
+
+    fn as_bool(&self) -> Result<bool, JsonError> {
+        match self {
+            Json::Bool(b) => Ok(*b),
+            _ => Err(malformed("expected a boolean")),
+        }
+    }
+
+    fn as_i64(&self) -> Result<i64, JsonError> {
+        match self {
+            Json::Int(v) => Ok(*v),
+            _ => Err(malformed("expected an integer")),
+        }
+    }
+
+    fn as_i32(&self) -> Result<i32, JsonError> {
+        i32::try_from(self.as_i64()?).map_err(|_| malformed("integer out of range"))
+    }
+
+    fn as_u32(&self) -> Result<u32, JsonError> {
+        u32::try_from(self.as_i64()?).map_err(|_| malformed("integer out of range"))
+    }
+
+    fn as_f64(&self) -> Result<f64, JsonError> {
+        match self {
+            Json::Int(v) => Ok(*v as f64),
+            Json::Float(v) => Ok(*v),
+            Json::String(s) => s
+                .parse()
+                .map_err(|_| malformed(format!("invalid float '{}'", s))),
+            _ => Err(malformed("expected a number")),
+        }
+    }
+
+    fn as_array(&self) -> Result<&[Json], JsonError> {
+        match self {
+            Json::Array(items) => Ok(items),
+            _ => Err(malformed("expected a list")),
+        }
+    }
+
+    fn entries(&self) -> Result<&[(String, Json)], JsonError> {
+        match self {
+            Json::Object(entries) => Ok(entries),
+            _ => Err(malformed("expected an object")),
+        }
+    }
+}
+
+fn newline(out: &mut String, indent: Option<usize>) {
+    if let Some(indent) = indent {
+        out.push('\n');
+        (0..indent).for_each(|_| out.push_str("  "));
+    }
+}
+
+fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Recursive descent parser for JSON values.
+struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+    line: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(data: &'a str) -> Self {
+        Self {
+            data: data.as_bytes(),
+            pos: 0,
+            line: 1,
+        }
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, JsonError> {
+        Err(JsonError::Syntax {
+            line: self.line,
+            message: message.into(),
+        })
+    }
+
+    /// Parse a complete document which consists of exactly one value.
+    fn document(&mut self) -> Result<Json, JsonError> {
+        let value = self.value()?;
+        match self.peek() {
+            None => Ok(value),
+            Some(_) => self.error("trailing characters after the document"),
+        }
+    }
+
+    /// Skip whitespace and get the next character without consuming it.
+    fn peek(&mut self) -> Option<u8> {
+        while let Some(&c) = self.data.get(self.pos) {
+            match c {
+                b'\n' => self.line += 1,
+                b' ' | b'\t' | b'\r' => {}
+                _ => return Some(c),
+            }
+            self.pos += 1;
+        }
+        None
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), JsonError> {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            self.error(format!("expected '{}'", c as char))
+        }
+    }
+
+    fn literal(&mut self, word: &str, value: Json) -> Result<Json, JsonError> {
+        if self.data[self.pos..].starts_with(word.as_bytes()) {
+            self.pos += word.len();
+            Ok(value)
+        } else {
+            self.error("invalid literal")
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, JsonError> {
+        match self.peek() {
+            Some(b'{') => {
+                self.pos += 1;
+                let mut entries = Vec::new();
+                if self.peek() == Some(b'}') {
+                    self.pos += 1;
+                    return Ok(Json::Object(entries));
+                }
+                loop {
+                    if self.peek() != Some(b'"') {
+                        return self.error("expected a key");
+                    }
+                    let key = self.string()?;
+                    self.expect(b':')?;
+                    entries.push((key, self.value()?));
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        Some(b'}') => {
+                            self.pos += 1;
+                            return Ok(Json::Object(entries));
+                        }
+                        _ => return self.error("expected ',' or '}'"),
+                    }
+                }
+            }
+            Some(b'[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                if self.peek() == Some(b']') {
+                    self.pos += 1;
+                    return Ok(Json::Array(items));
+                }
+                loop {
+                    items.push(self.value()?);
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        Some(b']') => {
+                            self.pos += 1;
+                            return Ok(Json::Array(items));
+                        }
+                        _ => return self.error("expected ',' or ']'"),
+                    }
+                }
+            }
+            Some(b'"') => Ok(Json::String(self.string()?)),
+            Some(b't') => self.literal("true", Json::Bool(true)),
+            Some(b'f') => self.literal("false", Json::Bool(false)),
+            Some(b'n') => self.literal("null", Json::Null),
+            Some(c) if c == b'-' || c.is_ascii_digit() => self.number(),
+            Some(c) => self.error(format!("unexpected character '{}'", c as char)),
+            None => self.error("unexpected end of data"),
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, JsonError> {
+        let start = self.pos;
+        let mut is_float = false;
+        while let Some(&c) = self.data.get(self.pos) {
+            match c {
+                b'0'..=b'9' | b'-' | b'+' => {}
+                b'.' | b'e' | b'E' => is_float = true,
+                _ => break,
+            }
+            self.pos += 1;
+        }
+        let text = String::from_utf8_lossy(&self.data[start..self.pos]);
+        if !is_float {
+            if let Ok(v) = text.parse() {
+                return Ok(Json::Int(v));
+            }
+        }
+        match text.parse() {
+            Ok(v) => Ok(Json::Float(v)),
+            Err(_) => self.error(format!("invalid number '{}'", text)),
+        }
+    }
+
+    /// Parse a string. The current character must be the opening quote.
+    fn string(&mut self) -> Result<String, JsonError> {
+        self.pos += 1;
+        let mut buf = Vec::new();
+        loop {
+            let c = match self.data.get(self.pos) {
+                Some(&c) => c,
+                None => return self.error("unterminated string"),
+            };
+            self.pos += 1;
+            match c {
+                b'"' => break,
+                b'\\' => {
+                    let escaped = match self.data.get(self.pos) {
+                        Some(&c) => c,
+                        None => return self.error("unterminated string"),
+                    };
+                    self.pos += 1;
+                    let c = match escaped {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => self.unicode_escape()?,
+                        _ => return self.error("invalid escape sequence"),
+                    };
+                    buf.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                c if c < 0x20 => return self.error("control character in string"),
+                c => buf.push(c),
+            }
+        }
+        String::from_utf8(buf).or_else(|_| self.error("invalid UTF-8 in string"))
+    }
+
+    fn hex4(&mut self) -> Result<u32, JsonError> {
+        let digits = self.data.get(self.pos..self.pos + 4).unwrap_or_default();
+        if digits.len() != 4 || !digits.iter().all(u8::is_ascii_hexdigit) {
+            return self.error("invalid unicode escape");
+        }
+        self.pos += 4;
+        Ok(digits
+            .iter()
+            .fold(0, |acc, &d| acc * 16 + (d as char).to_digit(16).unwrap()))
+    }
+
+    /// Decode the digits of a `\u` escape sequence including UTF-16 surrogate pairs.
+    fn unicode_escape(&mut self) -> Result<char, JsonError> {
+        let high = self.hex4()?;
+        let code = if (0xd800..0xdc00).contains(&high) {
+            if !self.data[self.pos..].starts_with(b"\\u") {
+                return self.error("unpaired surrogate");
+            }
+            self.pos += 2;
+            let low = self.hex4()?;
+            if !(0xdc00..0xe000).contains(&low) {
+                return self.error("unpaired surrogate");
+            }
+            0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+        } else {
+            high
+        };
+        match char::from_u32(code) {
+            Some(c) => Ok(c),
+            None => self.error("invalid unicode escape"),
+        }
+    }
+}
+
+// Writing.
+
+/// Content of a document which is assembled while the data base is traversed.
+struct Document {
+    fields: Vec<(String, Json)>,
+    cells: Vec<CellEntries>,
+}
+
+/// Content of a cell object.
+struct CellEntries {
+    fields: Vec<(String, Json)>,
+    instances: Vec<Vec<(String, Json)>>,
+    /// Only present if the layout is written.
+    shapes: Option<Vec<Vec<(String, Json)>>>,
+}
+
+impl Document {
+    fn into_json(self) -> Json {
+        let cells = self
+            .cells
+            .into_iter()
+            .map(|cell| {
+                let mut fields = cell.fields;
+                let instances = cell.instances.into_iter().map(Json::Object).collect();
+                fields.push(entry("instances", Json::Array(instances)));
+                if let Some(shapes) = cell.shapes {
+                    let shapes = shapes.into_iter().map(Json::Object).collect();
+                    fields.push(entry("shapes", Json::Array(shapes)));
+                }
+                Json::Object(fields)
+            })
+            .collect();
+        let mut fields = self.fields;
+        fields.push(entry("cells", Json::Array(cells)));
+        Json::Object(fields)
+    }
+}
+
+fn direction_name(direction: Direction) -> &'static str {
+    match direction {
+        Direction::None => "none",
+        Direction::Input => "input",
+        Direction::Output => "output",
+        Direction::InOut => "inout",
+        Direction::Clock => "clock",
+        Direction::Supply => "supply",
+        Direction::Ground => "ground",
+    }
+}
+
+fn direction_from_name(name: &str) -> Result<Direction, JsonError> {
+    Ok(match name {
+        "none" => Direction::None,
+        "input" => Direction::Input,
+        "output" => Direction::Output,
+        "inout" => Direction::InOut,
+        "clock" => Direction::Clock,
+        "supply" => Direction::Supply,
+        "ground" => Direction::Ground,
+        _ => return Err(malformed(format!("invalid pin direction '{}'", name))),
+    })
+}
+
+fn bit_order_name(bit_order: BitOrder) -> &'static str {
+    match bit_order {
+        BitOrder::Descending => "descending",
+        BitOrder::Ascending => "ascending",
+    }
+}
+
+fn bit_order_from_name(name: &str) -> Result<BitOrder, JsonError> {
+    Ok(match name {
+        "descending" => BitOrder::Descending,
+        "ascending" => BitOrder::Ascending,
+        _ => return Err(malformed(format!("invalid bit order '{}'", name))),
+    })
+}
+
+fn placement_status_name(status: PlacementStatus) -> &'static str {
+    match status {
+        PlacementStatus::Unplaced => "unplaced",
+        PlacementStatus::Placed => "placed",
+        PlacementStatus::Firm => "firm",
+        PlacementStatus::Fixed => "fixed",
+        PlacementStatus::Cover => "cover",
+    }
+}
+
+fn placement_status_from_name(name: &str) -> Result<PlacementStatus, JsonError> {
+    Ok(match name {
+        "unplaced" => PlacementStatus::Unplaced,
+        "placed" => PlacementStatus::Placed,
+        "firm" => PlacementStatus::Firm,
+        "fixed" => PlacementStatus::Fixed,
+        "cover" => PlacementStatus::Cover,
+        _ => return Err(malformed(format!("invalid placement status '{}'", name))),
+    })
+}
+
+fn encode_point(p: Point<i32>) -> Json {
+    Json::Array(vec![Json::Int(p.x.into()), Json::Int(p.y.into())])
+}
+
+fn encode_points<'a>(points: impl Iterator<Item = &'a Point<i32>>) -> Json {
+    Json::Array(points.map(|p| encode_point(*p)).collect())
+}
+
+/// Non-finite floats are written as strings.
+fn encode_float(v: f64) -> Json {
+    if v.is_finite() {
+        Json::Float(v)
+    } else {
+        Json::String(v.to_string())
+    }
+}
+
+fn encode_property_value(value: &PropertyValue) -> Json {
+    let (kind, value) = match value {
+        PropertyValue::String(s) => ("string", Json::String(s.as_str().into())),
+        PropertyValue::Bytes(b) => (
+            "bytes",
+            Json::Array(b.iter().map(|&b| Json::Int(b.into())).collect()),
+        ),
+        PropertyValue::SInt(v) => ("sint", Json::Int((*v).into())),
+        PropertyValue::UInt(v) => ("uint", Json::Int((*v).into())),
+        PropertyValue::Float(v) => ("float", encode_float(*v)),
+    };
+    Json::Object(vec![entry(kind, value)])
+}
+
+/// Encode properties which have been collected with one of the `for_each_*_property` functions.
+fn encode_properties<K: Into<String>>(properties: Vec<(K, PropertyValue)>) -> Json {
+    Json::Object(
+        properties
+            .into_iter()
+            .map(|(k, v)| entry(k, encode_property_value(&v)))
+            .collect(),
+    )
+}
+
+fn encode_transform(tf: &SimpleTransform<i32>) -> Json {
+    let rotation = match tf.rotation {
+        Angle::R0 => 0,
+        Angle::R90 => 90,
+        Angle::R180 => 180,
+        Angle::R270 => 270,
+    };
+    Json::Object(vec![
+        entry("mirror", Json::Bool(tf.mirror)),
+        entry("rotation", Json::Int(rotation)),
+        entry("magnification", Json::Int(tf.magnification.into())),
+        entry(
+            "displacement",
+            encode_point(Point::new(tf.displacement.x, tf.displacement.y)),
+        ),
+    ])
+}
+
+fn encode_array(array: &InstanceArray<i32>) -> Json {
+    let vector = |v: Vector<i32>| encode_point(Point::new(v.x, v.y));
+    Json::Object(vec![
+        entry("columns", Json::Int(array.columns.into())),
+        entry("rows", Json::Int(array.rows.into())),
+        entry("column_pitch", vector(array.column_pitch)),
+        entry("row_pitch", vector(array.row_pitch)),
+    ])
+}
+
+fn encode_geometry(geometry: &Geometry<i32>) -> Result<Json, JsonError> {
+    let fields = match geometry {
+        Geometry::Point(p) => vec![
+            entry("type", Json::String("point".into())),
+            entry("location", encode_point(*p)),
+        ],
+        Geometry::Edge(e) => vec![
+            entry("type", Json::String("edge".into())),
+            entry("start", encode_point(e.start)),
+            entry("end", encode_point(e.end)),
+        ],
+        Geometry::Rect(r) => vec![
+            entry("type", Json::String("rect".into())),
+            entry("lower_left", encode_point(r.lower_left())),
+            entry("upper_right", encode_point(r.upper_right())),
+        ],
+        Geometry::SimplePolygon(p) => vec![
+            entry("type", Json::String("polygon".into())),
+            entry("points", encode_points(p.iter())),
+        ],
+        Geometry::Polygon(p) => vec![
+            entry("type", Json::String("polygon".into())),
+            entry("points", encode_points(p.exterior.iter())),
+            entry(
+                "holes",
+                Json::Array(
+                    p.interiors
+                        .iter()
+                        .map(|hole| encode_points(hole.iter()))
+                        .collect(),
+                ),
+            ),
+        ],
+        Geometry::Path(p) => {
+            let mut fields = vec![
+                entry("type", Json::String("path".into())),
+                entry("points", encode_points(p.points.iter())),
+                entry("width", Json::Int(p.width.into())),
+            ];
+            match p.path_type {
+                PathEndType::Flat => fields.push(entry("end_type", Json::String("flat".into()))),
+                PathEndType::Round => fields.push(entry("end_type", Json::String("round".into()))),
+                PathEndType::Extended(begin, end) => {
+                    fields.push(entry("end_type", Json::String("extended".into())));
+                    fields.push(entry(
+                        "extension",
+                        Json::Array(vec![Json::Int(begin.into()), Json::Int(end.into())]),
+                    ));
+                }
+            }
+            fields
+        }
+        Geometry::Text(t) => vec![
+            entry("type", Json::String("text".into())),
+            entry("text", Json::String(t.text().to_string())),
+            entry("location", encode_point(t.location())),
+        ],
+        _ => return Err(JsonError::UnsupportedGeometry),
+    };
+    Ok(Json::Object(fields))
+}
+
+fn encode_hierarchy<H: HierarchyBase>(chip: &H, table: &CellTable<H>) -> Document {
+    let mut properties = vec![];
+    chip.for_each_chip_property(|k, v| properties.push((k.clone(), v.clone())));
+    let fields = vec![
+        entry("format", Json::String(FORMAT_NAME.into())),
+        entry("version", Json::Int(FORMAT_VERSION)),
+        entry("properties", encode_properties(properties)),
+    ];
+
+    let cells = table
+        .cells
+        .iter()
+        .zip(&table.instances)
+        .map(|(cell, instances)| {
+            let mut properties = vec![];
+            chip.for_each_cell_property(cell, |k, v| properties.push((k.clone(), v.clone())));
+            let instances = instances
+                .iter()
+                .map(|inst| {
+                    let mut properties = vec![];
+                    chip.for_each_cell_instance_property(inst, |k, v| {
+                        properties.push((k.clone(), v.clone()))
+                    });
+                    vec![
+                        entry("name", Json::opt_string(chip.cell_instance_name(inst))),
+                        entry(
+                            "template",
+                            Json::String(chip.cell_name(&chip.template_cell(inst)).into()),
+                        ),
+                        entry("properties", encode_properties(properties)),
+                    ]
+                })
+                .collect();
+            CellEntries {
+                fields: vec![
+                    entry("name", Json::String(chip.cell_name(cell).into())),
+                    entry("properties", encode_properties(properties)),
+                ],
+                instances,
+                shapes: None,
+            }
+        })
+        .collect();
+
+    Document { fields, cells }
+}
+
+/// Index of each net within the `nets` list of its cell.
+fn net_indices<T: Clone + Eq + Hash>(nets: &[T]) -> HashMap<T, i64> {
+    nets.iter()
+        .enumerate()
+        .map(|(i, net)| (net.clone(), i as i64))
+        .collect()
+}
+
+fn encode_netlist<N: NetlistBase>(netlist: &N, table: &CellTable<N>, doc: &mut Document) {
+    for ((cell, instances), cell_entries) in
+        table.cells.iter().zip(&table.instances).zip(&mut doc.cells)
+    {
+        let nets = netlist.each_internal_net_vec(cell);
+        let net_indices = net_indices(&nets);
+        let net_zero = netlist.net_zero(cell);
+        let net_one = netlist.net_one(cell);
+
+        let pins = netlist
+            .each_pin(cell)
+            .map(|pin| {
+                let mut fields = vec![
+                    entry("name", Json::String(netlist.pin_name(&pin).into())),
+                    entry(
+                        "direction",
+                        Json::String(direction_name(netlist.pin_direction(&pin)).into()),
+                    ),
+                ];
+                if let Some(net) = netlist.net_of_pin(&pin) {
+                    fields.push(entry("net", Json::Int(net_indices[&net])));
+                }
+                Json::Object(fields)
+            })
+            .collect();
+
+        let nets = nets
+            .iter()
+            .map(|net| {
+                let mut fields = vec![entry("name", Json::opt_string(netlist.net_name(net)))];
+                // Constant nets exist already in a new cell. They must not be created again.
+                if net == &net_zero {
+                    fields.push(entry("constant", Json::Int(0)));
+                } else if net == &net_one {
+                    fields.push(entry("constant", Json::Int(1)));
+                }
+                Json::Object(fields)
+            })
+            .collect();
+
+        cell_entries.fields.push(entry("pins", Json::Array(pins)));
+        cell_entries.fields.push(entry("nets", Json::Array(nets)));
+
+        for (inst, inst_fields) in instances.iter().zip(&mut cell_entries.instances) {
+            let connections = netlist
+                .each_pin_instance(inst)
+                .filter_map(|pin_inst| {
+                    let net = netlist.net_of_pin_instance(&pin_inst)?;
+                    let pin = netlist.template_pin(&pin_inst);
+                    Some(entry(netlist.pin_name(&pin), Json::Int(net_indices[&net])))
+                })
+                .collect();
+            inst_fields.push(entry("connections", Json::Object(connections)));
+        }
+    }
+}
+
+fn encode_bus(name: String, bit_order: BitOrder, bits: Vec<Json>) -> Json {
+    Json::Object(vec![
+        entry("name", Json::String(name)),
+        entry("bit_order", Json::String(bit_order_name(bit_order).into())),
+        entry("bits", Json::Array(bits)),
+    ])
+}
+
+/// Add the pin and net buses of cells which have any.
+fn encode_buses<N: BusBase>(netlist: &N, table: &CellTable<N>, doc: &mut Document) {
+    for (cell, cell_entries) in table.cells.iter().zip(&mut doc.cells) {
+        let net_indices = net_indices(&netlist.each_internal_net_vec(cell));
+
+        let pin_buses: Vec<_> = netlist
+            .each_pin_bus_vec(cell)
+            .iter()
+            .map(|bus| {
+                let bits = netlist
+                    .each_pin_of_bus_vec(bus)
+                    .iter()
+                    .map(|pin| Json::String(netlist.pin_name(pin).into()))
+                    .collect();
+                encode_bus(
+                    netlist.pin_bus_name(bus).into(),
+                    netlist.pin_bus_bit_order(bus),
+                    bits,
+                )
+            })
+            .collect();
+        let net_buses: Vec<_> = netlist
+            .each_net_bus_vec(cell)
+            .iter()
+            .map(|bus| {
+                let bits = netlist
+                    .each_net_of_bus_vec(bus)
+                    .iter()
+                    .map(|net| Json::Int(net_indices[net]))
+                    .collect();
+                encode_bus(
+                    netlist.net_bus_name(bus).into(),
+                    netlist.net_bus_bit_order(bus),
+                    bits,
+                )
+            })
+            .collect();
+
+        if !pin_buses.is_empty() || !net_buses.is_empty() {
+            cell_entries.fields.push(entry(
+                "buses",
+                Json::Object(vec![
+                    entry("pins", Json::Array(pin_buses)),
+                    entry("nets", Json::Array(net_buses)),
+                ]),
+            ));
+        }
+    }
+}
+
+/// Add the power domains and the assignments of cells and cell instances.
+fn encode_power<N: PowerBase>(netlist: &N, table: &CellTable<N>, doc: &mut Document) {
+    let domains = netlist
+        .each_power_domain()
+        .map(|domain| {
+            let parent = netlist.parent_cell_of_power_domain(&domain);
+            let net_indices = net_indices(&netlist.each_internal_net_vec(&parent));
+            let supply_net = netlist.power_domain_supply_net(&domain);
+            let ground_net = netlist.power_domain_ground_net(&domain);
+            Json::Object(vec![
+                entry(
+                    "name",
+                    Json::String(netlist.power_domain_name(&domain).into()),
+                ),
+                entry("cell", Json::String(netlist.cell_name(&parent).into())),
+                entry("supply_net", Json::Int(net_indices[&supply_net])),
+                entry("ground_net", Json::Int(net_indices[&ground_net])),
+                entry(
+                    "voltage",
+                    encode_float(netlist.power_domain_voltage(&domain)),
+                ),
+                entry(
+                    "always_on",
+                    Json::Bool(netlist.power_domain_is_always_on(&domain)),
+                ),
+            ])
+        })
+        .collect();
+    doc.fields
+        .push(entry("power_domains", Json::Array(domains)));
+
+    for ((cell, instances), cell_entries) in
+        table.cells.iter().zip(&table.instances).zip(&mut doc.cells)
+    {
+        if let Some(domain) = netlist.power_domain_of_cell(cell) {
+            let name = Json::String(netlist.power_domain_name(&domain).into());
+            cell_entries.fields.push(entry("power_domain", name));
+        }
+        for (inst, inst_fields) in instances.iter().zip(&mut cell_entries.instances) {
+            if let Some(domain) = netlist.assigned_power_domain_of_cell_instance(inst) {
+                let name = Json::String(netlist.power_domain_name(&domain).into());
+                inst_fields.push(entry("power_domain", name));
+            }
+        }
+    }
+}
+
+fn encode_layout<L: LayoutBase<Coord = i32>>(
+    layout: &L,
+    table: &CellTable<L>,
+    doc: &mut Document,
+) -> Result<(), JsonError> {
+    doc.fields
+        .push(entry("dbu", Json::Int(layout.dbu().into())));
+
+    let layers: Vec<_> = layout.each_layer().collect();
+    let layer_entries = layers
+        .iter()
+        .map(|layer| {
+            let info = layout.layer_info(layer);
+            Json::Object(vec![
+                entry("index", Json::Int(info.index.into())),
+                entry("datatype", Json::Int(info.datatype.into())),
+                entry("name", Json::opt_string(info.name)),
+            ])
+        })
+        .collect();
+    doc.fields.push(entry("layers", Json::Array(layer_entries)));
+
+    for ((cell, instances), cell_entries) in
+        table.cells.iter().zip(&table.instances).zip(&mut doc.cells)
+    {
+        for (inst, inst_fields) in instances.iter().zip(&mut cell_entries.instances) {
+            inst_fields.push(entry(
+                "transform",
+                encode_transform(&layout.get_transform(inst)),
+            ));
+            if let Some(array) = layout.cell_instance_array(inst) {
+                inst_fields.push(entry("array", encode_array(&array)));
+            }
+            let status = layout.placement_status(inst);
+            if status != PlacementStatus::default() {
+                let name = Json::String(placement_status_name(status).into());
+                inst_fields.push(entry("placement_status", name));
+            }
+        }
+
+        let mut shapes = Vec::new();
+        for (layer_index, layer) in layers.iter().enumerate() {
+            for shape in layout.each_shape_id(cell, layer) {
+                let mut properties = vec![];
+                layout.for_each_shape_property(&shape, |k, v| {
+                    properties.push((k.clone(), v.clone()))
+                });
+                shapes.push(vec![
+                    entry("layer", Json::Int(layer_index as i64)),
+                    entry("geometry", encode_geometry(&layout.shape_geometry(&shape))?),
+                    entry("properties", encode_properties(properties)),
+                ]);
+            }
+        }
+        cell_entries.shapes = Some(shapes);
+    }
+
+    Ok(())
+}
+
+/// Add the nets and pins of the shapes. The layout must have been encoded before.
+fn encode_l2n<LN: L2NBase>(chip: &LN, table: &CellTable<LN>, doc: &mut Document) {
+    let layers: Vec<_> = chip.each_layer().collect();
+    for (cell, cell_entries) in table.cells.iter().zip(&mut doc.cells) {
+        let net_indices = net_indices(&chip.each_internal_net_vec(cell));
+        // Use the same order as in `encode_layout`.
+        let shapes = layers
+            .iter()
+            .flat_map(|layer| chip.each_shape_id(cell, layer));
+        let shape_entries = cell_entries.shapes.iter_mut().flatten();
+        for (shape, fields) in shapes.zip(shape_entries) {
+            if let Some(net) = chip.get_net_of_shape(&shape) {
+                fields.push(entry("net", Json::Int(net_indices[&net])));
+            }
+            if let Some(pin) = chip.get_pin_of_shape(&shape) {
+                fields.push(entry("pin", Json::String(chip.pin_name(&pin).into())));
+            }
+        }
+    }
+}
+
+// Reading.
+
+/// Parse the document and check the version.
+fn read_document<R: Read>(reader: &mut R) -> Result<Json, JsonError> {
+    let mut data = String::new();
+    reader.read_to_string(&mut data)?;
+    let doc = Parser::new(&data).document()?;
+    match doc.field("version")?.as_i64()? {
+        FORMAT_VERSION => Ok(doc),
+        version => Err(JsonError::UnsupportedVersion(version)),
+    }
+}
+
+/// Look up the element with the index stored in `value`.
+fn index<'t, T>(table: &'t [T], value: &Json) -> Result<&'t T, JsonError> {
+    usize::try_from(value.as_i64()?)
+        .ok()
+        .and_then(|i| table.get(i))
+        .ok_or_else(|| malformed("index out of range"))
+}
+
+fn decode_point(value: &Json) -> Result<Point<i32>, JsonError> {
+    match value.as_array()? {
+        [x, y] => Ok(Point::new(x.as_i32()?, y.as_i32()?)),
+        _ => Err(malformed("a point must have two coordinates")),
+    }
+}
+
+fn decode_points(value: &Json) -> Result<Vec<Point<i32>>, JsonError> {
+    value.as_array()?.iter().map(decode_point).collect()
+}
+
+fn decode_property_value(value: &Json) -> Result<PropertyValue, JsonError> {
+    let (kind, value) = match value.entries()? {
+        [(kind, value)] => (kind, value),
+        _ => return Err(malformed("a property value must have exactly one entry")),
+    };
+    Ok(match kind.as_str() {
+        "string" => PropertyValue::String(value.as_str()?.to_string().into()),
+        "bytes" => PropertyValue::Bytes(
+            value
+                .as_array()?
+                .iter()
+                .map(|b| u8::try_from(b.as_i64()?).map_err(|_| malformed("byte out of range")))
+                .collect::<Result<_, _>>()?,
+        ),
+        "sint" => PropertyValue::SInt(value.as_i32()?),
+        "uint" => PropertyValue::UInt(value.as_u32()?),
+        "float" => PropertyValue::Float(value.as_f64()?),
+        _ => return Err(malformed(format!("invalid property type '{}'", kind))),
+    })
+}
+
+/// Read the optional properties of `value` and store them with `f`.
+fn decode_properties<F>(value: &Json, mut f: F) -> Result<(), JsonError>
+where
+    F: FnMut(String, PropertyValue),
+{
+    if let Some(properties) = value.get("properties") {
+        for (key, value) in properties.entries()? {
+            f(key.clone(), decode_property_value(value)?);
+        }
+    }
+    Ok(())
+}
+
+fn decode_transform(value: &Json) -> Result<SimpleTransform<i32>, JsonError> {
+    let mirror = match value.get("mirror") {
+        Some(mirror) => mirror.as_bool()?,
+        None => false,
+    };
+    let rotation = match value.get("rotation").map(Json::as_i64).transpose()? {
+        None | Some(0) => Angle::R0,
+        Some(90) => Angle::R90,
+        Some(180) => Angle::R180,
+        Some(270) => Angle::R270,
+        Some(r) => return Err(malformed(format!("invalid rotation {}", r))),
+    };
+    let magnification = match value.get("magnification") {
+        Some(m) => m.as_i32()?,
+        None => 1,
+    };
+    let displacement = match value.get("displacement") {
+        Some(d) => decode_point(d)?,
+        None => Point::zero(),
+    };
+    Ok(SimpleTransform::new(
+        mirror,
+        rotation,
+        magnification,
+        Vector::new(displacement.x, displacement.y),
+    ))
+}
+
+fn decode_array(value: &Json) -> Result<InstanceArray<i32>, JsonError> {
+    let columns = value.field("columns")?.as_u32()?;
+    let rows = value.field("rows")?.as_u32()?;
+    if columns == 0 || rows == 0 {
+        return Err(malformed("empty instance array"));
+    }
+    let vector = |key: &str| -> Result<Vector<i32>, JsonError> {
+        let p = decode_point(value.field(key)?)?;
+        Ok(Vector::new(p.x, p.y))
+    };
+    Ok(InstanceArray::new(
+        columns,
+        rows,
+        vector("column_pitch")?,
+        vector("row_pitch")?,
+    ))
+}
+
+fn decode_geometry(value: &Json) -> Result<Geometry<i32>, JsonError> {
+    Ok(match value.field("type")?.as_str()? {
+        "point" => decode_point(value.field("location")?)?.into(),
+        "edge" => Edge::new(
+            decode_point(value.field("start")?)?,
+            decode_point(value.field("end")?)?,
+        )
+        .into(),
+        "rect" => Rect::new(
+            decode_point(value.field("lower_left")?)?,
+            decode_point(value.field("upper_right")?)?,
+        )
+        .into(),
+        "polygon" => {
+            let exterior = SimplePolygon::new(decode_points(value.field("points")?)?);
+            match value.get("holes") {
+                None => exterior.into(),
+                Some(holes) => {
+                    let interiors = holes
+                        .as_array()?
+                        .iter()
+                        .map(|hole| decode_points(hole).map(SimplePolygon::new))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Polygon {
+                        exterior,
+                        interiors,
+                    }
+                    .into()
+                }
+            }
+        }
+        "path" => {
+            let points = decode_points(value.field("points")?)?;
+            let width = value.field("width")?.as_i32()?;
+            match value.opt_str("end_type")?.unwrap_or("flat") {
+                "flat" => Path::new(points, width),
+                "round" => Path::new_rounded(points, width),
+                "extended" => match value.field("extension")?.as_array()? {
+                    [begin, end] => {
+                        Path::new_extended(points, width, begin.as_i32()?, end.as_i32()?)
+                    }
+                    _ => return Err(malformed("a path extension must have two values")),
+                },
+                other => return Err(malformed(format!("invalid path end type '{}'", other))),
+            }
+            .into()
+        }
+        "text" => Text::new(
+            value.field("text")?.as_str()?.to_string(),
+            decode_point(value.field("location")?)?,
+        )
+        .into(),
+        other => return Err(malformed(format!("invalid geometry type '{}'", other))),
+    })
+}
+
+fn decode_hierarchy<H: HierarchyEdit>(doc: &Json, chip: &mut H) -> Result<CellTable<H>, JsonError> {
+    decode_properties(doc, |k, v| chip.set_chip_property(k.into(), v))?;
+
+    let cell_values = doc.items("cells")?;
+    let mut cells = Vec::with_capacity(cell_values.len());
+    for value in cell_values {
+        let name = value.field("name")?.as_str()?;
+        if chip.cell_by_name(name).is_some() {
+            return Err(JsonError::CellNameCollision(name.to_string()));
+        }
+        let cell = chip.create_cell(name.to_string().into());
+        decode_properties(value, |k, v| chip.set_cell_property(&cell, k.into(), v))?;
+        cells.push(cell);
+    }
+
+    // Instances are created after all cells such that templates can be defined anywhere in the document.
+    let mut instances = Vec::with_capacity(cell_values.len());
+    for (value, parent) in cell_values.iter().zip(&cells) {
+        let mut cell_instances = Vec::new();
+        for inst_value in value.items("instances")? {
+            let template_name = inst_value.field("template")?.as_str()?;
+            let template = chip
+                .cell_by_name(template_name)
+                .ok_or_else(|| malformed(format!("unknown template cell '{}'", template_name)))?;
+            let name = inst_value.opt_str("name")?;
+            if let Some(name) = name {
+                if chip.cell_instance_by_name(parent, name).is_some() {
+                    return Err(malformed(format!(
+                        "duplicate cell instance name '{}'",
+                        name
+                    )));
+                }
+            }
+            if chip.cell_depends_on(&template, parent) {
+                return Err(malformed(format!(
+                    "cell '{}' is instantiated recursively",
+                    template_name
+                )));
+            }
+            let inst =
+                chip.create_cell_instance(parent, &template, name.map(|n| n.to_string().into()));
+            decode_properties(inst_value, |k, v| {
+                chip.set_cell_instance_property(&inst, k.into(), v)
+            })?;
+            cell_instances.push(inst);
+        }
+        instances.push(cell_instances);
+    }
+
+    Ok(CellTable { cells, instances })
+}
+
+/// Returns the nets of each cell in the order in which they are stored in the document.
+fn decode_netlist<N: NetlistEdit>(
+    doc: &Json,
+    netlist: &mut N,
+    table: &CellTable<N>,
+) -> Result<Vec<Vec<N::NetId>>, JsonError> {
+    let cell_values = doc.items("cells")?;
+
+    // Pins of all cells must exist before instances can be connected.
+    let mut all_pins = Vec::with_capacity(cell_values.len());
+    for (value, cell) in cell_values.iter().zip(&table.cells) {
+        let mut pins = Vec::new();
+        for pin_value in value.items("pins")? {
+            let name = pin_value.field("name")?.as_str()?;
+            let direction = match pin_value.opt_str("direction")? {
+                Some(direction) => direction_from_name(direction)?,
+                None => Direction::None,
+            };
+            pins.push(netlist.create_pin(cell, name.to_string().into(), direction));
+        }
+        all_pins.push(pins);
+    }
+
+    let mut all_nets = Vec::with_capacity(cell_values.len());
+    for ((value, cell), pins) in cell_values.iter().zip(&table.cells).zip(&all_pins) {
+        let mut nets = Vec::new();
+        for net_value in value.items("nets")? {
+            let name = net_value.opt_str("name")?;
+            let net = match net_value.get("constant").map(Json::as_i64).transpose()? {
+                None => netlist.create_net(cell, name.map(|n| n.to_string().into())),
+                Some(0) => netlist.net_zero(cell),
+                Some(1) => netlist.net_one(cell),
+                Some(c) => return Err(malformed(format!("invalid constant net {}", c))),
+            };
+            nets.push(net);
+        }
+
+        for (pin_value, pin) in value.items("pins")?.iter().zip(pins) {
+            if let Some(net) = pin_value.get("net") {
+                netlist.connect_pin(pin, Some(index(&nets, net)?.clone()));
+            }
+        }
+
+        all_nets.push(nets);
+    }
+
+    for ((value, instances), nets) in cell_values.iter().zip(&table.instances).zip(&all_nets) {
+        for (inst_value, inst) in value.items("instances")?.iter().zip(instances) {
+            let connections = match inst_value.get("connections") {
+                Some(connections) => connections.entries()?,
+                None => continue,
+            };
+            let template = netlist.template_cell(inst);
+            for (pin_name, net) in connections {
+                let pin = netlist.pin_by_name(&template, pin_name).ok_or_else(|| {
+                    malformed(format!(
+                        "cell '{}' has no pin '{}'",
+                        netlist.cell_name(&template),
+                        pin_name
+                    ))
+                })?;
+                let pin_inst = netlist.pin_instance(inst, &pin);
+                netlist.connect_pin_instance(&pin_inst, Some(index(nets, net)?.clone()));
+            }
+        }
+    }
+
+    Ok(all_nets)
+}
+
+/// Read the name, bit order and bits of a bus. The bits must be distinct and not be part of another bus yet.
+fn decode_bus<T: Clone + PartialEq>(
+    value: &Json,
+    mut decode_bit: impl FnMut(&Json) -> Result<T, JsonError>,
+    is_in_bus: impl Fn(&T) -> bool,
+) -> Result<(String, BitOrder, Vec<T>), JsonError> {
+    let name = value.field("name")?.as_str()?.to_string();
+    let bit_order = match value.opt_str("bit_order")? {
+        Some(bit_order) => bit_order_from_name(bit_order)?,
+        None => BitOrder::default(),
+    };
+    let mut bits = Vec::new();
+    for bit_value in value.items("bits")? {
+        let bit = decode_bit(bit_value)?;
+        if is_in_bus(&bit) || bits.contains(&bit) {
+            return Err(malformed(format!(
+                "bus '{}' overlaps with another bus",
+                name
+            )));
+        }
+        bits.push(bit);
+    }
+    if bits.is_empty() {
+        return Err(malformed(format!("bus '{}' has no bits", name)));
+    }
+    Ok((name, bit_order, bits))
+}
+
+fn decode_buses<N: BusEdit>(
+    doc: &Json,
+    netlist: &mut N,
+    table: &CellTable<N>,
+    nets: &[Vec<N::NetId>],
+) -> Result<(), JsonError> {
+    let cells = doc.items("cells")?.iter().zip(&table.cells);
+    for ((value, cell), nets) in cells.zip(nets) {
+        let buses = match value.get("buses") {
+            Some(buses) => buses,
+            None => continue,
+        };
+
+        for bus_value in buses.items("pins")? {
+            let (name, bit_order, pins) = decode_bus(
+                bus_value,
+                |pin| {
+                    let pin_name = pin.as_str()?;
+                    netlist
+                        .pin_by_name(cell, pin_name)
+                        .ok_or_else(|| malformed(format!("unknown pin '{}'", pin_name)))
+                },
+                |pin| netlist.bus_of_pin(pin).is_some(),
+            )?;
+            if netlist.pin_bus_by_name(cell, &name).is_some() {
+                return Err(malformed(format!("duplicate pin bus '{}'", name)));
+            }
+            netlist.create_pin_bus_from_pins(name.into(), pins, bit_order);
+        }
+
+        for bus_value in buses.items("nets")? {
+            let (name, bit_order, bits) = decode_bus(
+                bus_value,
+                |net| index(nets, net).cloned(),
+                |net| netlist.bus_of_net(net).is_some(),
+            )?;
+            if netlist.net_bus_by_name(cell, &name).is_some() {
+                return Err(malformed(format!("duplicate net bus '{}'", name)));
+            }
+            netlist.create_net_bus_from_nets(name.into(), bits, bit_order);
+        }
+    }
+    Ok(())
+}
+
+fn decode_power<N: PowerEdit>(
+    doc: &Json,
+    netlist: &mut N,
+    table: &CellTable<N>,
+    nets: &[Vec<N::NetId>],
+) -> Result<(), JsonError> {
+    for value in doc.items("power_domains")? {
+        let name = value.field("name")?.as_str()?;
+        if netlist.power_domain_by_name(name).is_some() {
+            return Err(JsonError::PowerDomainNameCollision(name.to_string()));
+        }
+        // The nets are referenced by their index in the parent cell of the domain.
+        let cell_name = value.field("cell")?.as_str()?;
+        let cell_nets = netlist
+            .cell_by_name(cell_name)
+            .and_then(|cell| table.cells.iter().position(|c| c == &cell))
+            .map(|i| &nets[i])
+            .ok_or_else(|| malformed(format!("unknown cell '{}'", cell_name)))?;
+        let supply_net = index(cell_nets, value.field("supply_net")?)?.clone();
+        let ground_net = index(cell_nets, value.field("ground_net")?)?.clone();
+        let voltage = value.field("voltage")?.as_f64()?;
+        let always_on = match value.get("always_on") {
+            Some(always_on) => always_on.as_bool()?,
+            None => false,
+        };
+        netlist.create_power_domain(
+            name.to_string().into(),
+            supply_net,
+            ground_net,
+            voltage,
+            always_on,
+        );
+    }
+
+    let find_domain = |netlist: &N, value: &Json| -> Result<Option<N::PowerDomainId>, JsonError> {
+        value
+            .opt_str("power_domain")?
+            .map(|name| {
+                netlist
+                    .power_domain_by_name(name)
+                    .ok_or_else(|| malformed(format!("unknown power domain '{}'", name)))
+            })
+            .transpose()
+    };
+    let cells = doc.items("cells")?.iter().zip(&table.cells);
+    for ((value, cell), instances) in cells.zip(&table.instances) {
+        if let Some(domain) = find_domain(&*netlist, value)? {
+            netlist.set_power_domain_of_cell(cell, Some(domain));
+        }
+        for (inst_value, inst) in value.items("instances")?.iter().zip(instances) {
+            if let Some(domain) = find_domain(&*netlist, inst_value)? {
+                netlist.set_power_domain_of_cell_instance(inst, Some(domain));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the created shapes of each cell in the order in which they are stored in the document.
+fn decode_layout<L: LayoutEdit<Coord = i32>>(
+    doc: &Json,
+    layout: &mut L,
+    table: &CellTable<L>,
+) -> Result<Vec<Vec<L::ShapeId>>, JsonError> {
+    if let Some(dbu) = doc.get("dbu") {
+        layout.set_dbu(dbu.as_i32()?);
+    }
+
+    let mut layers = Vec::new();
+    for value in doc.items("layers")? {
+        let index = value.field("index")?.as_u32()?;
+        let datatype = value.field("datatype")?.as_u32()?;
+        let layer = layout
+            .find_layer(index, datatype)
+            .unwrap_or_else(|| layout.create_layer(index, datatype));
+        if let Some(name) = value.opt_str("name")? {
+            layout.set_layer_name(&layer, Some(name.to_string().into()));
+        }
+        layers.push(layer);
+    }
+
+    let cell_values = doc.items("cells")?;
+    let mut all_shapes = Vec::with_capacity(cell_values.len());
+    for ((value, cell), instances) in cell_values.iter().zip(&table.cells).zip(&table.instances) {
+        for (inst_value, inst) in value.items("instances")?.iter().zip(instances) {
+            if let Some(tf) = inst_value.get("transform") {
+                layout.set_transform(inst, decode_transform(tf)?);
+            }
+            if let Some(array) = inst_value.get("array") {
+                layout.set_cell_instance_array(inst, Some(decode_array(array)?));
+            }
+            if let Some(status) = inst_value.opt_str("placement_status")? {
+                layout.set_placement_status(inst, placement_status_from_name(status)?);
+            }
+        }
+
+        let mut shapes = Vec::new();
+        for shape_value in value.items("shapes")? {
+            let layer = index(&layers, shape_value.field("layer")?)?;
+            let geometry = decode_geometry(shape_value.field("geometry")?)?;
+            let shape = layout.insert_shape(cell, layer, geometry);
+            decode_properties(shape_value, |k, v| {
+                layout.set_shape_property(&shape, k.into(), v)
+            })?;
+            shapes.push(shape);
+        }
+        all_shapes.push(shapes);
+    }
+
+    Ok(all_shapes)
+}
+
+fn decode_l2n<LN: L2NEdit>(
+    doc: &Json,
+    chip: &mut LN,
+    table: &CellTable<LN>,
+    nets: &[Vec<LN::NetId>],
+    shapes: &[Vec<LN::ShapeId>],
+) -> Result<(), JsonError> {
+    let cells = doc.items("cells")?.iter().zip(&table.cells);
+    for (((value, cell), nets), shapes) in cells.zip(nets).zip(shapes) {
+        for (shape_value, shape) in value.items("shapes")?.iter().zip(shapes) {
+            if let Some(net) = shape_value.get("net") {
+                chip.set_net_of_shape(shape, Some(index(nets, net)?.clone()));
+            }
+            if let Some(pin_name) = shape_value.opt_str("pin")? {
+                let pin = chip
+                    .pin_by_name(cell, pin_name)
+                    .ok_or_else(|| malformed(format!("unknown pin '{}'", pin_name)))?;
+                chip.set_pin_of_shape(shape, Some(pin));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip::Chip;
+
+    fn create_test_chip() -> Chip {
+        let mut chip = Chip::new();
+        chip.set_chip_property("design".into(), PropertyValue::String("test".into()));
+        let layer = chip.create_layer(1, 0);
+        chip.set_layer_name(&layer, Some("metal1".into()));
+
+        let top = chip.create_cell("TOP".into());
+        let leaf = chip.create_cell("LEAF".into());
+        chip.set_cell_property(&leaf, "area".into(), PropertyValue::Float(1.5));
+        chip.set_cell_property(
+            &leaf,
+            "note".into(),
+            PropertyValue::String("\"q\"\n".into()),
+        );
+
+        let a = chip.create_pin(&leaf, "A".into(), Direction::Input);
+        let y = chip.create_pin(&leaf, "Y".into(), Direction::Output);
+        let leaf_net = chip.create_net(&leaf, Some("n".into()));
+        chip.connect_pin(&a, Some(leaf_net));
+        let tie = chip.net_one(&leaf);
+        chip.connect_pin(&y, Some(tie));
+
+        let pin_shape = chip.insert_shape(&leaf, &layer, Rect::new((0, 0), (1, 1)).into());
+        chip.set_pin_of_shape(&pin_shape, Some(a));
+        chip.set_net_of_shape(&pin_shape, Some(leaf_net));
+        chip.set_shape_property(
+            &pin_shape,
+            "mask".into(),
+            PropertyValue::Bytes(vec![0, 255]),
+        );
+        chip.insert_shape(
+            &leaf,
+            &layer,
+            Path::new_extended(vec![Point::new(0, 0), Point::new(10, 0)], 2, 1, 1).into(),
+        );
+
+        let inst = chip.create_cell_instance(&top, &leaf, Some("u1".into()));
+        chip.set_transform(
+            &inst,
+            SimpleTransform::new(true, Angle::R90, 1, Vector::new(100, 200)),
+        );
+        chip.set_cell_instance_property(&inst, "fixed".into(), PropertyValue::SInt(-1));
+        let top_net = chip.create_net(&top, Some("in".into()));
+        let pin_inst = chip.pin_instance(&inst, &a);
+        chip.connect_pin_instance(&pin_inst, Some(top_net));
+        let zero = chip.net_zero(&top);
+        let pin_inst = chip.pin_instance(&inst, &y);
+        chip.connect_pin_instance(&pin_inst, Some(zero));
+
+        chip
+    }
+
+    #[test]
+    fn test_parse_and_write_values() {
+        let text = r#" {"a": [1, -2.5e1, true, null], "bä😀": "x\"\\\n", "c": {}} "#;
+        let value = Parser::new(text).document().unwrap();
+        assert_eq!(
+            value,
+            Json::Object(vec![
+                entry(
+                    "a",
+                    Json::Array(vec![
+                        Json::Int(1),
+                        Json::Float(-25.0),
+                        Json::Bool(true),
+                        Json::Null
+                    ])
+                ),
+                entry("bä😀", Json::String("x\"\\\n".into())),
+                entry("c", Json::Object(vec![])),
+            ])
+        );
+
+        // Compact and indented output can be parsed again.
+        for indent in [None, Some(0)] {
+            let mut out = String::new();
+            value.write(&mut out, indent);
+            assert_eq!(Parser::new(&out).document().unwrap(), value);
+        }
+
+        for invalid in ["", "[1,]", "{\"a\" 1}", "\"abc", "[1] 2", "nul"] {
+            assert!(Parser::new(invalid).document().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_json_instance_arrays() {
+        let mut chip = create_test_chip();
+        let top = chip.cell_by_name("TOP").unwrap();
+        let inst = chip.cell_instance_by_name(&top, "u1").unwrap();
+        let array = InstanceArray::new(8, 4, Vector::new(5, 0), Vector::new(0, -10));
+        chip.set_cell_instance_array(&inst, Some(array));
+
+        let mut buffer = Vec::new();
+        JsonWriter::new().write_json(&mut buffer, &chip).unwrap();
+        let restored: Chip = JsonReader::new().read_json(&mut buffer.as_slice()).unwrap();
+
+        let top = restored.cell_by_name("TOP").unwrap();
+        let inst = restored.cell_instance_by_name(&top, "u1").unwrap();
+        assert_eq!(restored.cell_instance_array(&inst), Some(array));
+    }
+
+    #[test]
+    fn test_json_placement_status() {
+        let mut chip = create_test_chip();
+        let top = chip.cell_by_name("TOP").unwrap();
+        let inst = chip.cell_instance_by_name(&top, "u1").unwrap();
+        chip.set_placement_status(&inst, PlacementStatus::Cover);
+
+        let mut buffer = Vec::new();
+        JsonWriter::new().write_json(&mut buffer, &chip).unwrap();
+        let restored: Chip = JsonReader::new().read_json(&mut buffer.as_slice()).unwrap();
+
+        let top = restored.cell_by_name("TOP").unwrap();
+        let inst = restored.cell_instance_by_name(&top, "u1").unwrap();
+        assert_eq!(restored.placement_status(&inst), PlacementStatus::Cover);
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let chip = create_test_chip();
+
+        let mut buffer = Vec::new();
+        JsonWriter::new().write_json(&mut buffer, &chip).unwrap();
+        let restored: Chip = JsonReader::new().read_json(&mut buffer.as_slice()).unwrap();
+
+        assert_eq!(
+            restored
+                .get_chip_property(&"design".into())
+                .unwrap()
+                .get_str(),
+            Some("test")
+        );
+        let layer = restored.layer_by_name("metal1").unwrap();
+
+        let top = restored.cell_by_name("TOP").unwrap();
+        let leaf = restored.cell_by_name("LEAF").unwrap();
+        assert_eq!(
+            restored
+                .get_cell_property(&leaf, &"area".into())
+                .unwrap()
+                .get_float(),
+            Some(1.5)
+        );
+        assert_eq!(
+            restored
+                .get_cell_property(&leaf, &"note".into())
+                .unwrap()
+                .get_str(),
+            Some("\"q\"\n")
+        );
+
+        // Netlist.
+        let a = restored.pin_by_name(&leaf, "A").unwrap();
+        let y = restored.pin_by_name(&leaf, "Y").unwrap();
+        assert_eq!(restored.pin_direction(&a), Direction::Input);
+        assert_eq!(restored.pin_direction(&y), Direction::Output);
+        let leaf_net = restored.net_by_name(&leaf, "n").unwrap();
+        assert_eq!(restored.net_of_pin(&a), Some(leaf_net));
+        assert_eq!(restored.net_of_pin(&y), Some(restored.net_one(&leaf)));
+
+        let inst = restored.cell_instance_by_name(&top, "u1").unwrap();
+        assert_eq!(
+            restored.get_transform(&inst),
+            SimpleTransform::new(true, Angle::R90, 1, Vector::new(100, 200))
+        );
+        assert_eq!(
+            restored
+                .get_cell_instance_property(&inst, &"fixed".into())
+                .unwrap()
+                .get_sint(),
+            Some(-1)
+        );
+        let top_net = restored.net_by_name(&top, "in").unwrap();
+        assert_eq!(
+            restored.net_of_pin_instance(&restored.pin_instance(&inst, &a)),
+            Some(top_net)
+        );
+        assert_eq!(
+            restored.net_of_pin_instance(&restored.pin_instance(&inst, &y)),
+            Some(restored.net_zero(&top))
+        );
+
+        // Layout and L2N links.
+        let shapes: Vec<_> = restored.each_shape_id(&leaf, &layer).collect();
+        assert_eq!(shapes.len(), 2);
+        let pin_shape = shapes
+            .iter()
+            .find(|s| restored.get_pin_of_shape(s).is_some())
+            .unwrap();
+        assert_eq!(restored.get_pin_of_shape(pin_shape), Some(a));
+        assert_eq!(restored.get_net_of_shape(pin_shape), Some(leaf_net));
+        assert_eq!(
+            restored.get_shape_property(pin_shape, &"mask".into()),
+            Some(PropertyValue::Bytes(vec![0, 255]))
+        );
+        assert_eq!(restored.bounding_box(&leaf), chip.bounding_box(&leaf));
+        assert_eq!(
+            restored.bounding_box(&top),
+            chip.bounding_box(&chip.cell_by_name("TOP").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_json_buses() {
+        let mut chip = create_test_chip();
+        let leaf = chip.cell_by_name("LEAF").unwrap();
+        let a = chip.pin_by_name(&leaf, "A").unwrap();
+        let y = chip.pin_by_name(&leaf, "Y").unwrap();
+        chip.create_pin_bus_from_pins("p".into(), vec![y, a], BitOrder::Ascending);
+        chip.create_net_bus(&leaf, "data".into(), 3, BitOrder::Descending);
+
+        let mut buffer = Vec::new();
+        JsonWriter::new().write_json(&mut buffer, &chip).unwrap();
+        let restored: Chip = JsonReader::new().read_json(&mut buffer.as_slice()).unwrap();
+
+        let leaf = restored.cell_by_name("LEAF").unwrap();
+        let pin_bus = restored.pin_bus_by_name(&leaf, "p").unwrap();
+        assert_eq!(restored.pin_bus_bit_order(&pin_bus), BitOrder::Ascending);
+        assert_eq!(
+            restored.each_pin_of_bus_vec(&pin_bus),
+            vec![
+                restored.pin_by_name(&leaf, "Y").unwrap(),
+                restored.pin_by_name(&leaf, "A").unwrap()
+            ]
+        );
+        let net_bus = restored.net_bus_by_name(&leaf, "data").unwrap();
+        assert_eq!(restored.net_bus_bit_order(&net_bus), BitOrder::Descending);
+        assert_eq!(restored.net_bus_width(&net_bus), 3);
+        assert_eq!(
+            restored.net_bus_bit(&net_bus, 2),
+            restored.net_by_name(&leaf, "data[2]")
+        );
+
+        // Overlapping buses are rejected.
+        let text = r#"{"version": 1, "cells": [{
+            "name": "A",
+            "nets": [{"name": "x"}],
+            "buses": {"nets": [{"name": "b", "bits": [0]}, {"name": "c", "bits": [0]}]}
+        }]}"#;
+        let result: Result<Chip, _> = JsonReader::new().read_json(&mut text.as_bytes());
+        assert!(matches!(result, Err(JsonError::Malformed(_))));
+    }
+
+    #[test]
+    fn test_json_power_domains() {
+        let mut chip = create_test_chip();
+        let top = chip.cell_by_name("TOP").unwrap();
+        let leaf = chip.cell_by_name("LEAF").unwrap();
+        let inst = chip.cell_instance_by_name(&top, "u1").unwrap();
+        let vdd = chip.create_net(&top, Some("VDD".into()));
+        let vss = chip.net_zero(&top);
+        let core = chip.create_power_domain("CORE".into(), vdd, vss, 0.9, true);
+        let io = chip.create_power_domain("IO".into(), vdd, vss, 3.3, false);
+        chip.set_power_domain_of_cell(&leaf, Some(core));
+        chip.set_power_domain_of_cell_instance(&inst, Some(io));
+
+        let mut buffer = Vec::new();
+        JsonWriter::new().write_json(&mut buffer, &chip).unwrap();
+        let restored: Chip = JsonReader::new().read_json(&mut buffer.as_slice()).unwrap();
+
+        let top = restored.cell_by_name("TOP").unwrap();
+        let leaf = restored.cell_by_name("LEAF").unwrap();
+        let inst = restored.cell_instance_by_name(&top, "u1").unwrap();
+        let core = restored.power_domain_by_name("CORE").unwrap();
+        let io = restored.power_domain_by_name("IO").unwrap();
+        assert_eq!(restored.parent_cell_of_power_domain(&core), top);
+        assert_eq!(
+            restored.power_domain_supply_net(&core),
+            restored.net_by_name(&top, "VDD").unwrap()
+        );
+        assert_eq!(
+            restored.power_domain_ground_net(&io),
+            restored.net_zero(&top)
+        );
+        assert_eq!(restored.power_domain_voltage(&io), 3.3);
+        assert!(restored.power_domain_is_always_on(&core));
+        assert_eq!(restored.power_domain_of_cell(&leaf), Some(core));
+        assert_eq!(restored.power_domain_of_cell(&top), None);
+        assert_eq!(
+            restored.assigned_power_domain_of_cell_instance(&inst),
+            Some(io)
+        );
+
+        // Assignments to unknown domains are rejected.
+        let text = r#"{"version": 1, "cells": [{"name": "A", "power_domain": "X"}]}"#;
+        let result: Result<Chip, _> = JsonReader::new().read_json(&mut text.as_bytes());
+        assert!(matches!(result, Err(JsonError::Malformed(_))));
+    }
+
+    #[test]
+    fn test_read_handwritten_document() {
+        // Minimal document as it could be produced by a script. The template is defined after its use.
+        let text = r#"{
+            "version": 1,
+            "layers": [{"index": 2, "datatype": 0}],
+            "cells": [
+                {
+                    "name": "TOP",
+                    "nets": [{"name": "x"}],
+                    "instances": [
+                        {"template": "BUF", "connections": {"I": 0}, "transform": {"displacement": [5, 0]}}
+                    ]
+                },
+                {
+                    "name": "BUF",
+                    "pins": [{"name": "I", "direction": "input"}],
+                    "shapes": [
+                        {"layer": 0, "geometry": {"type": "polygon", "points": [[0, 0], [2, 0], [0, 2]]}}
+                    ]
+                }
+            ]
+        }"#;
+        let chip: Chip = JsonReader::new().read_json(&mut text.as_bytes()).unwrap();
+
+        let top = chip.cell_by_name("TOP").unwrap();
+        let buf = chip.cell_by_name("BUF").unwrap();
+        let pin = chip.pin_by_name(&buf, "I").unwrap();
+        let inst = chip.each_cell_instance_vec(&top)[0].clone();
+        assert_eq!(
+            chip.net_of_pin_instance(&chip.pin_instance(&inst, &pin)),
+            chip.net_by_name(&top, "x")
+        );
+        assert_eq!(chip.bounding_box(&top), Some(Rect::new((5, 0), (7, 2))));
+
+        // Errors in the schema are reported.
+        let result: Result<Chip, _> =
+            JsonReader::new().read_json(&mut r#"{"version": 2}"#.as_bytes());
+        assert!(matches!(result, Err(JsonError::UnsupportedVersion(2))));
+        let result: Result<Chip, _> = JsonReader::new().read_json(
+            &mut r#"{"version": 1, "cells": [{"name": "A", "instances": [{"template": "B"}]}]}"#
+                .as_bytes(),
+        );
+        assert!(matches!(result, Err(JsonError::Malformed(_))));
+
+        // Recursive and duplicate instances.
+        let documents = [
+            r#"{"version": 1, "cells": [{"name": "A", "instances": [{"template": "A"}]}]}"#,
+            r#"{"version": 1, "cells": [
+                {"name": "A", "instances": [{"template": "B"}]},
+                {"name": "B", "instances": [{"template": "A"}]}
+            ]}"#,
+            r#"{"version": 1, "cells": [
+                {"name": "A", "instances": [{"template": "B", "name": "b"}, {"template": "B", "name": "b"}]},
+                {"name": "B"}
+            ]}"#,
+        ];
+        for document in documents {
+            let result: Result<Chip, _> = JsonReader::new().read_json(&mut document.as_bytes());
+            assert!(matches!(result, Err(JsonError::Malformed(_))));
+        }
+    }
+
+    #[test]
+    fn test_netlist_and_layout_only() {
+        let chip = create_test_chip();
+        let writer = JsonWriter::new().pretty(false);
+
+        // A full document can be read as a netlist. The layout entries are ignored.
+        let mut buffer = Vec::new();
+        writer.write_json(&mut buffer, &chip).unwrap();
+        assert_eq!(buffer.iter().filter(|&&b| b == b'\n').count(), 1);
+        let netlist: Chip = JsonReader::new()
+            .read_netlist(&mut buffer.as_slice())
+            .unwrap();
+        let leaf = netlist.cell_by_name("LEAF").unwrap();
+        assert_eq!(netlist.num_pins(&leaf), 2);
+        assert_eq!(netlist.each_layer().count(), 0);
+
+        let mut buffer = Vec::new();
+        writer.write_layout(&mut buffer, &chip).unwrap();
+        let mut layout = Chip::new();
+        JsonReader::new()
+            .read_layout(&mut buffer.as_slice(), &mut layout)
+            .unwrap();
+        let leaf = layout.cell_by_name("LEAF").unwrap();
+        assert_eq!(layout.num_pins(&leaf), 0);
+        assert_eq!(
+            layout.bounding_box(&leaf),
+            Some(Rect::new((-1, -1), (11, 1)))
+        );
+    }
+}
@@ -0,0 +1,1768 @@
+// SPDX-FileCopyrightText: 2022 Thomas Kramer
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Reader for LEF libraries and reader and writer for DEF designs.
+//!
+//! LEF macros are created as cells. Their pins are created as netlist pins and the pin shapes
+//! are linked to the pins with [`L2NEdit::set_pin_of_shape`]. Obstructions become shapes
+//! which are not linked to anything. The macro origin is moved to the lower left corner of the macro.
+//! The size of the macro is stored in the cell properties [`MACRO_WIDTH_PROPERTY`] and [`MACRO_HEIGHT_PROPERTY`].
+//!
+//! Layers are identified by their names. Layers which do not exist yet are created
+//! with the next free layer number.
+//!
+//! Technology information which is needed for reading DEF files, namely default wire widths and via
+//! definitions, is collected in a [`LefLibrary`].
+//!
+//! DEF designs are read into a new cell:
+//! * `COMPONENTS` become cell instances. The placement status is stored as the [`PlacementStatus`]
+//! of the instance.
+//! * `PINS` become pins of the design cell. Pin shapes are linked to the pins.
+//! * `NETS` and `SPECIALNETS` become nets. The wiring is converted into paths, rectangles and polygons
+//! which are linked to the nets with [`L2NEdit::set_net_of_shape`]. Vias are flattened into their shapes.
+//! * The `DIEAREA` is stored as a shape on the layer [`DIE_AREA_LAYER`].
+//!
+//! Other sections like rows, tracks, blockages or regions are skipped.
+//!
+//! The DEF writer exports the placement, the pins and the connectivity. All wiring
+//! is written to the `SPECIALNETS` section because only there the wire widths can be stored explicitly.
+//!
+//! # Example
+//!
+//! ```
+//! use libreda_db::prelude::*;
+//! use libreda_db::l2n::io::lefdef::{DefReader, LefLibrary, LefReader};
+//!
+//! let lef = r#"
+//!     UNITS DATABASE MICRONS 1000 ; END UNITS
+//!     LAYER metal1 TYPE ROUTING ; WIDTH 0.1 ; END metal1
+//!     MACRO INV
+//!         SIZE 1 BY 2 ;
+//!         PIN A DIRECTION INPUT ; PORT LAYER metal1 ; RECT 0.1 0.1 0.3 0.3 ; END END A
+//!         PIN Y DIRECTION OUTPUT ; PORT LAYER metal1 ; RECT 0.6 0.1 0.8 0.3 ; END END Y
+//!     END INV
+//!     END LIBRARY
+//! "#;
+//!
+//! let def = r#"
+//!     DESIGN top ;
+//!     UNITS DISTANCE MICRONS 1000 ;
+//!     COMPONENTS 1 ;
+//!         - inv1 INV + PLACED ( 0 0 ) N ;
+//!     END COMPONENTS
+//!     NETS 1 ;
+//!         - a ( inv1 A ) + ROUTED metal1 ( 200 200 ) ( 200 5000 ) ;
+//!     END NETS
+//!     END DESIGN
+//! "#;
+//!
+//! let mut chip = Chip::new();
+//! let mut library = LefLibrary::new();
+//! LefReader::new().read_lef(&mut lef.as_bytes(), &mut chip, &mut library).unwrap();
+//! let top = DefReader::new().read_def(&mut def.as_bytes(), &mut chip, &library).unwrap();
+//!
+//! let net = chip.net_by_name(&top, "a").unwrap();
+//! assert_eq!(chip.num_net_pin_instances(&net), 1);
+//! assert_eq!(chip.shapes_of_net(&net).count(), 1);
+//! ```
+
+use crate::prelude::*;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+/// Cell property which holds the width of a LEF macro in database units.
+pub const MACRO_WIDTH_PROPERTY: &str = "lef_width";
+/// Cell property which holds the height of a LEF macro in database units.
+pub const MACRO_HEIGHT_PROPERTY: &str = "lef_height";
+/// Cell property which holds the `CLASS` of a LEF macro.
+pub const MACRO_CLASS_PROPERTY: &str = "lef_class";
+/// Name of the layer which holds the die area of a DEF design.
+pub const DIE_AREA_LAYER: &str = "DIEAREA";
+
+/// DEF orientations and the corresponding mirroring (at the x-axis) and rotation.
+const ORIENTATIONS: [(&str, bool, Angle); 8] = [
+    ("N", false, Angle::R0),
+    ("W", false, Angle::R90),
+    ("S", false, Angle::R180),
+    ("E", false, Angle::R270),
+    ("FS", true, Angle::R0),
+    ("FW", true, Angle::R90),
+    ("FN", true, Angle::R180),
+    ("FE", true, Angle::R270),
+];
+
+/// Error type used for reading and writing LEF and DEF files.
+#[derive(Debug)]
+pub enum LefDefError {
+    /// Error of the underlying byte stream.
+    Io(std::io::Error),
+    /// The input is not valid LEF or DEF.
+    Parse {
+        /// Line number where the error occurred.
+        line: usize,
+        /// Description of the error.
+        message: String,
+    },
+    /// A component or via refers to a macro or via which is not defined.
+    UnknownMacro(String),
+    /// A macro or design is defined but a cell with this name exists already.
+    CellNameCollision(String),
+    /// The content of the data base cannot be represented, for example a cell instance
+    /// with a magnification.
+    Invalid(String),
+}
+
+impl std::fmt::Display for LefDefError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LefDefError::Io(err) => write!(f, "IO error: {}", err),
+            LefDefError::Parse { line, message } => {
+                write!(f, "Parse error in line {}: {}", line, message)
+            }
+            LefDefError::UnknownMacro(name) => write!(f, "Macro '{}' is not defined.", name),
+            LefDefError::CellNameCollision(name) => {
+                write!(f, "Cell '{}' already exists.", name)
+            }
+            LefDefError::Invalid(message) => write!(f, "Invalid data: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for LefDefError {}
+
+impl From<std::io::Error> for LefDefError {
+    fn from(err: std::io::Error) -> Self {
+        LefDefError::Io(err)
+    }
+}
+
+/// Technology information from LEF files which is needed to read DEF files.
+#[derive(Debug, Clone, Default)]
+pub struct LefLibrary {
+    /// Default wire widths of routing layers in database units.
+    wire_widths: HashMap<String, i32>,
+    /// Shapes of the vias in database units.
+    vias: HashMap<String, Vec<(String, Geometry<i32>)>>,
+}
+
+impl LefLibrary {
+    /// Create an empty library.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the default wire width of a routing layer in database units.
+    pub fn wire_width(&self, layer: &str) -> Option<i32> {
+        self.wire_widths.get(layer).copied()
+    }
+
+    /// Check if a via with this name is defined.
+    pub fn has_via(&self, name: &str) -> bool {
+        self.vias.contains_key(name)
+    }
+}
+
+/// Split LEF or DEF source into tokens together with their line numbers.
+/// Quoted strings are kept as a single token including the quotes.
+fn tokenize(source: &str) -> Vec<(String, usize)> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut line = 1;
+    let mut in_quote = false;
+    let mut in_comment = false;
+    for c in source.chars() {
+        if in_comment || in_quote {
+            if in_quote {
+                current.push(c);
+                in_quote = c != '"';
+            }
+            if c == '\n' {
+                line += 1;
+                in_comment = false;
+            }
+            continue;
+        }
+        match c {
+            '"' => {
+                current.push(c);
+                in_quote = true;
+            }
+            '#' if current.is_empty() => in_comment = true,
+            ';' | '(' | ')' => {
+                if !current.is_empty() {
+                    tokens.push((std::mem::take(&mut current), line));
+                }
+                tokens.push((c.to_string(), line));
+            }
+            c if c.is_whitespace() => {
+                if !current.is_empty() {
+                    tokens.push((std::mem::take(&mut current), line));
+                }
+                if c == '\n' {
+                    line += 1;
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push((current, line));
+    }
+    tokens
+}
+
+struct Parser {
+    tokens: Vec<(String, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn new(source: &str) -> Self {
+        Self {
+            tokens: tokenize(source),
+            pos: 0,
+        }
+    }
+
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or_else(|| self.tokens.last())
+            .map_or(0, |(_, line)| *line)
+    }
+
+    fn error<T>(&self, message: &str) -> Result<T, LefDefError> {
+        Err(LefDefError::Parse {
+            line: self.line(),
+            message: message.to_string(),
+        })
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.tokens.len()
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(|(t, _)| t.as_str())
+    }
+
+    fn peek_is(&self, token: &str) -> bool {
+        self.peek() == Some(token)
+    }
+
+    fn next(&mut self) -> Result<String, LefDefError> {
+        match self.tokens.get(self.pos) {
+            Some((t, _)) => {
+                self.pos += 1;
+                Ok(t.clone())
+            }
+            None => self.error("unexpected end of file"),
+        }
+    }
+
+    /// Consume the token if it is the next one.
+    fn accept(&mut self, token: &str) -> bool {
+        let found = self.peek_is(token);
+        self.pos += found as usize;
+        found
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), LefDefError> {
+        if self.accept(token) {
+            Ok(())
+        } else {
+            self.error(&format!("expected '{}'", token))
+        }
+    }
+
+    fn expect_number(&mut self) -> Result<f64, LefDefError> {
+        let token = self.next()?;
+        match token.parse() {
+            Ok(value) => Ok(value),
+            Err(_) => {
+                self.pos -= 1;
+                self.error("expected number")
+            }
+        }
+    }
+
+    /// Skip tokens until after the next `;`.
+    fn skip_statement(&mut self) -> Result<(), LefDefError> {
+        while self.next()? != ";" {}
+        Ok(())
+    }
+
+    /// Skip tokens until after `END name`.
+    fn skip_block(&mut self, name: &str) -> Result<(), LefDefError> {
+        while !(self.next()? == "END" && self.accept(name)) {}
+        Ok(())
+    }
+
+    /// Read the remaining numbers of a statement including the terminating `;`.
+    /// Parentheses are ignored.
+    fn numbers_until_semicolon(&mut self) -> Result<Vec<f64>, LefDefError> {
+        let mut numbers = Vec::new();
+        loop {
+            match self.peek() {
+                Some(";") => {
+                    self.pos += 1;
+                    return Ok(numbers);
+                }
+                Some("(") | Some(")") => self.pos += 1,
+                _ => numbers.push(self.expect_number()?),
+            }
+        }
+    }
+}
+
+/// Find a layer by name or create it with the next free layer number.
+fn layer_by_name<L: LayoutEdit>(layout: &mut L, name: &str) -> L::LayerId {
+    if let Some(layer) = layout.layer_by_name(name) {
+        return layer;
+    }
+    let index = layout
+        .each_layer()
+        .map(|l| layout.layer_info(&l).index + 1)
+        .max()
+        .unwrap_or(1);
+    let layer = layout.create_layer(index, 0);
+    layout.set_layer_name(&layer, Some(name.to_string().into()));
+    layer
+}
+
+/// Name of a layer as used in LEF and DEF.
+fn layer_name<L: LayoutBase>(layout: &L, layer: &L::LayerId) -> String {
+    let info = layout.layer_info(layer);
+    match info.name {
+        Some(name) => name.to_string(),
+        None => format!("L{}D{}", info.index, info.datatype),
+    }
+}
+
+/// Create a rectangle or a polygon from a list of coordinates `x1 y1 x2 y2 ...`.
+fn geometry_from_coordinates(coordinates: &[i32]) -> Option<Geometry<i32>> {
+    let points: Vec<Point<i32>> = coordinates
+        .chunks_exact(2)
+        .map(|c| Point::new(c[0], c[1]))
+        .collect();
+    match points.len() {
+        0 | 1 => None,
+        2 => Some(Rect::new(points[0], points[1]).into()),
+        _ => Some(SimplePolygon::new(points).into()),
+    }
+}
+
+/// Get the size of a macro. Falls back to the bounding box if the size is not stored in the properties.
+fn macro_size<L: LayoutBase<Coord = i32>>(layout: &L, cell: &L::CellId) -> (i32, i32) {
+    let dimension = |key: &str| match layout.get_cell_property(cell, &key.to_string().into()) {
+        Some(PropertyValue::SInt(value)) => Some(value),
+        _ => None,
+    };
+    match (
+        dimension(MACRO_WIDTH_PROPERTY),
+        dimension(MACRO_HEIGHT_PROPERTY),
+    ) {
+        (Some(w), Some(h)) => (w, h),
+        _ => layout
+            .bounding_box(cell)
+            .map(|r| (r.upper_right().x, r.upper_right().y))
+            .unwrap_or((0, 0)),
+    }
+}
+
+/// Create the transformation of a component. The lower left corner of the
+/// rotated macro is placed at `location`.
+fn component_transform(
+    orientation: &str,
+    location: Point<i32>,
+    (w, h): (i32, i32),
+) -> Option<SimpleTransform<i32>> {
+    let &(_, mirror, rotation) = ORIENTATIONS.iter().find(|(o, _, _)| *o == orientation)?;
+    let tf = SimpleTransform::new(mirror, rotation, 1, Vector::new(0, 0));
+    let lower_left = Rect::new((0, 0), (w, h))
+        .transform(|p| tf.transform_point(p))
+        .lower_left();
+    Some(SimpleTransform::new(
+        mirror,
+        rotation,
+        1,
+        location - lower_left,
+    ))
+}
+
+/// Pin direction from the LEF/DEF `DIRECTION` and `USE` keywords.
+fn pin_direction(direction: Option<&str>, usage: Option<&str>) -> Direction {
+    match (direction, usage) {
+        (_, Some("POWER")) => Direction::Supply,
+        (_, Some("GROUND")) => Direction::Ground,
+        (_, Some("CLOCK")) => Direction::Clock,
+        (Some("INPUT"), _) => Direction::Input,
+        (Some("OUTPUT"), _) => Direction::Output,
+        (Some("INOUT"), _) | (Some("FEEDTHRU"), _) => Direction::InOut,
+        _ => Direction::None,
+    }
+}
+
+/// Read LEF files.
+#[derive(Debug, Clone, Default)]
+pub struct LefReader {}
+
+impl LefReader {
+    /// Create a LEF reader with default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read a LEF file. Macros are created as cells in `chip`. Layer and via
+    /// definitions are added to `library`.
+    ///
+    /// The database unit of `chip` is set by the `UNITS DATABASE MICRONS` statement.
+    /// If the statement is missing, the current database unit is used.
+    pub fn read_lef<R: Read, LN: L2NEdit<Coord = i32>>(
+        &self,
+        reader: &mut R,
+        chip: &mut LN,
+        library: &mut LefLibrary,
+    ) -> Result<(), LefDefError> {
+        let mut source = String::new();
+        reader.read_to_string(&mut source)?;
+        let mut lef = LefParser {
+            parser: Parser::new(&source),
+            dbu: chip.dbu() as f64,
+        };
+        lef.parse(chip, library)
+    }
+}
+
+/// Geometries of a LEF `PORT`, `OBS` or `VIA`: layer names and shapes.
+type Shapes = Vec<(String, Geometry<i32>)>;
+
+struct LefParser {
+    parser: Parser,
+    /// Database units per micron.
+    dbu: f64,
+}
+
+impl LefParser {
+    /// Convert microns into database units.
+    fn to_dbu(&self, value: f64) -> i32 {
+        (value * self.dbu).round() as i32
+    }
+
+    fn parse<LN: L2NEdit<Coord = i32>>(
+        &mut self,
+        chip: &mut LN,
+        library: &mut LefLibrary,
+    ) -> Result<(), LefDefError> {
+        while !self.parser.at_end() {
+            let keyword = self.parser.next()?;
+            match keyword.as_str() {
+                "UNITS" => {
+                    while !self.parser.accept("END") {
+                        if self.parser.accept("DATABASE") {
+                            self.parser.expect("MICRONS")?;
+                            let dbu = self.parser.expect_number()?;
+                            self.parser.expect(";")?;
+                            chip.set_dbu(dbu as i32);
+                            self.dbu = dbu;
+                        } else {
+                            self.parser.skip_statement()?;
+                        }
+                    }
+                    self.parser.expect("UNITS")?;
+                }
+                "LAYER" => {
+                    let name = self.parser.next()?;
+                    layer_by_name(chip, &name);
+                    while !(self.parser.accept("END") && self.parser.accept(&name)) {
+                        if self.parser.accept("WIDTH") {
+                            let width = self.parser.expect_number()?;
+                            library.wire_widths.insert(name.clone(), self.to_dbu(width));
+                        }
+                        self.parser.skip_statement()?;
+                    }
+                }
+                "VIA" => {
+                    let name = self.parser.next()?;
+                    // Optional keywords.
+                    self.parser.accept("DEFAULT");
+                    self.parser.accept("GENERATED");
+                    let shapes = self.geometries(&name, library)?;
+                    library.vias.insert(name, shapes);
+                }
+                "MACRO" => self.parse_macro(chip, library)?,
+                "VIARULE" | "SITE" | "NONDEFAULTRULE" => {
+                    let name = self.parser.next()?;
+                    self.parser.skip_block(&name)?;
+                }
+                "SPACING" | "PROPERTYDEFINITIONS" => self.parser.skip_block(&keyword)?,
+                "BEGINEXT" => while self.parser.next()? != "ENDEXT" {},
+                "END" => {
+                    self.parser.expect("LIBRARY")?;
+                    break;
+                }
+                _ => self.parser.skip_statement()?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Read geometry statements until `END` or `END end_name` if `end_name` is not empty.
+    fn geometries(&mut self, end_name: &str, library: &LefLibrary) -> Result<Shapes, LefDefError> {
+        let mut shapes = Vec::new();
+        let mut layer: Option<String> = None;
+        let mut width = 0;
+        loop {
+            let keyword = self.parser.next()?;
+            match keyword.as_str() {
+                "END" => {
+                    if end_name.is_empty() || self.parser.accept(end_name) {
+                        return Ok(shapes);
+                    }
+                    return self.parser.error(&format!("expected 'END {}'", end_name));
+                }
+                "LAYER" => {
+                    layer = Some(self.parser.next()?);
+                    self.parser.skip_statement()?;
+                }
+                "WIDTH" => {
+                    width = self.to_dbu(self.parser.expect_number()?);
+                    self.parser.expect(";")?;
+                }
+                "RECT" | "POLYGON" | "PATH" | "VIA" => {
+                    if self.parser.accept("MASK") {
+                        self.parser.next()?;
+                    }
+                    if self.parser.peek_is("ITERATE") {
+                        log::warn!("Arrays of LEF shapes (ITERATE) are not supported.");
+                        self.parser.skip_statement()?;
+                        continue;
+                    }
+                    if keyword == "VIA" {
+                        let x = self.to_dbu(self.parser.expect_number()?);
+                        let y = self.to_dbu(self.parser.expect_number()?);
+                        let via = self.parser.next()?;
+                        self.parser.expect(";")?;
+                        let via_shapes = library
+                            .vias
+                            .get(&via)
+                            .ok_or(LefDefError::UnknownMacro(via))?;
+                        let offset = Vector::new(x, y);
+                        shapes.extend(
+                            via_shapes
+                                .iter()
+                                .map(|(l, g)| (l.clone(), g.transform(|p| p + offset))),
+                        );
+                        continue;
+                    }
+                    let layer = match &layer {
+                        Some(layer) => layer.clone(),
+                        None => return self.parser.error("shape without layer"),
+                    };
+                    let coordinates: Vec<i32> = self
+                        .parser
+                        .numbers_until_semicolon()?
+                        .into_iter()
+                        .map(|v| self.to_dbu(v))
+                        .collect();
+                    let geometry = if keyword == "PATH" {
+                        let points = coordinates
+                            .chunks_exact(2)
+                            .map(|c| Point::new(c[0], c[1]))
+                            .collect();
+                        // LEF paths are extended by half of the width.
+                        Some(Path::new_extended(points, width, width / 2, width / 2).into())
+                    } else {
+                        geometry_from_coordinates(&coordinates)
+                    };
+                    match geometry {
+                        Some(geometry) => shapes.push((layer, geometry)),
+                        None => return self.parser.error("not enough coordinates"),
+                    }
+                }
+                _ => self.parser.skip_statement()?,
+            }
+        }
+    }
+
+    fn parse_macro<LN: L2NEdit<Coord = i32>>(
+        &mut self,
+        chip: &mut LN,
+        library: &LefLibrary,
+    ) -> Result<(), LefDefError> {
+        let name = self.parser.next()?;
+        if chip.cell_by_name(&name).is_some() {
+            return Err(LefDefError::CellNameCollision(name));
+        }
+        let mut class = None;
+        let mut origin = Vector::new(0, 0);
+        let mut size = None;
+        let mut pins = Vec::new();
+        let mut obstructions = Vec::new();
+
+        while !(self.parser.accept("END") && self.parser.accept(&name)) {
+            let keyword = self.parser.next()?;
+            match keyword.as_str() {
+                "CLASS" => {
+                    let mut words = Vec::new();
+                    while !self.parser.accept(";") {
+                        words.push(self.parser.next()?);
+                    }
+                    class = Some(words.join(" "));
+                }
+                "ORIGIN" => {
+                    let coordinates = self.parser.numbers_until_semicolon()?;
+                    if let [x, y] = coordinates[..] {
+                        origin = Vector::new(self.to_dbu(x), self.to_dbu(y));
+                    }
+                }
+                "SIZE" => {
+                    let w = self.to_dbu(self.parser.expect_number()?);
+                    self.parser.expect("BY")?;
+                    let h = self.to_dbu(self.parser.expect_number()?);
+                    self.parser.expect(";")?;
+                    size = Some((w, h));
+                }
+                "PIN" => {
+                    let pin_name = self.parser.next()?;
+                    let mut direction = None;
+                    let mut usage = None;
+                    let mut shapes = Vec::new();
+                    while !(self.parser.accept("END") && self.parser.accept(&pin_name)) {
+                        let keyword = self.parser.next()?;
+                        match keyword.as_str() {
+                            "DIRECTION" => direction = Some(self.parser.next()?),
+                            "USE" => usage = Some(self.parser.next()?),
+                            "PORT" => {
+                                shapes.extend(self.geometries("", library)?);
+                                continue;
+                            }
+                            _ => {}
+                        }
+                        self.parser.skip_statement()?;
+                    }
+                    let direction = pin_direction(direction.as_deref(), usage.as_deref());
+                    pins.push((pin_name, direction, shapes));
+                }
+                "OBS" => obstructions.extend(self.geometries("", library)?),
+                "DENSITY" => {
+                    self.geometries("", library)?;
+                }
+                _ => self.parser.skip_statement()?,
+            }
+        }
+
+        let cell = chip.create_cell(name.clone().into());
+        if let Some(class) = class {
+            chip.set_cell_property(&cell, MACRO_CLASS_PROPERTY.to_string().into(), class.into());
+        }
+        if let Some((w, h)) = size {
+            chip.set_cell_property(&cell, MACRO_WIDTH_PROPERTY.to_string().into(), w.into());
+            chip.set_cell_property(&cell, MACRO_HEIGHT_PROPERTY.to_string().into(), h.into());
+        }
+
+        // Move the origin to the lower left corner of the macro.
+        let insert_shape = |chip: &mut LN, layer: &str, geometry: &Geometry<i32>| {
+            let layer = layer_by_name(chip, layer);
+            chip.insert_shape(&cell, &layer, geometry.transform(|p| p + origin))
+        };
+
+        for (pin_name, direction, shapes) in pins {
+            let pin = chip.create_pin(&cell, pin_name.into(), direction);
+            for (layer, geometry) in &shapes {
+                let shape = insert_shape(chip, layer, geometry);
+                chip.set_pin_of_shape(&shape, Some(pin.clone()));
+            }
+        }
+        for (layer, geometry) in &obstructions {
+            insert_shape(chip, layer, geometry);
+        }
+
+        Ok(())
+    }
+}
+
+/// Read DEF files.
+#[derive(Debug, Clone, Default)]
+pub struct DefReader {}
+
+impl DefReader {
+    /// Create a DEF reader with default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read a DEF design into a new cell of `chip` and return the cell.
+    ///
+    /// All macros used by components must exist already as cells, usually they are
+    /// read with [`LefReader`] before. Coordinates are converted from the DEF distance unit to the
+    /// database unit of `chip`.
+    pub fn read_def<R: Read, LN: L2NEdit<Coord = i32>>(
+        &self,
+        reader: &mut R,
+        chip: &mut LN,
+        library: &LefLibrary,
+    ) -> Result<LN::CellId, LefDefError> {
+        let mut source = String::new();
+        reader.read_to_string(&mut source)?;
+        let mut def = DefParser {
+            parser: Parser::new(&source),
+            scale: 1.0,
+            library,
+            vias: HashMap::new(),
+        };
+        def.parse(chip)
+    }
+}
+
+struct DefParser<'a> {
+    parser: Parser,
+    /// Database units per DEF distance unit.
+    scale: f64,
+    library: &'a LefLibrary,
+    /// Vias defined in the `VIAS` section.
+    vias: HashMap<String, Shapes>,
+}
+
+impl<'a> DefParser<'a> {
+    fn to_dbu(&self, value: f64) -> i32 {
+        (value * self.scale).round() as i32
+    }
+
+    fn via(&self, name: &str) -> Option<&Shapes> {
+        self.vias.get(name).or_else(|| self.library.vias.get(name))
+    }
+
+    fn parse<LN: L2NEdit<Coord = i32>>(
+        &mut self,
+        chip: &mut LN,
+    ) -> Result<LN::CellId, LefDefError> {
+        let mut top = None;
+        while !self.parser.at_end() {
+            let keyword = self.parser.next()?;
+            if keyword == "DESIGN" {
+                let name = self.parser.next()?;
+                self.parser.expect(";")?;
+                if chip.cell_by_name(&name).is_some() {
+                    return Err(LefDefError::CellNameCollision(name));
+                }
+                top = Some(chip.create_cell(name.into()));
+                continue;
+            }
+            if keyword == "UNITS" {
+                self.parser.expect("DISTANCE")?;
+                self.parser.expect("MICRONS")?;
+                let units = self.parser.expect_number()?;
+                self.parser.expect(";")?;
+                self.scale = chip.dbu() as f64 / units;
+                continue;
+            }
+            let sections = [
+                "DIEAREA",
+                "VIAS",
+                "COMPONENTS",
+                "PINS",
+                "NETS",
+                "SPECIALNETS",
+            ];
+            if !sections.contains(&keyword.as_str()) {
+                match keyword.as_str() {
+                    "END" => {
+                        self.parser.expect("DESIGN")?;
+                        break;
+                    }
+                    "PROPERTYDEFINITIONS" => self.parser.skip_block(&keyword)?,
+                    "BEGINEXT" => while self.parser.next()? != "ENDEXT" {},
+                    _ => {
+                        // Skip other sections (`BLOCKAGES 3 ; ... END BLOCKAGES`) and statements.
+                        let next_tokens: Vec<&str> = self.parser.tokens[self.parser.pos..]
+                            .iter()
+                            .take(2)
+                            .map(|(t, _)| t.as_str())
+                            .collect();
+                        let is_section = matches!(next_tokens[..], [count, ";"] if count.parse::<usize>().is_ok());
+                        if is_section {
+                            self.parser.skip_block(&keyword)?;
+                        } else {
+                            self.parser.skip_statement()?;
+                        }
+                    }
+                }
+                continue;
+            }
+
+            let top = match &top {
+                Some(top) => top.clone(),
+                None => return self.parser.error("missing DESIGN statement"),
+            };
+            if keyword == "DIEAREA" {
+                let coordinates: Vec<i32> = self
+                    .parser
+                    .numbers_until_semicolon()?
+                    .into_iter()
+                    .map(|v| self.to_dbu(v))
+                    .collect();
+                if let Some(geometry) = geometry_from_coordinates(&coordinates) {
+                    let layer = layer_by_name(chip, DIE_AREA_LAYER);
+                    chip.insert_shape(&top, &layer, geometry);
+                }
+                continue;
+            }
+
+            // Sections with entries: `KEYWORD count ; - ... ; - ... ; END KEYWORD`
+            self.parser.skip_statement()?;
+            while !(self.parser.accept("END") && self.parser.accept(&keyword)) {
+                self.parser.expect("-")?;
+                match keyword.as_str() {
+                    "VIAS" => self.parse_via()?,
+                    "COMPONENTS" => self.parse_component(chip, &top)?,
+                    "PINS" => self.parse_pin(chip, &top)?,
+                    _ => self.parse_net(chip, &top, keyword == "SPECIALNETS")?,
+                }
+            }
+        }
+        match top {
+            Some(top) => Ok(top),
+            None => self.parser.error("missing DESIGN statement"),
+        }
+    }
+
+    /// Read a point `( x y [ext] )`. A `*` takes the coordinate of the previous point.
+    fn point(
+        &mut self,
+        previous: Option<Point<i32>>,
+    ) -> Result<(Point<i32>, Option<i32>), LefDefError> {
+        self.parser.expect("(")?;
+        let scale = self.scale;
+        let coordinate = |parser: &mut Parser, previous: Option<i32>| {
+            if parser.accept("*") {
+                match previous {
+                    Some(previous) => Ok(previous),
+                    None => parser.error("'*' without previous point"),
+                }
+            } else {
+                parser.expect_number().map(|v| (v * scale).round() as i32)
+            }
+        };
+        let x = coordinate(&mut self.parser, previous.map(|p| p.x))?;
+        let y = coordinate(&mut self.parser, previous.map(|p| p.y))?;
+        let extension = if self.parser.accept(")") {
+            None
+        } else {
+            let extension = self.to_dbu(self.parser.expect_number()?);
+            self.parser.expect(")")?;
+            Some(extension)
+        };
+        Ok((Point::new(x, y), extension))
+    }
+
+    /// Read points until the next token is not an opening parenthesis.
+    fn points(&mut self) -> Result<Vec<Point<i32>>, LefDefError> {
+        let mut points: Vec<Point<i32>> = Vec::new();
+        while self.parser.peek_is("(") {
+            let (point, _) = self.point(points.last().copied())?;
+            points.push(point);
+        }
+        Ok(points)
+    }
+
+    /// Read `layer [MASK n] [SPACING d | DESIGNRULEWIDTH d] points`.
+    fn layer_shape(&mut self) -> Result<(String, Geometry<i32>), LefDefError> {
+        let layer = self.parser.next()?;
+        while ["MASK", "SPACING", "DESIGNRULEWIDTH"].contains(&self.parser.peek().unwrap_or("")) {
+            self.parser.next()?;
+            self.parser.next()?;
+        }
+        let coordinates: Vec<i32> = self.points()?.iter().flat_map(|p| [p.x, p.y]).collect();
+        match geometry_from_coordinates(&coordinates) {
+            Some(geometry) => Ok((layer, geometry)),
+            None => self.parser.error("not enough points"),
+        }
+    }
+
+    /// Skip tokens of an option until the next `+` or `;`.
+    fn skip_option(&mut self) -> Result<(), LefDefError> {
+        while !self.parser.peek_is("+") && !self.parser.peek_is(";") {
+            self.parser.next()?;
+        }
+        Ok(())
+    }
+
+    fn parse_via(&mut self) -> Result<(), LefDefError> {
+        let name = self.parser.next()?;
+        let mut shapes = Vec::new();
+        while !self.parser.accept(";") {
+            self.parser.expect("+")?;
+            match self.parser.next()?.as_str() {
+                "RECT" | "POLYGON" => shapes.push(self.layer_shape()?),
+                "VIARULE" => {
+                    log::warn!(
+                        "Via '{}' is generated by a via rule. This is not supported.",
+                        name
+                    );
+                    self.skip_option()?;
+                }
+                _ => self.skip_option()?,
+            }
+        }
+        self.vias.insert(name, shapes);
+        Ok(())
+    }
+
+    fn parse_component<LN: L2NEdit<Coord = i32>>(
+        &mut self,
+        chip: &mut LN,
+        top: &LN::CellId,
+    ) -> Result<(), LefDefError> {
+        let name = self.parser.next()?;
+        let model = self.parser.next()?;
+        let template = chip
+            .cell_by_name(&model)
+            .ok_or(LefDefError::UnknownMacro(model))?;
+        if chip.cell_instance_by_name(top, &name).is_some() {
+            return self
+                .parser
+                .error(&format!("component '{}' is defined more than once", name));
+        }
+        if chip.cell_depends_on(&template, top) {
+            return self
+                .parser
+                .error("the design cannot be used as a component");
+        }
+        let inst = chip.create_cell_instance(top, &template, Some(name.into()));
+        while !self.parser.accept(";") {
+            self.parser.expect("+")?;
+            let keyword = self.parser.next()?;
+            let status = match keyword.as_str() {
+                "PLACED" => PlacementStatus::Placed,
+                "FIXED" => PlacementStatus::Fixed,
+                "COVER" => PlacementStatus::Cover,
+                "UNPLACED" => PlacementStatus::Unplaced,
+                _ => {
+                    self.skip_option()?;
+                    continue;
+                }
+            };
+            if status.is_placed() {
+                let (location, _) = self.point(None)?;
+                let orientation = self.parser.next()?;
+                let tf = component_transform(&orientation, location, macro_size(chip, &template));
+                match tf {
+                    Some(tf) => chip.set_transform(&inst, tf),
+                    None => return self.parser.error("invalid orientation"),
+                }
+            }
+            chip.set_placement_status(&inst, status);
+        }
+        Ok(())
+    }
+
+    fn net<LN: L2NEdit<Coord = i32>>(chip: &mut LN, top: &LN::CellId, name: &str) -> LN::NetId {
+        chip.net_by_name(top, name)
+            .unwrap_or_else(|| chip.create_net(top, Some(name.to_string().into())))
+    }
+
+    fn parse_pin<LN: L2NEdit<Coord = i32>>(
+        &mut self,
+        chip: &mut LN,
+        top: &LN::CellId,
+    ) -> Result<(), LefDefError> {
+        let name = self.parser.next()?;
+        let mut net_name = None;
+        let mut direction = None;
+        let mut usage = None;
+        // Shapes of each port together with the placement of the port.
+        let mut ports: Vec<(Shapes, Option<SimpleTransform<i32>>)> = vec![(vec![], None)];
+        while !self.parser.accept(";") {
+            self.parser.expect("+")?;
+            let keyword = self.parser.next()?;
+            match keyword.as_str() {
+                "NET" => net_name = Some(self.parser.next()?),
+                "DIRECTION" => direction = Some(self.parser.next()?),
+                "USE" => usage = Some(self.parser.next()?),
+                "LAYER" | "POLYGON" => {
+                    let shape = self.layer_shape()?;
+                    ports.last_mut().unwrap().0.push(shape);
+                }
+                "PORT" => ports.push((vec![], None)),
+                "PLACED" | "FIXED" | "COVER" => {
+                    let (location, _) = self.point(None)?;
+                    let orientation = self.parser.next()?;
+                    let tf = component_transform(&orientation, location, (0, 0));
+                    if tf.is_none() {
+                        return self.parser.error("invalid orientation");
+                    }
+                    ports.last_mut().unwrap().1 = tf;
+                }
+                _ => self.skip_option()?,
+            }
+        }
+
+        let direction = pin_direction(direction.as_deref(), usage.as_deref());
+        let pin = chip.create_pin(top, name.clone().into(), direction);
+        let net = Self::net(chip, top, net_name.as_deref().unwrap_or(&name));
+        chip.connect_pin(&pin, Some(net));
+        for (shapes, tf) in ports {
+            for (layer, geometry) in shapes {
+                let geometry = match tf {
+                    Some(tf) => geometry.transform(|p| tf.transform_point(p)),
+                    None => geometry,
+                };
+                let layer = layer_by_name(chip, &layer);
+                let shape = chip.insert_shape(top, &layer, geometry);
+                chip.set_pin_of_shape(&shape, Some(pin.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_net<LN: L2NEdit<Coord = i32>>(
+        &mut self,
+        chip: &mut LN,
+        top: &LN::CellId,
+        special: bool,
+    ) -> Result<(), LefDefError> {
+        let name = self.parser.next()?;
+        if name == "MUSTJOIN" {
+            log::warn!("MUSTJOIN nets are not supported.");
+            return self.parser.skip_statement();
+        }
+        let net = Self::net(chip, top, &name);
+
+        // Connections.
+        while self.parser.accept("(") {
+            let component = self.parser.next()?;
+            let pin_name = self.parser.next()?;
+            while !self.parser.accept(")") {
+                self.parser.next()?;
+            }
+            if component == "PIN" {
+                let pin = chip.pin_by_name(top, &pin_name).ok_or_else(|| {
+                    LefDefError::Invalid(format!("pin '{}' does not exist", pin_name))
+                })?;
+                chip.connect_pin(&pin, Some(net.clone()));
+                continue;
+            }
+            let instances = if component == "*" {
+                chip.each_cell_instance_vec(top)
+            } else {
+                let inst = chip.cell_instance_by_name(top, &component).ok_or_else(|| {
+                    LefDefError::Invalid(format!("component '{}' does not exist", component))
+                })?;
+                vec![inst]
+            };
+            for inst in instances {
+                let template = chip.template_cell(&inst);
+                match chip.pin_by_name(&template, &pin_name) {
+                    Some(pin) => {
+                        let pin_inst = chip.pin_instance(&inst, &pin);
+                        chip.connect_pin_instance(&pin_inst, Some(net.clone()));
+                    }
+                    None if component == "*" => {}
+                    None => {
+                        return Err(LefDefError::Invalid(format!(
+                            "component '{}' has no pin '{}'",
+                            component, pin_name
+                        )))
+                    }
+                }
+            }
+        }
+
+        // Wiring.
+        let mut shapes = Vec::new();
+        while !self.parser.accept(";") {
+            self.parser.expect("+")?;
+            match self.parser.next()?.as_str() {
+                "ROUTED" | "FIXED" | "COVER" | "NOSHIELD" => shapes.extend(self.wiring(special)?),
+                "RECT" | "POLYGON" if special => shapes.push(self.layer_shape()?),
+                _ => self.skip_option()?,
+            }
+        }
+        for (layer, geometry) in shapes {
+            let layer = layer_by_name(chip, &layer);
+            let shape = chip.insert_shape(top, &layer, geometry);
+            chip.set_net_of_shape(&shape, Some(net.clone()));
+        }
+        Ok(())
+    }
+
+    /// Read the routing points of regular or special wiring.
+    fn wiring(&mut self, special: bool) -> Result<Shapes, LefDefError> {
+        let mut shapes = Vec::new();
+        if self.parser.peek_is("+") || self.parser.peek_is(";") {
+            return Ok(shapes);
+        }
+        let mut layer = self.parser.next()?;
+        let mut width = self.wire_width(&layer, special)?;
+        let mut points: Vec<(Point<i32>, Option<i32>)> = Vec::new();
+
+        // Regular wires are extended by half of the width, special wires are flush by default.
+        let flush_wire = |shapes: &mut Shapes,
+                          points: &mut Vec<(Point<i32>, Option<i32>)>,
+                          layer: &str,
+                          width: i32| {
+            if points.len() >= 2 {
+                let default_extension = if special { 0 } else { width / 2 };
+                let begin = points[0].1.unwrap_or(default_extension);
+                let end = points[points.len() - 1].1.unwrap_or(default_extension);
+                let path_points = points.iter().map(|(p, _)| *p).collect();
+                let path = if begin == 0 && end == 0 {
+                    Path::new(path_points, width)
+                } else {
+                    Path::new_extended(path_points, width, begin, end)
+                };
+                shapes.push((layer.to_string(), path.into()));
+            }
+            points.clear();
+        };
+
+        loop {
+            let token = match self.parser.peek() {
+                Some(token) => token.to_string(),
+                None => return self.parser.error("unexpected end of file"),
+            };
+            match token.as_str() {
+                ";" => break,
+                "(" => {
+                    let previous = points.last().map(|(p, _)| *p);
+                    points.push(self.point(previous)?);
+                }
+                "+" => {
+                    let option = self
+                        .parser
+                        .tokens
+                        .get(self.parser.pos + 1)
+                        .map(|(t, _)| t.as_str());
+                    if matches!(option, Some("SHAPE") | Some("STYLE") | Some("MASK")) {
+                        self.parser.pos += 3;
+                    } else {
+                        break;
+                    }
+                }
+                "NEW" => {
+                    self.parser.next()?;
+                    flush_wire(&mut shapes, &mut points, &layer, width);
+                    layer = self.parser.next()?;
+                    width = self.wire_width(&layer, special)?;
+                }
+                "TAPER" => self.parser.pos += 1,
+                "TAPERRULE" | "STYLE" | "MASK" => self.parser.pos += 2,
+                "VIRTUAL" => {
+                    self.parser.next()?;
+                    flush_wire(&mut shapes, &mut points, &layer, width);
+                    let (point, _) = self.point(None)?;
+                    points.push((point, None));
+                }
+                "RECT" => {
+                    self.parser.next()?;
+                    let origin = match points.last() {
+                        Some((p, _)) => *p,
+                        None => return self.parser.error("RECT without previous point"),
+                    };
+                    self.parser.expect("(")?;
+                    let mut coordinates = [0; 4];
+                    for c in coordinates.iter_mut() {
+                        *c = self.to_dbu(self.parser.expect_number()?);
+                    }
+                    self.parser.expect(")")?;
+                    let rect = Rect::new(
+                        origin + Vector::new(coordinates[0], coordinates[1]),
+                        origin + Vector::new(coordinates[2], coordinates[3]),
+                    );
+                    shapes.push((layer.clone(), rect.into()));
+                }
+                _ => {
+                    // Via at the last point.
+                    let via_name = self.parser.next()?;
+                    if ORIENTATIONS.iter().any(|(o, _, _)| self.parser.peek_is(o)) {
+                        log::warn!("Rotated vias are not supported.");
+                        self.parser.next()?;
+                    }
+                    let location = match points.last() {
+                        Some((p, _)) => *p,
+                        None => return self.parser.error("via without previous point"),
+                    };
+                    let via = self
+                        .via(&via_name)
+                        .ok_or_else(|| LefDefError::UnknownMacro(via_name.clone()))?
+                        .clone();
+                    let offset = location - Point::new(0, 0);
+                    shapes.extend(
+                        via.iter()
+                            .map(|(l, g)| (l.clone(), g.transform(|p| p + offset))),
+                    );
+                    // The wire continues on the other routing layer of the via.
+                    flush_wire(&mut shapes, &mut points, &layer, width);
+                    points.push((location, None));
+                    let via_layers: Vec<&String> = via.iter().map(|(l, _)| l).collect();
+                    if let (Some(&first), Some(&last)) = (via_layers.first(), via_layers.last()) {
+                        if *first == layer {
+                            layer = last.clone();
+                        } else if *last == layer {
+                            layer = first.clone();
+                        }
+                        if !special {
+                            width = self.wire_width(&layer, special)?;
+                        }
+                    }
+                }
+            }
+        }
+        flush_wire(&mut shapes, &mut points, &layer, width);
+        Ok(shapes)
+    }
+
+    /// Read the width of special wires or get the default width of regular wires.
+    fn wire_width(&mut self, layer: &str, special: bool) -> Result<i32, LefDefError> {
+        if special {
+            Ok(self.to_dbu(self.parser.expect_number()?))
+        } else {
+            Ok(self.library.wire_width(layer).unwrap_or_else(|| {
+                log::warn!("No default wire width for layer '{}'.", layer);
+                0
+            }))
+        }
+    }
+}
+
+/// Write DEF files.
+#[derive(Debug, Clone, Default)]
+pub struct DefWriter {}
+
+impl DefWriter {
+    /// Create a DEF writer with default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write the placement, pins, connectivity and wiring of the cell `design` as DEF.
+    ///
+    /// Cell instances without name and nets without name get generated names.
+    pub fn write_def<W: Write, LN: L2NBase<Coord = i32>>(
+        &self,
+        writer: &mut W,
+        chip: &LN,
+        design: &LN::CellId,
+    ) -> Result<(), LefDefError> {
+        writeln!(writer, "VERSION 5.8 ;")?;
+        writeln!(writer, "DIVIDERCHAR \"/\" ;")?;
+        writeln!(writer, "BUSBITCHARS \"[]\" ;")?;
+        writeln!(writer, "DESIGN {} ;", chip.cell_name(design))?;
+        writeln!(writer, "UNITS DISTANCE MICRONS {} ;", chip.dbu())?;
+
+        let die_area = chip
+            .layer_by_name(DIE_AREA_LAYER)
+            .and_then(|layer| chip.bounding_box_per_layer(design, &layer))
+            .or_else(|| chip.bounding_box(design));
+        if let Some(r) = die_area {
+            writeln!(
+                writer,
+                "DIEAREA ( {} {} ) ( {} {} ) ;",
+                r.lower_left().x,
+                r.lower_left().y,
+                r.upper_right().x,
+                r.upper_right().y
+            )?;
+        }
+
+        // Components.
+        let mut instance_names = HashMap::new();
+        let instances = chip.each_cell_instance_vec(design);
+        writeln!(writer, "COMPONENTS {} ;", instances.len())?;
+        for (i, inst) in instances.iter().enumerate() {
+            let name = match chip.cell_instance_name(inst) {
+                Some(name) => name.to_string(),
+                None => format!("__inst{}", i),
+            };
+            let template = chip.template_cell(inst);
+            write!(writer, "- {} {}", name, chip.cell_name(&template))?;
+            // DEF components cannot be `FIRM`, the closest status is `FIXED`.
+            let status = match chip.placement_status(inst) {
+                PlacementStatus::Unplaced => "UNPLACED",
+                PlacementStatus::Placed => "PLACED",
+                PlacementStatus::Firm | PlacementStatus::Fixed => "FIXED",
+                PlacementStatus::Cover => "COVER",
+            };
+            if status == "UNPLACED" {
+                write!(writer, " + UNPLACED")?;
+            } else {
+                let tf = chip.get_transform(inst);
+                if tf.magnification != 1 {
+                    return Err(LefDefError::Invalid(format!(
+                        "instance '{}' has a magnification",
+                        name
+                    )));
+                }
+                let &(orientation, _, _) = ORIENTATIONS
+                    .iter()
+                    .find(|(_, mirror, rotation)| *mirror == tf.mirror && *rotation == tf.rotation)
+                    .unwrap();
+                // The location is the lower left corner of the transformed macro.
+                let (w, h) = macro_size(chip, &template);
+                let location = Rect::new((0, 0), (w, h))
+                    .transform(|p| tf.transform_point(p))
+                    .lower_left();
+                write!(
+                    writer,
+                    " + {} ( {} {} ) {}",
+                    status, location.x, location.y, orientation
+                )?;
+            }
+            writeln!(writer, " ;")?;
+            instance_names.insert(inst.clone(), name);
+        }
+        writeln!(writer, "END COMPONENTS")?;
+
+        // Net names.
+        let mut net_names = HashMap::new();
+        let mut nets = Vec::new();
+        for net in chip.each_internal_net(design) {
+            let has_content =
+                chip.num_net_terminals(&net) > 0 || chip.shapes_of_net(&net).next().is_some();
+            if !has_content {
+                continue;
+            }
+            let name = match chip.net_name(&net) {
+                Some(name) => name.to_string(),
+                None => format!("__net{}", net_names.len()),
+            };
+            net_names.insert(net.clone(), name);
+            nets.push(net);
+        }
+
+        // Pins.
+        let pins = chip.each_pin_vec(design);
+        writeln!(writer, "PINS {} ;", pins.len())?;
+        for pin in &pins {
+            let name = chip.pin_name(pin).to_string();
+            let net_name = chip
+                .net_of_pin(pin)
+                .and_then(|net| net_names.get(&net).cloned())
+                .unwrap_or_else(|| name.clone());
+            write!(writer, "- {} + NET {}", name, net_name)?;
+            let (direction, usage) = match chip.pin_direction(pin) {
+                Direction::Input => (Some("INPUT"), None),
+                Direction::Output => (Some("OUTPUT"), None),
+                Direction::InOut => (Some("INOUT"), None),
+                Direction::Clock => (Some("INPUT"), Some("CLOCK")),
+                Direction::Supply => (Some("INOUT"), Some("POWER")),
+                Direction::Ground => (Some("INOUT"), Some("GROUND")),
+                Direction::None => (None, None),
+            };
+            if let Some(direction) = direction {
+                write!(writer, " + DIRECTION {}", direction)?;
+            }
+            if let Some(usage) = usage {
+                write!(writer, " + USE {}", usage)?;
+            }
+            // Pin shapes are written relative to a placement at the origin.
+            let mut has_shapes = false;
+            for shape in chip.shapes_of_pin(pin) {
+                let layer = layer_name(chip, &chip.shape_layer(&shape));
+                match chip.shape_geometry(&shape) {
+                    Geometry::Rect(r) => write!(
+                        writer,
+                        "\n  + LAYER {} {}",
+                        layer,
+                        format_points(&[r.lower_left(), r.upper_right()])
+                    )?,
+                    geometry => match polygon_points(&geometry) {
+                        Some(points) => {
+                            write!(writer, "\n  + POLYGON {} {}", layer, format_points(&points))?
+                        }
+                        None => {
+                            log::warn!("Skipping pin shape which is not a rectangle or polygon.");
+                            continue;
+                        }
+                    },
+                }
+                has_shapes = true;
+            }
+            if has_shapes {
+                write!(writer, "\n  + FIXED ( 0 0 ) N")?;
+            }
+            writeln!(writer, " ;")?;
+        }
+        writeln!(writer, "END PINS")?;
+
+        // Connectivity.
+        writeln!(writer, "NETS {} ;", nets.len())?;
+        for net in &nets {
+            write!(writer, "- {}", net_names[net])?;
+            for pin in chip.each_pin_of_net(net) {
+                write!(writer, " ( PIN {} )", chip.pin_name(&pin))?;
+            }
+            for pin_inst in chip.each_pin_instance_of_net(net) {
+                let inst = chip.parent_of_pin_instance(&pin_inst);
+                let pin = chip.template_pin(&pin_inst);
+                write!(
+                    writer,
+                    " ( {} {} )",
+                    instance_names[&inst],
+                    chip.pin_name(&pin)
+                )?;
+            }
+            writeln!(writer, " ;")?;
+        }
+        writeln!(writer, "END NETS")?;
+
+        // Wiring.
+        let routed_nets: Vec<_> = nets
+            .iter()
+            .filter(|net| chip.shapes_of_net(net).next().is_some())
+            .collect();
+        writeln!(writer, "SPECIALNETS {} ;", routed_nets.len())?;
+        for net in routed_nets {
+            write!(writer, "- {}", net_names[net])?;
+            let mut wires = Vec::new();
+            for shape in chip.shapes_of_net(net) {
+                let layer = layer_name(chip, &chip.shape_layer(&shape));
+                match chip.shape_geometry(&shape) {
+                    Geometry::Rect(r) => write!(
+                        writer,
+                        "\n  + RECT {} {}",
+                        layer,
+                        format_points(&[r.lower_left(), r.upper_right()])
+                    )?,
+                    Geometry::Path(p) => {
+                        let (begin, end) = match p.path_type {
+                            PathEndType::Flat => (0, 0),
+                            PathEndType::Extended(begin, end) => (begin, end),
+                            _ => {
+                                return Err(LefDefError::Invalid(
+                                    "paths with round ends are not supported".to_string(),
+                                ))
+                            }
+                        };
+                        let path_points: Vec<Point<i32>> = p.points.iter().copied().collect();
+                        let n = path_points.len();
+                        let points: Vec<String> = path_points
+                            .iter()
+                            .enumerate()
+                            .map(|(i, pt)| {
+                                let extension = match i {
+                                    0 if begin != 0 => Some(begin),
+                                    i if i + 1 == n && end != 0 => Some(end),
+                                    _ => None,
+                                };
+                                match extension {
+                                    Some(e) => format!("( {} {} {} )", pt.x, pt.y, e),
+                                    None => format!("( {} {} )", pt.x, pt.y),
+                                }
+                            })
+                            .collect();
+                        wires.push(format!("{} {} {}", layer, p.width, points.join(" ")));
+                    }
+                    geometry => match polygon_points(&geometry) {
+                        Some(points) => {
+                            write!(writer, "\n  + POLYGON {} {}", layer, format_points(&points))?
+                        }
+                        None => log::warn!("Skipping net shape which cannot be written to DEF."),
+                    },
+                }
+            }
+            if !wires.is_empty() {
+                write!(writer, "\n  + ROUTED {}", wires.join("\n    NEW "))?;
+            }
+            writeln!(writer, " ;")?;
+        }
+        writeln!(writer, "END SPECIALNETS")?;
+        writeln!(writer, "END DESIGN")?;
+        Ok(())
+    }
+}
+
+fn format_points(points: &[Point<i32>]) -> String {
+    let points: Vec<String> = points
+        .iter()
+        .map(|p| format!("( {} {} )", p.x, p.y))
+        .collect();
+    points.join(" ")
+}
+
+/// Get the vertices of polygons without holes.
+fn polygon_points(geometry: &Geometry<i32>) -> Option<Vec<Point<i32>>> {
+    match geometry {
+        Geometry::SimplePolygon(p) => Some(p.iter().copied().collect()),
+        Geometry::Polygon(p) if p.interiors.is_empty() => {
+            Some(p.exterior.iter().copied().collect())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip::CellId;
+
+    const LEF: &str = r#"
+VERSION 5.8 ;
+UNITS
+  DATABASE MICRONS 1000 ;
+END UNITS
+LAYER metal1
+  TYPE ROUTING ;
+  WIDTH 0.1 ;
+  PROPERTY LEF58_TYPE "TYPE ; MASK 1 ;" ;
+END metal1
+LAYER via1
+  TYPE CUT ;
+END via1
+LAYER metal2
+  TYPE ROUTING ;
+  WIDTH 0.2 ;
+END metal2
+VIA via12 DEFAULT
+  LAYER metal1 ;
+    RECT -0.1 -0.1 0.1 0.1 ;
+  LAYER via1 ;
+    RECT -0.05 -0.05 0.05 0.05 ;
+  LAYER metal2 ;
+    RECT -0.1 -0.1 0.1 0.1 ;
+END via12
+SITE core
+  SIZE 0.2 BY 2 ;
+END core
+MACRO INV
+  CLASS CORE ;
+  ORIGIN 0 0 ;
+  SIZE 1 BY 2 ;
+  PIN A
+    DIRECTION INPUT ;
+    PORT
+      LAYER metal1 ;
+        RECT 0.1 0.1 0.3 0.3 ;
+    END
+  END A
+  PIN Y
+    DIRECTION OUTPUT ;
+    PORT
+      LAYER metal1 ;
+        POLYGON 0.6 0.1 0.8 0.1 0.8 0.5 0.6 0.3 ;
+    END
+  END Y
+  PIN VDD
+    DIRECTION INOUT ;
+    USE POWER ;
+    PORT
+      LAYER metal1 ;
+        RECT 0 1.9 1 2.1 ;
+    END
+  END VDD
+  OBS
+    LAYER metal1 ;
+      RECT 0.4 0.4 0.5 0.5 ;
+  END
+END INV
+END LIBRARY
+"#;
+
+    const DEF: &str = r#"
+VERSION 5.8 ;
+DESIGN top ;
+UNITS DISTANCE MICRONS 2000 ;
+DIEAREA ( 0 0 ) ( 20000 20000 ) ;
+ROW row1 core 0 0 N DO 10 BY 1 STEP 400 0 ;
+COMPONENTS 2 ;
+  - inv1 INV + PLACED ( 0 0 ) N ;
+  - inv2 INV + FIXED ( 4000 0 ) FS ;
+END COMPONENTS
+PINS 1 ;
+  - in + NET a + DIRECTION INPUT + USE SIGNAL
+    + LAYER metal2 ( -100 0 ) ( 100 200 )
+    + PLACED ( 1000 1000 ) N ;
+END PINS
+BLOCKAGES 1 ;
+  - LAYER metal1 RECT ( 0 0 ) ( 10 10 ) ;
+END BLOCKAGES
+NETS 2 ;
+  - a ( PIN in ) ( inv1 A )
+    + ROUTED metal2 ( 1000 1000 ) ( 400 * ) via12 ( * 400 ) ;
+  - b ( inv1 Y ) ( inv2 A ) + USE SIGNAL ;
+END NETS
+SPECIALNETS 1 ;
+  - VDD ( * VDD )
+    + ROUTED metal1 400 + SHAPE STRIPE ( 0 4000 ) ( 20000 4000 ) ;
+END SPECIALNETS
+END DESIGN
+"#;
+
+    fn read() -> (Chip, CellId) {
+        let mut chip = Chip::new();
+        let mut library = LefLibrary::new();
+        LefReader::new()
+            .read_lef(&mut LEF.as_bytes(), &mut chip, &mut library)
+            .unwrap();
+        let top = DefReader::new()
+            .read_def(&mut DEF.as_bytes(), &mut chip, &library)
+            .unwrap();
+        (chip, top)
+    }
+
+    #[test]
+    fn test_read_lef() {
+        let mut chip = Chip::new();
+        let mut library = LefLibrary::new();
+        LefReader::new()
+            .read_lef(&mut LEF.as_bytes(), &mut chip, &mut library)
+            .unwrap();
+        assert_eq!(chip.dbu(), 1000);
+        assert_eq!(library.wire_width("metal2"), Some(200));
+        assert!(library.has_via("via12"));
+
+        let inv = chip.cell_by_name("INV").unwrap();
+        assert_eq!(macro_size(&chip, &inv), (1000, 2000));
+        let a = chip.pin_by_name(&inv, "A").unwrap();
+        assert_eq!(chip.pin_direction(&a), Direction::Input);
+        let vdd = chip.pin_by_name(&inv, "VDD").unwrap();
+        assert_eq!(chip.pin_direction(&vdd), Direction::Supply);
+        let a_shapes: Vec<_> = chip
+            .shapes_of_pin(&a)
+            .map(|s| chip.shape_geometry(&s))
+            .collect();
+        assert_eq!(a_shapes, vec![Rect::new((100, 100), (300, 300)).into()]);
+
+        // Three pin shapes and one obstruction.
+        let metal1 = chip.layer_by_name("metal1").unwrap();
+        assert_eq!(chip.each_shape_id(&inv, &metal1).count(), 4);
+    }
+
+    #[test]
+    fn test_read_def() {
+        let (chip, top) = read();
+        let inv = chip.cell_by_name("INV").unwrap();
+
+        // DEF units are half of the database unit.
+        let inv2 = chip.cell_instance_by_name(&top, "inv2").unwrap();
+        let tf = chip.get_transform(&inv2);
+        assert!(tf.mirror);
+        let bbox = Rect::new((0, 0), (1000, 2000)).transform(|p| tf.transform_point(p));
+        assert_eq!(bbox, Rect::new((2000, 0), (3000, 2000)));
+        assert_eq!(chip.placement_status(&inv2), PlacementStatus::Fixed);
+
+        let a = chip.net_by_name(&top, "a").unwrap();
+        let pin_in = chip.pin_by_name(&top, "in").unwrap();
+        assert_eq!(chip.net_of_pin(&pin_in), Some(a.clone()));
+        assert_eq!(chip.num_net_pin_instances(&a), 1);
+        let pin_shape = chip.shapes_of_pin(&pin_in).next().unwrap();
+        assert_eq!(
+            chip.shape_geometry(&pin_shape),
+            Rect::new((450, 500), (550, 600)).into()
+        );
+
+        // Two wire segments and the three via shapes.
+        let wires: Vec<_> = chip
+            .shapes_of_net(&a)
+            .map(|s| chip.shape_geometry(&s))
+            .collect();
+        assert_eq!(wires.len(), 5);
+        assert!(wires.contains(
+            &Path::new_extended(vec![(500, 500).into(), (200, 500).into()], 200, 100, 100).into()
+        ));
+        assert!(wires.contains(
+            &Path::new_extended(vec![(200, 500).into(), (200, 200).into()], 100, 50, 50).into()
+        ));
+
+        let b = chip.net_by_name(&top, "b").unwrap();
+        assert_eq!(chip.num_net_pin_instances(&b), 2);
+
+        // The supply net is connected to all instances.
+        let vdd = chip.net_by_name(&top, "VDD").unwrap();
+        assert_eq!(chip.num_net_pin_instances(&vdd), 2);
+        let stripe = chip.shapes_of_net(&vdd).next().unwrap();
+        assert_eq!(
+            chip.shape_geometry(&stripe),
+            Path::new(vec![(0, 2000).into(), (10000, 2000).into()], 200).into()
+        );
+
+        let die_area = chip.layer_by_name(DIE_AREA_LAYER).unwrap();
+        assert_eq!(
+            chip.bounding_box_per_layer(&top, &die_area),
+            Some(Rect::new((0, 0), (10000, 10000)))
+        );
+        assert_eq!(chip.num_child_instances(&inv), 0);
+    }
+
+    #[test]
+    fn test_invalid_components() {
+        let mut chip = Chip::new();
+        let mut library = LefLibrary::new();
+        LefReader::new()
+            .read_lef(&mut LEF.as_bytes(), &mut chip, &mut library)
+            .unwrap();
+
+        let duplicate = DEF.replace("- inv2 INV", "- inv1 INV");
+        let result = DefReader::new().read_def(&mut duplicate.as_bytes(), &mut chip, &library);
+        assert!(matches!(result, Err(LefDefError::Parse { .. })));
+
+        let recursive = DEF
+            .replace("DESIGN top", "DESIGN top2")
+            .replace("- inv2 INV", "- inv2 top2");
+        let result = DefReader::new().read_def(&mut recursive.as_bytes(), &mut chip, &library);
+        assert!(matches!(result, Err(LefDefError::Parse { .. })));
+    }
+
+    #[test]
+    fn test_write_and_read_back() {
+        let (chip, top) = read();
+        let mut buffer = Vec::new();
+        DefWriter::new()
+            .write_def(&mut buffer, &chip, &top)
+            .unwrap();
+
+        // Read the DEF into a library which contains only the macros.
+        let mut restored = Chip::new();
+        let mut library = LefLibrary::new();
+        LefReader::new()
+            .read_lef(&mut LEF.as_bytes(), &mut restored, &mut library)
+            .unwrap();
+        let restored_top = DefReader::new()
+            .read_def(&mut buffer.as_slice(), &mut restored, &library)
+            .unwrap();
+
+        for inst in chip.each_cell_instance(&top) {
+            let name = chip.cell_instance_name(&inst).unwrap();
+            let restored_inst = restored
+                .cell_instance_by_name(&restored_top, &name)
+                .unwrap();
+            assert_eq!(
+                restored.get_transform(&restored_inst),
+                chip.get_transform(&inst)
+            );
+            assert_eq!(
+                restored.placement_status(&restored_inst),
+                chip.placement_status(&inst)
+            );
+        }
+
+        for net_name in ["a", "b", "VDD"] {
+            let net = chip.net_by_name(&top, net_name).unwrap();
+            let restored_net = restored.net_by_name(&restored_top, net_name).unwrap();
+            assert_eq!(
+                restored.num_net_terminals(&restored_net),
+                chip.num_net_terminals(&net)
+            );
+            let mut shapes: Vec<_> = chip
+                .shapes_of_net(&net)
+                .map(|s| format!("{:?}", chip.shape_geometry(&s)))
+                .collect();
+            let mut restored_shapes: Vec<_> = restored
+                .shapes_of_net(&restored_net)
+                .map(|s| format!("{:?}", restored.shape_geometry(&s)))
+                .collect();
+            shapes.sort();
+            restored_shapes.sort();
+            assert_eq!(restored_shapes, shapes);
+        }
+
+        let pin = restored.pin_by_name(&restored_top, "in").unwrap();
+        let pin_shape = restored.shapes_of_pin(&pin).next().unwrap();
+        assert_eq!(
+            restored.shape_geometry(&pin_shape),
+            Rect::new((450, 500), (550, 600)).into()
+        );
+    }
+}
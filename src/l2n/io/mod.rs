@@ -0,0 +1,9 @@
+// SPDX-FileCopyrightText: 2022 Thomas Kramer
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Input and output of fused layouts and netlists.
+
+pub mod json;
+pub mod lefdef;
+pub mod snapshot;
@@ -0,0 +1,1820 @@
+// SPDX-FileCopyrightText: 2022 Thomas Kramer
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Native binary snapshot format.
+//!
+//! A snapshot stores the content of a data base in a compact binary form such that it can
+//! be written and loaded again quickly. This is meant for checkpointing long running flows,
+//! not for exchanging data with other tools.
+//!
+//! Snapshots contain the cell hierarchy, the netlist including pin and net buses and power domains,
+//! the layout, the links between layout shapes and nets/pins and all user defined properties.
+//! Buses and power domains are only written and read by [`SnapshotWriter::write_snapshot()`] and
+//! [`SnapshotReader::read_snapshot()`]. IDs are not stored. All references are
+//! encoded as indices into the order in which the elements are written.
+//!
+//! # Format
+//!
+//! All integers are stored in little-endian byte order.
+//! Strings are stored as a `u32` length followed by UTF-8 bytes. Optional values are prefixed
+//! with a `u8` which is `0` for `None` and `1` for `Some`.
+//!
+//! A snapshot starts with the magic bytes `LDBSNAP\0` followed by the major and minor version
+//! of the format as two `u16`.
+//! The rest of the file is a sequence of sections. Each section starts with a `u8` tag and
+//! the `u64` length of the section content in bytes. The last section has the tag `0` and no content.
+//!
+//! | Tag | Section   | Content                                                                  |
+//! |-----|-----------|--------------------------------------------------------------------------|
+//! | 1   | Hierarchy | chip properties, cells with properties, cell instances with properties   |
+//! | 2   | Netlist   | pins, nets and the nets connected to pins and pin instances              |
+//! | 3   | Layout    | distance unit, layers, transforms of cell instances, shapes with properties |
+//! | 4   | L2N       | net and pin of each shape                                                |
+//! | 5   | Arrays    | array parameters of arrayed cell instances (since version 1.1)           |
+//! | 6   | Placement | placement status of cell instances which are not `Placed` (since version 1.2) |
+//! | 7   | Buses     | pin buses and net buses of each cell (since version 1.3)                 |
+//! | 8   | Power     | power domains and the domains assigned to cells and cell instances (since version 1.4) |
+//!
+//! The hierarchy section always comes first.
+//!
+//! # Compatibility
+//!
+//! Readers skip sections with unknown tags. Newer minor versions of the format may only add new sections,
+//! hence they can still be read by older readers. A change of the major version marks an incompatible
+//! change and is rejected by the reader.
+//!
+//! # Example
+//!
+//! ```
+//! use libreda_db::prelude::*;
+//! use libreda_db::l2n::io::snapshot::{SnapshotReader, SnapshotWriter};
+//!
+//! let mut chip = Chip::new();
+//! let top = chip.create_cell("TOP".into());
+//! let layer = chip.create_layer(1, 0);
+//! chip.insert_shape(&top, &layer, Rect::new((0, 0), (10, 10)).into());
+//!
+//! let mut buffer = Vec::new();
+//! SnapshotWriter::new().write_snapshot(&mut buffer, &chip).unwrap();
+//!
+//! let restored: Chip = SnapshotReader::new().read_snapshot(&mut buffer.as_slice()).unwrap();
+//! let top = restored.cell_by_name("TOP").unwrap();
+//! assert_eq!(restored.bounding_box(&top), Some(Rect::new((0, 0), (10, 10))));
+//! ```
+
+use crate::layout::io::{LayoutStreamReader, LayoutStreamWriter};
+use crate::netlist::io::{NetlistReader, NetlistWriter};
+use crate::prelude::*;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::io::{Read, Write};
+
+/// Magic bytes at the start of every snapshot.
+const MAGIC: [u8; 8] = *b"LDBSNAP\0";
+/// Major version of the format. Changes when the format becomes incompatible.
+pub const FORMAT_VERSION_MAJOR: u16 = 1;
+/// Minor version of the format. Changes when sections are added.
+pub const FORMAT_VERSION_MINOR: u16 = 4;
+
+const SECTION_END: u8 = 0;
+const SECTION_HIERARCHY: u8 = 1;
+const SECTION_NETLIST: u8 = 2;
+const SECTION_LAYOUT: u8 = 3;
+const SECTION_L2N: u8 = 4;
+const SECTION_ARRAYS: u8 = 5;
+const SECTION_PLACEMENT: u8 = 6;
+const SECTION_BUSES: u8 = 7;
+const SECTION_POWER: u8 = 8;
+
+/// Error type used for reading and writing snapshots.
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// Error of the underlying byte stream.
+    Io(std::io::Error),
+    /// The data does not start with the magic bytes of a snapshot.
+    InvalidMagic,
+    /// The snapshot was written with an incompatible version of the format.
+    UnsupportedVersion {
+        /// Major version of the snapshot.
+        major: u16,
+        /// Minor version of the snapshot.
+        minor: u16,
+    },
+    /// The content of the snapshot is inconsistent.
+    Malformed(&'static str),
+    /// A cell with this name already exists in the data base.
+    CellNameCollision(String),
+    /// A power domain with this name already exists in the data base.
+    PowerDomainNameCollision(String),
+    /// The geometry type cannot be stored in a snapshot.
+    UnsupportedGeometry,
+    /// A number of elements or the length of a string exceeds the limits of the format.
+    TooLarge,
+}
+
+impl std::fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SnapshotError::Io(err) => write!(f, "IO error: {}", err),
+            SnapshotError::InvalidMagic => write!(f, "Data is not a snapshot."),
+            SnapshotError::UnsupportedVersion { major, minor } => write!(
+                f,
+                "Unsupported snapshot version {}.{} (supported: {}.x).",
+                major, minor, FORMAT_VERSION_MAJOR
+            ),
+            SnapshotError::Malformed(msg) => write!(f, "Malformed snapshot: {}", msg),
+            SnapshotError::CellNameCollision(name) => {
+                write!(f, "Cell '{}' already exists.", name)
+            }
+            SnapshotError::PowerDomainNameCollision(name) => {
+                write!(f, "Power domain '{}' already exists.", name)
+            }
+            SnapshotError::UnsupportedGeometry => {
+                write!(f, "Geometry type is not supported by the snapshot format.")
+            }
+            SnapshotError::TooLarge => {
+                write!(f, "Data base exceeds the limits of the snapshot format.")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<std::io::Error> for SnapshotError {
+    fn from(err: std::io::Error) -> Self {
+        SnapshotError::Io(err)
+    }
+}
+
+/// Write data bases into the native snapshot format.
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotWriter {}
+
+impl SnapshotWriter {
+    /// Create a default snapshot writer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write the cell hierarchy, the netlist including buses and power domains, the layout,
+    /// the links between layout and netlist and all properties of `chip`. IDs are not stored.
+    pub fn write_snapshot<W: Write, LN: L2NBase<Coord = i32> + BusBase + PowerBase>(
+        &self,
+        writer: &mut W,
+        chip: &LN,
+    ) -> Result<(), SnapshotError> {
+        let table = CellTable::new(chip);
+        write_header(writer)?;
+        write_section(writer, SECTION_HIERARCHY, &encode_hierarchy(chip, &table)?)?;
+        write_section(writer, SECTION_NETLIST, &encode_netlist(chip, &table)?)?;
+        write_section(writer, SECTION_LAYOUT, &encode_layout(chip, &table)?)?;
+        write_section(writer, SECTION_L2N, &encode_l2n(chip, &table)?)?;
+        write_section(writer, SECTION_ARRAYS, &encode_arrays(chip, &table)?)?;
+        write_section(writer, SECTION_PLACEMENT, &encode_placement(chip, &table)?)?;
+        write_section(writer, SECTION_BUSES, &encode_buses(chip, &table)?)?;
+        write_section(writer, SECTION_POWER, &encode_power(chip, &table)?)?;
+        write_section(writer, SECTION_END, &[])
+    }
+}
+
+/// Write only the hierarchy and the netlist.
+impl NetlistWriter for SnapshotWriter {
+    type Error = SnapshotError;
+
+    fn write_netlist<W: Write, N: NetlistBase>(
+        &self,
+        writer: &mut W,
+        netlist: &N,
+    ) -> Result<(), Self::Error> {
+        let table = CellTable::new(netlist);
+        write_header(writer)?;
+        write_section(
+            writer,
+            SECTION_HIERARCHY,
+            &encode_hierarchy(netlist, &table)?,
+        )?;
+        write_section(writer, SECTION_NETLIST, &encode_netlist(netlist, &table)?)?;
+        write_section(writer, SECTION_END, &[])
+    }
+}
+
+/// Write only the hierarchy and the layout.
+impl LayoutStreamWriter for SnapshotWriter {
+    type Error = SnapshotError;
+
+    fn write_layout<W: Write, L: LayoutBase<Coord = i32>>(
+        &self,
+        writer: &mut W,
+        layout: &L,
+    ) -> Result<(), Self::Error> {
+        let table = CellTable::new(layout);
+        write_header(writer)?;
+        write_section(
+            writer,
+            SECTION_HIERARCHY,
+            &encode_hierarchy(layout, &table)?,
+        )?;
+        write_section(writer, SECTION_LAYOUT, &encode_layout(layout, &table)?)?;
+        write_section(writer, SECTION_ARRAYS, &encode_arrays(layout, &table)?)?;
+        write_section(
+            writer,
+            SECTION_PLACEMENT,
+            &encode_placement(layout, &table)?,
+        )?;
+        write_section(writer, SECTION_END, &[])
+    }
+}
+
+/// Read data bases from the native snapshot format.
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotReader {}
+
+impl SnapshotReader {
+    /// Create a default snapshot reader.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read a snapshot into a new data base.
+    pub fn read_snapshot<R: Read, LN: L2NEdit<Coord = i32> + BusEdit + PowerEdit>(
+        &self,
+        reader: &mut R,
+    ) -> Result<LN, SnapshotError> {
+        let mut chip = LN::new();
+        self.read_snapshot_into(reader, &mut chip)?;
+        Ok(chip)
+    }
+
+    /// Read a snapshot and add its content to `chip`.
+    /// Cell names of the snapshot must not exist yet in `chip`.
+    pub fn read_snapshot_into<R: Read, LN: L2NEdit<Coord = i32> + BusEdit + PowerEdit>(
+        &self,
+        reader: &mut R,
+        chip: &mut LN,
+    ) -> Result<(), SnapshotError> {
+        let mut table = None;
+        let mut netlist = None;
+        let mut shapes = None;
+        read_sections(reader, |tag, data| {
+            match tag {
+                SECTION_HIERARCHY => table = Some(decode_hierarchy(data, chip)?),
+                SECTION_NETLIST => {
+                    netlist = Some(decode_netlist(data, chip, hierarchy(&table)?)?);
+                }
+                SECTION_LAYOUT => shapes = Some(decode_layout(data, chip, hierarchy(&table)?)?),
+                SECTION_L2N => {
+                    let netlist = netlist
+                        .as_ref()
+                        .ok_or(SnapshotError::Malformed("L2N section before netlist"))?;
+                    let shapes = shapes
+                        .as_ref()
+                        .ok_or(SnapshotError::Malformed("L2N section before layout"))?;
+                    decode_l2n(data, chip, netlist, shapes)?
+                }
+                SECTION_ARRAYS => decode_arrays(data, chip, hierarchy(&table)?)?,
+                SECTION_PLACEMENT => decode_placement(data, chip, hierarchy(&table)?)?,
+                SECTION_BUSES => {
+                    let netlist = netlist
+                        .as_ref()
+                        .ok_or(SnapshotError::Malformed("bus section before netlist"))?;
+                    decode_buses(data, chip, netlist)?
+                }
+                SECTION_POWER => {
+                    let netlist = netlist
+                        .as_ref()
+                        .ok_or(SnapshotError::Malformed("power section before netlist"))?;
+                    decode_power(data, chip, hierarchy(&table)?, netlist)?
+                }
+                _ => {} // Skip unknown sections.
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Read the hierarchy and the netlist. Layout sections are skipped.
+impl NetlistReader for SnapshotReader {
+    type Error = SnapshotError;
+
+    fn read_into_netlist<R: Read, N: NetlistEdit>(
+        &self,
+        reader: &mut R,
+        netlist: &mut N,
+    ) -> Result<(), Self::Error> {
+        let mut table = None;
+        read_sections(reader, |tag, data| {
+            match tag {
+                SECTION_HIERARCHY => table = Some(decode_hierarchy(data, netlist)?),
+                SECTION_NETLIST => {
+                    decode_netlist(data, netlist, hierarchy(&table)?)?;
+                }
+                _ => {}
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Read the hierarchy and the layout. Netlist sections are skipped.
+impl LayoutStreamReader for SnapshotReader {
+    type Error = SnapshotError;
+
+    fn read_layout<R: Read, L: LayoutEdit<Coord = i32>>(
+        &self,
+        reader: &mut R,
+        layout: &mut L,
+    ) -> Result<(), Self::Error> {
+        let mut table = None;
+        read_sections(reader, |tag, data| {
+            match tag {
+                SECTION_HIERARCHY => table = Some(decode_hierarchy(data, layout)?),
+                SECTION_LAYOUT => {
+                    decode_layout(data, layout, hierarchy(&table)?)?;
+                }
+                SECTION_ARRAYS => decode_arrays(data, layout, hierarchy(&table)?)?,
+                SECTION_PLACEMENT => decode_placement(data, layout, hierarchy(&table)?)?,
+                _ => {}
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Cells and cell instances in the order in which they are stored in the snapshot.
+struct CellTable<H: HierarchyBase> {
+    cells: Vec<H::CellId>,
+    instances: Vec<H::CellInstId>,
+}
+
+impl<H: HierarchyBase> CellTable<H> {
+    /// Define the order of cells and instances for writing.
+    fn new(chip: &H) -> Self {
+        let cells = chip.each_cell_vec();
+        let instances = cells
+            .iter()
+            .flat_map(|cell| chip.each_cell_instance(cell))
+            .collect();
+        Self { cells, instances }
+    }
+
+    /// Create a map from cell IDs to their index in the snapshot.
+    fn cell_indices(&self) -> HashMap<H::CellId, u32> {
+        self.cells
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (c.clone(), i as u32))
+            .collect()
+    }
+}
+
+/// Get the hierarchy which must have been read before other sections.
+fn hierarchy<H: HierarchyBase>(
+    table: &Option<CellTable<H>>,
+) -> Result<&CellTable<H>, SnapshotError> {
+    table
+        .as_ref()
+        .ok_or(SnapshotError::Malformed("missing hierarchy section"))
+}
+
+/// Pins and nets of each cell in the order in which they are stored in the snapshot.
+struct NetTable<N: NetlistBase> {
+    pins: Vec<Vec<N::PinId>>,
+    nets: Vec<Vec<N::NetId>>,
+}
+
+/// Nets of a cell in the order in which they are stored.
+fn cell_nets<N: NetlistBase>(netlist: &N, cell: &N::CellId) -> Vec<N::NetId> {
+    netlist.each_internal_net_vec(cell)
+}
+
+// Writing.
+
+fn write_header<W: Write>(writer: &mut W) -> Result<(), SnapshotError> {
+    writer.write_all(&MAGIC)?;
+    writer.write_all(&FORMAT_VERSION_MAJOR.to_le_bytes())?;
+    writer.write_all(&FORMAT_VERSION_MINOR.to_le_bytes())?;
+    Ok(())
+}
+
+fn write_section<W: Write>(writer: &mut W, tag: u8, data: &[u8]) -> Result<(), SnapshotError> {
+    writer.write_all(&[tag])?;
+    writer.write_all(&(data.len() as u64).to_le_bytes())?;
+    writer.write_all(data)?;
+    Ok(())
+}
+
+/// Buffer for the content of a section.
+#[derive(Default)]
+struct Encoder {
+    buf: Vec<u8>,
+    /// Set if a length did not fit into the 32-bit length field.
+    too_large: bool,
+}
+
+impl Encoder {
+    fn u8(&mut self, v: u8) {
+        self.buf.push(v)
+    }
+
+    fn u32(&mut self, v: u32) {
+        self.buf.extend_from_slice(&v.to_le_bytes())
+    }
+
+    fn i32(&mut self, v: i32) {
+        self.buf.extend_from_slice(&v.to_le_bytes())
+    }
+
+    fn f64(&mut self, v: f64) {
+        self.buf.extend_from_slice(&v.to_le_bytes())
+    }
+
+    fn len(&mut self, len: usize) {
+        match u32::try_from(len) {
+            Ok(len) => self.u32(len),
+            Err(_) => {
+                self.too_large = true;
+                self.u32(u32::MAX)
+            }
+        }
+    }
+
+    /// Get the encoded data. Fails if a length could not be encoded.
+    fn finish(self) -> Result<Vec<u8>, SnapshotError> {
+        if self.too_large {
+            Err(SnapshotError::TooLarge)
+        } else {
+            Ok(self.buf)
+        }
+    }
+
+    fn bytes(&mut self, v: &[u8]) {
+        self.len(v.len());
+        self.buf.extend_from_slice(v)
+    }
+
+    fn str(&mut self, s: &str) {
+        self.bytes(s.as_bytes())
+    }
+
+    fn name<S: Into<String>>(&mut self, s: S) {
+        self.str(&s.into())
+    }
+
+    fn opt_str<S: Into<String>>(&mut self, s: Option<S>) {
+        match s {
+            None => self.u8(0),
+            Some(s) => {
+                self.u8(1);
+                self.str(&s.into())
+            }
+        }
+    }
+
+    fn opt_index(&mut self, index: Option<u32>) {
+        match index {
+            None => self.u8(0),
+            Some(i) => {
+                self.u8(1);
+                self.u32(i)
+            }
+        }
+    }
+
+    fn point(&mut self, p: Point<i32>) {
+        self.i32(p.x);
+        self.i32(p.y);
+    }
+
+    fn points<'a>(&mut self, points: impl Iterator<Item = &'a Point<i32>>) {
+        let points: Vec<_> = points.collect();
+        self.len(points.len());
+        points.into_iter().for_each(|p| self.point(*p))
+    }
+
+    fn property_value(&mut self, value: &PropertyValue) {
+        match value {
+            PropertyValue::String(s) => {
+                self.u8(0);
+                self.str(s.as_str())
+            }
+            PropertyValue::Bytes(b) => {
+                self.u8(1);
+                self.bytes(b)
+            }
+            PropertyValue::SInt(v) => {
+                self.u8(2);
+                self.i32(*v)
+            }
+            PropertyValue::UInt(v) => {
+                self.u8(3);
+                self.u32(*v)
+            }
+            PropertyValue::Float(v) => {
+                self.u8(4);
+                self.f64(*v)
+            }
+        }
+    }
+
+    /// Encode properties which have been collected with one of the `for_each_*_property` functions.
+    fn properties<K: Clone + Into<String>>(&mut self, properties: Vec<(K, PropertyValue)>) {
+        self.len(properties.len());
+        for (key, value) in properties {
+            self.name(key);
+            self.property_value(&value);
+        }
+    }
+
+    fn transform(&mut self, tf: &SimpleTransform<i32>) {
+        self.u8(tf.mirror as u8);
+        self.u8(match tf.rotation {
+            Angle::R0 => 0,
+            Angle::R90 => 1,
+            Angle::R180 => 2,
+            Angle::R270 => 3,
+        });
+        self.i32(tf.magnification);
+        self.i32(tf.displacement.x);
+        self.i32(tf.displacement.y);
+    }
+
+    fn geometry(&mut self, geometry: &Geometry<i32>) -> Result<(), SnapshotError> {
+        match geometry {
+            Geometry::Point(p) => {
+                self.u8(1);
+                self.point(*p);
+            }
+            Geometry::Edge(e) => {
+                self.u8(2);
+                self.point(e.start);
+                self.point(e.end);
+            }
+            Geometry::Rect(r) => {
+                self.u8(3);
+                self.point(r.lower_left());
+                self.point(r.upper_right());
+            }
+            Geometry::SimplePolygon(p) => {
+                self.u8(4);
+                self.points(p.iter());
+            }
+            Geometry::Polygon(p) => {
+                self.u8(5);
+                self.points(p.exterior.iter());
+                self.len(p.interiors.len());
+                p.interiors.iter().for_each(|hole| self.points(hole.iter()));
+            }
+            Geometry::Path(p) => {
+                self.u8(6);
+                self.points(p.points.iter());
+                self.i32(p.width);
+                match p.path_type {
+                    PathEndType::Flat => self.u8(0),
+                    PathEndType::Extended(begin, end) => {
+                        self.u8(1);
+                        self.i32(begin);
+                        self.i32(end);
+                    }
+                    PathEndType::Round => self.u8(2),
+                }
+            }
+            Geometry::Text(t) => {
+                self.u8(7);
+                self.str(t.text());
+                self.point(t.location());
+            }
+            _ => return Err(SnapshotError::UnsupportedGeometry),
+        }
+        Ok(())
+    }
+}
+
+fn direction_to_u8(direction: Direction) -> u8 {
+    match direction {
+        Direction::None => 0,
+        Direction::Input => 1,
+        Direction::Output => 2,
+        Direction::InOut => 3,
+        Direction::Clock => 4,
+        Direction::Supply => 5,
+        Direction::Ground => 6,
+    }
+}
+
+fn direction_from_u8(v: u8) -> Result<Direction, SnapshotError> {
+    Ok(match v {
+        0 => Direction::None,
+        1 => Direction::Input,
+        2 => Direction::Output,
+        3 => Direction::InOut,
+        4 => Direction::Clock,
+        5 => Direction::Supply,
+        6 => Direction::Ground,
+        _ => return Err(SnapshotError::Malformed("invalid pin direction")),
+    })
+}
+
+fn bit_order_to_u8(bit_order: BitOrder) -> u8 {
+    match bit_order {
+        BitOrder::Descending => 0,
+        BitOrder::Ascending => 1,
+    }
+}
+
+fn bit_order_from_u8(v: u8) -> Result<BitOrder, SnapshotError> {
+    Ok(match v {
+        0 => BitOrder::Descending,
+        1 => BitOrder::Ascending,
+        _ => return Err(SnapshotError::Malformed("invalid bit order")),
+    })
+}
+
+fn placement_status_to_u8(status: PlacementStatus) -> u8 {
+    match status {
+        PlacementStatus::Unplaced => 0,
+        PlacementStatus::Placed => 1,
+        PlacementStatus::Firm => 2,
+        PlacementStatus::Fixed => 3,
+        PlacementStatus::Cover => 4,
+    }
+}
+
+fn placement_status_from_u8(v: u8) -> Result<PlacementStatus, SnapshotError> {
+    Ok(match v {
+        0 => PlacementStatus::Unplaced,
+        1 => PlacementStatus::Placed,
+        2 => PlacementStatus::Firm,
+        3 => PlacementStatus::Fixed,
+        4 => PlacementStatus::Cover,
+        _ => return Err(SnapshotError::Malformed("invalid placement status")),
+    })
+}
+
+fn encode_hierarchy<H: HierarchyBase>(
+    chip: &H,
+    table: &CellTable<H>,
+) -> Result<Vec<u8>, SnapshotError> {
+    let mut enc = Encoder::default();
+    let cell_indices = table.cell_indices();
+
+    let mut properties = vec![];
+    chip.for_each_chip_property(|k, v| properties.push((k.clone(), v.clone())));
+    enc.properties(properties);
+
+    enc.len(table.cells.len());
+    for cell in &table.cells {
+        enc.name(chip.cell_name(cell));
+        let mut properties = vec![];
+        chip.for_each_cell_property(cell, |k, v| properties.push((k.clone(), v.clone())));
+        enc.properties(properties);
+    }
+
+    enc.len(table.instances.len());
+    for inst in &table.instances {
+        enc.u32(cell_indices[&chip.parent_cell(inst)]);
+        enc.u32(cell_indices[&chip.template_cell(inst)]);
+        enc.opt_str(chip.cell_instance_name(inst));
+        let mut properties = vec![];
+        chip.for_each_cell_instance_property(inst, |k, v| properties.push((k.clone(), v.clone())));
+        enc.properties(properties);
+    }
+
+    enc.finish()
+}
+
+fn encode_netlist<N: NetlistBase>(
+    netlist: &N,
+    table: &CellTable<N>,
+) -> Result<Vec<u8>, SnapshotError> {
+    let mut enc = Encoder::default();
+
+    // Index of each net within its cell.
+    let mut net_indices = HashMap::new();
+
+    for cell in &table.cells {
+        let pins = netlist.each_pin_vec(cell);
+        enc.len(pins.len());
+        for pin in &pins {
+            enc.name(netlist.pin_name(pin));
+            enc.u8(direction_to_u8(netlist.pin_direction(pin)));
+        }
+
+        let nets = cell_nets(netlist, cell);
+        let net_zero = netlist.net_zero(cell);
+        let net_one = netlist.net_one(cell);
+        enc.len(nets.len());
+        for (i, net) in nets.iter().enumerate() {
+            // Constant nets exist already in a new cell. They must not be created again.
+            let kind = if net == &net_zero {
+                1
+            } else if net == &net_one {
+                2
+            } else {
+                0
+            };
+            enc.u8(kind);
+            enc.opt_str(netlist.net_name(net));
+            net_indices.insert(net.clone(), i as u32);
+        }
+
+        for pin in &pins {
+            enc.opt_index(netlist.net_of_pin(pin).map(|net| net_indices[&net]));
+        }
+    }
+
+    // Pin instances are stored in the order of the pins of the template.
+    for inst in &table.instances {
+        for pin in netlist.each_pin(&netlist.template_cell(inst)) {
+            let pin_inst = netlist.pin_instance(inst, &pin);
+            enc.opt_index(
+                netlist
+                    .net_of_pin_instance(&pin_inst)
+                    .map(|net| net_indices[&net]),
+            );
+        }
+    }
+
+    enc.finish()
+}
+
+fn encode_layout<L: LayoutBase<Coord = i32>>(
+    layout: &L,
+    table: &CellTable<L>,
+) -> Result<Vec<u8>, SnapshotError> {
+    let mut enc = Encoder::default();
+
+    enc.i32(layout.dbu());
+
+    let layers: Vec<_> = layout.each_layer().collect();
+    enc.len(layers.len());
+    for layer in &layers {
+        let info = layout.layer_info(layer);
+        enc.u32(info.index);
+        enc.u32(info.datatype);
+        enc.opt_str(info.name);
+    }
+
+    for inst in &table.instances {
+        enc.transform(&layout.get_transform(inst));
+    }
+
+    for cell in &table.cells {
+        for layer in &layers {
+            let shapes: Vec<_> = layout.each_shape_id(cell, layer).collect();
+            enc.len(shapes.len());
+            for shape in &shapes {
+                enc.geometry(&layout.shape_geometry(shape))?;
+                let mut properties = vec![];
+                layout
+                    .for_each_shape_property(shape, |k, v| properties.push((k.clone(), v.clone())));
+                enc.properties(properties);
+            }
+        }
+    }
+
+    enc.finish()
+}
+
+fn encode_arrays<L: LayoutBase<Coord = i32>>(
+    layout: &L,
+    table: &CellTable<L>,
+) -> Result<Vec<u8>, SnapshotError> {
+    let mut enc = Encoder::default();
+
+    let arrays: Vec<_> = table
+        .instances
+        .iter()
+        .enumerate()
+        .filter_map(|(i, inst)| layout.cell_instance_array(inst).map(|a| (i, a)))
+        .collect();
+    enc.len(arrays.len());
+    for (i, array) in arrays {
+        enc.u32(i as u32);
+        enc.u32(array.columns);
+        enc.u32(array.rows);
+        enc.i32(array.column_pitch.x);
+        enc.i32(array.column_pitch.y);
+        enc.i32(array.row_pitch.x);
+        enc.i32(array.row_pitch.y);
+    }
+
+    enc.finish()
+}
+
+fn encode_placement<L: LayoutBase>(
+    layout: &L,
+    table: &CellTable<L>,
+) -> Result<Vec<u8>, SnapshotError> {
+    let mut enc = Encoder::default();
+
+    let statuses: Vec<_> = table
+        .instances
+        .iter()
+        .enumerate()
+        .map(|(i, inst)| (i, layout.placement_status(inst)))
+        .filter(|(_, status)| *status != PlacementStatus::default())
+        .collect();
+    enc.len(statuses.len());
+    for (i, status) in statuses {
+        enc.u32(i as u32);
+        enc.u8(placement_status_to_u8(status));
+    }
+
+    enc.finish()
+}
+
+fn encode_buses<N: BusBase>(netlist: &N, table: &CellTable<N>) -> Result<Vec<u8>, SnapshotError> {
+    let mut enc = Encoder::default();
+
+    for cell in &table.cells {
+        // Use the same order as in the netlist section.
+        let pin_indices: HashMap<_, _> = netlist
+            .each_pin(cell)
+            .enumerate()
+            .map(|(i, pin)| (pin, i as u32))
+            .collect();
+        let net_indices: HashMap<_, _> = cell_nets(netlist, cell)
+            .into_iter()
+            .enumerate()
+            .map(|(i, net)| (net, i as u32))
+            .collect();
+
+        let pin_buses = netlist.each_pin_bus_vec(cell);
+        enc.len(pin_buses.len());
+        for bus in &pin_buses {
+            enc.name(netlist.pin_bus_name(bus));
+            enc.u8(bit_order_to_u8(netlist.pin_bus_bit_order(bus)));
+            let pins = netlist.each_pin_of_bus_vec(bus);
+            enc.len(pins.len());
+            pins.iter().for_each(|pin| enc.u32(pin_indices[pin]));
+        }
+
+        let net_buses = netlist.each_net_bus_vec(cell);
+        enc.len(net_buses.len());
+        for bus in &net_buses {
+            enc.name(netlist.net_bus_name(bus));
+            enc.u8(bit_order_to_u8(netlist.net_bus_bit_order(bus)));
+            let nets = netlist.each_net_of_bus_vec(bus);
+            enc.len(nets.len());
+            nets.iter().for_each(|net| enc.u32(net_indices[net]));
+        }
+    }
+
+    enc.finish()
+}
+
+fn encode_power<N: PowerBase>(netlist: &N, table: &CellTable<N>) -> Result<Vec<u8>, SnapshotError> {
+    let mut enc = Encoder::default();
+    let cell_indices = table.cell_indices();
+
+    let domains = netlist.each_power_domain_vec();
+    enc.len(domains.len());
+    for domain in &domains {
+        let parent = netlist.parent_cell_of_power_domain(domain);
+        // Both nets live in the parent cell. Use the same order as in the netlist section.
+        let net_indices: HashMap<_, _> = cell_nets(netlist, &parent)
+            .into_iter()
+            .enumerate()
+            .map(|(i, net)| (net, i as u32))
+            .collect();
+        enc.name(netlist.power_domain_name(domain));
+        enc.u32(cell_indices[&parent]);
+        enc.u32(net_indices[&netlist.power_domain_supply_net(domain)]);
+        enc.u32(net_indices[&netlist.power_domain_ground_net(domain)]);
+        enc.f64(netlist.power_domain_voltage(domain));
+        enc.u8(netlist.power_domain_is_always_on(domain) as u8);
+    }
+    let domain_indices: HashMap<_, _> = domains
+        .into_iter()
+        .enumerate()
+        .map(|(i, d)| (d, i as u32))
+        .collect();
+
+    let cells: Vec<_> = table
+        .cells
+        .iter()
+        .enumerate()
+        .filter_map(|(i, cell)| netlist.power_domain_of_cell(cell).map(|d| (i, d)))
+        .collect();
+    enc.len(cells.len());
+    for (i, domain) in cells {
+        enc.u32(i as u32);
+        enc.u32(domain_indices[&domain]);
+    }
+
+    let instances: Vec<_> = table
+        .instances
+        .iter()
+        .enumerate()
+        .filter_map(|(i, inst)| {
+            netlist
+                .assigned_power_domain_of_cell_instance(inst)
+                .map(|d| (i, d))
+        })
+        .collect();
+    enc.len(instances.len());
+    for (i, domain) in instances {
+        enc.u32(i as u32);
+        enc.u32(domain_indices[&domain]);
+    }
+
+    enc.finish()
+}
+
+fn encode_l2n<LN: L2NBase>(chip: &LN, table: &CellTable<LN>) -> Result<Vec<u8>, SnapshotError> {
+    let mut enc = Encoder::default();
+
+    let layers: Vec<_> = chip.each_layer().collect();
+    for cell in &table.cells {
+        // Use the same order as in the netlist section.
+        let net_indices: HashMap<_, _> = cell_nets(chip, cell)
+            .into_iter()
+            .enumerate()
+            .map(|(i, net)| (net, i as u32))
+            .collect();
+        let pin_indices: HashMap<_, _> = chip
+            .each_pin(cell)
+            .enumerate()
+            .map(|(i, pin)| (pin, i as u32))
+            .collect();
+
+        for layer in &layers {
+            for shape in chip.each_shape_id(cell, layer) {
+                enc.opt_index(chip.get_net_of_shape(&shape).map(|n| net_indices[&n]));
+                enc.opt_index(chip.get_pin_of_shape(&shape).map(|p| pin_indices[&p]));
+            }
+        }
+    }
+
+    enc.finish()
+}
+
+// Reading.
+
+/// Check the header and pass the content of each section to `f`.
+fn read_sections<R: Read, F>(reader: &mut R, mut f: F) -> Result<(), SnapshotError>
+where
+    F: FnMut(u8, &mut Decoder<'_>) -> Result<(), SnapshotError>,
+{
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        return Err(SnapshotError::InvalidMagic);
+    }
+    let mut version = [0; 4];
+    reader.read_exact(&mut version)?;
+    let major = u16::from_le_bytes([version[0], version[1]]);
+    let minor = u16::from_le_bytes([version[2], version[3]]);
+    if major != FORMAT_VERSION_MAJOR {
+        return Err(SnapshotError::UnsupportedVersion { major, minor });
+    }
+
+    loop {
+        let mut section_header = [0; 9];
+        reader.read_exact(&mut section_header)?;
+        let tag = section_header[0];
+        let len = u64::from_le_bytes(section_header[1..].try_into().unwrap());
+        if tag == SECTION_END {
+            return Ok(());
+        }
+
+        let mut data = Vec::new();
+        reader.by_ref().take(len).read_to_end(&mut data)?;
+        if data.len() as u64 != len {
+            return Err(SnapshotError::Malformed("unexpected end of data"));
+        }
+        let mut decoder = Decoder { data: &data };
+        f(tag, &mut decoder)?;
+        if !decoder.data.is_empty() {
+            return Err(SnapshotError::Malformed("trailing bytes in section"));
+        }
+    }
+}
+
+/// Read values from the content of a section.
+struct Decoder<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], SnapshotError> {
+        if self.data.len() < N {
+            return Err(SnapshotError::Malformed("unexpected end of section"));
+        }
+        let (head, tail) = self.data.split_at(N);
+        self.data = tail;
+        Ok(head.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, SnapshotError> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, SnapshotError> {
+        Ok(u32::from_le_bytes(self.take()?))
+    }
+
+    fn i32(&mut self) -> Result<i32, SnapshotError> {
+        Ok(i32::from_le_bytes(self.take()?))
+    }
+
+    fn f64(&mut self) -> Result<f64, SnapshotError> {
+        Ok(f64::from_le_bytes(self.take()?))
+    }
+
+    fn len(&mut self) -> Result<usize, SnapshotError> {
+        Ok(self.u32()? as usize)
+    }
+
+    fn bool(&mut self) -> Result<bool, SnapshotError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(SnapshotError::Malformed("invalid boolean")),
+        }
+    }
+
+    fn bytes(&mut self) -> Result<Vec<u8>, SnapshotError> {
+        let len = self.len()?;
+        if self.data.len() < len {
+            return Err(SnapshotError::Malformed("unexpected end of section"));
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Ok(head.to_vec())
+    }
+
+    fn string(&mut self) -> Result<String, SnapshotError> {
+        String::from_utf8(self.bytes()?).map_err(|_| SnapshotError::Malformed("invalid UTF-8"))
+    }
+
+    fn opt_string(&mut self) -> Result<Option<String>, SnapshotError> {
+        Ok(if self.bool()? {
+            Some(self.string()?)
+        } else {
+            None
+        })
+    }
+
+    /// Read an index and look up the element in `table`.
+    fn index<'t, T>(&mut self, table: &'t [T]) -> Result<&'t T, SnapshotError> {
+        let i = self.u32()? as usize;
+        table
+            .get(i)
+            .ok_or(SnapshotError::Malformed("index out of range"))
+    }
+
+    fn opt_index<'t, T>(&mut self, table: &'t [T]) -> Result<Option<&'t T>, SnapshotError> {
+        Ok(if self.bool()? {
+            Some(self.index(table)?)
+        } else {
+            None
+        })
+    }
+
+    fn point(&mut self) -> Result<Point<i32>, SnapshotError> {
+        Ok(Point::new(self.i32()?, self.i32()?))
+    }
+
+    fn points(&mut self) -> Result<Vec<Point<i32>>, SnapshotError> {
+        (0..self.len()?).map(|_| self.point()).collect()
+    }
+
+    fn property_value(&mut self) -> Result<PropertyValue, SnapshotError> {
+        Ok(match self.u8()? {
+            0 => PropertyValue::String(self.string()?.into()),
+            1 => PropertyValue::Bytes(self.bytes()?),
+            2 => PropertyValue::SInt(self.i32()?),
+            3 => PropertyValue::UInt(self.u32()?),
+            4 => PropertyValue::Float(self.f64()?),
+            _ => return Err(SnapshotError::Malformed("invalid property type")),
+        })
+    }
+
+    /// Read properties and store them with `f`.
+    fn properties<F>(&mut self, mut f: F) -> Result<(), SnapshotError>
+    where
+        F: FnMut(String, PropertyValue),
+    {
+        for _ in 0..self.len()? {
+            let key = self.string()?;
+            let value = self.property_value()?;
+            f(key, value);
+        }
+        Ok(())
+    }
+
+    fn transform(&mut self) -> Result<SimpleTransform<i32>, SnapshotError> {
+        let mirror = self.bool()?;
+        let rotation = match self.u8()? {
+            0 => Angle::R0,
+            1 => Angle::R90,
+            2 => Angle::R180,
+            3 => Angle::R270,
+            _ => return Err(SnapshotError::Malformed("invalid rotation")),
+        };
+        let magnification = self.i32()?;
+        let displacement = Vector::new(self.i32()?, self.i32()?);
+        Ok(SimpleTransform::new(
+            mirror,
+            rotation,
+            magnification,
+            displacement,
+        ))
+    }
+
+    fn geometry(&mut self) -> Result<Geometry<i32>, SnapshotError> {
+        Ok(match self.u8()? {
+            1 => self.point()?.into(),
+            2 => Edge::new(self.point()?, self.point()?).into(),
+            3 => Rect::new(self.point()?, self.point()?).into(),
+            4 => SimplePolygon::new(self.points()?).into(),
+            5 => {
+                let exterior = SimplePolygon::new(self.points()?);
+                let interiors = (0..self.len()?)
+                    .map(|_| self.points().map(SimplePolygon::new))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Polygon {
+                    exterior,
+                    interiors,
+                }
+                .into()
+            }
+            6 => {
+                let points = self.points()?;
+                let width = self.i32()?;
+                match self.u8()? {
+                    0 => Path::new(points, width),
+                    1 => Path::new_extended(points, width, self.i32()?, self.i32()?),
+                    2 => Path::new_rounded(points, width),
+                    _ => return Err(SnapshotError::Malformed("invalid path type")),
+                }
+                .into()
+            }
+            7 => Text::new(self.string()?, self.point()?).into(),
+            _ => return Err(SnapshotError::Malformed("invalid geometry type")),
+        })
+    }
+}
+
+fn decode_hierarchy<H: HierarchyEdit>(
+    dec: &mut Decoder<'_>,
+    chip: &mut H,
+) -> Result<CellTable<H>, SnapshotError> {
+    dec.properties(|k, v| chip.set_chip_property(k.into(), v))?;
+
+    let num_cells = dec.len()?;
+    // Don't trust the counts for preallocation.
+    let mut cells = Vec::new();
+    for _ in 0..num_cells {
+        let name = dec.string()?;
+        if chip.cell_by_name(&name).is_some() {
+            return Err(SnapshotError::CellNameCollision(name));
+        }
+        let cell = chip.create_cell(name.into());
+        dec.properties(|k, v| chip.set_cell_property(&cell, k.into(), v))?;
+        cells.push(cell);
+    }
+
+    let num_instances = dec.len()?;
+    let mut instances = Vec::new();
+    for _ in 0..num_instances {
+        let parent = dec.index(&cells)?;
+        let template = dec.index(&cells)?;
+        let name = dec.opt_string()?;
+        if let Some(name) = &name {
+            if chip.cell_instance_by_name(parent, name).is_some() {
+                return Err(SnapshotError::Malformed("duplicate cell instance name"));
+            }
+        }
+        if chip.cell_depends_on(template, parent) {
+            return Err(SnapshotError::Malformed("recursive cell instance"));
+        }
+        let inst = chip.create_cell_instance(parent, template, name.map(|n| n.into()));
+        dec.properties(|k, v| chip.set_cell_instance_property(&inst, k.into(), v))?;
+        instances.push(inst);
+    }
+
+    Ok(CellTable { cells, instances })
+}
+
+fn decode_netlist<N: NetlistEdit>(
+    dec: &mut Decoder<'_>,
+    netlist: &mut N,
+    table: &CellTable<N>,
+) -> Result<NetTable<N>, SnapshotError> {
+    let mut all_pins = Vec::with_capacity(table.cells.len());
+    let mut all_nets = Vec::with_capacity(table.cells.len());
+
+    for cell in &table.cells {
+        let mut pins = Vec::new();
+        for _ in 0..dec.len()? {
+            let name = dec.string()?;
+            let direction = direction_from_u8(dec.u8()?)?;
+            pins.push(netlist.create_pin(cell, name.into(), direction));
+        }
+
+        let mut nets = Vec::new();
+        for _ in 0..dec.len()? {
+            let kind = dec.u8()?;
+            let name = dec.opt_string()?;
+            let net = match kind {
+                0 => netlist.create_net(cell, name.map(|n| n.into())),
+                1 => netlist.net_zero(cell),
+                2 => netlist.net_one(cell),
+                _ => return Err(SnapshotError::Malformed("invalid net type")),
+            };
+            nets.push(net);
+        }
+
+        for pin in &pins {
+            if let Some(net) = dec.opt_index(&nets)? {
+                netlist.connect_pin(pin, Some(net.clone()));
+            }
+        }
+
+        all_pins.push(pins);
+        all_nets.push(nets);
+    }
+
+    // Index of each cell in the tables.
+    let cell_indices = table.cell_indices();
+    for inst in &table.instances {
+        let parent_nets = &all_nets[cell_indices[&netlist.parent_cell(inst)] as usize];
+        let template_pins = &all_pins[cell_indices[&netlist.template_cell(inst)] as usize];
+        for pin in template_pins {
+            if let Some(net) = dec.opt_index(parent_nets)? {
+                let pin_inst = netlist.pin_instance(inst, pin);
+                netlist.connect_pin_instance(&pin_inst, Some(net.clone()));
+            }
+        }
+    }
+
+    Ok(NetTable {
+        pins: all_pins,
+        nets: all_nets,
+    })
+}
+
+/// Returns the created shapes together with the index of their cell.
+fn decode_layout<L: LayoutEdit<Coord = i32>>(
+    dec: &mut Decoder<'_>,
+    layout: &mut L,
+    table: &CellTable<L>,
+) -> Result<Vec<(usize, L::ShapeId)>, SnapshotError> {
+    layout.set_dbu(dec.i32()?);
+
+    let mut layers = Vec::new();
+    for _ in 0..dec.len()? {
+        let index = dec.u32()?;
+        let datatype = dec.u32()?;
+        let name = dec.opt_string()?;
+        let layer = layout
+            .find_layer(index, datatype)
+            .unwrap_or_else(|| layout.create_layer(index, datatype));
+        if name.is_some() {
+            layout.set_layer_name(&layer, name.map(|n| n.into()));
+        }
+        layers.push(layer);
+    }
+
+    for inst in &table.instances {
+        layout.set_transform(inst, dec.transform()?);
+    }
+
+    let mut shapes = Vec::new();
+    for (cell_index, cell) in table.cells.iter().enumerate() {
+        for layer in &layers {
+            for _ in 0..dec.len()? {
+                let shape = layout.insert_shape(cell, layer, dec.geometry()?);
+                dec.properties(|k, v| layout.set_shape_property(&shape, k.into(), v))?;
+                shapes.push((cell_index, shape));
+            }
+        }
+    }
+
+    Ok(shapes)
+}
+
+fn decode_arrays<L: LayoutEdit<Coord = i32>>(
+    dec: &mut Decoder<'_>,
+    layout: &mut L,
+    table: &CellTable<L>,
+) -> Result<(), SnapshotError> {
+    for _ in 0..dec.len()? {
+        let inst = dec.index(&table.instances)?;
+        let (columns, rows) = (dec.u32()?, dec.u32()?);
+        if columns == 0 || rows == 0 {
+            return Err(SnapshotError::Malformed("empty instance array"));
+        }
+        let column_pitch = Vector::new(dec.i32()?, dec.i32()?);
+        let row_pitch = Vector::new(dec.i32()?, dec.i32()?);
+        let array = InstanceArray::new(columns, rows, column_pitch, row_pitch);
+        layout.set_cell_instance_array(inst, Some(array));
+    }
+    Ok(())
+}
+
+fn decode_placement<L: LayoutEdit>(
+    dec: &mut Decoder<'_>,
+    layout: &mut L,
+    table: &CellTable<L>,
+) -> Result<(), SnapshotError> {
+    for _ in 0..dec.len()? {
+        let inst = dec.index(&table.instances)?;
+        let status = placement_status_from_u8(dec.u8()?)?;
+        layout.set_placement_status(inst, status);
+    }
+    Ok(())
+}
+
+/// Read the bits of a bus. The bits must be distinct and not be part of another bus yet.
+fn decode_bus_bits<T: Clone + PartialEq>(
+    dec: &mut Decoder<'_>,
+    table: &[T],
+    is_in_bus: impl Fn(&T) -> bool,
+) -> Result<Vec<T>, SnapshotError> {
+    let mut bits = Vec::new();
+    for _ in 0..dec.len()? {
+        let bit = dec.index(table)?;
+        if is_in_bus(bit) || bits.contains(bit) {
+            return Err(SnapshotError::Malformed("bit is part of multiple buses"));
+        }
+        bits.push(bit.clone());
+    }
+    if bits.is_empty() {
+        return Err(SnapshotError::Malformed("empty bus"));
+    }
+    Ok(bits)
+}
+
+fn decode_buses<N: BusEdit>(
+    dec: &mut Decoder<'_>,
+    netlist: &mut N,
+    net_table: &NetTable<N>,
+) -> Result<(), SnapshotError> {
+    for (cell_pins, cell_nets) in net_table.pins.iter().zip(&net_table.nets) {
+        for _ in 0..dec.len()? {
+            let name = dec.string()?;
+            let bit_order = bit_order_from_u8(dec.u8()?)?;
+            let pins = decode_bus_bits(dec, cell_pins, |p| netlist.bus_of_pin(p).is_some())?;
+            let cell = netlist.parent_cell_of_pin(&pins[0]);
+            if netlist.pin_bus_by_name(&cell, &name).is_some() {
+                return Err(SnapshotError::Malformed("duplicate pin bus name"));
+            }
+            netlist.create_pin_bus_from_pins(name.into(), pins, bit_order);
+        }
+
+        for _ in 0..dec.len()? {
+            let name = dec.string()?;
+            let bit_order = bit_order_from_u8(dec.u8()?)?;
+            let nets = decode_bus_bits(dec, cell_nets, |n| netlist.bus_of_net(n).is_some())?;
+            let cell = netlist.parent_cell_of_net(&nets[0]);
+            if netlist.net_bus_by_name(&cell, &name).is_some() {
+                return Err(SnapshotError::Malformed("duplicate net bus name"));
+            }
+            netlist.create_net_bus_from_nets(name.into(), nets, bit_order);
+        }
+    }
+    Ok(())
+}
+
+fn decode_power<N: PowerEdit>(
+    dec: &mut Decoder<'_>,
+    netlist: &mut N,
+    table: &CellTable<N>,
+    net_table: &NetTable<N>,
+) -> Result<(), SnapshotError> {
+    let mut domains = Vec::new();
+    for _ in 0..dec.len()? {
+        let name = dec.string()?;
+        // Both nets live in the parent cell of the domain.
+        let nets = dec.index(&net_table.nets)?;
+        let supply_net = dec.index(nets)?.clone();
+        let ground_net = dec.index(nets)?.clone();
+        let voltage = dec.f64()?;
+        let always_on = dec.bool()?;
+        if netlist.power_domain_by_name(&name).is_some() {
+            return Err(SnapshotError::PowerDomainNameCollision(name));
+        }
+        domains.push(netlist.create_power_domain(
+            name.into(),
+            supply_net,
+            ground_net,
+            voltage,
+            always_on,
+        ));
+    }
+
+    for _ in 0..dec.len()? {
+        let cell = dec.index(&table.cells)?;
+        let domain = dec.index(&domains)?;
+        netlist.set_power_domain_of_cell(cell, Some(domain.clone()));
+    }
+    for _ in 0..dec.len()? {
+        let inst = dec.index(&table.instances)?;
+        let domain = dec.index(&domains)?;
+        netlist.set_power_domain_of_cell_instance(inst, Some(domain.clone()));
+    }
+    Ok(())
+}
+
+fn decode_l2n<LN: L2NEdit>(
+    dec: &mut Decoder<'_>,
+    chip: &mut LN,
+    netlist: &NetTable<LN>,
+    shapes: &[(usize, LN::ShapeId)],
+) -> Result<(), SnapshotError> {
+    for (cell_index, shape) in shapes {
+        if let Some(net) = dec.opt_index(&netlist.nets[*cell_index])? {
+            chip.set_net_of_shape(shape, Some(net.clone()));
+        }
+        if let Some(pin) = dec.opt_index(&netlist.pins[*cell_index])? {
+            chip.set_pin_of_shape(shape, Some(pin.clone()));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip::Chip;
+
+    fn create_test_chip() -> Chip {
+        let mut chip = Chip::new();
+        chip.set_chip_property("design".into(), PropertyValue::String("test".into()));
+        let layer = chip.create_layer(1, 0);
+        chip.set_layer_name(&layer, Some("metal1".into()));
+
+        let top = chip.create_cell("TOP".into());
+        let leaf = chip.create_cell("LEAF".into());
+        chip.set_cell_property(&leaf, "area".into(), PropertyValue::Float(1.5));
+
+        let a = chip.create_pin(&leaf, "A".into(), Direction::Input);
+        let y = chip.create_pin(&leaf, "Y".into(), Direction::Output);
+        let leaf_net = chip.create_net(&leaf, Some("n".into()));
+        chip.connect_pin(&a, Some(leaf_net));
+        let tie = chip.net_one(&leaf);
+        chip.connect_pin(&y, Some(tie));
+
+        let pin_shape = chip.insert_shape(&leaf, &layer, Rect::new((0, 0), (1, 1)).into());
+        chip.set_pin_of_shape(&pin_shape, Some(a));
+        chip.set_net_of_shape(&pin_shape, Some(leaf_net));
+        chip.set_shape_property(&pin_shape, "mask".into(), PropertyValue::UInt(2));
+        chip.insert_shape(
+            &leaf,
+            &layer,
+            Path::new(vec![Point::new(0, 0), Point::new(10, 0)], 2).into(),
+        );
+
+        let inst = chip.create_cell_instance(&top, &leaf, Some("u1".into()));
+        chip.set_transform(&inst, SimpleTransform::translate((100, 200)));
+        chip.set_cell_instance_property(&inst, "fixed".into(), PropertyValue::SInt(1));
+        let top_net = chip.create_net(&top, Some("in".into()));
+        let pin_inst = chip.pin_instance(&inst, &a);
+        chip.connect_pin_instance(&pin_inst, Some(top_net));
+        let zero = chip.net_zero(&top);
+        let pin_inst = chip.pin_instance(&inst, &y);
+        chip.connect_pin_instance(&pin_inst, Some(zero));
+
+        chip
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let chip = create_test_chip();
+
+        let mut buffer = Vec::new();
+        SnapshotWriter::new()
+            .write_snapshot(&mut buffer, &chip)
+            .unwrap();
+        let restored: Chip = SnapshotReader::new()
+            .read_snapshot(&mut buffer.as_slice())
+            .unwrap();
+
+        assert_eq!(
+            restored
+                .get_chip_property(&"design".into())
+                .unwrap()
+                .get_str(),
+            Some("test")
+        );
+        let layer = restored.layer_by_name("metal1").unwrap();
+
+        let top = restored.cell_by_name("TOP").unwrap();
+        let leaf = restored.cell_by_name("LEAF").unwrap();
+        assert_eq!(
+            restored
+                .get_cell_property(&leaf, &"area".into())
+                .unwrap()
+                .get_float(),
+            Some(1.5)
+        );
+
+        // Netlist.
+        let a = restored.pin_by_name(&leaf, "A").unwrap();
+        let y = restored.pin_by_name(&leaf, "Y").unwrap();
+        assert_eq!(restored.pin_direction(&y), Direction::Output);
+        let leaf_net = restored.net_by_name(&leaf, "n").unwrap();
+        assert_eq!(restored.net_of_pin(&a), Some(leaf_net));
+        assert_eq!(restored.net_of_pin(&y), Some(restored.net_one(&leaf)));
+
+        let inst = restored.cell_instance_by_name(&top, "u1").unwrap();
+        assert_eq!(
+            restored.get_transform(&inst),
+            SimpleTransform::translate((100, 200))
+        );
+        assert_eq!(
+            restored
+                .get_cell_instance_property(&inst, &"fixed".into())
+                .unwrap()
+                .get_sint(),
+            Some(1)
+        );
+        let top_net = restored.net_by_name(&top, "in").unwrap();
+        assert_eq!(
+            restored.net_of_pin_instance(&restored.pin_instance(&inst, &a)),
+            Some(top_net)
+        );
+        assert_eq!(
+            restored.net_of_pin_instance(&restored.pin_instance(&inst, &y)),
+            Some(restored.net_zero(&top))
+        );
+
+        // Layout and L2N links.
+        let shapes: Vec<_> = restored.each_shape_id(&leaf, &layer).collect();
+        assert_eq!(shapes.len(), 2);
+        let pin_shape = shapes
+            .iter()
+            .find(|s| restored.get_pin_of_shape(s).is_some())
+            .unwrap();
+        assert_eq!(restored.get_pin_of_shape(pin_shape), Some(a));
+        assert_eq!(restored.get_net_of_shape(pin_shape), Some(leaf_net));
+        assert_eq!(
+            restored
+                .get_shape_property(pin_shape, &"mask".into())
+                .unwrap()
+                .get_uint(),
+            Some(2)
+        );
+        assert_eq!(
+            restored.bounding_box(&top),
+            Some(Rect::new((100, 199), (110, 201)))
+        );
+    }
+
+    #[test]
+    fn test_netlist_and_layout_only() {
+        let chip = create_test_chip();
+        let writer = SnapshotWriter::new();
+
+        // A full snapshot can be read as a netlist. The layout sections are skipped.
+        let mut buffer = Vec::new();
+        writer.write_snapshot(&mut buffer, &chip).unwrap();
+        let netlist: Chip = SnapshotReader::new()
+            .read_netlist(&mut buffer.as_slice())
+            .unwrap();
+        let leaf = netlist.cell_by_name("LEAF").unwrap();
+        assert_eq!(netlist.num_pins(&leaf), 2);
+        assert_eq!(netlist.each_layer().count(), 0);
+
+        let mut buffer = Vec::new();
+        writer.write_layout(&mut buffer, &chip).unwrap();
+        let mut layout = Chip::new();
+        SnapshotReader::new()
+            .read_layout(&mut buffer.as_slice(), &mut layout)
+            .unwrap();
+        let leaf = layout.cell_by_name("LEAF").unwrap();
+        assert_eq!(layout.num_pins(&leaf), 0);
+        assert_eq!(
+            layout.bounding_box(&leaf),
+            Some(Rect::new((0, -1), (10, 1)))
+        );
+    }
+
+    #[test]
+    fn test_snapshot_instance_arrays() {
+        let mut chip = create_test_chip();
+        let top = chip.cell_by_name("TOP").unwrap();
+        let inst = chip.cell_instance_by_name(&top, "u1").unwrap();
+        let array = InstanceArray::new(8, 4, Vector::new(5, 0), Vector::new(0, -10));
+        chip.set_cell_instance_array(&inst, Some(array));
+
+        let mut buffer = Vec::new();
+        SnapshotWriter::new()
+            .write_snapshot(&mut buffer, &chip)
+            .unwrap();
+        let restored: Chip = SnapshotReader::new()
+            .read_snapshot(&mut buffer.as_slice())
+            .unwrap();
+
+        let top = restored.cell_by_name("TOP").unwrap();
+        let inst = restored.cell_instance_by_name(&top, "u1").unwrap();
+        assert_eq!(restored.cell_instance_array(&inst), Some(array));
+    }
+
+    #[test]
+    fn test_snapshot_placement_status() {
+        let mut chip = create_test_chip();
+        let top = chip.cell_by_name("TOP").unwrap();
+        let inst = chip.cell_instance_by_name(&top, "u1").unwrap();
+        chip.set_placement_status(&inst, PlacementStatus::Fixed);
+
+        let mut buffer = Vec::new();
+        SnapshotWriter::new()
+            .write_layout(&mut buffer, &chip)
+            .unwrap();
+        let mut restored = Chip::new();
+        SnapshotReader::new()
+            .read_layout(&mut buffer.as_slice(), &mut restored)
+            .unwrap();
+
+        let top = restored.cell_by_name("TOP").unwrap();
+        let inst = restored.cell_instance_by_name(&top, "u1").unwrap();
+        assert_eq!(restored.placement_status(&inst), PlacementStatus::Fixed);
+    }
+
+    #[test]
+    fn test_snapshot_buses() {
+        let mut chip = create_test_chip();
+        let leaf = chip.cell_by_name("LEAF").unwrap();
+        let a = chip.pin_by_name(&leaf, "A").unwrap();
+        let y = chip.pin_by_name(&leaf, "Y").unwrap();
+        chip.create_pin_bus_from_pins("p".into(), vec![y, a], BitOrder::Ascending);
+        chip.create_net_bus(&leaf, "data".into(), 3, BitOrder::Descending);
+
+        let mut buffer = Vec::new();
+        SnapshotWriter::new()
+            .write_snapshot(&mut buffer, &chip)
+            .unwrap();
+        let restored: Chip = SnapshotReader::new()
+            .read_snapshot(&mut buffer.as_slice())
+            .unwrap();
+
+        let leaf = restored.cell_by_name("LEAF").unwrap();
+        let pin_bus = restored.pin_bus_by_name(&leaf, "p").unwrap();
+        assert_eq!(restored.pin_bus_bit_order(&pin_bus), BitOrder::Ascending);
+        assert_eq!(
+            restored.each_pin_of_bus_vec(&pin_bus),
+            vec![
+                restored.pin_by_name(&leaf, "Y").unwrap(),
+                restored.pin_by_name(&leaf, "A").unwrap()
+            ]
+        );
+        let net_bus = restored.net_bus_by_name(&leaf, "data").unwrap();
+        assert_eq!(restored.net_bus_bit_order(&net_bus), BitOrder::Descending);
+        assert_eq!(restored.net_bus_width(&net_bus), 3);
+        assert_eq!(
+            restored.net_bus_bit(&net_bus, 2),
+            restored.net_by_name(&leaf, "data[2]")
+        );
+    }
+
+    #[test]
+    fn test_snapshot_power_domains() {
+        let mut chip = create_test_chip();
+        let top = chip.cell_by_name("TOP").unwrap();
+        let leaf = chip.cell_by_name("LEAF").unwrap();
+        let inst = chip.cell_instance_by_name(&top, "u1").unwrap();
+        let vdd = chip.create_net(&top, Some("VDD".into()));
+        let vss = chip.net_zero(&top);
+        let core = chip.create_power_domain("CORE".into(), vdd, vss, 0.9, true);
+        let io = chip.create_power_domain("IO".into(), vdd, vss, 3.3, false);
+        chip.set_power_domain_of_cell(&leaf, Some(core));
+        chip.set_power_domain_of_cell_instance(&inst, Some(io));
+
+        let mut buffer = Vec::new();
+        SnapshotWriter::new()
+            .write_snapshot(&mut buffer, &chip)
+            .unwrap();
+        let restored: Chip = SnapshotReader::new()
+            .read_snapshot(&mut buffer.as_slice())
+            .unwrap();
+
+        let top = restored.cell_by_name("TOP").unwrap();
+        let leaf = restored.cell_by_name("LEAF").unwrap();
+        let inst = restored.cell_instance_by_name(&top, "u1").unwrap();
+        let core = restored.power_domain_by_name("CORE").unwrap();
+        let io = restored.power_domain_by_name("IO").unwrap();
+        assert_eq!(restored.parent_cell_of_power_domain(&core), top);
+        assert_eq!(
+            restored.power_domain_supply_net(&core),
+            restored.net_by_name(&top, "VDD").unwrap()
+        );
+        assert_eq!(
+            restored.power_domain_ground_net(&io),
+            restored.net_zero(&top)
+        );
+        assert_eq!(restored.power_domain_voltage(&io), 3.3);
+        assert!(restored.power_domain_is_always_on(&core));
+        assert!(!restored.power_domain_is_always_on(&io));
+        assert_eq!(restored.power_domain_of_cell(&leaf), Some(core));
+        assert_eq!(restored.power_domain_of_cell(&top), None);
+        assert_eq!(
+            restored.assigned_power_domain_of_cell_instance(&inst),
+            Some(io)
+        );
+
+        // Power domain names must not collide with existing domains.
+        let mut other = Chip::new();
+        let cell = other.create_cell("OTHER".into());
+        let net = other.create_net(&cell, None);
+        other.create_power_domain("CORE".into(), net, net, 1.0, false);
+        let result = SnapshotReader::new().read_snapshot_into(&mut buffer.as_slice(), &mut other);
+        assert!(matches!(
+            result,
+            Err(SnapshotError::PowerDomainNameCollision(_))
+        ));
+    }
+
+    #[test]
+    fn test_version_check() {
+        let chip = create_test_chip();
+        let mut buffer = Vec::new();
+        SnapshotWriter::new()
+            .write_snapshot(&mut buffer, &chip)
+            .unwrap();
+
+        // Unknown sections of newer minor versions are skipped.
+        let mut newer_minor = buffer.clone();
+        newer_minor[10..12].copy_from_slice(&(FORMAT_VERSION_MINOR + 1).to_le_bytes());
+        let end = newer_minor.len() - 9;
+        newer_minor.splice(end..end, vec![42, 3, 0, 0, 0, 0, 0, 0, 0, 1, 2, 3]);
+        let result: Result<Chip, _> =
+            SnapshotReader::new().read_snapshot(&mut newer_minor.as_slice());
+        assert!(result.is_ok());
+
+        // Newer major versions are rejected.
+        let mut newer_major = buffer.clone();
+        newer_major[8..10].copy_from_slice(&(FORMAT_VERSION_MAJOR + 1).to_le_bytes());
+        let result: Result<Chip, _> =
+            SnapshotReader::new().read_snapshot(&mut newer_major.as_slice());
+        assert!(matches!(
+            result,
+            Err(SnapshotError::UnsupportedVersion { .. })
+        ));
+
+        let result: Result<Chip, _> = SnapshotReader::new().read_snapshot(&mut &b"GARBAGE!"[..]);
+        assert!(matches!(result, Err(SnapshotError::InvalidMagic)));
+    }
+
+    #[test]
+    fn test_limits() {
+        // Lengths which don't fit into the length field are rejected by the writer.
+        #[cfg(target_pointer_width = "64")]
+        {
+            let mut enc = Encoder::default();
+            enc.len(u32::MAX as usize + 1);
+            assert!(matches!(enc.finish(), Err(SnapshotError::TooLarge)));
+        }
+
+        // Huge counts in a malformed snapshot must not be used to allocate memory.
+        let mut data = Vec::new();
+        data.extend_from_slice(&MAGIC);
+        data.extend_from_slice(&FORMAT_VERSION_MAJOR.to_le_bytes());
+        data.extend_from_slice(&FORMAT_VERSION_MINOR.to_le_bytes());
+        data.push(SECTION_HIERARCHY);
+        data.extend_from_slice(&8u64.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&u32::MAX.to_le_bytes());
+        data.push(SECTION_END);
+        data.extend_from_slice(&0u64.to_le_bytes());
+        let result: Result<Chip, _> = SnapshotReader::new().read_snapshot(&mut data.as_slice());
+        assert!(matches!(result, Err(SnapshotError::Malformed(_))));
+    }
+
+    #[test]
+    fn test_invalid_instances() {
+        // Hierarchy with cells `A` and `B` and instances given as (parent, template, name).
+        let hierarchy = |instances: &[(u32, u32, Option<&str>)]| {
+            let mut enc = Encoder::default();
+            enc.properties(Vec::<(String, PropertyValue)>::new());
+            enc.len(2);
+            for name in ["A", "B"] {
+                enc.str(name);
+                enc.properties(Vec::<(String, PropertyValue)>::new());
+            }
+            enc.len(instances.len());
+            for (parent, template, name) in instances {
+                enc.u32(*parent);
+                enc.u32(*template);
+                enc.opt_str(*name);
+                enc.properties(Vec::<(String, PropertyValue)>::new());
+            }
+            enc.finish().unwrap()
+        };
+
+        let invalid = [
+            hierarchy(&[(0, 0, None)]),
+            hierarchy(&[(0, 1, None), (1, 0, None)]),
+            hierarchy(&[(0, 1, Some("x")), (0, 1, Some("x"))]),
+        ];
+        for data in invalid {
+            let result = decode_hierarchy(&mut Decoder { data: &data }, &mut Chip::new());
+            assert!(matches!(result, Err(SnapshotError::Malformed(_))));
+        }
+    }
+}
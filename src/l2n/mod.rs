@@ -5,6 +5,7 @@
 
 //! Trait definitions for layouts fused with netlists.
 
+pub mod io;
 pub mod util;
 
 use super::traits::*;
@@ -20,6 +21,26 @@ pub trait L2NBase: LayoutBase + NetlistBase {
     fn get_net_of_shape(&self, shape_id: &Self::ShapeId) -> Option<Self::NetId>;
     /// Get the pin that belongs to the shape (if any).
     fn get_pin_of_shape(&self, shape_id: &Self::ShapeId) -> Option<Self::PinId>;
+    /// Get the net of a shape.
+    /// Returns `None` if the shape does not exist and `Some(None)` if the shape exists
+    /// but is not linked to a net.
+    fn try_get_net_of_shape(&self, shape_id: &Self::ShapeId) -> Option<Option<Self::NetId>> {
+        if self.shape_exists(shape_id) {
+            Some(self.get_net_of_shape(shape_id))
+        } else {
+            None
+        }
+    }
+    /// Get the pin that belongs to the shape (if any).
+    /// Returns `None` if the shape does not exist and `Some(None)` if the shape exists
+    /// but is not linked to a pin.
+    fn try_get_pin_of_shape(&self, shape_id: &Self::ShapeId) -> Option<Option<Self::PinId>> {
+        if self.shape_exists(shape_id) {
+            Some(self.get_pin_of_shape(shape_id))
+        } else {
+            None
+        }
+    }
 }
 
 /// Additional requirement that all ID types are `Send + Sync` as needed for multithreading
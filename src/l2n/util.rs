@@ -3,3 +3,904 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
 //! Utility functions for dealing with fused netlist-layouts.
+
+use super::{L2NBase, L2NEdit};
+use crate::hierarchy::util::{HierarchyEditUtil, HierarchyUtil};
+use crate::layout::util::copy_content_of_cell_instance;
+use crate::netlist::bus::{BusBase, BusEdit};
+use crate::netlist::power::{PowerBase, PowerEdit};
+use crate::netlist::util::{NetlistEditUtil, NetlistUtil};
+use std::borrow::Borrow;
+use std::collections::{HashMap, HashSet};
+
+/// Modifying utility functions for fused netlist-layouts.
+///
+/// This trait is automatically implemented for all types which implement [`L2NEdit`].
+pub trait L2NEditUtil: L2NEdit {
+    /// Create a copy of the `cell` with the name `new_name`.
+    ///
+    /// The copy has the same pins, nets, buses, child instances, shapes and properties as the
+    /// original cell. Shapes are linked to the copies of their nets and pins.
+    /// The copy and its child instances are assigned to the same power domains as the originals.
+    /// Power domains which are supplied by nets of `cell` are not duplicated.
+    ///
+    /// # Panics
+    /// Panics if a cell with the name `new_name` already exists.
+    fn clone_cell(&mut self, cell: &Self::CellId, new_name: Self::NameType) -> Self::CellId
+    where
+        Self: BusEdit + PowerEdit,
+    {
+        let new_cell = self.create_cell(new_name);
+        if let Some(domain) = self.power_domain_of_cell(cell) {
+            self.set_power_domain_of_cell(&new_cell, Some(domain));
+        }
+
+        let mut properties = vec![];
+        self.for_each_cell_property(cell, |k, v| properties.push((k.clone(), v.clone())));
+        for (key, value) in properties {
+            self.set_cell_property(&new_cell, key, value);
+        }
+
+        // Pins and pin buses.
+        let mut pin_mapping = HashMap::new();
+        for pin in self.each_pin_vec(cell) {
+            let new_pin = self.create_pin(&new_cell, self.pin_name(&pin), self.pin_direction(&pin));
+            pin_mapping.insert(pin, new_pin);
+        }
+        for bus in self.each_pin_bus_vec(cell) {
+            let pins = self
+                .each_pin_of_bus_vec(&bus)
+                .iter()
+                .map(|p| pin_mapping[p].clone())
+                .collect();
+            self.create_pin_bus_from_pins(
+                self.pin_bus_name(&bus),
+                pins,
+                self.pin_bus_bit_order(&bus),
+            );
+        }
+
+        // Nets and net buses. The constant nets already exist in the new cell.
+        let mut net_mapping = HashMap::new();
+        net_mapping.insert(self.net_zero(cell), self.net_zero(&new_cell));
+        net_mapping.insert(self.net_one(cell), self.net_one(&new_cell));
+        for net in self.each_internal_net_vec(cell) {
+            if !net_mapping.contains_key(&net) {
+                let new_net = self.create_net(&new_cell, self.net_name(&net));
+                net_mapping.insert(net, new_net);
+            }
+        }
+        for bus in self.each_net_bus_vec(cell) {
+            let nets = self
+                .each_net_of_bus_vec(&bus)
+                .iter()
+                .map(|n| net_mapping[n].clone())
+                .collect();
+            self.create_net_bus_from_nets(
+                self.net_bus_name(&bus),
+                nets,
+                self.net_bus_bit_order(&bus),
+            );
+        }
+        for (pin, new_pin) in &pin_mapping {
+            if let Some(net) = self.net_of_pin(pin) {
+                self.connect_pin(new_pin, Some(net_mapping[&net].clone()));
+            }
+        }
+
+        // Child instances.
+        for inst in self.each_cell_instance_vec(cell) {
+            let template = self.template_cell(&inst);
+            let new_inst =
+                self.create_cell_instance(&new_cell, &template, self.cell_instance_name(&inst));
+            copy_cell_instance_attributes(self, &inst, &new_inst);
+            for pin in self.each_pin_vec(&template) {
+                if let Some(net) = self.net_of_pin_instance(&self.pin_instance(&inst, &pin)) {
+                    let new_pin_inst = self.pin_instance(&new_inst, &pin);
+                    self.connect_pin_instance(&new_pin_inst, Some(net_mapping[&net].clone()));
+                }
+            }
+        }
+
+        // Shapes together with their properties and links to nets and pins.
+        let layers: Vec<_> = self.each_layer().collect();
+        for layer in layers {
+            let shapes: Vec<_> = self.each_shape_id(cell, &layer).collect();
+            for shape in shapes {
+                let new_shape = self.insert_shape(&new_cell, &layer, self.shape_geometry(&shape));
+                let mut properties = vec![];
+                self.for_each_shape_property(&shape, |k, v| {
+                    properties.push((k.clone(), v.clone()))
+                });
+                for (key, value) in properties {
+                    self.set_shape_property(&new_shape, key, value);
+                }
+                if let Some(net) = self.get_net_of_shape(&shape) {
+                    self.set_net_of_shape(&new_shape, Some(net_mapping[&net].clone()));
+                }
+                if let Some(pin) = self.get_pin_of_shape(&shape) {
+                    self.set_pin_of_shape(&new_shape, Some(pin_mapping[&pin].clone()));
+                }
+            }
+        }
+
+        new_cell
+    }
+
+    /// Give the cell instance its own copy of the template cell such that modifications
+    /// of the template do not affect other instances.
+    ///
+    /// # Instance ID
+    /// The instance gets a new ID: `inst` is removed and the returned instance takes its place.
+    /// IDs of `inst` and of its pin instances which are stored elsewhere become invalid
+    /// and must be replaced by the returned ID. Nothing is done if the instance
+    /// is the only reference of its template, then `inst` is returned.
+    ///
+    /// The copy is created with [`L2NEditUtil::clone_cell`] and named after the template
+    /// cell with an appended counter. Since the template of an existing instance cannot be changed,
+    /// the instance is replaced by a new instance with the same name, location, properties
+    /// and connections.
+    fn uniquify_instance(&mut self, inst: &Self::CellInstId) -> Self::CellInstId
+    where
+        Self: BusEdit + PowerEdit,
+    {
+        let template = self.template_cell(inst);
+        if self.num_cell_references(&template) <= 1 {
+            return inst.clone();
+        }
+
+        // Find an unused cell name.
+        let template_name = self.cell_name(&template);
+        let new_name = (1..)
+            .map(|i| format!("{}_{}", template_name, i))
+            .find(|name| self.cell_by_name(name).is_none())
+            .unwrap();
+        let new_template = self.clone_cell(&template, new_name.into());
+
+        let parent = self.parent_cell(inst);
+        let new_inst = self.create_cell_instance(&parent, &new_template, None);
+        copy_cell_instance_attributes(self, inst, &new_inst);
+        // Pins of the copy are created in the same order as the pins of the template.
+        let pins = self
+            .each_pin_vec(&template)
+            .into_iter()
+            .zip(self.each_pin_vec(&new_template));
+        for (pin, new_pin) in pins {
+            let net = self.net_of_pin_instance(&self.pin_instance(inst, &pin));
+            let new_pin_inst = self.pin_instance(&new_inst, &new_pin);
+            self.connect_pin_instance(&new_pin_inst, net);
+        }
+
+        let name = self.cell_instance_name(inst);
+        self.remove_cell_instance(inst);
+        self.rename_cell_instance(&new_inst, name);
+        new_inst
+    }
+
+    /// Uniquify all instances below the `top` cell such that each instance path gets
+    /// its own cell. Afterwards each cell in the hierarchy below `top` is referenced only once.
+    ///
+    /// Leaf cells (cells without child instances) stay shared unless `leaf_cells` is `true`.
+    /// Standard-cells are usually kept shared while blocks which contain only shapes may need their
+    /// own copies. Cells without child instances and without shapes always stay shared.
+    ///
+    /// The uniquified instances get new IDs, see [`L2NEditUtil::uniquify_instance`].
+    fn uniquify_hierarchy(&mut self, top: &Self::CellId, leaf_cells: bool)
+    where
+        Self: BusEdit + PowerEdit,
+    {
+        let mut stack = vec![top.clone()];
+        while let Some(cell) = stack.pop() {
+            for inst in self.each_cell_instance_vec(&cell) {
+                let template = self.template_cell(&inst);
+                if self.num_child_instances(&template) == 0 {
+                    let has_shapes = self
+                        .each_layer()
+                        .any(|layer| self.each_shape_id(&template, &layer).next().is_some());
+                    if !leaf_cells || !has_shapes {
+                        continue;
+                    }
+                }
+                let inst = self.uniquify_instance(&inst);
+                stack.push(self.template_cell(&inst));
+            }
+        }
+    }
+
+    /// Replace the cell instance with its layout and netlist content and remove the instance afterwards.
+    ///
+    /// This is the counterpart of [`NetlistEditUtil::flatten_circuit_instance`] which also moves the
+    /// shapes of the template into the parent cell (see [`LayoutEditUtil::flatten_cell_instance`]).
+    /// Copied shapes are attached to the copies of their nets. Inner nets which are connected
+    /// to a pin of the template are replaced by the outer nets connected to the pin instance.
+    /// If the template connects multiple pins internally, the outer nets get merged.
+    /// Links from shapes to pins of the template are dropped.
+    ///
+    /// Each element of an array instance gets its own copies of the inner nets while nets
+    /// connected to pins are shared by all elements.
+    ///
+    /// Nets keep their names if possible. If the net name already exists in the parent cell,
+    /// the name will be set to `None`.
+    ///
+    /// [`LayoutEditUtil::flatten_cell_instance`]: crate::layout::util::LayoutEditUtil::flatten_cell_instance
+    fn flatten_cell_instance_l2n(&mut self, inst: &Self::CellInstId) {
+        let template = self.template_cell(inst);
+        let parent = self.parent_cell(inst);
+
+        // Mapping from nets of the template to nets of the parent cell.
+        let mut net_mapping = HashMap::new();
+        net_mapping.insert(self.net_zero(&template), self.net_zero(&parent));
+        net_mapping.insert(self.net_one(&template), self.net_one(&parent));
+
+        // Inner nets which are connected to a pin are replaced by the outer nets.
+        for pin_inst in self.each_pin_instance_vec(inst) {
+            let outer_net = self.net_of_pin_instance(&pin_inst);
+            let inner_net = self.net_of_pin(&self.template_pin(&pin_inst));
+            let (outer_net, inner_net) = match (outer_net, inner_net) {
+                (Some(outer_net), Some(inner_net)) => (outer_net, inner_net),
+                _ => continue,
+            };
+            match net_mapping.get(&inner_net).cloned() {
+                None => {
+                    net_mapping.insert(inner_net, outer_net);
+                }
+                Some(previous) if previous != outer_net => {
+                    // The pins are shorted inside the template. Merge the outer nets
+                    // but never remove a constant net.
+                    let (old, new) = match (
+                        self.is_constant_net(&previous),
+                        self.is_constant_net(&outer_net),
+                    ) {
+                        (false, _) => (previous, outer_net),
+                        (true, false) => (outer_net, previous),
+                        (true, true) => {
+                            log::warn!("Cell instance shorts constant nets. Nets are not merged.");
+                            continue;
+                        }
+                    };
+                    let shapes: Vec<_> = self.shapes_of_net(&old).collect();
+                    for shape in shapes {
+                        self.set_net_of_shape(&shape, Some(new.clone()));
+                    }
+                    self.replace_net(&old, &new);
+                    for net in net_mapping.values_mut() {
+                        if net == &old {
+                            *net = new.clone();
+                        }
+                    }
+                }
+                Some(_) => {}
+            }
+        }
+
+        let internal_nets = self.each_internal_net_vec(&template);
+        let elements = copy_content_of_cell_instance(self, inst);
+
+        // Array elements share the pin instances but each element gets its own inner nets.
+        for element in elements {
+            let mut element_net_mapping = net_mapping.clone();
+            for net in &internal_nets {
+                if !element_net_mapping.contains_key(net) {
+                    let name = self
+                        .net_name(net)
+                        .filter(|name| self.net_by_name(&parent, name.borrow()).is_none());
+                    let new_net = self.create_net(&parent, name);
+                    element_net_mapping.insert(net.clone(), new_net);
+                }
+            }
+
+            for (child, copy) in element.instances {
+                // Pin instances of both instances have the same ordering.
+                let pin_mapping: Vec<_> = self
+                    .each_pin_instance(&child)
+                    .zip(self.each_pin_instance(&copy))
+                    .collect();
+                for (old_pin, new_pin) in pin_mapping {
+                    if let Some(net) = self.net_of_pin_instance(&old_pin) {
+                        self.connect_pin_instance(&new_pin, element_net_mapping.get(&net).cloned());
+                    }
+                }
+            }
+
+            for (shape, copy) in element.shapes {
+                if let Some(net) = self.get_net_of_shape(&shape) {
+                    self.set_net_of_shape(&copy, element_net_mapping.get(&net).cloned());
+                }
+            }
+        }
+
+        self.remove_cell_instance(inst);
+    }
+}
+
+impl<LN: L2NEdit> L2NEditUtil for LN {}
+
+/// Copy the location, the array parameters, the placement status, the power domain
+/// and the properties from one cell instance to another.
+fn copy_cell_instance_attributes<LN: L2NEdit + PowerEdit + ?Sized>(
+    chip: &mut LN,
+    inst: &LN::CellInstId,
+    new_inst: &LN::CellInstId,
+) {
+    chip.set_transform(new_inst, chip.get_transform(inst));
+    chip.set_cell_instance_array(new_inst, chip.cell_instance_array(inst));
+    chip.set_placement_status(new_inst, chip.placement_status(inst));
+    chip.set_power_domain_of_cell_instance(
+        new_inst,
+        chip.assigned_power_domain_of_cell_instance(inst),
+    );
+    let mut properties = vec![];
+    chip.for_each_cell_instance_property(inst, |k, v| properties.push((k.clone(), v.clone())));
+    for (key, value) in properties {
+        chip.set_cell_instance_property(new_inst, key, value);
+    }
+}
+
+/// Strategy of [`copy_cell_recursive`] for cells whose name already exists in the target.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CellCollisionPolicy {
+    /// Copy the cell under a new name. The name is made unique by appending a counter.
+    Rename,
+    /// Don't copy the cell but use the existing cell of the target instead.
+    /// Pins of the existing cell are matched by name.
+    Skip,
+    /// Replace the content of the existing cell with the content of the source cell.
+    /// Pins are matched by name such that instances of the existing cell stay connected.
+    /// Pins which don't exist in the source cell are removed.
+    Overwrite,
+}
+
+/// Correspondence between the elements of the source and their copies in the target.
+/// This is the result of [`copy_cell_recursive`].
+pub struct CopyMapping<LS: L2NBase + PowerBase, LT: L2NBase + PowerBase> {
+    /// Cells and their copies. Skipped cells map to the existing cells of the target.
+    pub cells: HashMap<LS::CellId, LT::CellId>,
+    /// Cell instances and their copies.
+    pub cell_instances: HashMap<LS::CellInstId, LT::CellInstId>,
+    /// Pins and their copies.
+    pub pins: HashMap<LS::PinId, LT::PinId>,
+    /// Nets and their copies.
+    pub nets: HashMap<LS::NetId, LT::NetId>,
+    /// Shapes and their copies.
+    pub shapes: HashMap<LS::ShapeId, LT::ShapeId>,
+    /// Layers of the source and the corresponding layers of the target.
+    pub layers: HashMap<LS::LayerId, LT::LayerId>,
+    /// Power domains and their copies.
+    pub power_domains: HashMap<LS::PowerDomainId, LT::PowerDomainId>,
+}
+
+/// Copy the `cell` together with all cells below it from the `source` into the `target` data base.
+///
+/// Pins, nets, buses, cell instances with their connections, transforms and placement status,
+/// shapes, links between shapes and nets or pins as well as properties of cells, instances
+/// and shapes are copied. Layers are matched by their index and data type and created
+/// in the target if necessary.
+///
+/// Power domains whose supply nets live in a copied cell are copied as well. If the name of
+/// a power domain exists already in the target, a counter is appended. Copied cells and instances
+/// are assigned to the copies of their power domains. Assignments to domains which are not copied
+/// are mapped to the domain with the same name in the target or are dropped if there is none.
+///
+/// Cells whose name already exists in the target are handled according to the `policy`.
+///
+/// Returns the mapping from the IDs of the source to the IDs of the copies.
+pub fn copy_cell_recursive<LS, LT>(
+    source: &LS,
+    cell: &LS::CellId,
+    target: &mut LT,
+    policy: CellCollisionPolicy,
+) -> CopyMapping<LS, LT>
+where
+    LS: L2NBase + BusBase + PowerBase,
+    LT: L2NEdit<Coord = LS::Coord> + BusEdit + PowerEdit,
+{
+    let mut mapping = CopyMapping {
+        cells: HashMap::new(),
+        cell_instances: HashMap::new(),
+        pins: HashMap::new(),
+        nets: HashMap::new(),
+        shapes: HashMap::new(),
+        layers: HashMap::new(),
+        power_domains: HashMap::new(),
+    };
+
+    for layer in source.each_layer() {
+        let info = source.layer_info(&layer);
+        let target_layer = match target.find_layer(info.index, info.datatype) {
+            Some(l) => l,
+            None => {
+                let l = target.create_layer(info.index, info.datatype);
+                if let Some(name) = &info.name {
+                    if target.layer_by_name(name.borrow()).is_none() {
+                        target.set_layer_name(&l, Some(convert_name(name)));
+                    }
+                }
+                l
+            }
+        };
+        mapping.layers.insert(layer, target_layer);
+    }
+
+    // Find the cells which need to be copied. Skipped cells are not descended into.
+    let mut cells_to_copy = HashSet::new();
+    let mut stack = vec![cell.clone()];
+    while let Some(c) = stack.pop() {
+        if cells_to_copy.contains(&c) || mapping.cells.contains_key(&c) {
+            continue;
+        }
+        let name = source.cell_name(&c).to_string();
+        match target.cell_by_name(&name) {
+            Some(existing) if policy == CellCollisionPolicy::Skip => {
+                for pin in source.each_pin(&c) {
+                    if let Some(p) = target.pin_by_name(&existing, source.pin_name(&pin).borrow()) {
+                        mapping.pins.insert(pin, p);
+                    }
+                }
+                mapping.cells.insert(c, existing);
+            }
+            _ => {
+                stack.extend(source.each_cell_dependency(&c));
+                cells_to_copy.insert(c);
+            }
+        }
+    }
+
+    // Copy bottom-up such that the templates of the instances exist already.
+    for c in source.each_cell_bottom_to_top() {
+        if !cells_to_copy.contains(&c) {
+            continue;
+        }
+        let name = source.cell_name(&c).to_string();
+        let target_cell = match target.cell_by_name(&name) {
+            None => target.create_cell(name.into()),
+            Some(existing) if policy == CellCollisionPolicy::Overwrite => {
+                clear_cell(target, &existing);
+                existing
+            }
+            Some(_) => {
+                let new_name = (1..)
+                    .map(|i| format!("{}_{}", name, i))
+                    .find(|n| target.cell_by_name(n).is_none())
+                    .unwrap();
+                target.create_cell(new_name.into())
+            }
+        };
+        copy_cell_content(source, &c, target, &target_cell, &mut mapping);
+        mapping.cells.insert(c, target_cell);
+    }
+
+    // Power domains can be created once the nets of all cells exist.
+    for domain in source.each_power_domain_vec() {
+        if !cells_to_copy.contains(&source.parent_cell_of_power_domain(&domain)) {
+            continue;
+        }
+        let name = source.power_domain_name(&domain).to_string();
+        let name = if target.power_domain_by_name(&name).is_none() {
+            name
+        } else {
+            (1..)
+                .map(|i| format!("{}_{}", name, i))
+                .find(|n| target.power_domain_by_name(n).is_none())
+                .unwrap()
+        };
+        let target_domain = target.create_power_domain(
+            name.into(),
+            mapping.nets[&source.power_domain_supply_net(&domain)].clone(),
+            mapping.nets[&source.power_domain_ground_net(&domain)].clone(),
+            source.power_domain_voltage(&domain),
+            source.power_domain_is_always_on(&domain),
+        );
+        mapping.power_domains.insert(domain, target_domain);
+    }
+    for c in &cells_to_copy {
+        let domain = source
+            .power_domain_of_cell(c)
+            .and_then(|d| target_power_domain(source, target, &mapping, &d));
+        target.set_power_domain_of_cell(&mapping.cells[c], domain);
+    }
+    for (inst, target_inst) in &mapping.cell_instances {
+        let domain = source
+            .assigned_power_domain_of_cell_instance(inst)
+            .and_then(|d| target_power_domain(source, target, &mapping, &d));
+        target.set_power_domain_of_cell_instance(target_inst, domain);
+    }
+
+    mapping
+}
+
+/// Find the power domain of the target which corresponds to the `domain` of the source.
+fn target_power_domain<LS, LT>(
+    source: &LS,
+    target: &LT,
+    mapping: &CopyMapping<LS, LT>,
+    domain: &LS::PowerDomainId,
+) -> Option<LT::PowerDomainId>
+where
+    LS: L2NBase + PowerBase,
+    LT: L2NBase + PowerBase,
+{
+    mapping.power_domains.get(domain).cloned().or_else(|| {
+        let name = source.power_domain_name(domain);
+        target.power_domain_by_name(name.borrow())
+    })
+}
+
+/// Remove the content of a cell but keep its pins.
+fn clear_cell<LN: L2NEdit + BusEdit + PowerEdit>(chip: &mut LN, cell: &LN::CellId) {
+    for domain in chip.each_power_domain_vec() {
+        if &chip.parent_cell_of_power_domain(&domain) == cell {
+            chip.remove_power_domain(&domain);
+        }
+    }
+    for bus in chip.each_pin_bus_vec(cell) {
+        chip.remove_pin_bus(&bus);
+    }
+    for bus in chip.each_net_bus_vec(cell) {
+        chip.remove_net_bus(&bus);
+    }
+    chip.clear_cell_instances(cell);
+    let layers: Vec<_> = chip.each_layer().collect();
+    for layer in layers {
+        let shapes: Vec<_> = chip.each_shape_id(cell, &layer).collect();
+        for shape in shapes {
+            chip.remove_shape(&shape);
+        }
+    }
+    let (zero, one) = (chip.net_zero(cell), chip.net_one(cell));
+    for net in chip.each_internal_net_vec(cell) {
+        if net != zero && net != one {
+            chip.remove_net(&net);
+        }
+    }
+    let mut keys = vec![];
+    chip.for_each_cell_property(cell, |k, _| keys.push(k.clone()));
+    for key in keys {
+        chip.remove_cell_property(cell, &key);
+    }
+}
+
+/// Copy the content of a single cell into an empty cell of the target.
+/// The templates of all child instances must be in the `mapping` already.
+/// Existing pins of the target cell are reused if their name matches.
+fn copy_cell_content<LS, LT>(
+    source: &LS,
+    source_cell: &LS::CellId,
+    target: &mut LT,
+    target_cell: &LT::CellId,
+    mapping: &mut CopyMapping<LS, LT>,
+) where
+    LS: L2NBase + BusBase + PowerBase,
+    LT: L2NEdit<Coord = LS::Coord> + BusEdit + PowerEdit,
+{
+    source.for_each_cell_property(source_cell, |k, v| {
+        target.set_cell_property(target_cell, convert_name(k), v.clone())
+    });
+
+    // Pins and pin buses.
+    let mut used_pins = HashSet::new();
+    for pin in source.each_pin(source_cell) {
+        let name = source.pin_name(&pin);
+        let target_pin = target
+            .pin_by_name(target_cell, name.borrow())
+            .unwrap_or_else(|| {
+                target.create_pin(target_cell, convert_name(&name), source.pin_direction(&pin))
+            });
+        used_pins.insert(target_pin.clone());
+        mapping.pins.insert(pin, target_pin);
+    }
+    for pin in target.each_pin_vec(target_cell) {
+        if !used_pins.contains(&pin) {
+            target.remove_pin(&pin);
+        }
+    }
+    source.for_each_pin_bus(source_cell, |bus| {
+        let pins = source
+            .each_pin_of_bus_vec(&bus)
+            .iter()
+            .map(|p| mapping.pins[p].clone())
+            .collect();
+        let name = convert_name(&source.pin_bus_name(&bus));
+        target.create_pin_bus_from_pins(name, pins, source.pin_bus_bit_order(&bus));
+    });
+
+    // Nets and net buses.
+    mapping
+        .nets
+        .insert(source.net_zero(source_cell), target.net_zero(target_cell));
+    mapping
+        .nets
+        .insert(source.net_one(source_cell), target.net_one(target_cell));
+    source.for_each_internal_net(source_cell, |net| {
+        if !mapping.nets.contains_key(&net) {
+            let name = source.net_name(&net).map(|n| convert_name(&n));
+            mapping
+                .nets
+                .insert(net, target.create_net(target_cell, name));
+        }
+    });
+    source.for_each_net_bus(source_cell, |bus| {
+        let nets = source
+            .each_net_of_bus_vec(&bus)
+            .iter()
+            .map(|n| mapping.nets[n].clone())
+            .collect();
+        let name = convert_name(&source.net_bus_name(&bus));
+        target.create_net_bus_from_nets(name, nets, source.net_bus_bit_order(&bus));
+    });
+    source.for_each_pin(source_cell, |pin| {
+        let net = source.net_of_pin(&pin).map(|n| mapping.nets[&n].clone());
+        target.connect_pin(&mapping.pins[&pin], net);
+    });
+
+    // Child instances.
+    source.for_each_cell_instance(source_cell, |inst| {
+        let template = source.template_cell(&inst);
+        let name = source.cell_instance_name(&inst).map(|n| convert_name(&n));
+        let target_inst = target.create_cell_instance(target_cell, &mapping.cells[&template], name);
+        target.set_transform(&target_inst, source.get_transform(&inst));
+        target.set_cell_instance_array(&target_inst, source.cell_instance_array(&inst));
+        target.set_placement_status(&target_inst, source.placement_status(&inst));
+        source.for_each_cell_instance_property(&inst, |k, v| {
+            target.set_cell_instance_property(&target_inst, convert_name(k), v.clone())
+        });
+        source.for_each_pin_instance(&inst, |pin_inst| {
+            let pin = source.template_pin(&pin_inst);
+            // Pins of skipped cells which don't exist in the target cannot be connected.
+            if let (Some(net), Some(target_pin)) = (
+                source.net_of_pin_instance(&pin_inst),
+                mapping.pins.get(&pin),
+            ) {
+                let target_pin_inst = target.pin_instance(&target_inst, target_pin);
+                target.connect_pin_instance(&target_pin_inst, Some(mapping.nets[&net].clone()));
+            }
+        });
+        mapping.cell_instances.insert(inst, target_inst);
+    });
+
+    // Shapes together with their properties and links to nets and pins.
+    for (layer, target_layer) in &mapping.layers {
+        source.for_each_shape(source_cell, layer, |shape, geometry| {
+            let target_shape = target.insert_shape(target_cell, target_layer, geometry.clone());
+            source.for_each_shape_property(&shape, |k, v| {
+                target.set_shape_property(&target_shape, convert_name(k), v.clone())
+            });
+            if let Some(net) = source.get_net_of_shape(&shape) {
+                target.set_net_of_shape(&target_shape, Some(mapping.nets[&net].clone()));
+            }
+            if let Some(pin) = source.get_pin_of_shape(&shape) {
+                target.set_pin_of_shape(&target_shape, Some(mapping.pins[&pin].clone()));
+            }
+            mapping.shapes.insert(shape.clone(), target_shape);
+        });
+    }
+}
+
+/// Convert between the name types of two data bases via `&str`.
+fn convert_name<NS: Borrow<str>, NT: From<String>>(name: &NS) -> NT {
+    let s: &str = name.borrow();
+    s.to_string().into()
+}
+
+#[test]
+fn test_uniquify_hierarchy() {
+    use crate::chip::Chip;
+    use crate::prelude::*;
+
+    let mut chip = Chip::new();
+    let layer = chip.create_layer(1, 0);
+    let top = chip.create_cell("TOP".into());
+    let block = chip.create_cell("BLOCK".into());
+    let inv = chip.create_cell("INV".into());
+    chip.create_pin(&inv, "A".into(), Direction::Input);
+    let a = chip.create_pin(&block, "A".into(), Direction::Input);
+    let net_a = chip.create_net(&block, Some("a".into()));
+    chip.connect_pin(&a, Some(net_a.clone()));
+    let inv_inst = chip.create_cell_instance(&block, &inv, Some("u1".into()));
+    chip.connect_pin_instance(
+        &chip.each_pin_instance_vec(&inv_inst)[0],
+        Some(net_a.clone()),
+    );
+    let shape = chip.insert_shape(&block, &layer, Rect::new((0, 0), (10, 10)).into());
+    chip.set_net_of_shape(&shape, Some(net_a));
+
+    let top_net = chip.create_net(&top, Some("in".into()));
+    chip.create_cell_instance(&top, &block, Some("b1".into()));
+    let b2 = chip.create_cell_instance(&top, &block, Some("b2".into()));
+    chip.set_transform(&b2, SimpleTransform::translate((100, 0)));
+    chip.set_placement_status(&b2, PlacementStatus::Fixed);
+    chip.connect_pin_instance(&chip.pin_instance(&b2, &a), Some(top_net.clone()));
+    let vdd = chip.create_net(&top, Some("VDD".into()));
+    let domain = chip.create_power_domain("CORE".into(), vdd, chip.net_zero(&top), 1.0, false);
+    chip.set_power_domain_of_cell(&block, Some(domain));
+    chip.set_power_domain_of_cell_instance(&b2, Some(domain));
+
+    chip.uniquify_hierarchy(&top, false);
+
+    // One of the instances keeps the original cell, the other one gets a copy.
+    let b1 = chip.cell_instance_by_name(&top, "b1").unwrap();
+    let b2 = chip.cell_instance_by_name(&top, "b2").unwrap();
+    let mut names = vec![
+        chip.cell_name(&chip.template_cell(&b1)),
+        chip.cell_name(&chip.template_cell(&b2)),
+    ];
+    names.sort();
+    assert_eq!(names, vec!["BLOCK".into(), "BLOCK_1".into()]);
+    assert_eq!(chip.num_cell_references(&block), 1);
+    assert_eq!(
+        chip.get_transform(&b2),
+        SimpleTransform::translate((100, 0))
+    );
+    assert_eq!(chip.placement_status(&b2), PlacementStatus::Fixed);
+
+    // Power domain assignments are preserved.
+    assert_eq!(
+        chip.assigned_power_domain_of_cell_instance(&b2),
+        Some(domain)
+    );
+    assert_eq!(
+        chip.power_domain_of_cell(&chip.template_cell(&b1)),
+        Some(domain)
+    );
+    assert_eq!(
+        chip.power_domain_of_cell(&chip.template_cell(&b2)),
+        Some(domain)
+    );
+
+    // Connections inside and outside of the cells are preserved.
+    let b2_template = chip.template_cell(&b2);
+    let a = chip.pin_by_name(&b2_template, "A").unwrap();
+    assert_eq!(
+        chip.net_of_pin_instance(&chip.pin_instance(&b2, &a)),
+        Some(top_net)
+    );
+    let net_a = chip.net_by_name(&b2_template, "a").unwrap();
+    assert_eq!(chip.net_of_pin(&a), Some(net_a.clone()));
+    assert_eq!(chip.num_net_pin_instances(&net_a), 1);
+    assert_eq!(chip.shapes_of_net(&net_a).count(), 1);
+
+    // Leaf cells stay shared.
+    assert_eq!(chip.num_cell_references(&inv), 2);
+}
+
+#[test]
+fn test_uniquify_leaf_cells() {
+    use crate::chip::Chip;
+    use crate::prelude::*;
+
+    let mut chip = Chip::new();
+    let layer = chip.create_layer(1, 0);
+    let top = chip.create_cell("TOP".into());
+    // A block which contains only layout.
+    let macro_cell = chip.create_cell("MACRO".into());
+    chip.insert_shape(&macro_cell, &layer, Rect::new((0, 0), (10, 10)).into());
+    // A cell without any content.
+    let empty = chip.create_cell("EMPTY".into());
+    for i in 0..2 {
+        chip.create_cell_instance(&top, &macro_cell, Some(format!("m{}", i).into()));
+        chip.create_cell_instance(&top, &empty, Some(format!("e{}", i).into()));
+    }
+
+    chip.uniquify_hierarchy(&top, false);
+    assert_eq!(chip.num_cell_references(&macro_cell), 2);
+
+    chip.uniquify_hierarchy(&top, true);
+    assert_eq!(chip.num_cell_references(&macro_cell), 1);
+    assert_eq!(chip.num_cell_references(&empty), 2);
+}
+
+#[test]
+fn test_copy_cell_recursive() {
+    use crate::chip::Chip;
+    use crate::prelude::*;
+
+    let mut source = Chip::new();
+    let metal1 = source.create_layer(1, 0);
+    source.set_layer_name(&metal1, Some("metal1".into()));
+    let top = source.create_cell("TOP".into());
+    let block = source.create_cell("BLOCK".into());
+    let inv = source.create_cell("INV".into());
+    let inv_a = source.create_pin(&inv, "A".into(), Direction::Input);
+    source.insert_shape(&inv, &metal1, Rect::new((0, 0), (1, 1)).into());
+    let block_a = source.create_pin(&block, "A".into(), Direction::Input);
+    let net = source.create_net(&block, Some("a".into()));
+    source.connect_pin(&block_a, Some(net.clone()));
+    let u1 = source.create_cell_instance(&block, &inv, Some("u1".into()));
+    source.connect_pin_instance(&source.pin_instance(&u1, &inv_a), Some(net.clone()));
+    source.set_cell_instance_property(&u1, "dont_touch".into(), PropertyValue::SInt(1));
+    let shape = source.insert_shape(&block, &metal1, Rect::new((0, 0), (10, 10)).into());
+    source.set_net_of_shape(&shape, Some(net.clone()));
+    let b1 = source.create_cell_instance(&top, &block, Some("b1".into()));
+    source.set_transform(&b1, SimpleTransform::translate((5, 5)));
+    let vdd = source.create_net(&top, Some("VDD".into()));
+    let core = source.create_power_domain("CORE".into(), vdd, source.net_zero(&top), 0.9, true);
+    source.set_power_domain_of_cell(&block, Some(core));
+    source.set_power_domain_of_cell_instance(&b1, Some(core));
+
+    // Target which already contains a cell named 'INV' which is used by 'OTHER'.
+    let create_target = || {
+        let mut target = Chip::new();
+        let inv = target.create_cell("INV".into());
+        let a = target.create_pin(&inv, "A".into(), Direction::Input);
+        target.create_pin(&inv, "B".into(), Direction::Input);
+        let other = target.create_cell("OTHER".into());
+        let net = target.create_net(&other, None);
+        let inst = target.create_cell_instance(&other, &inv, None);
+        target.connect_pin_instance(&target.pin_instance(&inst, &a), Some(net));
+        (target, inv)
+    };
+
+    // Rename.
+    let (mut target, target_inv) = create_target();
+    let mapping = copy_cell_recursive(&source, &top, &mut target, CellCollisionPolicy::Rename);
+    assert_eq!(target.num_cells(), 5);
+    assert_eq!(target.cell_name(&mapping.cells[&inv]), "INV_1".into());
+    assert_eq!(target.num_pins(&target_inv), 2);
+    let target_u1 = &mapping.cell_instances[&u1];
+    assert_eq!(target.template_cell(target_u1), mapping.cells[&inv]);
+    assert_eq!(
+        target
+            .get_cell_instance_property(target_u1, &"dont_touch".into())
+            .and_then(|v| v.get_sint()),
+        Some(1)
+    );
+    let target_net = &mapping.nets[&net];
+    assert_eq!(target.num_net_pin_instances(target_net), 1);
+    assert_eq!(
+        target.get_net_of_shape(&mapping.shapes[&shape]),
+        Some(target_net.clone())
+    );
+    assert_eq!(
+        target.get_transform(&mapping.cell_instances[&b1]),
+        SimpleTransform::translate((5, 5))
+    );
+    assert_eq!(
+        target.layer_by_name("metal1"),
+        Some(mapping.layers[&metal1])
+    );
+    let target_core = mapping.power_domains[&core];
+    assert_eq!(target.power_domain_name(&target_core), "CORE".into());
+    assert_eq!(
+        target.power_domain_supply_net(&target_core),
+        mapping.nets[&vdd]
+    );
+    assert!(target.power_domain_is_always_on(&target_core));
+    assert_eq!(
+        target.power_domain_of_cell(&mapping.cells[&block]),
+        Some(target_core)
+    );
+    assert_eq!(
+        target.assigned_power_domain_of_cell_instance(&mapping.cell_instances[&b1]),
+        Some(target_core)
+    );
+
+    // Skip.
+    let (mut target, target_inv) = create_target();
+    let mapping = copy_cell_recursive(&source, &top, &mut target, CellCollisionPolicy::Skip);
+    assert_eq!(target.num_cells(), 4);
+    assert_eq!(mapping.cells[&inv], target_inv);
+    assert_eq!(target.num_net_pin_instances(&mapping.nets[&net]), 1);
+    assert_eq!(
+        target
+            .each_shape_id(&target_inv, &mapping.layers[&metal1])
+            .count(),
+        0
+    );
+
+    // Overwrite.
+    let (mut target, target_inv) = create_target();
+    let mapping = copy_cell_recursive(&source, &top, &mut target, CellCollisionPolicy::Overwrite);
+    assert_eq!(target.num_cells(), 4);
+    assert_eq!(mapping.cells[&inv], target_inv);
+    assert_eq!(target.num_pins(&target_inv), 1);
+    assert_eq!(
+        target
+            .each_shape_id(&target_inv, &mapping.layers[&metal1])
+            .count(),
+        1
+    );
+    // The existing instance in 'OTHER' is still connected.
+    let other = target.cell_by_name("OTHER").unwrap();
+    let inst = target.each_cell_instance_vec(&other)[0];
+    let pin_inst = target.pin_instance(&inst, &mapping.pins[&inv_a]);
+    assert!(target.net_of_pin_instance(&pin_inst).is_some());
+}
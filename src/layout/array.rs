@@ -0,0 +1,332 @@
+// SPDX-FileCopyrightText: 2022 Thomas Kramer
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Regular arrays of cell instances.
+//!
+//! Memories and fill patterns consist of huge numbers of identical cells placed on a regular grid.
+//! Instead of creating a cell instance for each of them, a single cell instance can be turned into an
+//! [`InstanceArray`] with [`LayoutEdit::set_cell_instance_array()`]. This corresponds to `AREF`
+//! records in GDSII and to grid repetitions in OASIS.
+//!
+//! All elements of an array share the same cell instance ID and hence the same pin instances.
+//! An element is addressed by its column and row.
+//!
+//! [`LayoutEdit::set_cell_instance_array()`]: crate::traits::LayoutEdit::set_cell_instance_array
+//!
+//! # Example
+//!
+//! ```
+//! use libreda_db::prelude::*;
+//!
+//! let mut chip = Chip::new();
+//! let top = chip.create_cell("TOP".into());
+//! let bit = chip.create_cell("BIT".into());
+//! let layer = chip.create_layer(1, 0);
+//! chip.insert_shape(&bit, &layer, Rect::new((0, 0), (10, 10)).into());
+//!
+//! let inst = chip.create_cell_instance(&top, &bit, None);
+//! let array = InstanceArray::new(100, 50, Vector::new(10, 0), Vector::new(0, 10));
+//! chip.set_cell_instance_array(&inst, Some(array));
+//!
+//! assert_eq!(chip.num_child_instances(&top), 1);
+//! assert_eq!(chip.num_array_elements(&inst), 5000);
+//! assert_eq!(chip.bounding_box(&top), Some(Rect::new((0, 0), (1000, 500))));
+//! assert_eq!(
+//!     chip.array_element_transform(&inst, 3, 2).displacement,
+//!     Vector::new(30, 20)
+//! );
+//! ```
+
+use iron_shapes::prelude::{Rect, SimpleTransform, Vector};
+use iron_shapes::CoordinateType;
+use std::ops::Range;
+
+/// Regular two-dimensional repetition of a cell instance.
+///
+/// The element in column `c` and row `r` is displaced by `c * column_pitch + r * row_pitch`.
+/// The displacement is given in the coordinates of the parent cell, i.e. it is applied after
+/// the transform of the cell instance.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct InstanceArray<C> {
+    /// Number of columns. At least `1`.
+    pub columns: u32,
+    /// Number of rows. At least `1`.
+    pub rows: u32,
+    /// Displacement between two neighbouring columns.
+    pub column_pitch: Vector<C>,
+    /// Displacement between two neighbouring rows.
+    pub row_pitch: Vector<C>,
+}
+
+impl<C: CoordinateType> InstanceArray<C> {
+    /// Create an array with `columns` times `rows` elements.
+    ///
+    /// # Panics
+    /// Panics if there are no columns or no rows.
+    pub fn new(columns: u32, rows: u32, column_pitch: Vector<C>, row_pitch: Vector<C>) -> Self {
+        assert!(
+            columns > 0 && rows > 0,
+            "Array needs at least one column and one row."
+        );
+        Self {
+            columns,
+            rows,
+            column_pitch,
+            row_pitch,
+        }
+    }
+
+    /// Create an array with a single element. This is equivalent to a cell instance without array.
+    pub fn single() -> Self {
+        let zero = Vector::new(C::zero(), C::zero());
+        Self::new(1, 1, zero, zero)
+    }
+
+    /// Get the number of elements.
+    pub fn num_elements(&self) -> usize {
+        self.columns as usize * self.rows as usize
+    }
+
+    /// Get the displacement of the element in `column` and `row` relative to the first element.
+    pub fn offset(&self, column: u32, row: u32) -> Vector<C> {
+        debug_assert!(column < self.columns && row < self.rows);
+        scale_vector(self.column_pitch, column) + scale_vector(self.row_pitch, row)
+    }
+
+    /// Iterate over all elements as `(column, row)` tuples.
+    /// Rows are enumerated in the outer loop, columns in the inner loop.
+    pub fn each_element(&self) -> impl Iterator<Item = (u32, u32)> {
+        let columns = self.columns;
+        (0..self.rows).flat_map(move |r| (0..columns).map(move |c| (c, r)))
+    }
+
+    /// Get the transform of the element in `column` and `row` of an instance with transform `tf`.
+    pub fn element_transform(
+        &self,
+        tf: &SimpleTransform<C>,
+        column: u32,
+        row: u32,
+    ) -> SimpleTransform<C> {
+        tf.then(&SimpleTransform::translate(self.offset(column, row)))
+    }
+
+    /// Compute the bounding box of the whole array from the bounding box of the first element.
+    pub fn bounding_box(&self, element_bbox: &Rect<C>) -> Rect<C> {
+        let (c, r) = (self.columns - 1, self.rows - 1);
+        [(0, 0), (c, 0), (0, r), (c, r)]
+            .iter()
+            .map(|&(c, r)| translate_rect(element_bbox, self.offset(c, r)))
+            .reduce(|a, b| a.add_rect(&b))
+            .unwrap()
+    }
+
+    /// Find all elements `(column, row)` whose bounding box interacts with `region`.
+    /// `element_bbox` is the bounding box of the first element.
+    ///
+    /// Arrays with axis-aligned pitches are searched without visiting every element.
+    pub fn elements_in_region(&self, element_bbox: &Rect<C>, region: &Rect<C>) -> Vec<(u32, u32)> {
+        let zero = C::zero();
+        let (ll, ur) = (element_bbox.lower_left(), element_bbox.upper_right());
+        let (r_ll, r_ur) = (region.lower_left(), region.upper_right());
+        let (cp, rp) = (self.column_pitch, self.row_pitch);
+
+        let ranges = if cp.y == zero && rp.x == zero {
+            Some((
+                overlapping_indices((ll.x, ur.x), cp.x, self.columns, (r_ll.x, r_ur.x)),
+                overlapping_indices((ll.y, ur.y), rp.y, self.rows, (r_ll.y, r_ur.y)),
+            ))
+        } else if cp.x == zero && rp.y == zero {
+            Some((
+                overlapping_indices((ll.y, ur.y), cp.y, self.columns, (r_ll.y, r_ur.y)),
+                overlapping_indices((ll.x, ur.x), rp.x, self.rows, (r_ll.x, r_ur.x)),
+            ))
+        } else {
+            None
+        };
+
+        match ranges {
+            Some((columns, rows)) => rows
+                .flat_map(|r| columns.clone().map(move |c| (c, r)))
+                .collect(),
+            None => self
+                .each_element()
+                .filter(|&(c, r)| {
+                    interacts(&translate_rect(element_bbox, self.offset(c, r)), region)
+                })
+                .collect(),
+        }
+    }
+
+    /// Recognize a regular grid in a list of displacements.
+    ///
+    /// The first displacement must be zero and the displacements must be ordered
+    /// like [`InstanceArray::each_element()`] enumerates the elements.
+    /// Returns `None` if the list has less than two entries or does not describe a grid.
+    pub fn from_displacements(displacements: &[Vector<C>]) -> Option<Self> {
+        let zero = Vector::new(C::zero(), C::zero());
+        if displacements.len() < 2 || displacements[0] != zero || displacements[1] == zero {
+            return None;
+        }
+        let column_pitch = displacements[1];
+        let columns = displacements
+            .iter()
+            .enumerate()
+            .take_while(|(i, d)| **d == scale_vector(column_pitch, *i as u32))
+            .count();
+        if displacements.len() % columns != 0 {
+            return None;
+        }
+        let rows = displacements.len() / columns;
+        let row_pitch = if rows > 1 {
+            displacements[columns]
+        } else {
+            zero
+        };
+        let array = Self::new(columns as u32, rows as u32, column_pitch, row_pitch);
+        let is_grid = array
+            .each_element()
+            .zip(displacements)
+            .all(|((c, r), d)| array.offset(c, r) == *d);
+        if is_grid {
+            Some(array)
+        } else {
+            None
+        }
+    }
+}
+
+/// Multiply a coordinate with an integer without converting the integer into the coordinate type.
+fn scale<C: CoordinateType>(x: C, n: u32) -> C {
+    // Double-and-add.
+    let mut result = C::zero();
+    let mut power = x;
+    let mut n = n;
+    while n > 0 {
+        if n & 1 == 1 {
+            result = result + power;
+        }
+        n >>= 1;
+        if n > 0 {
+            power = power + power;
+        }
+    }
+    result
+}
+
+/// Multiply both components of a vector with an integer.
+fn scale_vector<C: CoordinateType>(v: Vector<C>, n: u32) -> Vector<C> {
+    Vector::new(scale(v.x, n), scale(v.y, n))
+}
+
+/// Move a rectangle by `v`.
+fn translate_rect<C: CoordinateType>(r: &Rect<C>, v: Vector<C>) -> Rect<C> {
+    Rect::new(r.lower_left() + v, r.upper_right() + v)
+}
+
+/// Check if two rectangles overlap or touch.
+fn interacts<C: CoordinateType>(a: &Rect<C>, b: &Rect<C>) -> bool {
+    let (a_ll, a_ur) = (a.lower_left(), a.upper_right());
+    let (b_ll, b_ur) = (b.lower_left(), b.upper_right());
+    a_ll.x <= b_ur.x && b_ll.x <= a_ur.x && a_ll.y <= b_ur.y && b_ll.y <= a_ur.y
+}
+
+/// Find the indices `i` in `0..n` for which the interval `element + i * pitch` interacts with `region`.
+/// Both intervals are given as `(low, high)`.
+fn overlapping_indices<C: CoordinateType>(
+    element: (C, C),
+    pitch: C,
+    n: u32,
+    region: (C, C),
+) -> Range<u32> {
+    let (low, high) = element;
+    let (region_low, region_high) = region;
+    let interval = |i: u32| {
+        let d = scale(pitch, i);
+        (low + d, high + d)
+    };
+    let zero = C::zero();
+    if pitch == zero {
+        let (l, h) = interval(0);
+        if l <= region_high && region_low <= h {
+            0..n
+        } else {
+            0..0
+        }
+    } else if pitch > zero {
+        // Intervals move upwards with increasing index.
+        let start = partition_point(n, |i| interval(i).1 < region_low);
+        let end = partition_point(n, |i| interval(i).0 <= region_high);
+        start..end.max(start)
+    } else {
+        // Intervals move downwards with increasing index.
+        let start = partition_point(n, |i| interval(i).0 > region_high);
+        let end = partition_point(n, |i| interval(i).1 >= region_low);
+        start..end.max(start)
+    }
+}
+
+/// Find the first index in `0..n` for which `pred` is false.
+/// `pred` must be true for a prefix of the indices and false for the rest.
+fn partition_point(n: u32, mut pred: impl FnMut(u32) -> bool) -> u32 {
+    let (mut low, mut high) = (0, n);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if pred(mid) {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    low
+}
+
+#[test]
+fn test_elements_in_region() {
+    let element = Rect::new((0, 0), (10, 5));
+    let region = Rect::new((25, 12), (44, 16));
+
+    let array = InstanceArray::new(10, 10, Vector::new(10, 0), Vector::new(0, 5));
+    assert_eq!(
+        array.elements_in_region(&element, &region),
+        vec![(2, 2), (3, 2), (4, 2), (2, 3), (3, 3), (4, 3)]
+    );
+
+    // Negative pitches and swapped axes.
+    let array = InstanceArray::new(10, 10, Vector::new(0, -5), Vector::new(-10, 0));
+    let region = Rect::new((-44, -14), (-25, -12));
+    let mut found = array.elements_in_region(&element, &region);
+    found.sort();
+    assert_eq!(found, vec![(3, 3), (3, 4), (3, 5)]);
+
+    // Diagonal pitches are searched by brute force.
+    let array = InstanceArray::new(3, 1, Vector::new(10, 10), Vector::new(0, 0));
+    assert_eq!(
+        array.elements_in_region(&element, &Rect::new((21, 21), (22, 22))),
+        vec![(2, 0)]
+    );
+
+    let bbox = array.bounding_box(&element);
+    assert_eq!(bbox, Rect::new((0, 0), (30, 25)));
+}
+
+#[test]
+fn test_from_displacements() {
+    let array = InstanceArray::new(3, 2, Vector::new(10, 0), Vector::new(0, 20));
+    let displacements: Vec<_> = array
+        .each_element()
+        .map(|(c, r)| array.offset(c, r))
+        .collect();
+    assert_eq!(
+        InstanceArray::from_displacements(&displacements),
+        Some(array)
+    );
+
+    let irregular = vec![Vector::new(0, 0), Vector::new(10, 0), Vector::new(25, 0)];
+    assert_eq!(InstanceArray::from_displacements(&irregular), None);
+    assert_eq!(
+        InstanceArray::<i32>::from_displacements(&[Vector::new(0, 0)]),
+        None
+    );
+}
@@ -0,0 +1,1044 @@
+// SPDX-FileCopyrightText: 2022 Thomas Kramer
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Reader and writer for the GDSII stream format.
+//!
+//! Structures are mapped to cells. `SREF` elements become cell instances. `AREF` elements become
+//! a single cell instance with an [`InstanceArray`](crate::layout::array::InstanceArray).
+//! The writer expands arrays with more than 32767 columns or rows into single references.
+//! Reflections, rotations by multiples of 90 degrees and integer magnifications are supported.
+//!
+//! `BOUNDARY` elements become rectangles or simple polygons, `BOX` elements become rectangles,
+//! `PATH` elements become paths and `TEXT` elements become texts. The GDSII layer and datatype
+//! (or texttype) are mapped to the layer with the same index and datatype.
+//!
+//! Properties (`PROPATTR`/`PROPVALUE`) of elements are stored as string properties of the
+//! shapes and cell instances. The key of the property is the attribute number, for example `"1"`.
+//!
+//! The database unit is derived from the `UNITS` record assuming that the user unit is one micron.
+//!
+//! # Example
+//!
+//! ```
+//! use libreda_db::prelude::*;
+//! use libreda_db::layout::io::gds::{GdsReader, GdsWriter};
+//!
+//! let mut chip = Chip::new();
+//! chip.set_dbu(1000);
+//! let top = chip.create_cell("TOP".into());
+//! let layer = chip.create_layer(1, 0);
+//! chip.insert_shape(&top, &layer, Rect::new((0, 0), (100, 200)).into());
+//!
+//! let mut buffer = Vec::new();
+//! GdsWriter::new().write_layout(&mut buffer, &chip).unwrap();
+//!
+//! let mut restored = Chip::new();
+//! GdsReader::new().read_layout(&mut buffer.as_slice(), &mut restored).unwrap();
+//! assert_eq!(restored.dbu(), 1000);
+//! assert!(restored.cell_by_name("TOP").is_some());
+//! ```
+
+use super::{LayoutStreamReader, LayoutStreamWriter};
+use crate::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
+
+// Record types.
+const HEADER: u8 = 0x00;
+const BGNLIB: u8 = 0x01;
+const LIBNAME: u8 = 0x02;
+const UNITS: u8 = 0x03;
+const ENDLIB: u8 = 0x04;
+const BGNSTR: u8 = 0x05;
+const STRNAME: u8 = 0x06;
+const ENDSTR: u8 = 0x07;
+const BOUNDARY: u8 = 0x08;
+const PATH: u8 = 0x09;
+const SREF: u8 = 0x0A;
+const AREF: u8 = 0x0B;
+const TEXT: u8 = 0x0C;
+const LAYER: u8 = 0x0D;
+const DATATYPE: u8 = 0x0E;
+const WIDTH: u8 = 0x0F;
+const XY: u8 = 0x10;
+const ENDEL: u8 = 0x11;
+const SNAME: u8 = 0x12;
+const COLROW: u8 = 0x13;
+const NODE: u8 = 0x15;
+const TEXTTYPE: u8 = 0x16;
+const STRING: u8 = 0x19;
+const STRANS: u8 = 0x1A;
+const MAG: u8 = 0x1B;
+const ANGLE: u8 = 0x1C;
+const PATHTYPE: u8 = 0x21;
+const PROPATTR: u8 = 0x2B;
+const PROPVALUE: u8 = 0x2C;
+const BOX: u8 = 0x2D;
+const BOXTYPE: u8 = 0x2E;
+const BGNEXTN: u8 = 0x30;
+const ENDEXTN: u8 = 0x31;
+
+// Data types.
+const NO_DATA: u8 = 0;
+const BIT_ARRAY: u8 = 1;
+const INT16: u8 = 2;
+const INT32: u8 = 3;
+const REAL8: u8 = 5;
+const ASCII: u8 = 6;
+
+/// Reflection flag of the `STRANS` record.
+const STRANS_REFLECTION: u16 = 0x8000;
+
+/// Maximal number of points in a single `XY` record.
+const MAX_POINTS: usize = 8191;
+
+/// Error type used for reading and writing GDSII streams.
+#[derive(Debug)]
+pub enum GdsError {
+    /// Error of the underlying byte stream.
+    Io(std::io::Error),
+    /// The stream is not valid GDSII.
+    Malformed(String),
+    /// A structure is defined in the stream but a cell with this name exists already.
+    CellNameCollision(String),
+    /// The transformation of a cell reference cannot be represented with a [`SimpleTransform`].
+    /// Only rotations by multiples of 90 degrees and integer magnifications are supported.
+    UnsupportedTransform,
+    /// The geometry cannot be represented in GDSII, for example polygons with holes.
+    UnsupportedGeometry,
+}
+
+impl std::fmt::Display for GdsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GdsError::Io(err) => write!(f, "IO error: {}", err),
+            GdsError::Malformed(message) => write!(f, "Malformed GDSII stream: {}", message),
+            GdsError::CellNameCollision(name) => write!(f, "Cell '{}' already exists.", name),
+            GdsError::UnsupportedTransform => write!(f, "Unsupported transformation."),
+            GdsError::UnsupportedGeometry => write!(f, "Geometry not supported by GDSII."),
+        }
+    }
+}
+
+impl std::error::Error for GdsError {}
+
+impl From<std::io::Error> for GdsError {
+    fn from(err: std::io::Error) -> Self {
+        GdsError::Io(err)
+    }
+}
+
+fn malformed<T>(message: &str) -> Result<T, GdsError> {
+    Err(GdsError::Malformed(message.to_string()))
+}
+
+/// Convert a GDSII 8-byte real into a `f64`.
+/// GDSII reals use a base-16 exponent with an offset of 64 and a 56-bit mantissa.
+fn real8_to_f64(bits: u64) -> f64 {
+    let sign = if bits >> 63 == 1 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 56) & 0x7f) as i32 - 64;
+    let mantissa = (bits & 0x00ff_ffff_ffff_ffff) as f64 / (1u64 << 56) as f64;
+    sign * mantissa * 16f64.powi(exponent)
+}
+
+/// Convert a `f64` into a GDSII 8-byte real.
+fn f64_to_real8(value: f64) -> u64 {
+    if value == 0.0 {
+        return 0;
+    }
+    let sign = if value < 0.0 { 1u64 << 63 } else { 0 };
+    let mut value = value.abs();
+    let mut exponent = 64i32;
+    while value >= 1.0 {
+        value /= 16.0;
+        exponent += 1;
+    }
+    while value < 1.0 / 16.0 {
+        value *= 16.0;
+        exponent -= 1;
+    }
+    let mut mantissa = (value * (1u64 << 56) as f64).round() as u64;
+    if mantissa >= 1 << 56 {
+        mantissa >>= 4;
+        exponent += 1;
+    }
+    sign | ((exponent as u64 & 0x7f) << 56) | mantissa
+}
+
+/// A single GDSII record.
+struct Record {
+    record_type: u8,
+    data: Vec<u8>,
+}
+
+impl Record {
+    fn i16s(&self) -> impl Iterator<Item = i16> + '_ {
+        self.data
+            .chunks_exact(2)
+            .map(|c| i16::from_be_bytes([c[0], c[1]]))
+    }
+
+    fn i32s(&self) -> impl Iterator<Item = i32> + '_ {
+        self.data
+            .chunks_exact(4)
+            .map(|c| i32::from_be_bytes([c[0], c[1], c[2], c[3]]))
+    }
+
+    fn reals(&self) -> impl Iterator<Item = f64> + '_ {
+        self.data.chunks_exact(8).map(|c| {
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(c);
+            real8_to_f64(u64::from_be_bytes(bytes))
+        })
+    }
+
+    fn i16(&self) -> Result<i16, GdsError> {
+        self.i16s()
+            .next()
+            .ok_or_else(|| GdsError::Malformed("missing 2-byte integer".into()))
+    }
+
+    fn i32(&self) -> Result<i32, GdsError> {
+        self.i32s()
+            .next()
+            .ok_or_else(|| GdsError::Malformed("missing 4-byte integer".into()))
+    }
+
+    fn real(&self) -> Result<f64, GdsError> {
+        self.reals()
+            .next()
+            .ok_or_else(|| GdsError::Malformed("missing real".into()))
+    }
+
+    /// Layer numbers are stored as 2-byte integers but are often used as unsigned values.
+    fn unsigned(&self) -> Result<UInt, GdsError> {
+        self.i16().map(|v| v as u16 as UInt)
+    }
+
+    fn string(&self) -> Result<String, GdsError> {
+        let end = self
+            .data
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(self.data.len());
+        String::from_utf8(self.data[..end].to_vec())
+            .map_err(|_| GdsError::Malformed("invalid string".into()))
+    }
+
+    fn points(&self) -> Vec<Point<i32>> {
+        let coords: Vec<_> = self.i32s().collect();
+        coords
+            .chunks_exact(2)
+            .map(|c| Point::new(c[0], c[1]))
+            .collect()
+    }
+}
+
+/// Read the next record. Returns `None` at the end of the stream.
+fn read_record<R: Read>(reader: &mut R) -> Result<Option<Record>, GdsError> {
+    let mut header = [0u8; 4];
+    match reader.read_exact(&mut header) {
+        Ok(()) => {}
+        Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err.into()),
+    }
+    let length = u16::from_be_bytes([header[0], header[1]]) as usize;
+    if length == 0 {
+        // Zero padding after the end of the library.
+        return Ok(None);
+    }
+    if length < 4 {
+        return malformed("invalid record length");
+    }
+    let mut data = vec![0; length - 4];
+    reader.read_exact(&mut data)?;
+    Ok(Some(Record {
+        record_type: header[2],
+        data,
+    }))
+}
+
+/// Element of a structure with all its records.
+#[derive(Default)]
+struct Element {
+    element_type: u8,
+    layer: UInt,
+    datatype: UInt,
+    width: i32,
+    path_type: i16,
+    begin_extension: i32,
+    end_extension: i32,
+    points: Vec<Point<i32>>,
+    structure_name: String,
+    strans: u16,
+    magnification: f64,
+    angle: f64,
+    columns: i16,
+    rows: i16,
+    string: String,
+    properties: Vec<(i16, String)>,
+}
+
+impl Element {
+    fn transform(&self, displacement: Point<i32>) -> Result<SimpleTransform<i32>, GdsError> {
+        let quarter_turns = self.angle / 90.0;
+        if (quarter_turns - quarter_turns.round()).abs() > 1e-9 {
+            return Err(GdsError::UnsupportedTransform);
+        }
+        let rotation = match (quarter_turns.round() as i64).rem_euclid(4) {
+            0 => Angle::R0,
+            1 => Angle::R90,
+            2 => Angle::R180,
+            _ => Angle::R270,
+        };
+        if self.magnification.fract() != 0.0 || self.magnification < 1.0 {
+            return Err(GdsError::UnsupportedTransform);
+        }
+        Ok(SimpleTransform::new(
+            self.strans & STRANS_REFLECTION != 0,
+            rotation,
+            self.magnification as i32,
+            Vector::new(displacement.x, displacement.y),
+        ))
+    }
+
+    /// Convert a `BOUNDARY`, `PATH`, `BOX` or `TEXT` element into a geometry.
+    fn geometry(&self) -> Result<Geometry<i32>, GdsError> {
+        let mut points = self.points.clone();
+        Ok(match self.element_type {
+            BOUNDARY | BOX => {
+                // Remove the closing point.
+                if points.len() > 1 && points.first() == points.last() {
+                    points.pop();
+                }
+                if points.len() < 3 {
+                    return malformed("boundary with less than three points");
+                }
+                match rectangle(&points) {
+                    Some(rect) => rect.into(),
+                    None if self.element_type == BOX => return malformed("invalid box"),
+                    None => SimplePolygon::new(points).into(),
+                }
+            }
+            PATH => {
+                let width = self.width.abs();
+                match self.path_type {
+                    1 => Path::new_rounded(points, width),
+                    2 => Path::new_extended(points, width, width / 2, width / 2),
+                    4 => {
+                        Path::new_extended(points, width, self.begin_extension, self.end_extension)
+                    }
+                    _ => Path::new(points, width),
+                }
+                .into()
+            }
+            TEXT => match points.first() {
+                Some(location) => Text::new(self.string.clone(), *location).into(),
+                None => return malformed("text without location"),
+            },
+            _ => unreachable!("not a shape element"),
+        })
+    }
+}
+
+/// Check if the four points form an axis-aligned rectangle.
+fn rectangle(points: &[Point<i32>]) -> Option<Rect<i32>> {
+    if let [a, b, c, d] = points {
+        let horizontal_first = a.y == b.y && b.x == c.x && c.y == d.y && d.x == a.x;
+        let vertical_first = a.x == b.x && b.y == c.y && c.x == d.x && d.y == a.y;
+        if horizontal_first || vertical_first {
+            return Some(Rect::new(*a, *c));
+        }
+    }
+    None
+}
+
+/// Read GDSII streams.
+#[derive(Debug, Clone, Default)]
+pub struct GdsReader {}
+
+impl GdsReader {
+    /// Create a reader with default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl LayoutStreamReader for GdsReader {
+    type Error = GdsError;
+
+    fn read_layout<R: Read, L: LayoutEdit<Coord = i32>>(
+        &self,
+        reader: &mut R,
+        layout: &mut L,
+    ) -> Result<(), Self::Error> {
+        let mut reader = GdsStreamReader {
+            reader,
+            layout,
+            cells: HashMap::new(),
+            defined: Default::default(),
+        };
+        reader.read_library()
+    }
+}
+
+struct GdsStreamReader<'a, R, L: LayoutEdit> {
+    reader: &'a mut R,
+    layout: &'a mut L,
+    /// Cells by name. Contains also cells which are referenced but not defined yet.
+    cells: HashMap<String, L::CellId>,
+    /// Names of structures which have been defined already.
+    defined: HashSet<String>,
+}
+
+impl<'a, R: Read, L: LayoutEdit<Coord = i32>> GdsStreamReader<'a, R, L> {
+    fn next(&mut self) -> Result<Record, GdsError> {
+        read_record(self.reader)?.ok_or_else(|| GdsError::Malformed("unexpected end".into()))
+    }
+
+    fn read_library(&mut self) -> Result<(), GdsError> {
+        if self.next()?.record_type != HEADER {
+            return malformed("missing HEADER record");
+        }
+        loop {
+            let record = self.next()?;
+            match record.record_type {
+                UNITS => {
+                    let units: Vec<f64> = record.reals().collect();
+                    let meters_per_dbu = match units.as_slice() {
+                        [_, meters] if *meters > 0.0 => *meters,
+                        _ => return malformed("invalid UNITS record"),
+                    };
+                    let dbu = (1e-6 / meters_per_dbu).round();
+                    if dbu >= 1.0 {
+                        self.layout.set_dbu(dbu as i32);
+                    } else {
+                        log::warn!("Database unit is larger than one micron.");
+                    }
+                }
+                BGNSTR => self.read_structure()?,
+                ENDLIB => break,
+                // Library name, reference libraries, fonts, etc.
+                _ => {}
+            }
+        }
+
+        for name in self.cells.keys() {
+            if !self.defined.contains(name) {
+                log::warn!("Structure '{}' is referenced but not defined.", name);
+            }
+        }
+        Ok(())
+    }
+
+    /// Get a cell by name or create it.
+    fn cell(&mut self, name: &str) -> L::CellId {
+        if let Some(cell) = self.cells.get(name) {
+            return cell.clone();
+        }
+        let cell = self.layout.create_cell(name.to_string().into());
+        self.cells.insert(name.to_string(), cell.clone());
+        cell
+    }
+
+    fn layer(&mut self, index: UInt, datatype: UInt) -> L::LayerId {
+        self.layout
+            .find_layer(index, datatype)
+            .unwrap_or_else(|| self.layout.create_layer(index, datatype))
+    }
+
+    fn read_structure(&mut self) -> Result<(), GdsError> {
+        let name_record = self.next()?;
+        if name_record.record_type != STRNAME {
+            return malformed("missing STRNAME record");
+        }
+        let name = name_record.string()?;
+        if !self.cells.contains_key(&name) && self.layout.cell_by_name(&name).is_some() {
+            return Err(GdsError::CellNameCollision(name));
+        }
+        if !self.defined.insert(name.clone()) {
+            return malformed(&format!("structure '{}' is defined twice", name));
+        }
+        let cell = self.cell(&name);
+
+        loop {
+            let record = self.next()?;
+            match record.record_type {
+                ENDSTR => break,
+                BOUNDARY | PATH | SREF | AREF | TEXT | NODE | BOX => {
+                    let element = self.read_element(record.record_type)?;
+                    self.insert_element(&cell, element)?;
+                }
+                _ => return malformed("unexpected record in structure"),
+            }
+        }
+        Ok(())
+    }
+
+    fn read_element(&mut self, element_type: u8) -> Result<Element, GdsError> {
+        let mut element = Element {
+            element_type,
+            magnification: 1.0,
+            ..Default::default()
+        };
+        let mut attribute = None;
+        loop {
+            let record = self.next()?;
+            match record.record_type {
+                ENDEL => break,
+                LAYER => element.layer = record.unsigned()?,
+                DATATYPE | TEXTTYPE | BOXTYPE => element.datatype = record.unsigned()?,
+                WIDTH => element.width = record.i32()?,
+                PATHTYPE => element.path_type = record.i16()?,
+                BGNEXTN => element.begin_extension = record.i32()?,
+                ENDEXTN => element.end_extension = record.i32()?,
+                XY => element.points = record.points(),
+                SNAME => element.structure_name = record.string()?,
+                STRANS => element.strans = record.i16()? as u16,
+                MAG => element.magnification = record.real()?,
+                ANGLE => element.angle = record.real()?,
+                COLROW => {
+                    let colrow: Vec<_> = record.i16s().collect();
+                    if let [columns, rows] = colrow.as_slice() {
+                        element.columns = *columns;
+                        element.rows = *rows;
+                    } else {
+                        return malformed("invalid COLROW record");
+                    }
+                }
+                STRING => element.string = record.string()?,
+                PROPATTR => attribute = Some(record.i16()?),
+                PROPVALUE => match attribute.take() {
+                    Some(attribute) => element.properties.push((attribute, record.string()?)),
+                    None => return malformed("PROPVALUE without PROPATTR"),
+                },
+                // ELFLAGS, PLEX, PRESENTATION, NODETYPE, ...
+                _ => {}
+            }
+        }
+        Ok(element)
+    }
+
+    fn insert_element(&mut self, cell: &L::CellId, element: Element) -> Result<(), GdsError> {
+        let properties: Vec<(L::NameType, PropertyValue)> = element
+            .properties
+            .iter()
+            .map(|(attribute, value)| (attribute.to_string().into(), value.clone().into()))
+            .collect();
+
+        match element.element_type {
+            SREF | AREF => {
+                let template = self.cell(&element.structure_name);
+                if self.layout.cell_depends_on(&template, cell) {
+                    return Err(GdsError::Malformed(format!(
+                        "structure '{}' is referenced recursively",
+                        element.structure_name
+                    )));
+                }
+                let placement = match element.element_type {
+                    SREF => element.points.first().map(|origin| (*origin, None)),
+                    _ => {
+                        let (origin, array) = aref_array(&element)?;
+                        Some((origin, Some(array)))
+                    }
+                };
+                if let Some((origin, array)) = placement {
+                    let tf = element.transform(origin)?;
+                    let inst = self.layout.create_cell_instance(cell, &template, None);
+                    self.layout.set_transform(&inst, tf);
+                    if array.is_some() {
+                        self.layout.set_cell_instance_array(&inst, array);
+                    }
+                    for (key, value) in properties {
+                        self.layout.set_cell_instance_property(&inst, key, value);
+                    }
+                }
+            }
+            NODE => {}
+            _ => {
+                let layer = self.layer(element.layer, element.datatype);
+                let shape = self.layout.insert_shape(cell, &layer, element.geometry()?);
+                for (key, value) in properties {
+                    self.layout.set_shape_property(&shape, key, value);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Get the location of the first element and the array parameters of an array reference.
+fn aref_array(element: &Element) -> Result<(Point<i32>, InstanceArray<i32>), GdsError> {
+    let (origin, column_end, row_end) = match element.points.as_slice() {
+        [a, b, c] => (*a, *b, *c),
+        _ => return malformed("AREF needs three points"),
+    };
+    if element.columns <= 0 || element.rows <= 0 {
+        return malformed("invalid number of columns or rows");
+    }
+    let (columns, rows) = (element.columns as i32, element.rows as i32);
+    let column_step = (column_end - origin) / columns;
+    let row_step = (row_end - origin) / rows;
+    let array = InstanceArray::new(columns as u32, rows as u32, column_step, row_step);
+    Ok((origin, array))
+}
+
+/// Write GDSII streams.
+#[derive(Debug, Clone)]
+pub struct GdsWriter {
+    library_name: String,
+}
+
+impl Default for GdsWriter {
+    fn default() -> Self {
+        Self {
+            library_name: "LIB".to_string(),
+        }
+    }
+}
+
+impl GdsWriter {
+    /// Create a writer with default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the name of the library. Default is `LIB`.
+    pub fn library_name(mut self, name: String) -> Self {
+        self.library_name = name;
+        self
+    }
+}
+
+impl LayoutStreamWriter for GdsWriter {
+    type Error = GdsError;
+
+    fn write_layout<W: Write, L: LayoutBase<Coord = i32>>(
+        &self,
+        writer: &mut W,
+        layout: &L,
+    ) -> Result<(), Self::Error> {
+        let mut w = RecordWriter { writer };
+        // Fixed time stamps make the output reproducible.
+        let time_stamps = [1970, 1, 1, 0, 0, 0, 1970, 1, 1, 0, 0, 0];
+
+        w.i16s(HEADER, &[600])?;
+        w.i16s(BGNLIB, &time_stamps)?;
+        w.string(LIBNAME, &self.library_name)?;
+        let dbu = layout.dbu().max(1) as f64;
+        w.reals(UNITS, &[1.0 / dbu, 1e-6 / dbu])?;
+
+        for cell in layout.each_cell_bottom_to_top() {
+            w.i16s(BGNSTR, &time_stamps)?;
+            w.string(STRNAME, &layout.cell_name(&cell).to_string())?;
+
+            for layer in layout.each_layer() {
+                let info = layout.layer_info(&layer);
+                let (index, datatype) = (info.index, info.datatype);
+                if index > u16::MAX as UInt || datatype > u16::MAX as UInt {
+                    return malformed("layer number out of range");
+                }
+                for shape in layout.each_shape_id(&cell, &layer) {
+                    let geometry = layout.shape_geometry(&shape);
+                    let mut properties = Vec::new();
+                    layout.for_each_shape_property(&shape, |key, value| {
+                        properties.push((key.clone().into(), value.clone()))
+                    });
+                    w.shape(index as u16, datatype as u16, &geometry)?;
+                    w.properties(&properties)?;
+                    w.no_data(ENDEL)?;
+                }
+            }
+
+            for inst in layout.each_cell_instance(&cell) {
+                let template = layout.template_cell(&inst);
+                let template_name = layout.cell_name(&template).to_string();
+                let mut properties = Vec::new();
+                layout.for_each_cell_instance_property(&inst, |key, value| {
+                    properties.push((key.clone().into(), value.clone()))
+                });
+                let max_count = i16::MAX as u32;
+                match layout.cell_instance_array(&inst) {
+                    Some(array) if array.columns <= max_count && array.rows <= max_count => {
+                        let tf = layout.get_transform(&inst);
+                        let origin = tf.displacement;
+                        let column_end = origin + array.column_pitch * array.columns as i32;
+                        let row_end = origin + array.row_pitch * array.rows as i32;
+                        w.no_data(AREF)?;
+                        w.string(SNAME, &template_name)?;
+                        w.transform(&tf)?;
+                        w.i16s(COLROW, &[array.columns as i16, array.rows as i16])?;
+                        w.i32s(
+                            XY,
+                            &[
+                                origin.x,
+                                origin.y,
+                                column_end.x,
+                                column_end.y,
+                                row_end.x,
+                                row_end.y,
+                            ],
+                        )?;
+                        w.properties(&properties)?;
+                        w.no_data(ENDEL)?;
+                    }
+                    _ => {
+                        let mut placements = Vec::new();
+                        layout.for_each_array_element(&inst, |_, _, tf| placements.push(tf));
+                        for tf in placements {
+                            w.no_data(SREF)?;
+                            w.string(SNAME, &template_name)?;
+                            w.transform(&tf)?;
+                            w.i32s(XY, &[tf.displacement.x, tf.displacement.y])?;
+                            w.properties(&properties)?;
+                            w.no_data(ENDEL)?;
+                        }
+                    }
+                }
+            }
+
+            w.no_data(ENDSTR)?;
+        }
+
+        w.no_data(ENDLIB)?;
+        Ok(())
+    }
+}
+
+struct RecordWriter<'a, W> {
+    writer: &'a mut W,
+}
+
+impl<'a, W: Write> RecordWriter<'a, W> {
+    fn record(&mut self, record_type: u8, data_type: u8, data: &[u8]) -> Result<(), GdsError> {
+        let length = data.len() + 4;
+        if length > u16::MAX as usize {
+            return Err(GdsError::UnsupportedGeometry);
+        }
+        self.writer.write_all(&(length as u16).to_be_bytes())?;
+        self.writer.write_all(&[record_type, data_type])?;
+        self.writer.write_all(data)?;
+        Ok(())
+    }
+
+    fn no_data(&mut self, record_type: u8) -> Result<(), GdsError> {
+        self.record(record_type, NO_DATA, &[])
+    }
+
+    fn i16s(&mut self, record_type: u8, values: &[i16]) -> Result<(), GdsError> {
+        let data: Vec<u8> = values.iter().flat_map(|v| v.to_be_bytes()).collect();
+        self.record(record_type, INT16, &data)
+    }
+
+    fn i32s(&mut self, record_type: u8, values: &[i32]) -> Result<(), GdsError> {
+        let data: Vec<u8> = values.iter().flat_map(|v| v.to_be_bytes()).collect();
+        self.record(record_type, INT32, &data)
+    }
+
+    fn reals(&mut self, record_type: u8, values: &[f64]) -> Result<(), GdsError> {
+        let data: Vec<u8> = values
+            .iter()
+            .flat_map(|v| f64_to_real8(*v).to_be_bytes())
+            .collect();
+        self.record(record_type, REAL8, &data)
+    }
+
+    fn string(&mut self, record_type: u8, s: &str) -> Result<(), GdsError> {
+        let mut data = s.as_bytes().to_vec();
+        // Strings are padded to an even length.
+        if data.len() % 2 == 1 {
+            data.push(0);
+        }
+        self.record(record_type, ASCII, &data)
+    }
+
+    fn points(&mut self, points: impl Iterator<Item = Point<i32>>) -> Result<(), GdsError> {
+        let coords: Vec<i32> = points.flat_map(|p| [p.x, p.y]).collect();
+        if coords.len() > 2 * MAX_POINTS {
+            return Err(GdsError::UnsupportedGeometry);
+        }
+        self.i32s(XY, &coords)
+    }
+
+    fn layer(&mut self, layer: u16, datatype_record: u8, datatype: u16) -> Result<(), GdsError> {
+        self.i16s(LAYER, &[layer as i16])?;
+        self.i16s(datatype_record, &[datatype as i16])
+    }
+
+    /// Write a closed boundary.
+    fn boundary(
+        &mut self,
+        layer: u16,
+        datatype: u16,
+        points: &[Point<i32>],
+    ) -> Result<(), GdsError> {
+        self.no_data(BOUNDARY)?;
+        self.layer(layer, DATATYPE, datatype)?;
+        let closing_point = points.first().copied();
+        self.points(points.iter().copied().chain(closing_point))
+    }
+
+    fn path(&mut self, layer: u16, datatype: u16, path: &Path<i32>) -> Result<(), GdsError> {
+        self.no_data(PATH)?;
+        self.layer(layer, DATATYPE, datatype)?;
+        let width = path.width;
+        match path.path_type {
+            PathEndType::Flat => self.i16s(PATHTYPE, &[0])?,
+            PathEndType::Round => self.i16s(PATHTYPE, &[1])?,
+            PathEndType::Extended(begin, end) if begin == width / 2 && end == width / 2 => {
+                self.i16s(PATHTYPE, &[2])?
+            }
+            PathEndType::Extended(begin, end) => {
+                self.i16s(PATHTYPE, &[4])?;
+                self.i32s(BGNEXTN, &[begin])?;
+                self.i32s(ENDEXTN, &[end])?;
+            }
+        }
+        self.i32s(WIDTH, &[width])?;
+        self.points(path.points.iter().copied())
+    }
+
+    /// Write the element records of a shape without the closing `ENDEL`.
+    fn shape(
+        &mut self,
+        layer: u16,
+        datatype: u16,
+        geometry: &Geometry<i32>,
+    ) -> Result<(), GdsError> {
+        match geometry {
+            Geometry::Rect(r) => {
+                let (ll, ur) = (r.lower_left(), r.upper_right());
+                let points = [ll, Point::new(ur.x, ll.y), ur, Point::new(ll.x, ur.y)];
+                self.boundary(layer, datatype, &points)
+            }
+            Geometry::SimplePolygon(p) => {
+                let points: Vec<_> = p.iter().copied().collect();
+                self.boundary(layer, datatype, &points)
+            }
+            Geometry::Polygon(p) if p.interiors.is_empty() => {
+                let points: Vec<_> = p.exterior.iter().copied().collect();
+                self.boundary(layer, datatype, &points)
+            }
+            Geometry::Path(p) => self.path(layer, datatype, p),
+            // Edges are written as paths of zero width.
+            Geometry::Edge(e) => self.path(layer, datatype, &Path::new(vec![e.start, e.end], 0)),
+            Geometry::Text(t) => {
+                self.no_data(TEXT)?;
+                self.layer(layer, TEXTTYPE, datatype)?;
+                self.points(std::iter::once(t.location()))?;
+                self.string(STRING, t.text())
+            }
+            _ => Err(GdsError::UnsupportedGeometry),
+        }
+    }
+
+    fn transform(&mut self, tf: &SimpleTransform<i32>) -> Result<(), GdsError> {
+        let angle = match tf.rotation {
+            Angle::R0 => 0.0,
+            Angle::R90 => 90.0,
+            Angle::R180 => 180.0,
+            Angle::R270 => 270.0,
+        };
+        if tf.mirror || angle != 0.0 || tf.magnification != 1 {
+            let strans = if tf.mirror { STRANS_REFLECTION } else { 0 };
+            self.record(STRANS, BIT_ARRAY, &strans.to_be_bytes())?;
+        }
+        if tf.magnification != 1 {
+            self.reals(MAG, &[tf.magnification as f64])?;
+        }
+        if angle != 0.0 {
+            self.reals(ANGLE, &[angle])?;
+        }
+        Ok(())
+    }
+
+    /// Write properties with numeric keys as `PROPATTR`/`PROPVALUE` pairs. Other properties are skipped.
+    fn properties(&mut self, properties: &[(String, PropertyValue)]) -> Result<(), GdsError> {
+        for (key, value) in properties {
+            let attribute: i16 = match key.parse() {
+                Ok(attribute) => attribute,
+                Err(_) => continue,
+            };
+            let value = match value {
+                PropertyValue::String(s) => s.to_string(),
+                PropertyValue::SInt(v) => v.to_string(),
+                PropertyValue::UInt(v) => v.to_string(),
+                PropertyValue::Float(v) => v.to_string(),
+                PropertyValue::Bytes(_) => continue,
+            };
+            self.i16s(PROPATTR, &[attribute])?;
+            self.string(PROPVALUE, &value)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_real8_conversion() {
+        for value in [0.0, 1.0, -2.5, 1e-3, 1e-9, 90.0, 270.0, 123456.75] {
+            let converted = real8_to_f64(f64_to_real8(value));
+            assert!((converted - value).abs() <= value.abs() * 1e-15);
+        }
+        // Known encoding of 1.0.
+        assert_eq!(f64_to_real8(1.0), 0x4110_0000_0000_0000);
+    }
+
+    #[test]
+    fn test_write_and_read_back() {
+        let mut chip = Chip::new();
+        chip.set_dbu(1000);
+        let top = chip.create_cell("TOP".into());
+        let sub = chip.create_cell("SUB".into());
+        let layer = chip.create_layer(1, 0);
+        let text_layer = chip.create_layer(2, 5);
+
+        let shapes: Vec<Geometry<i32>> = vec![
+            Rect::new((0, 0), (10, 20)).into(),
+            SimplePolygon::new(vec![(0, 0).into(), (10, 0).into(), (0, 10).into()]).into(),
+            Path::new(vec![(0, 0).into(), (100, 0).into()], 10).into(),
+            Path::new_rounded(vec![(0, 0).into(), (0, 100).into()], 10).into(),
+            Path::new_extended(vec![(0, 0).into(), (100, 100).into()], 10, 5, 5).into(),
+            Path::new_extended(vec![(0, 0).into(), (100, 50).into()], 10, 1, 2).into(),
+        ];
+        for shape in &shapes {
+            chip.insert_shape(&sub, &layer, shape.clone());
+        }
+        let text = chip.insert_shape(
+            &sub,
+            &text_layer,
+            Text::new("A".into(), (1, 2).into()).into(),
+        );
+        chip.set_shape_property(&text, "7".into(), "net A".into());
+
+        let inst = chip.create_cell_instance(&top, &sub, None);
+        let tf = SimpleTransform::new(true, Angle::R90, 2, Vector::new(100, -50));
+        chip.set_transform(&inst, tf);
+
+        let mut buffer = Vec::new();
+        GdsWriter::new().write_layout(&mut buffer, &chip).unwrap();
+
+        let mut restored = Chip::new();
+        GdsReader::new()
+            .read_layout(&mut buffer.as_slice(), &mut restored)
+            .unwrap();
+
+        assert_eq!(restored.dbu(), 1000);
+        let restored_top = restored.cell_by_name("TOP").unwrap();
+        let restored_sub = restored.cell_by_name("SUB").unwrap();
+        let restored_layer = restored.find_layer(1, 0).unwrap();
+        let restored_text_layer = restored.find_layer(2, 5).unwrap();
+
+        let restored_shapes: Vec<_> = restored
+            .each_shape_id(&restored_sub, &restored_layer)
+            .map(|s| restored.shape_geometry(&s))
+            .collect();
+        assert_eq!(restored_shapes, shapes);
+
+        let restored_text = restored
+            .each_shape_id(&restored_sub, &restored_text_layer)
+            .next()
+            .unwrap();
+        assert_eq!(
+            restored.shape_geometry(&restored_text),
+            Text::new("A".into(), (1, 2).into()).into()
+        );
+        assert_eq!(
+            restored
+                .get_shape_property(&restored_text, &"7".into())
+                .and_then(|v| v.get_string()),
+            Some("net A".into())
+        );
+
+        let restored_inst = restored.each_cell_instance(&restored_top).next().unwrap();
+        assert_eq!(restored.template_cell(&restored_inst), restored_sub);
+        assert_eq!(restored.get_transform(&restored_inst), tf);
+    }
+
+    #[test]
+    fn test_read_aref() {
+        // Assemble a stream with an array reference by hand.
+        let mut buffer = Vec::new();
+        let mut w = RecordWriter {
+            writer: &mut buffer,
+        };
+        let time_stamps = [0; 12];
+        w.i16s(HEADER, &[600]).unwrap();
+        w.i16s(BGNLIB, &time_stamps).unwrap();
+        w.reals(UNITS, &[1e-3, 1e-9]).unwrap();
+        w.i16s(BGNSTR, &time_stamps).unwrap();
+        w.string(STRNAME, "TOP").unwrap();
+        w.no_data(AREF).unwrap();
+        w.string(SNAME, "SUB").unwrap();
+        w.i16s(COLROW, &[3, 2]).unwrap();
+        w.i32s(XY, &[0, 0, 30, 0, 0, 40]).unwrap();
+        w.no_data(ENDEL).unwrap();
+        w.no_data(ENDSTR).unwrap();
+        w.i16s(BGNSTR, &time_stamps).unwrap();
+        w.string(STRNAME, "SUB").unwrap();
+        w.no_data(ENDSTR).unwrap();
+        w.no_data(ENDLIB).unwrap();
+
+        let mut chip = Chip::new();
+        GdsReader::new()
+            .read_layout(&mut buffer.as_slice(), &mut chip)
+            .unwrap();
+        let top = chip.cell_by_name("TOP").unwrap();
+        assert_eq!(chip.num_child_instances(&top), 1);
+        let inst = chip.each_cell_instance(&top).next().unwrap();
+        let array = InstanceArray::new(3, 2, Vector::new(10, 0), Vector::new(0, 20));
+        assert_eq!(chip.cell_instance_array(&inst), Some(array));
+        let mut locations = Vec::new();
+        chip.for_each_array_element(&inst, |_, _, tf| locations.push(tf.displacement));
+        locations.sort_by_key(|v| (v.x, v.y));
+        let expected: Vec<Vector<i32>> = vec![
+            Vector::new(0, 0),
+            Vector::new(0, 20),
+            Vector::new(10, 0),
+            Vector::new(10, 20),
+            Vector::new(20, 0),
+            Vector::new(20, 20),
+        ];
+        assert_eq!(locations, expected);
+
+        // Write the array and read it again.
+        let mut buffer = Vec::new();
+        GdsWriter::new().write_layout(&mut buffer, &chip).unwrap();
+        let mut restored = Chip::new();
+        GdsReader::new()
+            .read_layout(&mut buffer.as_slice(), &mut restored)
+            .unwrap();
+        let top = restored.cell_by_name("TOP").unwrap();
+        let inst = restored.each_cell_instance(&top).next().unwrap();
+        assert_eq!(restored.cell_instance_array(&inst), Some(array));
+    }
+
+    #[test]
+    fn test_read_recursive_reference() {
+        let mut buffer = Vec::new();
+        let mut w = RecordWriter {
+            writer: &mut buffer,
+        };
+        let time_stamps = [0; 12];
+        w.i16s(HEADER, &[600]).unwrap();
+        w.i16s(BGNLIB, &time_stamps).unwrap();
+        w.reals(UNITS, &[1e-3, 1e-9]).unwrap();
+        for (name, child) in [("A", "B"), ("B", "A")] {
+            w.i16s(BGNSTR, &time_stamps).unwrap();
+            w.string(STRNAME, name).unwrap();
+            w.no_data(SREF).unwrap();
+            w.string(SNAME, child).unwrap();
+            w.i32s(XY, &[0, 0]).unwrap();
+            w.no_data(ENDEL).unwrap();
+            w.no_data(ENDSTR).unwrap();
+        }
+        w.no_data(ENDLIB).unwrap();
+
+        let result = GdsReader::new().read_layout(&mut buffer.as_slice(), &mut Chip::new());
+        assert!(matches!(result, Err(GdsError::Malformed(_))));
+    }
+}
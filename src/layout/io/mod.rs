@@ -5,11 +5,17 @@
 
 //! Input and output interface definitions for layouts.
 //!
-//! Implementations for the various layout formats are located in other crates.
+//! Readers and writers for GDSII and OASIS streams are implemented in [`gds`] and [`oasis`].
+//! Cells can be rendered into SVG images for debugging with [`svg`].
+//! Implementations for other layout formats are located in other crates.
 
 use crate::prelude::{LayoutBase, LayoutEdit};
 use std::io::{Read, Write};
 
+pub mod gds;
+pub mod oasis;
+pub mod svg;
+
 /// Trait for reading a layout from a byte stream.
 pub trait LayoutStreamReader {
     /// Type of error that could happen while reading a layout.
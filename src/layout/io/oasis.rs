@@ -0,0 +1,2095 @@
+// SPDX-FileCopyrightText: 2022 Thomas Kramer
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Reader and writer for the OASIS stream format.
+//!
+//! The reader supports name tables with implicit and explicit reference numbers (also when the
+//! tables are located at the end of the file), modal variables, absolute and relative coordinates,
+//! `CBLOCK` compression and all kinds of repetitions. Placements which are repeated on a regular
+//! grid become a single cell instance with an [`InstanceArray`](crate::layout::array::InstanceArray).
+//! All other repetitions are expanded into individual cell instances or shapes.
+//!
+//! Geometries are mapped as follows:
+//! * `RECTANGLE` becomes a rectangle.
+//! * `POLYGON`, `TRAPEZOID` and `CTRAPEZOID` become simple polygons (or rectangles when possible).
+//! * `PATH` becomes a path with flat or extended ends.
+//! * `TEXT` becomes a text on the layer given by the text layer and text type.
+//! * `CIRCLE` is approximated by a polygon.
+//!
+//! Properties of elements, cells and of the file are stored as properties of shapes, cell instances,
+//! cells and the chip. GDSII properties (`S_GDS_PROPERTY`) use the attribute number as key, like
+//! the [`gds`](super::gds) module. Other standard properties are ignored.
+//!
+//! The writer uses `CBLOCK` compression for the content of cells unless disabled
+//! with [`OasisWriter::compress`].
+//!
+//! # Example
+//!
+//! ```
+//! use libreda_db::prelude::*;
+//! use libreda_db::layout::io::oasis::{OasisReader, OasisWriter};
+//!
+//! let mut chip = Chip::new();
+//! chip.set_dbu(1000);
+//! let top = chip.create_cell("TOP".into());
+//! let layer = chip.create_layer(1, 0);
+//! chip.insert_shape(&top, &layer, Rect::new((0, 0), (100, 200)).into());
+//!
+//! let mut buffer = Vec::new();
+//! OasisWriter::new().write_layout(&mut buffer, &chip).unwrap();
+//!
+//! let mut restored = Chip::new();
+//! OasisReader::new().read_layout(&mut buffer.as_slice(), &mut restored).unwrap();
+//! assert_eq!(restored.dbu(), 1000);
+//! assert!(restored.cell_by_name("TOP").is_some());
+//! ```
+
+use super::{LayoutStreamReader, LayoutStreamWriter};
+use crate::prelude::*;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+/// Magic bytes at the start of every OASIS file.
+const MAGIC: &[u8] = b"%SEMI-OASIS\r\n";
+
+// Record types.
+const PAD: u64 = 0;
+const START: u64 = 1;
+const END: u64 = 2;
+const CELLNAME_IMPLICIT: u64 = 3;
+const CELLNAME: u64 = 4;
+const TEXTSTRING_IMPLICIT: u64 = 5;
+const TEXTSTRING: u64 = 6;
+const PROPNAME_IMPLICIT: u64 = 7;
+const PROPNAME: u64 = 8;
+const PROPSTRING_IMPLICIT: u64 = 9;
+const PROPSTRING: u64 = 10;
+const LAYERNAME: u64 = 11;
+const LAYERNAME_TEXT: u64 = 12;
+const CELL_REF: u64 = 13;
+const CELL: u64 = 14;
+const XYABSOLUTE: u64 = 15;
+const XYRELATIVE: u64 = 16;
+const PLACEMENT: u64 = 17;
+const PLACEMENT_MAG_ANGLE: u64 = 18;
+const TEXT: u64 = 19;
+const RECTANGLE: u64 = 20;
+const POLYGON: u64 = 21;
+const PATH: u64 = 22;
+const TRAPEZOID: u64 = 23;
+const TRAPEZOID_A: u64 = 24;
+const TRAPEZOID_B: u64 = 25;
+const CTRAPEZOID: u64 = 26;
+const CIRCLE: u64 = 27;
+const PROPERTY: u64 = 28;
+const PROPERTY_REPEAT: u64 = 29;
+const XNAME_IMPLICIT: u64 = 30;
+const XNAME: u64 = 31;
+const XELEMENT: u64 = 32;
+const XGEOMETRY: u64 = 33;
+const CBLOCK: u64 = 34;
+
+/// Name of the standard property which holds GDSII properties.
+const S_GDS_PROPERTY: &str = "S_GDS_PROPERTY";
+
+/// Number of vertices used to approximate circles.
+const CIRCLE_VERTICES: usize = 64;
+
+/// Error type used for reading and writing OASIS streams.
+#[derive(Debug)]
+pub enum OasisError {
+    /// Error of the underlying byte stream.
+    Io(std::io::Error),
+    /// The stream is not valid OASIS.
+    Malformed(String),
+    /// The file uses an OASIS feature which is not supported.
+    Unsupported(String),
+    /// A cell is defined in the stream but a cell with this name exists already.
+    CellNameCollision(String),
+    /// The transformation of a cell reference cannot be represented with a [`SimpleTransform`].
+    /// Only rotations by multiples of 90 degrees and integer magnifications are supported.
+    UnsupportedTransform,
+    /// The geometry cannot be represented in OASIS, for example polygons with holes or paths with
+    /// round ends.
+    UnsupportedGeometry,
+}
+
+impl std::fmt::Display for OasisError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OasisError::Io(err) => write!(f, "IO error: {}", err),
+            OasisError::Malformed(message) => write!(f, "Malformed OASIS stream: {}", message),
+            OasisError::Unsupported(message) => write!(f, "Unsupported: {}", message),
+            OasisError::CellNameCollision(name) => write!(f, "Cell '{}' already exists.", name),
+            OasisError::UnsupportedTransform => write!(f, "Unsupported transformation."),
+            OasisError::UnsupportedGeometry => write!(f, "Geometry not supported by OASIS."),
+        }
+    }
+}
+
+impl std::error::Error for OasisError {}
+
+impl From<std::io::Error> for OasisError {
+    fn from(err: std::io::Error) -> Self {
+        OasisError::Io(err)
+    }
+}
+
+fn malformed<T>(message: &str) -> Result<T, OasisError> {
+    Err(OasisError::Malformed(message.to_string()))
+}
+
+/// Convert a 64-bit integer from the stream into a coordinate.
+fn coord(value: i64) -> Result<i32, OasisError> {
+    value
+        .try_into()
+        .map_err(|_| OasisError::Malformed("coordinate out of range".into()))
+}
+
+// Reader.
+
+/// Byte source which transparently switches to the content of `CBLOCK` records.
+struct ByteStream {
+    /// Stack of buffers. The last buffer is the decompressed content of the current `CBLOCK`.
+    buffers: Vec<(Vec<u8>, usize)>,
+}
+
+impl ByteStream {
+    fn new(data: Vec<u8>) -> Self {
+        Self {
+            buffers: vec![(data, 0)],
+        }
+    }
+
+    /// Check if all data has been consumed.
+    fn at_end(&mut self) -> bool {
+        while let Some((buffer, pos)) = self.buffers.last() {
+            if *pos < buffer.len() {
+                return false;
+            }
+            if self.buffers.len() == 1 {
+                return true;
+            }
+            self.buffers.pop();
+        }
+        true
+    }
+
+    /// Number of bytes which have not been consumed yet.
+    fn remaining(&self) -> usize {
+        self.buffers
+            .iter()
+            .map(|(buffer, pos)| buffer.len() - pos)
+            .sum()
+    }
+
+    fn byte(&mut self) -> Result<u8, OasisError> {
+        if self.at_end() {
+            return malformed("unexpected end of stream");
+        }
+        let (buffer, pos) = self.buffers.last_mut().unwrap();
+        let b = buffer[*pos];
+        *pos += 1;
+        Ok(b)
+    }
+
+    fn bytes(&mut self, n: usize) -> Result<Vec<u8>, OasisError> {
+        (0..n).map(|_| self.byte()).collect()
+    }
+
+    fn uint(&mut self) -> Result<u64, OasisError> {
+        let mut value = 0u64;
+        let mut shift = 0;
+        loop {
+            let b = self.byte()?;
+            if shift > 63 || (shift > 56 && (b & 0x7f) >> (64 - shift) != 0) {
+                return malformed("integer overflow");
+            }
+            value |= ((b & 0x7f) as u64) << shift;
+            if b & 0x80 == 0 {
+                return Ok(value);
+            }
+            shift += 7;
+        }
+    }
+
+    fn sint(&mut self) -> Result<i64, OasisError> {
+        let u = self.uint()?;
+        let magnitude = (u >> 1) as i64;
+        Ok(if u & 1 == 1 { -magnitude } else { magnitude })
+    }
+
+    fn real(&mut self) -> Result<f64, OasisError> {
+        let real_type = self.uint()?;
+        self.real_of_type(real_type)
+    }
+
+    fn real_of_type(&mut self, real_type: u64) -> Result<f64, OasisError> {
+        Ok(match real_type {
+            0 => self.uint()? as f64,
+            1 => -(self.uint()? as f64),
+            2 => 1.0 / self.uint()? as f64,
+            3 => -1.0 / self.uint()? as f64,
+            4 => self.uint()? as f64 / self.uint()? as f64,
+            5 => -(self.uint()? as f64) / self.uint()? as f64,
+            6 => {
+                let mut bytes = [0; 4];
+                bytes.copy_from_slice(&self.bytes(4)?);
+                f32::from_le_bytes(bytes) as f64
+            }
+            7 => {
+                let mut bytes = [0; 8];
+                bytes.copy_from_slice(&self.bytes(8)?);
+                f64::from_le_bytes(bytes)
+            }
+            _ => return malformed("invalid real type"),
+        })
+    }
+
+    fn byte_string(&mut self) -> Result<Vec<u8>, OasisError> {
+        let len = self.uint()? as usize;
+        self.bytes(len)
+    }
+
+    fn string(&mut self) -> Result<String, OasisError> {
+        String::from_utf8(self.byte_string()?)
+            .map_err(|_| OasisError::Malformed("invalid string".into()))
+    }
+
+    /// Read a general delta.
+    fn g_delta(&mut self) -> Result<Vector<i32>, OasisError> {
+        let u = self.uint()?;
+        if u & 1 == 0 {
+            // Form 1: octangular direction and magnitude.
+            let magnitude = coord((u >> 4) as i64)?;
+            Ok(direction_vector((u >> 1) & 7) * magnitude)
+        } else {
+            // Form 2: arbitrary vector.
+            let x = coord((u >> 2) as i64)?;
+            let x = if u & 2 != 0 { -x } else { x };
+            let y = coord(self.sint()?)?;
+            Ok(Vector::new(x, y))
+        }
+    }
+
+    /// Read a point list. Returns the points relative to the first point which is not included.
+    /// Implicit closing points of Manhattan polygons are added if `is_polygon` is set.
+    fn point_list(&mut self, is_polygon: bool) -> Result<Vec<Vector<i32>>, OasisError> {
+        let list_type = self.uint()?;
+        let count = usize::try_from(self.uint()?)
+            .ok()
+            .filter(|&c| c.checked_add(1).is_some())
+            .ok_or_else(|| OasisError::Malformed("point count out of range".into()))?;
+        // Each point takes at least one byte. Don't trust the count for preallocation.
+        let mut points = Vec::with_capacity(count.min(self.remaining()) + 1);
+        let mut current = Vector::new(0, 0);
+        let mut previous_delta = Vector::new(0, 0);
+        for i in 0..count {
+            let delta = match list_type {
+                0 | 1 => {
+                    let d = coord(self.sint()?)?;
+                    let horizontal = (i % 2 == 0) == (list_type == 0);
+                    if horizontal {
+                        Vector::new(d, 0)
+                    } else {
+                        Vector::new(0, d)
+                    }
+                }
+                2 => {
+                    let u = self.uint()?;
+                    direction_vector(u & 3) * coord((u >> 2) as i64)?
+                }
+                3 => {
+                    let u = self.uint()?;
+                    direction_vector(u & 7) * coord((u >> 3) as i64)?
+                }
+                4 => self.g_delta()?,
+                5 => {
+                    previous_delta = previous_delta + self.g_delta()?;
+                    previous_delta
+                }
+                _ => return malformed("invalid point list type"),
+            };
+            current = current + delta;
+            points.push(current);
+        }
+        if is_polygon && (list_type == 0 || list_type == 1) {
+            // Close the polygon with an edge perpendicular to the last edge.
+            let last_horizontal = (count % 2 == 1) == (list_type == 0);
+            if last_horizontal {
+                points.push(Vector::new(current.x, 0));
+            } else {
+                points.push(Vector::new(0, current.y));
+            }
+        }
+        Ok(points)
+    }
+
+    /// Read a repetition. Returns the displacements of all elements of the repetition.
+    fn repetition(&mut self, previous: &[Vector<i32>]) -> Result<Vec<Vector<i32>>, OasisError> {
+        let repetition_type = self.uint()?;
+        let grid_vectors = |n: i32, m: i32, a: Vector<i32>, b: Vector<i32>| {
+            (0..m)
+                .flat_map(move |j| (0..n).map(move |i| a * i + b * j))
+                .collect::<Vec<_>>()
+        };
+        Ok(match repetition_type {
+            0 => previous.to_vec(),
+            1 => {
+                let (nx, ny) = (self.dimension()?, self.dimension()?);
+                let (sx, sy) = (self.uint_coord()?, self.uint_coord()?);
+                grid_vectors(nx, ny, Vector::new(sx, 0), Vector::new(0, sy))
+            }
+            2 => {
+                let nx = self.dimension()?;
+                grid_vectors(nx, 1, Vector::new(self.uint_coord()?, 0), Vector::new(0, 0))
+            }
+            3 => {
+                let ny = self.dimension()?;
+                grid_vectors(1, ny, Vector::new(0, 0), Vector::new(0, self.uint_coord()?))
+            }
+            4..=7 => {
+                let n = self.dimension()?;
+                let grid = if repetition_type == 5 || repetition_type == 7 {
+                    self.uint_coord()?
+                } else {
+                    1
+                };
+                let mut position = 0;
+                let mut result = vec![Vector::new(0, 0)];
+                for _ in 1..n {
+                    position += self.uint_coord()? * grid;
+                    result.push(if repetition_type <= 5 {
+                        Vector::new(position, 0)
+                    } else {
+                        Vector::new(0, position)
+                    });
+                }
+                result
+            }
+            8 => {
+                let (n, m) = (self.dimension()?, self.dimension()?);
+                let (a, b) = (self.g_delta()?, self.g_delta()?);
+                grid_vectors(n, m, a, b)
+            }
+            9 => {
+                let n = self.dimension()?;
+                grid_vectors(n, 1, self.g_delta()?, Vector::new(0, 0))
+            }
+            10 | 11 => {
+                let n = self.dimension()?;
+                let grid = if repetition_type == 11 {
+                    self.uint_coord()?
+                } else {
+                    1
+                };
+                let mut position = Vector::new(0, 0);
+                let mut result = vec![position];
+                for _ in 1..n {
+                    position = position + self.g_delta()? * grid;
+                    result.push(position);
+                }
+                result
+            }
+            _ => return malformed("invalid repetition type"),
+        })
+    }
+
+    /// Read the number of elements of a repetition which is stored minus two.
+    fn dimension(&mut self) -> Result<i32, OasisError> {
+        coord(self.uint()? as i64 + 2)
+    }
+
+    fn uint_coord(&mut self) -> Result<i32, OasisError> {
+        coord(self.uint()? as i64)
+    }
+}
+
+/// Unit vector of an octangular direction as used in deltas:
+/// east, north, west, south, north-east, north-west, south-west, south-east.
+fn direction_vector(direction: u64) -> Vector<i32> {
+    let (x, y) = match direction {
+        0 => (1, 0),
+        1 => (0, 1),
+        2 => (-1, 0),
+        3 => (0, -1),
+        4 => (1, 1),
+        5 => (-1, 1),
+        6 => (-1, -1),
+        _ => (1, -1),
+    };
+    Vector::new(x, y)
+}
+
+/// Reference to a name which is either given directly or by a reference number.
+#[derive(Clone, Debug)]
+enum NameRef {
+    Name(String),
+    Ref(u64),
+}
+
+#[derive(Clone, Debug)]
+enum PropValue {
+    Real(f64),
+    UInt(u64),
+    SInt(i64),
+    String(Vec<u8>),
+    StringRef(u64),
+}
+
+#[derive(Clone, Debug)]
+struct Property {
+    name: NameRef,
+    is_standard: bool,
+    values: Vec<PropValue>,
+}
+
+enum ElementKind {
+    Shape {
+        layer: UInt,
+        datatype: UInt,
+        geometry: Geometry<i32>,
+    },
+    Text {
+        layer: UInt,
+        texttype: UInt,
+        string: NameRef,
+        location: Point<i32>,
+    },
+    Placement {
+        cell: NameRef,
+        transform: SimpleTransform<i32>,
+    },
+}
+
+struct Element {
+    kind: ElementKind,
+    /// Displacements of all copies of the element.
+    repetition: Vec<Vector<i32>>,
+    properties: Vec<Property>,
+}
+
+struct CellData {
+    name: NameRef,
+    properties: Vec<Property>,
+    elements: Vec<Element>,
+}
+
+/// Numbering of a name table. Implicit and explicit reference numbers must not be mixed.
+#[derive(Default)]
+struct NameTable {
+    names: HashMap<u64, String>,
+    next_implicit: u64,
+}
+
+impl NameTable {
+    fn insert(&mut self, name: String, reference: Option<u64>) {
+        let reference = reference.unwrap_or_else(|| {
+            self.next_implicit += 1;
+            self.next_implicit - 1
+        });
+        self.names.insert(reference, name);
+    }
+
+    fn resolve<'a>(&'a self, name: &'a NameRef) -> Result<&'a str, OasisError> {
+        match name {
+            NameRef::Name(name) => Ok(name),
+            NameRef::Ref(r) => {
+                self.names.get(r).map(|s| s.as_str()).ok_or_else(|| {
+                    OasisError::Malformed(format!("undefined reference number {}", r))
+                })
+            }
+        }
+    }
+}
+
+/// Modal variables of the OASIS format.
+#[derive(Default)]
+struct Modal {
+    relative: bool,
+    repetition: Vec<Vector<i32>>,
+    placement_x: i32,
+    placement_y: i32,
+    placement_cell: Option<NameRef>,
+    layer: Option<UInt>,
+    datatype: Option<UInt>,
+    textlayer: Option<UInt>,
+    texttype: Option<UInt>,
+    text_x: i32,
+    text_y: i32,
+    text_string: Option<NameRef>,
+    geometry_x: i32,
+    geometry_y: i32,
+    geometry_w: Option<i32>,
+    geometry_h: Option<i32>,
+    polygon_points: Option<Vec<Vector<i32>>>,
+    path_halfwidth: Option<i32>,
+    path_points: Option<Vec<Vector<i32>>>,
+    path_start_extension: Option<i32>,
+    path_end_extension: Option<i32>,
+    ctrapezoid_type: Option<u64>,
+    circle_radius: Option<i32>,
+    last_property: Option<Property>,
+}
+
+fn modal<T: Clone>(value: &Option<T>, name: &str) -> Result<T, OasisError> {
+    value
+        .clone()
+        .ok_or_else(|| OasisError::Malformed(format!("modal variable '{}' is not defined", name)))
+}
+
+/// Content of the file before name references are resolved.
+#[derive(Default)]
+struct OasisFile {
+    unit: f64,
+    properties: Vec<Property>,
+    cells: Vec<CellData>,
+    cell_names: NameTable,
+    text_strings: NameTable,
+    prop_names: NameTable,
+    prop_strings: NameTable,
+    /// Layer names together with layer number and datatype.
+    layer_names: Vec<(String, UInt, UInt)>,
+}
+
+/// Parser of OASIS records.
+struct Parser {
+    stream: ByteStream,
+    modal: Modal,
+    file: OasisFile,
+}
+
+impl Parser {
+    fn parse(data: Vec<u8>) -> Result<OasisFile, OasisError> {
+        let mut parser = Parser {
+            stream: ByteStream::new(data),
+            modal: Default::default(),
+            file: Default::default(),
+        };
+        if parser.stream.bytes(MAGIC.len())? != MAGIC {
+            return malformed("missing magic bytes");
+        }
+        if parser.stream.uint()? != START {
+            return malformed("missing START record");
+        }
+        let version = parser.stream.string()?;
+        if version != "1.0" {
+            return Err(OasisError::Unsupported(format!("version {}", version)));
+        }
+        parser.file.unit = parser.stream.real()?;
+        let offset_flag = parser.stream.uint()?;
+        if offset_flag == 0 {
+            // Table offsets are not needed because the whole file is read.
+            for _ in 0..12 {
+                parser.stream.uint()?;
+            }
+        }
+        parser.records()?;
+        Ok(parser.file)
+    }
+
+    /// Coordinate of an element, taking the modal variables into account.
+    fn xy(
+        &mut self,
+        has_value: bool,
+        modal_value: fn(&mut Modal) -> &mut i32,
+    ) -> Result<i32, OasisError> {
+        if has_value {
+            let value = coord(self.stream.sint()?)?;
+            let relative = self.modal.relative;
+            let v = modal_value(&mut self.modal);
+            *v = if relative { *v + value } else { value };
+        }
+        Ok(*modal_value(&mut self.modal))
+    }
+
+    fn repetition(&mut self, has_repetition: bool) -> Result<Vec<Vector<i32>>, OasisError> {
+        if has_repetition {
+            self.modal.repetition = self.stream.repetition(&self.modal.repetition)?;
+            if self.modal.repetition.is_empty() {
+                return malformed("empty repetition");
+            }
+            Ok(self.modal.repetition.clone())
+        } else {
+            Ok(vec![Vector::new(0, 0)])
+        }
+    }
+
+    fn name_ref(&mut self, is_reference: bool) -> Result<NameRef, OasisError> {
+        Ok(if is_reference {
+            NameRef::Ref(self.stream.uint()?)
+        } else {
+            NameRef::Name(self.stream.string()?)
+        })
+    }
+
+    fn layer_datatype(&mut self, info: u8) -> Result<(UInt, UInt), OasisError> {
+        if info & 0x01 != 0 {
+            self.modal.layer = Some(self.stream.uint()? as UInt);
+        }
+        if info & 0x02 != 0 {
+            self.modal.datatype = Some(self.stream.uint()? as UInt);
+        }
+        Ok((
+            modal(&self.modal.layer, "layer")?,
+            modal(&self.modal.datatype, "datatype")?,
+        ))
+    }
+
+    fn push_element(
+        &mut self,
+        kind: ElementKind,
+        repetition: Vec<Vector<i32>>,
+    ) -> Result<(), OasisError> {
+        match self.file.cells.last_mut() {
+            Some(cell) => {
+                cell.elements.push(Element {
+                    kind,
+                    repetition,
+                    properties: vec![],
+                });
+                Ok(())
+            }
+            None => malformed("element outside of cell"),
+        }
+    }
+
+    fn push_shape(
+        &mut self,
+        info: u8,
+        geometry: Geometry<i32>,
+        layer: UInt,
+        datatype: UInt,
+    ) -> Result<(), OasisError> {
+        // All shape records have the flags X, Y and R at the same position.
+        let x = self.xy(info & 0x10 != 0, |m| &mut m.geometry_x)?;
+        let y = self.xy(info & 0x08 != 0, |m| &mut m.geometry_y)?;
+        let repetition = self.repetition(info & 0x04 != 0)?;
+        let offset = Vector::new(x, y);
+        let geometry = geometry.transform(|p| p + offset);
+        self.push_element(
+            ElementKind::Shape {
+                layer,
+                datatype,
+                geometry,
+            },
+            repetition,
+        )
+    }
+
+    fn width_height(&mut self, info: u8) -> Result<(i32, i32), OasisError> {
+        if info & 0x40 != 0 {
+            self.modal.geometry_w = Some(self.stream.uint_coord()?);
+        }
+        if info & 0x20 != 0 {
+            self.modal.geometry_h = Some(self.stream.uint_coord()?);
+        }
+        Ok((
+            modal(&self.modal.geometry_w, "geometry-w")?,
+            modal(&self.modal.geometry_h, "geometry-h")?,
+        ))
+    }
+
+    fn records(&mut self) -> Result<(), OasisError> {
+        loop {
+            if self.stream.at_end() {
+                return malformed("missing END record");
+            }
+            let record_type = self.stream.uint()?;
+            match record_type {
+                PAD => {}
+                END => return Ok(()),
+                CELLNAME_IMPLICIT | CELLNAME => {
+                    let name = self.stream.string()?;
+                    let reference = self.explicit_reference(record_type == CELLNAME)?;
+                    self.file.cell_names.insert(name, reference);
+                }
+                TEXTSTRING_IMPLICIT | TEXTSTRING => {
+                    let name = self.stream.string()?;
+                    let reference = self.explicit_reference(record_type == TEXTSTRING)?;
+                    self.file.text_strings.insert(name, reference);
+                }
+                PROPNAME_IMPLICIT | PROPNAME => {
+                    let name = self.stream.string()?;
+                    let reference = self.explicit_reference(record_type == PROPNAME)?;
+                    self.file.prop_names.insert(name, reference);
+                }
+                PROPSTRING_IMPLICIT | PROPSTRING => {
+                    let value = self.stream.byte_string()?;
+                    let value = String::from_utf8_lossy(&value).to_string();
+                    let reference = self.explicit_reference(record_type == PROPSTRING)?;
+                    self.file.prop_strings.insert(value, reference);
+                }
+                LAYERNAME | LAYERNAME_TEXT => {
+                    let name = self.stream.string()?;
+                    let layers = self.interval()?;
+                    let datatypes = self.interval()?;
+                    // Only names of single layers are supported.
+                    if let (Some(layer), Some(datatype)) = (layers, datatypes) {
+                        self.file.layer_names.push((name, layer, datatype));
+                    }
+                }
+                CELL_REF | CELL => {
+                    let name = self.name_ref(record_type == CELL_REF)?;
+                    self.modal = Modal::default();
+                    self.file.cells.push(CellData {
+                        name,
+                        properties: vec![],
+                        elements: vec![],
+                    });
+                }
+                XYABSOLUTE => self.modal.relative = false,
+                XYRELATIVE => self.modal.relative = true,
+                PLACEMENT | PLACEMENT_MAG_ANGLE => self.placement(record_type)?,
+                TEXT => self.text()?,
+                RECTANGLE => {
+                    let info = self.stream.byte()?;
+                    let (layer, datatype) = self.layer_datatype(info)?;
+                    let is_square = info & 0x80 != 0;
+                    if info & 0x40 != 0 {
+                        self.modal.geometry_w = Some(self.stream.uint_coord()?);
+                    }
+                    let w = modal(&self.modal.geometry_w, "geometry-w")?;
+                    if info & 0x20 != 0 {
+                        if is_square {
+                            return malformed("square with height");
+                        }
+                        self.modal.geometry_h = Some(self.stream.uint_coord()?);
+                    }
+                    let h = if is_square {
+                        self.modal.geometry_h = Some(w);
+                        w
+                    } else {
+                        modal(&self.modal.geometry_h, "geometry-h")?
+                    };
+                    let rect = Rect::new((0, 0), (w, h));
+                    self.push_shape(info, rect.into(), layer, datatype)?;
+                }
+                POLYGON => {
+                    let info = self.stream.byte()?;
+                    let (layer, datatype) = self.layer_datatype(info)?;
+                    if info & 0x20 != 0 {
+                        self.modal.polygon_points = Some(self.stream.point_list(true)?);
+                    }
+                    let deltas = modal(&self.modal.polygon_points, "polygon-point-list")?;
+                    let mut points = vec![Point::new(0, 0)];
+                    points.extend(deltas.iter().map(|v| Point::new(v.x, v.y)));
+                    self.push_shape(info, polygon(points), layer, datatype)?;
+                }
+                PATH => self.path()?,
+                TRAPEZOID | TRAPEZOID_A | TRAPEZOID_B => {
+                    let info = self.stream.byte()?;
+                    let (layer, datatype) = self.layer_datatype(info)?;
+                    let (w, h) = self.width_height(info)?;
+                    let delta_a = if record_type != TRAPEZOID_B {
+                        coord(self.stream.sint()?)?
+                    } else {
+                        0
+                    };
+                    let delta_b = if record_type != TRAPEZOID_A {
+                        coord(self.stream.sint()?)?
+                    } else {
+                        0
+                    };
+                    let vertical = info & 0x80 != 0;
+                    let points = trapezoid(vertical, w, h, delta_a, delta_b);
+                    self.push_shape(info, polygon(points), layer, datatype)?;
+                }
+                CTRAPEZOID => {
+                    let info = self.stream.byte()?;
+                    let (layer, datatype) = self.layer_datatype(info)?;
+                    if info & 0x80 != 0 {
+                        self.modal.ctrapezoid_type = Some(self.stream.uint()?);
+                    }
+                    if info & 0x40 != 0 {
+                        self.modal.geometry_w = Some(self.stream.uint_coord()?);
+                    }
+                    if info & 0x20 != 0 {
+                        self.modal.geometry_h = Some(self.stream.uint_coord()?);
+                    }
+                    let ctrapezoid_type = modal(&self.modal.ctrapezoid_type, "ctrapezoid-type")?;
+                    let points = ctrapezoid(
+                        ctrapezoid_type,
+                        self.modal.geometry_w,
+                        self.modal.geometry_h,
+                    )?;
+                    self.push_shape(info, polygon(points), layer, datatype)?;
+                }
+                CIRCLE => {
+                    let info = self.stream.byte()?;
+                    let (layer, datatype) = self.layer_datatype(info)?;
+                    if info & 0x20 != 0 {
+                        self.modal.circle_radius = Some(self.stream.uint_coord()?);
+                    }
+                    let radius = modal(&self.modal.circle_radius, "circle-radius")?;
+                    self.push_shape(info, circle(radius).into(), layer, datatype)?;
+                }
+                PROPERTY => {
+                    let property = self.property()?;
+                    self.attach_property(property)?;
+                }
+                PROPERTY_REPEAT => {
+                    let property = modal(&self.modal.last_property, "last-property")?;
+                    self.attach_property(property)?;
+                }
+                XNAME_IMPLICIT | XNAME => {
+                    self.stream.uint()?;
+                    self.stream.byte_string()?;
+                    self.explicit_reference(record_type == XNAME)?;
+                }
+                XELEMENT => {
+                    self.stream.uint()?;
+                    self.stream.byte_string()?;
+                }
+                XGEOMETRY => {
+                    // Extension geometries are not interpreted.
+                    let info = self.stream.byte()?;
+                    self.stream.uint()?;
+                    self.layer_datatype(info)?;
+                    self.stream.byte_string()?;
+                    self.xy(info & 0x10 != 0, |m| &mut m.geometry_x)?;
+                    self.xy(info & 0x08 != 0, |m| &mut m.geometry_y)?;
+                    self.repetition(info & 0x04 != 0)?;
+                    log::warn!("Ignoring XGEOMETRY record.");
+                }
+                CBLOCK => {
+                    let compression_type = self.stream.uint()?;
+                    let uncompressed_size = self.stream.uint()?;
+                    let compressed_size = usize::try_from(self.stream.uint()?)
+                        .map_err(|_| OasisError::Malformed("CBLOCK size out of range".into()))?;
+                    let compressed = self.stream.bytes(compressed_size)?;
+                    if compression_type != 0 {
+                        return Err(OasisError::Unsupported(format!(
+                            "compression type {}",
+                            compression_type
+                        )));
+                    }
+                    // Don't trust the size for preallocation and stop decompressing
+                    // as soon as the data gets larger than announced.
+                    let mut data = Vec::new();
+                    DeflateDecoder::new(compressed.as_slice())
+                        .take(uncompressed_size.saturating_add(1))
+                        .read_to_end(&mut data)?;
+                    if data.len() as u64 != uncompressed_size {
+                        return malformed("wrong size of CBLOCK");
+                    }
+                    self.stream.buffers.push((data, 0));
+                }
+                _ => return malformed(&format!("unknown record type {}", record_type)),
+            }
+        }
+    }
+
+    fn explicit_reference(&mut self, is_explicit: bool) -> Result<Option<u64>, OasisError> {
+        Ok(if is_explicit {
+            Some(self.stream.uint()?)
+        } else {
+            None
+        })
+    }
+
+    /// Read an interval of layer numbers or datatypes. Returns the value if the interval contains
+    /// a single value only.
+    fn interval(&mut self) -> Result<Option<UInt>, OasisError> {
+        Ok(match self.stream.uint()? {
+            0 => None,
+            1 | 2 => {
+                self.stream.uint()?;
+                None
+            }
+            3 => Some(self.stream.uint()? as UInt),
+            4 => {
+                let (lower, upper) = (self.stream.uint()?, self.stream.uint()?);
+                (lower == upper).then_some(lower as UInt)
+            }
+            _ => return malformed("invalid interval type"),
+        })
+    }
+
+    fn placement(&mut self, record_type: u64) -> Result<(), OasisError> {
+        let info = self.stream.byte()?;
+        if info & 0x80 != 0 {
+            self.modal.placement_cell = Some(self.name_ref(info & 0x40 != 0)?);
+        }
+        let cell = modal(&self.modal.placement_cell, "placement-cell")?;
+        let mirror = info & 0x01 != 0;
+        let (magnification, angle) = if record_type == PLACEMENT {
+            (1.0, ((info >> 1) & 3) as f64 * 90.0)
+        } else {
+            let magnification = if info & 0x04 != 0 {
+                self.stream.real()?
+            } else {
+                1.0
+            };
+            let angle = if info & 0x02 != 0 {
+                self.stream.real()?
+            } else {
+                0.0
+            };
+            (magnification, angle)
+        };
+        let x = self.xy(info & 0x20 != 0, |m| &mut m.placement_x)?;
+        let y = self.xy(info & 0x10 != 0, |m| &mut m.placement_y)?;
+        let repetition = self.repetition(info & 0x08 != 0)?;
+
+        let transform = simple_transform(mirror, magnification, angle, Vector::new(x, y))?;
+        self.push_element(ElementKind::Placement { cell, transform }, repetition)
+    }
+
+    fn text(&mut self) -> Result<(), OasisError> {
+        let info = self.stream.byte()?;
+        if info & 0x40 != 0 {
+            self.modal.text_string = Some(self.name_ref(info & 0x20 != 0)?);
+        }
+        if info & 0x01 != 0 {
+            self.modal.textlayer = Some(self.stream.uint()? as UInt);
+        }
+        if info & 0x02 != 0 {
+            self.modal.texttype = Some(self.stream.uint()? as UInt);
+        }
+        let string = modal(&self.modal.text_string, "text-string")?;
+        let layer = modal(&self.modal.textlayer, "textlayer")?;
+        let texttype = modal(&self.modal.texttype, "texttype")?;
+        let x = self.xy(info & 0x10 != 0, |m| &mut m.text_x)?;
+        let y = self.xy(info & 0x08 != 0, |m| &mut m.text_y)?;
+        let repetition = self.repetition(info & 0x04 != 0)?;
+        self.push_element(
+            ElementKind::Text {
+                layer,
+                texttype,
+                string,
+                location: Point::new(x, y),
+            },
+            repetition,
+        )
+    }
+
+    fn path(&mut self) -> Result<(), OasisError> {
+        let info = self.stream.byte()?;
+        let (layer, datatype) = self.layer_datatype(info)?;
+        if info & 0x40 != 0 {
+            self.modal.path_halfwidth = Some(self.stream.uint_coord()?);
+        }
+        let halfwidth = modal(&self.modal.path_halfwidth, "path-halfwidth")?;
+        if info & 0x80 != 0 {
+            let scheme = self.stream.uint()?;
+            let extension =
+                |stream: &mut ByteStream, scheme: u64| -> Result<Option<i32>, OasisError> {
+                    Ok(match scheme {
+                        0 => None,
+                        1 => Some(0),
+                        2 => Some(halfwidth),
+                        _ => Some(coord(stream.sint()?)?),
+                    })
+                };
+            if let Some(e) = extension(&mut self.stream, (scheme >> 2) & 3)? {
+                self.modal.path_start_extension = Some(e);
+            }
+            if let Some(e) = extension(&mut self.stream, scheme & 3)? {
+                self.modal.path_end_extension = Some(e);
+            }
+        }
+        if info & 0x20 != 0 {
+            self.modal.path_points = Some(self.stream.point_list(false)?);
+        }
+        let start_extension = modal(&self.modal.path_start_extension, "path-start-extension")?;
+        let end_extension = modal(&self.modal.path_end_extension, "path-end-extension")?;
+        let deltas = modal(&self.modal.path_points, "path-point-list")?;
+        let mut points = vec![Point::new(0, 0)];
+        points.extend(deltas.iter().map(|v| Point::new(v.x, v.y)));
+        let width = 2 * halfwidth;
+        let path = if start_extension == 0 && end_extension == 0 {
+            Path::new(points, width)
+        } else {
+            Path::new_extended(points, width, start_extension, end_extension)
+        };
+        self.push_shape(info, path.into(), layer, datatype)
+    }
+
+    fn property(&mut self) -> Result<Property, OasisError> {
+        let info = self.stream.byte()?;
+        let name = if info & 0x04 != 0 {
+            self.name_ref(info & 0x02 != 0)?
+        } else {
+            modal(&self.modal.last_property, "propname")?.name
+        };
+        let is_standard = info & 0x01 != 0;
+        let values = if info & 0x08 != 0 {
+            modal(&self.modal.last_property, "last-value-list")?.values
+        } else {
+            let mut count = (info >> 4) as u64;
+            if count == 15 {
+                count = self.stream.uint()?;
+            }
+            (0..count)
+                .map(|_| self.property_value())
+                .collect::<Result<Vec<_>, _>>()?
+        };
+        let property = Property {
+            name,
+            is_standard,
+            values,
+        };
+        self.modal.last_property = Some(property.clone());
+        Ok(property)
+    }
+
+    fn property_value(&mut self) -> Result<PropValue, OasisError> {
+        let value_type = self.stream.uint()?;
+        Ok(match value_type {
+            0..=7 => PropValue::Real(self.stream.real_of_type(value_type)?),
+            8 => PropValue::UInt(self.stream.uint()?),
+            9 => PropValue::SInt(self.stream.sint()?),
+            10..=12 => PropValue::String(self.stream.byte_string()?),
+            13..=15 => PropValue::StringRef(self.stream.uint()?),
+            _ => return malformed("invalid property value type"),
+        })
+    }
+
+    /// Attach a property to the last element, the current cell or the file.
+    fn attach_property(&mut self, property: Property) -> Result<(), OasisError> {
+        match self.file.cells.last_mut() {
+            Some(cell) => match cell.elements.last_mut() {
+                Some(element) => element.properties.push(property),
+                None => cell.properties.push(property),
+            },
+            None => self.file.properties.push(property),
+        }
+        Ok(())
+    }
+}
+
+/// Create a rectangle if possible, otherwise a simple polygon.
+fn polygon(mut points: Vec<Point<i32>>) -> Geometry<i32> {
+    points.dedup();
+    if points.len() > 1 && points.first() == points.last() {
+        points.pop();
+    }
+    if let [a, b, c, d] = points.as_slice() {
+        let horizontal_first = a.y == b.y && b.x == c.x && c.y == d.y && d.x == a.x;
+        let vertical_first = a.x == b.x && b.y == c.y && c.x == d.x && d.y == a.y;
+        if horizontal_first || vertical_first {
+            return Rect::new(*a, *c).into();
+        }
+    }
+    SimplePolygon::new(points).into()
+}
+
+/// Vertices of a `TRAPEZOID`.
+fn trapezoid(vertical: bool, w: i32, h: i32, delta_a: i32, delta_b: i32) -> Vec<Point<i32>> {
+    if vertical {
+        vec![
+            Point::new(0, delta_a.max(0)),
+            Point::new(0, h + delta_b.min(0)),
+            Point::new(w, h - delta_b.max(0)),
+            Point::new(w, -delta_a.min(0)),
+        ]
+    } else {
+        vec![
+            Point::new(delta_a.max(0), h),
+            Point::new(w + delta_b.min(0), h),
+            Point::new(w - delta_b.max(0), 0),
+            Point::new(-delta_a.min(0), 0),
+        ]
+    }
+}
+
+/// Vertices of a `CTRAPEZOID` of the given type.
+fn ctrapezoid(
+    ctrapezoid_type: u64,
+    w: Option<i32>,
+    h: Option<i32>,
+) -> Result<Vec<Point<i32>>, OasisError> {
+    let w_or_h = |w: Option<i32>, h: Option<i32>| {
+        w.or(h)
+            .ok_or_else(|| OasisError::Malformed("missing size of ctrapezoid".into()))
+    };
+    // Some types define width or height implicitly.
+    let (w, h) = match ctrapezoid_type {
+        16..=19 | 25 => {
+            let w = w_or_h(w, h)?;
+            (w, w)
+        }
+        20 | 21 => {
+            let h = w_or_h(h, w)?;
+            (2 * h, h)
+        }
+        22 | 23 => {
+            let w = w_or_h(w, h)?;
+            (w, 2 * w)
+        }
+        _ => (modal(&w, "geometry-w")?, modal(&h, "geometry-h")?),
+    };
+    let points: &[(i32, i32)] = match ctrapezoid_type {
+        0 => &[(0, 0), (0, h), (w - h, h), (w, 0)],
+        1 => &[(0, 0), (0, h), (w, h), (w - h, 0)],
+        2 => &[(0, 0), (h, h), (w, h), (w, 0)],
+        3 => &[(h, 0), (0, h), (w, h), (w, 0)],
+        4 => &[(0, 0), (h, h), (w - h, h), (w, 0)],
+        5 => &[(h, 0), (0, h), (w, h), (w - h, 0)],
+        6 => &[(0, 0), (h, h), (w, h), (w - h, 0)],
+        7 => &[(h, 0), (0, h), (w - h, h), (w, 0)],
+        8 => &[(0, 0), (0, h), (w, h - w), (w, 0)],
+        9 => &[(0, 0), (0, h), (w, h), (w, w)],
+        10 => &[(0, 0), (0, h - w), (w, h), (w, 0)],
+        11 => &[(0, w), (0, h), (w, h), (w, 0)],
+        12 => &[(0, 0), (0, h), (w, h - w), (w, w)],
+        13 => &[(0, w), (0, h - w), (w, h), (w, 0)],
+        14 => &[(0, 0), (0, h - w), (w, h), (w, w)],
+        15 => &[(0, w), (0, h), (w, h - w), (w, 0)],
+        16 => &[(0, 0), (0, w), (w, 0)],
+        17 => &[(0, 0), (0, w), (w, w)],
+        18 => &[(0, 0), (w, w), (w, 0)],
+        19 => &[(0, w), (w, w), (w, 0)],
+        20 => &[(0, 0), (h, h), (2 * h, 0)],
+        21 => &[(0, h), (2 * h, h), (h, 0)],
+        22 => &[(0, 0), (0, 2 * w), (w, w)],
+        23 => &[(w, 0), (0, w), (w, 2 * w)],
+        24 | 25 => &[(0, 0), (0, h), (w, h), (w, 0)],
+        _ => return malformed("invalid ctrapezoid type"),
+    };
+    Ok(points.iter().map(|&(x, y)| Point::new(x, y)).collect())
+}
+
+/// Approximate a circle around the origin by a polygon.
+fn circle(radius: i32) -> SimplePolygon<i32> {
+    let points = (0..CIRCLE_VERTICES)
+        .map(|i| {
+            let phi = 2.0 * std::f64::consts::PI * i as f64 / CIRCLE_VERTICES as f64;
+            let r = radius as f64;
+            Point::new(
+                (r * phi.cos()).round() as i32,
+                (r * phi.sin()).round() as i32,
+            )
+        })
+        .collect();
+    SimplePolygon::new(points)
+}
+
+/// Create a transformation from the parameters of a placement.
+fn simple_transform(
+    mirror: bool,
+    magnification: f64,
+    angle: f64,
+    displacement: Vector<i32>,
+) -> Result<SimpleTransform<i32>, OasisError> {
+    let quarter_turns = angle / 90.0;
+    if (quarter_turns - quarter_turns.round()).abs() > 1e-9 {
+        return Err(OasisError::UnsupportedTransform);
+    }
+    let rotation = match (quarter_turns.round() as i64).rem_euclid(4) {
+        0 => Angle::R0,
+        1 => Angle::R90,
+        2 => Angle::R180,
+        _ => Angle::R270,
+    };
+    if magnification.fract() != 0.0 || magnification < 1.0 {
+        return Err(OasisError::UnsupportedTransform);
+    }
+    Ok(SimpleTransform::new(
+        mirror,
+        rotation,
+        magnification as i32,
+        displacement,
+    ))
+}
+
+/// Read OASIS streams.
+#[derive(Debug, Clone, Default)]
+pub struct OasisReader {}
+
+impl OasisReader {
+    /// Create a reader with default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl LayoutStreamReader for OasisReader {
+    type Error = OasisError;
+
+    fn read_layout<R: Read, L: LayoutEdit<Coord = i32>>(
+        &self,
+        reader: &mut R,
+        layout: &mut L,
+    ) -> Result<(), Self::Error> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        let file = Parser::parse(data)?;
+        build_layout(&file, layout)
+    }
+}
+
+/// Convert OASIS properties into key-value pairs.
+fn convert_properties(
+    file: &OasisFile,
+    properties: &[Property],
+) -> Result<Vec<(String, PropertyValue)>, OasisError> {
+    let mut result = Vec::new();
+    for property in properties {
+        let name = file.prop_names.resolve(&property.name)?;
+        let values = property
+            .values
+            .iter()
+            .map(|v| {
+                Ok(match v {
+                    PropValue::Real(v) => PropertyValue::Float(*v),
+                    PropValue::UInt(v) => match u32::try_from(*v) {
+                        Ok(v) => PropertyValue::UInt(v),
+                        Err(_) => PropertyValue::String(v.to_string().into()),
+                    },
+                    PropValue::SInt(v) => match i32::try_from(*v) {
+                        Ok(v) => PropertyValue::SInt(v),
+                        Err(_) => PropertyValue::String(v.to_string().into()),
+                    },
+                    PropValue::String(bytes) => match String::from_utf8(bytes.clone()) {
+                        Ok(s) => PropertyValue::String(s.into()),
+                        Err(_) => PropertyValue::Bytes(bytes.clone()),
+                    },
+                    PropValue::StringRef(r) => {
+                        let s = file.prop_strings.resolve(&NameRef::Ref(*r))?;
+                        PropertyValue::String(s.to_string().into())
+                    }
+                })
+            })
+            .collect::<Result<Vec<_>, OasisError>>()?;
+
+        if property.is_standard {
+            // GDSII properties are stored with the attribute number as key.
+            if name == S_GDS_PROPERTY {
+                if let [PropertyValue::UInt(attribute), value] = values.as_slice() {
+                    result.push((attribute.to_string(), value.clone()));
+                }
+            }
+            continue;
+        }
+
+        let value = match values.as_slice() {
+            [value] => value.clone(),
+            values => {
+                // Multiple values are joined into a single string.
+                let strings: Vec<String> = values
+                    .iter()
+                    .map(|v| match v {
+                        PropertyValue::String(s) => s.to_string(),
+                        PropertyValue::UInt(v) => v.to_string(),
+                        PropertyValue::SInt(v) => v.to_string(),
+                        PropertyValue::Float(v) => v.to_string(),
+                        PropertyValue::Bytes(b) => String::from_utf8_lossy(b).to_string(),
+                    })
+                    .collect();
+                PropertyValue::String(strings.join(" ").into())
+            }
+        };
+        result.push((name.to_string(), value));
+    }
+    Ok(result)
+}
+
+fn build_layout<L: LayoutEdit<Coord = i32>>(
+    file: &OasisFile,
+    layout: &mut L,
+) -> Result<(), OasisError> {
+    // The unit is given in grid steps per micron.
+    if file.unit >= 1.0 {
+        layout.set_dbu(file.unit.round() as i32);
+    } else {
+        log::warn!("Database unit is larger than one micron.");
+    }
+
+    for (key, value) in convert_properties(file, &file.properties)? {
+        layout.set_chip_property(key.into(), value);
+    }
+
+    for (name, index, datatype) in &file.layer_names {
+        let layer = layout
+            .find_layer(*index, *datatype)
+            .unwrap_or_else(|| layout.create_layer(*index, *datatype));
+        layout.set_layer_name(&layer, Some(name.clone().into()));
+    }
+
+    // Create the defined cells first to detect name collisions.
+    let mut cells: HashMap<String, L::CellId> = HashMap::new();
+    for cell_data in &file.cells {
+        let name = file.cell_names.resolve(&cell_data.name)?;
+        if cells.contains_key(name) || layout.cell_by_name(name).is_some() {
+            return Err(OasisError::CellNameCollision(name.to_string()));
+        }
+        let cell = layout.create_cell(name.to_string().into());
+        cells.insert(name.to_string(), cell);
+    }
+
+    for cell_data in &file.cells {
+        let cell = cells[file.cell_names.resolve(&cell_data.name)?].clone();
+        for (key, value) in convert_properties(file, &cell_data.properties)? {
+            layout.set_cell_property(&cell, key.into(), value);
+        }
+
+        for element in &cell_data.elements {
+            let properties = convert_properties(file, &element.properties)?;
+            // Placements repeated on a regular grid become a single array instance.
+            let array = match &element.kind {
+                ElementKind::Placement { .. } => {
+                    InstanceArray::from_displacements(&element.repetition)
+                }
+                _ => None,
+            };
+            let offsets = if array.is_some() {
+                &element.repetition[..1]
+            } else {
+                &element.repetition[..]
+            };
+            for offset in offsets {
+                let offset = *offset;
+                match &element.kind {
+                    ElementKind::Placement {
+                        cell: name,
+                        transform,
+                    } => {
+                        let name = file.cell_names.resolve(name)?;
+                        let template = match cells.get(name) {
+                            Some(template) => template.clone(),
+                            None => {
+                                // Cells may be referenced without being defined.
+                                let template = layout.cell_by_name(name).unwrap_or_else(|| {
+                                    log::warn!("Cell '{}' is referenced but not defined.", name);
+                                    layout.create_cell(name.to_string().into())
+                                });
+                                cells.insert(name.to_string(), template.clone());
+                                template
+                            }
+                        };
+                        if layout.cell_depends_on(&template, &cell) {
+                            return malformed(&format!("cell '{}' is placed recursively", name));
+                        }
+                        let mut tf = *transform;
+                        tf.displacement = tf.displacement + offset;
+                        let inst = layout.create_cell_instance(&cell, &template, None);
+                        layout.set_transform(&inst, tf);
+                        if array.is_some() {
+                            layout.set_cell_instance_array(&inst, array);
+                        }
+                        for (key, value) in &properties {
+                            layout.set_cell_instance_property(
+                                &inst,
+                                key.clone().into(),
+                                value.clone(),
+                            );
+                        }
+                    }
+                    kind => {
+                        let (layer, datatype, geometry) = match kind {
+                            ElementKind::Shape {
+                                layer,
+                                datatype,
+                                geometry,
+                            } => (*layer, *datatype, geometry.transform(|p| p + offset)),
+                            ElementKind::Text {
+                                layer,
+                                texttype,
+                                string,
+                                location,
+                            } => {
+                                let text = file.text_strings.resolve(string)?.to_string();
+                                (
+                                    *layer,
+                                    *texttype,
+                                    Text::new(text, *location + offset).into(),
+                                )
+                            }
+                            ElementKind::Placement { .. } => unreachable!(),
+                        };
+                        let layer = layout
+                            .find_layer(layer, datatype)
+                            .unwrap_or_else(|| layout.create_layer(layer, datatype));
+                        let shape = layout.insert_shape(&cell, &layer, geometry);
+                        for (key, value) in &properties {
+                            layout.set_shape_property(&shape, key.clone().into(), value.clone());
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Writer.
+
+/// Write OASIS streams.
+#[derive(Debug, Clone)]
+pub struct OasisWriter {
+    compress: bool,
+}
+
+impl Default for OasisWriter {
+    fn default() -> Self {
+        Self { compress: true }
+    }
+}
+
+impl OasisWriter {
+    /// Create a writer with default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enable or disable `CBLOCK` compression of the cell content. Enabled by default.
+    pub fn compress(mut self, compress: bool) -> Self {
+        self.compress = compress;
+        self
+    }
+}
+
+/// Encoder for the basic OASIS data types.
+#[derive(Default)]
+struct Encoder {
+    buf: Vec<u8>,
+}
+
+impl Encoder {
+    fn byte(&mut self, b: u8) {
+        self.buf.push(b);
+    }
+
+    fn uint(&mut self, mut value: u64) {
+        loop {
+            let b = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                self.buf.push(b);
+                return;
+            }
+            self.buf.push(b | 0x80);
+        }
+    }
+
+    fn sint(&mut self, value: i64) {
+        self.uint((value.unsigned_abs() << 1) | (value < 0) as u64);
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.uint(bytes.len() as u64);
+        self.buf.extend_from_slice(bytes);
+    }
+
+    fn string(&mut self, s: &str) {
+        self.bytes(s.as_bytes())
+    }
+
+    fn real(&mut self, value: f64) {
+        if value.fract() == 0.0 && value.abs() < u32::MAX as f64 {
+            self.uint(if value < 0.0 { 1 } else { 0 });
+            self.uint(value.abs() as u64);
+        } else {
+            self.uint(7);
+            self.buf.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    fn g_delta(&mut self, v: Vector<i32>) {
+        let x = v.x as i64;
+        self.uint((x.unsigned_abs() << 2) | (((x < 0) as u64) << 1) | 1);
+        self.sint(v.y as i64);
+    }
+
+    /// Write a point list as general deltas relative to the first point.
+    fn point_list(&mut self, points: &[Point<i32>]) {
+        self.uint(4);
+        self.uint(points.len() as u64 - 1);
+        for w in points.windows(2) {
+            self.g_delta(w[1] - w[0]);
+        }
+    }
+
+    fn property(&mut self, key: &str, value: &PropertyValue) {
+        // GDSII properties have numeric keys.
+        let gds_attribute: Option<u16> = key.parse().ok();
+        let (name, is_standard) = match gds_attribute {
+            Some(_) => (S_GDS_PROPERTY, true),
+            None => (key, false),
+        };
+        let num_values = if is_standard { 2 } else { 1 };
+        self.uint(PROPERTY);
+        // Flags: number of values, explicit name given as string, standard property.
+        self.byte((num_values << 4) | 0x04 | is_standard as u8);
+        self.string(name);
+        if let Some(attribute) = gds_attribute {
+            self.uint(8);
+            self.uint(attribute as u64);
+        }
+        match value {
+            PropertyValue::String(s) => {
+                self.uint(11);
+                self.string(s);
+            }
+            PropertyValue::Bytes(b) => {
+                self.uint(11);
+                self.bytes(b);
+            }
+            PropertyValue::UInt(v) => {
+                self.uint(8);
+                self.uint(*v as u64);
+            }
+            PropertyValue::SInt(v) => {
+                self.uint(9);
+                self.sint(*v as i64);
+            }
+            PropertyValue::Float(v) => {
+                self.uint(7);
+                self.buf.extend_from_slice(&v.to_le_bytes());
+            }
+        }
+    }
+
+    /// Write a shape. The `X`, `Y`, `D` and `L` flags are always set, no modal variables are used.
+    fn shape(
+        &mut self,
+        layer: UInt,
+        datatype: UInt,
+        geometry: &Geometry<i32>,
+    ) -> Result<(), OasisError> {
+        let layer_datatype = |e: &mut Self| {
+            e.uint(layer as u64);
+            e.uint(datatype as u64);
+        };
+        let xy = |e: &mut Self, p: Point<i32>| {
+            e.sint(p.x as i64);
+            e.sint(p.y as i64);
+        };
+        match geometry {
+            Geometry::Rect(r) => {
+                self.uint(RECTANGLE);
+                self.byte(0b0111_1011);
+                layer_datatype(self);
+                self.uint(r.width() as u64);
+                self.uint(r.height() as u64);
+                xy(self, r.lower_left());
+            }
+            Geometry::SimplePolygon(p) => {
+                let points: Vec<_> = p.iter().copied().collect();
+                self.polygon(layer, datatype, &points)?;
+            }
+            Geometry::Polygon(p) if p.interiors.is_empty() => {
+                let points: Vec<_> = p.exterior.iter().copied().collect();
+                self.polygon(layer, datatype, &points)?;
+            }
+            Geometry::Path(p) => {
+                let points: Vec<_> = p.points.iter().copied().collect();
+                let (start, end) = match p.path_type {
+                    PathEndType::Flat => (0, 0),
+                    PathEndType::Extended(start, end) => (start, end),
+                    _ => return Err(OasisError::UnsupportedGeometry),
+                };
+                self.path(layer, datatype, p.width, start, end, &points)?;
+            }
+            // Edges are written as paths of zero width.
+            Geometry::Edge(e) => self.path(layer, datatype, 0, 0, 0, &[e.start, e.end])?,
+            Geometry::Text(t) => {
+                self.uint(TEXT);
+                self.byte(0b0101_1011);
+                self.string(t.text());
+                self.uint(layer as u64);
+                self.uint(datatype as u64);
+                xy(self, t.location());
+            }
+            _ => return Err(OasisError::UnsupportedGeometry),
+        }
+        Ok(())
+    }
+
+    fn polygon(
+        &mut self,
+        layer: UInt,
+        datatype: UInt,
+        points: &[Point<i32>],
+    ) -> Result<(), OasisError> {
+        if points.len() < 3 {
+            return Err(OasisError::UnsupportedGeometry);
+        }
+        self.uint(POLYGON);
+        self.byte(0b0011_1011);
+        self.uint(layer as u64);
+        self.uint(datatype as u64);
+        self.point_list(points);
+        self.sint(points[0].x as i64);
+        self.sint(points[0].y as i64);
+        Ok(())
+    }
+
+    fn path(
+        &mut self,
+        layer: UInt,
+        datatype: UInt,
+        width: i32,
+        start_extension: i32,
+        end_extension: i32,
+        points: &[Point<i32>],
+    ) -> Result<(), OasisError> {
+        // OASIS stores the half width.
+        if width % 2 != 0 || points.len() < 2 {
+            return Err(OasisError::UnsupportedGeometry);
+        }
+        self.uint(PATH);
+        self.byte(0b1111_1011);
+        self.uint(layer as u64);
+        self.uint(datatype as u64);
+        self.uint((width / 2) as u64);
+        // Explicit extensions.
+        self.uint(0b1111);
+        self.sint(start_extension as i64);
+        self.sint(end_extension as i64);
+        self.point_list(points);
+        self.sint(points[0].x as i64);
+        self.sint(points[0].y as i64);
+        Ok(())
+    }
+
+    fn placement(
+        &mut self,
+        cell_reference: u64,
+        tf: &SimpleTransform<i32>,
+        array: Option<InstanceArray<i32>>,
+    ) {
+        let quarter_turns = match tf.rotation {
+            Angle::R0 => 0,
+            Angle::R90 => 1,
+            Angle::R180 => 2,
+            Angle::R270 => 3,
+        };
+        let flip = tf.mirror as u8;
+        let array = array.filter(|a| a.num_elements() > 1);
+        let repetition = if array.is_some() { 0b0000_1000 } else { 0 };
+        if tf.magnification == 1 {
+            self.uint(PLACEMENT);
+            self.byte(0b1011_0000 | repetition | (quarter_turns << 1) | flip);
+            self.uint(cell_reference);
+        } else {
+            self.uint(PLACEMENT_MAG_ANGLE);
+            self.byte(0b1011_0110 | repetition | flip);
+            self.uint(cell_reference);
+            self.real(tf.magnification as f64);
+            self.real(quarter_turns as f64 * 90.0);
+        }
+        self.sint(tf.displacement.x as i64);
+        self.sint(tf.displacement.y as i64);
+        if let Some(array) = array {
+            self.repetition(&array);
+        }
+    }
+
+    /// Write an instance array as an arbitrary grid. The array must have more than one element.
+    fn repetition(&mut self, array: &InstanceArray<i32>) {
+        match (array.columns, array.rows) {
+            (1, n) => {
+                self.uint(9);
+                self.uint(n as u64 - 2);
+                self.g_delta(array.row_pitch);
+            }
+            (n, 1) => {
+                self.uint(9);
+                self.uint(n as u64 - 2);
+                self.g_delta(array.column_pitch);
+            }
+            (n, m) => {
+                self.uint(8);
+                self.uint(n as u64 - 2);
+                self.uint(m as u64 - 2);
+                self.g_delta(array.column_pitch);
+                self.g_delta(array.row_pitch);
+            }
+        }
+    }
+}
+
+impl LayoutStreamWriter for OasisWriter {
+    type Error = OasisError;
+
+    fn write_layout<W: Write, L: LayoutBase<Coord = i32>>(
+        &self,
+        writer: &mut W,
+        layout: &L,
+    ) -> Result<(), Self::Error> {
+        let mut e = Encoder::default();
+        e.buf.extend_from_slice(MAGIC);
+        e.uint(START);
+        e.string("1.0");
+        e.real(layout.dbu().max(1) as f64);
+        // Table offsets are stored here and are all zero.
+        e.uint(0);
+        (0..12).for_each(|_| e.uint(0));
+
+        // Cell names with implicit reference numbers.
+        let cells: Vec<_> = layout.each_cell_bottom_to_top().collect();
+        let cell_references: HashMap<L::CellId, u64> = cells
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (c.clone(), i as u64))
+            .collect();
+        for cell in &cells {
+            e.uint(CELLNAME_IMPLICIT);
+            e.string(&layout.cell_name(cell).to_string());
+        }
+
+        for layer in layout.each_layer() {
+            let info = layout.layer_info(&layer);
+            if let Some(name) = info.name {
+                e.uint(LAYERNAME);
+                e.string(&name.to_string());
+                // Exact intervals.
+                e.uint(3);
+                e.uint(info.index as u64);
+                e.uint(3);
+                e.uint(info.datatype as u64);
+            }
+        }
+
+        let mut chip_properties = Vec::new();
+        layout.for_each_chip_property(|key, value| {
+            chip_properties.push((key.to_string(), value.clone()))
+        });
+        for (key, value) in &chip_properties {
+            e.property(key, value);
+        }
+
+        for cell in &cells {
+            e.uint(CELL_REF);
+            e.uint(cell_references[cell]);
+
+            let mut content = Encoder::default();
+            let mut cell_properties = Vec::new();
+            layout.for_each_cell_property(cell, |key, value| {
+                cell_properties.push((key.to_string(), value.clone()))
+            });
+            for (key, value) in &cell_properties {
+                content.property(key, value);
+            }
+
+            for inst in layout.each_cell_instance(cell) {
+                let template = layout.template_cell(&inst);
+                content.placement(
+                    cell_references[&template],
+                    &layout.get_transform(&inst),
+                    layout.cell_instance_array(&inst),
+                );
+                layout.for_each_cell_instance_property(&inst, |key, value| {
+                    content.property(&key.to_string(), value)
+                });
+            }
+
+            for layer in layout.each_layer() {
+                let info = layout.layer_info(&layer);
+                for shape in layout.each_shape_id(cell, &layer) {
+                    content.shape(info.index, info.datatype, &layout.shape_geometry(&shape))?;
+                    layout.for_each_shape_property(&shape, |key, value| {
+                        content.property(&key.to_string(), value)
+                    });
+                }
+            }
+
+            if self.compress && !content.buf.is_empty() {
+                let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(&content.buf)?;
+                let compressed = encoder.finish()?;
+                e.uint(CBLOCK);
+                e.uint(0);
+                e.uint(content.buf.len() as u64);
+                e.bytes(&compressed);
+            } else {
+                e.buf.extend_from_slice(&content.buf);
+            }
+        }
+
+        // The END record has a fixed length of 256 bytes.
+        e.uint(END);
+        e.bytes(&[0; 252]);
+        // No validation.
+        e.uint(0);
+
+        writer.write_all(&e.buf)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_integer_encoding() {
+        for value in [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX] {
+            let mut e = Encoder::default();
+            e.uint(value);
+            assert_eq!(ByteStream::new(e.buf).uint().unwrap(), value);
+        }
+        for value in [0, 1, -1, 63, -64, 1000, -100000] {
+            let mut e = Encoder::default();
+            e.sint(value);
+            assert_eq!(ByteStream::new(e.buf).sint().unwrap(), value);
+        }
+        for v in [Vector::new(0, 0), Vector::new(-5, 7), Vector::new(100, -3)] {
+            let mut e = Encoder::default();
+            e.g_delta(v);
+            assert_eq!(ByteStream::new(e.buf).g_delta().unwrap(), v);
+        }
+    }
+
+    #[test]
+    fn test_write_and_read_back() {
+        for compress in [false, true] {
+            let mut chip = Chip::new();
+            chip.set_dbu(1000);
+            let top = chip.create_cell("TOP".into());
+            let sub = chip.create_cell("SUB".into());
+            let layer = chip.create_layer(1, 0);
+            chip.set_layer_name(&layer, Some("metal1".into()));
+            let text_layer = chip.create_layer(2, 5);
+
+            let shapes: Vec<Geometry<i32>> = vec![
+                Rect::new((0, 0), (10, 20)).into(),
+                SimplePolygon::new(vec![(0, 0).into(), (10, 0).into(), (0, 10).into()]).into(),
+                Path::new(vec![(0, 0).into(), (100, 0).into()], 10).into(),
+                Path::new_extended(vec![(0, 0).into(), (100, 50).into()], 10, 1, 2).into(),
+            ];
+            for shape in &shapes {
+                chip.insert_shape(&sub, &layer, shape.clone());
+            }
+            let text = chip.insert_shape(
+                &sub,
+                &text_layer,
+                Text::new("A".into(), (1, 2).into()).into(),
+            );
+            chip.set_shape_property(&text, "7".into(), "net A".into());
+            chip.set_shape_property(&text, "note".into(), PropertyValue::SInt(-3));
+
+            let inst1 = chip.create_cell_instance(&top, &sub, None);
+            let tf1 = SimpleTransform::new(true, Angle::R90, 1, Vector::new(100, -50));
+            chip.set_transform(&inst1, tf1);
+            let inst2 = chip.create_cell_instance(&top, &sub, None);
+            let tf2 = SimpleTransform::new(false, Angle::R180, 3, Vector::new(-7, 8));
+            chip.set_transform(&inst2, tf2);
+
+            let mut buffer = Vec::new();
+            OasisWriter::new()
+                .compress(compress)
+                .write_layout(&mut buffer, &chip)
+                .unwrap();
+
+            let mut restored = Chip::new();
+            OasisReader::new()
+                .read_layout(&mut buffer.as_slice(), &mut restored)
+                .unwrap();
+
+            assert_eq!(restored.dbu(), 1000);
+            let restored_top = restored.cell_by_name("TOP").unwrap();
+            let restored_sub = restored.cell_by_name("SUB").unwrap();
+            let restored_layer = restored.layer_by_name("metal1").unwrap();
+            assert_eq!(restored.layer_info(&restored_layer).index, 1);
+
+            let restored_shapes: Vec<_> = restored
+                .each_shape_id(&restored_sub, &restored_layer)
+                .map(|s| restored.shape_geometry(&s))
+                .collect();
+            assert_eq!(restored_shapes, shapes);
+
+            let restored_text_layer = restored.find_layer(2, 5).unwrap();
+            let restored_text = restored
+                .each_shape_id(&restored_sub, &restored_text_layer)
+                .next()
+                .unwrap();
+            assert_eq!(
+                restored
+                    .get_shape_property(&restored_text, &"7".into())
+                    .and_then(|v| v.get_string()),
+                Some("net A".into())
+            );
+            assert!(matches!(
+                restored.get_shape_property(&restored_text, &"note".into()),
+                Some(PropertyValue::SInt(-3))
+            ));
+
+            let mut transforms: Vec<_> = restored
+                .each_cell_instance(&restored_top)
+                .map(|inst| restored.get_transform(&inst))
+                .collect();
+            transforms.sort_by_key(|tf| tf.magnification);
+            assert_eq!(transforms, vec![tf1, tf2]);
+        }
+    }
+
+    #[test]
+    fn test_read_repetitions_and_modal_variables() {
+        let mut e = Encoder::default();
+        e.buf.extend_from_slice(MAGIC);
+        e.uint(START);
+        e.string("1.0");
+        e.real(1000.0);
+        e.uint(1); // Table offsets are stored in the END record.
+
+        e.uint(CELL);
+        e.string("TOP");
+        // Rectangle with a 3x2 grid repetition.
+        e.uint(RECTANGLE);
+        e.byte(0b0111_1111);
+        e.uint(1);
+        e.uint(0);
+        e.uint(10);
+        e.uint(20);
+        e.sint(0);
+        e.sint(0);
+        e.uint(1); // Repetition type 1.
+        e.uint(1);
+        e.uint(0);
+        e.uint(100);
+        e.uint(200);
+        // Relative coordinates. Reuse layer, datatype and size of the previous rectangle.
+        e.uint(XYRELATIVE);
+        e.uint(RECTANGLE);
+        e.byte(0b0001_1000);
+        e.sint(5);
+        e.sint(-5);
+        // Placement of a cell which is referenced by a number. The name is defined later.
+        e.uint(PLACEMENT);
+        e.byte(0b1111_0000);
+        e.uint(0);
+        e.sint(1);
+        e.sint(1);
+        e.uint(CELLNAME);
+        e.string("SUB");
+        e.uint(0);
+
+        e.uint(END);
+        (0..12).for_each(|_| e.uint(0));
+        e.bytes(&[]);
+        e.uint(0);
+
+        let mut chip = Chip::new();
+        OasisReader::new()
+            .read_layout(&mut e.buf.as_slice(), &mut chip)
+            .unwrap();
+
+        let top = chip.cell_by_name("TOP").unwrap();
+        let layer = chip.find_layer(1, 0).unwrap();
+        let rects: Vec<_> = chip
+            .each_shape_id(&top, &layer)
+            .map(|s| chip.shape_geometry(&s))
+            .collect();
+        assert_eq!(rects.len(), 7);
+        assert!(rects.contains(&Rect::new((5, -5), (15, 15)).into()));
+        assert!(rects.contains(&Rect::new((200, 200), (210, 220)).into()));
+
+        let sub = chip.cell_by_name("SUB").unwrap();
+        let inst = chip.each_cell_instance(&top).next().unwrap();
+        assert_eq!(chip.template_cell(&inst), sub);
+        assert_eq!(chip.get_transform(&inst).displacement, Vector::new(1, 1));
+    }
+
+    #[test]
+    fn test_reject_malformed_sizes() {
+        // Point counts which don't fit into memory or overflow.
+        for count in [u64::MAX, 1 << 40] {
+            let mut e = Encoder::default();
+            e.uint(0);
+            e.uint(count);
+            e.sint(1);
+            assert!(ByteStream::new(e.buf).point_list(true).is_err());
+        }
+
+        // CBLOCK announcing a huge uncompressed size.
+        let mut e = Encoder::default();
+        e.buf.extend_from_slice(MAGIC);
+        e.uint(START);
+        e.string("1.0");
+        e.real(1000.0);
+        e.uint(1);
+        e.uint(CBLOCK);
+        e.uint(0);
+        e.uint(u64::MAX);
+        e.uint(0);
+        let result = OasisReader::new().read_layout(&mut e.buf.as_slice(), &mut Chip::new());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_reject_recursive_placement() {
+        let mut e = Encoder::default();
+        e.buf.extend_from_slice(MAGIC);
+        e.uint(START);
+        e.string("1.0");
+        e.real(1000.0);
+        e.uint(1);
+        for (name, child) in [("A", "B"), ("B", "A")] {
+            e.uint(CELL);
+            e.string(name);
+            e.uint(PLACEMENT);
+            e.byte(0b1000_0000);
+            e.string(child);
+        }
+        e.uint(END);
+        (0..12).for_each(|_| e.uint(0));
+        e.bytes(&[]);
+        e.uint(0);
+
+        let result = OasisReader::new().read_layout(&mut e.buf.as_slice(), &mut Chip::new());
+        assert!(matches!(result, Err(OasisError::Malformed(_))));
+    }
+
+    #[test]
+    fn test_write_and_read_instance_arrays() {
+        let mut chip = Chip::new();
+        let top = chip.create_cell("TOP".into());
+        let sub = chip.create_cell("SUB".into());
+        let grid = InstanceArray::new(4, 3, Vector::new(10, 0), Vector::new(0, 20));
+        let diagonal = InstanceArray::new(3, 1, Vector::new(7, -7), Vector::new(0, 0));
+        for array in [grid, diagonal] {
+            let inst = chip.create_cell_instance(&top, &sub, None);
+            chip.set_transform(&inst, SimpleTransform::translate((5, 5)));
+            chip.set_cell_instance_array(&inst, Some(array));
+        }
+
+        let mut buffer = Vec::new();
+        OasisWriter::new().write_layout(&mut buffer, &chip).unwrap();
+        let mut restored = Chip::new();
+        OasisReader::new()
+            .read_layout(&mut buffer.as_slice(), &mut restored)
+            .unwrap();
+
+        let top = restored.cell_by_name("TOP").unwrap();
+        assert_eq!(restored.num_child_instances(&top), 2);
+        let mut arrays: Vec<_> = restored
+            .each_cell_instance(&top)
+            .map(|inst| {
+                assert_eq!(
+                    restored.get_transform(&inst).displacement,
+                    Vector::new(5, 5)
+                );
+                restored.cell_instance_array(&inst).unwrap()
+            })
+            .collect();
+        arrays.sort_by_key(|a| a.rows);
+        assert_eq!(arrays, vec![diagonal, grid]);
+    }
+
+    #[test]
+    fn test_ctrapezoid() {
+        let points = ctrapezoid(0, Some(10), Some(4)).unwrap();
+        assert_eq!(
+            polygon(points),
+            SimplePolygon::new(vec![
+                (0, 0).into(),
+                (0, 4).into(),
+                (6, 4).into(),
+                (10, 0).into()
+            ])
+            .into()
+        );
+        // Type 24 is a rectangle.
+        let points = ctrapezoid(24, Some(10), Some(4)).unwrap();
+        assert_eq!(polygon(points), Rect::new((0, 0), (10, 4)).into());
+    }
+}
@@ -0,0 +1,397 @@
+// SPDX-FileCopyrightText: 2022 Thomas Kramer
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Export of cells as SVG images for debugging.
+//!
+//! The image shows the shapes of a cell and optionally of its sub cells up to a given depth
+//! of the hierarchy. Each layer gets a color which is derived from the layer number and datatype.
+//! Cell instances are drawn as dashed outlines of their bounding boxes. Instances which are not expanded
+//! are labelled with the name of their template cell. Shapes of selected nets can be highlighted.
+//!
+//! Shapes are grouped by layer. The groups have the class `layer` and an attribute `data-layer`
+//! with the layer number and datatype such that they can be toggled by scripts or style sheets.
+//!
+//! # Example
+//!
+//! ```
+//! use libreda_db::prelude::*;
+//! use libreda_db::layout::io::svg::SvgWriter;
+//!
+//! let mut chip = Chip::new();
+//! let top = chip.create_cell("TOP".into());
+//! let layer = chip.create_layer(1, 0);
+//! chip.insert_shape(&top, &layer, Rect::new((0, 0), (100, 200)).into());
+//!
+//! let mut svg = Vec::new();
+//! SvgWriter::new().depth(2).write_cell(&mut svg, &chip, &top).unwrap();
+//! assert!(String::from_utf8(svg).unwrap().starts_with("<svg"));
+//! ```
+
+use crate::prelude::*;
+use std::collections::HashSet;
+use std::io::Write;
+
+/// Color of highlighted net shapes.
+const HIGHLIGHT_COLOR: &str = "#ff00ff";
+
+/// Render cells into SVG images.
+#[derive(Debug, Clone)]
+pub struct SvgWriter {
+    depth: usize,
+    width: u32,
+    instance_outlines: bool,
+}
+
+impl Default for SvgWriter {
+    fn default() -> Self {
+        Self {
+            depth: 0,
+            width: 1024,
+            instance_outlines: true,
+        }
+    }
+}
+
+impl SvgWriter {
+    /// Create a writer with default settings. By default the hierarchy is not expanded
+    /// and the image is 1024 pixels wide.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the number of hierarchy levels which are expanded.
+    /// With a depth of `0` only the shapes of the cell itself are drawn.
+    pub fn depth(mut self, depth: usize) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// Set the width of the image in pixels. The height is derived from the aspect ratio of the cell.
+    pub fn width(mut self, width: u32) -> Self {
+        self.width = width;
+        self
+    }
+
+    /// Enable or disable drawing the bounding boxes of cell instances. Enabled by default.
+    pub fn instance_outlines(mut self, enable: bool) -> Self {
+        self.instance_outlines = enable;
+        self
+    }
+
+    /// Render the cell into an SVG image.
+    pub fn write_cell<W: Write, L: LayoutBase<Coord = i32>>(
+        &self,
+        writer: &mut W,
+        layout: &L,
+        cell: &L::CellId,
+    ) -> std::io::Result<()> {
+        self.write(writer, layout, cell, &HashSet::new())
+    }
+
+    /// Render the cell into an SVG image and highlight the shapes of the given nets.
+    /// Only shapes which are directly linked to the nets are highlighted, not the shapes
+    /// of the connected sub cells.
+    pub fn write_cell_with_nets<W: Write, LN: L2NBase<Coord = i32>>(
+        &self,
+        writer: &mut W,
+        chip: &LN,
+        cell: &LN::CellId,
+        nets: &[LN::NetId],
+    ) -> std::io::Result<()> {
+        let highlighted = nets
+            .iter()
+            .flat_map(|net| chip.shapes_of_net(net))
+            .collect();
+        self.write(writer, chip, cell, &highlighted)
+    }
+
+    fn write<W: Write, L: LayoutBase<Coord = i32>>(
+        &self,
+        writer: &mut W,
+        layout: &L,
+        cell: &L::CellId,
+        highlighted: &HashSet<L::ShapeId>,
+    ) -> std::io::Result<()> {
+        let bbox = layout
+            .bounding_box(cell)
+            .unwrap_or_else(|| Rect::new((0, 0), (1, 1)));
+        let (ll, ur) = (bbox.lower_left(), bbox.upper_right());
+        let w = (ur.x as i64 - ll.x as i64).max(1);
+        let h = (ur.y as i64 - ll.y as i64).max(1);
+        let margin = w.max(h) / 20;
+        let height = (self.width as i64 * (h + 2 * margin) / (w + 2 * margin)).max(1);
+
+        // The y-axis of SVG points downwards. The view box is given in flipped coordinates.
+        writeln!(
+            writer,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="{} {} {} {}">"#,
+            self.width,
+            height,
+            ll.x as i64 - margin,
+            -(ur.y as i64) - margin,
+            w + 2 * margin,
+            h + 2 * margin
+        )?;
+        writeln!(
+            writer,
+            "<title>{}</title>",
+            escape(&layout.cell_name(cell).to_string())
+        )?;
+        writeln!(writer, r#"<g transform="scale(1 -1)">"#)?;
+        let font_size = (w.max(h) / 50).max(1);
+        let mut renderer = Renderer {
+            writer,
+            layout,
+            highlighted,
+            font_size,
+            outlines: self.instance_outlines,
+        };
+        renderer.cell(cell, self.depth)?;
+        writeln!(renderer.writer, "</g>")?;
+        writeln!(renderer.writer, "</svg>")
+    }
+}
+
+struct Renderer<'a, W, L: LayoutBase> {
+    writer: &'a mut W,
+    layout: &'a L,
+    highlighted: &'a HashSet<L::ShapeId>,
+    font_size: i64,
+    outlines: bool,
+}
+
+impl<'a, W: Write, L: LayoutBase<Coord = i32>> Renderer<'a, W, L> {
+    /// Draw the shapes of a cell and of its instances down to the given depth.
+    fn cell(&mut self, cell: &L::CellId, depth: usize) -> std::io::Result<()> {
+        let mut layers: Vec<_> = self
+            .layout
+            .each_layer()
+            .map(|l| (self.layout.layer_info(&l), l))
+            .collect();
+        layers.sort_by_key(|(info, _)| (info.index, info.datatype));
+
+        for (info, layer) in &layers {
+            let shapes: Vec<_> = self.layout.each_shape_id(cell, layer).collect();
+            if shapes.is_empty() {
+                continue;
+            }
+            let color = layer_color(info.index, info.datatype);
+            writeln!(
+                self.writer,
+                r#"<g class="layer" data-layer="{}/{}" fill="{c}" stroke="{c}" fill-opacity="0.4">"#,
+                info.index,
+                info.datatype,
+                c = color
+            )?;
+            for shape in shapes {
+                let highlight = self.highlighted.contains(&shape);
+                let geometry = self.layout.shape_geometry(&shape);
+                self.shape(&geometry, highlight)?;
+            }
+            writeln!(self.writer, "</g>")?;
+        }
+
+        let layout = self.layout;
+        for inst in layout.each_cell_instance(cell) {
+            let template = layout.template_cell(&inst);
+            // Each element of an array is drawn separately.
+            let mut transforms = Vec::new();
+            layout.for_each_array_element(&inst, |_, _, tf| transforms.push(tf));
+            for tf in transforms {
+                // Derive the affine matrix from the images of the unit vectors.
+                let origin = tf.transform_point(Point::new(0, 0));
+                let ex = tf.transform_point(Point::new(1, 0)) - origin;
+                let ey = tf.transform_point(Point::new(0, 1)) - origin;
+                writeln!(
+                    self.writer,
+                    r#"<g class="instance" transform="matrix({} {} {} {} {} {})">"#,
+                    ex.x, ex.y, ey.x, ey.y, origin.x, origin.y
+                )?;
+                if depth > 0 {
+                    self.cell(&template, depth - 1)?;
+                }
+                if self.outlines {
+                    if let Some(r) = layout.bounding_box(&template) {
+                        let (ll, ur) = (r.lower_left(), r.upper_right());
+                        writeln!(
+                            self.writer,
+                            r#"<rect x="{}" y="{}" width="{}" height="{}" fill="none" stroke="gray" stroke-dasharray="4 2" vector-effect="non-scaling-stroke"/>"#,
+                            ll.x,
+                            ll.y,
+                            ur.x as i64 - ll.x as i64,
+                            ur.y as i64 - ll.y as i64
+                        )?;
+                        if depth == 0 {
+                            let name = layout.cell_name(&template).to_string();
+                            self.text(&name, ll, "gray")?;
+                        }
+                    }
+                }
+                writeln!(self.writer, "</g>")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn shape(&mut self, geometry: &Geometry<i32>, highlight: bool) -> std::io::Result<()> {
+        let style = if highlight {
+            format!(
+                r#" class="net" fill="{c}" stroke="{c}" fill-opacity="0.8" stroke-width="2" vector-effect="non-scaling-stroke""#,
+                c = HIGHLIGHT_COLOR
+            )
+        } else {
+            r#" vector-effect="non-scaling-stroke""#.to_string()
+        };
+        match geometry {
+            Geometry::Rect(r) => {
+                let (ll, ur) = (r.lower_left(), r.upper_right());
+                writeln!(
+                    self.writer,
+                    r#"<rect x="{}" y="{}" width="{}" height="{}"{}/>"#,
+                    ll.x,
+                    ll.y,
+                    ur.x as i64 - ll.x as i64,
+                    ur.y as i64 - ll.y as i64,
+                    style
+                )
+            }
+            Geometry::SimplePolygon(p) => {
+                let d = path_data(p.iter().copied());
+                writeln!(self.writer, r#"<path d="{}"{}/>"#, d, style)
+            }
+            Geometry::Polygon(p) => {
+                let mut d = path_data(p.exterior.iter().copied());
+                for hole in &p.interiors {
+                    d.push(' ');
+                    d.push_str(&path_data(hole.iter().copied()));
+                }
+                writeln!(
+                    self.writer,
+                    r#"<path d="{}" fill-rule="evenodd"{}/>"#,
+                    d, style
+                )
+            }
+            Geometry::Path(p) => {
+                let linecap = match p.path_type {
+                    PathEndType::Flat => "butt",
+                    PathEndType::Round => "round",
+                    _ => "square",
+                };
+                let points: Vec<String> = p
+                    .points
+                    .iter()
+                    .map(|p| format!("{},{}", p.x, p.y))
+                    .collect();
+                // The stroke width is the width of the path. Highlighting only changes the color.
+                let color = if highlight {
+                    format!(r#" class="net" stroke="{}""#, HIGHLIGHT_COLOR)
+                } else {
+                    String::new()
+                };
+                writeln!(
+                    self.writer,
+                    r#"<polyline points="{}" fill="none" stroke-width="{}" stroke-linecap="{}" stroke-opacity="0.4"{}/>"#,
+                    points.join(" "),
+                    p.width,
+                    linecap,
+                    color
+                )
+            }
+            Geometry::Edge(e) => writeln!(
+                self.writer,
+                r#"<line x1="{}" y1="{}" x2="{}" y2="{}"{}/>"#,
+                e.start.x, e.start.y, e.end.x, e.end.y, style
+            ),
+            Geometry::Text(t) => {
+                let color = if highlight { HIGHLIGHT_COLOR } else { "black" };
+                self.text(t.text(), t.location(), color)
+            }
+            _ => {
+                log::debug!("Skipping geometry which cannot be drawn.");
+                Ok(())
+            }
+        }
+    }
+
+    /// Draw a text label. The text is flipped back such that it is readable.
+    fn text(&mut self, text: &str, location: Point<i32>, color: &str) -> std::io::Result<()> {
+        writeln!(
+            self.writer,
+            r#"<text transform="translate({} {}) scale(1 -1)" font-size="{}" fill="{}" fill-opacity="1" stroke="none">{}</text>"#,
+            location.x,
+            location.y,
+            self.font_size,
+            color,
+            escape(text)
+        )
+    }
+}
+
+/// SVG path data of a closed polygon.
+fn path_data(points: impl Iterator<Item = Point<i32>>) -> String {
+    let points: Vec<String> = points.map(|p| format!("{} {}", p.x, p.y)).collect();
+    format!("M {} Z", points.join(" L "))
+}
+
+/// Derive a color from layer number and datatype. Neighbouring layers get clearly distinct hues.
+fn layer_color(index: UInt, datatype: UInt) -> String {
+    let hue = (index as u64 * 137 + datatype as u64 * 59) % 360;
+    let lightness = 40 + (datatype as u64 * 10) % 30;
+    format!("hsl({}, 80%, {}%)", hue, lightness)
+}
+
+/// Escape characters which have a special meaning in XML.
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_svg() {
+        let mut chip = Chip::new();
+        let top = chip.create_cell("TOP".into());
+        let sub = chip.create_cell("SUB<1>".into());
+        let layer = chip.create_layer(1, 0);
+        chip.insert_shape(&sub, &layer, Rect::new((0, 0), (10, 10)).into());
+        let inst = chip.create_cell_instance(&top, &sub, None);
+        chip.set_transform(
+            &inst,
+            SimpleTransform::new(false, Angle::R90, 1, Vector::new(100, 0)),
+        );
+        let net = chip.create_net(&top, Some("a".into()));
+        let wire = chip.insert_shape(
+            &top,
+            &layer,
+            Path::new(vec![(0, 0).into(), (100, 0).into()], 2).into(),
+        );
+        chip.set_net_of_shape(&wire, Some(net.clone()));
+
+        let render = |writer: SvgWriter| {
+            let mut buffer = Vec::new();
+            writer
+                .write_cell_with_nets(&mut buffer, &chip, &top, &[net.clone()])
+                .unwrap();
+            String::from_utf8(buffer).unwrap()
+        };
+
+        // Without expanding the hierarchy only the outline of the instance is drawn.
+        let svg = render(SvgWriter::new());
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        assert!(svg.contains(r#"matrix(0 1 -1 0 100 0)"#));
+        assert!(svg.contains("SUB&lt;1&gt;"));
+        assert!(svg.contains(r#"class="net""#));
+        assert_eq!(svg.matches("<rect").count(), 1);
+
+        let svg = render(SvgWriter::new().depth(1).instance_outlines(false));
+        assert_eq!(svg.matches("<rect").count(), 1);
+        assert!(!svg.contains("SUB&lt;1&gt;"));
+    }
+}
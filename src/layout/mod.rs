@@ -16,6 +16,7 @@ pub mod prelude;
 
 pub mod io;
 
+pub mod array;
 pub mod traits;
 pub mod types;
 pub mod util;
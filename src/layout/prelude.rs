@@ -5,6 +5,7 @@
 
 //! The `prelude` helps to import most commonly used modules.
 
+pub use super::array::*;
 pub use super::io::*;
 pub use super::traits::*;
 pub use super::types::*;
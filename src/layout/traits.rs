@@ -7,9 +7,11 @@
 
 #![allow(unused_variables)]
 
-use crate::layout::types::{LayerInfo, UInt};
+use crate::layout::array::InstanceArray;
+use crate::layout::types::{LayerInfo, PlacementStatus, UInt};
+use crate::layout::util::{enlarge_within, rect_distance_sq};
 use crate::prelude::PropertyValue;
-use crate::prelude::{Geometry, HierarchyMultithread, Rect};
+use crate::prelude::{Geometry, HierarchyMultithread, MapPointwise, Point, Rect, TryBoundingBox};
 use crate::traits::{HierarchyBase, HierarchyEdit};
 use iron_shapes::transform::SimpleTransform;
 use iron_shapes::CoordinateType;
@@ -99,6 +101,63 @@ pub trait LayoutBase: HierarchyBase {
     /// Get the parent cell and the layer of a shape as a (cell, layer) tuple.
     fn parent_of_shape(&self, shape_id: &Self::ShapeId) -> (Self::CellId, Self::LayerId);
 
+    /// Check if the layer ID is valid, i.e. the layer exists.
+    fn layer_exists(&self, layer: &Self::LayerId) -> bool {
+        self.each_layer().any(|l| &l == layer)
+    }
+
+    /// Check if the shape ID is valid, i.e. the shape exists.
+    fn shape_exists(&self, shape_id: &Self::ShapeId) -> bool {
+        // Inefficient default implementation.
+        self.each_cell().any(|cell| {
+            self.each_layer()
+                .any(|layer| self.each_shape_id(&cell, &layer).any(|s| &s == shape_id))
+        })
+    }
+
+    /// Get the `LayerInfo` data structure for this layer.
+    /// Returns `None` if the layer does not exist.
+    fn try_layer_info(&self, layer: &Self::LayerId) -> Option<LayerInfo<Self::NameType>> {
+        if self.layer_exists(layer) {
+            Some(self.layer_info(layer))
+        } else {
+            None
+        }
+    }
+
+    /// Get a clone of the shape geometry.
+    /// Returns `None` if the shape does not exist.
+    fn try_shape_geometry(&self, shape_id: &Self::ShapeId) -> Option<Geometry<Self::Coord>> {
+        if self.shape_exists(shape_id) {
+            Some(self.shape_geometry(shape_id))
+        } else {
+            None
+        }
+    }
+
+    /// Get the layer of a shape.
+    /// Returns `None` if the shape does not exist.
+    fn try_shape_layer(&self, shape_id: &Self::ShapeId) -> Option<Self::LayerId> {
+        if self.shape_exists(shape_id) {
+            Some(self.shape_layer(shape_id))
+        } else {
+            None
+        }
+    }
+
+    /// Get the parent cell and the layer of a shape as a (cell, layer) tuple.
+    /// Returns `None` if the shape does not exist.
+    fn try_parent_of_shape(
+        &self,
+        shape_id: &Self::ShapeId,
+    ) -> Option<(Self::CellId, Self::LayerId)> {
+        if self.shape_exists(shape_id) {
+            Some(self.parent_of_shape(shape_id))
+        } else {
+            None
+        }
+    }
+
     /// Call a function `f` for each shape of this cell and its sub cells.
     /// Along to the geometric shape `f` also gets a transformation as argument.
     /// The transformation describes the actual position of the geometric shape relative to the `cell`.
@@ -117,9 +176,10 @@ pub trait LayoutBase: HierarchyBase {
             // Push child instances.
             self.for_each_cell_instance(&cell, |inst| {
                 let template = self.template_cell(&inst);
-                let transform = self.get_transform(&inst);
-                let tf2 = transform.then(&tf);
-                stack.push((template, tf2));
+                self.for_each_array_element(&inst, |_, _, transform| {
+                    let tf2 = transform.then(&tf);
+                    stack.push((template.clone(), tf2));
+                });
             });
 
             // Process shapes of this cell.
@@ -130,6 +190,77 @@ pub trait LayoutBase: HierarchyBase {
     /// Get the geometric transform that describes the location of a cell instance relative to its parent.
     fn get_transform(&self, cell_inst: &Self::CellInstId) -> SimpleTransform<Self::Coord>;
 
+    /// Get the geometric transform that describes the location of a cell instance relative to its parent.
+    /// Returns `None` if the cell instance does not exist.
+    fn try_get_transform(
+        &self,
+        cell_inst: &Self::CellInstId,
+    ) -> Option<SimpleTransform<Self::Coord>> {
+        if self.cell_instance_exists(cell_inst) {
+            Some(self.get_transform(cell_inst))
+        } else {
+            None
+        }
+    }
+
+    /// Get the array parameters of a cell instance.
+    /// Returns `None` if the cell instance is a single placement.
+    ///
+    /// All elements of an array share the same cell instance ID.
+    fn cell_instance_array(
+        &self,
+        cell_inst: &Self::CellInstId,
+    ) -> Option<InstanceArray<Self::Coord>> {
+        None
+    }
+
+    /// Get the number of placements of a cell instance. This is `1` for instances which are not arrays.
+    fn num_array_elements(&self, cell_inst: &Self::CellInstId) -> usize {
+        self.cell_instance_array(cell_inst)
+            .map(|a| a.num_elements())
+            .unwrap_or(1)
+    }
+
+    /// Get the transform of the array element in `column` and `row` relative to the parent cell.
+    /// A cell instance which is not an array has only the element in column `0` and row `0`.
+    fn array_element_transform(
+        &self,
+        cell_inst: &Self::CellInstId,
+        column: u32,
+        row: u32,
+    ) -> SimpleTransform<Self::Coord> {
+        let tf = self.get_transform(cell_inst);
+        match self.cell_instance_array(cell_inst) {
+            Some(array) => array.element_transform(&tf, column, row),
+            None => {
+                assert!(column == 0 && row == 0, "Cell instance is not an array.");
+                tf
+            }
+        }
+    }
+
+    /// Call a function for each array element of a cell instance.
+    /// The function gets the column, the row and the transform of the element as arguments.
+    /// A cell instance which is not an array has a single element in column `0` and row `0`.
+    fn for_each_array_element<F>(&self, cell_inst: &Self::CellInstId, mut f: F)
+    where
+        F: FnMut(u32, u32, SimpleTransform<Self::Coord>) -> (),
+    {
+        let tf = self.get_transform(cell_inst);
+        match self.cell_instance_array(cell_inst) {
+            Some(array) => array
+                .each_element()
+                .for_each(|(c, r)| f(c, r, array.element_transform(&tf, c, r))),
+            None => f(0, 0, tf),
+        }
+    }
+
+    /// Get the placement status of a cell instance.
+    /// Layouts which do not track the status treat all instances as [`PlacementStatus::Placed`].
+    fn placement_status(&self, cell_inst: &Self::CellInstId) -> PlacementStatus {
+        PlacementStatus::Placed
+    }
+
     /// Get a property of a shape.
     fn get_shape_property(
         &self,
@@ -138,6 +269,13 @@ pub trait LayoutBase: HierarchyBase {
     ) -> Option<PropertyValue> {
         None
     }
+
+    /// Call a function for each property of a shape.
+    fn for_each_shape_property<F>(&self, shape: &Self::ShapeId, f: F)
+    where
+        F: FnMut(&Self::NameType, &PropertyValue) -> (),
+    {
+    }
 }
 
 /// Additional requirement that all ID types are `Send + Sync` as needed for multithreading
@@ -181,6 +319,97 @@ pub trait RegionSearch: LayoutBase {
         cell: &Self::CellId,
         search_region: &Rect<Self::Coord>,
     ) -> Box<dyn Iterator<Item = Self::CellInstId> + '_>;
+
+    /// Iterate over the array elements `(column, row)` of a cell instance whose bounding-box
+    /// overlaps with the `search_region`. The search region is given in the coordinates of the parent cell.
+    /// A cell instance which is not an array has a single element in column `0` and row `0`.
+    fn each_array_element_in_region(
+        &self,
+        cell_inst: &Self::CellInstId,
+        search_region: &Rect<Self::Coord>,
+    ) -> Box<dyn Iterator<Item = (u32, u32)> + '_> {
+        let template_bbox = match self.bounding_box(&self.template_cell(cell_inst)) {
+            Some(bbox) => bbox,
+            None => return Box::new(std::iter::empty()),
+        };
+        let tf = self.get_transform(cell_inst);
+        let element_bbox = template_bbox.transform(|p| tf.transform_point(p));
+        let array = self
+            .cell_instance_array(cell_inst)
+            .unwrap_or_else(InstanceArray::single);
+        Box::new(
+            array
+                .elements_in_region(&element_bbox, search_region)
+                .into_iter(),
+        )
+    }
+
+    /// Iterate over the IDs of the `k` shapes (on a specific layer) which are closest to `point`.
+    /// The shapes are ordered by increasing distance.
+    /// The distance is measured between the point and the bounding-box of a shape.
+    ///
+    /// The default implementation checks all shapes on the layer.
+    fn nearest_shapes(
+        &self,
+        cell: &Self::CellId,
+        layer_id: &Self::LayerId,
+        point: &Point<Self::Coord>,
+        k: usize,
+    ) -> Box<dyn Iterator<Item = Self::ShapeId> + '_> {
+        let point = Rect::new(*point, *point);
+        let mut shapes: Vec<(Self::Area, _)> = self
+            .each_shape_id(cell, layer_id)
+            .filter_map(|shape_id| {
+                self.shape_geometry(&shape_id)
+                    .try_bounding_box()
+                    .map(|bbox| (rect_distance_sq(&point, &bbox), shape_id))
+            })
+            .collect();
+        shapes.sort_by(|(d1, _), (d2, _)| d1.partial_cmp(d2).unwrap());
+        Box::new(shapes.into_iter().take(k).map(|(_, shape_id)| shape_id))
+    }
+
+    /// Iterate over the IDs of all shapes (on a specific layer) whose bounding-box is not further away
+    /// than `distance` from the bounding-box of `geometry`. Shapes which overlap with the bounding-box have distance zero.
+    ///
+    /// # Panics
+    /// Panics if `distance` is negative.
+    fn shapes_within_distance(
+        &self,
+        cell: &Self::CellId,
+        layer_id: &Self::LayerId,
+        geometry: &Geometry<Self::Coord>,
+        distance: Self::Coord,
+    ) -> Box<dyn Iterator<Item = Self::ShapeId> + '_> {
+        assert!(
+            distance >= Self::Coord::zero(),
+            "Distance must not be negative."
+        );
+        let bbox = match geometry.try_bounding_box() {
+            Some(bbox) => bbox,
+            None => return Box::new(std::iter::empty()),
+        };
+        // Limit the search region to the shapes on the layer such that the coordinates cannot overflow.
+        let search_region = match self
+            .bounding_box_per_layer(cell, layer_id)
+            .and_then(|bounds| enlarge_within::<_, Self::Area>(&bbox, distance, &bounds))
+        {
+            Some(r) => r,
+            None => return Box::new(std::iter::empty()),
+        };
+        let max_distance_sq = Self::Area::from(distance) * Self::Area::from(distance);
+
+        // The search region also contains the corners which are further away than `distance`.
+        Box::new(
+            self.each_shape_in_region_per_layer(cell, layer_id, &search_region)
+                .filter(move |shape_id| {
+                    self.shape_geometry(shape_id)
+                        .try_bounding_box()
+                        .map(|b| rect_distance_sq::<_, Self::Area>(&bbox, &b) <= max_distance_sq)
+                        .unwrap_or(false)
+                }),
+        )
+    }
 }
 
 /// Trait for layouts that support editing.
@@ -231,6 +460,44 @@ pub trait LayoutEdit: LayoutBase + HierarchyEdit {
     /// Set the geometric transform that describes the location of a cell instance relative to its parent.
     fn set_transform(&mut self, cell_inst: &Self::CellInstId, tf: SimpleTransform<Self::Coord>);
 
+    /// Turn the cell instance into a regular array or back into a single placement with `None`.
+    /// Returns the previous array parameters.
+    fn set_cell_instance_array(
+        &mut self,
+        cell_inst: &Self::CellInstId,
+        array: Option<InstanceArray<Self::Coord>>,
+    ) -> Option<InstanceArray<Self::Coord>>;
+
+    /// Set the placement status of a cell instance. Returns the previous status.
+    /// Layouts which do not track the status ignore the call and return [`PlacementStatus::Placed`].
+    ///
+    /// The status is not enforced by `set_transform()`. Wrap the layout into a
+    /// [`PlacementLock`](crate::placement_lock::PlacementLock) to reject moves of locked instances.
+    fn set_placement_status(
+        &mut self,
+        cell_inst: &Self::CellInstId,
+        status: PlacementStatus,
+    ) -> PlacementStatus {
+        PlacementStatus::Placed
+    }
+
+    /// Set the transform of a cell instance unless it is locked by its placement status.
+    /// Returns the placement status as an error if the instance is locked and the
+    /// transform would change.
+    fn try_set_transform(
+        &mut self,
+        cell_inst: &Self::CellInstId,
+        tf: SimpleTransform<Self::Coord>,
+    ) -> Result<(), PlacementStatus> {
+        let status = self.placement_status(cell_inst);
+        if status.is_locked() && self.get_transform(cell_inst) != tf {
+            Err(status)
+        } else {
+            self.set_transform(cell_inst, tf);
+            Ok(())
+        }
+    }
+
     /// Set a property of a shape.
     fn set_shape_property(
         &mut self,
@@ -239,4 +506,7 @@ pub trait LayoutEdit: LayoutBase + HierarchyEdit {
         value: PropertyValue,
     ) {
     }
+
+    /// Remove a property of a shape.
+    fn remove_shape_property(&mut self, shape: &Self::ShapeId, key: &Self::NameType) {}
 }
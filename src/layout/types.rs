@@ -24,3 +24,37 @@ pub struct LayerInfo<NameType> {
     /// Name of the layer.
     pub name: Option<NameType>,
 }
+
+/// Placement status of a cell instance.
+///
+/// The status tells placement and optimization tools whether they are allowed
+/// to move an instance. The names follow the component placement status of DEF.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum PlacementStatus {
+    /// The instance has no valid location yet.
+    Unplaced,
+    /// The instance has a location but can be moved freely.
+    #[default]
+    Placed,
+    /// The instance must not be moved by automatic tools but may be moved by hand.
+    Firm,
+    /// The location of the instance is fixed and must not be changed.
+    Fixed,
+    /// The location is fixed and the instance is part of the cover macro (e.g. bumps).
+    Cover,
+}
+
+impl PlacementStatus {
+    /// Check if the instance is locked to its current location.
+    /// This is the case for [`PlacementStatus::Firm`], [`PlacementStatus::Fixed`]
+    /// and [`PlacementStatus::Cover`].
+    pub fn is_locked(&self) -> bool {
+        matches!(self, Self::Firm | Self::Fixed | Self::Cover)
+    }
+
+    /// Check if the instance has a valid location.
+    pub fn is_placed(&self) -> bool {
+        !matches!(self, Self::Unplaced)
+    }
+}
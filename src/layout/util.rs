@@ -5,8 +5,11 @@
 
 //! Utility functions for dealing with layouts.
 
+use crate::layout::array::InstanceArray;
+use crate::prelude::{MapPointwise, Point, Rect, Vector};
 use crate::traits::{LayoutBase, LayoutEdit};
 use iron_shapes::CoordinateType;
+use num_traits::Num;
 use std::borrow::Borrow;
 
 /// Copy the shapes on a specific layer from one cell into another cell.
@@ -152,6 +155,178 @@ pub trait LayoutEditUtil: LayoutEdit {
         self.find_layer(index, datatype)
             .unwrap_or_else(|| self.create_layer(index, datatype))
     }
+
+    /// Replace the cell instance with the content of its template cell and remove the instance afterwards.
+    ///
+    /// The shapes of the template are copied into the parent cell with the transform of the
+    /// instance applied. Shape properties are copied too. Child instances of the template
+    /// become child instances of the parent cell. Their names are prefixed with the name of the
+    /// flattened instance like `INSTANCE:CHILD`. Arrays are flattened into all their elements.
+    ///
+    /// This flattens only the layout. Use
+    /// [`L2NEditUtil::flatten_cell_instance_l2n`](crate::l2n::util::L2NEditUtil::flatten_cell_instance_l2n)
+    /// to keep the netlist consistent.
+    fn flatten_cell_instance(&mut self, inst: &Self::CellInstId) {
+        copy_content_of_cell_instance(self, inst);
+        self.remove_cell_instance(inst);
+    }
 }
 
 impl<L: LayoutEdit> LayoutEditUtil for L {}
+
+/// Copies of the content of a template cell created for one element of a flattened cell instance.
+pub(crate) struct FlattenedElement<L: LayoutBase + ?Sized> {
+    /// Pairs of child instances of the template and their copies.
+    pub instances: Vec<(L::CellInstId, L::CellInstId)>,
+    /// Pairs of shapes of the template and their copies.
+    pub shapes: Vec<(L::ShapeId, L::ShapeId)>,
+}
+
+/// Copy the child instances and the shapes of the template cell of `inst` into the parent cell
+/// of `inst` as done when flattening the instance. The instance itself is not removed.
+///
+/// Returns the copies made for each array element. Single instances have exactly one element.
+pub(crate) fn copy_content_of_cell_instance<L: LayoutEdit + ?Sized>(
+    layout: &mut L,
+    inst: &L::CellInstId,
+) -> Vec<FlattenedElement<L>> {
+    let template = layout.template_cell(inst);
+    let parent = layout.parent_cell(inst);
+    assert!(template != parent, "Recursive instances are not allowed.");
+    let inst_name = layout.cell_instance_name(inst);
+
+    let mut element_transforms = vec![];
+    layout.for_each_array_element(inst, |_, _, tf| element_transforms.push(tf));
+
+    let mut elements = vec![];
+    for tf in element_transforms {
+        let mut instance_copies = vec![];
+        let mut shape_copies = vec![];
+        for child in layout.each_cell_instance_vec(&template) {
+            // Construct a name like `INSTANCE:CHILD` and append a counter if it is already used.
+            let name = match (&inst_name, layout.cell_instance_name(&child)) {
+                (Some(inst_name), Some(child_name)) => {
+                    let mut name = format!("{}:{}", inst_name, child_name);
+                    let mut i = 0;
+                    while layout.cell_instance_by_name(&parent, &name).is_some() {
+                        name = format!("{}:{}_{}", inst_name, child_name, i);
+                        i += 1;
+                    }
+                    Some(name.into())
+                }
+                _ => None,
+            };
+            let child_template = layout.template_cell(&child);
+            let copy = layout.create_cell_instance(&parent, &child_template, name);
+            layout.set_transform(&copy, layout.get_transform(&child).then(&tf));
+            if let Some(array) = layout.cell_instance_array(&child) {
+                // The pitches are rotated, mirrored and scaled like the instance.
+                let origin = tf.transform_point(Point::zero());
+                let pitch = |v: Vector<L::Coord>| tf.transform_point(Point::new(v.x, v.y)) - origin;
+                let array = InstanceArray {
+                    column_pitch: pitch(array.column_pitch),
+                    row_pitch: pitch(array.row_pitch),
+                    ..array
+                };
+                layout.set_cell_instance_array(&copy, Some(array));
+            }
+            layout.set_placement_status(&copy, layout.placement_status(&child));
+            let mut properties = vec![];
+            layout.for_each_cell_instance_property(&child, |k, v| {
+                properties.push((k.clone(), v.clone()))
+            });
+            for (key, value) in properties {
+                layout.set_cell_instance_property(&copy, key, value);
+            }
+            instance_copies.push((child, copy));
+        }
+
+        let layers: Vec<_> = layout.each_layer().collect();
+        for layer in layers {
+            let mut shapes = vec![];
+            layout.for_each_shape(&template, &layer, |id, g| {
+                shapes.push((id.clone(), g.transform(|p| tf.transform_point(p))))
+            });
+            for (shape, geometry) in shapes {
+                let copy = layout.insert_shape(&parent, &layer, geometry);
+                let mut properties = vec![];
+                layout.for_each_shape_property(&shape, |k, v| {
+                    properties.push((k.clone(), v.clone()))
+                });
+                for (key, value) in properties {
+                    layout.set_shape_property(&copy, key, value);
+                }
+                shape_copies.push((shape, copy));
+            }
+        }
+
+        elements.push(FlattenedElement {
+            instances: instance_copies,
+            shapes: shape_copies,
+        });
+    }
+
+    elements
+}
+
+/// Compute the squared euclidean distance between two rectangles.
+/// The distance is zero if the rectangles touch or overlap.
+pub(crate) fn rect_distance_sq<C, A>(a: &Rect<C>, b: &Rect<C>) -> A
+where
+    C: CoordinateType,
+    A: Num + Copy + From<C>,
+{
+    // Gap between two intervals.
+    let gap = |a_low: C, a_high: C, b_low: C, b_high: C| {
+        if b_low > a_high {
+            b_low - a_high
+        } else if a_low > b_high {
+            a_low - b_high
+        } else {
+            C::zero()
+        }
+    };
+    let (a_ll, a_ur) = (a.lower_left(), a.upper_right());
+    let (b_ll, b_ur) = (b.lower_left(), b.upper_right());
+    let dx = A::from(gap(a_ll.x, a_ur.x, b_ll.x, b_ur.x));
+    let dy = A::from(gap(a_ll.y, a_ur.y, b_ll.y, b_ur.y));
+    dx * dx + dy * dy
+}
+
+/// Enlarge `rect` by `distance` on all sides but do not grow it beyond `bounds`.
+/// The bounds are computed in the area type `A` such that the coordinates cannot overflow.
+///
+/// Returns `None` if the enlarged rectangle does not intersect with `bounds`.
+pub(crate) fn enlarge_within<C, A>(rect: &Rect<C>, distance: C, bounds: &Rect<C>) -> Option<Rect<C>>
+where
+    C: CoordinateType,
+    A: Num + Copy + PartialOrd + From<C>,
+{
+    let d = A::from(distance);
+    let lower = |x: C, low: C| {
+        if A::from(x) - d > A::from(low) {
+            x - distance
+        } else {
+            low
+        }
+    };
+    let upper = |x: C, high: C| {
+        if A::from(x) + d < A::from(high) {
+            x + distance
+        } else {
+            high
+        }
+    };
+    let (ll, ur) = (rect.lower_left(), rect.upper_right());
+    let (b_ll, b_ur) = (bounds.lower_left(), bounds.upper_right());
+    let (x_low, y_low) = (lower(ll.x, b_ll.x), lower(ll.y, b_ll.y));
+    let (x_high, y_high) = (upper(ur.x, b_ur.x), upper(ur.y, b_ur.y));
+    if x_low > x_high || y_low > y_high {
+        None
+    } else {
+        Some(Rect::new(
+            Point::new(x_low, y_low),
+            Point::new(x_high, y_high),
+        ))
+    }
+}
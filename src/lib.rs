@@ -30,6 +30,10 @@
 //! * [`LayoutEdit`] - edit layout shapes
 //! * [`L2NBase`] - access the links between layout shapes and netlist
 //! * [`L2NEdit`] - edit the links between layout shapes and netlists
+//! * [`PowerBase`] - access power domains of netlists
+//! * [`PowerEdit`] - create and assign power domains
+//! * [`BusBase`] - access multi-bit buses of pins and nets
+//! * [`BusEdit`] - create and remove buses
 //!
 //! Read more about netlists and layouts in the following modules:
 //! * [`Netlist`]
@@ -44,10 +48,23 @@
 //!
 //! * [`Undo`] - Make modifications reversible
 //! * [`FlatView`] - Create an on-the-fly flattened view of a hierarchical structure.
+//! * [`Observer`] - Get notified about modifications with callbacks or an event channel.
+//! * [`LibraryWrapper`] - Combine a design with read-only cell libraries.
 //!
 //! # Input/output
-//! Reading and writing data base structures is generally left to other crates such as `libreda-oasis`,
-//! `libreda-lefdef`, ...
+//! This crate comes with readers and writers for the following formats:
+//!
+//! * [`snapshot`] - The native binary format of this crate. It is used to quickly store and restore
+//! the content of a data base including buses and power domains. Only the IDs of the objects are not stored.
+//! * [`verilog`] - Structural (gate-level) Verilog netlists.
+//! * [`spice`] - SPICE and CDL subcircuit netlists.
+//! * [`gds`] - GDSII layout streams.
+//! * [`oasis`] - OASIS layout streams.
+//! * [`lefdef`] - LEF libraries and DEF designs with placement and routing.
+//! * [`svg`] - Export of cells as SVG images for debugging.
+//! * [`json`] - Human readable JSON documents for exchanging designs with scripts.
+//!
+//! Readers and writers for other formats are left to other crates.
 //!
 //! # Geometric primitives
 //! Two dimensional geometrical primitives (polygons, rectangles, etc.) are re-exported from the [`iron_shapes`] crate.
@@ -67,11 +84,25 @@
 //! [`LayoutEdit`]: layout::traits::LayoutEdit
 //! [`L2NBase`]: traits::L2NBase
 //! [`L2NEdit`]: traits::L2NEdit
+//! [`PowerBase`]: netlist::power::PowerBase
+//! [`PowerEdit`]: netlist::power::PowerEdit
+//! [`BusBase`]: netlist::bus::BusBase
+//! [`BusEdit`]: netlist::bus::BusEdit
 //! [`Netlist`]: netlist
 //! [`Layout`]: layout
 //! [`Chip`]: chip::Chip
 //! [`Undo`]: undo
 //! [`FlatView`]: flat_view
+//! [`Observer`]: observer
+//! [`LibraryWrapper`]: library
+//! [`snapshot`]: l2n::io::snapshot
+//! [`verilog`]: netlist::io::verilog
+//! [`spice`]: netlist::io::spice
+//! [`gds`]: layout::io::gds
+//! [`oasis`]: layout::io::oasis
+//! [`lefdef`]: l2n::io::lefdef
+//! [`svg`]: layout::io::svg
+//! [`json`]: l2n::io::json
 
 // Enforce documentation of the public API.
 #![deny(missing_docs)]
@@ -92,7 +123,10 @@ pub mod hierarchy;
 pub mod index;
 pub mod l2n;
 pub mod layout;
+pub mod library;
 pub mod netlist;
+pub mod observer;
+pub mod placement_lock;
 pub mod prelude;
 pub mod profile;
 pub mod property_storage;
@@ -106,5 +140,4 @@ pub mod undo;
 pub mod technology;
 
 mod decorator;
-mod library;
 mod slab_alloc;
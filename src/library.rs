@@ -2,48 +2,417 @@
 //
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
-//! # Experimental
-//!
 //! Simplify usage of cell libraries.
+//!
+//! A [`LibraryWrapper`] combines an owned design with any number of read-only cell libraries
+//! (standard-cells, IO cells, memory macros, ...) into one view. Cells of the libraries
+//! can be instantiated in the design without copying them into the design.
+//!
+//! # Name precedence
+//! Cells are looked up by name in the following order:
+//! 1. The owned design.
+//! 2. The libraries in the order they were added.
+//!
+//! The first definition of a name wins. Cells which are shadowed by another definition are still
+//! accessible by their ID but not by their name. Use [`LibraryWrapper::name_conflicts()`] to find
+//! names which are defined more than once.
+//!
+//! # Linked cells
+//! An instance in the owned design needs a template cell in the owned design. Library cells are
+//! therefore represented in the owned design by *stub* cells: leaf cells which carry the
+//! name and the pins of the library cell. A stub which is *linked* to a library cell is hidden
+//! in the combined view and is replaced by the library cell. Pins of the stub are
+//! matched with the pins of the library cell by name. A cell can only be linked if its pin names
+//! are the same as the pin names of the library cell.
 
-// TODO: Remove when implemented.
-#![allow(unused)]
-
-use crate::prelude::HierarchyBase;
+use crate::layout::array::InstanceArray;
+use crate::layout::types::{LayerInfo, PlacementStatus, UInt};
+use crate::netlist::bus::BitOrder;
+use crate::netlist::direction::Direction;
+use crate::prelude::{Geometry, PropertyValue, Rect};
+use crate::traits::{BusBase, HierarchyBase, HierarchyEdit, LayoutBase, NetlistBase, NetlistEdit};
+use iron_shapes::transform::SimpleTransform;
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
 /// Identifier of a library.
 #[derive(Copy, Clone, Debug, Hash, PartialOrd, PartialEq, Eq, Ord)]
 pub struct LibraryId(usize);
 
-pub struct LibraryWrapper<'a, T> {
+impl LibraryId {
+    /// Identifier of the owned design.
+    pub const OWNED: LibraryId = LibraryId(0);
+}
+
+/// A cell name which is defined more than once.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NameConflict<N> {
+    /// The name of the cells.
+    pub name: N,
+    /// Libraries which define a cell with this name, ordered by precedence.
+    /// Only the cell of the first library is found by name.
+    pub definitions: Vec<LibraryId>,
+}
+
+/// Error type used by the [`LibraryWrapper`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LibraryError<N> {
+    /// The library ID was not created by this wrapper.
+    UnknownLibrary(LibraryId),
+    /// The cell has child instances and cannot be replaced by a library cell.
+    NotALeafCell(N),
+    /// The pins of a cell do not match the pins of the library cell with the same name.
+    PinMismatch {
+        /// Name of the cell.
+        cell: N,
+        /// Library which defines the cell.
+        library: LibraryId,
+        /// Pins of the cell which do not exist in the library cell.
+        missing_in_library: Vec<N>,
+        /// Pins of the library cell which do not exist in the cell.
+        missing_in_cell: Vec<N>,
+    },
+}
+
+impl<N: std::fmt::Display> std::fmt::Display for LibraryError<N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        /// Format a list of names separated by commas.
+        fn join<N: std::fmt::Display>(names: &[N]) -> String {
+            names
+                .iter()
+                .map(|n| n.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+        match self {
+            LibraryError::UnknownLibrary(id) => {
+                write!(f, "Library {:?} does not belong to this wrapper.", id)
+            }
+            LibraryError::NotALeafCell(cell) => write!(
+                f,
+                "Cell '{}' has child instances and cannot be linked to a library cell.",
+                cell
+            ),
+            LibraryError::PinMismatch {
+                cell,
+                library,
+                missing_in_library,
+                missing_in_cell,
+            } => write!(
+                f,
+                "Pins of cell '{}' do not match the library cell in library {:?}. \
+                Missing in the library cell: [{}]. Missing in the cell: [{}].",
+                cell,
+                library,
+                join(missing_in_library),
+                join(missing_in_cell)
+            ),
+        }
+    }
+}
+
+impl<N: std::fmt::Display + std::fmt::Debug> std::error::Error for LibraryError<N> {}
+
+/// View of an owned design together with read-only cell libraries.
+///
+/// All IDs are pairs of the library ID and the ID within the library.
+/// Layers are the layers of the owned design. Shapes of library cells are mapped to the layers
+/// of the owned design by their layer number and datatype. Shapes on layers which do not exist
+/// in the owned design are not visible: They are skipped when iterating over shapes and
+/// [`LayoutBase::shape_exists()`] returns `false` for them. The libraries are expected to use the same
+/// database unit as the owned design.
+///
+/// # Example
+///
+/// ```
+/// use libreda_db::prelude::*;
+/// use libreda_db::library::{LibraryId, LibraryWrapper};
+///
+/// // Create a standard-cell library.
+/// let mut stdcells = Chip::new();
+/// let inv = stdcells.create_cell("INV".into());
+/// let inv_a = stdcells.create_pin(&inv, "A".into(), Direction::Input);
+/// stdcells.create_pin(&inv, "Y".into(), Direction::Output);
+///
+/// // Create the design.
+/// let mut design = Chip::new();
+/// let top = design.create_cell("TOP".into());
+///
+/// let mut lib = LibraryWrapper::new(design);
+/// let stdcells_id = lib.add_library(&stdcells);
+///
+/// // Instantiate the library cell in the design.
+/// let inv = lib.cell_by_name("INV").unwrap();
+/// assert_eq!(inv.0, stdcells_id);
+/// let inst = lib.create_cell_instance(&top, &inv, Some("inv1".into()));
+/// assert_eq!(lib.template_cell(&inst), inv);
+/// assert_eq!(lib.parent_cell(&inst), (LibraryId::OWNED, top));
+///
+/// // Pins of the instance refer to the pins of the library cell.
+/// let pin_a = lib.pin_by_name(&inv, "A").unwrap();
+/// let pin_inst = lib.pin_instance(&inst, &pin_a);
+/// assert_eq!(lib.template_pin(&pin_inst), (stdcells_id, inv_a));
+/// ```
+pub struct LibraryWrapper<'a, T: HierarchyBase> {
+    /// Read-only libraries.
     libraries: Vec<&'a T>,
+    /// The design.
     owned: T,
+    /// Links from stub cells in the owned design to library cells.
+    links: HashMap<T::CellId, (LibraryId, T::CellId)>,
+    /// Reverse of `links`.
+    stubs: HashMap<(LibraryId, T::CellId), T::CellId>,
 }
 
-impl<'a, T> LibraryWrapper<'a, T> {
+impl<'a, T: HierarchyBase> LibraryWrapper<'a, T> {
+    /// Wrap a design. Libraries can be added with [`LibraryWrapper::add_library()`].
+    pub fn new(owned: T) -> Self {
+        Self {
+            libraries: vec![],
+            owned,
+            links: Default::default(),
+            stubs: Default::default(),
+        }
+    }
+
     /// Register a library.
+    /// Libraries which are added first take precedence when cells are looked up by name.
     pub fn add_library(&mut self, library: &'a T) -> LibraryId {
         self.libraries.push(library);
         LibraryId(self.libraries.len())
     }
 
-    fn get_library(&self, library_id: &LibraryId) -> &T {
-        if library_id == &LibraryId(0) {
-            &self.owned
+    /// Get a reference to the owned design or to a library.
+    /// Returns an error if the library ID was not created by this wrapper.
+    pub fn library(&self, library_id: &LibraryId) -> Result<&T, LibraryError<T::NameType>> {
+        if library_id == &LibraryId::OWNED {
+            Ok(&self.owned)
         } else {
-            &self.libraries[library_id.0]
+            self.read_only_library(library_id)
         }
     }
 
+    /// Get a reference to a read-only library.
+    fn read_only_library(
+        &self,
+        library_id: &LibraryId,
+    ) -> Result<&'a T, LibraryError<T::NameType>> {
+        library_id
+            .0
+            .checked_sub(1)
+            .and_then(|i| self.libraries.get(i))
+            .copied()
+            .ok_or(LibraryError::UnknownLibrary(*library_id))
+    }
+
+    /// Get a reference to the owned design or to a library.
+    ///
+    /// # Panics
+    /// Panics if the library ID was not created by this wrapper.
+    fn lib(&self, library_id: &LibraryId) -> &T {
+        self.library(library_id)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Get a reference to the owned design.
+    pub fn owned(&self) -> &T {
+        &self.owned
+    }
+
+    /// Get a mutable reference to the owned design.
+    ///
+    /// Links are not updated automatically. A linked cell must be unlinked with
+    /// [`LibraryWrapper::unlink_cell()`] before it is removed, filled with content
+    /// or before its pins are changed.
+    pub fn owned_mut(&mut self) -> &mut T {
+        &mut self.owned
+    }
+
+    /// Release the owned design.
+    pub fn into_inner(self) -> T {
+        self.owned
+    }
+
     /// Get reference to owned data and to libraries.
     fn libraries(&self) -> impl Iterator<Item = (LibraryId, &T)> {
-        std::iter::once((LibraryId(0), &self.owned)).chain(
+        std::iter::once((LibraryId::OWNED, &self.owned)).chain(
             self.libraries
                 .iter()
                 .enumerate()
                 .map(|(id, lib)| (LibraryId(id + 1), *lib)),
         )
     }
+
+    /// Find a cell by name in the libraries only.
+    fn find_library_cell(&self, name: &str) -> Option<(LibraryId, T::CellId)> {
+        self.libraries()
+            .skip(1)
+            .find_map(|(lib_id, lib)| lib.cell_by_name(name).map(|cell| (lib_id, cell)))
+    }
+
+    /// Check if the cell is a stub of the owned design which is linked to a library cell.
+    fn is_stub(&self, (lib_id, cell): &(LibraryId, T::CellId)) -> bool {
+        lib_id == &LibraryId::OWNED && self.links.contains_key(cell)
+    }
+
+    /// Replace linked stub cells by their library cells.
+    fn resolve(&self, (lib_id, cell): (LibraryId, T::CellId)) -> (LibraryId, T::CellId) {
+        if lib_id == LibraryId::OWNED {
+            if let Some(target) = self.links.get(&cell) {
+                return target.clone();
+            }
+        }
+        (lib_id, cell)
+    }
+
+    /// Remove the link of the cell. Returns the library cell which was linked.
+    pub fn unlink_cell(&mut self, cell: &T::CellId) -> Option<(LibraryId, T::CellId)> {
+        let target = self.links.remove(cell)?;
+        self.stubs.remove(&target);
+        Some(target)
+    }
+
+    /// Get the library cell which is linked to the cell of the owned design.
+    pub fn linked_cell(&self, cell: &T::CellId) -> Option<(LibraryId, T::CellId)> {
+        self.links.get(cell).cloned()
+    }
+
+    /// Find all cell names which are defined more than once.
+    /// Linked cells of the owned design are not considered as conflicts.
+    pub fn name_conflicts(&self) -> Vec<NameConflict<T::NameType>> {
+        let mut definitions: BTreeMap<T::NameType, Vec<LibraryId>> = BTreeMap::new();
+        for (lib_id, lib) in self.libraries() {
+            lib.for_each_cell(|cell| {
+                if !self.is_stub(&(lib_id, cell.clone())) {
+                    definitions
+                        .entry(lib.cell_name(&cell))
+                        .or_default()
+                        .push(lib_id);
+                }
+            })
+        }
+        definitions
+            .into_iter()
+            .filter(|(_, libs)| libs.len() > 1)
+            .map(|(name, definitions)| NameConflict { name, definitions })
+            .collect()
+    }
+}
+
+impl<'a, T: NetlistBase> LibraryWrapper<'a, T> {
+    /// Link a cell of the owned design to the library cell with the same name.
+    /// Libraries are searched in the order they were added.
+    /// Returns the library cell or `None` if no library defines a cell with this name.
+    ///
+    /// Returns an error if the cell is not a leaf cell or if the pin names of the cell
+    /// differ from the pin names of the library cell. In this case the cell is not linked.
+    pub fn link_cell(
+        &mut self,
+        cell: &T::CellId,
+    ) -> Result<Option<(LibraryId, T::CellId)>, LibraryError<T::NameType>> {
+        let name = self.owned.cell_name(cell);
+        if self.owned.num_child_instances(cell) > 0 {
+            return Err(LibraryError::NotALeafCell(name));
+        }
+        let target = match self.find_library_cell(name.borrow()) {
+            Some(target) => target,
+            None => return Ok(None),
+        };
+        let (lib_id, lib_cell) = &target;
+        let lib = self.lib(lib_id);
+        let pins: BTreeSet<_> = self
+            .owned
+            .each_pin(cell)
+            .map(|p| self.owned.pin_name(&p))
+            .collect();
+        let lib_pins: BTreeSet<_> = lib.each_pin(lib_cell).map(|p| lib.pin_name(&p)).collect();
+        if pins != lib_pins {
+            return Err(LibraryError::PinMismatch {
+                cell: name,
+                library: *lib_id,
+                missing_in_library: pins.difference(&lib_pins).cloned().collect(),
+                missing_in_cell: lib_pins.difference(&pins).cloned().collect(),
+            });
+        }
+        self.unlink_cell(cell);
+        self.links.insert(cell.clone(), target.clone());
+        self.stubs.insert(target.clone(), cell.clone());
+        Ok(Some(target))
+    }
+
+    /// Link all leaf cells of the owned design to library cells with the same name.
+    /// Cells whose pins do not match the pins of the library cell are not linked,
+    /// [`LibraryWrapper::link_cell()`] tells the reason.
+    /// Returns the number of linked cells.
+    pub fn link_all(&mut self) -> usize {
+        let leaf_cells: Vec<_> = self
+            .owned
+            .each_cell()
+            .filter(|c| self.owned.num_child_instances(c) == 0)
+            .collect();
+        leaf_cells
+            .iter()
+            .filter(|c| match self.link_cell(c) {
+                Ok(target) => target.is_some(),
+                Err(err) => {
+                    log::warn!("{}", err);
+                    false
+                }
+            })
+            .count()
+    }
+}
+
+impl<'a, T: NetlistEdit> LibraryWrapper<'a, T> {
+    /// Create a stub cell in the owned design for a library cell and link it.
+    /// The stub gets the same name and the same pins as the library cell.
+    /// Returns the existing stub if the library cell is already linked.
+    ///
+    /// # Panics
+    /// Panics if the name of the library cell is already used in the owned design
+    /// or if the library ID was not created by this wrapper.
+    pub fn create_stub_cell(&mut self, library_cell: &(LibraryId, T::CellId)) -> T::CellId {
+        if let Some(stub) = self.stubs.get(library_cell) {
+            return stub.clone();
+        }
+        let (lib_id, cell) = library_cell;
+        assert_ne!(lib_id, &LibraryId::OWNED, "Cell is not a library cell.");
+        let lib = self
+            .read_only_library(lib_id)
+            .unwrap_or_else(|err| panic!("{}", err));
+        let name = lib.cell_name(cell);
+        assert!(
+            self.owned.cell_by_name(name.borrow()).is_none(),
+            "Cell name is already used in the design: {}",
+            &name
+        );
+        let stub = self.owned.create_cell(name);
+        lib.for_each_pin(cell, |pin| {
+            self.owned
+                .create_pin(&stub, lib.pin_name(&pin), lib.pin_direction(&pin));
+        });
+        self.links.insert(stub.clone(), library_cell.clone());
+        self.stubs.insert(library_cell.clone(), stub.clone());
+        stub
+    }
+
+    /// Create an instance of a cell in a cell of the owned design.
+    /// Library cells are instantiated by means of a linked stub cell which is created if necessary.
+    pub fn create_cell_instance(
+        &mut self,
+        parent_cell: &T::CellId,
+        template_cell: &(LibraryId, T::CellId),
+        name: Option<T::NameType>,
+    ) -> (LibraryId, T::CellInstId) {
+        let template = match template_cell {
+            (lib_id, cell) if lib_id == &LibraryId::OWNED => cell.clone(),
+            library_cell => self.create_stub_cell(library_cell),
+        };
+        let inst = self
+            .owned
+            .create_cell_instance(parent_cell, &template, name);
+        (LibraryId::OWNED, inst)
+    }
 }
 
 impl<'a, T> HierarchyBase for LibraryWrapper<'a, T>
@@ -57,10 +426,9 @@ where
     /// Find a cell by name.
     /// Precedence is: Current data base, then libraries in the order where they where added.
     fn cell_by_name(&self, name: &str) -> Option<Self::CellId> {
-        // Find in libraries.
         self.libraries()
-            .flat_map(|(lib_id, lib)| lib.cell_by_name(name).map(|cell| (lib_id, cell)))
-            .next()
+            .find_map(|(lib_id, lib)| lib.cell_by_name(name).map(|cell| (lib_id, cell)))
+            .map(|cell| self.resolve(cell))
     }
 
     fn cell_instance_by_name(
@@ -68,71 +436,696 @@ where
         (lib_id, parent_cell): &Self::CellId,
         name: &str,
     ) -> Option<Self::CellInstId> {
-        self.get_library(lib_id)
+        self.lib(lib_id)
             .cell_instance_by_name(parent_cell, name)
             .map(|inst| (*lib_id, inst))
     }
 
     fn cell_name(&self, (lib_id, cell): &Self::CellId) -> Self::NameType {
-        self.get_library(lib_id).cell_name(cell)
+        self.lib(lib_id).cell_name(cell)
     }
 
     fn cell_instance_name(&self, (lib_id, cell_inst): &Self::CellInstId) -> Option<Self::NameType> {
-        self.get_library(lib_id).cell_instance_name(cell_inst)
+        self.lib(lib_id).cell_instance_name(cell_inst)
     }
 
     fn parent_cell(&self, (lib_id, cell_instance): &Self::CellInstId) -> Self::CellId {
-        // self.get_library(lib_id)
-        //     .parent_cell(cell_instance)
-        unimplemented!()
+        // Stubs are leaf cells and never the parent of an instance.
+        (*lib_id, self.lib(lib_id).parent_cell(cell_instance))
     }
 
     fn template_cell(&self, (lib_id, cell_instance): &Self::CellInstId) -> Self::CellId {
-        // self.get_library(lib_id)
-        //     .template_cell(cell_instance)
-        unimplemented!()
+        self.resolve((*lib_id, self.lib(lib_id).template_cell(cell_instance)))
     }
 
-    fn for_each_cell<F>(&self, f: F)
+    fn cell_exists(&self, cell: &Self::CellId) -> bool {
+        self.lib(&cell.0).cell_exists(&cell.1) && !self.is_stub(cell)
+    }
+
+    fn cell_instance_exists(&self, (lib_id, cell_inst): &Self::CellInstId) -> bool {
+        self.lib(lib_id).cell_instance_exists(cell_inst)
+    }
+
+    fn for_each_cell<F>(&self, mut f: F)
     where
         F: FnMut(Self::CellId) -> (),
     {
-        unimplemented!()
+        for (lib_id, lib) in self.libraries() {
+            lib.for_each_cell(|cell| {
+                let cell = (lib_id, cell);
+                if !self.is_stub(&cell) {
+                    f(cell)
+                }
+            })
+        }
     }
 
-    fn for_each_cell_instance<F>(&self, cell: &Self::CellId, f: F)
+    fn for_each_cell_instance<F>(&self, (lib_id, cell): &Self::CellId, mut f: F)
     where
         F: FnMut(Self::CellInstId) -> (),
     {
-        unimplemented!()
+        self.lib(lib_id)
+            .for_each_cell_instance(cell, |inst| f((*lib_id, inst)))
     }
 
-    fn for_each_cell_dependency<F>(&self, cell: &Self::CellId, f: F)
+    fn for_each_cell_dependency<F>(&self, (lib_id, cell): &Self::CellId, mut f: F)
     where
         F: FnMut(Self::CellId) -> (),
     {
-        unimplemented!()
+        self.lib(lib_id)
+            .for_each_cell_dependency(cell, |dep| f(self.resolve((*lib_id, dep))))
     }
 
-    fn for_each_dependent_cell<F>(&self, cell: &Self::CellId, f: F)
+    fn for_each_dependent_cell<F>(&self, cell: &Self::CellId, mut f: F)
     where
         F: FnMut(Self::CellId) -> (),
     {
-        unimplemented!()
+        let (lib_id, cell_id) = cell;
+        self.lib(lib_id)
+            .for_each_dependent_cell(cell_id, |dep| f((*lib_id, dep)));
+        // Cells of the owned design which use the library cell.
+        if let Some(stub) = self.stubs.get(cell) {
+            self.owned
+                .for_each_dependent_cell(stub, |dep| f((LibraryId::OWNED, dep)));
+        }
     }
 
-    fn for_each_cell_reference<F>(&self, cell: &Self::CellId, f: F)
+    fn for_each_cell_reference<F>(&self, cell: &Self::CellId, mut f: F)
     where
         F: FnMut(Self::CellInstId) -> (),
     {
-        unimplemented!()
+        let (lib_id, cell_id) = cell;
+        self.lib(lib_id)
+            .for_each_cell_reference(cell_id, |inst| f((*lib_id, inst)));
+        // Instances in the owned design.
+        if let Some(stub) = self.stubs.get(cell) {
+            self.owned
+                .for_each_cell_reference(stub, |inst| f((LibraryId::OWNED, inst)));
+        }
+    }
+
+    fn cell_instance_array_size(&self, (lib_id, cell_inst): &Self::CellInstId) -> (u32, u32) {
+        self.lib(lib_id).cell_instance_array_size(cell_inst)
     }
 
-    fn num_child_instances(&self, cell: &Self::CellId) -> usize {
-        unimplemented!()
+    fn num_child_instances(&self, (lib_id, cell): &Self::CellId) -> usize {
+        self.lib(lib_id).num_child_instances(cell)
     }
 
     fn num_cells(&self) -> usize {
-        unimplemented!()
+        self.libraries()
+            .map(|(_, lib)| lib.num_cells())
+            .sum::<usize>()
+            - self.links.len()
+    }
+
+    fn get_chip_property(&self, key: &Self::NameType) -> Option<PropertyValue> {
+        self.owned.get_chip_property(key)
+    }
+
+    fn get_cell_property(
+        &self,
+        (lib_id, cell): &Self::CellId,
+        key: &Self::NameType,
+    ) -> Option<PropertyValue> {
+        self.lib(lib_id).get_cell_property(cell, key)
+    }
+
+    fn get_cell_instance_property(
+        &self,
+        (lib_id, inst): &Self::CellInstId,
+        key: &Self::NameType,
+    ) -> Option<PropertyValue> {
+        self.lib(lib_id).get_cell_instance_property(inst, key)
+    }
+
+    fn for_each_chip_property<F>(&self, f: F)
+    where
+        F: FnMut(&Self::NameType, &PropertyValue) -> (),
+    {
+        self.owned.for_each_chip_property(f)
+    }
+
+    fn for_each_cell_property<F>(&self, (lib_id, cell): &Self::CellId, f: F)
+    where
+        F: FnMut(&Self::NameType, &PropertyValue) -> (),
+    {
+        self.lib(lib_id).for_each_cell_property(cell, f)
+    }
+
+    fn for_each_cell_instance_property<F>(&self, (lib_id, inst): &Self::CellInstId, f: F)
+    where
+        F: FnMut(&Self::NameType, &PropertyValue) -> (),
+    {
+        self.lib(lib_id).for_each_cell_instance_property(inst, f)
+    }
+}
+
+impl<'a, T: NetlistBase> LibraryWrapper<'a, T> {
+    /// Replace pins of linked stub cells by the pins of the library cells.
+    fn resolve_pin(&self, (lib_id, pin): (LibraryId, T::PinId)) -> (LibraryId, T::PinId) {
+        if lib_id == LibraryId::OWNED {
+            let cell = self.owned.parent_cell_of_pin(&pin);
+            if let Some((target_lib, target)) = self.links.get(&cell) {
+                // Pins which were added to the stub after linking stay pins of the stub.
+                let name = self.owned.pin_name(&pin);
+                if let Some(target_pin) = self.lib(target_lib).pin_by_name(target, name.borrow()) {
+                    return (*target_lib, target_pin);
+                }
+            }
+        }
+        (lib_id, pin)
+    }
+}
+
+impl<'a, T: NetlistBase> NetlistBase for LibraryWrapper<'a, T> {
+    type PinId = (LibraryId, T::PinId);
+    type PinInstId = (LibraryId, T::PinInstId);
+    type NetId = (LibraryId, T::NetId);
+
+    fn template_pin(&self, (lib_id, pin_instance): &Self::PinInstId) -> Self::PinId {
+        self.resolve_pin((*lib_id, self.lib(lib_id).template_pin(pin_instance)))
+    }
+
+    fn pin_direction(&self, (lib_id, pin): &Self::PinId) -> Direction {
+        self.lib(lib_id).pin_direction(pin)
+    }
+
+    fn pin_name(&self, (lib_id, pin): &Self::PinId) -> Self::NameType {
+        self.lib(lib_id).pin_name(pin)
+    }
+
+    fn pin_by_name(&self, (lib_id, cell): &Self::CellId, name: &str) -> Option<Self::PinId> {
+        self.lib(lib_id)
+            .pin_by_name(cell, name)
+            .map(|pin| (*lib_id, pin))
+    }
+
+    fn parent_cell_of_pin(&self, (lib_id, pin): &Self::PinId) -> Self::CellId {
+        (*lib_id, self.lib(lib_id).parent_cell_of_pin(pin))
+    }
+
+    fn parent_of_pin_instance(&self, (lib_id, pin_inst): &Self::PinInstId) -> Self::CellInstId {
+        (*lib_id, self.lib(lib_id).parent_of_pin_instance(pin_inst))
+    }
+
+    fn pin_instance(
+        &self,
+        (inst_lib_id, cell_inst): &Self::CellInstId,
+        (pin_lib_id, pin): &Self::PinId,
+    ) -> Self::PinInstId {
+        let lib = self.lib(inst_lib_id);
+        if inst_lib_id == pin_lib_id {
+            (*inst_lib_id, lib.pin_instance(cell_inst, pin))
+        } else {
+            // The template of the instance is a stub. Find the corresponding pin of the stub.
+            let stub = lib.template_cell(cell_inst);
+            let name = self.lib(pin_lib_id).pin_name(pin);
+            // Linking guarantees that the stub has the same pins as the library cell.
+            let stub_pin = lib.pin_by_name(&stub, name.borrow()).unwrap_or_else(|| {
+                panic!(
+                    "Pin '{}' is not a pin of cell '{}'.",
+                    name,
+                    lib.cell_name(&stub)
+                )
+            });
+            (*inst_lib_id, lib.pin_instance(cell_inst, &stub_pin))
+        }
+    }
+
+    fn parent_cell_of_net(&self, (lib_id, net): &Self::NetId) -> Self::CellId {
+        (*lib_id, self.lib(lib_id).parent_cell_of_net(net))
+    }
+
+    fn net_of_pin(&self, (lib_id, pin): &Self::PinId) -> Option<Self::NetId> {
+        self.lib(lib_id).net_of_pin(pin).map(|net| (*lib_id, net))
+    }
+
+    fn net_of_pin_instance(&self, (lib_id, pin_instance): &Self::PinInstId) -> Option<Self::NetId> {
+        self.lib(lib_id)
+            .net_of_pin_instance(pin_instance)
+            .map(|net| (*lib_id, net))
+    }
+
+    fn net_zero(&self, (lib_id, cell): &Self::CellId) -> Self::NetId {
+        (*lib_id, self.lib(lib_id).net_zero(cell))
+    }
+
+    fn net_one(&self, (lib_id, cell): &Self::CellId) -> Self::NetId {
+        (*lib_id, self.lib(lib_id).net_one(cell))
+    }
+
+    fn net_by_name(&self, (lib_id, cell): &Self::CellId, name: &str) -> Option<Self::NetId> {
+        self.lib(lib_id)
+            .net_by_name(cell, name)
+            .map(|net| (*lib_id, net))
+    }
+
+    fn net_name(&self, (lib_id, net): &Self::NetId) -> Option<Self::NameType> {
+        self.lib(lib_id).net_name(net)
+    }
+
+    fn pin_exists(&self, (lib_id, pin): &Self::PinId) -> bool {
+        self.lib(lib_id).pin_exists(pin)
+    }
+
+    fn pin_instance_exists(&self, (lib_id, pin_instance): &Self::PinInstId) -> bool {
+        self.lib(lib_id).pin_instance_exists(pin_instance)
+    }
+
+    fn net_exists(&self, (lib_id, net): &Self::NetId) -> bool {
+        self.lib(lib_id).net_exists(net)
+    }
+
+    fn for_each_pin<F>(&self, (lib_id, cell): &Self::CellId, mut f: F)
+    where
+        F: FnMut(Self::PinId) -> (),
+    {
+        self.lib(lib_id).for_each_pin(cell, |pin| f((*lib_id, pin)))
+    }
+
+    fn for_each_pin_instance<F>(&self, (lib_id, cell_inst): &Self::CellInstId, mut f: F)
+    where
+        F: FnMut(Self::PinInstId) -> (),
+    {
+        self.lib(lib_id)
+            .for_each_pin_instance(cell_inst, |pin_inst| f((*lib_id, pin_inst)))
+    }
+
+    fn for_each_internal_net<F>(&self, (lib_id, cell): &Self::CellId, mut f: F)
+    where
+        F: FnMut(Self::NetId) -> (),
+    {
+        self.lib(lib_id)
+            .for_each_internal_net(cell, |net| f((*lib_id, net)))
+    }
+
+    fn num_pins(&self, (lib_id, cell): &Self::CellId) -> usize {
+        self.lib(lib_id).num_pins(cell)
+    }
+
+    fn for_each_pin_of_net<F>(&self, (lib_id, net): &Self::NetId, mut f: F)
+    where
+        F: FnMut(Self::PinId) -> (),
+    {
+        self.lib(lib_id)
+            .for_each_pin_of_net(net, |pin| f((*lib_id, pin)))
+    }
+
+    fn for_each_pin_instance_of_net<F>(&self, (lib_id, net): &Self::NetId, mut f: F)
+    where
+        F: FnMut(Self::PinInstId) -> (),
+    {
+        self.lib(lib_id)
+            .for_each_pin_instance_of_net(net, |pin_inst| f((*lib_id, pin_inst)))
+    }
+}
+
+impl<'a, T: BusBase> BusBase for LibraryWrapper<'a, T> {
+    type PinBusId = (LibraryId, T::PinBusId);
+    type NetBusId = (LibraryId, T::NetBusId);
+
+    fn pin_bus_by_name(&self, (lib_id, cell): &Self::CellId, name: &str) -> Option<Self::PinBusId> {
+        self.lib(lib_id)
+            .pin_bus_by_name(cell, name)
+            .map(|bus| (*lib_id, bus))
+    }
+
+    fn pin_bus_name(&self, (lib_id, bus): &Self::PinBusId) -> Self::NameType {
+        self.lib(lib_id).pin_bus_name(bus)
+    }
+
+    fn parent_cell_of_pin_bus(&self, (lib_id, bus): &Self::PinBusId) -> Self::CellId {
+        (*lib_id, self.lib(lib_id).parent_cell_of_pin_bus(bus))
+    }
+
+    fn pin_bus_bit_order(&self, (lib_id, bus): &Self::PinBusId) -> BitOrder {
+        self.lib(lib_id).pin_bus_bit_order(bus)
+    }
+
+    fn bus_of_pin(&self, (lib_id, pin): &Self::PinId) -> Option<Self::PinBusId> {
+        self.lib(lib_id).bus_of_pin(pin).map(|bus| (*lib_id, bus))
+    }
+
+    fn net_bus_by_name(&self, (lib_id, cell): &Self::CellId, name: &str) -> Option<Self::NetBusId> {
+        self.lib(lib_id)
+            .net_bus_by_name(cell, name)
+            .map(|bus| (*lib_id, bus))
+    }
+
+    fn net_bus_name(&self, (lib_id, bus): &Self::NetBusId) -> Self::NameType {
+        self.lib(lib_id).net_bus_name(bus)
+    }
+
+    fn parent_cell_of_net_bus(&self, (lib_id, bus): &Self::NetBusId) -> Self::CellId {
+        (*lib_id, self.lib(lib_id).parent_cell_of_net_bus(bus))
+    }
+
+    fn net_bus_bit_order(&self, (lib_id, bus): &Self::NetBusId) -> BitOrder {
+        self.lib(lib_id).net_bus_bit_order(bus)
+    }
+
+    fn bus_of_net(&self, (lib_id, net): &Self::NetId) -> Option<Self::NetBusId> {
+        self.lib(lib_id).bus_of_net(net).map(|bus| (*lib_id, bus))
+    }
+
+    fn for_each_pin_bus<F>(&self, (lib_id, cell): &Self::CellId, mut f: F)
+    where
+        F: FnMut(Self::PinBusId) -> (),
+    {
+        self.lib(lib_id)
+            .for_each_pin_bus(cell, |bus| f((*lib_id, bus)))
+    }
+
+    fn for_each_pin_of_bus<F>(&self, (lib_id, bus): &Self::PinBusId, mut f: F)
+    where
+        F: FnMut(Self::PinId) -> (),
+    {
+        self.lib(lib_id)
+            .for_each_pin_of_bus(bus, |pin| f((*lib_id, pin)))
+    }
+
+    fn for_each_net_bus<F>(&self, (lib_id, cell): &Self::CellId, mut f: F)
+    where
+        F: FnMut(Self::NetBusId) -> (),
+    {
+        self.lib(lib_id)
+            .for_each_net_bus(cell, |bus| f((*lib_id, bus)))
+    }
+
+    fn for_each_net_of_bus<F>(&self, (lib_id, bus): &Self::NetBusId, mut f: F)
+    where
+        F: FnMut(Self::NetId) -> (),
+    {
+        self.lib(lib_id)
+            .for_each_net_of_bus(bus, |net| f((*lib_id, net)))
+    }
+}
+
+impl<'a, T: LayoutBase> LibraryWrapper<'a, T> {
+    /// Find the layer of a library which corresponds to a layer of the owned design.
+    fn library_layer(&self, lib_id: &LibraryId, layer: &T::LayerId) -> Option<T::LayerId> {
+        if lib_id == &LibraryId::OWNED {
+            Some(layer.clone())
+        } else {
+            let info = self.owned.layer_info(layer);
+            self.lib(lib_id).find_layer(info.index, info.datatype)
+        }
+    }
+
+    /// Find the layer of the owned design which corresponds to a layer of a library.
+    fn owned_layer(&self, lib_id: &LibraryId, layer: &T::LayerId) -> Option<T::LayerId> {
+        if lib_id == &LibraryId::OWNED {
+            Some(layer.clone())
+        } else {
+            let info = self.lib(lib_id).layer_info(layer);
+            self.owned.find_layer(info.index, info.datatype)
+        }
+    }
+
+    /// Find the layer of the owned design which corresponds to the layer of a visible shape.
+    ///
+    /// # Panics
+    /// Panics if the layer does not exist in the owned design. Shapes on such layers
+    /// are not visible, just like shapes which do not exist.
+    fn visible_layer(&self, lib_id: &LibraryId, layer: &T::LayerId) -> T::LayerId {
+        self.owned_layer(lib_id, layer).unwrap_or_else(|| {
+            let info = self.lib(lib_id).layer_info(layer);
+            panic!(
+                "Shape is not visible: Layer {}/{} of library {:?} does not exist in the design.",
+                info.index, info.datatype, lib_id
+            )
+        })
+    }
+}
+
+impl<'a, T: LayoutBase> LayoutBase for LibraryWrapper<'a, T> {
+    type Coord = T::Coord;
+    type Area = T::Area;
+    type LayerId = T::LayerId;
+    type ShapeId = (LibraryId, T::ShapeId);
+
+    fn dbu(&self) -> Self::Coord {
+        self.owned.dbu()
+    }
+
+    fn each_layer(&self) -> Box<dyn Iterator<Item = Self::LayerId> + '_> {
+        self.owned.each_layer()
+    }
+
+    fn layer_info(&self, layer: &Self::LayerId) -> LayerInfo<Self::NameType> {
+        self.owned.layer_info(layer)
+    }
+
+    fn find_layer(&self, index: UInt, datatype: UInt) -> Option<Self::LayerId> {
+        self.owned.find_layer(index, datatype)
+    }
+
+    fn layer_by_name(&self, name: &str) -> Option<Self::LayerId> {
+        self.owned.layer_by_name(name)
+    }
+
+    fn bounding_box_per_layer(
+        &self,
+        (lib_id, cell): &Self::CellId,
+        layer: &Self::LayerId,
+    ) -> Option<Rect<Self::Coord>> {
+        let layer = self.library_layer(lib_id, layer)?;
+        self.lib(lib_id).bounding_box_per_layer(cell, &layer)
+    }
+
+    fn each_shape_id(
+        &self,
+        (lib_id, cell): &Self::CellId,
+        layer: &Self::LayerId,
+    ) -> Box<dyn Iterator<Item = Self::ShapeId> + '_> {
+        let lib_id = *lib_id;
+        match self.library_layer(&lib_id, layer) {
+            Some(layer) => Box::new(
+                self.lib(&lib_id)
+                    .each_shape_id(cell, &layer)
+                    .map(move |id| (lib_id, id)),
+            ),
+            None => Box::new(std::iter::empty()),
+        }
+    }
+
+    fn for_each_shape<F>(&self, (lib_id, cell): &Self::CellId, layer: &Self::LayerId, mut f: F)
+    where
+        F: FnMut(&Self::ShapeId, &Geometry<Self::Coord>) -> (),
+    {
+        if let Some(layer) = self.library_layer(lib_id, layer) {
+            self.lib(lib_id)
+                .for_each_shape(cell, &layer, |id, geometry| {
+                    f(&(*lib_id, id.clone()), geometry)
+                })
+        }
+    }
+
+    fn with_shape<F, R>(&self, (lib_id, shape_id): &Self::ShapeId, mut f: F) -> R
+    where
+        F: FnMut(&Self::LayerId, &Geometry<Self::Coord>) -> R,
+    {
+        self.lib(lib_id).with_shape(shape_id, |layer, geometry| {
+            f(&self.visible_layer(lib_id, layer), geometry)
+        })
+    }
+
+    fn parent_of_shape(&self, (lib_id, shape_id): &Self::ShapeId) -> (Self::CellId, Self::LayerId) {
+        let (cell, layer) = self.lib(lib_id).parent_of_shape(shape_id);
+        ((*lib_id, cell), self.visible_layer(lib_id, &layer))
+    }
+
+    fn layer_exists(&self, layer: &Self::LayerId) -> bool {
+        self.owned.layer_exists(layer)
+    }
+
+    fn shape_exists(&self, (lib_id, shape_id): &Self::ShapeId) -> bool {
+        // Shapes on layers which do not exist in the design are not visible.
+        let lib = self.lib(lib_id);
+        lib.shape_exists(shape_id)
+            && self
+                .owned_layer(lib_id, &lib.parent_of_shape(shape_id).1)
+                .is_some()
+    }
+
+    fn get_transform(
+        &self,
+        (lib_id, cell_inst): &Self::CellInstId,
+    ) -> SimpleTransform<Self::Coord> {
+        self.lib(lib_id).get_transform(cell_inst)
+    }
+
+    fn cell_instance_array(
+        &self,
+        (lib_id, cell_inst): &Self::CellInstId,
+    ) -> Option<InstanceArray<Self::Coord>> {
+        self.lib(lib_id).cell_instance_array(cell_inst)
+    }
+
+    fn placement_status(&self, (lib_id, cell_inst): &Self::CellInstId) -> PlacementStatus {
+        self.lib(lib_id).placement_status(cell_inst)
+    }
+
+    fn get_shape_property(
+        &self,
+        (lib_id, shape_id): &Self::ShapeId,
+        key: &Self::NameType,
+    ) -> Option<PropertyValue> {
+        self.lib(lib_id).get_shape_property(shape_id, key)
+    }
+
+    fn for_each_shape_property<F>(&self, (lib_id, shape_id): &Self::ShapeId, f: F)
+    where
+        F: FnMut(&Self::NameType, &PropertyValue) -> (),
+    {
+        self.lib(lib_id).for_each_shape_property(shape_id, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip::Chip;
+    use crate::prelude::*;
+
+    fn create_library(cells: &[&str]) -> Chip {
+        let mut lib = Chip::new();
+        let layer = lib.create_layer(1, 0);
+        for name in cells {
+            let cell = lib.create_cell(name.to_string());
+            lib.create_pin(&cell, "A".into(), Direction::Input);
+            lib.create_pin(&cell, "Y".into(), Direction::Output);
+            lib.insert_shape(&cell, &layer, Rect::new((0, 0), (10, 10)).into());
+        }
+        lib
+    }
+
+    #[test]
+    fn test_name_precedence() {
+        let stdcells = create_library(&["INV", "NAND2"]);
+        let io = create_library(&["INV", "PAD"]);
+        let mut design = Chip::new();
+        let top = design.create_cell("TOP".into());
+        let nand2 = design.create_cell("NAND2".into());
+        design.create_pin(&nand2, "A".into(), Direction::Input);
+        design.create_pin(&nand2, "Y".into(), Direction::Output);
+
+        let mut lib = LibraryWrapper::new(design);
+        let stdcells_id = lib.add_library(&stdcells);
+        let io_id = lib.add_library(&io);
+
+        assert_eq!(lib.num_cells(), 2 + 2 + 2);
+        assert_eq!(lib.cell_by_name("TOP"), Some((LibraryId::OWNED, top)));
+        assert_eq!(lib.cell_by_name("INV").unwrap().0, stdcells_id);
+        assert_eq!(lib.cell_by_name("PAD").unwrap().0, io_id);
+        // The cell of the design shadows the library cell.
+        assert_eq!(lib.cell_by_name("NAND2").unwrap().0, LibraryId::OWNED);
+
+        let conflicts = lib.name_conflicts();
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(conflicts[0].name, "INV");
+        assert_eq!(conflicts[0].definitions, vec![stdcells_id, io_id]);
+        assert_eq!(conflicts[1].name, "NAND2");
+        assert_eq!(
+            conflicts[1].definitions,
+            vec![LibraryId::OWNED, stdcells_id]
+        );
+
+        // After linking the NAND2 of the design is replaced by the library cell.
+        assert_eq!(lib.link_all(), 1);
+        assert_eq!(lib.cell_by_name("NAND2").unwrap().0, stdcells_id);
+        assert_eq!(lib.num_cells(), 1 + 2 + 2);
+        assert_eq!(lib.name_conflicts().len(), 1);
+    }
+
+    #[test]
+    fn test_link_cell_errors() {
+        let stdcells = create_library(&["INV"]);
+        let mut design = Chip::new();
+        let inv = design.create_cell("INV".into());
+        design.create_pin(&inv, "A".into(), Direction::Input);
+        design.create_pin(&inv, "Z".into(), Direction::Output);
+
+        let mut lib = LibraryWrapper::new(design);
+        let stdcells_id = lib.add_library(&stdcells);
+
+        assert_eq!(
+            lib.link_cell(&inv),
+            Err(LibraryError::PinMismatch {
+                cell: "INV".into(),
+                library: stdcells_id,
+                missing_in_library: vec!["Z".into()],
+                missing_in_cell: vec!["Y".into()],
+            })
+        );
+        assert_eq!(lib.link_all(), 0);
+        assert_eq!(lib.linked_cell(&inv), None);
+
+        // ID of a library of another wrapper.
+        let unknown_id = LibraryId(2);
+        assert_eq!(
+            lib.library(&unknown_id).err(),
+            Some(LibraryError::UnknownLibrary(unknown_id))
+        );
+    }
+
+    #[test]
+    fn test_missing_library_layer() {
+        let stdcells = create_library(&["INV"]);
+        let mut lib = LibraryWrapper::new(Chip::new());
+        lib.add_library(&stdcells);
+        let inv = lib.cell_by_name("INV").unwrap();
+        let layer = stdcells.find_layer(1, 0).unwrap();
+        let shape = (
+            inv.0,
+            stdcells.each_shape_id(&inv.1, &layer).next().unwrap(),
+        );
+        // The layer of the shape does not exist in the design.
+        assert!(!lib.shape_exists(&shape));
+    }
+
+    #[test]
+    fn test_instantiate_library_cells() {
+        let stdcells = create_library(&["INV"]);
+        let mut design = Chip::new();
+        let layer = design.create_layer(1, 0);
+        let top = design.create_cell("TOP".into());
+
+        let mut lib = LibraryWrapper::new(design);
+        lib.add_library(&stdcells);
+        let inv = lib.cell_by_name("INV").unwrap();
+        let inst1 = lib.create_cell_instance(&top, &inv, Some("inv1".into()));
+        let inst2 = lib.create_cell_instance(&top, &inv, Some("inv2".into()));
+        // Only one stub is created.
+        assert_eq!(lib.owned().num_cells(), 2);
+
+        let top = (LibraryId::OWNED, top);
+        assert_eq!(lib.template_cell(&inst1), inv);
+        assert_eq!(lib.each_cell_reference(&inv).count(), 2);
+        assert_eq!(lib.each_dependent_cell_vec(&inv), vec![top]);
+        assert_eq!(lib.each_cell_dependency_vec(&top), vec![inv]);
+
+        // Connect the instances.
+        let y = lib.pin_by_name(&inv, "Y").unwrap();
+        let a = lib.pin_by_name(&inv, "A").unwrap();
+        let inst1_y = lib.pin_instance(&inst1, &y);
+        let inst2_a = lib.pin_instance(&inst2, &a);
+        assert_eq!(lib.template_pin(&inst1_y), y);
+        let net = lib.owned_mut().create_net(&top.1, Some("n".into()));
+        lib.owned_mut().connect_pin_instance(&inst1_y.1, Some(net));
+        lib.owned_mut().connect_pin_instance(&inst2_a.1, Some(net));
+        assert_eq!(
+            lib.net_of_pin_instance(&inst2_a),
+            Some((LibraryId::OWNED, net))
+        );
+
+        // Shapes of the library cells are visible on the layers of the design.
+        let mut num_shapes = 0;
+        lib.for_each_shape_recursive(&top, &layer, |_, id, _| {
+            assert_eq!(lib.shape_layer(id), layer);
+            num_shapes += 1;
+        });
+        assert_eq!(num_shapes, 2);
     }
 }
@@ -0,0 +1,321 @@
+// SPDX-FileCopyrightText: 2022 Thomas Kramer
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Multi-bit buses of pins and nets.
+//!
+//! A bus groups single-bit pins or nets of the same cell. The bits of a bus are indexed
+//! starting from `0`, the index `0` denotes the least significant bit.
+//! The [`BitOrder`] only remembers how the bus was declared (for example `a[7:0]` or `a[0:7]`)
+//! such that writers can reproduce the original declaration.
+//!
+//! Buses are accessed with the [`BusBase`] trait and created with the [`BusEdit`] trait.
+//! Netlists which don't support buses only need to implement [`NetlistBase`] and [`NetlistEdit`].
+//!
+//! # Example
+//!
+//! ```
+//! use libreda_db::prelude::*;
+//!
+//! let mut chip = Chip::new();
+//! let top = chip.create_cell("TOP".into());
+//! let data = chip.create_pin_bus(&top, "data".into(), Direction::Input, 8, BitOrder::Descending);
+//! let bit_3 = chip.pin_bus_bit(&data, 3).unwrap();
+//! assert_eq!(chip.pin_name(&bit_3), "data[3]");
+//! assert_eq!(chip.pin_bit_index(&bit_3), Some(3));
+//! ```
+
+use super::prelude::*;
+use itertools::Itertools;
+use std::borrow::Borrow;
+use std::hash::Hash;
+
+/// Order in which the bits of a bus are declared.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum BitOrder {
+    /// The most significant bit comes first, like `a[7:0]`.
+    Descending,
+    /// The least significant bit comes first, like `a[0:7]`.
+    Ascending,
+}
+
+impl Default for BitOrder {
+    fn default() -> Self {
+        BitOrder::Descending
+    }
+}
+
+/// Get the name of a single bit of a bus, for example `data[3]`.
+pub fn bus_bit_name(bus_name: &str, bit_index: usize) -> String {
+    format!("{}[{}]", bus_name, bit_index)
+}
+
+/// Access to the buses of a netlist.
+///
+/// Pins and nets of a cell can be grouped into multi-bit buses. A bus has a name and
+/// an ordered list of bits. Each pin or net is part of at most one bus.
+/// Removing a pin or net also removes the bus which contains it.
+pub trait BusBase: NetlistBase {
+    /// Pin bus identifier type. Uniquely identifies a bus of pins in the whole netlist.
+    type PinBusId: Eq + Hash + Clone + std::fmt::Debug + 'static;
+    /// Net bus identifier type. Uniquely identifies a bus of nets in the whole netlist.
+    type NetBusId: Eq + Hash + Clone + std::fmt::Debug + 'static;
+
+    /// Find a pin bus by its name.
+    /// Returns `None` if no such bus can be found.
+    fn pin_bus_by_name(&self, parent_circuit: &Self::CellId, name: &str) -> Option<Self::PinBusId>;
+
+    /// Get the name of the pin bus.
+    fn pin_bus_name(&self, bus: &Self::PinBusId) -> Self::NameType;
+
+    /// Get the ID of the cell which contains the pin bus.
+    fn parent_cell_of_pin_bus(&self, bus: &Self::PinBusId) -> Self::CellId;
+
+    /// Get the order in which the bits of the pin bus have been declared.
+    fn pin_bus_bit_order(&self, bus: &Self::PinBusId) -> BitOrder;
+
+    /// Get the bus which contains this pin.
+    /// Returns `None` if the pin is not part of a bus.
+    fn bus_of_pin(&self, pin: &Self::PinId) -> Option<Self::PinBusId>;
+
+    /// Get the index of the pin within its bus.
+    /// Returns `None` if the pin is not part of a bus.
+    fn pin_bit_index(&self, pin: &Self::PinId) -> Option<usize> {
+        // Inefficient default implementation.
+        let bus = self.bus_of_pin(pin)?;
+        self.each_pin_of_bus_vec(&bus).iter().position(|p| p == pin)
+    }
+
+    /// Find a net bus by its name.
+    /// Returns `None` if no such bus can be found.
+    fn net_bus_by_name(&self, parent_circuit: &Self::CellId, name: &str) -> Option<Self::NetBusId>;
+
+    /// Get the name of the net bus.
+    fn net_bus_name(&self, bus: &Self::NetBusId) -> Self::NameType;
+
+    /// Get the ID of the cell which contains the net bus.
+    fn parent_cell_of_net_bus(&self, bus: &Self::NetBusId) -> Self::CellId;
+
+    /// Get the order in which the bits of the net bus have been declared.
+    fn net_bus_bit_order(&self, bus: &Self::NetBusId) -> BitOrder;
+
+    /// Get the bus which contains this net.
+    /// Returns `None` if the net is not part of a bus.
+    fn bus_of_net(&self, net: &Self::NetId) -> Option<Self::NetBusId>;
+
+    /// Get the index of the net within its bus.
+    /// Returns `None` if the net is not part of a bus.
+    fn net_bit_index(&self, net: &Self::NetId) -> Option<usize> {
+        // Inefficient default implementation.
+        let bus = self.bus_of_net(net)?;
+        self.each_net_of_bus_vec(&bus).iter().position(|n| n == net)
+    }
+    /// Call a function for each pin bus of the circuit.
+    fn for_each_pin_bus<F>(&self, circuit: &Self::CellId, f: F)
+    where
+        F: FnMut(Self::PinBusId) -> ();
+
+    /// Get a `Vec` with the IDs of all pin buses of this circuit.
+    fn each_pin_bus_vec(&self, circuit: &Self::CellId) -> Vec<Self::PinBusId> {
+        let mut v = Vec::new();
+        self.for_each_pin_bus(circuit, |b| v.push(b));
+        v
+    }
+
+    /// Call a function for each pin of the bus. The pins are visited in the order
+    /// of their bit index, starting with bit `0`.
+    fn for_each_pin_of_bus<F>(&self, bus: &Self::PinBusId, f: F)
+    where
+        F: FnMut(Self::PinId) -> ();
+
+    /// Get a `Vec` with the pins of the bus. The position in the `Vec` is the bit index.
+    fn each_pin_of_bus_vec(&self, bus: &Self::PinBusId) -> Vec<Self::PinId> {
+        let mut v = Vec::new();
+        self.for_each_pin_of_bus(bus, |p| v.push(p));
+        v
+    }
+
+    /// Get the pin of the bus with the given bit index.
+    /// Returns `None` if the index is out of range.
+    fn pin_bus_bit(&self, bus: &Self::PinBusId, bit_index: usize) -> Option<Self::PinId> {
+        self.each_pin_of_bus_vec(bus).into_iter().nth(bit_index)
+    }
+
+    /// Get the number of bits of the pin bus.
+    fn pin_bus_width(&self, bus: &Self::PinBusId) -> usize {
+        let mut n = 0;
+        self.for_each_pin_of_bus(bus, |_| n += 1);
+        n
+    }
+
+    /// Call a function for each net bus of the circuit.
+    fn for_each_net_bus<F>(&self, circuit: &Self::CellId, f: F)
+    where
+        F: FnMut(Self::NetBusId) -> ();
+
+    /// Get a `Vec` with the IDs of all net buses of this circuit.
+    fn each_net_bus_vec(&self, circuit: &Self::CellId) -> Vec<Self::NetBusId> {
+        let mut v = Vec::new();
+        self.for_each_net_bus(circuit, |b| v.push(b));
+        v
+    }
+
+    /// Call a function for each net of the bus. The nets are visited in the order
+    /// of their bit index, starting with bit `0`.
+    fn for_each_net_of_bus<F>(&self, bus: &Self::NetBusId, f: F)
+    where
+        F: FnMut(Self::NetId) -> ();
+
+    /// Get a `Vec` with the nets of the bus. The position in the `Vec` is the bit index.
+    fn each_net_of_bus_vec(&self, bus: &Self::NetBusId) -> Vec<Self::NetId> {
+        let mut v = Vec::new();
+        self.for_each_net_of_bus(bus, |n| v.push(n));
+        v
+    }
+
+    /// Get the net of the bus with the given bit index.
+    /// Returns `None` if the index is out of range.
+    fn net_bus_bit(&self, bus: &Self::NetBusId, bit_index: usize) -> Option<Self::NetId> {
+        self.each_net_of_bus_vec(bus).into_iter().nth(bit_index)
+    }
+
+    /// Get the number of bits of the net bus.
+    fn net_bus_width(&self, bus: &Self::NetBusId) -> usize {
+        let mut n = 0;
+        self.for_each_net_of_bus(bus, |_| n += 1);
+        n
+    }
+}
+
+/// Create and remove buses of pins and nets.
+pub trait BusEdit: BusBase + NetlistEdit {
+    /// Group existing pins into a bus. The position of a pin in `pins` is its bit index.
+    /// The pins keep their names.
+    ///
+    /// # Panics
+    /// Panics if `pins` is empty, if the pins don't live in the same cell, if a pin
+    /// is already part of a bus or if a pin bus with this name already exists in the cell.
+    fn create_pin_bus_from_pins(
+        &mut self,
+        name: Self::NameType,
+        pins: Vec<Self::PinId>,
+        bit_order: BitOrder,
+    ) -> Self::PinBusId;
+
+    /// Create a multi-bit port.
+    /// Internally creates a pin for every bit of the port. The pin of bit `i` is named `name[i]`.
+    ///
+    /// # Panics
+    /// Panics if `width` is zero.
+    fn create_pin_bus(
+        &mut self,
+        cell: &Self::CellId,
+        name: Self::NameType,
+        direction: Direction,
+        width: usize,
+        bit_order: BitOrder,
+    ) -> Self::PinBusId {
+        assert!(width > 0, "A bus must have at least one bit.");
+        let pins = (0..width)
+            .map(|i| self.create_pin(cell, bus_bit_name(name.borrow(), i).into(), direction))
+            .collect();
+        self.create_pin_bus_from_pins(name, pins, bit_order)
+    }
+
+    /// Remove the bus. The pins of the bus are not removed but become single-bit pins.
+    fn remove_pin_bus(&mut self, bus: &Self::PinBusId);
+
+    /// Group existing nets into a bus. The position of a net in `nets` is its bit index.
+    ///
+    /// # Panics
+    /// Panics if `nets` is empty, if the nets don't live in the same cell, if a net
+    /// is already part of a bus or if a net bus with this name already exists in the cell.
+    fn create_net_bus_from_nets(
+        &mut self,
+        name: Self::NameType,
+        nets: Vec<Self::NetId>,
+        bit_order: BitOrder,
+    ) -> Self::NetBusId;
+
+    /// Create a multi-bit net.
+    /// Internally creates a net for every bit. The net of bit `i` is named `name[i]`.
+    ///
+    /// # Panics
+    /// Panics if `width` is zero.
+    fn create_net_bus(
+        &mut self,
+        parent: &Self::CellId,
+        name: Self::NameType,
+        width: usize,
+        bit_order: BitOrder,
+    ) -> Self::NetBusId {
+        assert!(width > 0, "A bus must have at least one bit.");
+        let nets = (0..width)
+            .map(|i| self.create_net(parent, Some(bus_bit_name(name.borrow(), i).into())))
+            .collect();
+        self.create_net_bus_from_nets(name, nets, bit_order)
+    }
+
+    /// Remove the bus. The nets of the bus are not removed but become single-bit nets.
+    fn remove_net_bus(&mut self, bus: &Self::NetBusId);
+
+    /// Connect each pin of the bus to the net with the same bit index.
+    /// Disconnects all pins of the bus if `nets` is `None`.
+    /// Returns the previously connected nets ordered by bit index.
+    ///
+    /// # Panics
+    /// Panics if the buses don't have the same width.
+    fn connect_pin_bus(
+        &mut self,
+        bus: &Self::PinBusId,
+        nets: Option<&Self::NetBusId>,
+    ) -> Vec<Option<Self::NetId>> {
+        let pins = self.each_pin_of_bus_vec(bus);
+        let nets = match nets {
+            Some(nets) => {
+                let nets = self.each_net_of_bus_vec(nets);
+                assert_eq!(pins.len(), nets.len(), "Bus widths don't match.");
+                nets.into_iter().map(Some).collect()
+            }
+            None => vec![None; pins.len()],
+        };
+        pins.iter()
+            .zip(nets)
+            .map(|(pin, net)| self.connect_pin(pin, net))
+            .collect()
+    }
+
+    /// Connect the pin instances of the bus `bus` of the cell instance `inst` to the nets
+    /// with the same bit index. `bus` is a pin bus of the template cell.
+    /// Disconnects all pin instances of the bus if `nets` is `None`.
+    /// Returns the previously connected nets ordered by bit index.
+    ///
+    /// # Panics
+    /// Panics if the buses don't have the same width.
+    fn connect_pin_bus_instance(
+        &mut self,
+        inst: &Self::CellInstId,
+        bus: &Self::PinBusId,
+        nets: Option<&Self::NetBusId>,
+    ) -> Vec<Option<Self::NetId>> {
+        let pin_insts = self
+            .each_pin_of_bus_vec(bus)
+            .iter()
+            .map(|pin| self.pin_instance(inst, pin))
+            .collect_vec();
+        let nets = match nets {
+            Some(nets) => {
+                let nets = self.each_net_of_bus_vec(nets);
+                assert_eq!(pin_insts.len(), nets.len(), "Bus widths don't match.");
+                nets.into_iter().map(Some).collect()
+            }
+            None => vec![None; pin_insts.len()],
+        };
+        pin_insts
+            .iter()
+            .zip(nets)
+            .map(|(pin_inst, net)| self.connect_pin_instance(pin_inst, net))
+            .collect()
+    }
+}
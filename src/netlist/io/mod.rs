@@ -5,11 +5,15 @@
 
 //! Input and output interface definitions for netlists.
 //!
-//! Implementations for the various netlist formats are located in other crates.
+//! Readers and writers for structural Verilog and SPICE are implemented in [`verilog`] and [`spice`].
+//! Implementations for other netlist formats are located in other crates.
 
 use crate::netlist::traits::{NetlistBase, NetlistEdit};
 use std::io::{Read, Write};
 
+pub mod spice;
+pub mod verilog;
+
 /// Read a netlist from a byte stream.
 pub trait NetlistReader {
     /// Type of error that could happen while reading a netlist.
@@ -0,0 +1,991 @@
+// SPDX-FileCopyrightText: 2022 Thomas Kramer
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Reader and writer for SPICE and CDL subcircuit netlists.
+//!
+//! Each `.SUBCKT` is mapped to a cell. The ports of a subcircuit become the pins of the cell in the
+//! order of declaration. Directions of pins are taken from CDL `*.PININFO` comments if present.
+//!
+//! `X` lines become instances of subcircuits. Device lines (`M`, `R`, `C`, `L`, `D`, `Q`, `J`)
+//! become instances of leaf cells which represent the device. The cell of a device is named after
+//! its model. Resistors, capacitors and inductors without model are instances of the
+//! cells `resistor`, `capacitor` and `inductor`. Device cells are marked with the
+//! [`DEVICE_TYPE_PROPERTY`] cell property which holds the element letter.
+//!
+//! Parameters of instances (`w=1u`) are stored as string properties of the cell instance.
+//! The value of a resistor, capacitor or inductor is stored in the `value` property.
+//!
+//! The node `0` is mapped to the `net_zero` of the cell.
+//!
+//! # Example
+//!
+//! ```
+//! use libreda_db::prelude::*;
+//! use libreda_db::netlist::io::spice::SpiceReader;
+//!
+//! let spice = r"
+//! .SUBCKT INV A Y VDD GND
+//! M1 Y A VDD VDD pmos w=2u l=0.1u
+//! M2 Y A GND GND nmos w=1u l=0.1u
+//! .ENDS
+//! ";
+//!
+//! let chip: Chip = SpiceReader::new().read_netlist(&mut spice.as_bytes()).unwrap();
+//! let inv = chip.cell_by_name("INV").unwrap();
+//! let m1 = chip.cell_instance_by_name(&inv, "M1").unwrap();
+//! assert_eq!(
+//!     chip.get_cell_instance_property(&m1, &"w".into()).and_then(|v| v.get_string()),
+//!     Some("2u".into())
+//! );
+//! ```
+
+use super::{NetlistReader, NetlistWriter};
+use crate::prelude::{Direction, HierarchyUtil, NetlistBase, NetlistEdit, PropertyValue};
+use std::borrow::Borrow;
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
+
+/// Name of the cell property which marks a cell as a SPICE device.
+/// The value is the element letter of the device, for example `M` for a MOSFET.
+pub const DEVICE_TYPE_PROPERTY: &str = "spice_device";
+
+/// Name of the cell instance property which holds the value of
+/// resistors, capacitors and inductors.
+pub const VALUE_PROPERTY: &str = "value";
+
+/// Lines longer than this are wrapped by the writer.
+const MAX_LINE_LENGTH: usize = 80;
+
+/// Error type used for reading and writing SPICE netlists.
+#[derive(Debug)]
+pub enum SpiceError {
+    /// Error of the underlying byte stream.
+    Io(std::io::Error),
+    /// The input is not a valid netlist or uses unsupported constructs.
+    Parse {
+        /// Line number where the error occurred.
+        line: usize,
+        /// Description of the error.
+        message: String,
+    },
+    /// A subcircuit is instantiated but neither defined in the file nor in the netlist.
+    UnknownSubcircuit(String),
+    /// A subcircuit is defined in the file but a cell with this name exists already.
+    CellNameCollision(String),
+    /// The netlist cannot be represented in SPICE, for example because two pins are shorted.
+    Invalid(String),
+}
+
+impl std::fmt::Display for SpiceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SpiceError::Io(err) => write!(f, "IO error: {}", err),
+            SpiceError::Parse { line, message } => {
+                write!(f, "Parse error in line {}: {}", line, message)
+            }
+            SpiceError::UnknownSubcircuit(name) => {
+                write!(f, "Subcircuit '{}' is not defined.", name)
+            }
+            SpiceError::CellNameCollision(name) => write!(f, "Cell '{}' already exists.", name),
+            SpiceError::Invalid(message) => write!(f, "Invalid netlist: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for SpiceError {}
+
+impl From<std::io::Error> for SpiceError {
+    fn from(err: std::io::Error) -> Self {
+        SpiceError::Io(err)
+    }
+}
+
+/// Read SPICE and CDL netlists.
+#[derive(Debug, Clone, Default)]
+pub struct SpiceReader {
+    skip_title_line: bool,
+}
+
+impl SpiceReader {
+    /// Create a reader with default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Treat the first line of the file as title and ignore it, as done by SPICE simulators.
+    /// By default the first line is parsed like every other line.
+    pub fn skip_title_line(mut self, skip: bool) -> Self {
+        self.skip_title_line = skip;
+        self
+    }
+}
+
+impl NetlistReader for SpiceReader {
+    type Error = SpiceError;
+
+    fn read_into_netlist<R: Read, N: NetlistEdit>(
+        &self,
+        reader: &mut R,
+        netlist: &mut N,
+    ) -> Result<(), Self::Error> {
+        let mut source = String::new();
+        reader.read_to_string(&mut source)?;
+        let subcircuits = parse_subcircuits(&logical_lines(&source, self.skip_title_line))?;
+        populate_netlist(&subcircuits, netlist)
+    }
+}
+
+/// Write SPICE netlists.
+#[derive(Debug, Clone, Default)]
+pub struct SpiceWriter {
+    write_leaf_cells: bool,
+}
+
+impl SpiceWriter {
+    /// Create a writer with default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also write cells which contain no cell instances as empty subcircuits.
+    /// Device cells are never written.
+    pub fn write_leaf_cells(mut self, write: bool) -> Self {
+        self.write_leaf_cells = write;
+        self
+    }
+}
+
+impl NetlistWriter for SpiceWriter {
+    type Error = SpiceError;
+
+    fn write_netlist<W: Write, N: NetlistBase>(
+        &self,
+        writer: &mut W,
+        netlist: &N,
+    ) -> Result<(), Self::Error> {
+        // Write subcircuits before the subcircuits which use them.
+        for cell in netlist.each_cell_bottom_to_top() {
+            let is_device = device_type(netlist, &cell).is_some();
+            if !is_device && (self.write_leaf_cells || netlist.num_child_instances(&cell) > 0) {
+                write_subcircuit(writer, netlist, &cell)?;
+            }
+        }
+        writeln!(writer, ".END")?;
+        Ok(())
+    }
+}
+
+// Parser.
+
+/// Join continuation lines and remove comments.
+/// Returns the lines together with their original line numbers.
+fn logical_lines(source: &str, skip_title_line: bool) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    let skip = skip_title_line as usize;
+    for (i, line) in source.lines().enumerate().skip(skip) {
+        let line = line.trim();
+        let line_num = i + 1;
+        let is_pin_info = line
+            .get(..9)
+            .map_or(false, |s| s.eq_ignore_ascii_case("*.PININFO"));
+        if is_pin_info {
+            lines.push((line_num, line.to_string()));
+            continue;
+        }
+        if line.is_empty() || line.starts_with('*') {
+            continue;
+        }
+        // Inline comments.
+        let line = match line.find(" $").or_else(|| line.find(';')) {
+            Some(pos) => line[..pos].trim_end(),
+            None => line,
+        };
+        match (line.strip_prefix('+'), lines.last_mut()) {
+            (Some(continuation), Some((_, last))) => {
+                last.push(' ');
+                last.push_str(continuation);
+            }
+            _ => lines.push((line_num, line.to_string())),
+        }
+    }
+    lines
+}
+
+/// Split a logical line into tokens. Expressions in quotes or braces are kept as single tokens.
+/// Parameter assignments like `w = 1u` are joined into a single token `w=1u`.
+fn tokenize(line: &str) -> Vec<String> {
+    let mut tokens: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0;
+    let mut quote = None;
+    for c in line.chars() {
+        match (quote, c) {
+            (Some(q), c) => {
+                current.push(c);
+                if c == q {
+                    quote = None;
+                }
+            }
+            (None, '\'') | (None, '"') => {
+                quote = Some(c);
+                current.push(c);
+            }
+            (None, '{') | (None, '(') => {
+                depth += 1;
+                current.push(c);
+            }
+            (None, '}') | (None, ')') => {
+                depth -= 1;
+                current.push(c);
+            }
+            (None, '=') if depth == 0 => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+                tokens.push("=".to_string());
+            }
+            (None, c) if c.is_whitespace() && depth == 0 => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            (None, c) => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    // Join parameter assignments.
+    let mut joined: Vec<String> = Vec::new();
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        if token == "=" {
+            let value = tokens.next().unwrap_or_default();
+            match joined.last_mut() {
+                Some(key) => {
+                    key.push('=');
+                    key.push_str(&value);
+                }
+                None => joined.push(format!("={}", value)),
+            }
+        } else {
+            joined.push(token);
+        }
+    }
+    joined
+}
+
+/// Separate positional arguments from parameter assignments.
+fn split_parameters(tokens: &[String]) -> (Vec<String>, Vec<(String, String)>) {
+    let mut positional = Vec::new();
+    let mut parameters = Vec::new();
+    for token in tokens {
+        match token.split_once('=') {
+            Some((key, value)) => parameters.push((key.to_string(), value.to_string())),
+            // `PARAMS:` keyword in subcircuit definitions and `/` in CDL instance lines.
+            None if token.eq_ignore_ascii_case("params:") || token == "/" => {}
+            None => positional.push(token.clone()),
+        }
+    }
+    (positional, parameters)
+}
+
+#[derive(Clone, Debug)]
+struct Element {
+    /// Full name of the element including the type letter, for example `M1`.
+    name: String,
+    /// Element type letter in upper case.
+    kind: char,
+    nodes: Vec<String>,
+    /// Name of the subcircuit or device model.
+    template: Option<String>,
+    /// Value of resistors, capacitors and inductors.
+    value: Option<String>,
+    parameters: Vec<(String, String)>,
+    line: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Subcircuit {
+    name: String,
+    ports: Vec<String>,
+    parameters: Vec<(String, String)>,
+    pin_directions: HashMap<String, Direction>,
+    elements: Vec<Element>,
+}
+
+fn parse_error<T>(line: usize, message: &str) -> Result<T, SpiceError> {
+    Err(SpiceError::Parse {
+        line,
+        message: message.to_string(),
+    })
+}
+
+/// Check if the token is a numeric value or an expression rather than a model name.
+fn is_value(token: &str) -> bool {
+    token
+        .chars()
+        .next()
+        .map_or(false, |c| c.is_ascii_digit() || ".-+'{\"".contains(c))
+}
+
+fn parse_element(line_num: usize, tokens: &[String]) -> Result<Element, SpiceError> {
+    let name = tokens[0].clone();
+    let kind = name.chars().next().unwrap().to_ascii_uppercase();
+    let (mut positional, parameters) = split_parameters(&tokens[1..]);
+
+    let num_nodes = match kind {
+        'X' => positional.len().saturating_sub(1),
+        'M' => 4,
+        'J' => 3,
+        // Bipolar transistors have an optional substrate node.
+        'Q' if positional.len() > 4 => 4,
+        'Q' => 3,
+        'R' | 'C' | 'L' | 'D' => 2,
+        _ => return parse_error(line_num, &format!("unsupported element '{}'", name)),
+    };
+    if positional.len() < num_nodes {
+        return parse_error(line_num, &format!("missing nodes of element '{}'", name));
+    }
+    let rest = positional.split_off(num_nodes);
+
+    let (template, value) = match (kind, rest.as_slice()) {
+        ('X', [subckt]) => (Some(subckt.clone()), None),
+        ('X', _) => return parse_error(line_num, "missing subcircuit name"),
+        ('R' | 'C' | 'L', []) => (None, None),
+        ('R' | 'C' | 'L', [a]) if is_value(a) => (None, Some(a.clone())),
+        ('R' | 'C' | 'L', [a]) => (Some(a.clone()), None),
+        ('R' | 'C' | 'L', [a, b]) if is_value(a) => (Some(b.clone()), Some(a.clone())),
+        ('R' | 'C' | 'L', [a, b]) => (Some(a.clone()), Some(b.clone())),
+        (_, [model]) => (Some(model.clone()), None),
+        (_, []) => return parse_error(line_num, &format!("missing model of '{}'", name)),
+        _ => return parse_error(line_num, &format!("too many arguments of '{}'", name)),
+    };
+
+    Ok(Element {
+        name,
+        kind,
+        nodes: positional,
+        template,
+        value,
+        parameters,
+        line: line_num,
+    })
+}
+
+fn parse_subcircuits(lines: &[(usize, String)]) -> Result<Vec<Subcircuit>, SpiceError> {
+    let mut subcircuits = Vec::new();
+    let mut current: Option<Subcircuit> = None;
+
+    for (line_num, line) in lines {
+        let line_num = *line_num;
+        let tokens = tokenize(line);
+        let keyword = tokens[0].to_ascii_uppercase();
+
+        if keyword == "*.PININFO" {
+            let subckt = match current.as_mut() {
+                Some(s) => s,
+                None => continue,
+            };
+            for info in &tokens[1..] {
+                let (pin, direction) = match info.rsplit_once(':') {
+                    Some(split) => split,
+                    None => return parse_error(line_num, "invalid pin information"),
+                };
+                let direction = match direction.to_ascii_uppercase().as_str() {
+                    "I" => Direction::Input,
+                    "O" => Direction::Output,
+                    "B" => Direction::InOut,
+                    _ => Direction::None,
+                };
+                subckt.pin_directions.insert(pin.to_string(), direction);
+            }
+            continue;
+        }
+
+        match keyword.as_str() {
+            ".SUBCKT" => {
+                if current.is_some() {
+                    return parse_error(line_num, "nested subcircuits are not supported");
+                }
+                let (mut ports, parameters) = split_parameters(&tokens[1..]);
+                if ports.is_empty() {
+                    return parse_error(line_num, "missing name of subcircuit");
+                }
+                let name = ports.remove(0);
+                let mut unique = HashSet::new();
+                if let Some(port) = ports.iter().find(|p| !unique.insert(*p)) {
+                    return parse_error(line_num, &format!("port '{}' is used twice", port));
+                }
+                current = Some(Subcircuit {
+                    name,
+                    ports,
+                    parameters,
+                    ..Default::default()
+                });
+            }
+            ".ENDS" => match current.take() {
+                Some(subckt) => subcircuits.push(subckt),
+                None => return parse_error(line_num, "'.ENDS' without '.SUBCKT'"),
+            },
+            ".END" => break,
+            keyword if keyword.starts_with('.') => {
+                // Control statements, models, parameters, ...
+                log::debug!(
+                    "Ignoring SPICE statement '{}' in line {}.",
+                    keyword,
+                    line_num
+                );
+            }
+            _ => match current.as_mut() {
+                Some(subckt) => subckt.elements.push(parse_element(line_num, &tokens)?),
+                None => {
+                    return parse_error(
+                        line_num,
+                        "elements outside of subcircuits are not supported",
+                    )
+                }
+            },
+        }
+    }
+
+    if let Some(subckt) = current {
+        return Err(SpiceError::Parse {
+            line: lines.last().map_or(0, |(l, _)| *l),
+            message: format!("missing '.ENDS' of subcircuit '{}'", subckt.name),
+        });
+    }
+
+    Ok(subcircuits)
+}
+
+// Building the netlist.
+
+/// Names of the terminals of devices, in the order of the nodes in the element line.
+fn device_terminals(kind: char, num_nodes: usize) -> &'static [&'static str] {
+    match (kind, num_nodes) {
+        ('M', _) => &["D", "G", "S", "B"],
+        ('J', _) => &["D", "G", "S"],
+        ('Q', 3) => &["C", "B", "E"],
+        ('Q', _) => &["C", "B", "E", "S"],
+        _ => &["P", "N"],
+    }
+}
+
+/// Name of the cell used for devices without model.
+fn generic_device_name(kind: char) -> Option<&'static str> {
+    match kind {
+        'R' => Some("resistor"),
+        'C' => Some("capacitor"),
+        'L' => Some("inductor"),
+        _ => None,
+    }
+}
+
+/// Get the element letter of a device cell.
+fn device_type<N: NetlistBase>(netlist: &N, cell: &N::CellId) -> Option<char> {
+    netlist
+        .get_cell_property(cell, &DEVICE_TYPE_PROPERTY.to_string().into())
+        .and_then(|v| v.get_string())
+        .and_then(|s| s.chars().next())
+}
+
+fn populate_netlist<N: NetlistEdit>(
+    subcircuits: &[Subcircuit],
+    netlist: &mut N,
+) -> Result<(), SpiceError> {
+    // Create cells and pins.
+    let mut cells = HashMap::new();
+    for subckt in subcircuits {
+        if netlist.cell_by_name(&subckt.name).is_some() {
+            return Err(SpiceError::CellNameCollision(subckt.name.clone()));
+        }
+        let cell = netlist.create_cell(subckt.name.clone().into());
+        for port in &subckt.ports {
+            let direction = subckt
+                .pin_directions
+                .get(port)
+                .copied()
+                .unwrap_or(Direction::None);
+            netlist.create_pin(&cell, port.clone().into(), direction);
+        }
+        for (key, value) in &subckt.parameters {
+            netlist.set_cell_property(&cell, key.clone().into(), value.clone().into());
+        }
+        cells.insert(subckt.name.clone(), cell);
+    }
+
+    for subckt in subcircuits {
+        let cell = cells[&subckt.name].clone();
+
+        // Nets connected to the ports.
+        let mut nets = HashMap::new();
+        nets.insert("0".to_string(), netlist.net_zero(&cell));
+        for pin in netlist.each_pin_vec(&cell) {
+            let name: String = netlist.pin_name(&pin).into();
+            let net = netlist.create_net(&cell, Some(name.clone().into()));
+            netlist.connect_pin(&pin, Some(net.clone()));
+            nets.insert(name, net);
+        }
+
+        for element in &subckt.elements {
+            let template = match element.kind {
+                'X' => {
+                    let name = element.template.as_ref().unwrap();
+                    let template = match cells.get(name) {
+                        Some(cell) => cell.clone(),
+                        None => netlist
+                            .cell_by_name(name)
+                            .ok_or_else(|| SpiceError::UnknownSubcircuit(name.clone()))?,
+                    };
+                    cells.insert(name.clone(), template.clone());
+                    template
+                }
+                kind => get_or_create_device(netlist, element, kind)?,
+            };
+
+            let pins = netlist.each_pin_vec(&template);
+            if pins.len() != element.nodes.len() {
+                return parse_error(
+                    element.line,
+                    &format!(
+                        "'{}' has {} pins but {} nodes are connected",
+                        netlist.cell_name(&template),
+                        pins.len(),
+                        element.nodes.len()
+                    ),
+                );
+            }
+
+            if netlist
+                .cell_instance_by_name(&cell, &element.name)
+                .is_some()
+            {
+                return parse_error(
+                    element.line,
+                    &format!("element '{}' is defined more than once", element.name),
+                );
+            }
+            if netlist.cell_depends_on(&template, &cell) {
+                return parse_error(
+                    element.line,
+                    &format!(
+                        "subcircuit '{}' is instantiated recursively",
+                        netlist.cell_name(&template)
+                    ),
+                );
+            }
+            let inst =
+                netlist.create_cell_instance(&cell, &template, Some(element.name.clone().into()));
+            for (pin, node) in pins.iter().zip(&element.nodes) {
+                let net = match nets.get(node) {
+                    Some(net) => net.clone(),
+                    None => {
+                        let net = netlist.create_net(&cell, Some(node.clone().into()));
+                        nets.insert(node.clone(), net.clone());
+                        net
+                    }
+                };
+                let pin_inst = netlist.pin_instance(&inst, pin);
+                netlist.connect_pin_instance(&pin_inst, Some(net));
+            }
+
+            if let Some(value) = &element.value {
+                netlist.set_cell_instance_property(
+                    &inst,
+                    VALUE_PROPERTY.to_string().into(),
+                    value.clone().into(),
+                );
+            }
+            for (key, value) in &element.parameters {
+                netlist.set_cell_instance_property(&inst, key.clone().into(), value.clone().into());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Find the cell which represents the device of the element. Create it if it does not exist yet.
+fn get_or_create_device<N: NetlistEdit>(
+    netlist: &mut N,
+    element: &Element,
+    kind: char,
+) -> Result<N::CellId, SpiceError> {
+    let name = match &element.template {
+        Some(model) => model.as_str(),
+        None => generic_device_name(kind).unwrap(),
+    };
+    if let Some(cell) = netlist.cell_by_name(name) {
+        return Ok(cell);
+    }
+    let cell = netlist.create_cell(name.to_string().into());
+    for terminal in device_terminals(kind, element.nodes.len()) {
+        netlist.create_pin(&cell, terminal.to_string().into(), Direction::None);
+    }
+    netlist.set_cell_property(
+        &cell,
+        DEVICE_TYPE_PROPERTY.to_string().into(),
+        kind.to_string().into(),
+    );
+    Ok(cell)
+}
+
+// Writer.
+
+/// Check that the name can be used in a SPICE netlist.
+fn check_name(name: &str) -> Result<&str, SpiceError> {
+    if name.is_empty() || name.contains(|c: char| c.is_whitespace() || "=;$".contains(c)) {
+        Err(SpiceError::Invalid(format!(
+            "'{}' is not a valid SPICE name",
+            name
+        )))
+    } else {
+        Ok(name)
+    }
+}
+
+fn format_property(value: &PropertyValue) -> Option<String> {
+    match value {
+        PropertyValue::String(s) => Some(s.to_string()),
+        PropertyValue::SInt(v) => Some(v.to_string()),
+        PropertyValue::UInt(v) => Some(v.to_string()),
+        PropertyValue::Float(v) => Some(v.to_string()),
+        PropertyValue::Bytes(_) => None,
+    }
+}
+
+/// Write the tokens as a single logical line. Long lines are wrapped with continuation lines.
+fn write_line<W: Write>(writer: &mut W, tokens: &[String]) -> Result<(), SpiceError> {
+    let mut length = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        if i > 0 && length + token.len() + 1 > MAX_LINE_LENGTH {
+            write!(writer, "\n+ {}", token)?;
+            length = token.len() + 2;
+        } else if i > 0 {
+            write!(writer, " {}", token)?;
+            length += token.len() + 1;
+        } else {
+            write!(writer, "{}", token)?;
+            length = token.len();
+        }
+    }
+    writeln!(writer)?;
+    Ok(())
+}
+
+fn write_subcircuit<W: Write, N: NetlistBase>(
+    writer: &mut W,
+    netlist: &N,
+    cell: &N::CellId,
+) -> Result<(), SpiceError> {
+    let cell_name: String = netlist.cell_name(cell).into();
+    let pins = netlist.each_pin_vec(cell);
+    let pin_names: Vec<String> = pins.iter().map(|p| netlist.pin_name(p).into()).collect();
+
+    // Ports are the nodes of the nets connected to the pins.
+    let mut node_names: HashMap<N::NetId, String> = HashMap::new();
+    let mut used_names: HashSet<String> = pin_names.iter().cloned().collect();
+    used_names.insert("0".to_string());
+    for (pin, name) in pins.iter().zip(&pin_names) {
+        if let Some(net) = netlist.net_of_pin(pin) {
+            if let Some(other) = node_names.insert(net, name.clone()) {
+                return Err(SpiceError::Invalid(format!(
+                    "pins '{}' and '{}' of '{}' are shorted",
+                    other, name, cell_name
+                )));
+            }
+        }
+    }
+    let net_zero = netlist.net_zero(cell);
+    node_names
+        .entry(net_zero)
+        .or_insert_with(|| "0".to_string());
+
+    // Header.
+    let mut header = vec![".SUBCKT".to_string(), check_name(&cell_name)?.to_string()];
+    for name in &pin_names {
+        header.push(check_name(name)?.to_string());
+    }
+    netlist.for_each_cell_property(cell, |key, value| {
+        if let Some(value) = format_property(value) {
+            header.push(format!("{}={}", key, value));
+        }
+    });
+    write_line(writer, &header)?;
+
+    let pin_info: Vec<String> = pins
+        .iter()
+        .zip(&pin_names)
+        .filter_map(|(pin, name)| {
+            let direction = match netlist.pin_direction(pin) {
+                Direction::Input | Direction::Clock => "I",
+                Direction::Output => "O",
+                Direction::InOut => "B",
+                _ => return None,
+            };
+            Some(format!("{}:{}", name, direction))
+        })
+        .collect();
+    if !pin_info.is_empty() {
+        let mut line = vec!["*.PININFO".to_string()];
+        line.extend(pin_info);
+        // Continuation lines are not possible in comments.
+        writeln!(writer, "{}", line.join(" "))?;
+    }
+
+    let mut num_generated = 0;
+    let mut node_name = |net: Option<N::NetId>| -> Result<String, SpiceError> {
+        if let Some(name) = net.as_ref().and_then(|net| node_names.get(net)) {
+            return Ok(name.clone());
+        }
+        let net_name = net
+            .as_ref()
+            .and_then(|net| netlist.net_name(net))
+            .map(Into::<String>::into);
+        let name = match net_name {
+            Some(name) if !used_names.contains(&name) => {
+                check_name(&name)?;
+                name
+            }
+            // Unnamed nets and dangling nodes get a generated name.
+            _ => loop {
+                num_generated += 1;
+                let prefix = if net.is_some() {
+                    "__net"
+                } else {
+                    "__unconnected"
+                };
+                let name = format!("{}{}", prefix, num_generated);
+                if !used_names.contains(&name) {
+                    break name;
+                }
+            },
+        };
+        used_names.insert(name.clone());
+        if let Some(net) = net {
+            node_names.insert(net, name.clone());
+        }
+        Ok(name)
+    };
+
+    // Instances.
+    let mut used_instance_names = HashSet::new();
+    for (i, inst) in netlist.each_cell_instance(cell).enumerate() {
+        let template = netlist.template_cell(&inst);
+        let template_name: String = netlist.cell_name(&template).into();
+        let kind = device_type(netlist, &template).unwrap_or('X');
+
+        // Instance names must start with the element letter.
+        let mut name: String = netlist
+            .cell_instance_name(&inst)
+            .map(|n| n.into())
+            .unwrap_or_else(|| i.to_string());
+        if !name.starts_with(|c: char| c.to_ascii_uppercase() == kind) {
+            name.insert(0, kind);
+        }
+        while !used_instance_names.insert(name.clone()) {
+            name.push('_');
+        }
+        let mut line = vec![check_name(&name)?.to_string()];
+
+        for pin in netlist.each_pin(&template) {
+            let net = netlist.net_of_pin_instance(&netlist.pin_instance(&inst, &pin));
+            line.push(node_name(net)?);
+        }
+
+        let mut value = None;
+        let mut parameters = Vec::new();
+        netlist.for_each_cell_instance_property(&inst, |key, v| {
+            if let Some(v) = format_property(v) {
+                let key_str: &str = key.borrow();
+                if key_str == VALUE_PROPERTY && "RCL".contains(kind) {
+                    value = Some(v);
+                } else {
+                    parameters.push(format!("{}={}", key, v));
+                }
+            }
+        });
+        line.extend(value);
+        if generic_device_name(kind) != Some(template_name.as_str()) {
+            line.push(check_name(&template_name)?.to_string());
+        }
+        parameters.sort();
+        line.extend(parameters);
+
+        write_line(writer, &line)?;
+    }
+
+    writeln!(writer, ".ENDS {}", cell_name)?;
+    writeln!(writer)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    const NETLIST: &str = r"
+* Test netlist.
+.SUBCKT INV A Y VDD GND
+*.PININFO A:I Y:O VDD:B GND:B
+M1 Y A VDD VDD pmos w = 2u
++ l=0.1u
+M2 Y A GND GND nmos w=1u l=0.1u $ Comment.
+.ENDS INV
+
+.subckt top in out vdd
+XU1 in n1 vdd 0 INV
+XU2 n1 out vdd 0 / INV
+R1 out 0 1k
+C1 n1 0 cmim 10f m=2
+.ends
+.END
+";
+
+    fn string_property(chip: &Chip, inst: &crate::chip::CellInstId, key: &str) -> Option<String> {
+        chip.get_cell_instance_property(inst, &key.into())
+            .and_then(|v| v.get_string())
+            .map(|s| s.to_string())
+    }
+
+    #[test]
+    fn test_read_spice() {
+        let chip: Chip = SpiceReader::new()
+            .read_netlist(&mut NETLIST.as_bytes())
+            .unwrap();
+
+        let inv = chip.cell_by_name("INV").unwrap();
+        let pin_names: Vec<String> = chip
+            .each_pin(&inv)
+            .map(|p| chip.pin_name(&p).into())
+            .collect();
+        assert_eq!(pin_names, vec!["A", "Y", "VDD", "GND"]);
+        let a = chip.pin_by_name(&inv, "A").unwrap();
+        assert_eq!(chip.pin_direction(&a), Direction::Input);
+
+        // Devices.
+        let pmos = chip.cell_by_name("pmos").unwrap();
+        assert_eq!(chip.num_pins(&pmos), 4);
+        assert_eq!(device_type(&chip, &pmos), Some('M'));
+        let m1 = chip.cell_instance_by_name(&inv, "M1").unwrap();
+        assert_eq!(string_property(&chip, &m1, "w"), Some("2u".into()));
+        assert_eq!(string_property(&chip, &m1, "l"), Some("0.1u".into()));
+        let gate = chip.pin_by_name(&pmos, "G").unwrap();
+        assert_eq!(
+            chip.net_of_pin_instance(&chip.pin_instance(&m1, &gate)),
+            chip.net_of_pin(&a)
+        );
+
+        // Subcircuit instances.
+        let top = chip.cell_by_name("top").unwrap();
+        let u1 = chip.cell_instance_by_name(&top, "XU1").unwrap();
+        let u2 = chip.cell_instance_by_name(&top, "XU2").unwrap();
+        assert_eq!(chip.template_cell(&u2), inv);
+        let y = chip.pin_by_name(&inv, "Y").unwrap();
+        let gnd = chip.pin_by_name(&inv, "GND").unwrap();
+        assert_eq!(
+            chip.net_of_pin_instance(&chip.pin_instance(&u1, &y)),
+            chip.net_of_pin_instance(&chip.pin_instance(&u2, &a))
+        );
+        assert_eq!(
+            chip.net_of_pin_instance(&chip.pin_instance(&u1, &gnd)),
+            Some(chip.net_zero(&top))
+        );
+
+        // Passive devices.
+        let r1 = chip.cell_instance_by_name(&top, "R1").unwrap();
+        assert_eq!(
+            chip.cell_name(&chip.template_cell(&r1)).as_str(),
+            "resistor"
+        );
+        assert_eq!(
+            string_property(&chip, &r1, VALUE_PROPERTY),
+            Some("1k".into())
+        );
+        let c1 = chip.cell_instance_by_name(&top, "C1").unwrap();
+        assert_eq!(chip.cell_name(&chip.template_cell(&c1)).as_str(), "cmim");
+        assert_eq!(
+            string_property(&chip, &c1, VALUE_PROPERTY),
+            Some("10f".into())
+        );
+        assert_eq!(string_property(&chip, &c1, "m"), Some("2".into()));
+    }
+
+    #[test]
+    fn test_write_and_read_back() {
+        let chip: Chip = SpiceReader::new()
+            .read_netlist(&mut NETLIST.as_bytes())
+            .unwrap();
+
+        let mut buffer = Vec::new();
+        SpiceWriter::new()
+            .write_netlist(&mut buffer, &chip)
+            .unwrap();
+        let spice = String::from_utf8(buffer).unwrap();
+        assert!(spice.contains("*.PININFO A:I Y:O VDD:B GND:B"));
+
+        let restored: Chip = SpiceReader::new()
+            .read_netlist(&mut spice.as_bytes())
+            .unwrap();
+
+        assert_eq!(restored.num_cells(), chip.num_cells());
+        for cell in chip.each_cell() {
+            let restored_cell = restored.cell_by_name(&chip.cell_name(&cell)).unwrap();
+            assert_eq!(restored.num_pins(&restored_cell), chip.num_pins(&cell));
+            for inst in chip.each_cell_instance(&cell) {
+                let name = chip.cell_instance_name(&inst).unwrap();
+                let restored_inst = restored
+                    .cell_instance_by_name(&restored_cell, &name)
+                    .unwrap();
+                assert_eq!(
+                    restored.cell_name(&restored.template_cell(&restored_inst)),
+                    chip.cell_name(&chip.template_cell(&inst))
+                );
+                for key in ["w", "l", "m", VALUE_PROPERTY] {
+                    assert_eq!(
+                        string_property(&restored, &restored_inst, key),
+                        string_property(&chip, &inst, key)
+                    );
+                }
+                // Compare connectivity by the names of the nets.
+                let restored_nets: Vec<_> = restored
+                    .each_pin_instance(&restored_inst)
+                    .map(|p| restored.net_of_pin_instance(&p))
+                    .map(|n| n.and_then(|n| restored.net_name(&n)))
+                    .collect();
+                let nets: Vec<_> = chip
+                    .each_pin_instance(&inst)
+                    .map(|p| chip.net_of_pin_instance(&p))
+                    .map(|n| n.and_then(|n| chip.net_name(&n)))
+                    .collect();
+                assert_eq!(restored_nets, nets);
+            }
+        }
+    }
+
+    #[test]
+    fn test_unknown_subcircuit() {
+        let spice = ".SUBCKT top a\nX1 a BUF\n.ENDS\n";
+        let result: Result<Chip, _> = SpiceReader::new().read_netlist(&mut spice.as_bytes());
+        assert!(matches!(result, Err(SpiceError::UnknownSubcircuit(_))));
+    }
+
+    #[test]
+    fn test_invalid_instances() {
+        let recursive = ".SUBCKT top a\nX1 a top\n.ENDS\n";
+        let result: Result<Chip, _> = SpiceReader::new().read_netlist(&mut recursive.as_bytes());
+        assert!(matches!(result, Err(SpiceError::Parse { line: 2, .. })));
+
+        let recursive = ".SUBCKT a x\nX1 x b\n.ENDS\n.SUBCKT b x\nX1 x a\n.ENDS\n";
+        let result: Result<Chip, _> = SpiceReader::new().read_netlist(&mut recursive.as_bytes());
+        assert!(matches!(result, Err(SpiceError::Parse { .. })));
+
+        let duplicate = ".SUBCKT top a\nR1 a 0 1k\nR1 a 0 2k\n.ENDS\n";
+        let result: Result<Chip, _> = SpiceReader::new().read_netlist(&mut duplicate.as_bytes());
+        assert!(matches!(result, Err(SpiceError::Parse { line: 3, .. })));
+    }
+}
@@ -0,0 +1,1776 @@
+// SPDX-FileCopyrightText: 2022 Thomas Kramer
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Reader and writer for structural (gate-level) Verilog netlists.
+//!
+//! Supported are modules with ANSI and non-ANSI port declarations, `wire` declarations,
+//! `assign` statements, module instances with named or positional port connections,
+//! bit-selects, part-selects, concatenations, constants and escaped identifiers.
+//! Behavioural constructs are not supported.
+//!
+//! Each bit of a bus is represented by a separate pin or net. Their names are formed like `name[3]`.
+//! When reading with [`VerilogReader::read_into_netlist_with_buses()`], ports and wires whose range
+//! starts at index `0` are additionally grouped into pin and net buses.
+//! [`NetlistReader::read_into_netlist()`] works with any [`NetlistEdit`] and does not create buses.
+//! The writer combines such pins and nets into buses again if possible.
+//!
+//! Nets which are connected by `assign` statements are merged into a single net. Constants are mapped to
+//! the `net_zero` and `net_one` nets of the cell.
+//!
+//! # Example
+//!
+//! ```
+//! use libreda_db::prelude::*;
+//! use libreda_db::netlist::io::verilog::VerilogReader;
+//!
+//! let verilog = r"
+//!     module TOP (a, y);
+//!         input a;
+//!         output y;
+//!         INV inv1 (.A(a), .Y(y));
+//!     endmodule
+//! ";
+//!
+//! // `INV` is not defined in the netlist. Create it as an empty cell.
+//! let reader = VerilogReader::new().create_black_boxes(true);
+//! let chip: Chip = reader.read_netlist(&mut verilog.as_bytes()).unwrap();
+//! let inv = chip.cell_by_name("INV").unwrap();
+//! assert_eq!(chip.num_pins(&inv), 2);
+//! ```
+
+use super::{NetlistReader, NetlistWriter};
+use crate::prelude::{BitOrder, BusEdit, Direction, HierarchyUtil, NetlistBase, NetlistEdit};
+use std::borrow::Borrow;
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
+
+/// Error type used for reading and writing Verilog netlists.
+#[derive(Debug)]
+pub enum VerilogError {
+    /// Error of the underlying byte stream.
+    Io(std::io::Error),
+    /// The input is not valid Verilog or uses unsupported constructs.
+    Parse {
+        /// Line number where the error occurred.
+        line: usize,
+        /// Description of the error.
+        message: String,
+    },
+    /// A module is instantiated but neither defined in the file nor in the netlist.
+    UnknownModule(String),
+    /// A module is defined in the file but a cell with this name exists already.
+    CellNameCollision(String),
+    /// The content of the netlist is inconsistent, for example a port does not exist.
+    Invalid(String),
+}
+
+impl std::fmt::Display for VerilogError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerilogError::Io(err) => write!(f, "IO error: {}", err),
+            VerilogError::Parse { line, message } => {
+                write!(f, "Parse error in line {}: {}", line, message)
+            }
+            VerilogError::UnknownModule(name) => write!(f, "Module '{}' is not defined.", name),
+            VerilogError::CellNameCollision(name) => {
+                write!(f, "Cell '{}' already exists.", name)
+            }
+            VerilogError::Invalid(message) => write!(f, "Invalid netlist: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for VerilogError {}
+
+impl From<std::io::Error> for VerilogError {
+    fn from(err: std::io::Error) -> Self {
+        VerilogError::Io(err)
+    }
+}
+
+/// Read structural Verilog netlists.
+#[derive(Debug, Clone, Default)]
+pub struct VerilogReader {
+    create_black_boxes: bool,
+}
+
+impl VerilogReader {
+    /// Create a reader with default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create empty cells for instantiated modules which are neither defined in the file nor
+    /// in the netlist. The pins are derived from named port connections.
+    /// By default this is disabled and unknown modules cause an error.
+    pub fn create_black_boxes(mut self, create: bool) -> Self {
+        self.create_black_boxes = create;
+        self
+    }
+
+    /// Read a netlist like [`NetlistReader::read_into_netlist()`] and additionally group
+    /// ports and wires into pin and net buses.
+    pub fn read_into_netlist_with_buses<R: Read, N: BusEdit>(
+        &self,
+        reader: &mut R,
+        netlist: &mut N,
+    ) -> Result<(), VerilogError> {
+        let buses = self.read_modules(reader, netlist)?;
+        for (name, pins, bit_order) in buses.pin_buses {
+            netlist.create_pin_bus_from_pins(name.into(), pins, bit_order);
+        }
+        for (name, nets, bit_order) in buses.net_buses {
+            netlist.create_net_bus_from_nets(name.into(), nets, bit_order);
+        }
+        Ok(())
+    }
+
+    /// Read a netlist with buses from a byte stream.
+    pub fn read_netlist_with_buses<R: Read, N: BusEdit>(
+        &self,
+        reader: &mut R,
+    ) -> Result<N, VerilogError> {
+        let mut netlist = N::new();
+        self.read_into_netlist_with_buses(reader, &mut netlist)?;
+        Ok(netlist)
+    }
+
+    fn read_modules<R: Read, N: NetlistEdit>(
+        &self,
+        reader: &mut R,
+        netlist: &mut N,
+    ) -> Result<Buses<N>, VerilogError> {
+        let mut source = String::new();
+        reader.read_to_string(&mut source)?;
+        let modules = Parser::new(tokenize(&source)?).parse_modules()?;
+        populate_netlist(&modules, netlist, self.create_black_boxes)
+    }
+}
+
+impl NetlistReader for VerilogReader {
+    type Error = VerilogError;
+
+    fn read_into_netlist<R: Read, N: NetlistEdit>(
+        &self,
+        reader: &mut R,
+        netlist: &mut N,
+    ) -> Result<(), Self::Error> {
+        self.read_modules(reader, netlist).map(|_| ())
+    }
+}
+
+/// Write structural Verilog netlists.
+#[derive(Debug, Clone, Default)]
+pub struct VerilogWriter {
+    write_leaf_cells: bool,
+}
+
+impl VerilogWriter {
+    /// Create a writer with default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also write cells which contain no cell instances as empty modules.
+    /// By default leaf cells are skipped because they are usually
+    /// standard-cells which are defined in a library.
+    pub fn write_leaf_cells(mut self, write: bool) -> Self {
+        self.write_leaf_cells = write;
+        self
+    }
+}
+
+impl NetlistWriter for VerilogWriter {
+    type Error = VerilogError;
+
+    fn write_netlist<W: Write, N: NetlistBase>(
+        &self,
+        writer: &mut W,
+        netlist: &N,
+    ) -> Result<(), Self::Error> {
+        // Write sub modules before the modules which use them.
+        for cell in netlist.each_cell_bottom_to_top() {
+            if self.write_leaf_cells || netlist.num_child_instances(&cell) > 0 {
+                write_module(writer, netlist, &cell)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Lexer.
+
+/// Single bit of a signal or constant.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum Bit {
+    Zero,
+    One,
+    /// `x` or `z`. Treated as unconnected.
+    Undefined,
+    /// Bit of a signal, identified by the name of the bit, for example `a` or `a[3]`.
+    Signal(String),
+}
+
+impl Bit {
+    fn from_bool(value: bool) -> Self {
+        if value {
+            Bit::One
+        } else {
+            Bit::Zero
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    /// Identifier or keyword. Escaped identifiers are stored without the backslash.
+    Ident(String),
+    /// Unsized decimal number.
+    Int(u64),
+    /// Based number like `4'b1010`. Bits are ordered MSB first.
+    Constant(Vec<Bit>),
+    Symbol(char),
+}
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, VerilogError> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    let mut line = 1;
+
+    let error = |line: usize, message: &str| VerilogError::Parse {
+        line,
+        message: message.to_string(),
+    };
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            '\n' => {
+                line += 1;
+                chars.next();
+            }
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '/' => {
+                chars.next();
+                match chars.peek() {
+                    Some('/') => {
+                        // Line comment.
+                        while chars.peek().map_or(false, |&c| c != '\n') {
+                            chars.next();
+                        }
+                    }
+                    Some('*') => {
+                        // Block comment.
+                        chars.next();
+                        let mut prev = ' ';
+                        loop {
+                            match chars.next() {
+                                Some('/') if prev == '*' => break,
+                                Some(c) => {
+                                    line += (c == '\n') as usize;
+                                    prev = c;
+                                }
+                                None => return Err(error(line, "unterminated comment")),
+                            }
+                        }
+                    }
+                    _ => tokens.push((Token::Symbol('/'), line)),
+                }
+            }
+            '`' => {
+                // Compiler directives like `timescale are ignored.
+                while chars.peek().map_or(false, |&c| c != '\n') {
+                    chars.next();
+                }
+            }
+            '\\' => {
+                // Escaped identifier, terminated by white space.
+                chars.next();
+                let mut name = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() {
+                        break;
+                    }
+                    name.push(c);
+                    chars.next();
+                }
+                tokens.push((Token::Ident(name), line));
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut name = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_ascii_alphanumeric() || c == '_' || c == '$' {
+                        name.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push((Token::Ident(name), line));
+            }
+            c if c.is_ascii_digit() || c == '\'' => {
+                let mut size = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_ascii_digit() || c == '_' {
+                        size.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                let size = size.replace('_', "");
+                if chars.peek() != Some(&'\'') {
+                    let value = size.parse().map_err(|_| error(line, "invalid number"))?;
+                    tokens.push((Token::Int(value), line));
+                    continue;
+                }
+                chars.next();
+                if matches!(chars.peek(), Some('s') | Some('S')) {
+                    chars.next();
+                }
+                let base = chars
+                    .next()
+                    .map(|c| c.to_ascii_lowercase())
+                    .ok_or_else(|| error(line, "missing base of number"))?;
+                let mut digits = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_ascii_alphanumeric() || c == '_' || c == '?' {
+                        digits.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                let width = if size.is_empty() {
+                    None
+                } else {
+                    Some(size.parse().map_err(|_| error(line, "invalid width"))?)
+                };
+                let bits = parse_based_number(base, &digits, width)
+                    .ok_or_else(|| error(line, "invalid number"))?;
+                tokens.push((Token::Constant(bits), line));
+            }
+            '(' => {
+                chars.next();
+                if chars.peek() == Some(&'*') {
+                    // Skip attributes `(* ... *)`.
+                    chars.next();
+                    let mut prev = ' ';
+                    loop {
+                        match chars.next() {
+                            Some(')') if prev == '*' => break,
+                            Some(c) => {
+                                line += (c == '\n') as usize;
+                                prev = c;
+                            }
+                            None => return Err(error(line, "unterminated attribute")),
+                        }
+                    }
+                } else {
+                    tokens.push((Token::Symbol('('), line));
+                }
+            }
+            c => {
+                chars.next();
+                tokens.push((Token::Symbol(c), line));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Convert the digits of a based number into bits (MSB first).
+fn parse_based_number(base: char, digits: &str, width: Option<usize>) -> Option<Vec<Bit>> {
+    let digits = digits.replace('_', "");
+    let mut bits = Vec::new();
+    match base {
+        'b' | 'o' | 'h' => {
+            let bits_per_digit = match base {
+                'b' => 1,
+                'o' => 3,
+                _ => 4,
+            };
+            for d in digits.chars() {
+                if matches!(d, 'x' | 'X' | 'z' | 'Z' | '?') {
+                    bits.extend((0..bits_per_digit).map(|_| Bit::Undefined));
+                } else {
+                    let value = d.to_digit(1 << bits_per_digit)?;
+                    bits.extend(
+                        (0..bits_per_digit)
+                            .rev()
+                            .map(|i| Bit::from_bool((value >> i) & 1 == 1)),
+                    );
+                }
+            }
+        }
+        'd' => {
+            let value: u64 = digits.parse().ok()?;
+            bits = int_to_bits(value);
+        }
+        _ => return None,
+    }
+    if let Some(width) = width {
+        resize_bits(&mut bits, width, Bit::Zero);
+    }
+    Some(bits)
+}
+
+/// Binary representation of an integer (MSB first).
+fn int_to_bits(value: u64) -> Vec<Bit> {
+    let num_bits = (64 - value.leading_zeros()).max(1);
+    (0..num_bits)
+        .rev()
+        .map(|i| Bit::from_bool((value >> i) & 1 == 1))
+        .collect()
+}
+
+/// Truncate or extend the bits to `width` such that the least significant bits are preserved.
+fn resize_bits(bits: &mut Vec<Bit>, width: usize, fill: Bit) {
+    if bits.len() > width {
+        bits.drain(0..bits.len() - width);
+    } else {
+        let padding = width - bits.len();
+        bits.splice(0..0, (0..padding).map(|_| fill.clone()));
+    }
+}
+
+// Parser.
+
+/// Right-hand side of a connection or assignment.
+#[derive(Clone, Debug)]
+enum Expr {
+    Ident(String),
+    BitSelect(String, i64),
+    PartSelect(String, i64, i64),
+    Constant(Vec<Bit>),
+    Concat(Vec<Expr>),
+}
+
+#[derive(Clone, Debug, Default)]
+struct Signal {
+    direction: Option<Direction>,
+    /// Declared range `[msb:lsb]`.
+    range: Option<(i64, i64)>,
+}
+
+#[derive(Clone, Debug)]
+enum Connections {
+    Named(Vec<(String, Option<Expr>)>),
+    Positional(Vec<Option<Expr>>),
+}
+
+#[derive(Clone, Debug)]
+struct Instance {
+    template: String,
+    name: String,
+    connections: Connections,
+    line: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Module {
+    name: String,
+    /// Names of the ports in the order of the module header.
+    ports: Vec<String>,
+    /// Declared ports and wires.
+    signals: HashMap<String, Signal>,
+    /// Names of the declared signals in the order of declaration.
+    signal_order: Vec<String>,
+    assignments: Vec<(Expr, Expr)>,
+    instances: Vec<Instance>,
+}
+
+impl Module {
+    fn declare(&mut self, name: String, direction: Option<Direction>, range: Option<(i64, i64)>) {
+        let signal = self.signals.entry(name.clone()).or_insert_with(|| {
+            self.signal_order.push(name);
+            Signal::default()
+        });
+        signal.direction = direction.or(signal.direction);
+        signal.range = range.or(signal.range);
+    }
+
+    /// Names of all bits of a declared or implicit signal, MSB first.
+    fn bit_names(&self, name: &str) -> Vec<String> {
+        match self.signals.get(name).and_then(|s| s.range) {
+            None => vec![name.to_string()],
+            Some((msb, lsb)) => range_indices(msb, lsb).map(|i| bit_name(name, i)).collect(),
+        }
+    }
+
+    /// Resolve an expression into single bits, MSB first.
+    fn bits(&self, expr: &Expr) -> Vec<Bit> {
+        match expr {
+            Expr::Ident(name) => self.bit_names(name).into_iter().map(Bit::Signal).collect(),
+            Expr::BitSelect(name, i) => vec![Bit::Signal(bit_name(name, *i))],
+            Expr::PartSelect(name, msb, lsb) => range_indices(*msb, *lsb)
+                .map(|i| Bit::Signal(bit_name(name, i)))
+                .collect(),
+            Expr::Constant(bits) => bits.clone(),
+            Expr::Concat(exprs) => exprs.iter().flat_map(|e| self.bits(e)).collect(),
+        }
+    }
+}
+
+/// Indices from `msb` to `lsb` (inclusive).
+fn range_indices(msb: i64, lsb: i64) -> Box<dyn Iterator<Item = i64>> {
+    if msb >= lsb {
+        Box::new((lsb..=msb).rev())
+    } else {
+        Box::new(msb..=lsb)
+    }
+}
+
+fn bit_name(name: &str, index: i64) -> String {
+    format!("{}[{}]", name, index)
+}
+
+/// Order the bits of a signal with the declared `range` by their bit index.
+/// `bits` must be ordered like [`Module::bit_names`].
+/// Returns `None` if the signal is not a bus or if the range does not start at `0`
+/// because the index of a bit in the netlist is its position in the bus.
+fn bus_bits<T>(range: Option<(i64, i64)>, mut bits: Vec<T>) -> Option<(BitOrder, Vec<T>)> {
+    let (msb, lsb) = range?;
+    if msb.min(lsb) != 0 {
+        return None;
+    }
+    if msb >= lsb {
+        bits.reverse();
+        Some((BitOrder::Descending, bits))
+    } else {
+        Some((BitOrder::Ascending, bits))
+    }
+}
+
+/// Upper limit of the number of bits created by a replication `{n{...}}`.
+/// Protects against exhausting memory with huge replication counts.
+const MAX_REPLICATION_WIDTH: usize = 1 << 20;
+
+/// Lower bound of the number of bits of the expression. Plain identifiers count as one bit
+/// because their width is not known while parsing.
+fn expr_width(expr: &Expr) -> usize {
+    match expr {
+        Expr::Ident(_) | Expr::BitSelect(_, _) => 1,
+        Expr::PartSelect(_, msb, lsb) => usize::try_from(msb.abs_diff(*lsb))
+            .unwrap_or(usize::MAX)
+            .saturating_add(1),
+        Expr::Constant(bits) => bits.len(),
+        Expr::Concat(exprs) => exprs
+            .iter()
+            .fold(0, |acc: usize, e| acc.saturating_add(expr_width(e))),
+    }
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn new(tokens: Vec<(Token, usize)>) -> Self {
+        Self { tokens, pos: 0 }
+    }
+
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or_else(|| self.tokens.last())
+            .map_or(0, |(_, line)| *line)
+    }
+
+    fn error<T>(&self, message: &str) -> Result<T, VerilogError> {
+        Err(VerilogError::Parse {
+            line: self.line(),
+            message: message.to_string(),
+        })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(t, _)| t)
+    }
+
+    fn next(&mut self) -> Result<Token, VerilogError> {
+        match self.tokens.get(self.pos) {
+            Some((t, _)) => {
+                self.pos += 1;
+                Ok(t.clone())
+            }
+            None => self.error("unexpected end of file"),
+        }
+    }
+
+    fn peek_symbol(&self, c: char) -> bool {
+        self.peek() == Some(&Token::Symbol(c))
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(s)) if s == keyword)
+    }
+
+    /// Consume the symbol `c` if it is the next token.
+    fn accept_symbol(&mut self, c: char) -> bool {
+        let found = self.peek_symbol(c);
+        self.pos += found as usize;
+        found
+    }
+
+    fn expect_symbol(&mut self, c: char) -> Result<(), VerilogError> {
+        if self.accept_symbol(c) {
+            Ok(())
+        } else {
+            self.error(&format!("expected '{}'", c))
+        }
+    }
+
+    fn expect_ident(&mut self) -> Result<String, VerilogError> {
+        match self.next()? {
+            Token::Ident(name) => Ok(name),
+            _ => {
+                self.pos -= 1;
+                self.error("expected identifier")
+            }
+        }
+    }
+
+    fn expect_int(&mut self) -> Result<i64, VerilogError> {
+        match self.next()? {
+            Token::Int(value) => Ok(value as i64),
+            _ => {
+                self.pos -= 1;
+                self.error("expected integer")
+            }
+        }
+    }
+
+    /// Skip tokens until after the next `;`.
+    fn skip_statement(&mut self) -> Result<(), VerilogError> {
+        while !matches!(self.next()?, Token::Symbol(';')) {}
+        Ok(())
+    }
+
+    /// Skip a parenthesized block. The opening parenthesis is the next token.
+    fn skip_parentheses(&mut self) -> Result<(), VerilogError> {
+        self.expect_symbol('(')?;
+        let mut depth = 1;
+        while depth > 0 {
+            match self.next()? {
+                Token::Symbol('(') => depth += 1,
+                Token::Symbol(')') => depth -= 1,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_modules(&mut self) -> Result<Vec<Module>, VerilogError> {
+        let mut modules = Vec::new();
+        while self.peek().is_some() {
+            if self.peek_keyword("module") {
+                modules.push(self.parse_module()?);
+            } else {
+                return self.error("expected 'module'");
+            }
+        }
+        Ok(modules)
+    }
+
+    /// Parse an optional range `[msb:lsb]`.
+    fn parse_range(&mut self) -> Result<Option<(i64, i64)>, VerilogError> {
+        if self.accept_symbol('[') {
+            let msb = self.expect_int()?;
+            self.expect_symbol(':')?;
+            let lsb = self.expect_int()?;
+            self.expect_symbol(']')?;
+            Ok(Some((msb, lsb)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn parse_direction(&mut self) -> Option<Direction> {
+        let direction = match self.peek() {
+            Some(Token::Ident(s)) if s == "input" => Direction::Input,
+            Some(Token::Ident(s)) if s == "output" => Direction::Output,
+            Some(Token::Ident(s)) if s == "inout" => Direction::InOut,
+            _ => return None,
+        };
+        self.pos += 1;
+        Some(direction)
+    }
+
+    /// Skip net type and `signed` keywords in declarations.
+    fn skip_declaration_modifiers(&mut self) {
+        while ["wire", "reg", "tri", "signed", "logic"]
+            .iter()
+            .any(|k| self.peek_keyword(k))
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn parse_module(&mut self) -> Result<Module, VerilogError> {
+        self.pos += 1; // Skip `module`.
+        let mut module = Module {
+            name: self.expect_ident()?,
+            ..Default::default()
+        };
+
+        if self.accept_symbol('#') {
+            self.skip_parentheses()?;
+        }
+
+        // Port list.
+        if self.accept_symbol('(') {
+            let mut direction = None;
+            let mut range = None;
+            while !self.accept_symbol(')') {
+                if let Some(d) = self.parse_direction() {
+                    // ANSI style port declaration.
+                    direction = Some(d);
+                    self.skip_declaration_modifiers();
+                    range = self.parse_range()?;
+                }
+                let name = self.expect_ident()?;
+                module.ports.push(name.clone());
+                module.declare(name, direction, range);
+                if !self.peek_symbol(')') {
+                    self.expect_symbol(',')?;
+                }
+            }
+        }
+        self.expect_symbol(';')?;
+
+        loop {
+            let keyword = match self.peek() {
+                Some(Token::Ident(keyword)) => keyword.clone(),
+                Some(_) => return self.error("unexpected token"),
+                None => return self.error("missing 'endmodule'"),
+            };
+            match keyword.as_str() {
+                "endmodule" => {
+                    self.pos += 1;
+                    break;
+                }
+                "input" | "output" | "inout" => {
+                    let direction = self.parse_direction();
+                    self.skip_declaration_modifiers();
+                    let range = self.parse_range()?;
+                    for name in self.parse_name_list()? {
+                        module.declare(name, direction, range);
+                    }
+                }
+                "wire" | "tri" | "wand" | "wor" => {
+                    self.pos += 1;
+                    self.skip_declaration_modifiers();
+                    let range = self.parse_range()?;
+                    loop {
+                        let name = self.expect_ident()?;
+                        module.declare(name.clone(), None, range);
+                        if self.accept_symbol('=') {
+                            // Net declaration assignment.
+                            let rhs = self.parse_expr()?;
+                            module.assignments.push((Expr::Ident(name), rhs));
+                        }
+                        if !self.accept_symbol(',') {
+                            break;
+                        }
+                    }
+                    self.expect_symbol(';')?;
+                }
+                "supply0" | "supply1" => {
+                    self.pos += 1;
+                    let value = if keyword == "supply0" {
+                        Bit::Zero
+                    } else {
+                        Bit::One
+                    };
+                    for name in self.parse_name_list()? {
+                        module.declare(name.clone(), None, None);
+                        module
+                            .assignments
+                            .push((Expr::Ident(name), Expr::Constant(vec![value.clone()])));
+                    }
+                }
+                "assign" => {
+                    self.pos += 1;
+                    loop {
+                        let lhs = self.parse_expr()?;
+                        self.expect_symbol('=')?;
+                        let rhs = self.parse_expr()?;
+                        module.assignments.push((lhs, rhs));
+                        if !self.accept_symbol(',') {
+                            break;
+                        }
+                    }
+                    self.expect_symbol(';')?;
+                }
+                "parameter" | "localparam" | "defparam" | "timeunit" | "timeprecision" => {
+                    self.skip_statement()?
+                }
+                "specify" => {
+                    while !self.peek_keyword("endspecify") {
+                        self.next()?;
+                    }
+                    self.pos += 1;
+                }
+                "always" | "initial" | "reg" | "function" | "task" | "generate" => {
+                    return self.error(&format!("'{}' is not supported", keyword));
+                }
+                _ => self.parse_instances(&mut module)?,
+            }
+        }
+
+        Ok(module)
+    }
+
+    /// Parse a comma separated list of names terminated by `;`.
+    fn parse_name_list(&mut self) -> Result<Vec<String>, VerilogError> {
+        let mut names = vec![self.expect_ident()?];
+        while self.accept_symbol(',') {
+            names.push(self.expect_ident()?);
+        }
+        self.expect_symbol(';')?;
+        Ok(names)
+    }
+
+    /// Parse one or more instances of the same module.
+    fn parse_instances(&mut self, module: &mut Module) -> Result<(), VerilogError> {
+        let template = self.expect_ident()?;
+        if self.accept_symbol('#') {
+            // Parameters are ignored.
+            self.skip_parentheses()?;
+        }
+        loop {
+            let line = self.line();
+            let name = self.expect_ident()?;
+            if self.peek_symbol('[') {
+                return self.error("arrays of instances are not supported");
+            }
+            self.expect_symbol('(')?;
+            let connections = if self.peek_symbol('.') {
+                let mut named = Vec::new();
+                while self.accept_symbol('.') {
+                    let port = self.expect_ident()?;
+                    self.expect_symbol('(')?;
+                    let expr = if self.peek_symbol(')') {
+                        None
+                    } else {
+                        Some(self.parse_expr()?)
+                    };
+                    self.expect_symbol(')')?;
+                    named.push((port, expr));
+                    if !self.accept_symbol(',') {
+                        break;
+                    }
+                }
+                Connections::Named(named)
+            } else {
+                let mut positional = Vec::new();
+                if !self.peek_symbol(')') {
+                    loop {
+                        if self.peek_symbol(',') || self.peek_symbol(')') {
+                            positional.push(None);
+                        } else {
+                            positional.push(Some(self.parse_expr()?));
+                        }
+                        if !self.accept_symbol(',') {
+                            break;
+                        }
+                    }
+                }
+                Connections::Positional(positional)
+            };
+            self.expect_symbol(')')?;
+            module.instances.push(Instance {
+                template: template.clone(),
+                name,
+                connections,
+                line,
+            });
+            if !self.accept_symbol(',') {
+                break;
+            }
+        }
+        self.expect_symbol(';')
+    }
+
+    fn parse_expr(&mut self) -> Result<Expr, VerilogError> {
+        match self.next()? {
+            Token::Ident(name) => {
+                if self.accept_symbol('[') {
+                    let msb = self.expect_int()?;
+                    let expr = if self.accept_symbol(':') {
+                        Expr::PartSelect(name, msb, self.expect_int()?)
+                    } else {
+                        Expr::BitSelect(name, msb)
+                    };
+                    self.expect_symbol(']')?;
+                    Ok(expr)
+                } else {
+                    Ok(Expr::Ident(name))
+                }
+            }
+            Token::Int(value) => Ok(Expr::Constant(int_to_bits(value))),
+            Token::Constant(bits) => Ok(Expr::Constant(bits)),
+            Token::Symbol('{') => {
+                let first = self.parse_expr()?;
+                if self.peek_symbol('{') {
+                    // Replication `{n{...}}`.
+                    let n = match first {
+                        Expr::Constant(bits) => bits.iter().try_fold(0usize, |acc, b| {
+                            acc.checked_mul(2)?.checked_add((b == &Bit::One) as usize)
+                        }),
+                        _ => return self.error("expected replication count"),
+                    };
+                    let inner = self.parse_expr()?;
+                    self.expect_symbol('}')?;
+                    let width = expr_width(&inner);
+                    return match n.filter(|n| n.saturating_mul(width) <= MAX_REPLICATION_WIDTH) {
+                        Some(n) => Ok(Expr::Concat(vec![inner; n])),
+                        None => self.error("replication count out of range"),
+                    };
+                }
+                let mut exprs = vec![first];
+                while self.accept_symbol(',') {
+                    exprs.push(self.parse_expr()?);
+                }
+                self.expect_symbol('}')?;
+                Ok(Expr::Concat(exprs))
+            }
+            _ => {
+                self.pos -= 1;
+                self.error("expected expression")
+            }
+        }
+    }
+}
+
+// Building the netlist.
+
+/// Group pin or net names into ports/buses.
+/// Consecutive names of the form `name[i]` with increasing or decreasing
+/// indices form a bus. Returns the base name, the range of the bus (if it is a bus) and the
+/// indices of the members in `names`.
+fn group_buses(names: &[String]) -> Vec<(String, Option<(i64, i64)>, Vec<usize>)> {
+    let mut groups: Vec<(String, Option<(i64, i64)>, Vec<usize>)> = Vec::new();
+    for (i, name) in names.iter().enumerate() {
+        let split = split_bit_name(name);
+        if let (Some((base, index)), Some((last_base, Some((msb, lsb)), members))) =
+            (&split, groups.last_mut())
+        {
+            let step = index - *lsb;
+            // The direction of a bus is defined by its first two members.
+            let same_direction = members.len() == 1 || step == (*lsb - *msb).signum();
+            if last_base == base && step.abs() == 1 && same_direction {
+                *lsb = *index;
+                members.push(i);
+                continue;
+            }
+        }
+        match split {
+            Some((base, index)) => groups.push((base, Some((index, index)), vec![i])),
+            None => groups.push((name.clone(), None, vec![i])),
+        }
+    }
+    groups
+}
+
+/// Split a name like `a[3]` into `("a", 3)`.
+fn split_bit_name(name: &str) -> Option<(String, i64)> {
+    let open = name.rfind('[')?;
+    let index = name.strip_suffix(']')?.get(open + 1..)?.parse().ok()?;
+    Some((name[..open].to_string(), index))
+}
+
+/// Ports of a cell: Port names and the pins of each port, MSB first.
+fn cell_ports<N: NetlistBase>(netlist: &N, cell: &N::CellId) -> Vec<(String, Vec<N::PinId>)> {
+    let pins = netlist.each_pin_vec(cell);
+    let names: Vec<String> = pins.iter().map(|p| netlist.pin_name(p).into()).collect();
+    group_buses(&names)
+        .into_iter()
+        .map(|(name, _, members)| {
+            let port_pins = members.iter().map(|&i| pins[i].clone()).collect();
+            (name, port_pins)
+        })
+        .collect()
+}
+
+/// Union-find structure for merging nets.
+struct NetClasses {
+    parent: HashMap<Bit, Bit>,
+}
+
+impl NetClasses {
+    fn find(&mut self, bit: &Bit) -> Bit {
+        let parent = self
+            .parent
+            .entry(bit.clone())
+            .or_insert_with(|| bit.clone())
+            .clone();
+        if &parent == bit {
+            parent
+        } else {
+            let root = self.find(&parent);
+            self.parent.insert(bit.clone(), root.clone());
+            root
+        }
+    }
+
+    fn union(&mut self, a: &Bit, b: &Bit) -> Result<(), VerilogError> {
+        if a == &Bit::Undefined || b == &Bit::Undefined {
+            return Ok(());
+        }
+        let (ra, rb) = (self.find(a), self.find(b));
+        if ra == rb {
+            return Ok(());
+        }
+        // Constants always are the representative of their class.
+        match (&ra, &rb) {
+            (Bit::Signal(_), _) => {
+                self.parent.insert(ra, rb);
+            }
+            (_, Bit::Signal(_)) => {
+                self.parent.insert(rb, ra);
+            }
+            _ => return Err(VerilogError::Invalid("constant 0 and 1 are shorted".into())),
+        }
+        Ok(())
+    }
+}
+
+/// Bits of the buses found while populating the netlist. They are only created
+/// if the netlist supports buses.
+struct Buses<N: NetlistBase> {
+    pin_buses: Vec<(String, Vec<N::PinId>, BitOrder)>,
+    net_buses: Vec<(String, Vec<N::NetId>, BitOrder)>,
+}
+
+fn populate_netlist<N: NetlistEdit>(
+    modules: &[Module],
+    netlist: &mut N,
+    create_black_boxes: bool,
+) -> Result<Buses<N>, VerilogError> {
+    let mut buses = Buses {
+        pin_buses: vec![],
+        net_buses: vec![],
+    };
+
+    // Create cells and pins.
+    let mut cells = HashMap::new();
+    for module in modules {
+        if netlist.cell_by_name(&module.name).is_some() {
+            return Err(VerilogError::CellNameCollision(module.name.clone()));
+        }
+        let cell = netlist.create_cell(module.name.clone().into());
+        for port in &module.ports {
+            let direction = module.signals[port].direction.ok_or_else(|| {
+                VerilogError::Invalid(format!(
+                    "direction of port '{}' in module '{}' is not declared",
+                    port, module.name
+                ))
+            })?;
+            let pins: Vec<_> = module
+                .bit_names(port)
+                .into_iter()
+                .map(|bit| netlist.create_pin(&cell, bit.into(), direction))
+                .collect();
+            if let Some((bit_order, pins)) = bus_bits(module.signals[port].range, pins) {
+                buses.pin_buses.push((port.clone(), pins, bit_order));
+            }
+        }
+        cells.insert(module.name.clone(), cell);
+    }
+
+    // Find templates of all instances. Create black boxes if necessary.
+    for module in modules {
+        for inst in &module.instances {
+            if cells.contains_key(&inst.template) {
+                continue;
+            }
+            if let Some(cell) = netlist.cell_by_name(&inst.template) {
+                cells.insert(inst.template.clone(), cell);
+            } else if create_black_boxes {
+                let cell = netlist.create_cell(inst.template.clone().into());
+                let named = match &inst.connections {
+                    Connections::Named(named) => named,
+                    Connections::Positional(_) => {
+                        return Err(VerilogError::UnknownModule(inst.template.clone()))
+                    }
+                };
+                for (port, expr) in named {
+                    let width = expr.as_ref().map_or(1, |e| module.bits(e).len());
+                    if width == 1 {
+                        netlist.create_pin(&cell, port.clone().into(), Direction::None);
+                    } else {
+                        for i in (0..width as i64).rev() {
+                            netlist.create_pin(&cell, bit_name(port, i).into(), Direction::None);
+                        }
+                    }
+                }
+                cells.insert(inst.template.clone(), cell);
+            } else {
+                return Err(VerilogError::UnknownModule(inst.template.clone()));
+            }
+        }
+    }
+
+    let mut ports_of_cell = HashMap::new();
+
+    for module in modules {
+        let cell = cells[&module.name].clone();
+
+        // Collect the nets connected to each pin instance.
+        let mut instance_connections = Vec::new();
+        for inst in &module.instances {
+            let template = &cells[&inst.template];
+            let ports = ports_of_cell
+                .entry(inst.template.clone())
+                .or_insert_with(|| cell_ports(netlist, template));
+
+            let invalid = |message: String| VerilogError::Parse {
+                line: inst.line,
+                message,
+            };
+
+            let mut connections = Vec::new();
+            let mut connect = |pins: &Vec<N::PinId>, expr: &Expr| {
+                let mut bits = module.bits(expr);
+                resize_bits(&mut bits, pins.len(), Bit::Zero);
+                connections.extend(pins.iter().cloned().zip(bits));
+            };
+            match &inst.connections {
+                Connections::Named(named) => {
+                    for (port, expr) in named {
+                        // Single bits of a bus can also be connected by the full name of the pin.
+                        let pins = match ports.iter().find(|(name, _)| name == port) {
+                            Some((_, pins)) => pins.clone(),
+                            None => vec![netlist.pin_by_name(template, port).ok_or_else(|| {
+                                invalid(format!(
+                                    "module '{}' has no port '{}'",
+                                    inst.template, port
+                                ))
+                            })?],
+                        };
+                        if let Some(expr) = expr {
+                            connect(&pins, expr);
+                        }
+                    }
+                }
+                Connections::Positional(positional) => {
+                    if positional.len() > ports.len() {
+                        return Err(invalid(format!(
+                            "too many ports connected to '{}'",
+                            inst.template
+                        )));
+                    }
+                    for ((_, pins), expr) in ports.iter().zip(positional) {
+                        if let Some(expr) = expr {
+                            connect(pins, expr);
+                        }
+                    }
+                }
+            }
+
+            if netlist.cell_instance_by_name(&cell, &inst.name).is_some() {
+                return Err(invalid(format!(
+                    "instance '{}' is defined more than once",
+                    inst.name
+                )));
+            }
+            if netlist.cell_depends_on(template, &cell) {
+                return Err(invalid(format!(
+                    "module '{}' is instantiated recursively",
+                    inst.template
+                )));
+            }
+            let inst_id =
+                netlist.create_cell_instance(&cell, template, Some(inst.name.clone().into()));
+            instance_connections.push((inst_id, connections));
+        }
+
+        // Merge nets which are connected by `assign` statements.
+        let mut classes = NetClasses {
+            parent: HashMap::new(),
+        };
+        for (lhs, rhs) in &module.assignments {
+            let lhs = module.bits(lhs);
+            let mut rhs = module.bits(rhs);
+            resize_bits(&mut rhs, lhs.len(), Bit::Zero);
+            for (a, b) in lhs.iter().zip(&rhs) {
+                classes.union(a, b)?;
+            }
+        }
+
+        // Create one net per class.
+        let mut nets: HashMap<Bit, N::NetId> = HashMap::new();
+        let mut net_of_bit = |netlist: &mut N, classes: &mut NetClasses, bit: &Bit| {
+            let root = classes.find(bit);
+            nets.entry(root.clone())
+                .or_insert_with(|| match (&root, bit) {
+                    (Bit::Zero, _) => netlist.net_zero(&cell),
+                    (Bit::One, _) => netlist.net_one(&cell),
+                    // The net is named after the first bit which uses it.
+                    (_, Bit::Signal(name)) => netlist.create_net(&cell, Some(name.clone().into())),
+                    _ => unreachable!("undefined bits are never connected"),
+                })
+                .clone()
+        };
+
+        // Ports define the names of the nets first.
+        let pins = netlist.each_pin_vec(&cell);
+        for pin in pins {
+            let bit = Bit::Signal(netlist.pin_name(&pin).into());
+            let net = net_of_bit(netlist, &mut classes, &bit);
+            netlist.connect_pin(&pin, Some(net));
+        }
+        let mut bus_nets = HashSet::new();
+        for name in &module.signal_order {
+            let bits = module.bit_names(name);
+            let bit_nets: Vec<_> = bits
+                .iter()
+                .map(|bit| net_of_bit(netlist, &mut classes, &Bit::Signal(bit.clone())))
+                .collect();
+            // Bits which got merged with other signals cannot be part of the bus.
+            let own_nets = bits.iter().zip(&bit_nets).all(|(bit, net)| {
+                !bus_nets.contains(net)
+                    && netlist.net_name(net).map_or(false, |n| {
+                        let n: &str = n.borrow();
+                        n == bit
+                    })
+            });
+            if own_nets {
+                if let Some((bit_order, nets)) = bus_bits(module.signals[name].range, bit_nets) {
+                    bus_nets.extend(nets.iter().cloned());
+                    buses.net_buses.push((name.clone(), nets, bit_order));
+                }
+            }
+        }
+        for (inst, connections) in instance_connections {
+            for (pin, bit) in connections {
+                if bit != Bit::Undefined {
+                    let net = net_of_bit(netlist, &mut classes, &bit);
+                    let pin_inst = netlist.pin_instance(&inst, &pin);
+                    netlist.connect_pin_instance(&pin_inst, Some(net));
+                }
+            }
+        }
+    }
+
+    Ok(buses)
+}
+
+// Writer.
+
+const KEYWORDS: &[&str] = &[
+    "always",
+    "and",
+    "assign",
+    "begin",
+    "buf",
+    "case",
+    "default",
+    "else",
+    "end",
+    "endcase",
+    "endmodule",
+    "for",
+    "function",
+    "if",
+    "initial",
+    "inout",
+    "input",
+    "integer",
+    "module",
+    "nand",
+    "nor",
+    "not",
+    "or",
+    "output",
+    "parameter",
+    "reg",
+    "supply0",
+    "supply1",
+    "tri",
+    "wire",
+    "xor",
+];
+
+/// Escape identifiers which are not simple Verilog identifiers.
+fn escape_identifier(name: &str) -> String {
+    let is_simple = name
+        .chars()
+        .next()
+        .map_or(false, |c| c.is_ascii_alphabetic() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+        && !KEYWORDS.contains(&name);
+    if is_simple {
+        name.to_string()
+    } else {
+        format!("\\{} ", name)
+    }
+}
+
+fn format_range(range: Option<(i64, i64)>) -> String {
+    match range {
+        Some((msb, lsb)) => format!("[{}:{}] ", msb, lsb),
+        None => String::new(),
+    }
+}
+
+/// Find buses which can be declared with a range.
+/// Returns the ranges of the buses and the names which are members of those buses.
+fn declarable_buses(
+    groups: &[(String, Option<(i64, i64)>, Vec<usize>)],
+    reserved: &HashSet<String>,
+) -> HashMap<String, (i64, i64)> {
+    let mut count: HashMap<&str, usize> = HashMap::new();
+    for (base, _, _) in groups {
+        *count.entry(base).or_default() += 1;
+    }
+    groups
+        .iter()
+        .filter_map(|(base, range, _)| range.map(|r| (base, r)))
+        .filter(|(base, _)| {
+            count[base.as_str()] == 1
+                && !reserved.contains(*base)
+                && escape_identifier(base) == **base
+        })
+        .map(|(base, range)| (base.clone(), range))
+        .collect()
+}
+
+fn write_module<W: Write, N: NetlistBase>(
+    writer: &mut W,
+    netlist: &N,
+    cell: &N::CellId,
+) -> Result<(), VerilogError> {
+    let pins = netlist.each_pin_vec(cell);
+    let pin_names: Vec<String> = pins.iter().map(|p| netlist.pin_name(p).into()).collect();
+    let port_groups = group_buses(&pin_names);
+    let port_buses = declarable_buses(&port_groups, &HashSet::new());
+
+    // Reference to a single bit of a port or wire.
+    let bit_ref = |name: &str, buses: &HashMap<String, (i64, i64)>| {
+        let in_bus = split_bit_name(name).map_or(false, |(base, index)| {
+            buses.get(&base).map_or(false, |&(msb, lsb)| {
+                msb.min(lsb) <= index && index <= msb.max(lsb)
+            })
+        });
+        if in_bus {
+            name.to_string()
+        } else {
+            escape_identifier(name)
+        }
+    };
+
+    // Choose names of nets.
+    let net_zero = netlist.net_zero(cell);
+    let net_one = netlist.net_one(cell);
+    let mut used_names: HashSet<String> = pin_names.iter().cloned().collect();
+    used_names.extend(port_buses.keys().cloned());
+    let mut net_names: HashMap<N::NetId, String> = HashMap::new();
+    // Nets which are connected to a pin with the same name keep the name of the pin.
+    for (pin, name) in pins.iter().zip(&pin_names) {
+        if let Some(net) = netlist.net_of_pin(pin) {
+            if netlist.net_name(&net).map(Into::<String>::into).as_ref() == Some(name) {
+                net_names.insert(net, name.clone());
+            }
+        }
+    }
+    let mut wires = Vec::new();
+    for net in netlist.each_internal_net(cell) {
+        if net == net_zero || net == net_one || net_names.contains_key(&net) {
+            continue;
+        }
+        let mut name: String = netlist
+            .net_name(&net)
+            .map(|n| n.into())
+            .unwrap_or_else(|| "__net".to_string());
+        if used_names.contains(&name) {
+            let base = name;
+            name = (1..)
+                .map(|i| format!("{}_{}", base, i))
+                .find(|n| !used_names.contains(n))
+                .unwrap();
+        }
+        used_names.insert(name.clone());
+        net_names.insert(net, name.clone());
+        wires.push(name);
+    }
+
+    // Declare wires as buses if possible.
+    wires.sort_by_key(|name| split_bit_name(name).map(|(base, index)| (base, -index)));
+    let wire_groups = group_buses(&wires);
+    let reserved: HashSet<String> = port_groups.iter().map(|(b, _, _)| b.clone()).collect();
+    let wire_buses = declarable_buses(&wire_groups, &reserved);
+    let all_buses: HashMap<String, (i64, i64)> = port_buses
+        .iter()
+        .chain(&wire_buses)
+        .map(|(k, v)| (k.clone(), *v))
+        .collect();
+
+    let net_ref = |net: &Option<N::NetId>| -> String {
+        match net {
+            None => "1'bz".to_string(),
+            Some(net) if net == &net_zero => "1'b0".to_string(),
+            Some(net) if net == &net_one => "1'b1".to_string(),
+            Some(net) => bit_ref(&net_names[net], &all_buses),
+        }
+    };
+
+    // Port list.
+    let port_list: Vec<String> = port_groups
+        .iter()
+        .flat_map(|(base, _, members)| {
+            if port_buses.contains_key(base) {
+                vec![base.clone()]
+            } else {
+                members
+                    .iter()
+                    .map(|&i| escape_identifier(&pin_names[i]))
+                    .collect()
+            }
+        })
+        .collect();
+    let cell_name: String = netlist.cell_name(cell).into();
+    writeln!(
+        writer,
+        "module {}({});",
+        escape_identifier(&cell_name),
+        port_list.join(", ")
+    )?;
+
+    // Port declarations.
+    for (base, range, members) in &port_groups {
+        let direction = match netlist.pin_direction(&pins[members[0]]) {
+            Direction::Input | Direction::Clock => "input",
+            Direction::Output => "output",
+            _ => "inout",
+        };
+        if port_buses.contains_key(base) {
+            writeln!(writer, "  {} {}{};", direction, format_range(*range), base)?;
+        } else {
+            for &i in members {
+                let direction = match netlist.pin_direction(&pins[i]) {
+                    Direction::Input | Direction::Clock => "input",
+                    Direction::Output => "output",
+                    _ => "inout",
+                };
+                writeln!(
+                    writer,
+                    "  {} {};",
+                    direction,
+                    escape_identifier(&pin_names[i])
+                )?;
+            }
+        }
+    }
+
+    // Wire declarations.
+    for (base, range, members) in &wire_groups {
+        if wire_buses.contains_key(base) {
+            writeln!(writer, "  wire {}{};", format_range(*range), base)?;
+        } else {
+            for &i in members {
+                writeln!(writer, "  wire {};", escape_identifier(&wires[i]))?;
+            }
+        }
+    }
+
+    // Connect ports to nets with another name.
+    for (pin, name) in pins.iter().zip(&pin_names) {
+        let net = netlist.net_of_pin(pin);
+        let same_name = net
+            .as_ref()
+            .map_or(false, |n| net_names.get(n) == Some(name));
+        if net.is_some() && !same_name {
+            writeln!(
+                writer,
+                "  assign {} = {};",
+                bit_ref(name, &port_buses),
+                net_ref(&net)
+            )?;
+        }
+    }
+
+    // Instances.
+    let mut used_instance_names = HashSet::new();
+    for (i, inst) in netlist.each_cell_instance(cell).enumerate() {
+        let template = netlist.template_cell(&inst);
+        let template_name: String = netlist.cell_name(&template).into();
+        let inst_name: String = netlist
+            .cell_instance_name(&inst)
+            .map(|n| n.into())
+            .filter(|n: &String| !used_instance_names.contains(n))
+            .unwrap_or_else(|| format!("__inst{}", i));
+        used_instance_names.insert(inst_name.clone());
+
+        // Ports which appear more than once cannot be connected by their name.
+        // Connect each pin separately instead.
+        let template_ports = cell_ports(netlist, &template);
+        let mut port_count: HashMap<&str, usize> = HashMap::new();
+        for (port, _) in &template_ports {
+            *port_count.entry(port).or_default() += 1;
+        }
+        let connections: Vec<String> = template_ports
+            .iter()
+            .flat_map(|(port, pins)| {
+                if port_count[port.as_str()] == 1 {
+                    vec![(port.clone(), pins.clone())]
+                } else {
+                    pins.iter()
+                        .map(|p| (netlist.pin_name(p).into(), vec![p.clone()]))
+                        .collect()
+                }
+            })
+            .map(|(port, pins)| {
+                let nets: Vec<_> = pins
+                    .iter()
+                    .map(|pin| netlist.net_of_pin_instance(&netlist.pin_instance(&inst, pin)))
+                    .collect();
+                let expr = if nets.iter().all(|n| n.is_none()) {
+                    String::new()
+                } else if nets.len() == 1 {
+                    net_ref(&nets[0])
+                } else {
+                    let bits: Vec<_> = nets.iter().map(|n| net_ref(n)).collect();
+                    format!("{{{}}}", bits.join(", "))
+                };
+                format!(".{}({})", escape_identifier(&port), expr)
+            })
+            .collect();
+
+        writeln!(
+            writer,
+            "  {} {} ({});",
+            escape_identifier(&template_name),
+            escape_identifier(&inst_name),
+            connections.join(", ")
+        )?;
+    }
+
+    writeln!(writer, "endmodule")?;
+    writeln!(writer)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    const NETLIST: &str = r"
+        // Leaf cell.
+        module INV (A, Y);
+            input A;
+            output Y;
+        endmodule
+
+        module AND2 (input A, input B, output Y);
+        endmodule
+
+        /* Top cell with a bus. */
+        module TOP (in, out, \esc[0]$ , tie);
+            input [1:0] in;
+            output [1:0] out;
+            output \esc[0]$ ;
+            output tie;
+            wire n1, n2;
+            assign n2 = n1;
+            assign tie = 1'b1;
+            (* keep *)
+            INV inv0 (.A(in[0]), .Y(n1));
+            INV inv1 (in[1], out[1]);
+            AND2 and0 (.A(n2), .B(1'b0), .Y(out[0])), and1 (.A(in[1]), .B(), .Y(\esc[0]$ ));
+        endmodule
+    ";
+
+    #[test]
+    fn test_read_verilog() {
+        let chip: Chip = VerilogReader::new()
+            .read_netlist_with_buses(&mut NETLIST.as_bytes())
+            .unwrap();
+
+        let top = chip.cell_by_name("TOP").unwrap();
+        let pin_names: Vec<String> = chip
+            .each_pin(&top)
+            .map(|p| chip.pin_name(&p).into())
+            .collect();
+        assert_eq!(
+            pin_names,
+            vec!["in[1]", "in[0]", "out[1]", "out[0]", "esc[0]$", "tie"]
+        );
+
+        // Constant.
+        let tie = chip.pin_by_name(&top, "tie").unwrap();
+        assert_eq!(chip.net_of_pin(&tie), Some(chip.net_one(&top)));
+
+        // Named connections and `assign`.
+        let inv0 = chip.cell_instance_by_name(&top, "inv0").unwrap();
+        let and0 = chip.cell_instance_by_name(&top, "and0").unwrap();
+        let inv = chip.cell_by_name("INV").unwrap();
+        let and2 = chip.cell_by_name("AND2").unwrap();
+        let inv_y = chip.pin_by_name(&inv, "Y").unwrap();
+        let and_a = chip.pin_by_name(&and2, "A").unwrap();
+        let and_b = chip.pin_by_name(&and2, "B").unwrap();
+        let n1 = chip.net_of_pin_instance(&chip.pin_instance(&inv0, &inv_y));
+        assert!(n1.is_some());
+        assert_eq!(
+            chip.net_of_pin_instance(&chip.pin_instance(&and0, &and_a)),
+            n1
+        );
+        assert_eq!(
+            chip.net_of_pin_instance(&chip.pin_instance(&and0, &and_b)),
+            Some(chip.net_zero(&top))
+        );
+
+        // Positional connections.
+        let inv1 = chip.cell_instance_by_name(&top, "inv1").unwrap();
+        let out1 = chip.pin_by_name(&top, "out[1]").unwrap();
+        assert_eq!(
+            chip.net_of_pin_instance(&chip.pin_instance(&inv1, &inv_y)),
+            chip.net_of_pin(&out1)
+        );
+
+        // Buses.
+        let in_bus = chip.pin_bus_by_name(&top, "in").unwrap();
+        let in0 = chip.pin_by_name(&top, "in[0]").unwrap();
+        assert_eq!(chip.pin_bus_width(&in_bus), 2);
+        assert_eq!(chip.pin_bus_bit_order(&in_bus), BitOrder::Descending);
+        assert_eq!(chip.bus_of_pin(&in0), Some(in_bus));
+        assert_eq!(chip.pin_bit_index(&in0), Some(0));
+        let out_nets = chip.net_bus_by_name(&top, "out").unwrap();
+        assert_eq!(chip.net_bus_bit(&out_nets, 1), chip.net_of_pin(&out1));
+        assert!(chip.net_bus_by_name(&top, "esc").is_none());
+
+        // Escaped identifier and unconnected port.
+        let and1 = chip.cell_instance_by_name(&top, "and1").unwrap();
+        let and_y = chip.pin_by_name(&and2, "Y").unwrap();
+        let esc = chip.pin_by_name(&top, "esc[0]$").unwrap();
+        assert_eq!(
+            chip.net_of_pin_instance(&chip.pin_instance(&and1, &and_y)),
+            chip.net_of_pin(&esc)
+        );
+        assert_eq!(
+            chip.net_of_pin_instance(&chip.pin_instance(&and1, &and_b)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_write_and_read_back() {
+        let chip: Chip = VerilogReader::new()
+            .read_netlist(&mut NETLIST.as_bytes())
+            .unwrap();
+
+        let mut buffer = Vec::new();
+        VerilogWriter::new()
+            .write_leaf_cells(true)
+            .write_netlist(&mut buffer, &chip)
+            .unwrap();
+        let verilog = String::from_utf8(buffer).unwrap();
+        assert!(verilog.contains("input [1:0] in;"));
+
+        let restored: Chip = VerilogReader::new()
+            .read_netlist(&mut verilog.as_bytes())
+            .unwrap();
+
+        // Compare the connectivity of all pin instances by the names of the nets.
+        let top = chip.cell_by_name("TOP").unwrap();
+        let restored_top = restored.cell_by_name("TOP").unwrap();
+        for inst in chip.each_cell_instance(&top) {
+            let name = chip.cell_instance_name(&inst).unwrap();
+            let restored_inst = restored
+                .cell_instance_by_name(&restored_top, &name)
+                .unwrap();
+            for pin_inst in chip.each_pin_instance(&inst) {
+                let pin = chip.template_pin(&pin_inst);
+                let pin_name = chip.pin_name(&pin);
+                let restored_pin = restored
+                    .pin_by_name(&restored.template_cell(&restored_inst), &pin_name)
+                    .unwrap();
+                let net = chip.net_of_pin_instance(&pin_inst);
+                let restored_net = restored
+                    .net_of_pin_instance(&restored.pin_instance(&restored_inst, &restored_pin));
+                assert_eq!(net.is_some(), restored_net.is_some());
+                if let (Some(net), Some(restored_net)) = (net, restored_net) {
+                    assert_eq!(chip.net_name(&net), restored.net_name(&restored_net));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_unknown_module() {
+        let verilog = "module TOP (a); input a; BUF b (.A(a)); endmodule";
+        let result: Result<Chip, _> = VerilogReader::new().read_netlist(&mut verilog.as_bytes());
+        assert!(matches!(result, Err(VerilogError::UnknownModule(_))));
+
+        let chip: Chip = VerilogReader::new()
+            .create_black_boxes(true)
+            .read_netlist(&mut verilog.as_bytes())
+            .unwrap();
+        let buf = chip.cell_by_name("BUF").unwrap();
+        assert!(chip.pin_by_name(&buf, "A").is_some());
+    }
+
+    #[test]
+    fn test_invalid_instances() {
+        let recursive = "module A (); B b (); endmodule module B (); A a (); endmodule";
+        let result: Result<Chip, _> = VerilogReader::new().read_netlist(&mut recursive.as_bytes());
+        assert!(matches!(result, Err(VerilogError::Parse { .. })));
+
+        let recursive = "module A (); A a (); endmodule";
+        let result: Result<Chip, _> = VerilogReader::new().read_netlist(&mut recursive.as_bytes());
+        assert!(matches!(result, Err(VerilogError::Parse { .. })));
+
+        let duplicate = "module A (); endmodule module TOP (); A a (); A a (); endmodule";
+        let result: Result<Chip, _> = VerilogReader::new().read_netlist(&mut duplicate.as_bytes());
+        assert!(matches!(result, Err(VerilogError::Parse { .. })));
+    }
+
+    #[test]
+    fn test_replication() {
+        let verilog = "module TOP (out); output [3:0] out; assign out = {2{2'b10}}; endmodule";
+        let chip: Chip = VerilogReader::new()
+            .read_netlist(&mut verilog.as_bytes())
+            .unwrap();
+        let top = chip.cell_by_name("TOP").unwrap();
+        let out3 = chip.pin_by_name(&top, "out[3]").unwrap();
+        assert_eq!(chip.net_of_pin(&out3), Some(chip.net_one(&top)));
+
+        // Replications which would exhaust the memory.
+        for count in ["32'hFFFFFFFF", "80'hFFFFFFFFFFFFFFFFFFFF"] {
+            let verilog = format!(
+                "module TOP (out); output out; assign out = {{{}{{1'b0}}}}; endmodule",
+                count
+            );
+            let result: Result<Chip, _> =
+                VerilogReader::new().read_netlist(&mut verilog.as_bytes());
+            assert!(matches!(result, Err(VerilogError::Parse { .. })));
+        }
+    }
+
+    #[test]
+    fn test_group_buses() {
+        let names: Vec<String> = vec!["a[1]", "a[0]", "b", "c[0]", "c[1]", "c[3]"]
+            .into_iter()
+            .map(|s| s.to_string())
+            .collect();
+        let groups = group_buses(&names);
+        let ranges: Vec<_> = groups.iter().map(|(b, r, _)| (b.as_str(), *r)).collect();
+        assert_eq!(
+            ranges,
+            vec![
+                ("a", Some((1, 0))),
+                ("b", None),
+                ("c", Some((0, 1))),
+                ("c", Some((3, 3)))
+            ]
+        );
+    }
+}
@@ -23,8 +23,10 @@
 //! [`NetlistEdit`]: traits::NetlistEdit
 
 pub mod arc_id;
+pub mod bus;
 pub mod direction;
 pub mod io;
+pub mod power;
 pub mod prelude;
 pub mod terminal_id;
 pub mod traits;
@@ -0,0 +1,223 @@
+// SPDX-FileCopyrightText: 2022 Thomas Kramer
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Traits for power domains.
+//!
+//! A power domain is supplied by a pair of nets: a supply net (VDD) and a ground net (VSS).
+//! Both nets live in the same cell, this is the parent cell of the domain. Additionally a domain
+//! has a nominal voltage and can be marked as *always-on*, i.e. it is never switched off.
+//!
+//! Cells and cell instances can be assigned to power domains. A cell instance without
+//! an explicit assignment belongs to the domain of the cell it lives in.
+//!
+//! Supply pins of cells are marked with [`Direction::Supply`] and [`Direction::Ground`].
+//!
+//! # Example
+//!
+//! ```
+//! use libreda_db::prelude::*;
+//!
+//! let mut chip = Chip::new();
+//! let top = chip.create_cell("TOP".into());
+//! let inv = chip.create_cell("INV".into());
+//! chip.create_pin(&inv, "VDD".into(), Direction::Supply);
+//! chip.create_pin(&inv, "VSS".into(), Direction::Ground);
+//!
+//! let vdd = chip.create_net(&top, Some("VDD_CORE".into()));
+//! let vss = chip.create_net(&top, Some("VSS".into()));
+//! let core = chip.create_power_domain("CORE".into(), vdd, vss, 0.9, false);
+//! chip.set_power_domain_of_cell(&top, Some(core));
+//!
+//! let inst = chip.create_cell_instance(&top, &inv, None);
+//! chip.connect_supply_pins(&inst, &core);
+//! assert_eq!(chip.power_domain_of_cell_instance(&inst), Some(core));
+//! ```
+
+use super::prelude::*;
+use std::hash::Hash;
+
+/// Access to power domains of a netlist.
+pub trait PowerBase: NetlistBase {
+    /// Power domain identifier type. Uniquely identifies a power domain in the whole netlist.
+    type PowerDomainId: Eq + Hash + Clone + std::fmt::Debug + 'static;
+
+    /// Find a power domain by its name.
+    /// Returns `None` if no such domain exists.
+    fn power_domain_by_name(&self, name: &str) -> Option<Self::PowerDomainId>;
+
+    /// Get the name of the power domain.
+    fn power_domain_name(&self, domain: &Self::PowerDomainId) -> Self::NameType;
+
+    /// Get the cell which contains the supply and ground nets of the power domain.
+    fn parent_cell_of_power_domain(&self, domain: &Self::PowerDomainId) -> Self::CellId;
+
+    /// Get the net which supplies the power domain (VDD).
+    fn power_domain_supply_net(&self, domain: &Self::PowerDomainId) -> Self::NetId;
+
+    /// Get the ground net of the power domain (VSS).
+    fn power_domain_ground_net(&self, domain: &Self::PowerDomainId) -> Self::NetId;
+
+    /// Get the nominal supply voltage of the power domain in volts.
+    fn power_domain_voltage(&self, domain: &Self::PowerDomainId) -> f64;
+
+    /// Check if the power domain is always on, i.e. it cannot be switched off.
+    fn power_domain_is_always_on(&self, domain: &Self::PowerDomainId) -> bool;
+
+    /// Call a function for each power domain.
+    fn for_each_power_domain<F>(&self, f: F)
+    where
+        F: FnMut(Self::PowerDomainId) -> ();
+
+    /// Get a `Vec` with the IDs of all power domains.
+    fn each_power_domain_vec(&self) -> Vec<Self::PowerDomainId> {
+        let mut v = Vec::new();
+        self.for_each_power_domain(|d| v.push(d));
+        v
+    }
+
+    /// Iterate over all power domains.
+    fn each_power_domain(&self) -> Box<dyn Iterator<Item = Self::PowerDomainId> + '_> {
+        Box::new(self.each_power_domain_vec().into_iter())
+    }
+
+    /// Get the power domain which is assigned to the cell.
+    /// The content of the cell belongs to this domain unless cell instances are assigned
+    /// to other domains.
+    fn power_domain_of_cell(&self, cell: &Self::CellId) -> Option<Self::PowerDomainId>;
+
+    /// Get the power domain which is explicitly assigned to the cell instance.
+    fn assigned_power_domain_of_cell_instance(
+        &self,
+        inst: &Self::CellInstId,
+    ) -> Option<Self::PowerDomainId>;
+
+    /// Get the power domain of the cell instance. This is the explicitly assigned
+    /// domain or, if there is none, the domain of the parent cell.
+    fn power_domain_of_cell_instance(
+        &self,
+        inst: &Self::CellInstId,
+    ) -> Option<Self::PowerDomainId> {
+        self.assigned_power_domain_of_cell_instance(inst)
+            .or_else(|| self.power_domain_of_cell(&self.parent_cell(inst)))
+    }
+
+    /// Get all power domains which are supplied by the net.
+    /// The net can be the supply net or the ground net of a domain.
+    fn each_power_domain_of_supply_net_vec(&self, net: &Self::NetId) -> Vec<Self::PowerDomainId> {
+        // Inefficient default implementation.
+        self.each_power_domain()
+            .filter(|d| {
+                &self.power_domain_supply_net(d) == net || &self.power_domain_ground_net(d) == net
+            })
+            .collect()
+    }
+
+    /// Check if the net supplies any power domain.
+    fn is_supply_net(&self, net: &Self::NetId) -> bool {
+        !self.each_power_domain_of_supply_net_vec(net).is_empty()
+    }
+
+    /// Get the power domains of all terminals connected to the net. Pins belong to the
+    /// domain of their cell, pin instances belong to the domain of their cell instance.
+    ///
+    /// A signal net which connects more than one domain needs level-shifters
+    /// or isolation cells.
+    fn each_power_domain_of_net_vec(&self, net: &Self::NetId) -> Vec<Self::PowerDomainId> {
+        let mut domains = Vec::new();
+        let mut add = |d: Option<Self::PowerDomainId>| {
+            if let Some(d) = d {
+                if !domains.contains(&d) {
+                    domains.push(d);
+                }
+            }
+        };
+        if self.num_net_pins(net) > 0 {
+            add(self.power_domain_of_cell(&self.parent_cell_of_net(net)));
+        }
+        self.for_each_pin_instance_of_net(net, |pin_inst| {
+            add(self.power_domain_of_cell_instance(&self.parent_of_pin_instance(&pin_inst)))
+        });
+        domains
+    }
+
+    /// Get all supply pins (direction [`Direction::Supply`]) of the cell.
+    fn each_supply_pin_vec(&self, cell: &Self::CellId) -> Vec<Self::PinId> {
+        self.each_pin(cell)
+            .filter(|p| self.pin_direction(p) == Direction::Supply)
+            .collect()
+    }
+
+    /// Get all ground pins (direction [`Direction::Ground`]) of the cell.
+    fn each_ground_pin_vec(&self, cell: &Self::CellId) -> Vec<Self::PinId> {
+        self.each_pin(cell)
+            .filter(|p| self.pin_direction(p) == Direction::Ground)
+            .collect()
+    }
+}
+
+/// Create, modify and assign power domains.
+pub trait PowerEdit: PowerBase + NetlistEdit {
+    /// Create a power domain which is supplied by `supply_net` and `ground_net`.
+    ///
+    /// # Panics
+    /// Panics if the name is already used by another domain or if the nets don't live in the same cell.
+    fn create_power_domain(
+        &mut self,
+        name: Self::NameType,
+        supply_net: Self::NetId,
+        ground_net: Self::NetId,
+        voltage: f64,
+        always_on: bool,
+    ) -> Self::PowerDomainId;
+
+    /// Remove the power domain. All cells and cell instances assigned to this
+    /// domain lose their assignment. The supply nets are not removed.
+    fn remove_power_domain(&mut self, domain: &Self::PowerDomainId);
+
+    /// Set the nominal supply voltage of the domain. Returns the old voltage.
+    fn set_power_domain_voltage(&mut self, domain: &Self::PowerDomainId, voltage: f64) -> f64;
+
+    /// Mark the domain as always-on or switchable. Returns the old value.
+    fn set_power_domain_always_on(&mut self, domain: &Self::PowerDomainId, always_on: bool)
+        -> bool;
+
+    /// Assign the cell to a power domain or remove the assignment with `None`.
+    /// Returns the previously assigned domain.
+    fn set_power_domain_of_cell(
+        &mut self,
+        cell: &Self::CellId,
+        domain: Option<Self::PowerDomainId>,
+    ) -> Option<Self::PowerDomainId>;
+
+    /// Assign the cell instance to a power domain or remove the assignment with `None`.
+    /// Returns the previously assigned domain.
+    fn set_power_domain_of_cell_instance(
+        &mut self,
+        inst: &Self::CellInstId,
+        domain: Option<Self::PowerDomainId>,
+    ) -> Option<Self::PowerDomainId>;
+
+    /// Connect the supply pins of the cell instance to the supply net of the domain
+    /// and the ground pins to the ground net of the domain.
+    ///
+    /// # Panics
+    /// Panics if the domain does not live in the parent cell of the instance.
+    fn connect_supply_pins(&mut self, inst: &Self::CellInstId, domain: &Self::PowerDomainId) {
+        assert!(
+            self.parent_cell(inst) == self.parent_cell_of_power_domain(domain),
+            "Power domain does not live in the parent cell of the instance."
+        );
+        let template = self.template_cell(inst);
+        let supply_net = self.power_domain_supply_net(domain);
+        let ground_net = self.power_domain_ground_net(domain);
+        for pin in self.each_supply_pin_vec(&template) {
+            let pin_inst = self.pin_instance(inst, &pin);
+            self.connect_pin_instance(&pin_inst, Some(supply_net.clone()));
+        }
+        for pin in self.each_ground_pin_vec(&template) {
+            let pin_inst = self.pin_instance(inst, &pin);
+            self.connect_pin_instance(&pin_inst, Some(ground_net.clone()));
+        }
+    }
+}
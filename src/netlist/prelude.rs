@@ -6,8 +6,10 @@
 //! The `prelude` helps to import most commonly used modules.
 
 pub use super::arc_id::*;
+pub use super::bus::*;
 pub use super::direction::*;
 pub use super::io::*;
+pub use super::power::*;
 pub use super::terminal_id::*;
 pub use super::traits::*;
 pub use super::util::*;
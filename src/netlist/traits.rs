@@ -99,6 +99,124 @@ pub trait NetlistBase: HierarchyBase {
     /// Get the name of the net.
     fn net_name(&self, net: &Self::NetId) -> Option<Self::NameType>;
 
+    /// Check if the pin ID is valid, i.e. the pin exists.
+    fn pin_exists(&self, pin: &Self::PinId) -> bool {
+        // Inefficient default implementation.
+        self.each_cell()
+            .any(|c| self.each_pin(&c).any(|p| &p == pin))
+    }
+
+    /// Check if the pin instance ID is valid, i.e. the pin instance exists.
+    fn pin_instance_exists(&self, pin_instance: &Self::PinInstId) -> bool {
+        // Inefficient default implementation.
+        self.each_cell().any(|c| {
+            self.each_cell_instance(&c)
+                .any(|inst| self.each_pin_instance(&inst).any(|p| &p == pin_instance))
+        })
+    }
+
+    /// Check if the net ID is valid, i.e. the net exists.
+    fn net_exists(&self, net: &Self::NetId) -> bool {
+        // Inefficient default implementation.
+        self.each_cell()
+            .any(|c| self.each_internal_net(&c).any(|n| &n == net))
+    }
+
+    /// Get the ID of the template pin of this pin instance.
+    /// Returns `None` if the pin instance does not exist.
+    fn try_template_pin(&self, pin_instance: &Self::PinInstId) -> Option<Self::PinId> {
+        if self.pin_instance_exists(pin_instance) {
+            Some(self.template_pin(pin_instance))
+        } else {
+            None
+        }
+    }
+
+    /// Get the signal direction of the pin.
+    /// Returns `None` if the pin does not exist.
+    fn try_pin_direction(&self, pin: &Self::PinId) -> Option<Direction> {
+        if self.pin_exists(pin) {
+            Some(self.pin_direction(pin))
+        } else {
+            None
+        }
+    }
+
+    /// Get the name of the pin.
+    /// Returns `None` if the pin does not exist.
+    fn try_pin_name(&self, pin: &Self::PinId) -> Option<Self::NameType> {
+        if self.pin_exists(pin) {
+            Some(self.pin_name(pin))
+        } else {
+            None
+        }
+    }
+
+    /// Get the ID of the parent circuit of this pin.
+    /// Returns `None` if the pin does not exist.
+    fn try_parent_cell_of_pin(&self, pin: &Self::PinId) -> Option<Self::CellId> {
+        if self.pin_exists(pin) {
+            Some(self.parent_cell_of_pin(pin))
+        } else {
+            None
+        }
+    }
+
+    /// Get the ID of the circuit instance that holds this pin instance.
+    /// Returns `None` if the pin instance does not exist.
+    fn try_parent_of_pin_instance(&self, pin_inst: &Self::PinInstId) -> Option<Self::CellInstId> {
+        if self.pin_instance_exists(pin_inst) {
+            Some(self.parent_of_pin_instance(pin_inst))
+        } else {
+            None
+        }
+    }
+
+    /// Get the ID of the parent circuit of this net.
+    /// Returns `None` if the net does not exist.
+    fn try_parent_cell_of_net(&self, net: &Self::NetId) -> Option<Self::CellId> {
+        if self.net_exists(net) {
+            Some(self.parent_cell_of_net(net))
+        } else {
+            None
+        }
+    }
+
+    /// Get the internal net attached to this pin.
+    /// Returns `None` if the pin does not exist and `Some(None)` if the pin exists
+    /// but is not connected to any net.
+    fn try_net_of_pin(&self, pin: &Self::PinId) -> Option<Option<Self::NetId>> {
+        if self.pin_exists(pin) {
+            Some(self.net_of_pin(pin))
+        } else {
+            None
+        }
+    }
+
+    /// Get the external net attached to this pin instance.
+    /// Returns `None` if the pin instance does not exist and `Some(None)` if the pin instance exists
+    /// but is not connected to any net.
+    fn try_net_of_pin_instance(
+        &self,
+        pin_instance: &Self::PinInstId,
+    ) -> Option<Option<Self::NetId>> {
+        if self.pin_instance_exists(pin_instance) {
+            Some(self.net_of_pin_instance(pin_instance))
+        } else {
+            None
+        }
+    }
+
+    /// Get the name of the net.
+    /// Returns `None` if the net does not exist and `Some(None)` if the net exists but has no name.
+    fn try_net_name(&self, net: &Self::NetId) -> Option<Option<Self::NameType>> {
+        if self.net_exists(net) {
+            Some(self.net_name(net))
+        } else {
+            None
+        }
+    }
+
     /// Call a function for each pin of the circuit.
     fn for_each_pin<F>(&self, circuit: &Self::CellId, f: F)
     where
@@ -323,10 +441,6 @@ where
 ///
 /// [`NetlistEditUtil`]: crate::netlist::util::NetlistEditUtil
 pub trait NetlistEdit: NetlistBase + HierarchyEdit {
-    // /// Create a multi-bit port.
-    // /// Internally creates a pin for every bit of the port.
-    // fn create_bus(&mut self, circuit: &Self::CellId, name: Self::NameType, direction: Direction, width: usize) -> Vec<Self::PinId>;
-
     /// Create a new pin in this cell.
     /// Also adds the pin to all instances of the cell.
     fn create_pin(
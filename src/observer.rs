@@ -0,0 +1,1486 @@
+// SPDX-FileCopyrightText: 2022 Thomas Kramer
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Wrapper around netlist, layout and L2N structures that allows to observe modifications.
+//!
+//! Modifications done through the [`Observer`] wrapper are reported as typed events
+//! to registered callback functions or to an event channel.
+//!
+//! # Example
+//! ```
+//! use libreda_db::prelude::*;
+//! use libreda_db::observer::{Observer, HierarchyEvent};
+//!
+//! let mut chip = Chip::new();
+//! let mut num_created_cells = 0;
+//! {
+//!     let mut observer = Observer::new_hierarchy_observer(&mut chip);
+//!     observer.register(|event| {
+//!         if let HierarchyEvent::CellCreated(_) = event {
+//!             num_created_cells += 1;
+//!         }
+//!     });
+//!     observer.create_cell("A".to_string().into());
+//!     observer.create_cell("B".to_string().into());
+//! }
+//! assert_eq!(num_created_cells, 2);
+//! ```
+//!
+//! # Caveat
+//! Removing a cell, a cell instance, a pin or a net is reported as a single event.
+//! Objects which are removed implicitly along with it (for example the instances of a removed cell)
+//! are not reported separately.
+
+use crate::decorator::bus::{BusBaseDecorator, BusEditDecorator};
+use crate::decorator::hierarchy::{HierarchyBaseDecorator, HierarchyEditDecorator};
+use crate::decorator::l2n::{L2NBaseDecorator, L2NEditDecorator};
+use crate::decorator::layout::{LayoutBaseDecorator, LayoutEditDecorator};
+use crate::decorator::netlist::{NetlistBaseDecorator, NetlistEditDecorator};
+use crate::decorator::power::{PowerBaseDecorator, PowerEditDecorator};
+use crate::decorator::{Decorator, MutDecorator};
+use crate::netlist::bus::BitOrder;
+use crate::netlist::direction::Direction;
+use crate::prelude::{
+    Geometry, InstanceArray, PlacementStatus, PropertyValue, SimpleTransform, UInt,
+};
+use crate::traits::*;
+use std::ops::Deref;
+use std::sync::mpsc::Sender;
+
+/// Modification of the cell hierarchy.
+pub enum HierarchyEvent<T: HierarchyBase> {
+    /// A cell has been created.
+    CellCreated(T::CellId),
+    /// A cell has been removed.
+    CellRemoved(T::CellId),
+    /// A cell instance has been created.
+    CellInstanceCreated {
+        /// The new instance.
+        inst: T::CellInstId,
+        /// The cell which contains the instance.
+        parent_cell: T::CellId,
+        /// The instantiated cell.
+        template_cell: T::CellId,
+    },
+    /// A cell instance has been removed.
+    CellInstanceRemoved(T::CellInstId),
+    /// A cell has been renamed.
+    CellRenamed {
+        /// The renamed cell.
+        cell: T::CellId,
+        /// Name before the modification.
+        previous_name: T::NameType,
+        /// Name after the modification.
+        new_name: T::NameType,
+    },
+    /// A cell instance has been renamed.
+    CellInstanceRenamed {
+        /// The renamed instance.
+        inst: T::CellInstId,
+        /// Name before the modification.
+        previous_name: Option<T::NameType>,
+        /// Name after the modification.
+        new_name: Option<T::NameType>,
+    },
+    /// A property of the chip has been set.
+    ChipPropertySet {
+        /// Property key.
+        key: T::NameType,
+        /// New property value.
+        value: PropertyValue,
+    },
+    /// A property of a cell has been set.
+    CellPropertySet {
+        /// The modified cell.
+        cell: T::CellId,
+        /// Property key.
+        key: T::NameType,
+        /// New property value.
+        value: PropertyValue,
+    },
+    /// A property of a cell instance has been set.
+    CellInstancePropertySet {
+        /// The modified instance.
+        inst: T::CellInstId,
+        /// Property key.
+        key: T::NameType,
+        /// New property value.
+        value: PropertyValue,
+    },
+    /// A property of the chip has been removed.
+    ChipPropertyRemoved {
+        /// Property key.
+        key: T::NameType,
+    },
+    /// A property of a cell has been removed.
+    CellPropertyRemoved {
+        /// The modified cell.
+        cell: T::CellId,
+        /// Property key.
+        key: T::NameType,
+    },
+    /// A property of a cell instance has been removed.
+    CellInstancePropertyRemoved {
+        /// The modified instance.
+        inst: T::CellInstId,
+        /// Property key.
+        key: T::NameType,
+    },
+}
+
+impl<T: HierarchyBase> Clone for HierarchyEvent<T> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::CellCreated(cell) => Self::CellCreated(cell.clone()),
+            Self::CellRemoved(cell) => Self::CellRemoved(cell.clone()),
+            Self::CellInstanceCreated {
+                inst,
+                parent_cell,
+                template_cell,
+            } => Self::CellInstanceCreated {
+                inst: inst.clone(),
+                parent_cell: parent_cell.clone(),
+                template_cell: template_cell.clone(),
+            },
+            Self::CellInstanceRemoved(inst) => Self::CellInstanceRemoved(inst.clone()),
+            Self::CellRenamed {
+                cell,
+                previous_name,
+                new_name,
+            } => Self::CellRenamed {
+                cell: cell.clone(),
+                previous_name: previous_name.clone(),
+                new_name: new_name.clone(),
+            },
+            Self::CellInstanceRenamed {
+                inst,
+                previous_name,
+                new_name,
+            } => Self::CellInstanceRenamed {
+                inst: inst.clone(),
+                previous_name: previous_name.clone(),
+                new_name: new_name.clone(),
+            },
+            Self::ChipPropertySet { key, value } => Self::ChipPropertySet {
+                key: key.clone(),
+                value: value.clone(),
+            },
+            Self::CellPropertySet { cell, key, value } => Self::CellPropertySet {
+                cell: cell.clone(),
+                key: key.clone(),
+                value: value.clone(),
+            },
+            Self::CellInstancePropertySet { inst, key, value } => Self::CellInstancePropertySet {
+                inst: inst.clone(),
+                key: key.clone(),
+                value: value.clone(),
+            },
+            Self::ChipPropertyRemoved { key } => Self::ChipPropertyRemoved { key: key.clone() },
+            Self::CellPropertyRemoved { cell, key } => Self::CellPropertyRemoved {
+                cell: cell.clone(),
+                key: key.clone(),
+            },
+            Self::CellInstancePropertyRemoved { inst, key } => Self::CellInstancePropertyRemoved {
+                inst: inst.clone(),
+                key: key.clone(),
+            },
+        }
+    }
+}
+
+/// Modification of the netlist.
+pub enum NetlistEvent<T: NetlistBase> {
+    /// Modification of the cell hierarchy.
+    Hierarchy(HierarchyEvent<T>),
+    /// A pin has been created.
+    PinCreated(T::PinId),
+    /// A pin has been removed.
+    PinRemoved(T::PinId),
+    /// A pin has been renamed.
+    PinRenamed {
+        /// The renamed pin.
+        pin: T::PinId,
+        /// Name before the modification.
+        previous_name: T::NameType,
+        /// Name after the modification.
+        new_name: T::NameType,
+    },
+    /// A net has been created.
+    NetCreated(T::NetId),
+    /// A net has been removed.
+    NetRemoved(T::NetId),
+    /// A net has been renamed.
+    NetRenamed {
+        /// The renamed net.
+        net: T::NetId,
+        /// Name before the modification.
+        previous_name: Option<T::NameType>,
+        /// Name after the modification.
+        new_name: Option<T::NameType>,
+    },
+    /// A pin has been connected to a net or has been disconnected.
+    PinConnected {
+        /// The modified pin.
+        pin: T::PinId,
+        /// Net which was connected to the pin before the modification.
+        previous_net: Option<T::NetId>,
+        /// Net which is connected to the pin after the modification.
+        net: Option<T::NetId>,
+    },
+    /// A pin instance has been connected to a net or has been disconnected.
+    PinInstanceConnected {
+        /// The modified pin instance.
+        pin_instance: T::PinInstId,
+        /// Net which was connected to the pin instance before the modification.
+        previous_net: Option<T::NetId>,
+        /// Net which is connected to the pin instance after the modification.
+        net: Option<T::NetId>,
+    },
+}
+
+impl<T: NetlistBase> Clone for NetlistEvent<T> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Hierarchy(e) => Self::Hierarchy(e.clone()),
+            Self::PinCreated(pin) => Self::PinCreated(pin.clone()),
+            Self::PinRemoved(pin) => Self::PinRemoved(pin.clone()),
+            Self::PinRenamed {
+                pin,
+                previous_name,
+                new_name,
+            } => Self::PinRenamed {
+                pin: pin.clone(),
+                previous_name: previous_name.clone(),
+                new_name: new_name.clone(),
+            },
+            Self::NetCreated(net) => Self::NetCreated(net.clone()),
+            Self::NetRemoved(net) => Self::NetRemoved(net.clone()),
+            Self::NetRenamed {
+                net,
+                previous_name,
+                new_name,
+            } => Self::NetRenamed {
+                net: net.clone(),
+                previous_name: previous_name.clone(),
+                new_name: new_name.clone(),
+            },
+            Self::PinConnected {
+                pin,
+                previous_net,
+                net,
+            } => Self::PinConnected {
+                pin: pin.clone(),
+                previous_net: previous_net.clone(),
+                net: net.clone(),
+            },
+            Self::PinInstanceConnected {
+                pin_instance,
+                previous_net,
+                net,
+            } => Self::PinInstanceConnected {
+                pin_instance: pin_instance.clone(),
+                previous_net: previous_net.clone(),
+                net: net.clone(),
+            },
+        }
+    }
+}
+
+impl<T: NetlistBase> From<HierarchyEvent<T>> for NetlistEvent<T> {
+    fn from(e: HierarchyEvent<T>) -> Self {
+        Self::Hierarchy(e)
+    }
+}
+
+/// Modification of the netlist including buses.
+pub enum BusEvent<T: BusBase> {
+    /// Modification of the netlist.
+    Netlist(NetlistEvent<T>),
+    /// A pin bus has been created.
+    PinBusCreated(T::PinBusId),
+    /// A pin bus has been removed. The pins of the bus are not necessarily removed.
+    PinBusRemoved(T::PinBusId),
+    /// A net bus has been created.
+    NetBusCreated(T::NetBusId),
+    /// A net bus has been removed. The nets of the bus are not necessarily removed.
+    NetBusRemoved(T::NetBusId),
+}
+
+impl<T: BusBase> Clone for BusEvent<T> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Netlist(e) => Self::Netlist(e.clone()),
+            Self::PinBusCreated(bus) => Self::PinBusCreated(bus.clone()),
+            Self::PinBusRemoved(bus) => Self::PinBusRemoved(bus.clone()),
+            Self::NetBusCreated(bus) => Self::NetBusCreated(bus.clone()),
+            Self::NetBusRemoved(bus) => Self::NetBusRemoved(bus.clone()),
+        }
+    }
+}
+
+impl<T: BusBase> From<HierarchyEvent<T>> for BusEvent<T> {
+    fn from(e: HierarchyEvent<T>) -> Self {
+        Self::Netlist(e.into())
+    }
+}
+
+impl<T: BusBase> From<NetlistEvent<T>> for BusEvent<T> {
+    fn from(e: NetlistEvent<T>) -> Self {
+        Self::Netlist(e)
+    }
+}
+
+/// Modification of the netlist including power domains.
+pub enum PowerEvent<T: PowerBase> {
+    /// Modification of the netlist.
+    Netlist(NetlistEvent<T>),
+    /// A power domain has been created.
+    PowerDomainCreated(T::PowerDomainId),
+    /// A power domain has been removed. Its supply nets are not removed.
+    PowerDomainRemoved(T::PowerDomainId),
+    /// The nominal voltage of a power domain has been changed.
+    PowerDomainVoltageChanged {
+        /// The modified power domain.
+        domain: T::PowerDomainId,
+        /// Voltage before the modification.
+        previous_voltage: f64,
+        /// Voltage after the modification.
+        voltage: f64,
+    },
+    /// A power domain has been marked as always-on or switchable.
+    PowerDomainAlwaysOnChanged {
+        /// The modified power domain.
+        domain: T::PowerDomainId,
+        /// Always-on flag after the modification.
+        always_on: bool,
+    },
+    /// A cell has been assigned to a power domain or the assignment has been removed.
+    PowerDomainOfCellChanged {
+        /// The modified cell.
+        cell: T::CellId,
+        /// Domain which was assigned before the modification.
+        previous_domain: Option<T::PowerDomainId>,
+        /// Domain which is assigned after the modification.
+        domain: Option<T::PowerDomainId>,
+    },
+    /// A cell instance has been assigned to a power domain or the assignment has been removed.
+    PowerDomainOfCellInstanceChanged {
+        /// The modified cell instance.
+        inst: T::CellInstId,
+        /// Domain which was assigned before the modification.
+        previous_domain: Option<T::PowerDomainId>,
+        /// Domain which is assigned after the modification.
+        domain: Option<T::PowerDomainId>,
+    },
+}
+
+impl<T: PowerBase> Clone for PowerEvent<T> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Netlist(e) => Self::Netlist(e.clone()),
+            Self::PowerDomainCreated(domain) => Self::PowerDomainCreated(domain.clone()),
+            Self::PowerDomainRemoved(domain) => Self::PowerDomainRemoved(domain.clone()),
+            Self::PowerDomainVoltageChanged {
+                domain,
+                previous_voltage,
+                voltage,
+            } => Self::PowerDomainVoltageChanged {
+                domain: domain.clone(),
+                previous_voltage: *previous_voltage,
+                voltage: *voltage,
+            },
+            Self::PowerDomainAlwaysOnChanged { domain, always_on } => {
+                Self::PowerDomainAlwaysOnChanged {
+                    domain: domain.clone(),
+                    always_on: *always_on,
+                }
+            }
+            Self::PowerDomainOfCellChanged {
+                cell,
+                previous_domain,
+                domain,
+            } => Self::PowerDomainOfCellChanged {
+                cell: cell.clone(),
+                previous_domain: previous_domain.clone(),
+                domain: domain.clone(),
+            },
+            Self::PowerDomainOfCellInstanceChanged {
+                inst,
+                previous_domain,
+                domain,
+            } => Self::PowerDomainOfCellInstanceChanged {
+                inst: inst.clone(),
+                previous_domain: previous_domain.clone(),
+                domain: domain.clone(),
+            },
+        }
+    }
+}
+
+impl<T: PowerBase> From<HierarchyEvent<T>> for PowerEvent<T> {
+    fn from(e: HierarchyEvent<T>) -> Self {
+        Self::Netlist(e.into())
+    }
+}
+
+impl<T: PowerBase> From<NetlistEvent<T>> for PowerEvent<T> {
+    fn from(e: NetlistEvent<T>) -> Self {
+        Self::Netlist(e)
+    }
+}
+
+/// Modification of the layout.
+pub enum LayoutEvent<T: LayoutBase> {
+    /// Modification of the cell hierarchy.
+    Hierarchy(HierarchyEvent<T>),
+    /// The distance unit has been changed.
+    DbuChanged {
+        /// Distance unit before the modification.
+        previous_dbu: T::Coord,
+        /// Distance unit after the modification.
+        dbu: T::Coord,
+    },
+    /// A layer has been created.
+    LayerCreated(T::LayerId),
+    /// A layer has been renamed.
+    LayerRenamed {
+        /// The renamed layer.
+        layer: T::LayerId,
+        /// Name before the modification.
+        previous_name: Option<T::NameType>,
+        /// Name after the modification.
+        new_name: Option<T::NameType>,
+    },
+    /// A shape has been inserted.
+    ShapeInserted {
+        /// The new shape.
+        shape: T::ShapeId,
+        /// The cell which contains the shape.
+        parent_cell: T::CellId,
+        /// The layer of the shape.
+        layer: T::LayerId,
+    },
+    /// A shape has been removed.
+    ShapeRemoved {
+        /// The removed shape.
+        shape: T::ShapeId,
+        /// The cell which contained the shape.
+        parent_cell: T::CellId,
+        /// The layer of the shape.
+        layer: T::LayerId,
+        /// Geometry of the removed shape.
+        geometry: Geometry<T::Coord>,
+    },
+    /// The geometry of a shape has been replaced.
+    ShapeReplaced {
+        /// The modified shape.
+        shape: T::ShapeId,
+        /// Geometry before the modification.
+        previous_geometry: Geometry<T::Coord>,
+        /// Geometry after the modification.
+        geometry: Geometry<T::Coord>,
+    },
+    /// The location of a cell instance has been changed.
+    TransformChanged {
+        /// The modified instance.
+        inst: T::CellInstId,
+        /// Transform before the modification.
+        previous_transform: SimpleTransform<T::Coord>,
+        /// Transform after the modification.
+        transform: SimpleTransform<T::Coord>,
+    },
+    /// A cell instance has been turned into an array or back into a single placement.
+    CellInstanceArrayChanged {
+        /// The modified instance.
+        inst: T::CellInstId,
+        /// Array parameters before the modification.
+        previous_array: Option<InstanceArray<T::Coord>>,
+        /// Array parameters after the modification.
+        array: Option<InstanceArray<T::Coord>>,
+    },
+    /// The placement status of a cell instance has been changed.
+    PlacementStatusChanged {
+        /// The modified instance.
+        inst: T::CellInstId,
+        /// Status before the modification.
+        previous_status: PlacementStatus,
+        /// Status after the modification.
+        status: PlacementStatus,
+    },
+    /// A property of a shape has been set.
+    ShapePropertySet {
+        /// The modified shape.
+        shape: T::ShapeId,
+        /// Property key.
+        key: T::NameType,
+        /// New property value.
+        value: PropertyValue,
+    },
+    /// A property of a shape has been removed.
+    ShapePropertyRemoved {
+        /// The modified shape.
+        shape: T::ShapeId,
+        /// Property key.
+        key: T::NameType,
+    },
+}
+
+impl<T: LayoutBase> Clone for LayoutEvent<T> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Hierarchy(e) => Self::Hierarchy(e.clone()),
+            Self::DbuChanged { previous_dbu, dbu } => Self::DbuChanged {
+                previous_dbu: *previous_dbu,
+                dbu: *dbu,
+            },
+            Self::LayerCreated(layer) => Self::LayerCreated(layer.clone()),
+            Self::LayerRenamed {
+                layer,
+                previous_name,
+                new_name,
+            } => Self::LayerRenamed {
+                layer: layer.clone(),
+                previous_name: previous_name.clone(),
+                new_name: new_name.clone(),
+            },
+            Self::ShapeInserted {
+                shape,
+                parent_cell,
+                layer,
+            } => Self::ShapeInserted {
+                shape: shape.clone(),
+                parent_cell: parent_cell.clone(),
+                layer: layer.clone(),
+            },
+            Self::ShapeRemoved {
+                shape,
+                parent_cell,
+                layer,
+                geometry,
+            } => Self::ShapeRemoved {
+                shape: shape.clone(),
+                parent_cell: parent_cell.clone(),
+                layer: layer.clone(),
+                geometry: geometry.clone(),
+            },
+            Self::ShapeReplaced {
+                shape,
+                previous_geometry,
+                geometry,
+            } => Self::ShapeReplaced {
+                shape: shape.clone(),
+                previous_geometry: previous_geometry.clone(),
+                geometry: geometry.clone(),
+            },
+            Self::TransformChanged {
+                inst,
+                previous_transform,
+                transform,
+            } => Self::TransformChanged {
+                inst: inst.clone(),
+                previous_transform: previous_transform.clone(),
+                transform: transform.clone(),
+            },
+            Self::CellInstanceArrayChanged {
+                inst,
+                previous_array,
+                array,
+            } => Self::CellInstanceArrayChanged {
+                inst: inst.clone(),
+                previous_array: *previous_array,
+                array: *array,
+            },
+            Self::PlacementStatusChanged {
+                inst,
+                previous_status,
+                status,
+            } => Self::PlacementStatusChanged {
+                inst: inst.clone(),
+                previous_status: *previous_status,
+                status: *status,
+            },
+            Self::ShapePropertySet { shape, key, value } => Self::ShapePropertySet {
+                shape: shape.clone(),
+                key: key.clone(),
+                value: value.clone(),
+            },
+            Self::ShapePropertyRemoved { shape, key } => Self::ShapePropertyRemoved {
+                shape: shape.clone(),
+                key: key.clone(),
+            },
+        }
+    }
+}
+
+impl<T: LayoutBase> From<HierarchyEvent<T>> for LayoutEvent<T> {
+    fn from(e: HierarchyEvent<T>) -> Self {
+        Self::Hierarchy(e)
+    }
+}
+
+/// Modification of a fused layout and netlist.
+pub enum L2NEvent<T: L2NBase> {
+    /// Modification of the cell hierarchy.
+    Hierarchy(HierarchyEvent<T>),
+    /// Modification of the netlist.
+    Netlist(NetlistEvent<T>),
+    /// Modification of the layout.
+    Layout(LayoutEvent<T>),
+    /// The net of a shape has been changed.
+    NetOfShapeChanged {
+        /// The modified shape.
+        shape: T::ShapeId,
+        /// Net of the shape before the modification.
+        previous_net: Option<T::NetId>,
+        /// Net of the shape after the modification.
+        net: Option<T::NetId>,
+    },
+    /// The pin of a shape has been changed.
+    PinOfShapeChanged {
+        /// The modified shape.
+        shape: T::ShapeId,
+        /// Pin of the shape before the modification.
+        previous_pin: Option<T::PinId>,
+        /// Pin of the shape after the modification.
+        pin: Option<T::PinId>,
+    },
+}
+
+impl<T: L2NBase> Clone for L2NEvent<T> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Hierarchy(e) => Self::Hierarchy(e.clone()),
+            Self::Netlist(e) => Self::Netlist(e.clone()),
+            Self::Layout(e) => Self::Layout(e.clone()),
+            Self::NetOfShapeChanged {
+                shape,
+                previous_net,
+                net,
+            } => Self::NetOfShapeChanged {
+                shape: shape.clone(),
+                previous_net: previous_net.clone(),
+                net: net.clone(),
+            },
+            Self::PinOfShapeChanged {
+                shape,
+                previous_pin,
+                pin,
+            } => Self::PinOfShapeChanged {
+                shape: shape.clone(),
+                previous_pin: previous_pin.clone(),
+                pin: pin.clone(),
+            },
+        }
+    }
+}
+
+impl<T: L2NBase> From<HierarchyEvent<T>> for L2NEvent<T> {
+    fn from(e: HierarchyEvent<T>) -> Self {
+        Self::Hierarchy(e)
+    }
+}
+
+impl<T: L2NBase> From<NetlistEvent<T>> for L2NEvent<T> {
+    fn from(e: NetlistEvent<T>) -> Self {
+        Self::Netlist(e)
+    }
+}
+
+impl<T: L2NBase> From<LayoutEvent<T>> for L2NEvent<T> {
+    fn from(e: LayoutEvent<T>) -> Self {
+        Self::Layout(e)
+    }
+}
+
+/// Handle of a registered callback.
+/// Used to unregister the callback.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CallbackId(usize);
+
+/// Wrapper around netlist, layout and L2N structures that reports modifications
+/// to registered callbacks.
+///
+/// # Types
+/// * `T`: Underlying data structure.
+/// * `E`: Event type.
+pub struct Observer<'a, T, E> {
+    /// Underlying data structure.
+    chip: &'a mut T,
+    /// Registered callbacks. They are called in the order of registration.
+    callbacks: Vec<(CallbackId, Box<dyn FnMut(&E) + 'a>)>,
+    /// Counter for generating callback IDs.
+    callback_id_counter: usize,
+}
+
+impl<'a, T, E> Observer<'a, T, E> {
+    fn new(chip: &'a mut T) -> Self {
+        Self {
+            chip,
+            callbacks: vec![],
+            callback_id_counter: 0,
+        }
+    }
+
+    /// Register a function which is called after each modification.
+    /// Returns an ID which can be used to unregister the callback.
+    pub fn register<F>(&mut self, callback: F) -> CallbackId
+    where
+        F: FnMut(&E) + 'a,
+    {
+        let id = CallbackId(self.callback_id_counter);
+        self.callback_id_counter += 1;
+        self.callbacks.push((id, Box::new(callback)));
+        id
+    }
+
+    /// Send a copy of each event to the channel.
+    /// Events are silently dropped once the receiver is disconnected.
+    /// Returns an ID which can be used to unregister the channel.
+    pub fn register_channel(&mut self, sender: Sender<E>) -> CallbackId
+    where
+        E: Clone + 'a,
+    {
+        self.register(move |event: &E| {
+            let _ = sender.send(event.clone());
+        })
+    }
+
+    /// Remove a callback.
+    /// Returns `false` if there's no callback with this ID.
+    pub fn unregister(&mut self, id: CallbackId) -> bool {
+        let num_callbacks = self.callbacks.len();
+        self.callbacks.retain(|(i, _)| i != &id);
+        self.callbacks.len() != num_callbacks
+    }
+
+    /// Get the number of registered callbacks.
+    pub fn num_callbacks(&self) -> usize {
+        self.callbacks.len()
+    }
+
+    /// Pass an event to all registered callbacks.
+    fn emit<V: Into<E>>(&mut self, event: V) {
+        if !self.callbacks.is_empty() {
+            let event = event.into();
+            for (_, callback) in &mut self.callbacks {
+                callback(&event);
+            }
+        }
+    }
+}
+
+impl<'a, T: HierarchyEdit> Observer<'a, T, HierarchyEvent<T>> {
+    /// Create a wrapper which reports operations performed
+    /// on the `HierarchyEdit` trait.
+    pub fn new_hierarchy_observer(chip: &'a mut T) -> Self {
+        Self::new(chip)
+    }
+}
+
+impl<'a, T: NetlistEdit> Observer<'a, T, NetlistEvent<T>> {
+    /// Create a wrapper which reports operations performed
+    /// on the `NetlistEdit` trait.
+    pub fn new_netlist_observer(chip: &'a mut T) -> Self {
+        Self::new(chip)
+    }
+}
+
+impl<'a, T: BusEdit> Observer<'a, T, BusEvent<T>> {
+    /// Create a wrapper which reports operations performed
+    /// on the `NetlistEdit` and `BusEdit` traits.
+    pub fn new_bus_observer(chip: &'a mut T) -> Self {
+        Self::new(chip)
+    }
+}
+
+impl<'a, T: PowerEdit> Observer<'a, T, PowerEvent<T>> {
+    /// Create a wrapper which reports operations performed
+    /// on the `NetlistEdit` and `PowerEdit` traits.
+    pub fn new_power_observer(chip: &'a mut T) -> Self {
+        Self::new(chip)
+    }
+}
+
+impl<'a, T: LayoutEdit> Observer<'a, T, LayoutEvent<T>> {
+    /// Create a wrapper which reports operations performed
+    /// on the `LayoutEdit` trait.
+    pub fn new_layout_observer(chip: &'a mut T) -> Self {
+        Self::new(chip)
+    }
+}
+
+impl<'a, T: L2NEdit> Observer<'a, T, L2NEvent<T>> {
+    /// Create a wrapper around a fused layout and netlist which
+    /// reports modifications.
+    pub fn new_l2n_observer(chip: &'a mut T) -> Self {
+        Self::new(chip)
+    }
+}
+
+impl<'a, T, E> Deref for Observer<'a, T, E> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.chip
+    }
+}
+
+impl<'a, H, E> Decorator for Observer<'a, H, E> {
+    type D = H;
+
+    fn base(&self) -> &Self::D {
+        &self.chip
+    }
+}
+
+impl<'a, H, E> MutDecorator for Observer<'a, H, E> {
+    fn mut_base(&mut self) -> &mut Self::D {
+        &mut self.chip
+    }
+}
+
+// Inherit everything from HierarchyBase.
+impl<'a, H: HierarchyBase + 'static, E> HierarchyBaseDecorator for Observer<'a, H, E> {
+    type NameType = H::NameType;
+    type CellId = H::CellId;
+    type CellInstId = H::CellInstId;
+}
+
+// Inherit everything from NetlistBase.
+impl<'a, N: NetlistBase + 'static, E> NetlistBaseDecorator for Observer<'a, N, E> {}
+
+// Inherit everything from BusBase.
+impl<'a, N: BusBase + 'static, E> BusBaseDecorator for Observer<'a, N, E> {}
+
+// Inherit everything from PowerBase.
+impl<'a, N: PowerBase + 'static, E> PowerBaseDecorator for Observer<'a, N, E> {}
+
+// Inherit everything from LayoutBase.
+impl<'a, L: LayoutBase + 'static, E> LayoutBaseDecorator for Observer<'a, L, E> {}
+
+// Inherit everything from L2NBase.
+impl<'a, LN: L2NBase + 'static, E> L2NBaseDecorator for Observer<'a, LN, E> {}
+
+impl<'a, H, E> HierarchyEditDecorator for Observer<'a, H, E>
+where
+    H: HierarchyEdit + 'static,
+    E: From<HierarchyEvent<H>>,
+{
+    fn d_new() -> Self {
+        unimplemented!()
+    }
+
+    fn d_create_cell(&mut self, name: H::NameType) -> H::CellId {
+        let cell = self.chip.create_cell(name);
+        self.emit(HierarchyEvent::<H>::CellCreated(cell.clone()));
+        cell
+    }
+
+    fn d_remove_cell(&mut self, cell_id: &H::CellId) {
+        self.chip.remove_cell(cell_id);
+        self.emit(HierarchyEvent::<H>::CellRemoved(cell_id.clone()));
+    }
+
+    fn d_create_cell_instance(
+        &mut self,
+        parent_cell: &H::CellId,
+        template_cell: &H::CellId,
+        name: Option<H::NameType>,
+    ) -> H::CellInstId {
+        let inst = self
+            .chip
+            .create_cell_instance(parent_cell, template_cell, name);
+        self.emit(HierarchyEvent::<H>::CellInstanceCreated {
+            inst: inst.clone(),
+            parent_cell: parent_cell.clone(),
+            template_cell: template_cell.clone(),
+        });
+        inst
+    }
+
+    fn d_remove_cell_instance(&mut self, inst: &H::CellInstId) {
+        self.chip.remove_cell_instance(inst);
+        self.emit(HierarchyEvent::<H>::CellInstanceRemoved(inst.clone()));
+    }
+
+    fn d_rename_cell_instance(&mut self, inst: &H::CellInstId, new_name: Option<H::NameType>) {
+        let previous_name = self.chip.cell_instance_name(inst);
+        self.chip.rename_cell_instance(inst, new_name.clone());
+        self.emit(HierarchyEvent::<H>::CellInstanceRenamed {
+            inst: inst.clone(),
+            previous_name,
+            new_name,
+        });
+    }
+
+    fn d_rename_cell(&mut self, cell: &H::CellId, new_name: H::NameType) {
+        let previous_name = self.chip.cell_name(cell);
+        self.chip.rename_cell(cell, new_name.clone());
+        self.emit(HierarchyEvent::<H>::CellRenamed {
+            cell: cell.clone(),
+            previous_name,
+            new_name,
+        });
+    }
+
+    fn d_set_chip_property(&mut self, key: H::NameType, value: PropertyValue) {
+        self.chip.set_chip_property(key.clone(), value.clone());
+        self.emit(HierarchyEvent::<H>::ChipPropertySet { key, value });
+    }
+
+    fn d_set_cell_property(&mut self, cell: &H::CellId, key: H::NameType, value: PropertyValue) {
+        self.chip
+            .set_cell_property(cell, key.clone(), value.clone());
+        self.emit(HierarchyEvent::<H>::CellPropertySet {
+            cell: cell.clone(),
+            key,
+            value,
+        });
+    }
+
+    fn d_set_cell_instance_property(
+        &mut self,
+        inst: &H::CellInstId,
+        key: H::NameType,
+        value: PropertyValue,
+    ) {
+        self.chip
+            .set_cell_instance_property(inst, key.clone(), value.clone());
+        self.emit(HierarchyEvent::<H>::CellInstancePropertySet {
+            inst: inst.clone(),
+            key,
+            value,
+        });
+    }
+
+    fn d_remove_chip_property(&mut self, key: &H::NameType) {
+        self.chip.remove_chip_property(key);
+        self.emit(HierarchyEvent::<H>::ChipPropertyRemoved { key: key.clone() });
+    }
+
+    fn d_remove_cell_property(&mut self, cell: &H::CellId, key: &H::NameType) {
+        self.chip.remove_cell_property(cell, key);
+        self.emit(HierarchyEvent::<H>::CellPropertyRemoved {
+            cell: cell.clone(),
+            key: key.clone(),
+        });
+    }
+
+    fn d_remove_cell_instance_property(&mut self, inst: &H::CellInstId, key: &H::NameType) {
+        self.chip.remove_cell_instance_property(inst, key);
+        self.emit(HierarchyEvent::<H>::CellInstancePropertyRemoved {
+            inst: inst.clone(),
+            key: key.clone(),
+        });
+    }
+}
+
+impl<'a, N, E> NetlistEditDecorator for Observer<'a, N, E>
+where
+    N: NetlistEdit + 'static,
+    E: From<NetlistEvent<N>>,
+{
+    fn d_create_pin(
+        &mut self,
+        cell: &N::CellId,
+        name: N::NameType,
+        direction: Direction,
+    ) -> N::PinId {
+        let pin = self.chip.create_pin(cell, name, direction);
+        self.emit(NetlistEvent::<N>::PinCreated(pin.clone()));
+        pin
+    }
+
+    fn d_remove_pin(&mut self, id: &N::PinId) {
+        self.chip.remove_pin(id);
+        self.emit(NetlistEvent::<N>::PinRemoved(id.clone()));
+    }
+
+    fn d_rename_pin(&mut self, pin: &N::PinId, new_name: N::NameType) -> N::NameType {
+        let previous_name = self.chip.rename_pin(pin, new_name.clone());
+        self.emit(NetlistEvent::<N>::PinRenamed {
+            pin: pin.clone(),
+            previous_name: previous_name.clone(),
+            new_name,
+        });
+        previous_name
+    }
+
+    fn d_create_net(&mut self, parent: &N::CellId, name: Option<N::NameType>) -> N::NetId {
+        let net = self.chip.create_net(parent, name);
+        self.emit(NetlistEvent::<N>::NetCreated(net.clone()));
+        net
+    }
+
+    fn d_rename_net(
+        &mut self,
+        net_id: &N::NetId,
+        new_name: Option<N::NameType>,
+    ) -> Option<N::NameType> {
+        let previous_name = self.chip.rename_net(net_id, new_name.clone());
+        self.emit(NetlistEvent::<N>::NetRenamed {
+            net: net_id.clone(),
+            previous_name: previous_name.clone(),
+            new_name,
+        });
+        previous_name
+    }
+
+    fn d_remove_net(&mut self, net: &N::NetId) {
+        self.chip.remove_net(net);
+        self.emit(NetlistEvent::<N>::NetRemoved(net.clone()));
+    }
+
+    fn d_connect_pin(&mut self, pin: &N::PinId, net: Option<N::NetId>) -> Option<N::NetId> {
+        let previous_net = self.chip.connect_pin(pin, net.clone());
+        self.emit(NetlistEvent::<N>::PinConnected {
+            pin: pin.clone(),
+            previous_net: previous_net.clone(),
+            net,
+        });
+        previous_net
+    }
+
+    fn d_connect_pin_instance(
+        &mut self,
+        pin: &N::PinInstId,
+        net: Option<N::NetId>,
+    ) -> Option<N::NetId> {
+        let previous_net = self.chip.connect_pin_instance(pin, net.clone());
+        self.emit(NetlistEvent::<N>::PinInstanceConnected {
+            pin_instance: pin.clone(),
+            previous_net: previous_net.clone(),
+            net,
+        });
+        previous_net
+    }
+}
+
+impl<'a, N, E> BusEditDecorator for Observer<'a, N, E>
+where
+    N: BusEdit + 'static,
+    E: From<BusEvent<N>>,
+{
+    fn d_create_pin_bus_from_pins(
+        &mut self,
+        name: N::NameType,
+        pins: Vec<N::PinId>,
+        bit_order: BitOrder,
+    ) -> N::PinBusId {
+        let bus = self.chip.create_pin_bus_from_pins(name, pins, bit_order);
+        self.emit(BusEvent::<N>::PinBusCreated(bus.clone()));
+        bus
+    }
+
+    fn d_remove_pin_bus(&mut self, bus: &N::PinBusId) {
+        self.chip.remove_pin_bus(bus);
+        self.emit(BusEvent::<N>::PinBusRemoved(bus.clone()));
+    }
+
+    fn d_create_net_bus_from_nets(
+        &mut self,
+        name: N::NameType,
+        nets: Vec<N::NetId>,
+        bit_order: BitOrder,
+    ) -> N::NetBusId {
+        let bus = self.chip.create_net_bus_from_nets(name, nets, bit_order);
+        self.emit(BusEvent::<N>::NetBusCreated(bus.clone()));
+        bus
+    }
+
+    fn d_remove_net_bus(&mut self, bus: &N::NetBusId) {
+        self.chip.remove_net_bus(bus);
+        self.emit(BusEvent::<N>::NetBusRemoved(bus.clone()));
+    }
+}
+
+impl<'a, N, E> PowerEditDecorator for Observer<'a, N, E>
+where
+    N: PowerEdit + 'static,
+    E: From<PowerEvent<N>>,
+{
+    fn d_create_power_domain(
+        &mut self,
+        name: N::NameType,
+        supply_net: N::NetId,
+        ground_net: N::NetId,
+        voltage: f64,
+        always_on: bool,
+    ) -> N::PowerDomainId {
+        let domain = self
+            .chip
+            .create_power_domain(name, supply_net, ground_net, voltage, always_on);
+        self.emit(PowerEvent::<N>::PowerDomainCreated(domain.clone()));
+        domain
+    }
+
+    fn d_remove_power_domain(&mut self, domain: &N::PowerDomainId) {
+        self.chip.remove_power_domain(domain);
+        self.emit(PowerEvent::<N>::PowerDomainRemoved(domain.clone()));
+    }
+
+    fn d_set_power_domain_voltage(&mut self, domain: &N::PowerDomainId, voltage: f64) -> f64 {
+        let previous_voltage = self.chip.set_power_domain_voltage(domain, voltage);
+        self.emit(PowerEvent::<N>::PowerDomainVoltageChanged {
+            domain: domain.clone(),
+            previous_voltage,
+            voltage,
+        });
+        previous_voltage
+    }
+
+    fn d_set_power_domain_always_on(&mut self, domain: &N::PowerDomainId, always_on: bool) -> bool {
+        let previous = self.chip.set_power_domain_always_on(domain, always_on);
+        self.emit(PowerEvent::<N>::PowerDomainAlwaysOnChanged {
+            domain: domain.clone(),
+            always_on,
+        });
+        previous
+    }
+
+    fn d_set_power_domain_of_cell(
+        &mut self,
+        cell: &N::CellId,
+        domain: Option<N::PowerDomainId>,
+    ) -> Option<N::PowerDomainId> {
+        let previous_domain = self.chip.set_power_domain_of_cell(cell, domain.clone());
+        self.emit(PowerEvent::<N>::PowerDomainOfCellChanged {
+            cell: cell.clone(),
+            previous_domain: previous_domain.clone(),
+            domain,
+        });
+        previous_domain
+    }
+
+    fn d_set_power_domain_of_cell_instance(
+        &mut self,
+        inst: &N::CellInstId,
+        domain: Option<N::PowerDomainId>,
+    ) -> Option<N::PowerDomainId> {
+        let previous_domain = self
+            .chip
+            .set_power_domain_of_cell_instance(inst, domain.clone());
+        self.emit(PowerEvent::<N>::PowerDomainOfCellInstanceChanged {
+            inst: inst.clone(),
+            previous_domain: previous_domain.clone(),
+            domain,
+        });
+        previous_domain
+    }
+}
+
+impl<'a, L, E> LayoutEditDecorator for Observer<'a, L, E>
+where
+    L: LayoutEdit + 'static,
+    E: From<LayoutEvent<L>>,
+{
+    fn d_set_dbu(&mut self, dbu: L::Coord) {
+        let previous_dbu = self.chip.dbu();
+        self.chip.set_dbu(dbu);
+        self.emit(LayoutEvent::<L>::DbuChanged { previous_dbu, dbu });
+    }
+
+    fn d_create_layer(&mut self, index: UInt, datatype: UInt) -> L::LayerId {
+        let layer = self.chip.create_layer(index, datatype);
+        self.emit(LayoutEvent::<L>::LayerCreated(layer.clone()));
+        layer
+    }
+
+    fn d_create_layer_with_id(
+        &mut self,
+        layer_id: L::LayerId,
+        index: UInt,
+        datatype: UInt,
+    ) -> Result<(), ()> {
+        let result = self
+            .chip
+            .create_layer_with_id(layer_id.clone(), index, datatype);
+        if result.is_ok() {
+            self.emit(LayoutEvent::<L>::LayerCreated(layer_id));
+        }
+        result
+    }
+
+    fn d_set_layer_name(
+        &mut self,
+        layer: &L::LayerId,
+        name: Option<L::NameType>,
+    ) -> Option<L::NameType> {
+        let previous_name = self.chip.set_layer_name(layer, name.clone());
+        self.emit(LayoutEvent::<L>::LayerRenamed {
+            layer: layer.clone(),
+            previous_name: previous_name.clone(),
+            new_name: name,
+        });
+        previous_name
+    }
+
+    fn d_insert_shape(
+        &mut self,
+        parent_cell: &L::CellId,
+        layer: &L::LayerId,
+        geometry: Geometry<L::Coord>,
+    ) -> L::ShapeId {
+        let shape = self.chip.insert_shape(parent_cell, layer, geometry);
+        self.emit(LayoutEvent::<L>::ShapeInserted {
+            shape: shape.clone(),
+            parent_cell: parent_cell.clone(),
+            layer: layer.clone(),
+        });
+        shape
+    }
+
+    fn d_remove_shape(&mut self, shape_id: &L::ShapeId) -> Option<Geometry<L::Coord>> {
+        // The parent must be looked up before the shape is gone.
+        let parent = self.chip.try_parent_of_shape(shape_id);
+        let geometry = self.chip.remove_shape(shape_id);
+        if let (Some((parent_cell, layer)), Some(geometry)) = (parent, &geometry) {
+            self.emit(LayoutEvent::<L>::ShapeRemoved {
+                shape: shape_id.clone(),
+                parent_cell,
+                layer,
+                geometry: geometry.clone(),
+            });
+        }
+        geometry
+    }
+
+    fn d_replace_shape(
+        &mut self,
+        shape_id: &L::ShapeId,
+        geometry: Geometry<L::Coord>,
+    ) -> Geometry<L::Coord> {
+        let previous_geometry = self.chip.replace_shape(shape_id, geometry.clone());
+        self.emit(LayoutEvent::<L>::ShapeReplaced {
+            shape: shape_id.clone(),
+            previous_geometry: previous_geometry.clone(),
+            geometry,
+        });
+        previous_geometry
+    }
+
+    fn d_set_transform(&mut self, cell_inst: &L::CellInstId, tf: SimpleTransform<L::Coord>) {
+        let previous_transform = self.chip.get_transform(cell_inst);
+        self.chip.set_transform(cell_inst, tf.clone());
+        self.emit(LayoutEvent::<L>::TransformChanged {
+            inst: cell_inst.clone(),
+            previous_transform,
+            transform: tf,
+        });
+    }
+
+    fn d_set_cell_instance_array(
+        &mut self,
+        cell_inst: &L::CellInstId,
+        array: Option<InstanceArray<L::Coord>>,
+    ) -> Option<InstanceArray<L::Coord>> {
+        let previous_array = self.chip.set_cell_instance_array(cell_inst, array);
+        self.emit(LayoutEvent::<L>::CellInstanceArrayChanged {
+            inst: cell_inst.clone(),
+            previous_array,
+            array,
+        });
+        previous_array
+    }
+
+    fn d_set_placement_status(
+        &mut self,
+        cell_inst: &L::CellInstId,
+        status: PlacementStatus,
+    ) -> PlacementStatus {
+        let previous_status = self.chip.set_placement_status(cell_inst, status);
+        self.emit(LayoutEvent::<L>::PlacementStatusChanged {
+            inst: cell_inst.clone(),
+            previous_status,
+            status,
+        });
+        previous_status
+    }
+
+    fn d_set_shape_property(&mut self, shape: &L::ShapeId, key: L::NameType, value: PropertyValue) {
+        self.chip
+            .set_shape_property(shape, key.clone(), value.clone());
+        self.emit(LayoutEvent::<L>::ShapePropertySet {
+            shape: shape.clone(),
+            key,
+            value,
+        });
+    }
+
+    fn d_remove_shape_property(&mut self, shape: &L::ShapeId, key: &L::NameType) {
+        self.chip.remove_shape_property(shape, key);
+        self.emit(LayoutEvent::<L>::ShapePropertyRemoved {
+            shape: shape.clone(),
+            key: key.clone(),
+        });
+    }
+}
+
+impl<'a, LN, E> L2NEditDecorator for Observer<'a, LN, E>
+where
+    LN: L2NEdit + 'static,
+    E: From<L2NEvent<LN>>,
+{
+    fn d_set_pin_of_shape(
+        &mut self,
+        shape_id: &LN::ShapeId,
+        pin: Option<LN::PinId>,
+    ) -> Option<LN::PinId> {
+        let previous_pin = self.chip.set_pin_of_shape(shape_id, pin.clone());
+        self.emit(L2NEvent::<LN>::PinOfShapeChanged {
+            shape: shape_id.clone(),
+            previous_pin: previous_pin.clone(),
+            pin,
+        });
+        previous_pin
+    }
+
+    fn d_set_net_of_shape(
+        &mut self,
+        shape_id: &LN::ShapeId,
+        net: Option<LN::NetId>,
+    ) -> Option<LN::NetId> {
+        let previous_net = self.chip.set_net_of_shape(shape_id, net.clone());
+        self.emit(L2NEvent::<LN>::NetOfShapeChanged {
+            shape: shape_id.clone(),
+            previous_net: previous_net.clone(),
+            net,
+        });
+        previous_net
+    }
+}
+
+#[test]
+fn test_l2n_observer_events() {
+    use crate::prelude::*;
+    use std::sync::mpsc::channel;
+
+    let mut chip = Chip::new();
+    let (sender, receiver) = channel();
+    let mut num_events = 0;
+
+    {
+        let mut observer = Observer::new_l2n_observer(&mut chip);
+        observer.register(|_| num_events += 1);
+        observer.register_channel(sender);
+
+        let layer = observer.create_layer(1, 0);
+        let top = observer.create_cell("TOP".to_string().into());
+        let sub = observer.create_cell("SUB".to_string().into());
+        let pin = observer.create_pin(&sub, "A".to_string().into(), Direction::Input);
+        let inst = observer.create_cell_instance(&top, &sub, None);
+        let net = observer.create_net(&top, None);
+        let pin_inst = observer.pin_instance(&inst, &pin);
+        observer.connect_pin_instance(&pin_inst, Some(net));
+        observer.set_transform(&inst, SimpleTransform::translate((1, 2)));
+        let shape = observer.insert_shape(&top, &layer, Rect::new((0, 0), (1, 1)).into());
+        observer.set_net_of_shape(&shape, Some(net));
+        observer.remove_shape(&shape);
+    }
+
+    let events: Vec<_> = receiver.try_iter().collect();
+    assert_eq!(events.len(), 11);
+    assert_eq!(num_events, 11);
+
+    assert!(matches!(
+        events[0],
+        L2NEvent::Layout(LayoutEvent::LayerCreated(_))
+    ));
+    assert!(matches!(
+        events[1],
+        L2NEvent::Hierarchy(HierarchyEvent::CellCreated(_))
+    ));
+    assert!(matches!(
+        events[3],
+        L2NEvent::Netlist(NetlistEvent::PinCreated(_))
+    ));
+    assert!(matches!(
+        events[4],
+        L2NEvent::Hierarchy(HierarchyEvent::CellInstanceCreated { .. })
+    ));
+    assert!(matches!(
+        events[6],
+        L2NEvent::Netlist(NetlistEvent::PinInstanceConnected {
+            previous_net: None,
+            net: Some(_),
+            ..
+        })
+    ));
+    assert!(matches!(
+        events[7],
+        L2NEvent::Layout(LayoutEvent::TransformChanged { .. })
+    ));
+    assert!(matches!(
+        events[8],
+        L2NEvent::Layout(LayoutEvent::ShapeInserted { .. })
+    ));
+    assert!(matches!(
+        events[9],
+        L2NEvent::NetOfShapeChanged { net: Some(_), .. }
+    ));
+    assert!(matches!(
+        events[10],
+        L2NEvent::Layout(LayoutEvent::ShapeRemoved { .. })
+    ));
+}
+
+#[test]
+fn test_power_observer_events() {
+    use crate::prelude::*;
+    use std::sync::mpsc::channel;
+
+    let mut chip = Chip::new();
+    let (sender, receiver) = channel();
+    {
+        let mut observer = Observer::new_power_observer(&mut chip);
+        observer.register_channel(sender);
+
+        let top = observer.create_cell("TOP".to_string().into());
+        let vdd = observer.create_net(&top, None);
+        let vss = observer.create_net(&top, None);
+        let core = observer.create_power_domain("CORE".to_string().into(), vdd, vss, 0.9, false);
+        observer.set_power_domain_of_cell(&top, Some(core));
+        observer.set_power_domain_voltage(&core, 1.2);
+        observer.remove_power_domain(&core);
+    }
+
+    let events: Vec<_> = receiver.try_iter().collect();
+    assert_eq!(events.len(), 7);
+    assert!(matches!(events[3], PowerEvent::PowerDomainCreated(_)));
+    assert!(matches!(
+        events[4],
+        PowerEvent::PowerDomainOfCellChanged {
+            previous_domain: None,
+            domain: Some(_),
+            ..
+        }
+    ));
+    assert!(matches!(
+        events[5],
+        PowerEvent::PowerDomainVoltageChanged {
+            previous_voltage, ..
+        } if previous_voltage == 0.9
+    ));
+    assert!(matches!(events[6], PowerEvent::PowerDomainRemoved(_)));
+    assert!(chip.power_domain_by_name("CORE").is_none());
+}
+
+#[test]
+fn test_unregister_callback() {
+    use crate::prelude::*;
+
+    let mut chip = Chip::new();
+    let mut num_events = 0;
+    {
+        let mut observer = Observer::new_hierarchy_observer(&mut chip);
+        let id = observer.register(|_| num_events += 1);
+        observer.create_cell("A".to_string().into());
+        assert!(observer.unregister(id));
+        assert!(!observer.unregister(id));
+        assert_eq!(observer.num_callbacks(), 0);
+        observer.create_cell("B".to_string().into());
+    }
+    assert_eq!(num_events, 1);
+    assert_eq!(chip.num_cells(), 2);
+}
@@ -0,0 +1,182 @@
+// SPDX-FileCopyrightText: 2022 Thomas Kramer
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Wrapper around layouts which protects locked cell instances from being moved.
+//!
+//! [`PlacementLock`] rejects calls of `set_transform()` which would move an instance
+//! whose [`PlacementStatus`] is locked (`Firm`, `Fixed` or `Cover`). Rejected moves are
+//! ignored, logged as a warning and counted. All other operations are passed through.
+//!
+//! `set_transform()` does not tell the caller whether the move was rejected. Callers which need
+//! to know must use [`LayoutEdit::try_set_transform()`] instead, which returns the placement status
+//! of a locked instance as an error.
+//!
+//! # Example
+//! ```
+//! use libreda_db::prelude::*;
+//! use libreda_db::placement_lock::PlacementLock;
+//!
+//! let mut chip = Chip::new();
+//! let top = chip.create_cell("TOP".into());
+//! let sram = chip.create_cell("SRAM".into());
+//! let inst = chip.create_cell_instance(&top, &sram, None);
+//! chip.set_placement_status(&inst, PlacementStatus::Fixed);
+//!
+//! let mut lock = PlacementLock::new(&mut chip);
+//! lock.set_transform(&inst, SimpleTransform::translate((100, 0)));
+//! assert_eq!(lock.num_rejected_moves(), 1);
+//! assert_eq!(lock.get_transform(&inst), SimpleTransform::identity());
+//!
+//! // Moves can be allowed again temporarily.
+//! lock.set_enforced(false);
+//! lock.set_transform(&inst, SimpleTransform::translate((100, 0)));
+//! assert_eq!(lock.get_transform(&inst), SimpleTransform::translate((100, 0)));
+//! ```
+
+use crate::decorator::bus::*;
+use crate::decorator::hierarchy::*;
+use crate::decorator::l2n::*;
+use crate::decorator::layout::*;
+use crate::decorator::netlist::*;
+use crate::decorator::power::*;
+use crate::decorator::{Decorator, MutDecorator};
+use crate::prelude::*;
+
+/// Wrapper around netlist, layout and L2N structures which rejects moves of locked cell instances.
+///
+/// # Types
+/// * `T`: Underlying data structure.
+pub struct PlacementLock<'a, T> {
+    /// Underlying data structure.
+    chip: &'a mut T,
+    /// Reject moves of locked instances if set.
+    enforced: bool,
+    /// Number of calls to `set_transform()` which have been rejected.
+    num_rejected_moves: usize,
+}
+
+impl<'a, T> PlacementLock<'a, T> {
+    /// Wrap the `chip` structure. The placement status is enforced right away.
+    pub fn new(chip: &'a mut T) -> Self {
+        Self {
+            chip,
+            enforced: true,
+            num_rejected_moves: 0,
+        }
+    }
+
+    /// Enable or disable the enforcement of the placement status.
+    /// While disabled, all moves are passed to the underlying structure.
+    pub fn set_enforced(&mut self, enforced: bool) {
+        self.enforced = enforced;
+    }
+
+    /// Check if moves of locked instances are currently rejected.
+    pub fn is_enforced(&self) -> bool {
+        self.enforced
+    }
+
+    /// Get the number of moves which have been rejected so far.
+    pub fn num_rejected_moves(&self) -> usize {
+        self.num_rejected_moves
+    }
+}
+
+impl<'a, T> Decorator for PlacementLock<'a, T> {
+    type D = T;
+
+    fn base(&self) -> &Self::D {
+        &self.chip
+    }
+}
+
+impl<'a, T> MutDecorator for PlacementLock<'a, T> {
+    fn mut_base(&mut self) -> &mut Self::D {
+        &mut self.chip
+    }
+}
+
+// Inherit everything from HierarchyBase.
+impl<'a, H: HierarchyBase + 'static> HierarchyBaseDecorator for PlacementLock<'a, H> {
+    type NameType = H::NameType;
+    type CellId = H::CellId;
+    type CellInstId = H::CellInstId;
+}
+
+// Inherit everything from NetlistBase.
+impl<'a, N: NetlistBase + 'static> NetlistBaseDecorator for PlacementLock<'a, N> {}
+
+// Inherit everything from BusBase.
+impl<'a, N: BusBase + 'static> BusBaseDecorator for PlacementLock<'a, N> {}
+
+// Inherit everything from PowerBase.
+impl<'a, N: PowerBase + 'static> PowerBaseDecorator for PlacementLock<'a, N> {}
+
+// Inherit everything from LayoutBase.
+impl<'a, L: LayoutBase + 'static> LayoutBaseDecorator for PlacementLock<'a, L> {}
+
+// Inherit everything from L2NBase.
+impl<'a, LN: L2NBase + 'static> L2NBaseDecorator for PlacementLock<'a, LN> {}
+
+// Inherit everything from HierarchyEdit.
+impl<'a, H: HierarchyEdit + 'static> HierarchyEditDecorator for PlacementLock<'a, H> {
+    fn d_new() -> Self {
+        unimplemented!()
+    }
+}
+
+// Inherit everything from NetlistEdit.
+impl<'a, N: NetlistEdit + 'static> NetlistEditDecorator for PlacementLock<'a, N> {}
+
+// Inherit everything from BusEdit.
+impl<'a, N: BusEdit + 'static> BusEditDecorator for PlacementLock<'a, N> {}
+
+// Inherit everything from PowerEdit.
+impl<'a, N: PowerEdit + 'static> PowerEditDecorator for PlacementLock<'a, N> {}
+
+impl<'a, L: LayoutEdit + 'static> LayoutEditDecorator for PlacementLock<'a, L> {
+    /// Move the instance unless it is locked. A rejected move is only logged, use
+    /// `try_set_transform()` to detect it.
+    fn d_set_transform(&mut self, cell_inst: &L::CellInstId, tf: SimpleTransform<L::Coord>) {
+        let status = self.chip.placement_status(cell_inst);
+        if self.enforced && status.is_locked() && self.chip.get_transform(cell_inst) != tf {
+            log::warn!(
+                "Rejected move of cell instance '{:?}' with placement status {:?}.",
+                self.chip.cell_instance_name(cell_inst),
+                status
+            );
+            self.num_rejected_moves += 1;
+        } else {
+            self.chip.set_transform(cell_inst, tf)
+        }
+    }
+}
+
+// Inherit everything from L2NEdit.
+impl<'a, LN: L2NEdit + 'static> L2NEditDecorator for PlacementLock<'a, LN> {}
+
+#[test]
+fn test_placement_lock() {
+    use crate::chip::Chip;
+    let mut chip = Chip::new();
+    let top = chip.create_cell("TOP".into());
+    let sub = chip.create_cell("SUB".into());
+    let a = chip.create_cell_instance(&top, &sub, None);
+    let b = chip.create_cell_instance(&top, &sub, None);
+    chip.set_placement_status(&a, PlacementStatus::Firm);
+
+    let mut lock = PlacementLock::new(&mut chip);
+    let tf = SimpleTransform::translate((1, 2));
+    lock.set_transform(&a, tf);
+    lock.set_transform(&b, tf);
+    assert_eq!(lock.num_rejected_moves(), 1);
+    assert_eq!(lock.get_transform(&a), SimpleTransform::identity());
+    assert_eq!(lock.get_transform(&b), tf);
+    assert_eq!(lock.try_set_transform(&a, tf), Err(PlacementStatus::Firm));
+
+    // Unlocking the instance allows to move it.
+    lock.set_placement_status(&a, PlacementStatus::Placed);
+    assert_eq!(lock.try_set_transform(&a, tf), Ok(()));
+    assert_eq!(lock.get_transform(&a), tf);
+}
@@ -8,8 +8,10 @@
 pub use crate::chip::Chip;
 pub use crate::flat_view::FlatView;
 pub use crate::hierarchy::prelude::*;
+pub use crate::l2n::util::*;
 pub use crate::l2n::*;
 pub use crate::layout::prelude::*;
+pub use crate::library::{LibraryError, LibraryId, LibraryWrapper};
 pub use crate::netlist::prelude::*;
 pub use crate::netlist::util::*;
 pub use crate::property_storage::PropertyValue;
@@ -26,9 +28,11 @@ pub use iron_shapes::prelude::*;
 pub mod traits {
     pub use crate::hierarchy::traits::*;
     pub use crate::hierarchy::util::*;
+    pub use crate::l2n::util::*;
     pub use crate::l2n::*;
     pub use crate::layout::traits::*;
     pub use crate::layout::util::*;
+    pub use crate::netlist::power::*;
     pub use crate::netlist::traits::*;
     pub use crate::netlist::util::*;
     pub use crate::reference_access::*;
@@ -4,9 +4,11 @@
 
 //! Acquire performance metrics of single data-base functions.
 
+use crate::decorator::bus::*;
 use crate::decorator::hierarchy::*;
 use crate::decorator::layout::*;
 use crate::decorator::netlist::*;
+use crate::decorator::power::*;
 use crate::decorator::{Decorator, MutDecorator};
 use crate::prelude::*;
 
@@ -109,6 +111,12 @@ impl<'a, L: LayoutBase + 'static> LayoutBaseDecorator for DBPerf<'a, L> {}
 // Inherit everything from NetlistBase.
 impl<'a, N: NetlistBase + 'static> NetlistBaseDecorator for DBPerf<'a, N> {}
 
+// Inherit everything from BusBase.
+impl<'a, N: BusBase + 'static> BusBaseDecorator for DBPerf<'a, N> {}
+
+// Inherit everything from PowerBase.
+impl<'a, N: PowerBase + 'static> PowerBaseDecorator for DBPerf<'a, N> {}
+
 // Inherit everything from HierarchyEdit.
 impl<'a, H: HierarchyEdit + 'static> HierarchyEditDecorator for DBPerf<'a, H> {
     fn d_new() -> Self {
@@ -134,3 +142,9 @@ impl<'a, L: LayoutEdit + 'static> LayoutEditDecorator for DBPerf<'a, L> {
 
 // Inherit everything from NetlistBase.
 impl<'a, N: NetlistEdit + 'static> NetlistEditDecorator for DBPerf<'a, N> {}
+
+// Inherit everything from BusEdit.
+impl<'a, N: BusEdit + 'static> BusEditDecorator for DBPerf<'a, N> {}
+
+// Inherit everything from PowerEdit.
+impl<'a, N: PowerEdit + 'static> PowerEditDecorator for DBPerf<'a, N> {}
@@ -204,6 +204,16 @@ impl<K: Hash + Eq> PropertyStore<K> {
         self.content.insert(key, value.into())
     }
 
+    /// Remove a property.
+    /// Returns the old property value if there was a property stored under this key.
+    pub fn remove<Q: ?Sized>(&mut self, key: &Q) -> Option<PropertyValue>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash,
+    {
+        self.content.remove(key)
+    }
+
     /// Get a property value by the property key.
     pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<&PropertyValue>
     where
@@ -213,6 +223,11 @@ impl<K: Hash + Eq> PropertyStore<K> {
         self.content.get(key)
     }
 
+    /// Iterate over all stored (key, value) pairs.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &PropertyValue)> {
+        self.content.iter()
+    }
+
     /// Check if the `key` is contained in this property store.
     pub fn contains_key<Q: ?Sized>(&self, key: &Q) -> bool
     where
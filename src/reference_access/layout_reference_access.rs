@@ -4,7 +4,9 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
 use super::hierarchy_reference_access::*;
-use crate::prelude::{Geometry, LayerInfo, LayoutBase, Rect, SimpleTransform};
+use crate::prelude::{
+    Geometry, InstanceArray, LayerInfo, LayoutBase, PlacementStatus, Rect, SimpleTransform,
+};
 
 /// Trait that provides object-like read access to a layout structure and its elements.
 pub trait LayoutReferenceAccess: LayoutBase {
@@ -42,6 +44,16 @@ impl<'a, L: LayoutBase> CellInstRef<'a, L> {
     pub fn get_transform(&self) -> SimpleTransform<L::Coord> {
         self.base().get_transform(&self.id)
     }
+
+    /// Get the array parameters of the cell instance or `None` if it is a single placement.
+    pub fn cell_instance_array(&self) -> Option<InstanceArray<L::Coord>> {
+        self.base().cell_instance_array(&self.id)
+    }
+
+    /// Get the placement status of the cell instance.
+    pub fn placement_status(&self) -> PlacementStatus {
+        self.base().placement_status(&self.id)
+    }
 }
 
 impl<'a, L: LayoutBase> CellRef<'a, L> {
@@ -3,19 +3,26 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
 //! Add fast region queries to layouts.
-
-// TODO: Remove this, once implemented.
-#![allow(unused_variables)]
+//!
+//! The spatial indices are kept up-to-date when the layout is edited through the adapter.
+//! A change of a cell's bounding box is propagated to all cells which instantiate it.
 
 use fnv::FnvHashMap;
-use num_traits::{PrimInt, Signed};
-use rstar::{RTree, RTreeObject};
+use iron_shapes::CoordinateType;
+use num_traits::{Num, PrimInt, Signed};
+use rstar::{RTree, RTreeNode, RTreeObject};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
 
+use crate::decorator::bus::*;
 use crate::decorator::hierarchy::*;
 use crate::decorator::l2n::*;
 use crate::decorator::layout::*;
 use crate::decorator::netlist::*;
+use crate::decorator::power::*;
 use crate::decorator::{Decorator, MutDecorator};
+use crate::flat_view::inverse_transform_rect;
+use crate::layout::util::rect_distance_sq;
 use crate::prelude::*;
 
 /// Wrapper around netlist, layout and L2N structures that allows fast region queries.
@@ -46,12 +53,19 @@ pub struct ShapeEntry<ShapeId, Coord> {
 }
 
 /// Wrapper for cell instances
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct CellInstanceEntry<L: LayoutBase> {
     bounding_box: Rect<L::Coord>,
     cell_inst_id: L::CellInstId,
 }
 
+// Implement manually. Deriving `PartialEq` would require `L: PartialEq`.
+impl<L: LayoutBase> PartialEq for CellInstanceEntry<L> {
+    fn eq(&self, other: &Self) -> bool {
+        self.bounding_box == other.bounding_box && self.cell_inst_id == other.cell_inst_id
+    }
+}
+
 // Make `ShapeEntry` usable within RTrees.
 impl<ShapeId, Coord> BoundingBox<Coord> for ShapeEntry<ShapeId, Coord>
 where
@@ -163,18 +177,11 @@ where
                 .chip
                 .each_cell_instance(&cell)
                 .filter_map(|inst| {
-                    self.cell_bounding_boxes
-                        .get(&self.chip.template_cell(&inst))
-                        .map(|bbox| (inst, bbox))
-                })
-                .map(|(inst, bbox)| {
-                    let tf = self.chip.get_transform(&inst);
-                    let bbox_transformed = bbox.transform(|p| tf.transform_point(p));
-
-                    CellInstanceEntry {
-                        bounding_box: bbox_transformed,
-                        cell_inst_id: inst,
-                    }
+                    self.cell_instance_bbox(&inst)
+                        .map(|bounding_box| CellInstanceEntry {
+                            bounding_box,
+                            cell_inst_id: inst,
+                        })
                 })
                 .collect();
 
@@ -209,6 +216,8 @@ where
             .into_iter()
             // Get rtree of each layer.
             .flat_map(|layer_trees| layer_trees.values())
+            // The envelope of an empty tree is not a valid bounding box.
+            .filter(|rtree| rtree.size() > 0)
             // Get the bounding box of the layer.
             .map(|rtree| rtree.root().envelope())
             // Convert to Rect
@@ -218,7 +227,6 @@ where
     }
 
     /// Compute bounding box of all subcells, excluding shapes in the current cell.
-    #[allow(unused)]
     fn compute_subcell_bboxes(&self, cell_id: &T::CellId) -> Option<Rect<T::Coord>> {
         if let Some(instance_rtree) = self.instance_rtree.get(cell_id) {
             if instance_rtree.size() > 0 {
@@ -245,6 +253,132 @@ where
         //     acc.add_rect(&b)
         // })
     }
+
+    /// Compute the bounding box of a cell including the sub cells.
+    fn compute_cell_bbox(&self, cell_id: &T::CellId) -> Option<Rect<T::Coord>> {
+        self.compute_shape_bbox(cell_id)
+            .into_iter()
+            .chain(self.compute_subcell_bboxes(cell_id))
+            .reduce(|acc, b| acc.add_rect(&b))
+    }
+
+    /// Get the bounding box of a cell instance in the coordinates of the parent cell.
+    /// This is based on the cached bounding box of the template cell.
+    /// The bounding box of an array covers all its elements.
+    fn cell_instance_bbox(&self, inst: &T::CellInstId) -> Option<Rect<T::Coord>> {
+        self.cell_bounding_boxes
+            .get(&self.chip.template_cell(inst))
+            .map(|bbox| {
+                let tf = self.chip.get_transform(inst);
+                let bbox = bbox.transform(|p| tf.transform_point(p));
+                match self.chip.cell_instance_array(inst) {
+                    Some(array) => array.bounding_box(&bbox),
+                    None => bbox,
+                }
+            })
+    }
+
+    /// Register the cell instance in the RTree of its parent.
+    fn insert_instance_entry(&mut self, inst: &T::CellInstId) {
+        if let Some(bounding_box) = self.cell_instance_bbox(inst) {
+            self.instance_rtree
+                .entry(self.chip.parent_cell(inst))
+                .or_default()
+                .insert(CellInstanceEntry {
+                    bounding_box,
+                    cell_inst_id: inst.clone(),
+                });
+        }
+    }
+
+    /// Remove the cell instance from the RTree of its parent.
+    /// Must be called before the location or the bounding box of the instance changes.
+    fn remove_instance_entry(&mut self, inst: &T::CellInstId) {
+        if let Some(bounding_box) = self.cell_instance_bbox(inst) {
+            if let Some(rtree) = self.instance_rtree.get_mut(&self.chip.parent_cell(inst)) {
+                let removed = rtree.remove(&CellInstanceEntry {
+                    bounding_box,
+                    cell_inst_id: inst.clone(),
+                });
+                debug_assert!(removed.is_some(), "Cell instance not found in RTree.");
+            }
+        }
+    }
+
+    /// Register a shape in the RTree of its cell and layer.
+    fn insert_shape_entry(
+        &mut self,
+        cell: &T::CellId,
+        layer: &T::LayerId,
+        shape_id: &T::ShapeId,
+        geometry: &Geometry<T::Coord>,
+    ) {
+        if let Some(bounding_box) = geometry.try_bounding_box() {
+            self.shape_rtrees
+                .entry(cell.clone())
+                .or_default()
+                .entry(layer.clone())
+                .or_default()
+                .insert(ShapeEntry {
+                    bounding_box,
+                    shape_id: shape_id.clone(),
+                });
+        }
+    }
+
+    /// Remove a shape from the RTree of its cell and layer.
+    fn remove_shape_entry(
+        &mut self,
+        cell: &T::CellId,
+        layer: &T::LayerId,
+        shape_id: &T::ShapeId,
+        geometry: &Geometry<T::Coord>,
+    ) {
+        if let Some(bounding_box) = geometry.try_bounding_box() {
+            if let Some(rtree) = self
+                .shape_rtrees
+                .get_mut(cell)
+                .and_then(|trees| trees.get_mut(layer))
+            {
+                let removed = rtree.remove(&ShapeEntry {
+                    bounding_box,
+                    shape_id: shape_id.clone(),
+                });
+                debug_assert!(removed.is_some(), "Shape not found in RTree.");
+            }
+        }
+    }
+
+    /// Recompute the bounding box of the cell after its content changed.
+    /// If the bounding box changed, the entries of all instances of the cell are
+    /// updated and the change is propagated to the parent cells.
+    fn update_bounding_box(&mut self, cell: &T::CellId) {
+        let new_bbox = self.compute_cell_bbox(cell);
+        if self.cell_bounding_boxes.get(cell) == new_bbox.as_ref() {
+            // Nothing changed. No need to update the parents.
+            return;
+        }
+
+        // Instances are stored in the RTrees with the old bounding box.
+        let references = self.chip.each_cell_reference_vec(cell);
+        for inst in &references {
+            self.remove_instance_entry(inst);
+        }
+
+        match new_bbox {
+            Some(bbox) => self.cell_bounding_boxes.insert(cell.clone(), bbox),
+            None => self.cell_bounding_boxes.remove(cell),
+        };
+
+        for inst in &references {
+            self.insert_instance_entry(inst);
+        }
+
+        // Propagate the change towards the top cells.
+        for parent in self.chip.each_dependent_cell_vec(cell) {
+            self.update_bounding_box(&parent);
+        }
+    }
 }
 
 impl<'a, T> Decorator for RegionSearchAdapter<'a, T>
@@ -328,6 +462,220 @@ where
 
         Box::new(intersecting_instances)
     }
+
+    fn each_array_element_in_region(
+        &self,
+        cell_inst: &Self::CellInstId,
+        search_region: &Rect<Self::Coord>,
+    ) -> Box<dyn Iterator<Item = (u32, u32)> + '_> {
+        let tf = self.chip.get_transform(cell_inst);
+        let element_bbox = match self
+            .cell_bounding_boxes
+            .get(&self.chip.template_cell(cell_inst))
+        {
+            Some(bbox) => bbox.transform(|p| tf.transform_point(p)),
+            None => return Box::new(std::iter::empty()),
+        };
+        let array = self
+            .chip
+            .cell_instance_array(cell_inst)
+            .unwrap_or_else(InstanceArray::single);
+        Box::new(
+            array
+                .elements_in_region(&element_bbox, search_region)
+                .into_iter(),
+        )
+    }
+
+    fn nearest_shapes(
+        &self,
+        cell: &Self::CellId,
+        layer_id: &Self::LayerId,
+        point: &Point<Self::Coord>,
+        k: usize,
+    ) -> Box<dyn Iterator<Item = Self::ShapeId> + '_> {
+        let rtree = match self
+            .shape_rtrees
+            .get(cell)
+            .expect("cell not found")
+            .get(layer_id)
+        {
+            Some(rtree) if rtree.size() > 0 => rtree,
+            _ => return Box::new(std::iter::empty()),
+        };
+        let point = Rect::new(*point, *point);
+        let distance = move |node: &RTreeNode<ShapeEntry<L::ShapeId, L::Coord>>| -> L::Area {
+            let bbox = match node {
+                RTreeNode::Leaf(entry) => entry.bounding_box,
+                RTreeNode::Parent(parent) => {
+                    let envelope = parent.envelope();
+                    Rect::new(envelope.lower(), envelope.upper())
+                }
+            };
+            rect_distance_sq(&point, &bbox)
+        };
+
+        // Best-first search through the tree: Always expand the node which is closest to the point.
+        // Leaves come out of the queue ordered by their distance.
+        let mut queue: BinaryHeap<_> = rtree
+            .root()
+            .children()
+            .iter()
+            .map(|node| ClosestFirst(distance(node), node))
+            .collect();
+
+        let nearest = std::iter::from_fn(move || {
+            while let Some(ClosestFirst(_, node)) = queue.pop() {
+                match node {
+                    RTreeNode::Leaf(entry) => return Some(entry.shape_id.clone()),
+                    RTreeNode::Parent(parent) => queue.extend(
+                        parent
+                            .children()
+                            .iter()
+                            .map(|child| ClosestFirst(distance(child), child)),
+                    ),
+                }
+            }
+            None
+        });
+
+        Box::new(nearest.take(k))
+    }
+
+    fn shapes_within_distance(
+        &self,
+        cell: &Self::CellId,
+        layer_id: &Self::LayerId,
+        geometry: &Geometry<Self::Coord>,
+        distance: Self::Coord,
+    ) -> Box<dyn Iterator<Item = Self::ShapeId> + '_> {
+        assert!(
+            distance >= Self::Coord::zero(),
+            "Distance must not be negative."
+        );
+        let bbox = match geometry.try_bounding_box() {
+            Some(bbox) => bbox,
+            None => return Box::new(std::iter::empty()),
+        };
+        let (ll, ur) = (bbox.lower_left(), bbox.upper_right());
+        let search_region = rect2aabb(&Rect::new(
+            Point::new(ll.x.saturating_sub(distance), ll.y.saturating_sub(distance)),
+            Point::new(ur.x.saturating_add(distance), ur.y.saturating_add(distance)),
+        ));
+        let max_distance_sq = L::Area::from(distance) * L::Area::from(distance);
+
+        // Use the bounding boxes stored in the tree instead of looking up the shapes.
+        let shapes = self
+            .shape_rtrees
+            .get(cell)
+            .expect("cell not found")
+            .get(layer_id)
+            .into_iter()
+            .flat_map(move |rtree| rtree.locate_in_envelope_intersecting(&search_region))
+            .filter(move |entry| {
+                rect_distance_sq::<_, L::Area>(&bbox, &entry.bounding_box) <= max_distance_sq
+            })
+            .map(|entry| entry.shape_id.clone());
+
+        Box::new(shapes)
+    }
+}
+
+/// Node of an RTree together with its distance to a query location.
+/// Ordered such that a `BinaryHeap` returns the closest node first.
+struct ClosestFirst<'a, A, T: RTreeObject>(A, &'a RTreeNode<T>);
+
+impl<'a, A: PartialOrd, T: RTreeObject> PartialEq for ClosestFirst<'a, A, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<'a, A: PartialOrd, T: RTreeObject> Eq for ClosestFirst<'a, A, T> {}
+
+impl<'a, A: PartialOrd, T: RTreeObject> PartialOrd for ClosestFirst<'a, A, T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<'a, A: PartialOrd, T: RTreeObject> Ord for ClosestFirst<'a, A, T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed, `BinaryHeap` is a max-heap.
+        other.0.partial_cmp(&self.0).unwrap_or(Ordering::Equal)
+    }
+}
+
+impl<'a, L> RegionSearchAdapter<'a, L>
+where
+    L: LayoutBase + 'static,
+    L::Coord: PrimInt + Signed + std::fmt::Debug,
+{
+    /// Find all shapes on `layer` whose bounding boxes interact with `search_region`,
+    /// including the shapes in the sub cells of `cell`.
+    ///
+    /// The search descends into the cell instances which interact with the search region.
+    /// Shapes of `cell` itself have depth `0`, shapes of direct child instances have depth `1` etc.
+    /// Sub cells deeper than `max_depth` are not visited. `None` means no depth limit.
+    ///
+    /// Returns tuples of the form `(instance path, shape ID, geometry)`. The instance path starts
+    /// with an instance in `cell` and ends with the instance of the cell which contains the shape.
+    /// The geometry is transformed into the coordinates of `cell`.
+    /// Only the elements of instance arrays which interact with the search region are visited.
+    /// They share the same instance path.
+    pub fn each_shape_in_region_recursive(
+        &self,
+        cell: &L::CellId,
+        layer: &L::LayerId,
+        search_region: &Rect<L::Coord>,
+        max_depth: Option<usize>,
+    ) -> impl Iterator<Item = (Vec<L::CellInstId>, L::ShapeId, Geometry<L::Coord>)> + '_ {
+        let search_region = *search_region;
+        let layer = layer.clone();
+
+        // Cells which still need to be visited together with the path to them
+        // and their location relative to the top cell.
+        let mut stack = vec![(cell.clone(), Vec::new(), SimpleTransform::identity())];
+        // Results of the current cell.
+        let mut found = Vec::new();
+
+        std::iter::from_fn(move || loop {
+            if let Some(result) = found.pop() {
+                return Some(result);
+            }
+            let (cell, path, tf) = stack.pop()?;
+
+            // Search region in the coordinates of this cell.
+            let local_region = inverse_transform_rect(&tf, &search_region);
+
+            let depth = path.len();
+            if max_depth.map_or(true, |max| depth < max) {
+                for inst in self.each_cell_instance_in_region(&cell, &local_region) {
+                    let template = self.chip.template_cell(&inst);
+                    for (column, row) in self.each_array_element_in_region(&inst, &local_region) {
+                        let tf_inst = self
+                            .chip
+                            .array_element_transform(&inst, column, row)
+                            .then(&tf);
+                        let mut sub_path = path.clone();
+                        sub_path.push(inst.clone());
+                        stack.push((template.clone(), sub_path, tf_inst));
+                    }
+                }
+            }
+
+            found.extend(
+                self.each_shape_in_region_per_layer(&cell, &layer, &local_region)
+                    .map(|shape_id| {
+                        let geometry = self
+                            .chip
+                            .shape_geometry(&shape_id)
+                            .transform(|p| tf.transform_point(p));
+                        (path.clone(), shape_id, geometry)
+                    }),
+            );
+        })
+    }
 }
 
 /// Convert a rectangle into an axis aligned bounding box used by RStar.
@@ -346,6 +694,22 @@ where
 {
 }
 
+// Inherit everything from BusBase.
+impl<'a, N> BusBaseDecorator for RegionSearchAdapter<'a, N>
+where
+    N: LayoutBase + BusBase + 'static,
+    N::Coord: PrimInt + Signed + std::fmt::Debug,
+{
+}
+
+// Inherit everything from PowerBase.
+impl<'a, N> PowerBaseDecorator for RegionSearchAdapter<'a, N>
+where
+    N: LayoutBase + PowerBase + 'static,
+    N::Coord: PrimInt + Signed + std::fmt::Debug,
+{
+}
+
 impl<'a, LN> L2NBaseDecorator for RegionSearchAdapter<'a, LN>
 where
     LN: L2NBase + 'static,
@@ -364,11 +728,27 @@ where
     }
 
     fn d_create_cell(&mut self, name: H::NameType) -> H::CellId {
-        todo!()
+        let cell = self.chip.create_cell(name);
+        self.shape_rtrees.insert(cell.clone(), Default::default());
+        self.instance_rtree.insert(cell.clone(), Default::default());
+        cell
     }
 
     fn d_remove_cell(&mut self, cell_id: &H::CellId) {
-        todo!()
+        // Instances of the cell are removed together with the cell.
+        for inst in self.chip.each_cell_reference_vec(cell_id) {
+            self.remove_instance_entry(&inst);
+        }
+        let parents = self.chip.each_dependent_cell_vec(cell_id);
+
+        self.chip.remove_cell(cell_id);
+        self.shape_rtrees.remove(cell_id);
+        self.instance_rtree.remove(cell_id);
+        self.cell_bounding_boxes.remove(cell_id);
+
+        for parent in parents {
+            self.update_bounding_box(&parent);
+        }
     }
 
     fn d_create_cell_instance(
@@ -377,11 +757,19 @@ where
         template_cell: &H::CellId,
         name: Option<H::NameType>,
     ) -> H::CellInstId {
-        todo!()
+        let inst = self
+            .chip
+            .create_cell_instance(parent_cell, template_cell, name);
+        self.insert_instance_entry(&inst);
+        self.update_bounding_box(parent_cell);
+        inst
     }
 
     fn d_remove_cell_instance(&mut self, inst: &H::CellInstId) {
-        todo!()
+        let parent_cell = self.chip.parent_cell(inst);
+        self.remove_instance_entry(inst);
+        self.chip.remove_cell_instance(inst);
+        self.update_bounding_box(&parent_cell);
     }
 }
 
@@ -397,14 +785,20 @@ where
         layer: &L::LayerId,
         geometry: Geometry<L::Coord>,
     ) -> L::ShapeId {
-        todo!("update RTree");
-        // let shape_id = self.chip.insert_shape(parent_cell, layer, geometry);
-        // shape_id
+        let shape_id = self.chip.insert_shape(parent_cell, layer, geometry.clone());
+        self.insert_shape_entry(parent_cell, layer, &shape_id, &geometry);
+        self.update_bounding_box(parent_cell);
+        shape_id
     }
 
     fn d_remove_shape(&mut self, shape_id: &L::ShapeId) -> Option<Geometry<L::Coord>> {
-        let _geo = self.chip.remove_shape(shape_id);
-        todo!("update RTree")
+        let (cell, layer) = self.chip.parent_of_shape(shape_id);
+        let geometry = self.chip.remove_shape(shape_id);
+        if let Some(geometry) = &geometry {
+            self.remove_shape_entry(&cell, &layer, shape_id, geometry);
+            self.update_bounding_box(&cell);
+        }
+        geometry
     }
 
     fn d_replace_shape(
@@ -412,13 +806,33 @@ where
         shape_id: &L::ShapeId,
         geometry: Geometry<L::Coord>,
     ) -> Geometry<L::Coord> {
-        let _geo = self.chip.replace_shape(shape_id, geometry);
-        todo!("update RTree")
+        let (cell, layer) = self.chip.parent_of_shape(shape_id);
+        let old_geometry = self.chip.replace_shape(shape_id, geometry.clone());
+        self.remove_shape_entry(&cell, &layer, shape_id, &old_geometry);
+        self.insert_shape_entry(&cell, &layer, shape_id, &geometry);
+        self.update_bounding_box(&cell);
+        old_geometry
     }
 
     fn d_set_transform(&mut self, cell_inst: &L::CellInstId, tf: SimpleTransform<L::Coord>) {
+        self.remove_instance_entry(cell_inst);
         self.chip.set_transform(cell_inst, tf);
-        todo!("update RTree")
+        self.insert_instance_entry(cell_inst);
+        let parent_cell = self.chip.parent_cell(cell_inst);
+        self.update_bounding_box(&parent_cell);
+    }
+
+    fn d_set_cell_instance_array(
+        &mut self,
+        cell_inst: &L::CellInstId,
+        array: Option<InstanceArray<L::Coord>>,
+    ) -> Option<InstanceArray<L::Coord>> {
+        self.remove_instance_entry(cell_inst);
+        let previous_array = self.chip.set_cell_instance_array(cell_inst, array);
+        self.insert_instance_entry(cell_inst);
+        let parent_cell = self.chip.parent_cell(cell_inst);
+        self.update_bounding_box(&parent_cell);
+        previous_array
     }
 }
 
@@ -430,6 +844,22 @@ where
 {
 }
 
+// Inherit everything from BusEdit.
+impl<'a, N> BusEditDecorator for RegionSearchAdapter<'a, N>
+where
+    N: LayoutBase + BusEdit + 'static,
+    N::Coord: PrimInt + Signed + std::fmt::Debug,
+{
+}
+
+// Inherit everything from PowerEdit.
+impl<'a, N> PowerEditDecorator for RegionSearchAdapter<'a, N>
+where
+    N: LayoutBase + PowerEdit + 'static,
+    N::Coord: PrimInt + Signed + std::fmt::Debug,
+{
+}
+
 impl<'a, LN> L2NEditDecorator for RegionSearchAdapter<'a, LN>
 where
     LN: L2NEdit + 'static,
@@ -452,3 +882,364 @@ fn test_create_region_search() {
         Rect::new((0, 0), (10, 10))
     );
 }
+
+#[test]
+fn test_each_shape_in_region_recursive() {
+    let mut chip = Chip::new();
+    let layer = chip.create_layer(1, 0);
+    let top = chip.create_cell("TOP".to_string().into());
+    let leaf = chip.create_cell("LEAF".to_string().into());
+
+    let top_shape = chip.insert_shape(&top, &layer, Rect::new((0, 0), (10, 10)).into());
+    let leaf_shape = chip.insert_shape(&leaf, &layer, Rect::new((0, 0), (1, 1)).into());
+    let inst1 = chip.create_cell_instance(&top, &leaf, None);
+    let inst2 = chip.create_cell_instance(&top, &leaf, None);
+    chip.set_transform(&inst1, SimpleTransform::translate((100, 0)));
+    chip.set_transform(&inst2, SimpleTransform::translate((200, 0)));
+
+    let region_search = RegionSearchAdapter::new(&mut chip);
+
+    let found: Vec<_> = region_search
+        .each_shape_in_region_recursive(&top, &layer, &Rect::new((5, 0), (150, 1)), None)
+        .collect();
+    assert_eq!(found.len(), 2);
+    assert!(found.contains(&(vec![], top_shape, Rect::new((0, 0), (10, 10)).into())));
+    assert!(found.contains(&(
+        vec![inst1],
+        leaf_shape,
+        Rect::new((100, 0), (101, 1)).into()
+    )));
+
+    // Do not descend into the sub cells.
+    let found: Vec<_> = region_search
+        .each_shape_in_region_recursive(&top, &layer, &Rect::new((5, 0), (250, 1)), Some(0))
+        .map(|(_, shape_id, _)| shape_id)
+        .collect();
+    assert_eq!(found, vec![top_shape]);
+}
+
+#[test]
+fn test_region_search_in_array() {
+    let mut chip = Chip::new();
+    let layer = chip.create_layer(1, 0);
+    let top = chip.create_cell("TOP".to_string().into());
+    let leaf = chip.create_cell("LEAF".to_string().into());
+    let leaf_shape = chip.insert_shape(&leaf, &layer, Rect::new((0, 0), (1, 1)).into());
+    let inst = chip.create_cell_instance(&top, &leaf, None);
+
+    let mut region_search = RegionSearchAdapter::new(&mut chip);
+    let array = InstanceArray::new(1000, 1000, Vector::new(10, 0), Vector::new(0, 10));
+    region_search.set_cell_instance_array(&inst, Some(array));
+
+    assert_eq!(
+        region_search.cell_bounding_boxes[&top],
+        Rect::new((0, 0), (9991, 9991))
+    );
+    assert_eq!(
+        region_search
+            .each_cell_instance_in_region(&top, &Rect::new((9990, 9990), (9990, 9990)))
+            .count(),
+        1
+    );
+
+    let found: Vec<_> = region_search
+        .each_shape_in_region_recursive(&top, &layer, &Rect::new((15, 25), (31, 31)), None)
+        .collect();
+    assert_eq!(found.len(), 2);
+    assert!(found.contains(&(vec![inst], leaf_shape, Rect::new((20, 30), (21, 31)).into())));
+    assert!(found.contains(&(vec![inst], leaf_shape, Rect::new((30, 30), (31, 31)).into())));
+}
+
+#[test]
+fn test_nearest_shapes() {
+    let mut chip = Chip::new();
+    let layer = chip.create_layer(1, 0);
+    let top = chip.create_cell("TOP".to_string().into());
+
+    // Row of squares with increasing distance to the origin.
+    let shapes: Vec<_> = (0..100)
+        .map(|i| {
+            let x = 10 * i + 5;
+            chip.insert_shape(&top, &layer, Rect::new((x, 0), (x + 2, 2)).into())
+        })
+        .collect();
+
+    let region_search = RegionSearchAdapter::new(&mut chip);
+
+    let nearest: Vec<_> = region_search
+        .nearest_shapes(&top, &layer, &Point::new(0, 0), 3)
+        .collect();
+    assert_eq!(nearest, shapes[0..3].to_vec());
+
+    let nearest: Vec<_> = region_search
+        .nearest_shapes(&top, &layer, &Point::new(500, 1), 1)
+        .collect();
+    assert_eq!(nearest, vec![shapes[49]]);
+
+    // Distances must be the same as with the default implementation which checks all shapes.
+    let distance = |p: Point<i32>, id: &crate::chip::ShapeId| -> i64 {
+        let bbox = region_search.shape_geometry(id).try_bounding_box().unwrap();
+        rect_distance_sq(&Rect::new(p, p), &bbox)
+    };
+    for &(x, y, k) in &[(0, 0, 10), (333, 100, 5), (-50, -50, 200)] {
+        let p = Point::new(x, y);
+        let mut expected: Vec<_> = region_search
+            .each_shape_id(&top, &layer)
+            .map(|id| distance(p, &id))
+            .collect();
+        expected.sort();
+        expected.truncate(k);
+        let actual: Vec<_> = region_search
+            .nearest_shapes(&top, &layer, &p, k)
+            .map(|id| distance(p, &id))
+            .collect();
+        assert_eq!(actual, expected);
+    }
+}
+
+#[test]
+fn test_shapes_within_distance() {
+    let mut chip = Chip::new();
+    let layer = chip.create_layer(1, 0);
+    let top = chip.create_cell("TOP".to_string().into());
+
+    let center = chip.insert_shape(&top, &layer, Rect::new((0, 0), (10, 10)).into());
+    let right = chip.insert_shape(&top, &layer, Rect::new((15, 0), (20, 10)).into());
+    // The diagonal distance is larger than 5.
+    chip.insert_shape(&top, &layer, Rect::new((14, 14), (20, 20)).into());
+    chip.insert_shape(&top, &layer, Rect::new((100, 0), (110, 10)).into());
+
+    let region_search = RegionSearchAdapter::new(&mut chip);
+    let query = Rect::new((0, 0), (10, 10)).into();
+
+    let found: Vec<_> = region_search
+        .shapes_within_distance(&top, &layer, &query, 5)
+        .collect();
+    assert_eq!(found.len(), 2);
+    assert!(found.contains(&center));
+    assert!(found.contains(&right));
+
+    // The search region must not overflow for large distances.
+    let found = region_search
+        .shapes_within_distance(&top, &layer, &query, i32::MAX)
+        .count();
+    assert_eq!(found, 4);
+}
+
+#[test]
+#[should_panic(expected = "Distance must not be negative.")]
+fn test_shapes_within_negative_distance() {
+    let mut chip = Chip::new();
+    let layer = chip.create_layer(1, 0);
+    let top = chip.create_cell("TOP".to_string().into());
+    chip.insert_shape(&top, &layer, Rect::new((0, 0), (10, 10)).into());
+
+    let region_search = RegionSearchAdapter::new(&mut chip);
+    let query = Rect::new((0, 0), (10, 10)).into();
+    let _ = region_search
+        .shapes_within_distance(&top, &layer, &query, -1)
+        .count();
+}
+
+#[cfg(test)]
+mod tests_incremental_update {
+    use super::*;
+    use crate::chip::{CellId, CellInstId, LayerId, ShapeId};
+    use std::collections::HashMap;
+
+    /// Simple xorshift pseudo-random number generator for reproducible tests.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+
+        fn coord(&mut self) -> i32 {
+            self.below(200) as i32 - 100
+        }
+
+        fn rect(&mut self) -> Rect<i32> {
+            Rect::new((self.coord(), self.coord()), (self.coord(), self.coord()))
+        }
+    }
+
+    type ShapeIndex = HashMap<(CellId, LayerId, ShapeId), Rect<i32>>;
+    type InstanceIndex = HashMap<(CellId, CellInstId), Rect<i32>>;
+    type BBoxes = HashMap<CellId, Rect<i32>>;
+
+    /// Dump the content of all spatial indices.
+    fn snapshot(rs: &RegionSearchAdapter<Chip>) -> (ShapeIndex, InstanceIndex, BBoxes) {
+        let shapes = rs
+            .shape_rtrees
+            .iter()
+            .flat_map(|(cell, trees)| {
+                trees.iter().flat_map(move |(layer, rtree)| {
+                    rtree
+                        .iter()
+                        .map(move |e| ((*cell, *layer, e.shape_id), e.bounding_box))
+                })
+            })
+            .collect();
+        let instances = rs
+            .instance_rtree
+            .iter()
+            .flat_map(|(cell, rtree)| {
+                rtree
+                    .iter()
+                    .map(move |e| ((*cell, e.cell_inst_id), e.bounding_box))
+            })
+            .collect();
+        let bboxes = rs
+            .cell_bounding_boxes
+            .iter()
+            .map(|(cell, bbox)| (*cell, *bbox))
+            .collect();
+
+        // Every cell must have an instance tree, otherwise region queries panic.
+        for cell in rs.each_cell() {
+            assert!(rs.instance_rtree.contains_key(&cell));
+            assert!(rs.shape_rtrees.contains_key(&cell));
+        }
+
+        (shapes, instances, bboxes)
+    }
+
+    /// Apply a random edit. `cells` is ordered such that a cell only instantiates cells
+    /// which come later in the list. This keeps the hierarchy free of cycles.
+    fn random_edit(
+        rs: &mut RegionSearchAdapter<Chip>,
+        rng: &mut Rng,
+        cells: &mut Vec<CellId>,
+        layers: &[LayerId],
+    ) {
+        let cell = cells[rng.below(cells.len())];
+        let layer = layers[rng.below(layers.len())];
+        let shapes: Vec<_> = rs.each_shape_id(&cell, &layer).collect();
+        let instances = rs.each_cell_instance_vec(&cell);
+
+        match rng.below(22) {
+            0..=5 => {
+                rs.insert_shape(&cell, &layer, rng.rect().into());
+            }
+            6..=7 if !shapes.is_empty() => {
+                rs.remove_shape(&shapes[rng.below(shapes.len())]);
+            }
+            8..=9 if !shapes.is_empty() => {
+                rs.replace_shape(&shapes[rng.below(shapes.len())], rng.rect().into());
+            }
+            10..=13 => {
+                let parent_idx = rng.below(cells.len());
+                if parent_idx + 1 < cells.len() {
+                    let template_idx = parent_idx + 1 + rng.below(cells.len() - parent_idx - 1);
+                    rs.create_cell_instance(&cells[parent_idx], &cells[template_idx], None);
+                }
+            }
+            14..=15 if !instances.is_empty() => {
+                rs.remove_cell_instance(&instances[rng.below(instances.len())]);
+            }
+            16..=17 if !instances.is_empty() => {
+                let inst = instances[rng.below(instances.len())];
+                rs.set_transform(
+                    &inst,
+                    SimpleTransform::translate((rng.coord(), rng.coord())),
+                );
+            }
+            18 => {
+                let name = format!("cell{}", rng.next());
+                let new_cell = rs.create_cell(name.into());
+                let idx = rng.below(cells.len() + 1);
+                cells.insert(idx, new_cell);
+            }
+            19 if cells.len() > 2 => {
+                let idx = rng.below(cells.len());
+                let cell = cells.remove(idx);
+                rs.remove_cell(&cell);
+            }
+            20..=21 if !instances.is_empty() => {
+                let inst = instances[rng.below(instances.len())];
+                let array = if rng.below(4) == 0 {
+                    None
+                } else {
+                    let (columns, rows) = (1 + rng.below(3) as u32, 1 + rng.below(3) as u32);
+                    let column_pitch = Vector::new(rng.coord(), rng.coord());
+                    let row_pitch = Vector::new(rng.coord(), rng.coord());
+                    Some(InstanceArray::new(columns, rows, column_pitch, row_pitch))
+                };
+                rs.set_cell_instance_array(&inst, array);
+            }
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn test_incremental_update_matches_rebuild() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        let mut chip = Chip::new();
+        let layers = vec![chip.create_layer(1, 0), chip.create_layer(2, 0)];
+        let mut cells: Vec<_> = (0..5)
+            .map(|i| chip.create_cell(format!("CELL{}", i).into()))
+            .collect();
+
+        for _round in 0..50 {
+            let incremental = {
+                let mut rs = RegionSearchAdapter::new(&mut chip);
+                for _ in 0..40 {
+                    random_edit(&mut rs, &mut rng, &mut cells, &layers);
+                }
+                snapshot(&rs)
+            };
+            let rebuilt = snapshot(&RegionSearchAdapter::new(&mut chip));
+
+            assert_eq!(incremental.0, rebuilt.0, "Shape trees differ.");
+            assert_eq!(incremental.1, rebuilt.1, "Cell instance trees differ.");
+            assert_eq!(incremental.2, rebuilt.2, "Cell bounding boxes differ.");
+        }
+    }
+
+    #[test]
+    fn test_bounding_box_propagates_to_parents() {
+        let mut chip = Chip::new();
+        let layer = chip.create_layer(1, 0);
+        let top = chip.create_cell("TOP".to_string().into());
+        let mid = chip.create_cell("MID".to_string().into());
+        let leaf = chip.create_cell("LEAF".to_string().into());
+
+        let mut rs = RegionSearchAdapter::new(&mut chip);
+        let mid_inst = rs.create_cell_instance(&top, &mid, None);
+        let leaf_inst = rs.create_cell_instance(&mid, &leaf, None);
+        rs.set_transform(&mid_inst, SimpleTransform::translate((100, 0)));
+        assert!(rs.cell_bounding_boxes.get(&top).is_none());
+
+        // A shape in the leaf cell must be visible from the top cell.
+        let shape = rs.insert_shape(&leaf, &layer, Rect::new((0, 0), (10, 10)).into());
+        assert_eq!(rs.cell_bounding_boxes[&top], Rect::new((100, 0), (110, 10)));
+        let found: Vec<_> = rs
+            .each_cell_instance_in_region(&top, &Rect::new((105, 5), (106, 6)))
+            .collect();
+        assert_eq!(found, vec![mid_inst]);
+
+        rs.set_transform(&leaf_inst, SimpleTransform::translate((0, 50)));
+        assert_eq!(
+            rs.cell_bounding_boxes[&top],
+            Rect::new((100, 50), (110, 60))
+        );
+        assert_eq!(
+            rs.each_cell_instance_in_region(&top, &Rect::new((105, 5), (106, 6)))
+                .count(),
+            0
+        );
+
+        // Removing the only shape empties all bounding boxes.
+        rs.remove_shape(&shape);
+        assert!(rs.cell_bounding_boxes.is_empty());
+        assert_eq!(rs.instance_rtree[&top].size(), 0);
+    }
+}
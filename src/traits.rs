@@ -8,4 +8,6 @@
 pub use crate::hierarchy::traits::*;
 pub use crate::l2n::*;
 pub use crate::layout::traits::*;
+pub use crate::netlist::bus::{BusBase, BusEdit};
+pub use crate::netlist::power::{PowerBase, PowerEdit};
 pub use crate::netlist::traits::*;
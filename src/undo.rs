@@ -3,27 +3,68 @@
 //
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
-//! *Experimental*: Wrapper around netlist, layout and L2N structures that allows undoing of operations.
+//! Wrapper around netlist, layout and L2N structures that allows undoing and redoing of operations.
 //!
-//! This is work in progress.
-//! Missing things are:
-//! * Undoing a operation on the hierarchy does not necessarily restore netlist, layout and l2n information.
-//! * Undoing a netlist operation does not restore l2n information.
-//! * Undoing a layout operation does not restore l2n information.
-//! * Undoing does not restore user-defined properties.
+//! Each modifying call on the [`Undo`] wrapper is recorded as one transaction. Operations which
+//! consist of many primitive modifications, such as removing a cell together with its
+//! instances, pins, nets and shapes, are decomposed into reversible steps and undone as a whole.
+//! Undone transactions are kept on a redo stack until a new modification is made.
+//!
+//! Many modifications can be grouped into one named transaction with
+//! [`Undo::begin_transaction()`] and [`Undo::commit()`]. An open transaction can be reverted
+//! with [`Undo::rollback()`]. Nested transactions act as savepoints. A [`Checkpoint`] marks a
+//! state in the history which can be restored with [`Undo::undo_to()`].
+//!
+//! ```
+//! use libreda_db::prelude::*;
+//! use libreda_db::undo::Undo;
+//!
+//! let mut chip = Chip::new();
+//! let mut undo = Undo::new_hierarchy_undo(&mut chip);
+//!
+//! let checkpoint = undo.checkpoint();
+//!
+//! undo.begin_transaction("create cells");
+//! undo.create_cell("A".into());
+//! undo.begin_transaction("failing step");
+//! undo.create_cell("B".into());
+//! undo.rollback(); // Reverts only the creation of cell `B`.
+//! undo.commit();
+//!
+//! assert_eq!(undo.num_cells(), 1);
+//! assert_eq!(undo.undo_name(), Some("create cells"));
+//!
+//! undo.undo_to(&checkpoint);
+//! assert_eq!(undo.num_cells(), 0);
+//! ```
+//!
+//! The type of the undo operations defines what information is restored. For example
+//! a wrapper created with [`Undo::new_hierarchy_undo()`] only restores the cell hierarchy
+//! while [`Undo::new_l2n_undo()`] also restores nets, pins, shapes and the links between them.
+//! Buses of pins and nets are only restored by a wrapper created with [`Undo::new_bus_undo()`].
+//! With other wrappers, a bus which is removed together with one of its pins or nets is lost.
+//! Likewise power domains and their assignments are only restored by a wrapper created with
+//! [`Undo::new_power_undo()`], which also restores buses.
 //!
 //! # Caveat
 //! Undoing removal of some objects does not preserve the ID of the object.
 //! For example if a cell is deleted this can be undone. The restored cell, pins, instances, etc.
-//! will have the same properties but different IDs.
+//! will have the same properties but different IDs. The IDs stored in the undo and redo
+//! history are updated accordingly. This takes time proportional to the length of the history.
+//!
+//! A restored pin is appended to the list of pins of its cell. Hence the order of the pins can change.
+//!
+//! Creating a layer cannot be undone.
 
 use crate::decorator::hierarchy::HierarchyBaseDecorator;
 use crate::decorator::layout::LayoutBaseDecorator;
 use crate::decorator::{Decorator, MutDecorator};
-use crate::layout::prelude::{Geometry, SimpleTransform};
+use crate::layout::prelude::{Geometry, InstanceArray, PlacementStatus, SimpleTransform};
+use crate::netlist::bus::{bus_bit_name, BitOrder};
 use crate::netlist::direction::Direction;
 use crate::prelude::PropertyValue;
 use crate::traits::*;
+use std::borrow::Borrow;
 use std::ops::Deref;
 
 /// Undo operations on the netlist.
@@ -32,18 +73,59 @@ pub enum NetlistUndoOp<T: NetlistBase> {
     HierarchyOp(HierarchyUndoOp<T>),
     /// Undo creating a pin.
     CreatePin(T::PinId),
+    /// Store the definition of a removed pin.
+    RemovePin {
+        /// ID of the removed pin.
+        pin: T::PinId,
+        /// Cell of the removed pin.
+        cell: T::CellId,
+        /// Name of the removed pin.
+        name: T::NameType,
+        /// Direction of the removed pin.
+        direction: Direction,
+        /// Instances of the removed pin as (cell instance, pin instance) tuples.
+        pin_instances: Vec<(T::CellInstId, T::PinInstId)>,
+    },
     /// Store the old pin name.
     RenamePin(T::PinId, T::NameType),
     /// Undo creating a net.
     CreateNet(T::NetId),
+    /// Store parent and name of a removed net.
+    /// The connections of the net are stored as separate `ConnectPin` and `ConnectPinInstance` operations.
+    RemoveNet {
+        /// ID of the removed net.
+        net: T::NetId,
+        /// Cell of the removed net.
+        parent_cell: T::CellId,
+        /// Name of the removed net.
+        name: Option<T::NameType>,
+    },
     /// Store the previous net of the pin.
     ConnectPin(T::PinId, Option<T::NetId>),
     /// Store the previous net of the pin instance.
     ConnectPinInstance(T::PinInstId, Option<T::NetId>),
     /// Store old name of the net.
     RenameNet(T::NetId, Option<T::NameType>),
-    // /// Store parent, old name and connected terminals of a net.
-    // RemoveNet(T::CellId, Option<T::NameType>, Vec<TerminalId<T>>)
+    /// Pin instances of a removed cell instance.
+    /// Restoring the cell instance creates new pin instances. This entry is used to
+    /// update the pin instance IDs in the history.
+    CellInstancePins {
+        /// The removed cell instance.
+        inst: T::CellInstId,
+        /// Pin instances as (template pin, pin instance) tuples.
+        pins: Vec<(T::PinId, T::PinInstId)>,
+    },
+    /// Constant nets of a removed cell.
+    /// Restoring the cell creates new constant nets. This entry is used to
+    /// update the net IDs in the history.
+    CellConstantNets {
+        /// The removed cell.
+        cell: T::CellId,
+        /// The constant LOW net.
+        net_zero: T::NetId,
+        /// The constant HIGH net.
+        net_one: T::NetId,
+    },
 }
 
 impl<T: NetlistBase> From<HierarchyUndoOp<T>> for NetlistUndoOp<T> {
@@ -52,6 +134,101 @@ impl<T: NetlistBase> From<HierarchyUndoOp<T>> for NetlistUndoOp<T> {
     }
 }
 
+/// Undo operations on the netlist including buses.
+pub enum BusUndoOp<T: BusBase> {
+    /// Undo an operation on the netlist.
+    NetlistOp(NetlistUndoOp<T>),
+    /// Undo creating a pin bus.
+    CreatePinBus(T::PinBusId),
+    /// Store the definition of a removed pin bus.
+    RemovePinBus {
+        /// ID of the removed bus.
+        bus: T::PinBusId,
+        /// Name of the removed bus.
+        name: T::NameType,
+        /// Pins of the removed bus ordered by bit index.
+        pins: Vec<T::PinId>,
+        /// Bit order of the removed bus.
+        bit_order: BitOrder,
+    },
+    /// Undo creating a net bus.
+    CreateNetBus(T::NetBusId),
+    /// Store the definition of a removed net bus.
+    RemoveNetBus {
+        /// ID of the removed bus.
+        bus: T::NetBusId,
+        /// Name of the removed bus.
+        name: T::NameType,
+        /// Nets of the removed bus ordered by bit index.
+        nets: Vec<T::NetId>,
+        /// Bit order of the removed bus.
+        bit_order: BitOrder,
+    },
+}
+
+impl<T: BusBase> From<HierarchyUndoOp<T>> for BusUndoOp<T> {
+    fn from(op: HierarchyUndoOp<T>) -> Self {
+        Self::NetlistOp(op.into())
+    }
+}
+
+impl<T: BusBase> From<NetlistUndoOp<T>> for BusUndoOp<T> {
+    fn from(op: NetlistUndoOp<T>) -> Self {
+        Self::NetlistOp(op)
+    }
+}
+
+/// Undo operations on the netlist including buses and power domains.
+pub enum PowerUndoOp<T: BusBase + PowerBase> {
+    /// Undo an operation on the netlist or on buses.
+    BusOp(BusUndoOp<T>),
+    /// Undo creating a power domain.
+    CreatePowerDomain(T::PowerDomainId),
+    /// Store the definition of a removed power domain.
+    /// The assignments of the domain are stored as separate `SetPowerDomainOfCell` and
+    /// `SetPowerDomainOfCellInstance` operations.
+    RemovePowerDomain {
+        /// ID of the removed domain.
+        domain: T::PowerDomainId,
+        /// Name of the removed domain.
+        name: T::NameType,
+        /// Supply net of the removed domain.
+        supply_net: T::NetId,
+        /// Ground net of the removed domain.
+        ground_net: T::NetId,
+        /// Nominal voltage of the removed domain.
+        voltage: f64,
+        /// Always-on flag of the removed domain.
+        always_on: bool,
+    },
+    /// Store the previous voltage of the domain.
+    SetPowerDomainVoltage(T::PowerDomainId, f64),
+    /// Store the previous always-on flag of the domain.
+    SetPowerDomainAlwaysOn(T::PowerDomainId, bool),
+    /// Store the previous power domain of the cell.
+    SetPowerDomainOfCell(T::CellId, Option<T::PowerDomainId>),
+    /// Store the previously assigned power domain of the cell instance.
+    SetPowerDomainOfCellInstance(T::CellInstId, Option<T::PowerDomainId>),
+}
+
+impl<T: BusBase + PowerBase> From<HierarchyUndoOp<T>> for PowerUndoOp<T> {
+    fn from(op: HierarchyUndoOp<T>) -> Self {
+        Self::BusOp(op.into())
+    }
+}
+
+impl<T: BusBase + PowerBase> From<NetlistUndoOp<T>> for PowerUndoOp<T> {
+    fn from(op: NetlistUndoOp<T>) -> Self {
+        Self::BusOp(op.into())
+    }
+}
+
+impl<T: BusBase + PowerBase> From<BusUndoOp<T>> for PowerUndoOp<T> {
+    fn from(op: BusUndoOp<T>) -> Self {
+        Self::BusOp(op)
+    }
+}
+
 /// Undo operation for `LayoutEdit` operations.
 pub enum LayoutUndoOp<T: LayoutBase> {
     /// Undo an operation on the cell hierarchy.
@@ -66,17 +243,34 @@ pub enum LayoutUndoOp<T: LayoutBase> {
     InsertShape(T::ShapeId),
     /// Store the geometry of the previous shape.
     RemoveShape {
+        /// ID of the removed shape.
+        shape: T::ShapeId,
         /// Parent cell of the removed shape.
         parent_cell: T::CellId,
         /// Layer of the removed shape.
         layer: T::LayerId,
         /// Geometry of the removed shape.
         geometry: Geometry<T::Coord>,
+        /// Properties of the removed shape.
+        properties: Vec<(T::NameType, PropertyValue)>,
     },
     /// Store the old geometry of the shape.
     ReplaceShape(T::ShapeId, Geometry<T::Coord>),
     /// Store the old transform.
     SetTransform(T::CellInstId, SimpleTransform<T::Coord>),
+    /// Store the old array parameters of a cell instance.
+    SetCellInstanceArray(T::CellInstId, Option<InstanceArray<T::Coord>>),
+    /// Store the old placement status of a cell instance.
+    SetPlacementStatus(T::CellInstId, PlacementStatus),
+    /// Store the previous value of a shape property.
+    SetShapeProperty {
+        /// The modified shape.
+        shape: T::ShapeId,
+        /// Property key.
+        key: T::NameType,
+        /// Previous value of the property. `None` if the property was not set.
+        previous: Option<PropertyValue>,
+    },
 }
 
 impl<T: LayoutBase> From<HierarchyUndoOp<T>> for LayoutUndoOp<T> {
@@ -128,8 +322,31 @@ impl<T: L2NBase> From<LayoutUndoOp<T>> for L2NUndoOp<T> {
 pub enum HierarchyUndoOp<T: HierarchyBase> {
     /// Undo creating a cell.
     CreateCell(T::CellId),
+    /// Store name and properties of a removed cell.
+    /// The content of the cell is stored as separate operations.
+    RemoveCell {
+        /// ID of the removed cell.
+        cell: T::CellId,
+        /// Name of the removed cell.
+        name: T::NameType,
+        /// Properties of the removed cell.
+        properties: Vec<(T::NameType, PropertyValue)>,
+    },
     /// Undo creating a cell instance.
     CreateCellInstance(T::CellInstId),
+    /// Store the definition of a removed cell instance.
+    RemoveCellInstance {
+        /// ID of the removed instance.
+        inst: T::CellInstId,
+        /// Cell which contained the instance.
+        parent_cell: T::CellId,
+        /// Template cell of the instance.
+        template_cell: T::CellId,
+        /// Name of the instance.
+        name: Option<T::NameType>,
+        /// Properties of the removed instance.
+        properties: Vec<(T::NameType, PropertyValue)>,
+    },
     /// Holds the previous name of the cell.
     RenameCell {
         /// The renamed cell.
@@ -144,277 +361,1591 @@ pub enum HierarchyUndoOp<T: HierarchyBase> {
         /// The name to be restored when undoing.
         previous_name: Option<T::NameType>,
     },
+    /// Store the previous value of a chip property.
+    SetChipProperty {
+        /// Property key.
+        key: T::NameType,
+        /// Previous value of the property. `None` if the property was not set.
+        previous: Option<PropertyValue>,
+    },
+    /// Store the previous value of a cell property.
+    SetCellProperty {
+        /// The modified cell.
+        cell: T::CellId,
+        /// Property key.
+        key: T::NameType,
+        /// Previous value of the property. `None` if the property was not set.
+        previous: Option<PropertyValue>,
+    },
+    /// Store the previous value of a cell instance property.
+    SetCellInstanceProperty {
+        /// The modified instance.
+        inst: T::CellInstId,
+        /// Property key.
+        key: T::NameType,
+        /// Previous value of the property. `None` if the property was not set.
+        previous: Option<PropertyValue>,
+    },
 }
 
-/// Wrapper around netlist, layout and L2N structures that allows undoing of operations.
+/// Undo operation which can be reverted on a data structure of type `T`.
 ///
-/// # Types
-/// * `T`: Underlying data structure.
-/// * `U`: Undo-operation.
-pub struct Undo<'a, T, U> {
-    /// Underlying data structure.
-    chip: &'a mut T,
-    /// A list of performed transactions.
-    /// To undo operations, this list has to be worked through from the end.
-    transactions: Vec<U>,
+/// Besides reverting the operation itself this trait defines how removals are decomposed
+/// into reversible steps. The decomposition depends on the type of the undo operation: A hierarchy
+/// operation knows only about cells and instances while a L2N operation also knows about nets,
+/// pins and shapes.
+pub trait UndoOp<T: HierarchyEdit + 'static>: From<HierarchyUndoOp<T>> + Sized {
+    /// Revert the operation. Modifications done while reverting are recorded by `undo`.
+    fn revert(self, undo: &mut Undo<'_, T, Self>);
+
+    /// Remove a cell such that the removal can be undone.
+    fn remove_cell(undo: &mut Undo<'_, T, Self>, cell: &T::CellId);
+
+    /// Remove a cell instance such that the removal can be undone.
+    fn remove_cell_instance(undo: &mut Undo<'_, T, Self>, inst: &T::CellInstId);
+
+    /// Replace the cell ID `old` by `new`.
+    fn remap_cell(&mut self, old: &T::CellId, new: &T::CellId);
+
+    /// Replace the cell instance ID `old` by `new`.
+    fn remap_cell_instance(&mut self, old: &T::CellInstId, new: &T::CellInstId);
 }
 
-impl<'a, T, U> Undo<'a, T, U> {
-    /// Return the number of undoable transactions.
-    pub fn num_transactions(&self) -> usize {
-        self.transactions.len()
-    }
+/// Undo operation which also handles netlist modifications.
+pub trait NetlistUndo<T: NetlistEdit + 'static>: UndoOp<T> + From<NetlistUndoOp<T>> {
+    /// Remove a pin such that the removal can be undone.
+    fn remove_pin(undo: &mut Undo<'_, T, Self>, pin: &T::PinId);
 
-    /// Clear the undo buffer and make changes permanent.
-    pub fn flush(&mut self) {
-        self.transactions.clear()
-    }
+    /// Remove a net such that the removal can be undone.
+    fn remove_net(undo: &mut Undo<'_, T, Self>, net: &T::NetId);
+
+    /// Replace the pin ID `old` by `new`.
+    fn remap_pin(&mut self, old: &T::PinId, new: &T::PinId);
+
+    /// Replace the pin instance ID `old` by `new`.
+    fn remap_pin_instance(&mut self, old: &T::PinInstId, new: &T::PinInstId);
+
+    /// Replace the net ID `old` by `new`.
+    fn remap_net(&mut self, old: &T::NetId, new: &T::NetId);
 }
 
-impl<'a, T, U> Deref for Undo<'a, T, U> {
-    type Target = T;
+/// Undo operation which also handles buses.
+pub trait BusUndo<T: BusEdit + 'static>: NetlistUndo<T> + From<BusUndoOp<T>> {
+    /// Replace the pin bus ID `old` by `new`.
+    fn remap_pin_bus(&mut self, old: &T::PinBusId, new: &T::PinBusId);
 
-    fn deref(&self) -> &Self::Target {
-        &self.chip
-    }
+    /// Replace the net bus ID `old` by `new`.
+    fn remap_net_bus(&mut self, old: &T::NetBusId, new: &T::NetBusId);
 }
 
-impl<'a, T: HierarchyEdit, U> Undo<'a, T, U> {
-    /// Undo a hierarchy operation.
-    fn undo_hierarchy_op(&mut self, op: HierarchyUndoOp<T>) {
-        match op {
-            HierarchyUndoOp::CreateCell(c) => self.chip.remove_cell(&c),
-            HierarchyUndoOp::CreateCellInstance(c) => self.chip.remove_cell_instance(&c),
-            HierarchyUndoOp::RenameCell {
-                cell,
-                previous_name,
-            } => self.chip.rename_cell(&cell, previous_name),
-            HierarchyUndoOp::RenameCellInst {
-                inst,
-                previous_name,
-            } => self.chip.rename_cell_instance(&inst, previous_name),
-        }
+/// Undo operation which also handles power domains.
+pub trait PowerUndo<T: BusEdit + PowerEdit + 'static>: BusUndo<T> + From<PowerUndoOp<T>> {
+    /// Replace the power domain ID `old` by `new`.
+    fn remap_power_domain(&mut self, old: &T::PowerDomainId, new: &T::PowerDomainId);
+}
+
+/// Undo operation which also handles layout modifications.
+pub trait LayoutUndo<T: LayoutEdit + 'static>: UndoOp<T> + From<LayoutUndoOp<T>> {
+    /// Remove a shape such that the removal can be undone.
+    fn remove_shape(undo: &mut Undo<'_, T, Self>, shape: &T::ShapeId)
+        -> Option<Geometry<T::Coord>>;
+
+    /// Replace the shape ID `old` by `new`.
+    fn remap_shape(&mut self, old: &T::ShapeId, new: &T::ShapeId);
+}
+
+/// Replace `id` by `new` if it is equal to `old`.
+fn remap<I: PartialEq + Clone>(id: &mut I, old: &I, new: &I) {
+    if id == old {
+        *id = new.clone();
     }
 }
 
-impl<'a, T: L2NBase + 'static, U> L2NBase for Undo<'a, T, U> {
-    fn shapes_of_net(&self, net_id: &Self::NetId) -> Box<dyn Iterator<Item = Self::ShapeId> + '_> {
-        self.chip.shapes_of_net(net_id)
+/// Replace `id` by `new` if it is equal to `old`.
+fn remap_option<I: PartialEq + Clone>(id: &mut Option<I>, old: &I, new: &I) {
+    if let Some(id) = id {
+        remap(id, old, new)
     }
+}
 
-    fn shapes_of_pin(&self, pin_id: &Self::PinId) -> Box<dyn Iterator<Item = Self::ShapeId> + '_> {
-        self.chip.shapes_of_pin(pin_id)
+impl<T: HierarchyEdit + 'static> UndoOp<T> for HierarchyUndoOp<T> {
+    fn revert(self, undo: &mut Undo<'_, T, Self>) {
+        undo.undo_hierarchy_op(self)
     }
 
-    fn get_net_of_shape(&self, shape_id: &Self::ShapeId) -> Option<Self::NetId> {
-        self.chip.get_net_of_shape(shape_id)
+    fn remove_cell(undo: &mut Undo<'_, T, Self>, cell: &T::CellId) {
+        undo.begin_group();
+        undo.remove_instances_of_cell(cell);
+        undo.remove_cell_bare(cell);
+        undo.end_group();
     }
 
-    fn get_pin_of_shape(&self, shape_id: &Self::ShapeId) -> Option<Self::PinId> {
-        self.chip.get_pin_of_shape(shape_id)
+    fn remove_cell_instance(undo: &mut Undo<'_, T, Self>, inst: &T::CellInstId) {
+        undo.remove_cell_instance_bare(inst)
     }
-}
 
-impl<'a, T: L2NEdit, U> Undo<'a, T, U> {
-    /// Undo an operation on fused netlist and layout.
-    fn undo_l2n_op(&mut self, op: L2NUndoOp<T>) {
-        match op {
-            // Redirect to base traits.
-            L2NUndoOp::HierarchyOp(op) => self.undo_hierarchy_op(op),
-            L2NUndoOp::NetlistOp(op) => self.undo_netlist_op(op),
-            L2NUndoOp::LayoutOp(op) => self.undo_layout_op(op),
-            // L2N specific operations
-            L2NUndoOp::SetNetOfShape {
-                shape_id,
-                previous_net,
-            } => {
-                self.chip.set_net_of_shape(&shape_id, previous_net);
-            }
-            L2NUndoOp::SetPinOfShape {
-                shape_id,
-                previous_pin,
+    fn remap_cell(&mut self, old: &T::CellId, new: &T::CellId) {
+        match self {
+            HierarchyUndoOp::CreateCell(cell)
+            | HierarchyUndoOp::RemoveCell { cell, .. }
+            | HierarchyUndoOp::RenameCell { cell, .. }
+            | HierarchyUndoOp::SetCellProperty { cell, .. } => remap(cell, old, new),
+            HierarchyUndoOp::RemoveCellInstance {
+                parent_cell,
+                template_cell,
+                ..
             } => {
-                self.chip.set_pin_of_shape(&shape_id, previous_pin);
+                remap(parent_cell, old, new);
+                remap(template_cell, old, new);
             }
+            _ => {}
+        }
+    }
+
+    fn remap_cell_instance(&mut self, old: &T::CellInstId, new: &T::CellInstId) {
+        match self {
+            HierarchyUndoOp::CreateCellInstance(inst)
+            | HierarchyUndoOp::RemoveCellInstance { inst, .. }
+            | HierarchyUndoOp::RenameCellInst { inst, .. }
+            | HierarchyUndoOp::SetCellInstanceProperty { inst, .. } => remap(inst, old, new),
+            _ => {}
         }
     }
 }
 
-impl<'a, T: L2NEdit> Undo<'a, T, L2NUndoOp<T>> {
-    /// Create a wrapper around a fused layout and netlist which
-    /// allows to undo operations.
-    pub fn new_l2n_undo(chip: &'a mut T) -> Self {
-        Self {
-            chip,
-            transactions: vec![],
+impl<T: NetlistEdit + 'static> UndoOp<T> for NetlistUndoOp<T> {
+    fn revert(self, undo: &mut Undo<'_, T, Self>) {
+        undo.undo_netlist_op(self)
+    }
+
+    fn remove_cell(undo: &mut Undo<'_, T, Self>, cell: &T::CellId) {
+        undo.begin_group();
+        undo.remove_instances_of_cell(cell);
+        undo.remove_nets_and_pins_of_cell(cell);
+        undo.remove_cell_bare(cell);
+        undo.end_group();
+    }
+
+    fn remove_cell_instance(undo: &mut Undo<'_, T, Self>, inst: &T::CellInstId) {
+        undo.begin_group();
+        undo.disconnect_cell_instance(inst);
+        undo.record_cell_instance_pins(inst);
+        undo.remove_cell_instance_bare(inst);
+        undo.end_group();
+    }
+
+    fn remap_cell(&mut self, old: &T::CellId, new: &T::CellId) {
+        match self {
+            NetlistUndoOp::HierarchyOp(op) => op.remap_cell(old, new),
+            NetlistUndoOp::RemovePin { cell, .. }
+            | NetlistUndoOp::RemoveNet {
+                parent_cell: cell, ..
+            }
+            | NetlistUndoOp::CellConstantNets { cell, .. } => remap(cell, old, new),
+            _ => {}
         }
     }
 
-    /// Undo the latest transaction.
-    /// Does nothing if there's no transaction left to be undone.
-    pub fn undo(&mut self) {
-        if let Some(op) = self.transactions.pop() {
-            self.undo_l2n_op(op)
+    fn remap_cell_instance(&mut self, old: &T::CellInstId, new: &T::CellInstId) {
+        match self {
+            NetlistUndoOp::HierarchyOp(op) => op.remap_cell_instance(old, new),
+            NetlistUndoOp::RemovePin { pin_instances, .. } => pin_instances
+                .iter_mut()
+                .for_each(|(inst, _)| remap(inst, old, new)),
+            NetlistUndoOp::CellInstancePins { inst, .. } => remap(inst, old, new),
+            _ => {}
         }
     }
 }
 
-impl<'a, T: LayoutEdit, U> Undo<'a, T, U> {
-    /// Undo a layout operation
-    fn undo_layout_op(&mut self, op: LayoutUndoOp<T>) {
-        match op {
-            LayoutUndoOp::HierarchyOp(op) => self.undo_hierarchy_op(op),
-            LayoutUndoOp::SetDbu(dbu) => self.chip.set_dbu(dbu),
-            LayoutUndoOp::CreateLayer(_id) => {
-                // TODO
-                log::error!("Creating a layer cannot be undone.");
-            }
-            LayoutUndoOp::SetLayerName(id, old_name) => {
-                self.chip.set_layer_name(&id, old_name);
+impl<T: NetlistEdit + 'static> NetlistUndo<T> for NetlistUndoOp<T> {
+    fn remove_pin(undo: &mut Undo<'_, T, Self>, pin: &T::PinId) {
+        undo.begin_group();
+        undo.disconnect_pin_and_instances(pin);
+        undo.remove_pin_bare(pin);
+        undo.end_group();
+    }
+
+    fn remove_net(undo: &mut Undo<'_, T, Self>, net: &T::NetId) {
+        undo.begin_group();
+        undo.disconnect_net(net);
+        undo.remove_net_bare(net);
+        undo.end_group();
+    }
+
+    fn remap_pin(&mut self, old: &T::PinId, new: &T::PinId) {
+        match self {
+            NetlistUndoOp::CreatePin(pin)
+            | NetlistUndoOp::RemovePin { pin, .. }
+            | NetlistUndoOp::RenamePin(pin, _)
+            | NetlistUndoOp::ConnectPin(pin, _) => remap(pin, old, new),
+            NetlistUndoOp::CellInstancePins { pins, .. } => {
+                pins.iter_mut().for_each(|(pin, _)| remap(pin, old, new))
             }
-            LayoutUndoOp::InsertShape(id) => {
-                self.chip.remove_shape(&id);
+            _ => {}
+        }
+    }
+
+    fn remap_pin_instance(&mut self, old: &T::PinInstId, new: &T::PinInstId) {
+        match self {
+            NetlistUndoOp::ConnectPinInstance(pin_inst, _) => remap(pin_inst, old, new),
+            NetlistUndoOp::RemovePin { pin_instances, .. } => pin_instances
+                .iter_mut()
+                .for_each(|(_, pin_inst)| remap(pin_inst, old, new)),
+            NetlistUndoOp::CellInstancePins { pins, .. } => pins
+                .iter_mut()
+                .for_each(|(_, pin_inst)| remap(pin_inst, old, new)),
+            _ => {}
+        }
+    }
+
+    fn remap_net(&mut self, old: &T::NetId, new: &T::NetId) {
+        match self {
+            NetlistUndoOp::CreateNet(net)
+            | NetlistUndoOp::RemoveNet { net, .. }
+            | NetlistUndoOp::RenameNet(net, _) => remap(net, old, new),
+            NetlistUndoOp::ConnectPin(_, net) | NetlistUndoOp::ConnectPinInstance(_, net) => {
+                remap_option(net, old, new)
             }
-            LayoutUndoOp::RemoveShape {
-                parent_cell,
-                layer,
-                geometry,
+            NetlistUndoOp::CellConstantNets {
+                net_zero, net_one, ..
             } => {
-                self.chip.insert_shape(&parent_cell, &layer, geometry);
-            }
-            LayoutUndoOp::ReplaceShape(id, geometry) => {
-                self.chip.replace_shape(&id, geometry);
+                remap(net_zero, old, new);
+                remap(net_one, old, new);
             }
-            LayoutUndoOp::SetTransform(inst, old_tf) => self.chip.set_transform(&inst, old_tf),
+            _ => {}
         }
     }
 }
 
-impl<'a, T: LayoutEdit> Undo<'a, T, LayoutUndoOp<T>> {
-    /// Create a wrapper which allows to undo operations performed
-    /// on the `LayoutEdit` trait.
-    pub fn new_layout_undo(chip: &'a mut T) -> Self {
-        Self {
-            chip,
-            transactions: vec![],
+impl<T: BusEdit + 'static> UndoOp<T> for BusUndoOp<T> {
+    fn revert(self, undo: &mut Undo<'_, T, Self>) {
+        undo.undo_bus_op(self)
+    }
+
+    fn remove_cell(undo: &mut Undo<'_, T, Self>, cell: &T::CellId) {
+        undo.begin_group();
+        undo.remove_instances_of_cell(cell);
+        undo.remove_nets_and_pins_of_cell(cell);
+        undo.remove_cell_bare(cell);
+        undo.end_group();
+    }
+
+    fn remove_cell_instance(undo: &mut Undo<'_, T, Self>, inst: &T::CellInstId) {
+        undo.begin_group();
+        undo.disconnect_cell_instance(inst);
+        undo.record_cell_instance_pins(inst);
+        undo.remove_cell_instance_bare(inst);
+        undo.end_group();
+    }
+
+    fn remap_cell(&mut self, old: &T::CellId, new: &T::CellId) {
+        if let BusUndoOp::NetlistOp(op) = self {
+            op.remap_cell(old, new)
         }
     }
 
-    /// Undo the latest transaction.
-    /// Does nothing if there's no transaction left to be undone.
-    pub fn undo(&mut self) {
-        if let Some(op) = self.transactions.pop() {
-            self.undo_layout_op(op)
+    fn remap_cell_instance(&mut self, old: &T::CellInstId, new: &T::CellInstId) {
+        if let BusUndoOp::NetlistOp(op) = self {
+            op.remap_cell_instance(old, new)
         }
     }
 }
 
-impl<'a, T: NetlistEdit, U> Undo<'a, T, U> {
-    /// Undo a netlist operation.
-    fn undo_netlist_op(&mut self, op: NetlistUndoOp<T>) {
-        match op {
-            NetlistUndoOp::HierarchyOp(op) => self.undo_hierarchy_op(op),
-            NetlistUndoOp::CreatePin(p) => self.chip.remove_pin(&p),
-            NetlistUndoOp::RenamePin(p, n) => {
-                self.chip.rename_pin(&p, n);
+impl<T: BusEdit + 'static> NetlistUndo<T> for BusUndoOp<T> {
+    fn remove_pin(undo: &mut Undo<'_, T, Self>, pin: &T::PinId) {
+        undo.begin_group();
+        undo.remove_bus_of_pin(pin);
+        undo.disconnect_pin_and_instances(pin);
+        undo.remove_pin_bare(pin);
+        undo.end_group();
+    }
+
+    fn remove_net(undo: &mut Undo<'_, T, Self>, net: &T::NetId) {
+        undo.begin_group();
+        undo.remove_bus_of_net(net);
+        undo.disconnect_net(net);
+        undo.remove_net_bare(net);
+        undo.end_group();
+    }
+
+    fn remap_pin(&mut self, old: &T::PinId, new: &T::PinId) {
+        match self {
+            BusUndoOp::NetlistOp(op) => op.remap_pin(old, new),
+            BusUndoOp::RemovePinBus { pins, .. } => {
+                pins.iter_mut().for_each(|pin| remap(pin, old, new))
             }
-            NetlistUndoOp::CreateNet(n) => self.chip.remove_net(&n),
-            NetlistUndoOp::ConnectPin(p, n) => {
-                self.chip.connect_pin(&p, n);
+            _ => {}
+        }
+    }
+
+    fn remap_pin_instance(&mut self, old: &T::PinInstId, new: &T::PinInstId) {
+        if let BusUndoOp::NetlistOp(op) = self {
+            op.remap_pin_instance(old, new)
+        }
+    }
+
+    fn remap_net(&mut self, old: &T::NetId, new: &T::NetId) {
+        match self {
+            BusUndoOp::NetlistOp(op) => op.remap_net(old, new),
+            BusUndoOp::RemoveNetBus { nets, .. } => {
+                nets.iter_mut().for_each(|net| remap(net, old, new))
             }
-            NetlistUndoOp::ConnectPinInstance(p, n) => {
-                self.chip.connect_pin_instance(&p, n);
+            _ => {}
+        }
+    }
+}
+
+impl<T: BusEdit + 'static> BusUndo<T> for BusUndoOp<T> {
+    fn remap_pin_bus(&mut self, old: &T::PinBusId, new: &T::PinBusId) {
+        match self {
+            BusUndoOp::CreatePinBus(bus) | BusUndoOp::RemovePinBus { bus, .. } => {
+                remap(bus, old, new)
             }
-            NetlistUndoOp::RenameNet(net, name) => {
-                self.chip.rename_net(&net, name);
+            _ => {}
+        }
+    }
+
+    fn remap_net_bus(&mut self, old: &T::NetBusId, new: &T::NetBusId) {
+        match self {
+            BusUndoOp::CreateNetBus(bus) | BusUndoOp::RemoveNetBus { bus, .. } => {
+                remap(bus, old, new)
             }
+            _ => {}
         }
     }
 }
 
-impl<'a, T: NetlistEdit> Undo<'a, T, NetlistUndoOp<T>> {
-    /// Create a wrapper which allows to undo operations performed
-    /// on the `NetlistEdit` trait.
-    pub fn new_netlist_undo(chip: &'a mut T) -> Self {
-        Self {
-            chip,
-            transactions: vec![],
+impl<T: BusEdit + PowerEdit + 'static> UndoOp<T> for PowerUndoOp<T> {
+    fn revert(self, undo: &mut Undo<'_, T, Self>) {
+        undo.undo_power_op(self)
+    }
+
+    fn remove_cell(undo: &mut Undo<'_, T, Self>, cell: &T::CellId) {
+        undo.begin_group();
+        undo.remove_instances_of_cell(cell);
+        // Clear the assignment before the domains of the cell are removed, such that
+        // undoing restores the domains first.
+        undo.clear_power_domain_of_cell(cell);
+        undo.remove_power_domains_of_cell(cell);
+        undo.remove_nets_and_pins_of_cell(cell);
+        undo.remove_cell_bare(cell);
+        undo.end_group();
+    }
+
+    fn remove_cell_instance(undo: &mut Undo<'_, T, Self>, inst: &T::CellInstId) {
+        undo.begin_group();
+        undo.clear_power_domain_of_cell_instance(inst);
+        undo.disconnect_cell_instance(inst);
+        undo.record_cell_instance_pins(inst);
+        undo.remove_cell_instance_bare(inst);
+        undo.end_group();
+    }
+
+    fn remap_cell(&mut self, old: &T::CellId, new: &T::CellId) {
+        match self {
+            PowerUndoOp::BusOp(op) => op.remap_cell(old, new),
+            PowerUndoOp::SetPowerDomainOfCell(cell, _) => remap(cell, old, new),
+            _ => {}
         }
     }
 
-    /// Undo the latest transaction.
-    /// Does nothing if there's no transaction left to be undone.
-    pub fn undo(&mut self) {
-        if let Some(op) = self.transactions.pop() {
-            self.undo_netlist_op(op)
+    fn remap_cell_instance(&mut self, old: &T::CellInstId, new: &T::CellInstId) {
+        match self {
+            PowerUndoOp::BusOp(op) => op.remap_cell_instance(old, new),
+            PowerUndoOp::SetPowerDomainOfCellInstance(inst, _) => remap(inst, old, new),
+            _ => {}
         }
     }
 }
 
-impl<'a, T: HierarchyEdit> Undo<'a, T, HierarchyUndoOp<T>> {
-    /// Create a wrapper which allows to undo operations performed
-    /// on the `HierarchyEdit` trait.
-    pub fn new_hierarchy_undo(chip: &'a mut T) -> Self {
-        Self {
-            chip,
-            transactions: vec![],
+impl<T: BusEdit + PowerEdit + 'static> NetlistUndo<T> for PowerUndoOp<T> {
+    fn remove_pin(undo: &mut Undo<'_, T, Self>, pin: &T::PinId) {
+        undo.begin_group();
+        undo.remove_bus_of_pin(pin);
+        undo.disconnect_pin_and_instances(pin);
+        undo.remove_pin_bare(pin);
+        undo.end_group();
+    }
+
+    fn remove_net(undo: &mut Undo<'_, T, Self>, net: &T::NetId) {
+        undo.begin_group();
+        undo.remove_bus_of_net(net);
+        undo.remove_power_domains_of_net(net);
+        undo.disconnect_net(net);
+        undo.remove_net_bare(net);
+        undo.end_group();
+    }
+
+    fn remap_pin(&mut self, old: &T::PinId, new: &T::PinId) {
+        if let PowerUndoOp::BusOp(op) = self {
+            op.remap_pin(old, new)
         }
     }
 
-    /// Undo the latest transaction.
-    /// Does nothing if there's no transaction left to be undone.
-    pub fn undo(&mut self) {
-        if let Some(op) = self.transactions.pop() {
-            self.undo_hierarchy_op(op)
+    fn remap_pin_instance(&mut self, old: &T::PinInstId, new: &T::PinInstId) {
+        if let PowerUndoOp::BusOp(op) = self {
+            op.remap_pin_instance(old, new)
         }
     }
 
-    /// Undoes all transactions.
-    pub fn undo_all(&mut self) {
-        while !self.transactions.is_empty() {
-            self.undo();
+    fn remap_net(&mut self, old: &T::NetId, new: &T::NetId) {
+        match self {
+            PowerUndoOp::BusOp(op) => op.remap_net(old, new),
+            PowerUndoOp::RemovePowerDomain {
+                supply_net,
+                ground_net,
+                ..
+            } => {
+                remap(supply_net, old, new);
+                remap(ground_net, old, new);
+            }
+            _ => {}
         }
     }
 }
 
-impl<'a, H, U> Decorator for Undo<'a, H, U> {
-    type D = H;
+impl<T: BusEdit + PowerEdit + 'static> BusUndo<T> for PowerUndoOp<T> {
+    fn remap_pin_bus(&mut self, old: &T::PinBusId, new: &T::PinBusId) {
+        if let PowerUndoOp::BusOp(op) = self {
+            op.remap_pin_bus(old, new)
+        }
+    }
 
-    fn base(&self) -> &Self::D {
-        &self.chip
+    fn remap_net_bus(&mut self, old: &T::NetBusId, new: &T::NetBusId) {
+        if let PowerUndoOp::BusOp(op) = self {
+            op.remap_net_bus(old, new)
+        }
     }
 }
 
-impl<'a, H, U> MutDecorator for Undo<'a, H, U> {
-    fn mut_base(&mut self) -> &mut Self::D {
-        &mut self.chip
+impl<T: BusEdit + PowerEdit + 'static> PowerUndo<T> for PowerUndoOp<T> {
+    fn remap_power_domain(&mut self, old: &T::PowerDomainId, new: &T::PowerDomainId) {
+        match self {
+            PowerUndoOp::CreatePowerDomain(domain)
+            | PowerUndoOp::RemovePowerDomain { domain, .. }
+            | PowerUndoOp::SetPowerDomainVoltage(domain, _)
+            | PowerUndoOp::SetPowerDomainAlwaysOn(domain, _) => remap(domain, old, new),
+            PowerUndoOp::SetPowerDomainOfCell(_, domain)
+            | PowerUndoOp::SetPowerDomainOfCellInstance(_, domain) => {
+                remap_option(domain, old, new)
+            }
+            _ => {}
+        }
     }
 }
 
-// Inherit everything from HierarchyBase.
-impl<'a, H: HierarchyBase + 'static, U> HierarchyBaseDecorator for Undo<'a, H, U> {
-    type NameType = H::NameType;
-    type CellId = H::CellId;
-    type CellInstId = H::CellInstId;
-}
+impl<T: LayoutEdit + 'static> UndoOp<T> for LayoutUndoOp<T> {
+    fn revert(self, undo: &mut Undo<'_, T, Self>) {
+        undo.undo_layout_op(self)
+    }
 
-// Inherit everything from LayoutBase.
-impl<'a, L: LayoutBase + 'static, U> LayoutBaseDecorator for Undo<'a, L, U> {}
+    fn remove_cell(undo: &mut Undo<'_, T, Self>, cell: &T::CellId) {
+        undo.begin_group();
+        undo.remove_instances_of_cell(cell);
+        undo.remove_shapes_of_cell(cell);
+        undo.remove_cell_bare(cell);
+        undo.end_group();
+    }
+
+    fn remove_cell_instance(undo: &mut Undo<'_, T, Self>, inst: &T::CellInstId) {
+        undo.begin_group();
+        undo.reset_transform(inst);
+        undo.remove_cell_instance_bare(inst);
+        undo.end_group();
+    }
+
+    fn remap_cell(&mut self, old: &T::CellId, new: &T::CellId) {
+        match self {
+            LayoutUndoOp::HierarchyOp(op) => op.remap_cell(old, new),
+            LayoutUndoOp::RemoveShape { parent_cell, .. } => remap(parent_cell, old, new),
+            _ => {}
+        }
+    }
+
+    fn remap_cell_instance(&mut self, old: &T::CellInstId, new: &T::CellInstId) {
+        match self {
+            LayoutUndoOp::HierarchyOp(op) => op.remap_cell_instance(old, new),
+            LayoutUndoOp::SetTransform(inst, _)
+            | LayoutUndoOp::SetCellInstanceArray(inst, _)
+            | LayoutUndoOp::SetPlacementStatus(inst, _) => remap(inst, old, new),
+            _ => {}
+        }
+    }
+}
+
+impl<T: LayoutEdit + 'static> LayoutUndo<T> for LayoutUndoOp<T> {
+    fn remove_shape(
+        undo: &mut Undo<'_, T, Self>,
+        shape: &T::ShapeId,
+    ) -> Option<Geometry<T::Coord>> {
+        undo.remove_shape_bare(shape)
+    }
+
+    fn remap_shape(&mut self, old: &T::ShapeId, new: &T::ShapeId) {
+        match self {
+            LayoutUndoOp::InsertShape(shape)
+            | LayoutUndoOp::RemoveShape { shape, .. }
+            | LayoutUndoOp::ReplaceShape(shape, _)
+            | LayoutUndoOp::SetShapeProperty { shape, .. } => remap(shape, old, new),
+            _ => {}
+        }
+    }
+}
+
+impl<T: L2NEdit + 'static> UndoOp<T> for L2NUndoOp<T> {
+    fn revert(self, undo: &mut Undo<'_, T, Self>) {
+        undo.undo_l2n_op(self)
+    }
+
+    fn remove_cell(undo: &mut Undo<'_, T, Self>, cell: &T::CellId) {
+        undo.begin_group();
+        undo.remove_instances_of_cell(cell);
+        undo.remove_shapes_of_cell(cell);
+        undo.remove_nets_and_pins_of_cell(cell);
+        undo.remove_cell_bare(cell);
+        undo.end_group();
+    }
+
+    fn remove_cell_instance(undo: &mut Undo<'_, T, Self>, inst: &T::CellInstId) {
+        undo.begin_group();
+        undo.disconnect_cell_instance(inst);
+        undo.reset_transform(inst);
+        undo.record_cell_instance_pins(inst);
+        undo.remove_cell_instance_bare(inst);
+        undo.end_group();
+    }
+
+    fn remap_cell(&mut self, old: &T::CellId, new: &T::CellId) {
+        match self {
+            L2NUndoOp::HierarchyOp(op) => op.remap_cell(old, new),
+            L2NUndoOp::NetlistOp(op) => op.remap_cell(old, new),
+            L2NUndoOp::LayoutOp(op) => op.remap_cell(old, new),
+            _ => {}
+        }
+    }
+
+    fn remap_cell_instance(&mut self, old: &T::CellInstId, new: &T::CellInstId) {
+        match self {
+            L2NUndoOp::HierarchyOp(op) => op.remap_cell_instance(old, new),
+            L2NUndoOp::NetlistOp(op) => op.remap_cell_instance(old, new),
+            L2NUndoOp::LayoutOp(op) => op.remap_cell_instance(old, new),
+            _ => {}
+        }
+    }
+}
+
+impl<T: L2NEdit + 'static> NetlistUndo<T> for L2NUndoOp<T> {
+    fn remove_pin(undo: &mut Undo<'_, T, Self>, pin: &T::PinId) {
+        undo.begin_group();
+        undo.unlink_shapes_of_pin(pin);
+        undo.disconnect_pin_and_instances(pin);
+        undo.remove_pin_bare(pin);
+        undo.end_group();
+    }
+
+    fn remove_net(undo: &mut Undo<'_, T, Self>, net: &T::NetId) {
+        undo.begin_group();
+        undo.unlink_shapes_of_net(net);
+        undo.disconnect_net(net);
+        undo.remove_net_bare(net);
+        undo.end_group();
+    }
+
+    fn remap_pin(&mut self, old: &T::PinId, new: &T::PinId) {
+        match self {
+            L2NUndoOp::NetlistOp(op) => op.remap_pin(old, new),
+            L2NUndoOp::SetPinOfShape { previous_pin, .. } => remap_option(previous_pin, old, new),
+            _ => {}
+        }
+    }
+
+    fn remap_pin_instance(&mut self, old: &T::PinInstId, new: &T::PinInstId) {
+        if let L2NUndoOp::NetlistOp(op) = self {
+            op.remap_pin_instance(old, new)
+        }
+    }
+
+    fn remap_net(&mut self, old: &T::NetId, new: &T::NetId) {
+        match self {
+            L2NUndoOp::NetlistOp(op) => op.remap_net(old, new),
+            L2NUndoOp::SetNetOfShape { previous_net, .. } => remap_option(previous_net, old, new),
+            _ => {}
+        }
+    }
+}
+
+impl<T: L2NEdit + 'static> LayoutUndo<T> for L2NUndoOp<T> {
+    fn remove_shape(
+        undo: &mut Undo<'_, T, Self>,
+        shape: &T::ShapeId,
+    ) -> Option<Geometry<T::Coord>> {
+        undo.begin_group();
+        undo.unlink_shape(shape);
+        let geometry = undo.remove_shape_bare(shape);
+        undo.end_group();
+        geometry
+    }
+
+    fn remap_shape(&mut self, old: &T::ShapeId, new: &T::ShapeId) {
+        match self {
+            L2NUndoOp::LayoutOp(op) => op.remap_shape(old, new),
+            L2NUndoOp::SetNetOfShape { shape_id, .. }
+            | L2NUndoOp::SetPinOfShape { shape_id, .. } => remap(shape_id, old, new),
+            _ => {}
+        }
+    }
+}
+
+/// A group of operations which is undone and redone as a whole.
+struct Transaction<U> {
+    /// Unique number of the transaction. Used to validate checkpoints.
+    id: u64,
+    /// Name given with `begin_transaction()`.
+    name: Option<String>,
+    /// Operations in the order of execution.
+    ops: Vec<U>,
+}
+
+/// A transaction started with `begin_transaction()` which is not committed yet.
+struct Savepoint {
+    /// Name of the transaction.
+    name: String,
+    /// Number of recorded operations when the transaction was started.
+    start: usize,
+}
+
+/// Marks a state in the undo history.
+/// The state can be restored with [`Undo::undo_to()`] as long as the transactions after
+/// the checkpoint are not discarded.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Checkpoint {
+    /// Number of undoable transactions at the checkpoint.
+    num_transactions: usize,
+    /// ID of the latest transaction at the checkpoint.
+    last_transaction: Option<u64>,
+}
+
+/// Wrapper around netlist, layout and L2N structures that allows undoing of operations.
+///
+/// # Types
+/// * `T`: Underlying data structure.
+/// * `U`: Undo-operation.
+pub struct Undo<'a, T, U> {
+    /// Underlying data structure.
+    chip: &'a mut T,
+    /// A list of performed transactions. Each transaction consists of one or more operations.
+    /// To undo operations, this list has to be worked through from the end.
+    undo_stack: Vec<Transaction<U>>,
+    /// A list of undone transactions. They are stored as the operations which revert the undoing.
+    redo_stack: Vec<Transaction<U>>,
+    /// Operations of the transaction which is currently recorded.
+    recording: Vec<U>,
+    /// Remaining operations of the transaction which is currently undone or redone.
+    replaying: Vec<U>,
+    /// Nesting depth of compound operations. The recorded transaction is closed
+    /// when this drops to zero.
+    depth: usize,
+    /// Transactions opened with `begin_transaction()`. Inner transactions act as savepoints.
+    savepoints: Vec<Savepoint>,
+    /// Counter for generating transaction IDs.
+    transaction_id_counter: u64,
+}
+
+impl<'a, T, U> Undo<'a, T, U> {
+    /// Create a wrapper with an empty history.
+    fn with_chip(chip: &'a mut T) -> Self {
+        Self {
+            chip,
+            undo_stack: vec![],
+            redo_stack: vec![],
+            recording: vec![],
+            replaying: vec![],
+            depth: 0,
+            savepoints: vec![],
+            transaction_id_counter: 0,
+        }
+    }
+
+    /// Return the number of undoable transactions.
+    pub fn num_transactions(&self) -> usize {
+        self.undo_stack.len()
+    }
+
+    /// Return the number of transactions which can be redone.
+    pub fn num_redo_transactions(&self) -> usize {
+        self.redo_stack.len()
+    }
+
+    /// Get the name of the transaction which will be undone by the next call of `undo()`.
+    /// Returns `None` if there's no transaction or if the transaction has no name.
+    pub fn undo_name(&self) -> Option<&str> {
+        self.undo_stack.last().and_then(|t| t.name.as_deref())
+    }
+
+    /// Get the name of the transaction which will be redone by the next call of `redo()`.
+    /// Returns `None` if there's no transaction or if the transaction has no name.
+    pub fn redo_name(&self) -> Option<&str> {
+        self.redo_stack.last().and_then(|t| t.name.as_deref())
+    }
+
+    /// Clear the undo and redo buffers and make changes permanent.
+    /// Existing checkpoints become invalid.
+    pub fn flush(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+    }
+
+    /// Start a named transaction. All modifications until the matching [`Undo::commit()`]
+    /// are undone and redone as a whole.
+    ///
+    /// Transactions can be nested. A nested transaction acts as a savepoint: It can be rolled
+    /// back on its own, and when committed its modifications become part of the enclosing transaction.
+    /// Only the name of the outermost transaction is kept.
+    pub fn begin_transaction<S: Into<String>>(&mut self, name: S) {
+        self.savepoints.push(Savepoint {
+            name: name.into(),
+            start: self.recording.len(),
+        });
+        self.depth += 1;
+    }
+
+    /// Close the innermost open transaction.
+    /// If this is the outermost transaction, its modifications are put on the undo stack.
+    ///
+    /// # Panics
+    /// Panics if there's no open transaction.
+    pub fn commit(&mut self) {
+        let savepoint = self
+            .savepoints
+            .pop()
+            .expect("commit() without open transaction.");
+        self.depth -= 1;
+        if self.depth == 0 {
+            self.finish_transaction(Some(savepoint.name));
+        }
+    }
+
+    /// Get the number of open transactions.
+    pub fn num_open_transactions(&self) -> usize {
+        self.savepoints.len()
+    }
+
+    /// Get a checkpoint of the current state of the undo history.
+    /// Use [`Undo::undo_to()`] to go back to this state.
+    ///
+    /// Modifications of an open transaction are not part of the history yet.
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            num_transactions: self.undo_stack.len(),
+            last_transaction: self.undo_stack.last().map(|t| t.id),
+        }
+    }
+
+    /// Check if the state of the checkpoint can be restored with [`Undo::undo_to()`].
+    /// This is not the case anymore if the transactions after the checkpoint have been undone
+    /// and replaced by new transactions or if the history has been flushed.
+    pub fn can_undo_to(&self, checkpoint: &Checkpoint) -> bool {
+        let n = checkpoint.num_transactions;
+        n <= self.undo_stack.len()
+            && n.checked_sub(1).map(|i| self.undo_stack[i].id) == checkpoint.last_transaction
+    }
+
+    /// Record an operation.
+    fn push_op<V: Into<U>>(&mut self, op: V) {
+        self.recording.push(op.into());
+        if self.depth == 0 {
+            self.finish_transaction(None);
+        }
+    }
+
+    /// Start a compound operation. All operations recorded until the matching
+    /// `end_group()` form one transaction.
+    fn begin_group(&mut self) {
+        self.depth += 1;
+    }
+
+    /// End a compound operation.
+    fn end_group(&mut self) {
+        debug_assert!(self.depth > 0, "end_group() without begin_group().");
+        self.depth -= 1;
+        if self.depth == 0 {
+            self.finish_transaction(None);
+        }
+    }
+
+    /// Move the recorded operations to the undo stack.
+    /// A new transaction invalidates the redo stack.
+    fn finish_transaction(&mut self, name: Option<String>) {
+        if !self.recording.is_empty() {
+            let ops = std::mem::take(&mut self.recording);
+            self.transaction_id_counter += 1;
+            self.undo_stack.push(Transaction {
+                id: self.transaction_id_counter,
+                name,
+                ops,
+            });
+            self.redo_stack.clear();
+        }
+    }
+
+    /// Apply `f` to all operations in the history.
+    /// This is used to update IDs of restored objects.
+    fn remap_ids<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&mut U),
+    {
+        self.undo_stack
+            .iter_mut()
+            .chain(self.redo_stack.iter_mut())
+            .flat_map(|t| t.ops.iter_mut())
+            .chain(self.recording.iter_mut())
+            .chain(self.replaying.iter_mut())
+            .for_each(|op| f(op));
+    }
+}
+
+impl<'a, T, U> Deref for Undo<'a, T, U> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.chip
+    }
+}
+
+impl<'a, T: HierarchyEdit + 'static, U: UndoOp<T>> Undo<'a, T, U> {
+    /// Undo the latest transaction.
+    /// Does nothing if there's no transaction left to be undone.
+    ///
+    /// # Panics
+    /// Panics if a transaction is open.
+    pub fn undo(&mut self) {
+        self.assert_no_open_transaction();
+        if let Some(mut t) = self.undo_stack.pop() {
+            t.ops = self.revert_ops(t.ops);
+            self.redo_stack.push(t);
+        }
+    }
+
+    /// Redo the latest undone transaction.
+    /// Does nothing if there's no transaction left to be redone.
+    ///
+    /// # Panics
+    /// Panics if a transaction is open.
+    pub fn redo(&mut self) {
+        self.assert_no_open_transaction();
+        if let Some(mut t) = self.redo_stack.pop() {
+            t.ops = self.revert_ops(t.ops);
+            self.undo_stack.push(t);
+        }
+    }
+
+    /// Undoes all transactions.
+    pub fn undo_all(&mut self) {
+        while !self.undo_stack.is_empty() {
+            self.undo();
+        }
+    }
+
+    /// Undo transactions until the state of the checkpoint is reached.
+    /// The undone transactions can be redone.
+    ///
+    /// # Panics
+    /// Panics if a transaction is open or if the checkpoint cannot be reached anymore.
+    /// See [`Undo::can_undo_to()`].
+    pub fn undo_to(&mut self, checkpoint: &Checkpoint) {
+        assert!(
+            self.can_undo_to(checkpoint),
+            "Checkpoint is not part of the undo history anymore."
+        );
+        while self.undo_stack.len() > checkpoint.num_transactions {
+            self.undo();
+        }
+    }
+
+    /// Revert all modifications since the start of the innermost open transaction and close it.
+    /// The reverted modifications cannot be redone.
+    ///
+    /// # Panics
+    /// Panics if there's no open transaction.
+    pub fn rollback(&mut self) {
+        let savepoint = self
+            .savepoints
+            .pop()
+            .expect("rollback() without open transaction.");
+        let ops = self.recording.split_off(savepoint.start);
+        // Discard the inverse operations.
+        let _ = self.revert_ops(ops);
+        self.depth -= 1;
+    }
+
+    /// Panic if a transaction is open.
+    fn assert_no_open_transaction(&self) {
+        assert!(
+            self.savepoints.is_empty(),
+            "Transaction '{}' is not committed.",
+            self.savepoints
+                .last()
+                .map(|s| s.name.as_str())
+                .unwrap_or("")
+        );
+    }
+
+    /// Revert the operations in reverse order.
+    /// Returns the recorded operations which revert the reversion.
+    fn revert_ops(&mut self, ops: Vec<U>) -> Vec<U> {
+        debug_assert!(self.replaying.is_empty());
+        let start = self.recording.len();
+        // Don't close transactions while reverting.
+        self.depth += 1;
+        self.replaying = ops;
+        while let Some(op) = self.replaying.pop() {
+            op.revert(self);
+        }
+        self.depth -= 1;
+        self.recording.split_off(start)
+    }
+
+    /// Remove all instances inside the cell and all instances of the cell.
+    fn remove_instances_of_cell(&mut self, cell: &T::CellId) {
+        for inst in self.chip.each_cell_instance_vec(cell) {
+            U::remove_cell_instance(self, &inst);
+        }
+        for inst in self.chip.each_cell_reference_vec(cell) {
+            U::remove_cell_instance(self, &inst);
+        }
+    }
+
+    /// Record name and properties of the cell and remove it.
+    fn remove_cell_bare(&mut self, cell: &T::CellId) {
+        let name = self.chip.cell_name(cell);
+        let mut properties = vec![];
+        self.chip
+            .for_each_cell_property(cell, |k, v| properties.push((k.clone(), v.clone())));
+        self.push_op(HierarchyUndoOp::RemoveCell {
+            cell: cell.clone(),
+            name,
+            properties,
+        });
+        self.chip.remove_cell(cell);
+    }
+
+    /// Record the definition of the cell instance and remove it.
+    fn remove_cell_instance_bare(&mut self, inst: &T::CellInstId) {
+        let parent_cell = self.chip.parent_cell(inst);
+        let template_cell = self.chip.template_cell(inst);
+        let name = self.chip.cell_instance_name(inst);
+        let mut properties = vec![];
+        self.chip
+            .for_each_cell_instance_property(inst, |k, v| properties.push((k.clone(), v.clone())));
+        self.push_op(HierarchyUndoOp::RemoveCellInstance {
+            inst: inst.clone(),
+            parent_cell,
+            template_cell,
+            name,
+            properties,
+        });
+        self.chip.remove_cell_instance(inst);
+    }
+
+    /// Undo a hierarchy operation.
+    fn undo_hierarchy_op(&mut self, op: HierarchyUndoOp<T>) {
+        match op {
+            HierarchyUndoOp::CreateCell(c) => self.remove_cell(&c),
+            HierarchyUndoOp::RemoveCell {
+                cell,
+                name,
+                properties,
+            } => {
+                let new_cell = self.create_cell(name);
+                for (key, value) in properties {
+                    self.chip.set_cell_property(&new_cell, key, value);
+                }
+                self.remap_ids(|op| op.remap_cell(&cell, &new_cell));
+            }
+            HierarchyUndoOp::CreateCellInstance(c) => self.remove_cell_instance(&c),
+            HierarchyUndoOp::RemoveCellInstance {
+                inst,
+                parent_cell,
+                template_cell,
+                name,
+                properties,
+            } => {
+                let new_inst = self.create_cell_instance(&parent_cell, &template_cell, name);
+                for (key, value) in properties {
+                    self.chip.set_cell_instance_property(&new_inst, key, value);
+                }
+                self.remap_ids(|op| op.remap_cell_instance(&inst, &new_inst));
+            }
+            HierarchyUndoOp::RenameCell {
+                cell,
+                previous_name,
+            } => self.rename_cell(&cell, previous_name),
+            HierarchyUndoOp::RenameCellInst {
+                inst,
+                previous_name,
+            } => self.rename_cell_instance(&inst, previous_name),
+            HierarchyUndoOp::SetChipProperty { key, previous } => match previous {
+                Some(value) => self.set_chip_property(key, value),
+                None => self.remove_chip_property(&key),
+            },
+            HierarchyUndoOp::SetCellProperty {
+                cell,
+                key,
+                previous,
+            } => match previous {
+                Some(value) => self.set_cell_property(&cell, key, value),
+                None => self.remove_cell_property(&cell, &key),
+            },
+            HierarchyUndoOp::SetCellInstanceProperty {
+                inst,
+                key,
+                previous,
+            } => match previous {
+                Some(value) => self.set_cell_instance_property(&inst, key, value),
+                None => self.remove_cell_instance_property(&inst, &key),
+            },
+        }
+    }
+}
+
+impl<'a, T: NetlistEdit + 'static, U: NetlistUndo<T>> Undo<'a, T, U> {
+    /// Disconnect all pin instances of the cell instance.
+    fn disconnect_cell_instance(&mut self, inst: &T::CellInstId) {
+        for pin_inst in self.chip.each_pin_instance_vec(inst) {
+            if self.chip.net_of_pin_instance(&pin_inst).is_some() {
+                self.connect_pin_instance(&pin_inst, None);
+            }
+        }
+    }
+
+    /// Remember the pin instances of a cell instance which is about to be removed.
+    fn record_cell_instance_pins(&mut self, inst: &T::CellInstId) {
+        let pins = self
+            .chip
+            .each_pin_instance_vec(inst)
+            .into_iter()
+            .map(|pin_inst| (self.chip.template_pin(&pin_inst), pin_inst))
+            .collect();
+        self.push_op(NetlistUndoOp::CellInstancePins {
+            inst: inst.clone(),
+            pins,
+        });
+    }
+
+    /// Remove all nets and pins of a cell which is about to be removed.
+    fn remove_nets_and_pins_of_cell(&mut self, cell: &T::CellId) {
+        let net_zero = self.chip.net_zero(cell);
+        let net_one = self.chip.net_one(cell);
+        for net in self.chip.each_internal_net_vec(cell) {
+            if net != net_zero && net != net_one {
+                U::remove_net(self, &net);
+            }
+        }
+        // Remove pins in reverse order such that they are restored in the original order.
+        for pin in self.chip.each_pin_vec(cell).into_iter().rev() {
+            U::remove_pin(self, &pin);
+        }
+        self.push_op(NetlistUndoOp::CellConstantNets {
+            cell: cell.clone(),
+            net_zero,
+            net_one,
+        });
+    }
+
+    /// Disconnect the pin and all its instances from their nets.
+    fn disconnect_pin_and_instances(&mut self, pin: &T::PinId) {
+        let cell = self.chip.parent_cell_of_pin(pin);
+        for inst in self.chip.each_cell_reference_vec(&cell) {
+            let pin_inst = self.chip.pin_instance(&inst, pin);
+            if self.chip.net_of_pin_instance(&pin_inst).is_some() {
+                self.connect_pin_instance(&pin_inst, None);
+            }
+        }
+        if self.chip.net_of_pin(pin).is_some() {
+            self.connect_pin(pin, None);
+        }
+    }
+
+    /// Record the definition of the pin and remove it.
+    fn remove_pin_bare(&mut self, pin: &T::PinId) {
+        let cell = self.chip.parent_cell_of_pin(pin);
+        let pin_instances = self
+            .chip
+            .each_cell_reference_vec(&cell)
+            .into_iter()
+            .map(|inst| {
+                let pin_inst = self.chip.pin_instance(&inst, pin);
+                (inst, pin_inst)
+            })
+            .collect();
+        self.push_op(NetlistUndoOp::RemovePin {
+            pin: pin.clone(),
+            name: self.chip.pin_name(pin),
+            direction: self.chip.pin_direction(pin),
+            cell,
+            pin_instances,
+        });
+        self.chip.remove_pin(pin);
+    }
+
+    /// Disconnect all pins and pin instances from the net.
+    fn disconnect_net(&mut self, net: &T::NetId) {
+        for pin in self.chip.each_pin_of_net_vec(net) {
+            self.connect_pin(&pin, None);
+        }
+        for pin_inst in self.chip.each_pin_instance_of_net_vec(net) {
+            self.connect_pin_instance(&pin_inst, None);
+        }
+    }
+
+    /// Record parent and name of the net and remove it.
+    fn remove_net_bare(&mut self, net: &T::NetId) {
+        self.push_op(NetlistUndoOp::RemoveNet {
+            net: net.clone(),
+            parent_cell: self.chip.parent_cell_of_net(net),
+            name: self.chip.net_name(net),
+        });
+        self.chip.remove_net(net);
+    }
+
+    /// Undo a netlist operation.
+    fn undo_netlist_op(&mut self, op: NetlistUndoOp<T>) {
+        match op {
+            NetlistUndoOp::HierarchyOp(op) => self.undo_hierarchy_op(op),
+            NetlistUndoOp::CreatePin(p) => self.remove_pin(&p),
+            NetlistUndoOp::RemovePin {
+                pin,
+                cell,
+                name,
+                direction,
+                pin_instances,
+            } => {
+                let new_pin = self.create_pin(&cell, name, direction);
+                self.remap_ids(|op| op.remap_pin(&pin, &new_pin));
+                for (inst, pin_inst) in pin_instances {
+                    let new_pin_inst = self.chip.pin_instance(&inst, &new_pin);
+                    self.remap_ids(|op| op.remap_pin_instance(&pin_inst, &new_pin_inst));
+                }
+            }
+            NetlistUndoOp::RenamePin(p, n) => {
+                self.rename_pin(&p, n);
+            }
+            NetlistUndoOp::CreateNet(n) => self.remove_net(&n),
+            NetlistUndoOp::RemoveNet {
+                net,
+                parent_cell,
+                name,
+            } => {
+                let new_net = self.create_net(&parent_cell, name);
+                self.remap_ids(|op| op.remap_net(&net, &new_net));
+            }
+            NetlistUndoOp::ConnectPin(p, n) => {
+                self.connect_pin(&p, n);
+            }
+            NetlistUndoOp::ConnectPinInstance(p, n) => {
+                self.connect_pin_instance(&p, n);
+            }
+            NetlistUndoOp::RenameNet(net, name) => {
+                self.rename_net(&net, name);
+            }
+            NetlistUndoOp::CellInstancePins { inst, pins } => {
+                for (pin, pin_inst) in pins {
+                    let new_pin_inst = self.chip.pin_instance(&inst, &pin);
+                    if new_pin_inst != pin_inst {
+                        self.remap_ids(|op| op.remap_pin_instance(&pin_inst, &new_pin_inst));
+                    }
+                }
+            }
+            NetlistUndoOp::CellConstantNets {
+                cell,
+                net_zero,
+                net_one,
+            } => {
+                let new_net_zero = self.chip.net_zero(&cell);
+                let new_net_one = self.chip.net_one(&cell);
+                if new_net_zero != net_zero {
+                    self.remap_ids(|op| op.remap_net(&net_zero, &new_net_zero));
+                }
+                if new_net_one != net_one {
+                    self.remap_ids(|op| op.remap_net(&net_one, &new_net_one));
+                }
+            }
+        }
+    }
+}
+
+impl<'a, T: BusEdit + 'static, U: BusUndo<T>> Undo<'a, T, U> {
+    /// Remove the bus which contains the pin, if any.
+    fn remove_bus_of_pin(&mut self, pin: &T::PinId) {
+        if let Some(bus) = self.chip.bus_of_pin(pin) {
+            self.remove_pin_bus(&bus);
+        }
+    }
+
+    /// Remove the bus which contains the net, if any.
+    fn remove_bus_of_net(&mut self, net: &T::NetId) {
+        if let Some(bus) = self.chip.bus_of_net(net) {
+            self.remove_net_bus(&bus);
+        }
+    }
+
+    /// Undo a bus operation.
+    fn undo_bus_op(&mut self, op: BusUndoOp<T>) {
+        match op {
+            BusUndoOp::NetlistOp(op) => self.undo_netlist_op(op),
+            BusUndoOp::CreatePinBus(bus) => self.remove_pin_bus(&bus),
+            BusUndoOp::RemovePinBus {
+                bus,
+                name,
+                pins,
+                bit_order,
+            } => {
+                let new_bus = self.create_pin_bus_from_pins(name, pins, bit_order);
+                self.remap_ids(|op| op.remap_pin_bus(&bus, &new_bus));
+            }
+            BusUndoOp::CreateNetBus(bus) => self.remove_net_bus(&bus),
+            BusUndoOp::RemoveNetBus {
+                bus,
+                name,
+                nets,
+                bit_order,
+            } => {
+                let new_bus = self.create_net_bus_from_nets(name, nets, bit_order);
+                self.remap_ids(|op| op.remap_net_bus(&bus, &new_bus));
+            }
+        }
+    }
+}
+
+impl<'a, T: BusEdit + PowerEdit + 'static, U: PowerUndo<T>> Undo<'a, T, U> {
+    /// Remove the assignment of the cell to a power domain, if any.
+    fn clear_power_domain_of_cell(&mut self, cell: &T::CellId) {
+        if self.chip.power_domain_of_cell(cell).is_some() {
+            self.set_power_domain_of_cell(cell, None);
+        }
+    }
+
+    /// Remove the explicit assignment of the cell instance to a power domain, if any.
+    fn clear_power_domain_of_cell_instance(&mut self, inst: &T::CellInstId) {
+        if self
+            .chip
+            .assigned_power_domain_of_cell_instance(inst)
+            .is_some()
+        {
+            self.set_power_domain_of_cell_instance(inst, None);
+        }
+    }
 
-impl<'a, T: HierarchyEdit + 'static, U: From<HierarchyUndoOp<T>>> HierarchyEdit for Undo<'a, T, U> {
+    /// Remove all power domains which live in the cell.
+    fn remove_power_domains_of_cell(&mut self, cell: &T::CellId) {
+        for domain in self.chip.each_power_domain_vec() {
+            if &self.chip.parent_cell_of_power_domain(&domain) == cell {
+                self.remove_power_domain(&domain);
+            }
+        }
+    }
+
+    /// Remove all power domains which are supplied by the net.
+    fn remove_power_domains_of_net(&mut self, net: &T::NetId) {
+        for domain in self.chip.each_power_domain_of_supply_net_vec(net) {
+            self.remove_power_domain(&domain);
+        }
+    }
+
+    /// Undo a power domain operation.
+    fn undo_power_op(&mut self, op: PowerUndoOp<T>) {
+        match op {
+            PowerUndoOp::BusOp(op) => self.undo_bus_op(op),
+            PowerUndoOp::CreatePowerDomain(domain) => self.remove_power_domain(&domain),
+            PowerUndoOp::RemovePowerDomain {
+                domain,
+                name,
+                supply_net,
+                ground_net,
+                voltage,
+                always_on,
+            } => {
+                let new_domain =
+                    self.create_power_domain(name, supply_net, ground_net, voltage, always_on);
+                self.remap_ids(|op| op.remap_power_domain(&domain, &new_domain));
+            }
+            PowerUndoOp::SetPowerDomainVoltage(domain, voltage) => {
+                self.set_power_domain_voltage(&domain, voltage);
+            }
+            PowerUndoOp::SetPowerDomainAlwaysOn(domain, always_on) => {
+                self.set_power_domain_always_on(&domain, always_on);
+            }
+            PowerUndoOp::SetPowerDomainOfCell(cell, domain) => {
+                self.set_power_domain_of_cell(&cell, domain);
+            }
+            PowerUndoOp::SetPowerDomainOfCellInstance(inst, domain) => {
+                self.set_power_domain_of_cell_instance(&inst, domain);
+            }
+        }
+    }
+}
+
+impl<'a, T: LayoutEdit + 'static, U: LayoutUndo<T>> Undo<'a, T, U> {
+    /// Remove all shapes of a cell which is about to be removed.
+    fn remove_shapes_of_cell(&mut self, cell: &T::CellId) {
+        let layers: Vec<_> = self.chip.each_layer().collect();
+        for layer in layers {
+            let shapes: Vec<_> = self.chip.each_shape_id(cell, &layer).collect();
+            for shape in shapes {
+                U::remove_shape(self, &shape);
+            }
+        }
+    }
+
+    /// Move the cell instance to its default location and turn arrays into single placements.
+    /// This allows to restore the location when undoing the removal of the instance.
+    fn reset_transform(&mut self, inst: &T::CellInstId) {
+        // Unlock the instance first, otherwise an enforcing layout would reject the move.
+        if self.chip.placement_status(inst) != PlacementStatus::default() {
+            self.set_placement_status(inst, PlacementStatus::default());
+        }
+        self.set_transform(inst, SimpleTransform::identity());
+        if self.chip.cell_instance_array(inst).is_some() {
+            self.set_cell_instance_array(inst, None);
+        }
+    }
+
+    /// Record geometry and properties of the shape and remove it.
+    fn remove_shape_bare(&mut self, shape_id: &T::ShapeId) -> Option<Geometry<T::Coord>> {
+        let (parent_cell, layer) = self.chip.parent_of_shape(shape_id);
+        let mut properties = vec![];
+        self.chip
+            .for_each_shape_property(shape_id, |k, v| properties.push((k.clone(), v.clone())));
+        let geometry = self.chip.remove_shape(shape_id);
+        if let Some(geometry) = &geometry {
+            self.push_op(LayoutUndoOp::RemoveShape {
+                shape: shape_id.clone(),
+                parent_cell,
+                layer,
+                geometry: geometry.clone(),
+                properties,
+            });
+        }
+        geometry
+    }
+
+    /// Undo a layout operation
+    fn undo_layout_op(&mut self, op: LayoutUndoOp<T>) {
+        match op {
+            LayoutUndoOp::HierarchyOp(op) => self.undo_hierarchy_op(op),
+            LayoutUndoOp::SetDbu(dbu) => self.set_dbu(dbu),
+            LayoutUndoOp::CreateLayer(_id) => {
+                // TODO
+                log::error!("Creating a layer cannot be undone.");
+            }
+            LayoutUndoOp::SetLayerName(id, old_name) => {
+                self.set_layer_name(&id, old_name);
+            }
+            LayoutUndoOp::InsertShape(id) => {
+                self.remove_shape(&id);
+            }
+            LayoutUndoOp::RemoveShape {
+                shape,
+                parent_cell,
+                layer,
+                geometry,
+                properties,
+            } => {
+                let new_shape = self.insert_shape(&parent_cell, &layer, geometry);
+                for (key, value) in properties {
+                    self.chip.set_shape_property(&new_shape, key, value);
+                }
+                self.remap_ids(|op| op.remap_shape(&shape, &new_shape));
+            }
+            LayoutUndoOp::ReplaceShape(id, geometry) => {
+                self.replace_shape(&id, geometry);
+            }
+            LayoutUndoOp::SetTransform(inst, old_tf) => self.set_transform(&inst, old_tf),
+            LayoutUndoOp::SetCellInstanceArray(inst, old_array) => {
+                self.set_cell_instance_array(&inst, old_array);
+            }
+            LayoutUndoOp::SetPlacementStatus(inst, old_status) => {
+                self.set_placement_status(&inst, old_status);
+            }
+            LayoutUndoOp::SetShapeProperty {
+                shape,
+                key,
+                previous,
+            } => match previous {
+                Some(value) => self.set_shape_property(&shape, key, value),
+                None => self.remove_shape_property(&shape, &key),
+            },
+        }
+    }
+}
+
+impl<'a, T, U> Undo<'a, T, U>
+where
+    T: L2NEdit + 'static,
+    U: NetlistUndo<T> + LayoutUndo<T> + From<L2NUndoOp<T>>,
+{
+    /// Remove the links from the shape to its net and pin.
+    fn unlink_shape(&mut self, shape: &T::ShapeId) {
+        if self.chip.get_net_of_shape(shape).is_some() {
+            self.set_net_of_shape(shape, None);
+        }
+        if self.chip.get_pin_of_shape(shape).is_some() {
+            self.set_pin_of_shape(shape, None);
+        }
+    }
+
+    /// Remove the links from all shapes of the net.
+    fn unlink_shapes_of_net(&mut self, net: &T::NetId) {
+        let shapes: Vec<_> = self.chip.shapes_of_net(net).collect();
+        for shape in shapes {
+            self.set_net_of_shape(&shape, None);
+        }
+    }
+
+    /// Remove the links from all shapes of the pin.
+    fn unlink_shapes_of_pin(&mut self, pin: &T::PinId) {
+        let shapes: Vec<_> = self.chip.shapes_of_pin(pin).collect();
+        for shape in shapes {
+            self.set_pin_of_shape(&shape, None);
+        }
+    }
+
+    /// Undo an operation on fused netlist and layout.
+    fn undo_l2n_op(&mut self, op: L2NUndoOp<T>) {
+        match op {
+            // Redirect to base traits.
+            L2NUndoOp::HierarchyOp(op) => self.undo_hierarchy_op(op),
+            L2NUndoOp::NetlistOp(op) => self.undo_netlist_op(op),
+            L2NUndoOp::LayoutOp(op) => self.undo_layout_op(op),
+            // L2N specific operations
+            L2NUndoOp::SetNetOfShape {
+                shape_id,
+                previous_net,
+            } => {
+                self.set_net_of_shape(&shape_id, previous_net);
+            }
+            L2NUndoOp::SetPinOfShape {
+                shape_id,
+                previous_pin,
+            } => {
+                self.set_pin_of_shape(&shape_id, previous_pin);
+            }
+        }
+    }
+}
+
+impl<'a, T: L2NBase + 'static, U> L2NBase for Undo<'a, T, U> {
+    fn shapes_of_net(&self, net_id: &Self::NetId) -> Box<dyn Iterator<Item = Self::ShapeId> + '_> {
+        self.chip.shapes_of_net(net_id)
+    }
+
+    fn shapes_of_pin(&self, pin_id: &Self::PinId) -> Box<dyn Iterator<Item = Self::ShapeId> + '_> {
+        self.chip.shapes_of_pin(pin_id)
+    }
+
+    fn get_net_of_shape(&self, shape_id: &Self::ShapeId) -> Option<Self::NetId> {
+        self.chip.get_net_of_shape(shape_id)
+    }
+
+    fn get_pin_of_shape(&self, shape_id: &Self::ShapeId) -> Option<Self::PinId> {
+        self.chip.get_pin_of_shape(shape_id)
+    }
+
+    fn try_get_net_of_shape(&self, shape_id: &Self::ShapeId) -> Option<Option<Self::NetId>> {
+        self.chip.try_get_net_of_shape(shape_id)
+    }
+
+    fn try_get_pin_of_shape(&self, shape_id: &Self::ShapeId) -> Option<Option<Self::PinId>> {
+        self.chip.try_get_pin_of_shape(shape_id)
+    }
+}
+
+impl<'a, T: L2NEdit> Undo<'a, T, L2NUndoOp<T>> {
+    /// Create a wrapper around a fused layout and netlist which
+    /// allows to undo operations.
+    pub fn new_l2n_undo(chip: &'a mut T) -> Self {
+        Self::with_chip(chip)
+    }
+}
+
+impl<'a, T: LayoutEdit> Undo<'a, T, LayoutUndoOp<T>> {
+    /// Create a wrapper which allows to undo operations performed
+    /// on the `LayoutEdit` trait.
+    pub fn new_layout_undo(chip: &'a mut T) -> Self {
+        Self::with_chip(chip)
+    }
+}
+
+impl<'a, T: BusEdit + PowerEdit> Undo<'a, T, PowerUndoOp<T>> {
+    /// Create a wrapper which allows to undo operations performed
+    /// on the `NetlistEdit`, `BusEdit` and `PowerEdit` traits.
+    pub fn new_power_undo(chip: &'a mut T) -> Self {
+        Self::with_chip(chip)
+    }
+}
+
+impl<'a, T: BusEdit> Undo<'a, T, BusUndoOp<T>> {
+    /// Create a wrapper which allows to undo operations performed
+    /// on the `NetlistEdit` and `BusEdit` traits.
+    pub fn new_bus_undo(chip: &'a mut T) -> Self {
+        Self::with_chip(chip)
+    }
+}
+
+impl<'a, T: NetlistEdit> Undo<'a, T, NetlistUndoOp<T>> {
+    /// Create a wrapper which allows to undo operations performed
+    /// on the `NetlistEdit` trait.
+    pub fn new_netlist_undo(chip: &'a mut T) -> Self {
+        Self::with_chip(chip)
+    }
+}
+
+impl<'a, T: HierarchyEdit> Undo<'a, T, HierarchyUndoOp<T>> {
+    /// Create a wrapper which allows to undo operations performed
+    /// on the `HierarchyEdit` trait.
+    pub fn new_hierarchy_undo(chip: &'a mut T) -> Self {
+        Self::with_chip(chip)
+    }
+}
+
+impl<'a, H, U> Decorator for Undo<'a, H, U> {
+    type D = H;
+
+    fn base(&self) -> &Self::D {
+        &self.chip
+    }
+}
+
+impl<'a, H, U> MutDecorator for Undo<'a, H, U> {
+    fn mut_base(&mut self) -> &mut Self::D {
+        &mut self.chip
+    }
+}
+
+// Inherit everything from HierarchyBase.
+impl<'a, H: HierarchyBase + 'static, U> HierarchyBaseDecorator for Undo<'a, H, U> {
+    type NameType = H::NameType;
+    type CellId = H::CellId;
+    type CellInstId = H::CellInstId;
+}
+
+// Inherit everything from LayoutBase.
+impl<'a, L: LayoutBase + 'static, U> LayoutBaseDecorator for Undo<'a, L, U> {}
+
+impl<'a, T: HierarchyEdit + 'static, U: UndoOp<T>> HierarchyEdit for Undo<'a, T, U> {
     fn new() -> Self {
         unimplemented!()
     }
 
     fn create_cell(&mut self, name: Self::NameType) -> Self::CellId {
         let id = self.chip.create_cell(name);
-        self.transactions
-            .push(HierarchyUndoOp::CreateCell(id.clone()).into());
+        self.push_op(HierarchyUndoOp::CreateCell(id.clone()));
         id
     }
 
-    fn remove_cell(&mut self, _cell_id: &Self::CellId) {
-        unimplemented!()
+    fn remove_cell(&mut self, cell_id: &Self::CellId) {
+        U::remove_cell(self, cell_id)
     }
 
     fn create_cell_instance(
@@ -426,37 +1957,99 @@ impl<'a, T: HierarchyEdit + 'static, U: From<HierarchyUndoOp<T>>> HierarchyEdit
         let id = self
             .chip
             .create_cell_instance(parent_cell, template_cell, name);
-        self.transactions
-            .push(HierarchyUndoOp::CreateCellInstance(id.clone()).into());
+        self.push_op(HierarchyUndoOp::CreateCellInstance(id.clone()));
         id
     }
 
-    fn remove_cell_instance(&mut self, _inst: &Self::CellInstId) {
-        unimplemented!()
+    fn remove_cell_instance(&mut self, inst: &Self::CellInstId) {
+        U::remove_cell_instance(self, inst)
     }
 
     fn rename_cell_instance(&mut self, inst: &Self::CellInstId, new_name: Option<Self::NameType>) {
         let previous_name = self.d_cell_instance_name(inst);
         self.chip.rename_cell_instance(inst, new_name);
-        self.transactions.push(
-            HierarchyUndoOp::RenameCellInst {
-                inst: inst.clone(),
-                previous_name,
-            }
-            .into(),
-        );
+        self.push_op(HierarchyUndoOp::RenameCellInst {
+            inst: inst.clone(),
+            previous_name,
+        });
     }
 
     fn rename_cell(&mut self, cell: &Self::CellId, new_name: Self::NameType) {
         let previous_name = self.d_cell_name(cell);
         self.chip.rename_cell(cell, new_name);
-        self.transactions.push(
-            HierarchyUndoOp::RenameCell {
+        self.push_op(HierarchyUndoOp::RenameCell {
+            cell: cell.clone(),
+            previous_name,
+        });
+    }
+
+    fn set_chip_property(&mut self, key: Self::NameType, value: PropertyValue) {
+        let previous = self.chip.get_chip_property(&key);
+        self.chip.set_chip_property(key.clone(), value);
+        self.push_op(HierarchyUndoOp::SetChipProperty { key, previous });
+    }
+
+    fn set_cell_property(
+        &mut self,
+        cell: &Self::CellId,
+        key: Self::NameType,
+        value: PropertyValue,
+    ) {
+        let previous = self.chip.get_cell_property(cell, &key);
+        self.chip.set_cell_property(cell, key.clone(), value);
+        self.push_op(HierarchyUndoOp::SetCellProperty {
+            cell: cell.clone(),
+            key,
+            previous,
+        });
+    }
+
+    fn set_cell_instance_property(
+        &mut self,
+        inst: &Self::CellInstId,
+        key: Self::NameType,
+        value: PropertyValue,
+    ) {
+        let previous = self.chip.get_cell_instance_property(inst, &key);
+        self.chip
+            .set_cell_instance_property(inst, key.clone(), value);
+        self.push_op(HierarchyUndoOp::SetCellInstanceProperty {
+            inst: inst.clone(),
+            key,
+            previous,
+        });
+    }
+
+    fn remove_chip_property(&mut self, key: &Self::NameType) {
+        if let Some(previous) = self.chip.get_chip_property(key) {
+            self.chip.remove_chip_property(key);
+            self.push_op(HierarchyUndoOp::SetChipProperty {
+                key: key.clone(),
+                previous: Some(previous),
+            });
+        }
+    }
+
+    fn remove_cell_property(&mut self, cell: &Self::CellId, key: &Self::NameType) {
+        if let Some(previous) = self.chip.get_cell_property(cell, key) {
+            self.chip.remove_cell_property(cell, key);
+            self.push_op(HierarchyUndoOp::SetCellProperty {
                 cell: cell.clone(),
-                previous_name,
-            }
-            .into(),
-        );
+                key: key.clone(),
+                previous: Some(previous),
+            });
+        }
+    }
+
+    fn remove_cell_instance_property(&mut self, inst: &Self::CellInstId, key: &Self::NameType) {
+        if let Some(previous) = self.chip.get_cell_instance_property(inst, key) {
+            self.chip.remove_cell_instance_property(inst, key);
+            self.push_op(HierarchyUndoOp::SetCellInstanceProperty {
+                inst: inst.clone(),
+                key: key.clone(),
+                previous: Some(previous),
+            });
+        }
     }
 }
 
@@ -497,24 +2090,75 @@ impl<'a, T: NetlistBase + 'static, U> NetlistBase for Undo<'a, T, U> {
         self.chip.net_of_pin(pin)
     }
 
-    fn net_of_pin_instance(&self, pin_instance: &Self::PinInstId) -> Option<Self::NetId> {
-        self.chip.net_of_pin_instance(pin_instance)
+    fn net_of_pin_instance(&self, pin_instance: &Self::PinInstId) -> Option<Self::NetId> {
+        self.chip.net_of_pin_instance(pin_instance)
+    }
+
+    fn net_zero(&self, parent_circuit: &Self::CellId) -> Self::NetId {
+        self.chip.net_zero(parent_circuit)
+    }
+
+    fn net_one(&self, parent_circuit: &Self::CellId) -> Self::NetId {
+        self.chip.net_one(parent_circuit)
+    }
+
+    fn net_by_name(&self, parent_circuit: &Self::CellId, name: &str) -> Option<Self::NetId> {
+        self.chip.net_by_name(parent_circuit, name)
+    }
+
+    fn net_name(&self, net: &Self::NetId) -> Option<Self::NameType> {
+        self.chip.net_name(net)
+    }
+
+    fn pin_exists(&self, pin: &Self::PinId) -> bool {
+        self.chip.pin_exists(pin)
+    }
+
+    fn pin_instance_exists(&self, pin_instance: &Self::PinInstId) -> bool {
+        self.chip.pin_instance_exists(pin_instance)
+    }
+
+    fn net_exists(&self, net: &Self::NetId) -> bool {
+        self.chip.net_exists(net)
+    }
+
+    fn try_template_pin(&self, pin_instance: &Self::PinInstId) -> Option<Self::PinId> {
+        self.chip.try_template_pin(pin_instance)
+    }
+
+    fn try_pin_direction(&self, pin: &Self::PinId) -> Option<Direction> {
+        self.chip.try_pin_direction(pin)
+    }
+
+    fn try_pin_name(&self, pin: &Self::PinId) -> Option<Self::NameType> {
+        self.chip.try_pin_name(pin)
+    }
+
+    fn try_parent_cell_of_pin(&self, pin: &Self::PinId) -> Option<Self::CellId> {
+        self.chip.try_parent_cell_of_pin(pin)
+    }
+
+    fn try_parent_of_pin_instance(&self, pin_inst: &Self::PinInstId) -> Option<Self::CellInstId> {
+        self.chip.try_parent_of_pin_instance(pin_inst)
     }
 
-    fn net_zero(&self, parent_circuit: &Self::CellId) -> Self::NetId {
-        self.chip.net_zero(parent_circuit)
+    fn try_parent_cell_of_net(&self, net: &Self::NetId) -> Option<Self::CellId> {
+        self.chip.try_parent_cell_of_net(net)
     }
 
-    fn net_one(&self, parent_circuit: &Self::CellId) -> Self::NetId {
-        self.chip.net_one(parent_circuit)
+    fn try_net_of_pin(&self, pin: &Self::PinId) -> Option<Option<Self::NetId>> {
+        self.chip.try_net_of_pin(pin)
     }
 
-    fn net_by_name(&self, parent_circuit: &Self::CellId, name: &str) -> Option<Self::NetId> {
-        self.chip.net_by_name(parent_circuit, name)
+    fn try_net_of_pin_instance(
+        &self,
+        pin_instance: &Self::PinInstId,
+    ) -> Option<Option<Self::NetId>> {
+        self.chip.try_net_of_pin_instance(pin_instance)
     }
 
-    fn net_name(&self, net: &Self::NetId) -> Option<Self::NameType> {
-        self.chip.net_name(net)
+    fn try_net_name(&self, net: &Self::NetId) -> Option<Option<Self::NameType>> {
+        self.chip.try_net_name(net)
     }
 
     fn for_each_pin<F>(&self, circuit: &Self::CellId, f: F)
@@ -560,7 +2204,7 @@ impl<'a, T: NetlistBase + 'static, U> NetlistBase for Undo<'a, T, U> {
 impl<'a, T, U> NetlistEdit for Undo<'a, T, U>
 where
     T: NetlistEdit + 'static,
-    U: From<NetlistUndoOp<T>> + From<HierarchyUndoOp<T>>,
+    U: NetlistUndo<T>,
 {
     fn create_pin(
         &mut self,
@@ -569,26 +2213,23 @@ where
         direction: Direction,
     ) -> Self::PinId {
         let id = self.chip.create_pin(circuit, name, direction);
-        self.transactions
-            .push(NetlistUndoOp::CreatePin(id.clone()).into());
+        self.push_op(NetlistUndoOp::CreatePin(id.clone()));
         id
     }
 
-    fn remove_pin(&mut self, _id: &Self::PinId) {
-        unimplemented!("Removing a pin is not implemented to be undoable.")
+    fn remove_pin(&mut self, id: &Self::PinId) {
+        U::remove_pin(self, id)
     }
 
     fn rename_pin(&mut self, pin: &Self::PinId, new_name: Self::NameType) -> Self::NameType {
-        let prev_name = self.chip.pin_name(pin);
-        self.transactions
-            .push(NetlistUndoOp::RenamePin(pin.clone(), prev_name).into());
-        self.chip.rename_pin(pin, new_name)
+        let prev_name = self.chip.rename_pin(pin, new_name);
+        self.push_op(NetlistUndoOp::RenamePin(pin.clone(), prev_name.clone()));
+        prev_name
     }
 
     fn create_net(&mut self, parent: &Self::CellId, name: Option<Self::NameType>) -> Self::NetId {
         let id = self.chip.create_net(parent, name);
-        self.transactions
-            .push(NetlistUndoOp::CreateNet(id.clone()).into());
+        self.push_op(NetlistUndoOp::CreateNet(id.clone()));
         id
     }
 
@@ -598,23 +2239,17 @@ where
         new_name: Option<Self::NameType>,
     ) -> Option<Self::NameType> {
         let old_name = self.chip.rename_net(net_id, new_name);
-        self.transactions
-            .push(NetlistUndoOp::RenameNet(net_id.clone(), old_name.clone()).into());
+        self.push_op(NetlistUndoOp::RenameNet(net_id.clone(), old_name.clone()));
         old_name
     }
 
-    fn remove_net(&mut self, _net: &Self::NetId) {
-        // let old_name = self.net_name(net);
-        // let old_terminals = self.each_terminal_of_net_vec(net);
-        // self.transactions.push(NetlistUndoOp::RemoveNet(old_name, old_terminals).into());
-        // self.chip.remove_net(net);
-        unimplemented!("Removing a net is not implemented to be undoable.")
+    fn remove_net(&mut self, net: &Self::NetId) {
+        U::remove_net(self, net)
     }
 
     fn connect_pin(&mut self, pin: &Self::PinId, net: Option<Self::NetId>) -> Option<Self::NetId> {
         let prev_net = self.chip.connect_pin(pin, net);
-        self.transactions
-            .push(NetlistUndoOp::ConnectPin(pin.clone(), prev_net.clone()).into());
+        self.push_op(NetlistUndoOp::ConnectPin(pin.clone(), prev_net.clone()));
         prev_net
     }
 
@@ -624,27 +2259,343 @@ where
         net: Option<Self::NetId>,
     ) -> Option<Self::NetId> {
         let prev_net = self.chip.connect_pin_instance(pin, net);
-        self.transactions
-            .push(NetlistUndoOp::ConnectPinInstance(pin.clone(), prev_net.clone()).into());
+        self.push_op(NetlistUndoOp::ConnectPinInstance(
+            pin.clone(),
+            prev_net.clone(),
+        ));
         prev_net
     }
 }
 
+impl<'a, T: BusBase + 'static, U> BusBase for Undo<'a, T, U> {
+    type PinBusId = T::PinBusId;
+    type NetBusId = T::NetBusId;
+
+    fn pin_bus_by_name(&self, parent_circuit: &Self::CellId, name: &str) -> Option<Self::PinBusId> {
+        self.chip.pin_bus_by_name(parent_circuit, name)
+    }
+
+    fn pin_bus_name(&self, bus: &Self::PinBusId) -> Self::NameType {
+        self.chip.pin_bus_name(bus)
+    }
+
+    fn parent_cell_of_pin_bus(&self, bus: &Self::PinBusId) -> Self::CellId {
+        self.chip.parent_cell_of_pin_bus(bus)
+    }
+
+    fn pin_bus_bit_order(&self, bus: &Self::PinBusId) -> BitOrder {
+        self.chip.pin_bus_bit_order(bus)
+    }
+
+    fn bus_of_pin(&self, pin: &Self::PinId) -> Option<Self::PinBusId> {
+        self.chip.bus_of_pin(pin)
+    }
+
+    fn pin_bit_index(&self, pin: &Self::PinId) -> Option<usize> {
+        self.chip.pin_bit_index(pin)
+    }
+
+    fn for_each_pin_bus<F>(&self, circuit: &Self::CellId, f: F)
+    where
+        F: FnMut(Self::PinBusId) -> (),
+    {
+        self.chip.for_each_pin_bus(circuit, f)
+    }
+
+    fn for_each_pin_of_bus<F>(&self, bus: &Self::PinBusId, f: F)
+    where
+        F: FnMut(Self::PinId) -> (),
+    {
+        self.chip.for_each_pin_of_bus(bus, f)
+    }
+
+    fn net_bus_by_name(&self, parent_circuit: &Self::CellId, name: &str) -> Option<Self::NetBusId> {
+        self.chip.net_bus_by_name(parent_circuit, name)
+    }
+
+    fn net_bus_name(&self, bus: &Self::NetBusId) -> Self::NameType {
+        self.chip.net_bus_name(bus)
+    }
+
+    fn parent_cell_of_net_bus(&self, bus: &Self::NetBusId) -> Self::CellId {
+        self.chip.parent_cell_of_net_bus(bus)
+    }
+
+    fn net_bus_bit_order(&self, bus: &Self::NetBusId) -> BitOrder {
+        self.chip.net_bus_bit_order(bus)
+    }
+
+    fn bus_of_net(&self, net: &Self::NetId) -> Option<Self::NetBusId> {
+        self.chip.bus_of_net(net)
+    }
+
+    fn net_bit_index(&self, net: &Self::NetId) -> Option<usize> {
+        self.chip.net_bit_index(net)
+    }
+
+    fn for_each_net_bus<F>(&self, circuit: &Self::CellId, f: F)
+    where
+        F: FnMut(Self::NetBusId) -> (),
+    {
+        self.chip.for_each_net_bus(circuit, f)
+    }
+
+    fn for_each_net_of_bus<F>(&self, bus: &Self::NetBusId, f: F)
+    where
+        F: FnMut(Self::NetId) -> (),
+    {
+        self.chip.for_each_net_of_bus(bus, f)
+    }
+}
+
+impl<'a, T, U> BusEdit for Undo<'a, T, U>
+where
+    T: BusEdit + 'static,
+    U: BusUndo<T>,
+{
+    fn create_pin_bus_from_pins(
+        &mut self,
+        name: Self::NameType,
+        pins: Vec<Self::PinId>,
+        bit_order: BitOrder,
+    ) -> Self::PinBusId {
+        let id = self.chip.create_pin_bus_from_pins(name, pins, bit_order);
+        self.push_op(BusUndoOp::CreatePinBus(id.clone()));
+        id
+    }
+
+    fn create_pin_bus(
+        &mut self,
+        cell: &Self::CellId,
+        name: Self::NameType,
+        direction: Direction,
+        width: usize,
+        bit_order: BitOrder,
+    ) -> Self::PinBusId {
+        assert!(width > 0, "A bus must have at least one bit.");
+        // Pins and bus are undone in a single step.
+        self.begin_group();
+        let pins = (0..width)
+            .map(|i| self.create_pin(cell, bus_bit_name(name.borrow(), i).into(), direction))
+            .collect();
+        let bus = self.create_pin_bus_from_pins(name, pins, bit_order);
+        self.end_group();
+        bus
+    }
+
+    fn remove_pin_bus(&mut self, bus: &Self::PinBusId) {
+        self.push_op(BusUndoOp::RemovePinBus {
+            bus: bus.clone(),
+            name: self.chip.pin_bus_name(bus),
+            pins: self.chip.each_pin_of_bus_vec(bus),
+            bit_order: self.chip.pin_bus_bit_order(bus),
+        });
+        self.chip.remove_pin_bus(bus)
+    }
+
+    fn create_net_bus_from_nets(
+        &mut self,
+        name: Self::NameType,
+        nets: Vec<Self::NetId>,
+        bit_order: BitOrder,
+    ) -> Self::NetBusId {
+        let id = self.chip.create_net_bus_from_nets(name, nets, bit_order);
+        self.push_op(BusUndoOp::CreateNetBus(id.clone()));
+        id
+    }
+
+    fn create_net_bus(
+        &mut self,
+        parent: &Self::CellId,
+        name: Self::NameType,
+        width: usize,
+        bit_order: BitOrder,
+    ) -> Self::NetBusId {
+        assert!(width > 0, "A bus must have at least one bit.");
+        // Nets and bus are undone in a single step.
+        self.begin_group();
+        let nets = (0..width)
+            .map(|i| self.create_net(parent, Some(bus_bit_name(name.borrow(), i).into())))
+            .collect();
+        let bus = self.create_net_bus_from_nets(name, nets, bit_order);
+        self.end_group();
+        bus
+    }
+
+    fn remove_net_bus(&mut self, bus: &Self::NetBusId) {
+        self.push_op(BusUndoOp::RemoveNetBus {
+            bus: bus.clone(),
+            name: self.chip.net_bus_name(bus),
+            nets: self.chip.each_net_of_bus_vec(bus),
+            bit_order: self.chip.net_bus_bit_order(bus),
+        });
+        self.chip.remove_net_bus(bus)
+    }
+}
+
+impl<'a, T: PowerBase + 'static, U> PowerBase for Undo<'a, T, U> {
+    type PowerDomainId = T::PowerDomainId;
+
+    fn power_domain_by_name(&self, name: &str) -> Option<Self::PowerDomainId> {
+        self.chip.power_domain_by_name(name)
+    }
+
+    fn power_domain_name(&self, domain: &Self::PowerDomainId) -> Self::NameType {
+        self.chip.power_domain_name(domain)
+    }
+
+    fn parent_cell_of_power_domain(&self, domain: &Self::PowerDomainId) -> Self::CellId {
+        self.chip.parent_cell_of_power_domain(domain)
+    }
+
+    fn power_domain_supply_net(&self, domain: &Self::PowerDomainId) -> Self::NetId {
+        self.chip.power_domain_supply_net(domain)
+    }
+
+    fn power_domain_ground_net(&self, domain: &Self::PowerDomainId) -> Self::NetId {
+        self.chip.power_domain_ground_net(domain)
+    }
+
+    fn power_domain_voltage(&self, domain: &Self::PowerDomainId) -> f64 {
+        self.chip.power_domain_voltage(domain)
+    }
+
+    fn power_domain_is_always_on(&self, domain: &Self::PowerDomainId) -> bool {
+        self.chip.power_domain_is_always_on(domain)
+    }
+
+    fn for_each_power_domain<F>(&self, f: F)
+    where
+        F: FnMut(Self::PowerDomainId) -> (),
+    {
+        self.chip.for_each_power_domain(f)
+    }
+
+    fn power_domain_of_cell(&self, cell: &Self::CellId) -> Option<Self::PowerDomainId> {
+        self.chip.power_domain_of_cell(cell)
+    }
+
+    fn assigned_power_domain_of_cell_instance(
+        &self,
+        inst: &Self::CellInstId,
+    ) -> Option<Self::PowerDomainId> {
+        self.chip.assigned_power_domain_of_cell_instance(inst)
+    }
+
+    fn each_power_domain_of_supply_net_vec(&self, net: &Self::NetId) -> Vec<Self::PowerDomainId> {
+        self.chip.each_power_domain_of_supply_net_vec(net)
+    }
+}
+
+impl<'a, T, U> PowerEdit for Undo<'a, T, U>
+where
+    T: BusEdit + PowerEdit + 'static,
+    U: PowerUndo<T>,
+{
+    fn create_power_domain(
+        &mut self,
+        name: Self::NameType,
+        supply_net: Self::NetId,
+        ground_net: Self::NetId,
+        voltage: f64,
+        always_on: bool,
+    ) -> Self::PowerDomainId {
+        let id = self
+            .chip
+            .create_power_domain(name, supply_net, ground_net, voltage, always_on);
+        self.push_op(PowerUndoOp::CreatePowerDomain(id.clone()));
+        id
+    }
+
+    fn remove_power_domain(&mut self, domain: &Self::PowerDomainId) {
+        // Assignments and domain are undone in a single step.
+        self.begin_group();
+        for cell in self.chip.each_cell_vec() {
+            if self.chip.power_domain_of_cell(&cell).as_ref() == Some(domain) {
+                self.set_power_domain_of_cell(&cell, None);
+            }
+            for inst in self.chip.each_cell_instance_vec(&cell) {
+                if self
+                    .chip
+                    .assigned_power_domain_of_cell_instance(&inst)
+                    .as_ref()
+                    == Some(domain)
+                {
+                    self.set_power_domain_of_cell_instance(&inst, None);
+                }
+            }
+        }
+        self.push_op(PowerUndoOp::RemovePowerDomain {
+            domain: domain.clone(),
+            name: self.chip.power_domain_name(domain),
+            supply_net: self.chip.power_domain_supply_net(domain),
+            ground_net: self.chip.power_domain_ground_net(domain),
+            voltage: self.chip.power_domain_voltage(domain),
+            always_on: self.chip.power_domain_is_always_on(domain),
+        });
+        self.chip.remove_power_domain(domain);
+        self.end_group();
+    }
+
+    fn set_power_domain_voltage(&mut self, domain: &Self::PowerDomainId, voltage: f64) -> f64 {
+        let previous = self.chip.set_power_domain_voltage(domain, voltage);
+        self.push_op(PowerUndoOp::SetPowerDomainVoltage(domain.clone(), previous));
+        previous
+    }
+
+    fn set_power_domain_always_on(
+        &mut self,
+        domain: &Self::PowerDomainId,
+        always_on: bool,
+    ) -> bool {
+        let previous = self.chip.set_power_domain_always_on(domain, always_on);
+        self.push_op(PowerUndoOp::SetPowerDomainAlwaysOn(
+            domain.clone(),
+            previous,
+        ));
+        previous
+    }
+
+    fn set_power_domain_of_cell(
+        &mut self,
+        cell: &Self::CellId,
+        domain: Option<Self::PowerDomainId>,
+    ) -> Option<Self::PowerDomainId> {
+        let previous = self.chip.set_power_domain_of_cell(cell, domain);
+        self.push_op(PowerUndoOp::SetPowerDomainOfCell(
+            cell.clone(),
+            previous.clone(),
+        ));
+        previous
+    }
+
+    fn set_power_domain_of_cell_instance(
+        &mut self,
+        inst: &Self::CellInstId,
+        domain: Option<Self::PowerDomainId>,
+    ) -> Option<Self::PowerDomainId> {
+        let previous = self.chip.set_power_domain_of_cell_instance(inst, domain);
+        self.push_op(PowerUndoOp::SetPowerDomainOfCellInstance(
+            inst.clone(),
+            previous.clone(),
+        ));
+        previous
+    }
+}
+
 impl<'a, T, U> LayoutEdit for Undo<'a, T, U>
 where
     T: LayoutEdit + 'static,
-    U: From<LayoutUndoOp<T>> + From<HierarchyUndoOp<T>>,
+    U: LayoutUndo<T>,
 {
     fn set_dbu(&mut self, dbu: Self::Coord) {
-        self.transactions
-            .push(LayoutUndoOp::SetDbu(self.dbu()).into());
+        self.push_op(LayoutUndoOp::SetDbu(self.chip.dbu()));
         self.chip.set_dbu(dbu)
     }
 
     fn create_layer(&mut self, index: u32, datatype: u32) -> Self::LayerId {
         let id = self.chip.create_layer(index, datatype);
-        self.transactions
-            .push(LayoutUndoOp::CreateLayer(id.clone()).into());
+        self.push_op(LayoutUndoOp::CreateLayer(id.clone()));
         id
     }
 
@@ -656,8 +2607,7 @@ where
     ) -> Result<(), ()> {
         self.chip
             .create_layer_with_id(layer_id.clone(), index, datatype)?;
-        self.transactions
-            .push(LayoutUndoOp::CreateLayer(layer_id.clone()).into());
+        self.push_op(LayoutUndoOp::CreateLayer(layer_id.clone()));
         Ok(())
     }
 
@@ -667,8 +2617,7 @@ where
         name: Option<Self::NameType>,
     ) -> Option<Self::NameType> {
         let old_name = self.layer_info(layer).name.clone();
-        self.transactions
-            .push(LayoutUndoOp::SetLayerName(layer.clone(), old_name).into());
+        self.push_op(LayoutUndoOp::SetLayerName(layer.clone(), old_name));
         self.chip.set_layer_name(layer, name)
     }
 
@@ -679,25 +2628,12 @@ where
         geometry: Geometry<Self::Coord>,
     ) -> Self::ShapeId {
         let id = self.chip.insert_shape(parent_cell, layer, geometry);
-        self.transactions
-            .push(LayoutUndoOp::InsertShape(id.clone()).into());
+        self.push_op(LayoutUndoOp::InsertShape(id.clone()));
         id
     }
 
     fn remove_shape(&mut self, shape_id: &Self::ShapeId) -> Option<Geometry<Self::Coord>> {
-        let geometry = self.chip.remove_shape(shape_id);
-        let (parent_cell, layer) = self.parent_of_shape(shape_id);
-        if let Some(geometry) = &geometry {
-            self.transactions.push(
-                LayoutUndoOp::RemoveShape {
-                    parent_cell,
-                    layer,
-                    geometry: geometry.clone(),
-                }
-                .into(),
-            );
-        }
-        geometry
+        U::remove_shape(self, shape_id)
     }
 
     fn replace_shape(
@@ -707,37 +2643,77 @@ where
     ) -> Geometry<Self::Coord> {
         let old_geometry = self.chip.replace_shape(shape_id, geometry);
 
-        self.transactions
-            .push(LayoutUndoOp::ReplaceShape(shape_id.clone(), old_geometry.clone()).into());
+        self.push_op(LayoutUndoOp::ReplaceShape(
+            shape_id.clone(),
+            old_geometry.clone(),
+        ));
 
         old_geometry
     }
 
     fn set_transform(&mut self, cell_inst: &Self::CellInstId, tf: SimpleTransform<Self::Coord>) {
         let old_transform = self.get_transform(cell_inst);
-        self.transactions
-            .push(LayoutUndoOp::SetTransform(cell_inst.clone(), old_transform).into());
+        self.push_op(LayoutUndoOp::SetTransform(cell_inst.clone(), old_transform));
         self.chip.set_transform(cell_inst, tf)
     }
 
+    fn set_cell_instance_array(
+        &mut self,
+        cell_inst: &Self::CellInstId,
+        array: Option<InstanceArray<Self::Coord>>,
+    ) -> Option<InstanceArray<Self::Coord>> {
+        let old_array = self.chip.set_cell_instance_array(cell_inst, array);
+        self.push_op(LayoutUndoOp::SetCellInstanceArray(
+            cell_inst.clone(),
+            old_array,
+        ));
+        old_array
+    }
+
+    fn set_placement_status(
+        &mut self,
+        cell_inst: &Self::CellInstId,
+        status: PlacementStatus,
+    ) -> PlacementStatus {
+        let old_status = self.chip.set_placement_status(cell_inst, status);
+        self.push_op(LayoutUndoOp::SetPlacementStatus(
+            cell_inst.clone(),
+            old_status,
+        ));
+        old_status
+    }
+
     fn set_shape_property(
         &mut self,
         shape: &Self::ShapeId,
         key: Self::NameType,
-        _value: PropertyValue,
+        value: PropertyValue,
     ) {
-        let _old_property = self.get_shape_property(shape, &key);
-        unimplemented!("set_shape_property() is currently not undoable.")
+        let previous = self.chip.get_shape_property(shape, &key);
+        self.chip.set_shape_property(shape, key.clone(), value);
+        self.push_op(LayoutUndoOp::SetShapeProperty {
+            shape: shape.clone(),
+            key,
+            previous,
+        });
+    }
+
+    fn remove_shape_property(&mut self, shape: &Self::ShapeId, key: &Self::NameType) {
+        if let Some(previous) = self.chip.get_shape_property(shape, key) {
+            self.chip.remove_shape_property(shape, key);
+            self.push_op(LayoutUndoOp::SetShapeProperty {
+                shape: shape.clone(),
+                key: key.clone(),
+                previous: Some(previous),
+            });
+        }
     }
 }
 
 impl<'a, T, U> L2NEdit for Undo<'a, T, U>
 where
     T: L2NEdit + 'static,
-    U: From<L2NUndoOp<T>>
-        + From<LayoutUndoOp<T>>
-        + From<NetlistUndoOp<T>>
-        + From<HierarchyUndoOp<T>>,
+    U: NetlistUndo<T> + LayoutUndo<T> + From<L2NUndoOp<T>>,
 {
     fn set_pin_of_shape(
         &mut self,
@@ -745,13 +2721,10 @@ where
         pin: Option<Self::PinId>,
     ) -> Option<Self::PinId> {
         let previous_pin = self.get_pin_of_shape(shape_id);
-        self.transactions.push(
-            L2NUndoOp::SetPinOfShape {
-                shape_id: shape_id.clone(),
-                previous_pin,
-            }
-            .into(),
-        );
+        self.push_op(L2NUndoOp::SetPinOfShape {
+            shape_id: shape_id.clone(),
+            previous_pin,
+        });
         self.chip.set_pin_of_shape(shape_id, pin)
     }
 
@@ -761,13 +2734,10 @@ where
         net: Option<Self::NetId>,
     ) -> Option<Self::NetId> {
         let previous_net = self.get_net_of_shape(shape_id);
-        self.transactions.push(
-            L2NUndoOp::SetNetOfShape {
-                shape_id: shape_id.clone(),
-                previous_net,
-            }
-            .into(),
-        );
+        self.push_op(L2NUndoOp::SetNetOfShape {
+            shape_id: shape_id.clone(),
+            previous_net,
+        });
         self.chip.set_net_of_shape(shape_id, net)
     }
 }
@@ -805,3 +2775,375 @@ fn test_hierarchy_undoing() {
     undo.undo();
     assert_eq!(undo.num_cells(), 0);
 }
+
+#[test]
+fn test_undo_remove_cell() {
+    use crate::chip::Chip;
+    use crate::prelude::*;
+    let mut chip = Chip::new();
+    let mut undo = Undo::new_l2n_undo(&mut chip);
+
+    let layer = undo.create_layer(1, 0);
+    let top = undo.create_cell("TOP".into());
+    let sub = undo.create_cell("SUB".into());
+    let sub_a = undo.create_pin(&sub, "A".into(), Direction::Input);
+    let sub_net = undo.create_net(&sub, Some("net_a".into()));
+    undo.connect_pin(&sub_a, Some(sub_net));
+    let shape = undo.insert_shape(&sub, &layer, Rect::new((0, 0), (10, 10)).into());
+    undo.set_net_of_shape(&shape, Some(sub_net));
+    undo.set_pin_of_shape(&shape, Some(sub_a));
+    undo.set_shape_property(&shape, "key".into(), PropertyValue::SInt(42));
+    undo.set_cell_property(&sub, "key".into(), PropertyValue::SInt(7));
+
+    let inst = undo.create_cell_instance(&top, &sub, Some("inst1".into()));
+    undo.set_transform(&inst, SimpleTransform::translate((1, 2)));
+    let top_net = undo.create_net(&top, Some("net_b".into()));
+    let pin_inst = undo.pin_instance(&inst, &sub_a);
+    undo.connect_pin_instance(&pin_inst, Some(top_net));
+
+    let num_transactions = undo.num_transactions();
+
+    undo.remove_cell(&sub);
+    assert_eq!(undo.num_cells(), 1);
+    assert_eq!(undo.num_transactions(), num_transactions + 1);
+
+    undo.undo();
+    assert_eq!(undo.num_cells(), 2);
+
+    // The restored cell has a new ID but the same content.
+    let sub = undo.cell_by_name("SUB").unwrap();
+    assert_eq!(
+        undo.get_cell_property(&sub, &"key".into())
+            .and_then(|v| v.get_sint()),
+        Some(7)
+    );
+    let sub_a = undo.pin_by_name(&sub, "A").unwrap();
+    let sub_net = undo.net_by_name(&sub, "net_a").unwrap();
+    assert_eq!(undo.net_of_pin(&sub_a), Some(sub_net));
+
+    let shapes: Vec<_> = undo.each_shape_id(&sub, &layer).collect();
+    assert_eq!(shapes.len(), 1);
+    let shape = &shapes[0];
+    assert_eq!(undo.get_net_of_shape(shape), Some(sub_net));
+    assert_eq!(undo.get_pin_of_shape(shape), Some(sub_a));
+    assert_eq!(
+        undo.get_shape_property(shape, &"key".into())
+            .and_then(|v| v.get_sint()),
+        Some(42)
+    );
+
+    let inst = undo.cell_instance_by_name(&top, "inst1").unwrap();
+    assert_eq!(undo.template_cell(&inst), sub);
+    assert_eq!(
+        undo.get_transform(&inst).transform_point(Point::zero()),
+        Point::new(1, 2)
+    );
+    let pin_inst = undo.pin_instance(&inst, &sub_a);
+    assert_eq!(undo.net_of_pin_instance(&pin_inst), Some(top_net));
+
+    // Redo the removal and undo it again.
+    undo.redo();
+    assert_eq!(undo.num_cells(), 1);
+    assert!(undo.cell_by_name("SUB").is_none());
+    undo.undo();
+    assert_eq!(undo.num_cells(), 2);
+
+    // Undo everything including operations which refer to the old IDs.
+    undo.undo_all();
+    assert_eq!(undo.num_cells(), 0);
+}
+
+#[test]
+fn test_undo_remove_net_and_pin() {
+    use crate::chip::Chip;
+    let mut chip = Chip::new();
+    let mut undo = Undo::new_netlist_undo(&mut chip);
+
+    let top = undo.create_cell("TOP".into());
+    let sub = undo.create_cell("SUB".into());
+    let _sub_a = undo.create_pin(&sub, "A".into(), Direction::Input);
+    let sub_b = undo.create_pin(&sub, "B".into(), Direction::Output);
+    let inst = undo.create_cell_instance(&top, &sub, None);
+    let net = undo.create_net(&top, Some("net".into()));
+    let pin_inst = undo.pin_instance(&inst, &sub_b);
+    undo.connect_pin_instance(&pin_inst, Some(net));
+
+    // Remove and restore the net.
+    undo.remove_net(&net);
+    assert!(undo.net_by_name(&top, "net").is_none());
+    assert_eq!(undo.net_of_pin_instance(&pin_inst), None);
+    undo.undo();
+    let net = undo.net_by_name(&top, "net").unwrap();
+    assert_eq!(undo.net_of_pin_instance(&pin_inst), Some(net));
+
+    // Remove and restore the pin.
+    undo.remove_pin(&sub_b);
+    assert_eq!(undo.num_pins(&sub), 1);
+    undo.undo();
+    assert_eq!(undo.num_pins(&sub), 2);
+    let sub_b = undo.pin_by_name(&sub, "B").unwrap();
+    let pin_inst = undo.pin_instance(&inst, &sub_b);
+    assert_eq!(undo.net_of_pin_instance(&pin_inst), Some(net));
+
+    // A new modification clears the redo stack.
+    undo.redo();
+    assert_eq!(undo.num_pins(&sub), 1);
+    undo.undo();
+    assert_eq!(undo.num_redo_transactions(), 1);
+    undo.rename_cell(&top, "NewName".into());
+    assert_eq!(undo.num_redo_transactions(), 0);
+
+    undo.undo_all();
+    assert_eq!(undo.num_cells(), 0);
+}
+
+#[test]
+fn test_undo_buses() {
+    use crate::chip::Chip;
+    let mut chip = Chip::new();
+    let mut undo = Undo::new_bus_undo(&mut chip);
+
+    let top = undo.create_cell("TOP".into());
+    let data = undo.create_pin_bus(
+        &top,
+        "data".into(),
+        Direction::Input,
+        4,
+        BitOrder::Descending,
+    );
+    let nets = undo.create_net_bus(&top, "d".into(), 4, BitOrder::Descending);
+    undo.connect_pin_bus(&data, Some(&nets));
+    assert_eq!(undo.num_pins(&top), 4);
+
+    // Removing a pin dissolves its bus, undoing restores both.
+    let bit_2 = undo.pin_bus_bit(&data, 2).unwrap();
+    undo.remove_pin(&bit_2);
+    assert!(undo.pin_bus_by_name(&top, "data").is_none());
+    undo.undo();
+    let data = undo.pin_bus_by_name(&top, "data").unwrap();
+    assert_eq!(undo.pin_bus_width(&data), 4);
+    let bit_2 = undo.pin_bus_bit(&data, 2).unwrap();
+    assert_eq!(undo.pin_name(&bit_2), "data[2]");
+    assert_eq!(undo.pin_bit_index(&bit_2), Some(2));
+    assert_eq!(undo.net_of_pin(&bit_2), undo.net_bus_bit(&nets, 2));
+
+    // Removing and restoring a net bus keeps the nets.
+    undo.remove_net_bus(&nets);
+    assert!(undo.bus_of_net(&undo.net_of_pin(&bit_2).unwrap()).is_none());
+    undo.undo();
+    let nets = undo.net_bus_by_name(&top, "d").unwrap();
+    assert_eq!(
+        undo.net_bit_index(&undo.net_of_pin(&bit_2).unwrap()),
+        Some(2)
+    );
+    assert_eq!(
+        undo.bus_of_net(&undo.net_of_pin(&bit_2).unwrap()),
+        Some(nets)
+    );
+
+    undo.undo_all();
+    assert_eq!(undo.num_cells(), 0);
+}
+
+#[test]
+fn test_undo_power_domains() {
+    use crate::chip::Chip;
+    let mut chip = Chip::new();
+    let mut undo = Undo::new_power_undo(&mut chip);
+
+    let top = undo.create_cell("TOP".into());
+    let inv = undo.create_cell("INV".into());
+    let vdd = undo.create_net(&top, Some("VDD".into()));
+    let vss = undo.create_net(&top, Some("VSS".into()));
+    let core = undo.create_power_domain("CORE".into(), vdd, vss, 0.9, false);
+    undo.set_power_domain_of_cell(&top, Some(core));
+    let inst = undo.create_cell_instance(&top, &inv, None);
+    undo.set_power_domain_of_cell_instance(&inst, Some(core));
+
+    undo.set_power_domain_voltage(&core, 1.2);
+    undo.undo();
+    assert_eq!(undo.power_domain_voltage(&core), 0.9);
+
+    // Removing the supply net removes the domain, undoing restores the assignments.
+    undo.remove_net(&vdd);
+    assert!(undo.power_domain_by_name("CORE").is_none());
+    assert_eq!(undo.power_domain_of_cell(&top), None);
+    undo.undo();
+    let core = undo.power_domain_by_name("CORE").unwrap();
+    assert_eq!(undo.power_domain_of_cell(&top), Some(core));
+    assert_eq!(
+        undo.assigned_power_domain_of_cell_instance(&inst),
+        Some(core)
+    );
+    assert_eq!(
+        undo.net_name(&undo.power_domain_supply_net(&core)),
+        Some("VDD".into())
+    );
+
+    // Removing the parent cell of the domain.
+    undo.remove_cell(&top);
+    assert!(undo.power_domain_by_name("CORE").is_none());
+    undo.undo();
+    let top = undo.cell_by_name("TOP").unwrap();
+    let core = undo.power_domain_by_name("CORE").unwrap();
+    assert_eq!(undo.power_domain_of_cell(&top), Some(core));
+    let inst = undo.each_cell_instance_vec(&top)[0];
+    assert_eq!(
+        undo.assigned_power_domain_of_cell_instance(&inst),
+        Some(core)
+    );
+
+    undo.redo();
+    assert!(undo.power_domain_by_name("CORE").is_none());
+    undo.undo_all();
+    assert_eq!(undo.num_cells(), 0);
+}
+
+#[test]
+fn test_undo_properties() {
+    use crate::chip::Chip;
+    let mut chip = Chip::new();
+    let mut undo = Undo::new_hierarchy_undo(&mut chip);
+
+    let top = undo.create_cell("TOP".into());
+    undo.set_cell_property(&top, "a".into(), PropertyValue::SInt(1));
+    undo.set_cell_property(&top, "a".into(), PropertyValue::SInt(2));
+    undo.remove_cell_property(&top, &"a".into());
+    assert!(undo.get_cell_property(&top, &"a".into()).is_none());
+
+    undo.undo();
+    assert_eq!(
+        undo.get_cell_property(&top, &"a".into())
+            .and_then(|v| v.get_sint()),
+        Some(2)
+    );
+    undo.undo();
+    assert_eq!(
+        undo.get_cell_property(&top, &"a".into())
+            .and_then(|v| v.get_sint()),
+        Some(1)
+    );
+    undo.undo();
+    assert!(undo.get_cell_property(&top, &"a".into()).is_none());
+    undo.redo();
+    assert_eq!(
+        undo.get_cell_property(&top, &"a".into())
+            .and_then(|v| v.get_sint()),
+        Some(1)
+    );
+}
+
+#[test]
+fn test_undo_cell_instance_array() {
+    use crate::chip::Chip;
+    use crate::prelude::*;
+    let mut chip = Chip::new();
+    let mut undo = Undo::new_layout_undo(&mut chip);
+
+    let top = undo.create_cell("TOP".into());
+    let sub = undo.create_cell("SUB".into());
+    let inst = undo.create_cell_instance(&top, &sub, Some("inst1".into()));
+    let array = InstanceArray::new(4, 2, Vector::new(10, 0), Vector::new(0, 20));
+    undo.set_cell_instance_array(&inst, Some(array));
+    assert_eq!(undo.num_array_elements(&inst), 8);
+
+    // Removing the instance must also restore the array when undone.
+    undo.remove_cell_instance(&inst);
+    undo.undo();
+    let inst = undo.cell_instance_by_name(&top, "inst1").unwrap();
+    assert_eq!(undo.cell_instance_array(&inst), Some(array));
+
+    undo.undo();
+    assert_eq!(undo.cell_instance_array(&inst), None);
+    assert_eq!(undo.num_array_elements(&inst), 1);
+}
+
+#[test]
+fn test_undo_placement_status() {
+    use crate::chip::Chip;
+    use crate::prelude::*;
+    let mut chip = Chip::new();
+    let mut undo = Undo::new_layout_undo(&mut chip);
+
+    let top = undo.create_cell("TOP".into());
+    let sub = undo.create_cell("SUB".into());
+    let inst = undo.create_cell_instance(&top, &sub, Some("inst1".into()));
+    undo.set_transform(&inst, SimpleTransform::translate((10, 20)));
+    undo.set_placement_status(&inst, PlacementStatus::Fixed);
+
+    // Removing the instance must also restore the status when undone.
+    undo.remove_cell_instance(&inst);
+    undo.undo();
+    let inst = undo.cell_instance_by_name(&top, "inst1").unwrap();
+    assert_eq!(undo.placement_status(&inst), PlacementStatus::Fixed);
+    assert_eq!(
+        undo.get_transform(&inst),
+        SimpleTransform::translate((10, 20))
+    );
+
+    undo.undo();
+    assert_eq!(undo.placement_status(&inst), PlacementStatus::Placed);
+}
+
+#[test]
+fn test_nested_transactions() {
+    use crate::chip::Chip;
+    let mut chip = Chip::new();
+    let mut undo = Undo::new_netlist_undo(&mut chip);
+
+    let top = undo.create_cell("TOP".into());
+
+    undo.begin_transaction("outer");
+    let a = undo.create_net(&top, Some("a".into()));
+    undo.begin_transaction("inner");
+    undo.create_net(&top, Some("b".into()));
+    undo.remove_net(&a);
+    undo.rollback();
+    // The rollback restored net `a` with a new ID.
+    let a = undo.net_by_name(&top, "a").unwrap();
+    assert!(undo.net_by_name(&top, "b").is_none());
+    undo.begin_transaction("inner");
+    undo.rename_net(&a, Some("c".into()));
+    undo.commit();
+    assert_eq!(undo.num_open_transactions(), 1);
+    assert_eq!(undo.num_transactions(), 1);
+    undo.commit();
+
+    assert_eq!(undo.num_transactions(), 2);
+    assert_eq!(undo.undo_name(), Some("outer"));
+    assert!(undo.net_by_name(&top, "c").is_some());
+
+    // Undo the whole transaction at once.
+    undo.undo();
+    assert!(undo.net_by_name(&top, "a").is_none());
+    assert!(undo.net_by_name(&top, "c").is_none());
+    assert_eq!(undo.redo_name(), Some("outer"));
+
+    undo.redo();
+    assert!(undo.net_by_name(&top, "c").is_some());
+}
+
+#[test]
+fn test_undo_to_checkpoint() {
+    use crate::chip::Chip;
+    let mut chip = Chip::new();
+    let mut undo = Undo::new_hierarchy_undo(&mut chip);
+
+    undo.create_cell("A".into());
+    let checkpoint = undo.checkpoint();
+    undo.create_cell("B".into());
+    undo.create_cell("C".into());
+
+    undo.undo_to(&checkpoint);
+    assert_eq!(undo.num_cells(), 1);
+    assert_eq!(undo.num_redo_transactions(), 2);
+
+    // Redoing keeps the checkpoint valid.
+    undo.redo();
+    assert!(undo.can_undo_to(&checkpoint));
+
+    // The checkpoint is lost when the history before it is replaced.
+    undo.undo_all();
+    undo.create_cell("D".into());
+    assert!(!undo.can_undo_to(&checkpoint));
+}
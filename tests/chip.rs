@@ -121,6 +121,123 @@ fn test_create_nets() {
     assert_eq!(chip.net_by_name(&b, "net2").as_ref(), Some(&b_net2));
 }
 
+#[test]
+fn test_create_buses() {
+    let mut chip = Chip::new();
+    let a = chip.create_cell("A".into());
+    let b = chip.create_cell("B".into());
+    let inst = chip.create_cell_instance(&b, &a, Some("a1".into()));
+
+    let data = chip.create_pin_bus(&a, "data".into(), Direction::Input, 4, BitOrder::Descending);
+    assert_eq!(chip.num_pins(&a), 4);
+    assert_eq!(chip.pin_bus_width(&data), 4);
+    assert_eq!(chip.pin_bus_by_name(&a, "data"), Some(data));
+    assert_eq!(chip.pin_bus_bit_order(&data), BitOrder::Descending);
+    let bit3 = chip.pin_by_name(&a, "data[3]").unwrap();
+    assert_eq!(chip.bus_of_pin(&bit3), Some(data));
+    assert_eq!(chip.pin_bit_index(&bit3), Some(3));
+    assert_eq!(chip.pin_bus_bit(&data, 3), Some(bit3));
+
+    // Connect the bus instance to a net bus.
+    let nets = chip.create_net_bus(&b, "d".into(), 4, BitOrder::Ascending);
+    assert_eq!(chip.each_net_bus_vec(&b), vec![nets]);
+    let previous = chip.connect_pin_bus_instance(&inst, &data, Some(&nets));
+    assert_eq!(previous, vec![None; 4]);
+    let net3 = chip.net_bus_bit(&nets, 3).unwrap();
+    assert_eq!(chip.net_bit_index(&net3), Some(3));
+    assert_eq!(
+        chip.net_of_pin_instance(&chip.pin_instance(&inst, &bit3)),
+        Some(net3)
+    );
+
+    // Removing a bus keeps its members.
+    chip.remove_pin_bus(&data);
+    assert_eq!(chip.bus_of_pin(&bit3), None);
+    assert_eq!(chip.num_pins(&a), 4);
+
+    // Removing a member dissolves the bus.
+    chip.remove_net(&net3);
+    assert_eq!(chip.net_bus_by_name(&b, "d"), None);
+    assert_eq!(chip.num_internal_nets(&b), 3 + 2);
+}
+
+#[test]
+#[should_panic]
+fn test_bus_width_mismatch() {
+    let mut chip = Chip::new();
+    let a = chip.create_cell("A".into());
+    let pins = chip.create_pin_bus(&a, "a".into(), Direction::Input, 2, BitOrder::Descending);
+    let nets = chip.create_net_bus(&a, "n".into(), 3, BitOrder::Descending);
+    chip.connect_pin_bus(&pins, Some(&nets));
+}
+
+#[test]
+fn test_power_domains() {
+    let mut chip = Chip::new();
+    let top = chip.create_cell("TOP".into());
+    let inv = chip.create_cell("INV".into());
+    chip.create_pin(&inv, "A".into(), Direction::Input);
+    let inv_y = chip.create_pin(&inv, "Y".into(), Direction::Output);
+    let inv_vdd = chip.create_pin(&inv, "VDD".into(), Direction::Supply);
+    let inv_vss = chip.create_pin(&inv, "VSS".into(), Direction::Ground);
+    assert_eq!(chip.each_supply_pin_vec(&inv), vec![inv_vdd]);
+    assert_eq!(chip.each_ground_pin_vec(&inv), vec![inv_vss]);
+
+    let vdd_core = chip.create_net(&top, Some("VDD_CORE".into()));
+    let vdd_io = chip.create_net(&top, Some("VDD_IO".into()));
+    let vss = chip.create_net(&top, Some("VSS".into()));
+    let core = chip.create_power_domain("CORE".into(), vdd_core, vss, 0.9, false);
+    let io = chip.create_power_domain("IO".into(), vdd_io, vss, 3.3, true);
+    assert_eq!(chip.power_domain_by_name("CORE"), Some(core));
+    assert_eq!(chip.parent_cell_of_power_domain(&io), top);
+    assert_eq!(chip.power_domain_voltage(&io), 3.3);
+    assert!(chip.power_domain_is_always_on(&io));
+    assert_eq!(chip.set_power_domain_voltage(&core, 1.0), 0.9);
+    assert!(chip.is_supply_net(&vss));
+    assert_eq!(chip.each_power_domain_of_supply_net_vec(&vss).len(), 2);
+
+    chip.set_power_domain_of_cell(&top, Some(core));
+    let inst1 = chip.create_cell_instance(&top, &inv, Some("inv1".into()));
+    let inst2 = chip.create_cell_instance(&top, &inv, Some("inv2".into()));
+    assert_eq!(
+        chip.set_power_domain_of_cell_instance(&inst2, Some(io)),
+        None
+    );
+    assert_eq!(chip.power_domain_of_cell_instance(&inst1), Some(core));
+    assert_eq!(chip.power_domain_of_cell_instance(&inst2), Some(io));
+
+    chip.connect_supply_pins(&inst1, &core);
+    chip.connect_supply_pins(&inst2, &io);
+    assert_eq!(
+        chip.net_of_pin_instance(&chip.pin_instance(&inst1, &inv_vdd)),
+        Some(vdd_core)
+    );
+    assert_eq!(
+        chip.net_of_pin_instance(&chip.pin_instance(&inst2, &inv_vss)),
+        Some(vss)
+    );
+
+    // A signal crossing the domains.
+    let signal = chip.create_net(&top, Some("signal".into()));
+    let inst1_y = chip.pin_instance(&inst1, &inv_y);
+    chip.connect_pin_instance(&inst1_y, Some(signal));
+    assert_eq!(chip.each_power_domain_of_net_vec(&signal), vec![core]);
+    let inv_a = chip.pin_by_name(&inv, "A").unwrap();
+    let inst2_a = chip.pin_instance(&inst2, &inv_a);
+    chip.connect_pin_instance(&inst2_a, Some(signal));
+    let domains = chip.each_power_domain_of_net_vec(&signal);
+    assert_eq!(domains.len(), 2);
+    assert!(domains.contains(&io));
+
+    // Removing a supply net removes the domain.
+    chip.remove_net(&vdd_io);
+    assert_eq!(chip.power_domain_by_name("IO"), None);
+    assert_eq!(chip.power_domain_of_cell_instance(&inst2), Some(core));
+
+    chip.remove_cell(&top);
+    assert!(chip.each_power_domain_vec().is_empty());
+}
+
 #[test]
 fn test_connect_nets() {
     #![allow(unused_variables)]
@@ -285,6 +402,274 @@ fn test_flatten_circuit_instance() {
     assert_eq!(chip.num_net_terminals(&net1), 2);
 }
 
+#[test]
+fn test_flatten_cell_instance() {
+    let mut chip = Chip::new();
+    let layer = chip.create_layer(1, 0);
+    let top = chip.create_cell("TOP".into());
+    let macro_cell = chip.create_cell("MACRO".into());
+    let bit = chip.create_cell("BIT".into());
+    chip.insert_shape(&bit, &layer, Rect::new((0, 0), (4, 8)).into());
+
+    let shape = chip.insert_shape(&macro_cell, &layer, Rect::new((0, 0), (10, 20)).into());
+    chip.set_shape_property(&shape, "purpose".into(), 7.into());
+    let u1 = chip.create_cell_instance(&macro_cell, &bit, Some("u1".into()));
+    chip.set_transform(&u1, SimpleTransform::translate((1, 2)));
+    let array = InstanceArray::new(2, 1, Vector::new(5, 0), Vector::new(0, 0));
+    chip.set_cell_instance_array(&u1, Some(array));
+    chip.set_placement_status(&u1, PlacementStatus::Fixed);
+
+    let inst = chip.create_cell_instance(&top, &macro_cell, Some("m".into()));
+    let tf = SimpleTransform::new(false, Angle::R90, 1, Vector::new(100, 0));
+    chip.set_transform(&inst, tf);
+    let bbox = chip.bounding_box(&top);
+
+    chip.flatten_cell_instance(&inst);
+    assert_eq!(chip.num_child_instances(&top), 1);
+    assert_eq!(chip.cell_instance_by_name(&top, "m"), None);
+    assert_eq!(chip.bounding_box(&top), bbox);
+    assert_eq!(chip.bounding_box(&top), Some(Rect::new((80, 0), (100, 10))));
+
+    // Shapes are copied together with their properties.
+    let shapes = chip.each_shape_id(&top, &layer).collect_vec();
+    assert_eq!(shapes.len(), 1);
+    assert_eq!(
+        chip.get_shape_property(&shapes[0], &"purpose".into())
+            .and_then(|v| v.get_sint()),
+        Some(7)
+    );
+
+    // Child instances are moved into the parent and keep their array and placement status.
+    let u1_copy = chip.cell_instance_by_name(&top, "m:u1").unwrap();
+    assert_eq!(
+        chip.get_transform(&u1_copy),
+        SimpleTransform::translate((1, 2)).then(&tf)
+    );
+    assert_eq!(
+        chip.cell_instance_array(&u1_copy).map(|a| a.column_pitch),
+        Some(Vector::new(0, 5))
+    );
+    assert_eq!(chip.placement_status(&u1_copy), PlacementStatus::Fixed);
+}
+
+#[test]
+fn test_flatten_cell_instance_l2n() {
+    let mut chip = Chip::new();
+    let layer = chip.create_layer(1, 0);
+    let top = chip.create_cell("TOP".into());
+    let macro_cell = chip.create_cell("MACRO".into());
+    let bit = chip.create_cell("BIT".into());
+    let bit_pin = chip.create_pin(&bit, "P".into(), Direction::Input);
+
+    // Both pins of the macro are shorted by the net `n`.
+    let pin_a = chip.create_pin(&macro_cell, "A".into(), Direction::InOut);
+    let pin_b = chip.create_pin(&macro_cell, "B".into(), Direction::InOut);
+    let n = chip.create_net(&macro_cell, Some("n".into()));
+    chip.connect_pin(&pin_a, Some(n));
+    chip.connect_pin(&pin_b, Some(n));
+    let n_shape = chip.insert_shape(&macro_cell, &layer, Rect::new((0, 0), (10, 1)).into());
+    chip.set_net_of_shape(&n_shape, Some(n));
+
+    let internal = chip.create_net(&macro_cell, Some("internal".into()));
+    let u1 = chip.create_cell_instance(&macro_cell, &bit, Some("u1".into()));
+    chip.connect_pin_instance(&chip.pin_instance(&u1, &bit_pin), Some(internal));
+    let internal_shape = chip.insert_shape(&macro_cell, &layer, Rect::new((0, 5), (1, 6)).into());
+    chip.set_net_of_shape(&internal_shape, Some(internal));
+
+    let x = chip.create_net(&top, Some("x".into()));
+    let y = chip.create_net(&top, Some("y".into()));
+    let x_shape = chip.insert_shape(&top, &layer, Rect::new((0, 0), (1, 1)).into());
+    chip.set_net_of_shape(&x_shape, Some(x));
+    let inst = chip.create_cell_instance(&top, &macro_cell, Some("m".into()));
+    chip.set_transform(&inst, SimpleTransform::translate((100, 0)));
+    chip.connect_pin_instance(&chip.pin_instance(&inst, &pin_a), Some(x));
+    chip.connect_pin_instance(&chip.pin_instance(&inst, &pin_b), Some(y));
+
+    chip.flatten_cell_instance_l2n(&inst);
+    assert_eq!(chip.cell_instance_by_name(&top, "m"), None);
+
+    // The outer nets are merged because of the short inside the macro.
+    let merged = chip.net_by_name(&top, "y").unwrap();
+    assert_eq!(chip.net_by_name(&top, "x"), None);
+    assert_eq!(chip.get_net_of_shape(&x_shape), Some(merged));
+    assert_eq!(chip.shapes_of_net(&merged).count(), 2);
+
+    // Internal nets are copied and connected to the copied shapes and instances.
+    let internal_copy = chip.net_by_name(&top, "internal").unwrap();
+    let u1_copy = chip.cell_instance_by_name(&top, "m:u1").unwrap();
+    assert_eq!(
+        chip.net_of_pin_instance(&chip.pin_instance(&u1_copy, &bit_pin)),
+        Some(internal_copy)
+    );
+    let shapes = chip.shapes_of_net(&internal_copy).collect_vec();
+    assert_eq!(shapes.len(), 1);
+    assert_eq!(
+        chip.shape_geometry(&shapes[0]).try_bounding_box(),
+        Some(Rect::new((100, 5), (101, 6)))
+    );
+}
+
+#[test]
+fn test_flatten_array_instance_l2n() {
+    let mut chip = Chip::new();
+    let layer = chip.create_layer(1, 0);
+    let top = chip.create_cell("TOP".into());
+    let macro_cell = chip.create_cell("MACRO".into());
+    let bit = chip.create_cell("BIT".into());
+    let bit_pin = chip.create_pin(&bit, "P".into(), Direction::Input);
+
+    let pin_a = chip.create_pin(&macro_cell, "A".into(), Direction::Input);
+    let a = chip.create_net(&macro_cell, Some("a".into()));
+    chip.connect_pin(&pin_a, Some(a));
+    let a_shape = chip.insert_shape(&macro_cell, &layer, Rect::new((0, 0), (10, 1)).into());
+    chip.set_net_of_shape(&a_shape, Some(a));
+
+    let internal = chip.create_net(&macro_cell, Some("internal".into()));
+    let u1 = chip.create_cell_instance(&macro_cell, &bit, Some("u1".into()));
+    chip.connect_pin_instance(&chip.pin_instance(&u1, &bit_pin), Some(internal));
+    let internal_shape = chip.insert_shape(&macro_cell, &layer, Rect::new((0, 5), (1, 6)).into());
+    chip.set_net_of_shape(&internal_shape, Some(internal));
+
+    let x = chip.create_net(&top, Some("x".into()));
+    let inst = chip.create_cell_instance(&top, &macro_cell, Some("m".into()));
+    let array = InstanceArray::new(3, 1, Vector::new(20, 0), Vector::new(0, 0));
+    chip.set_cell_instance_array(&inst, Some(array));
+    chip.connect_pin_instance(&chip.pin_instance(&inst, &pin_a), Some(x));
+
+    chip.flatten_cell_instance_l2n(&inst);
+    assert_eq!(chip.num_child_instances(&top), 3);
+
+    // Nets connected to pins are shared by all elements.
+    assert_eq!(chip.shapes_of_net(&x).count(), 3);
+
+    // Each element gets its own copy of the internal net.
+    let internal_nets = chip
+        .each_cell_instance(&top)
+        .map(|u| chip.net_of_pin_instance(&chip.pin_instance(&u, &bit_pin)))
+        .collect_vec();
+    assert!(internal_nets.iter().all(|n| n.is_some()));
+    assert_eq!(internal_nets.iter().unique().count(), 3);
+    for net in internal_nets.into_iter().flatten() {
+        assert_eq!(chip.shapes_of_net(&net).count(), 1);
+        assert_ne!(net, x);
+    }
+}
+
+#[test]
+fn test_try_accessors() {
+    let mut chip = Chip::new();
+    let layer = chip.create_layer(1, 0);
+    let top = chip.create_cell("TOP".into());
+    let sub = chip.create_cell("SUB".into());
+    let pin = chip.create_pin(&sub, "A".into(), Direction::Input);
+    let inst = chip.create_cell_instance(&top, &sub, Some("inst".into()));
+    let pin_inst = chip.pin_instance(&inst, &pin);
+    let net = chip.create_net(&top, Some("net".into()));
+    chip.connect_pin_instance(&pin_inst, Some(net));
+    let shape = chip.insert_shape(&top, &layer, Rect::new((0, 0), (1, 1)).into());
+    chip.set_net_of_shape(&shape, Some(net));
+
+    assert!(chip.cell_exists(&top));
+    assert_eq!(chip.try_cell_name(&sub), Some("SUB".into()));
+    assert_eq!(
+        chip.try_cell_instance_name(&inst),
+        Some(Some("inst".into()))
+    );
+    assert_eq!(chip.try_parent_cell(&inst), Some(top));
+    assert_eq!(chip.try_template_cell(&inst), Some(sub));
+    assert_eq!(chip.try_template_pin(&pin_inst), Some(pin));
+    assert_eq!(chip.try_net_of_pin(&pin), Some(None));
+    assert_eq!(chip.try_net_of_pin_instance(&pin_inst), Some(Some(net)));
+    assert_eq!(chip.try_net_name(&net), Some(Some("net".into())));
+    assert_eq!(
+        chip.try_shape_geometry(&shape),
+        Some(Rect::new((0, 0), (1, 1)).into())
+    );
+    assert_eq!(chip.try_parent_of_shape(&shape), Some((top, layer)));
+    assert_eq!(chip.try_get_net_of_shape(&shape), Some(Some(net)));
+
+    // Remove the objects. The IDs become invalid.
+    chip.remove_shape(&shape);
+    chip.remove_net(&net);
+    chip.remove_cell(&sub);
+
+    assert!(!chip.shape_exists(&shape));
+    assert!(!chip.net_exists(&net));
+    assert!(!chip.cell_exists(&sub));
+    assert!(!chip.cell_instance_exists(&inst));
+    assert!(!chip.pin_exists(&pin));
+    assert!(!chip.pin_instance_exists(&pin_inst));
+
+    assert_eq!(chip.try_cell_name(&sub), None);
+    assert_eq!(chip.try_cell_instance_name(&inst), None);
+    assert_eq!(chip.try_parent_cell(&inst), None);
+    assert_eq!(chip.try_template_pin(&pin_inst), None);
+    assert_eq!(chip.try_pin_name(&pin), None);
+    assert_eq!(chip.try_net_of_pin(&pin), None);
+    assert_eq!(chip.try_net_name(&net), None);
+    assert_eq!(chip.try_shape_geometry(&shape), None);
+    assert_eq!(chip.try_get_net_of_shape(&shape), None);
+    assert_eq!(chip.try_get_transform(&inst), None);
+}
+
+#[test]
+fn test_instance_arrays() {
+    let mut chip = Chip::new();
+    let layer = chip.create_layer(1, 0);
+    let top = chip.create_cell("TOP".to_string());
+    let bit = chip.create_cell("BIT".to_string());
+    chip.insert_shape(&bit, &layer, Rect::new((0, 0), (4, 8)).into());
+
+    let inst = chip.create_cell_instance(&top, &bit, None);
+    chip.set_transform(&inst, SimpleTransform::translate((100, 0)));
+    let array = InstanceArray::new(256, 128, Vector::new(5, 0), Vector::new(0, 10));
+    assert_eq!(chip.set_cell_instance_array(&inst, Some(array)), None);
+
+    assert_eq!(chip.num_child_instances(&top), 1);
+    assert_eq!(chip.num_array_elements(&inst), 256 * 128);
+    assert_eq!(
+        chip.bounding_box(&top),
+        Some(Rect::new((100, 0), (100 + 255 * 5 + 4, 127 * 10 + 8)))
+    );
+    assert_eq!(
+        chip.array_element_transform(&inst, 2, 3),
+        SimpleTransform::translate((110, 30))
+    );
+
+    let mut num_shapes = 0;
+    chip.for_each_shape_recursive(&top, &layer, |_, _, _| num_shapes += 1);
+    assert_eq!(num_shapes, 256 * 128);
+
+    // Turn the array back into a single placement.
+    assert_eq!(chip.set_cell_instance_array(&inst, None), Some(array));
+    assert_eq!(chip.num_array_elements(&inst), 1);
+    assert_eq!(chip.bounding_box(&top), Some(Rect::new((100, 0), (104, 8))));
+}
+
+#[test]
+fn test_placement_status() {
+    let mut chip = Chip::new();
+    let top = chip.create_cell("TOP".to_string());
+    let sram = chip.create_cell("SRAM".to_string());
+    let inst = chip.create_cell_instance(&top, &sram, None);
+    assert_eq!(chip.placement_status(&inst), PlacementStatus::Placed);
+
+    let previous = chip.set_placement_status(&inst, PlacementStatus::Fixed);
+    assert_eq!(previous, PlacementStatus::Placed);
+    assert!(chip.placement_status(&inst).is_locked());
+
+    // Locked instances cannot be moved with `try_set_transform()`.
+    let tf = SimpleTransform::translate((10, 0));
+    assert_eq!(
+        chip.try_set_transform(&inst, tf),
+        Err(PlacementStatus::Fixed)
+    );
+    assert_eq!(chip.get_transform(&inst), SimpleTransform::identity());
+    // `set_transform()` does not check the status.
+    chip.set_transform(&inst, tf);
+    assert_eq!(chip.get_transform(&inst), tf);
+}
+
 // Does not work yet. Kept as a reminder to eventually support trait objects.
 // #[test]
 // fn test_hierarchy_trait_object() {